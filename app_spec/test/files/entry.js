@@ -51,7 +51,7 @@ module.exports = scenario => {
 
     const updateParamsV2 = { post_address: createResult.Ok, new_content: 'Hello Holo world 321' }
     const UpdateResultV2 = await bob.callSync('app', 'blog', 'update_post', updateParamsV2)
-    t.deepEqual(JSON.parse(UpdateResultV2.Err.Internal).kind.ValidationFailed, 'Trying to modify with same data')
+    t.deepEqual(JSON.parse(UpdateResultV2.Err.Internal).kind.ValidationFailed.message, 'Trying to modify with same data')
   })
 
   scenario('update_post', async (s, t) => {
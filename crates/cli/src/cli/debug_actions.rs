@@ -0,0 +1,134 @@
+use crate::error::DefaultResult;
+use holochain_core::{
+    action_debugger::ActionDebugger,
+    action_recording::{read_recording, replay_context, RecordedAction},
+    state_dump::DumpOptions,
+};
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+const HELP: &str = "\
+n [count]        step forward, once or count times
+b [count]        step back, once or count times
+g <position>     go to the state after that many actions
+u <action type>  step forward until an action of that type got reduced
+a                show the action that led to the current state
+d [file]         dump the current state as JSON, to stdout or the file
+q                quit";
+
+fn action_type(recorded: &RecordedAction) -> String {
+    serde_json::to_value(recorded.action.action())
+        .ok()
+        .and_then(|action| action["action_type"].as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn describe(recorded: Option<&RecordedAction>) -> String {
+    match recorded {
+        Some(recorded) => format!(
+            "{} {:?} {}",
+            recorded.seq,
+            recorded.time,
+            action_type(recorded)
+        ),
+        None => String::from("-"),
+    }
+}
+
+fn count(argument: Option<&str>) -> DefaultResult<usize> {
+    Ok(argument.map(str::parse).transpose()?.unwrap_or(1))
+}
+
+/// Steps interactively through an action recording of an instance, forward and backward,
+/// reading commands from stdin
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn debug_actions(path: PathBuf) -> DefaultResult<()> {
+    let recording = read_recording(&path)?;
+    let mut debugger = ActionDebugger::new(replay_context(&recording.header), recording.actions);
+    println!(
+        "{} actions of instance {}, type h for help",
+        debugger.actions().len(),
+        recording.header.instance_name
+    );
+
+    let stdin = io::stdin();
+    loop {
+        print!(
+            "[{}/{}] {} > ",
+            debugger.position(),
+            debugger.actions().len(),
+            describe(debugger.current_action())
+        );
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let mut words = line.split_whitespace();
+        let command = words.next();
+        let argument = words.next();
+        let result: DefaultResult<()> = match command {
+            None => Ok(()),
+            Some("n") => count(argument).map(|count| {
+                for _ in 0..count {
+                    debugger.step_forward();
+                }
+            }),
+            Some("b") => count(argument).map(|count| {
+                for _ in 0..count {
+                    debugger.step_backward();
+                }
+            }),
+            Some("g") => match argument {
+                Some(position) => position
+                    .parse()
+                    .map(|position| debugger.seek(position))
+                    .map_err(Into::into),
+                None => Err(format_err!("g needs a position")),
+            },
+            Some("u") => match argument {
+                Some(wanted) => {
+                    if debugger
+                        .run_until(|recorded, _| action_type(recorded) == wanted)
+                        .is_none()
+                    {
+                        println!("no {} action until the end of the recording", wanted);
+                    }
+                    Ok(())
+                }
+                None => Err(format_err!(
+                    "u needs an action type, like Commit or HoldAspect"
+                )),
+            },
+            Some("a") => serde_json::to_string_pretty(&debugger.current_action())
+                .map(|json| println!("{}", json))
+                .map_err(Into::into),
+            Some("d") => {
+                let dump = debugger.state_dump(DumpOptions {
+                    include_eavis: true,
+                    include_storage_usage: false,
+                });
+                serde_json::to_string_pretty(&dump)
+                    .map_err(Into::into)
+                    .and_then(|json| match argument {
+                        Some(file) => fs::write(file, json).map_err(Into::into),
+                        None => {
+                            println!("{}", json);
+                            Ok(())
+                        }
+                    })
+            }
+            Some("q") => return Ok(()),
+            Some(_) => {
+                println!("{}", HELP);
+                Ok(())
+            }
+        };
+        if let Err(error) = result {
+            println!("{}", error);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::error::DefaultResult;
+use holochain_core::event_log::read_event_log;
+use std::{fs, path::PathBuf};
+
+/// Verifies the hash chain of a conductor event log and exports its events as a JSON array,
+/// optionally only those of one kind or instance
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn event_log(
+    path: PathBuf,
+    verify: bool,
+    kind: Option<String>,
+    instance_id: Option<String>,
+    output: Option<PathBuf>,
+) -> DefaultResult<()> {
+    let entries = read_event_log(&path)?;
+    if verify {
+        println!(
+            "Event log {} is intact, with {} entries",
+            path.display(),
+            entries.len()
+        );
+        return Ok(());
+    }
+
+    let exported: Vec<_> = entries
+        .into_iter()
+        .filter(|entry| kind.as_ref().map_or(true, |kind| &entry.kind == kind))
+        .filter(|entry| {
+            instance_id
+                .as_ref()
+                .map_or(true, |id| entry.instance_id.as_ref() == Some(id))
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&exported)?;
+    match output {
+        Some(output) => fs::write(output, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
@@ -1,21 +1,31 @@
 mod chain_log;
+mod debug_actions;
+mod event_log;
 mod generate;
 mod hash_dna;
 pub mod init;
 mod keygen;
 pub mod package;
+mod replay_actions;
 pub mod run;
+mod simulate_network;
 mod sim2h_client;
 pub mod test;
+mod validation_coverage;
 
 pub use self::{
     chain_log::{chain_list, chain_log},
+    debug_actions::debug_actions,
+    event_log::event_log,
     generate::generate,
     hash_dna::hash_dna,
     init::init,
     keygen::keygen,
     package::package,
+    replay_actions::replay_actions,
     run::{get_interface_type_string, hc_run_bundle_configuration, hc_run_configuration, run},
+    simulate_network::simulate_network,
     sim2h_client::sim2h_client,
     test::{test, TEST_DIR_NAME},
+    validation_coverage::validation_coverage,
 };
@@ -0,0 +1,51 @@
+use crate::error::DefaultResult;
+use holochain_core::{
+    action_recording::{read_recording, replay, replay_context},
+    agent::state::AgentStateSnapshot,
+    dht::dht_store::DhtStoreSnapshot,
+    nucleus::state::NucleusStateSnapshot,
+};
+use serde_json::json;
+use std::{fs, path::PathBuf};
+
+/// Replays an action recording of an instance against fresh state and exports the state it
+/// leads to as JSON, or lists the recorded actions
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn replay_actions(
+    path: PathBuf,
+    until: Option<u64>,
+    list: bool,
+    output: Option<PathBuf>,
+) -> DefaultResult<()> {
+    let recording = read_recording(&path)?;
+    if list {
+        for recorded in recording.actions.iter() {
+            let action = serde_json::to_value(recorded.action.action())?;
+            println!(
+                "{} {:?} {}",
+                recorded.seq, recorded.time, action["action_type"]
+            );
+        }
+        return Ok(());
+    }
+
+    let state = replay(replay_context(&recording.header), &recording.actions, until);
+    let replayed = recording
+        .actions
+        .iter()
+        .filter(|recorded| until.map_or(true, |until| recorded.seq <= until))
+        .count();
+    let json = serde_json::to_string_pretty(&json!({
+        "instance": recording.header.instance_name,
+        "agent": recording.header.agent_id,
+        "replayed_actions": replayed,
+        "agent_state": AgentStateSnapshot::from(&state),
+        "nucleus_state": NucleusStateSnapshot::from(&state),
+        "dht_state": DhtStoreSnapshot::from(&state),
+    }))?;
+    match output {
+        Some(output) => fs::write(output, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
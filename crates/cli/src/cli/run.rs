@@ -99,6 +99,7 @@ pub fn hc_run_configuration(
         interfaces: vec![interface_configuration(&interface_type, port)?],
         network: networking_configuration(networked),
         logger: logger_configuration(logging),
+        dev_mode: true,
         ..Default::default()
     })
 }
@@ -146,6 +147,10 @@ fn agent_configuration(agent_name: String) -> AgentConfiguration {
         keystore_file: agent_name,
         holo_remote_key: None,
         test_agent: Some(true),
+        seed_file: None,
+        client_keys: None,
+        key_backend: None,
+        tenant: None,
     }
 }
 
@@ -197,6 +202,20 @@ fn instance_configuration(storage: StorageConfiguration) -> InstanceConfiguratio
         dna: DNA_CONFIG_ID.into(),
         agent: AGENT_CONFIG_ID.into(),
         storage,
+        membrane_proof: None,
+        rate_limits: None,
+        compression: None,
+        encrypt_storage: false,
+        hot_tier: None,
+        cold_storage: None,
+        resource_limits: None,
+        restart_policy: None,
+        logger: None,
+        slow_zome_calls: None,
+        record_actions: None,
+        validation_coverage: None,
+        pending_validation: None,
+        validation_package_cache_size: None,
     }
 }
 
@@ -212,6 +231,10 @@ fn interface_configuration(
         InterfaceDriver::Websocket { port }
     } else if interface_type == "http" {
         InterfaceDriver::Http { port }
+    } else if interface_type == "graphql" {
+        InterfaceDriver::Graphql { port }
+    } else if interface_type == "grpc" {
+        InterfaceDriver::Grpc { port }
     } else {
         return Err(format_err!("unknown interface type: {}", interface_type));
     };
@@ -225,6 +248,11 @@ fn interface_configuration(
             alias: None,
         }],
         choose_free_port: None,
+        require_signed_calls: false,
+        filter_signals: false,
+        tenant: None,
+        auth: None,
+        tls: None,
     })
 }
 
@@ -240,6 +268,7 @@ fn logger_configuration(logging: bool) -> LoggerConfiguration {
             LogRules::new()
         },
         state_dump: true,
+        format: LogFormat::Text,
     }
 }
 
@@ -323,6 +352,10 @@ mod tests {
                 keystore_file: "testAgent".to_string(),
                 holo_remote_key: None,
                 test_agent: Some(true),
+                seed_file: None,
+                client_keys: None,
+                key_backend: None,
+                tenant: None,
             },
         );
     }
@@ -375,6 +408,20 @@ mod tests {
                 dna: "hc-run-dna".to_string(),
                 agent: "hc-run-agent".to_string(),
                 storage: StorageConfiguration::Memory,
+                membrane_proof: None,
+                rate_limits: None,
+                compression: None,
+                encrypt_storage: false,
+                hot_tier: None,
+                cold_storage: None,
+                resource_limits: None,
+                restart_policy: None,
+                logger: None,
+                slow_zome_calls: None,
+                record_actions: None,
+                validation_coverage: None,
+                pending_validation: None,
+                validation_package_cache_size: None,
             }
         )
     }
@@ -393,6 +440,11 @@ mod tests {
                     alias: None,
                 }],
                 choose_free_port: None,
+                require_signed_calls: false,
+                filter_signals: false,
+                tenant: None,
+                auth: None,
+                tls: None,
             }
         );
 
@@ -409,6 +461,11 @@ mod tests {
                     alias: None,
                 }],
                 choose_free_port: None,
+                require_signed_calls: false,
+                filter_signals: false,
+                tenant: None,
+                auth: None,
+                tls: None,
             }
         );
 
@@ -0,0 +1,30 @@
+use crate::error::DefaultResult;
+use holochain_core::simulation::{RoundMetrics, Simulation, SimulationConfig};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// Runs a network simulation and writes the metrics of each round as a line of JSON
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn simulate_network(config: SimulationConfig, output: Option<PathBuf>) -> DefaultResult<()> {
+    let mut out: Box<dyn Write> = match output {
+        Some(output) => Box::new(File::create(output)?),
+        None => Box::new(io::stdout()),
+    };
+    let max_rounds = config.max_rounds;
+    let mut simulation = Simulation::new(config);
+    let mut metrics = RoundMetrics::default();
+    while metrics.round < max_rounds && !simulation.is_converged() {
+        metrics = simulation.step();
+        writeln!(out, "{}", serde_json::to_string(&metrics)?)?;
+    }
+    if !simulation.is_converged() {
+        eprintln!(
+            "Not converged after {} rounds: {} of {} holdings",
+            metrics.round, metrics.holdings, metrics.expected_holdings
+        );
+    }
+    Ok(())
+}
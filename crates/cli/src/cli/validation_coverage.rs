@@ -0,0 +1,30 @@
+use crate::error::DefaultResult;
+use holochain_core::validation_coverage::CoverageReport;
+use std::{fs, path::PathBuf};
+
+/// Merges the validation coverage reports of the instances of a test run and prints which
+/// validation branches were hit how often, and which never
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn validation_coverage(paths: Vec<PathBuf>, fail_under: Option<f64>) -> DefaultResult<()> {
+    let mut report = CoverageReport::default();
+    for path in paths.iter() {
+        let content = fs::read_to_string(path)?;
+        let instance_report: CoverageReport = serde_json::from_str(&content)
+            .map_err(|e| format_err!("{} is not a coverage report: {}", path.display(), e))?;
+        report.merge(&instance_report);
+    }
+    println!("{}", report);
+
+    if let Some(minimum) = fail_under {
+        let total = report.branches.len().max(1) as f64;
+        let percentage = report.covered() as f64 * 100.0 / total;
+        if percentage < minimum {
+            return Err(format_err!(
+                "Validation coverage of {:.1}% is below {}%",
+                percentage,
+                minimum
+            ));
+        }
+    }
+    Ok(())
+}
@@ -42,6 +42,7 @@ mod util;
 
 use crate::error::{HolochainError, HolochainResult};
 use holochain_conductor_lib::happ_bundle::HappBundle;
+use holochain_core::simulation::SimulationConfig;
 use std::{fs::File, io::Read, path::PathBuf, str::FromStr};
 use structopt::{clap::arg_enum, StructOpt};
 new_relic_setup!("NEW_RELIC_LICENSE_KEY");
@@ -74,7 +75,7 @@ enum Cli {
         template: String,
     },
     #[structopt(alias = "r")]
-    /// Starts a development conductor with a websocket or http interface
+    /// Starts a development conductor with a websocket, http, GraphQL or gRPC interface
     Run {
         #[structopt(long, short, default_value = "8888")]
         /// The port to run the websocket server at
@@ -98,7 +99,7 @@ enum Cli {
         /// Set the sim2h server url if you are using real networking.
         sim2h_server: String,
         #[structopt(long, short, default_value = "websocket")]
-        /// Specify interface type to use: websocket/http
+        /// Specify interface type to use: websocket/http/graphql/grpc
         interface: String,
         #[structopt(long, short, default_value = cli::run::AGENT_NAME_DEFAULT)]
         /// Specify agent name which will be used to generate the %agent_id.
@@ -146,6 +147,95 @@ enum Cli {
         /// List available instances
         list: bool,
     },
+    #[structopt(name = "event-log")]
+    /// Verify a conductor event log and export its events as JSON
+    EventLog {
+        #[structopt(name = "FILE", parse(from_os_str))]
+        /// Event log file, as set with `event_log` in the conductor config
+        path: PathBuf,
+        #[structopt(long)]
+        /// Only check that the log has not been tampered with
+        verify: bool,
+        #[structopt(long, short)]
+        /// Only export events of this kind (commit, hold, validation_failed, capability_use, admin)
+        kind: Option<String>,
+        #[structopt(long, short)]
+        /// Only export events of this instance
+        instance: Option<String>,
+        #[structopt(long, short, parse(from_os_str))]
+        /// File to export to instead of stdout
+        output: Option<PathBuf>,
+    },
+    #[structopt(name = "replay-actions")]
+    /// Replay an action recording of an instance and export the state it leads to as JSON
+    ReplayActions {
+        #[structopt(name = "FILE", parse(from_os_str))]
+        /// Recording, as set with `record_actions` in the instance config
+        path: PathBuf,
+        #[structopt(long, short)]
+        /// Only replay up to and including the action with this number
+        until: Option<u64>,
+        #[structopt(long, short)]
+        /// List the recorded actions instead of replaying them
+        list: bool,
+        #[structopt(long, short, parse(from_os_str))]
+        /// File to export to instead of stdout
+        output: Option<PathBuf>,
+    },
+    #[structopt(name = "debug-actions")]
+    /// Step forward and backward through an action recording of an instance and look at the
+    /// state after each action
+    DebugActions {
+        #[structopt(name = "FILE", parse(from_os_str))]
+        /// Recording, as set with `record_actions` in the instance config
+        path: PathBuf,
+    },
+    #[structopt(name = "validation-coverage")]
+    /// Merge the validation coverage reports of a test run and show which validation branches
+    /// of the DNA were never hit
+    ValidationCoverage {
+        #[structopt(name = "FILES", parse(from_os_str), required = true)]
+        /// Reports, as set with `validation_coverage` in the instance configs
+        paths: Vec<PathBuf>,
+        #[structopt(long)]
+        /// Fail if less than this percentage of the branches were hit
+        fail_under: Option<f64>,
+    },
+    #[structopt(name = "simulate-network")]
+    /// Simulate how published entries spread through a network of many lightweight agents and
+    /// print the metrics of each round as JSON lines
+    SimulateNetwork {
+        #[structopt(long, default_value = "1000")]
+        /// Number of agents
+        agents: usize,
+        #[structopt(long, default_value = "50")]
+        /// How many agents should hold each entry
+        redundancy: usize,
+        #[structopt(long, default_value = "1")]
+        /// Entries every agent publishes per publishing round
+        entries: usize,
+        #[structopt(long, default_value = "1")]
+        /// Rounds in which the agents publish
+        publish_rounds: u64,
+        #[structopt(long, default_value = "3")]
+        /// Agents every agent gossips with per round
+        gossip_peers: usize,
+        #[structopt(long, default_value = "1")]
+        /// Rounds it takes a message to arrive
+        latency: u64,
+        #[structopt(long, default_value = "0")]
+        /// Share of messages the network drops, between 0 and 1
+        drop_rate: f64,
+        #[structopt(long, default_value = "1000")]
+        /// Rounds after which the simulation stops, converged or not
+        max_rounds: u64,
+        #[structopt(long, default_value = "1")]
+        /// Seed of all random decisions
+        seed: u64,
+        #[structopt(long, short, parse(from_os_str))]
+        /// File to write the metrics to instead of stdout
+        output: Option<PathBuf>,
+    },
     #[structopt(name = "hash")]
     /// Parse and hash a DNA file to determine its unique network hash
     HashDna {
@@ -326,6 +416,55 @@ fn run() -> HolochainResult<()> {
                     .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
             }
         },
+        Cli::EventLog {
+            path,
+            verify,
+            kind,
+            instance,
+            output,
+        } => cli::event_log(path, verify, kind, instance, output)
+            .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
+        Cli::ReplayActions {
+            path,
+            until,
+            list,
+            output,
+        } => cli::replay_actions(path, until, list, output)
+            .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
+        Cli::DebugActions { path } => {
+            cli::debug_actions(path).map_err(|e| HolochainError::Default(format_err!("{}", e)))?
+        }
+        Cli::ValidationCoverage { paths, fail_under } => {
+            cli::validation_coverage(paths, fail_under)
+                .map_err(|e| HolochainError::Default(format_err!("{}", e)))?
+        }
+        Cli::SimulateNetwork {
+            agents,
+            redundancy,
+            entries,
+            publish_rounds,
+            gossip_peers,
+            latency,
+            drop_rate,
+            max_rounds,
+            seed,
+            output,
+        } => cli::simulate_network(
+            SimulationConfig {
+                agents,
+                redundancy,
+                entries_per_round: entries,
+                publish_rounds,
+                gossip_peers,
+                latency_rounds: latency,
+                drop_rate,
+                max_rounds,
+                seed,
+                ..SimulationConfig::default()
+            },
+            output,
+        )
+        .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
         Cli::HashDna {
             path,
             property,
@@ -6,7 +6,7 @@
 //! - **HC_AGENT** *string* Set an alternative name for the agent for the development instance.
 //! Default value is `testAgent`.
 //! Useful for changing the agent while running multiple instances.
-//! - **HC_INTERFACE** *string* **websocket** OR **http** Set an interface type to use. Setting this as an environment variable will override the
+//! - **HC_INTERFACE** *string* **websocket**, **http**, **graphql** OR **grpc** Set an interface type to use. Setting this as an environment variable will override the
 //! value of the `--interface` option for `hc run`. The default interface if neither is set is `websocket`.
 //! Default is temporary directory which will get removed again once the Conductor process stops. Recommended not to use this at this time.
 //! Without this, a second node will of a network will be unable to find any others. See [configuring networking]([here](https://developer.holochain.org/guide/latest/hc_configuring_networking.html)
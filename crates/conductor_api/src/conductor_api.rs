@@ -1,7 +1,7 @@
 use holochain_core_types::error::HolochainError;
 use holochain_locksmith::RwLock;
 use holochain_wasm_utils::api_serialization::crypto::CryptoMethod;
-use jsonrpc_core::IoHandler;
+use jsonrpc_core::{ErrorCode, IoHandler};
 use jsonrpc_lite::JsonRpc;
 use serde_json::json;
 use snowflake::ProcessUniqueId;
@@ -74,6 +74,43 @@ impl ConductorApi {
         send_json_rpc(self.0.clone(), encoded_payload, request_response)
     }
 
+    /// Asks the conductor whether the agent's key is registered in DPKI
+    /// (`agent/is_key_registered`). None if the conductor doesn't manage keys with DPKI,
+    /// so that there is no registration to check.
+    pub fn is_agent_key_registered(&self) -> Result<Option<bool>, HolochainError> {
+        let handler = self.0.write().unwrap();
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "agent/is_key_registered",
+            "params": {},
+            "id": format!("{}", ProcessUniqueId::new()),
+        })
+        .to_string();
+        let response = handler
+            .handle_request_sync(&request)
+            .ok_or_else(|| "Conductor request agent/is_key_registered failed".to_string())?;
+
+        let response = JsonRpc::parse(&response)?;
+        match response {
+            JsonRpc::Success(_) => Ok(Some(
+                response.get_result()?["registered"]
+                    .as_bool()
+                    .unwrap_or(false),
+            )),
+            JsonRpc::Error(_) => {
+                let err = response.get_error().unwrap(); // unwrap ok because we know its an err
+                if err.code == ErrorCode::MethodNotFound.code() {
+                    Ok(None)
+                } else {
+                    Err(HolochainError::ErrorGeneric(serde_json::to_string(err)?))
+                }
+            }
+            _ => Err(HolochainError::ErrorGeneric(
+                "agent/is_key_registered failed".to_string(),
+            )),
+        }
+    }
+
     pub fn get(&self) -> &Arc<RwLock<IoHandler>> {
         &self.0
     }
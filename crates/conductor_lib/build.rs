@@ -0,0 +1,26 @@
+//! Generates the protobuf messages of the gRPC interface (see src/interface_impls/grpc.rs)
+//! from proto/conductor.proto.
+use std::{env, fs, path::Path};
+
+fn main() {
+    println!("cargo:rerun-if-changed=proto/conductor.proto");
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    protobuf_codegen_pure::run(protobuf_codegen_pure::Args {
+        out_dir: &out_dir,
+        input: &["proto/conductor.proto"],
+        includes: &["proto"],
+        customize: Default::default(),
+    })
+    .expect("Could not generate the messages of proto/conductor.proto");
+
+    // The generated file starts with inner attributes and doc comments, which include!()
+    // doesn't accept. The module that includes it allows the lints itself.
+    let path = Path::new(&out_dir).join("conductor.rs");
+    let code = fs::read_to_string(&path).expect("Could not read generated protobuf code");
+    let code: String = code
+        .lines()
+        .filter(|line| !line.starts_with("#![") && !line.starts_with("//!"))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    fs::write(&path, code).expect("Could not write generated protobuf code");
+}
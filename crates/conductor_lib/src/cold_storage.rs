@@ -0,0 +1,469 @@
+//! Archival of cold CAS content to an S3-compatible object store.
+//!
+//! Nodes holding large neighborhoods don't need the old segments of their source chain or
+//! content that dropped out of their caches on the local disk all the time. [ColdStorage] sits
+//! in front of an instance's persistent CAS and, when [archive_cold_content] runs, uploads
+//! such content to an [ObjectStore] and removes it locally. Reads of archived content then get
+//! served from the object store on demand. Which addresses got archived is kept in an index
+//! next to the state snapshots, so archived content is still known after a restart.
+use holochain_core::{
+    context::Context,
+    storage_compaction::{live_addresses, CompactStorage},
+};
+use holochain_core_types::error::{HcResult, HolochainError};
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_locksmith::{Mutex, RwLock};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    error::{PersistenceError, PersistenceResult},
+    reporting::ReportStorage,
+};
+use rusoto_core::{region::Region, RusotoError};
+use rusoto_s3::{GetObjectError, GetObjectRequest, PutObjectRequest, S3Client, S3};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+    io::Read,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+use uuid::Uuid;
+
+pub static COLD_STORAGE_INDEX_ADDRESS: &str = "ColdStorageIndex";
+
+/// Where and what to archive
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct ColdStorageConfig {
+    pub bucket: String,
+    /// AWS region of the bucket, or the region name to sign requests with if `endpoint` is set
+    #[serde(default = "default_region")]
+    pub region: String,
+    /// URL of an S3-compatible service other than AWS, like MinIO
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Prepended to the address of archived content to make its object key.
+    /// Instances sharing a bucket need different prefixes.
+    #[serde(default)]
+    pub prefix: String,
+    /// Number of most recent source chain headers whose content always stays local
+    #[serde(default = "default_keep_chain_headers")]
+    pub keep_chain_headers: usize,
+}
+
+fn default_region() -> String {
+    String::from("us-east-1")
+}
+
+fn default_keep_chain_headers() -> usize {
+    1000
+}
+
+/// A store of objects under string keys
+pub trait ObjectStore: Send + Sync {
+    fn put(&self, key: &str, object: Vec<u8>) -> HcResult<()>;
+    fn get(&self, key: &str) -> HcResult<Option<Vec<u8>>>;
+}
+
+/// Object store backed by an S3 bucket. Credentials are looked up the usual AWS ways:
+/// environment variables, the credentials file and instance metadata.
+pub struct S3ObjectStore {
+    client: S3Client,
+    bucket: String,
+}
+
+impl S3ObjectStore {
+    pub fn new(config: &ColdStorageConfig) -> HcResult<Self> {
+        let region = match config.endpoint {
+            Some(ref endpoint) => Region::Custom {
+                name: config.region.clone(),
+                endpoint: endpoint.clone(),
+            },
+            None => config.region.parse::<Region>().map_err(|error| {
+                HolochainError::ConfigError(format!(
+                    "Invalid cold storage region \"{}\": {}",
+                    config.region, error
+                ))
+            })?,
+        };
+        Ok(S3ObjectStore {
+            client: S3Client::new(region),
+            bucket: config.bucket.clone(),
+        })
+    }
+}
+
+impl ObjectStore for S3ObjectStore {
+    fn put(&self, key: &str, object: Vec<u8>) -> HcResult<()> {
+        self.client
+            .put_object(PutObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                body: Some(object.into()),
+                ..Default::default()
+            })
+            .sync()
+            .map_err(|error| {
+                HolochainError::ErrorGeneric(format!("Could not upload {}: {}", key, error))
+            })?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> HcResult<Option<Vec<u8>>> {
+        let output = match self
+            .client
+            .get_object(GetObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                ..Default::default()
+            })
+            .sync()
+        {
+            Ok(output) => output,
+            Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => return Ok(None),
+            Err(error) => {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Could not download {}: {}",
+                    key, error
+                )))
+            }
+        };
+        let mut object = Vec::new();
+        if let Some(body) = output.body {
+            body.into_blocking_read().read_to_end(&mut object)?;
+        }
+        Ok(Some(object))
+    }
+}
+
+/// Object store in memory, for tests
+#[derive(Default)]
+pub struct MemoryObjectStore {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl ObjectStore for MemoryObjectStore {
+    fn put(&self, key: &str, object: Vec<u8>) -> HcResult<()> {
+        self.objects.lock()?.insert(key.to_string(), object);
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> HcResult<Option<Vec<u8>>> {
+        Ok(self.objects.lock()?.get(key).cloned())
+    }
+}
+
+/// Addresses of the content that got moved to the object store
+#[derive(Clone, Debug, Default, Serialize, Deserialize, DefaultJson)]
+struct ColdStorageIndex {
+    archived: HashSet<Address>,
+}
+
+impl AddressableContent for ColdStorageIndex {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+
+    fn address(&self) -> Address {
+        COLD_STORAGE_INDEX_ADDRESS.into()
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ArchivalReport {
+    /// Number of contents that got moved to the object store
+    pub archived: usize,
+    /// Number of contents that stay in the local storage
+    pub kept: usize,
+}
+
+/// CAS that serves content from a local storage and, once it got archived, from an object
+/// store. Needs the local storage's compactor to list and remove content.
+#[derive(Clone)]
+pub struct ColdStorage {
+    local: Arc<RwLock<dyn ContentAddressableStorage>>,
+    compactor: Arc<dyn CompactStorage>,
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+    index: Arc<RwLock<ColdStorageIndex>>,
+}
+
+impl fmt::Debug for ColdStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ColdStorage")
+            .field("prefix", &self.prefix)
+            .field("archived", &self.index.read().unwrap().archived.len())
+            .finish()
+    }
+}
+
+impl ColdStorage {
+    pub fn new(
+        local: Arc<RwLock<dyn ContentAddressableStorage>>,
+        compactor: Arc<dyn CompactStorage>,
+        store: Arc<dyn ObjectStore>,
+        prefix: String,
+    ) -> HcResult<Self> {
+        let index = match local
+            .read()?
+            .fetch(&Address::from(COLD_STORAGE_INDEX_ADDRESS))?
+        {
+            Some(content) => ColdStorageIndex::try_from_content(&content)?,
+            None => ColdStorageIndex::default(),
+        };
+        Ok(ColdStorage {
+            local,
+            compactor,
+            store,
+            prefix,
+            index: Arc::new(RwLock::new(index)),
+        })
+    }
+
+    /// Compactor for the instance's context. It works on the local storage but leaves
+    /// the index of archived content alone.
+    pub fn compactor(&self) -> Arc<dyn CompactStorage> {
+        Arc::new(ColdStorageCompactor {
+            inner: self.compactor.clone(),
+        })
+    }
+
+    fn key(&self, address: &Address) -> String {
+        format!("{}{}", self.prefix, address)
+    }
+
+    /// Uploads the content at the given addresses to the object store and removes it from
+    /// the local storage. Addresses that aren't stored locally get skipped.
+    /// Returns the number of archived contents.
+    pub fn archive(&self, addresses: &[Address]) -> HcResult<usize> {
+        let mut archived = Vec::new();
+        for address in addresses {
+            let content = match self.local.read()?.fetch(address)? {
+                Some(content) => content,
+                None => continue,
+            };
+            self.store
+                .put(&self.key(address), String::from(content).into_bytes())?;
+            archived.push(address.clone());
+        }
+        if archived.is_empty() {
+            return Ok(0);
+        }
+        // Only remove local copies once the index that leads to the archived ones is saved
+        {
+            let mut index = self.index.write()?;
+            index.archived.extend(archived.iter().cloned());
+            self.local.write()?.add(&*index)?;
+        }
+        self.compactor.remove(&archived)?;
+        Ok(archived.len())
+    }
+
+    fn is_archived(&self, address: &Address) -> bool {
+        self.index.read().unwrap().archived.contains(address)
+    }
+}
+
+impl ContentAddressableStorage for ColdStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        self.local.write().unwrap().add(content)
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        Ok(self.local.read().unwrap().contains(address)? || self.is_archived(address))
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        if let Some(content) = self.local.read().unwrap().fetch(address)? {
+            return Ok(Some(content));
+        }
+        if !self.is_archived(address) {
+            return Ok(None);
+        }
+        let object = self
+            .store
+            .get(&self.key(address))
+            .map_err(|error| PersistenceError::ErrorGeneric(error.to_string()))?;
+        match object {
+            Some(object) => {
+                let json = String::from_utf8(object).map_err(|error| {
+                    PersistenceError::ErrorGeneric(format!(
+                        "Archived content {} is not valid UTF-8: {}",
+                        address, error
+                    ))
+                })?;
+                Ok(Some(JsonString::from_json(&json)))
+            }
+            None => Err(PersistenceError::ErrorGeneric(format!(
+                "Archived content {} is missing in the object store",
+                address
+            ))),
+        }
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.local.read().unwrap().get_id()
+    }
+}
+
+impl ReportStorage for ColdStorage {}
+
+struct ColdStorageCompactor {
+    inner: Arc<dyn CompactStorage>,
+}
+
+impl CompactStorage for ColdStorageCompactor {
+    fn addresses(&self) -> HcResult<Vec<Address>> {
+        let index = Address::from(COLD_STORAGE_INDEX_ADDRESS);
+        Ok(self
+            .inner
+            .addresses()?
+            .into_iter()
+            .filter(|address| *address != index)
+            .collect())
+    }
+
+    fn remove(&self, addresses: &[Address]) -> HcResult<()> {
+        self.inner.remove(addresses)
+    }
+
+    fn quarantine(&self, address: &Address) -> HcResult<()> {
+        self.inner.quarantine(address)
+    }
+
+    fn vacuum(&self) -> HcResult<()> {
+        self.inner.vacuum()
+    }
+}
+
+/// Moves the instance's cold content to the object store: the headers and entries of its
+/// source chain except the `keep_chain_headers` most recent ones, and content nothing
+/// references anymore, like entries evicted from caches, which compaction would drop.
+pub fn archive_cold_content(
+    context: &Arc<Context>,
+    cold_storage: &ColdStorage,
+    keep_chain_headers: usize,
+) -> HcResult<ArchivalReport> {
+    if context.state().is_none() {
+        return Err(HolochainError::ErrorGeneric(
+            "Couldn't get instance state".to_string(),
+        ));
+    }
+    // Like compaction, hold the state's read lock so that content doesn't change hands
+    // between the chain, caches and the DHT while we pick what to archive
+    let state = loop {
+        match context.try_state() {
+            Some(state) => break state,
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+
+    let mut recent = HashSet::new();
+    let mut old_chain = HashSet::new();
+    for (index, header) in state.agent().iter_chain().enumerate() {
+        let addresses = if index < keep_chain_headers {
+            &mut recent
+        } else {
+            &mut old_chain
+        };
+        addresses.insert(header.address());
+        addresses.insert(header.entry_address().clone());
+    }
+    let live = live_addresses(&state)?;
+
+    let (cold, kept): (Vec<Address>, Vec<Address>) = cold_storage
+        .compactor()
+        .addresses()?
+        .into_iter()
+        .partition(|address| {
+            !recent.contains(address) && (old_chain.contains(address) || !live.contains(address))
+        });
+    let archived = cold_storage.archive(&cold)?;
+    log_info!(
+        "cold_storage/{}: archived {} contents, kept {}",
+        context.get_instance_name(),
+        archived,
+        kept.len()
+    );
+    Ok(ArchivalReport {
+        archived,
+        kept: kept.len(),
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::sqlite_storage::{EavSqliteStorage, SqliteCompactor, SqliteStorage};
+    use holochain_json_api::json::RawString;
+    use holochain_persistence_api::cas::content::ExampleAddressableContent;
+    use tempfile::tempdir;
+
+    fn content(text: &str) -> ExampleAddressableContent {
+        ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from(text)))
+            .unwrap()
+    }
+
+    #[test]
+    fn archived_content_gets_fetched_from_object_store() {
+        let temp = tempdir().unwrap();
+        let cas = SqliteStorage::new(temp.path().join("cas.sqlite")).unwrap();
+        let eav = EavSqliteStorage::new(temp.path().join("eav.sqlite")).unwrap();
+        let compactor: Arc<dyn CompactStorage> = Arc::new(SqliteCompactor::new(cas.clone(), eav));
+        let local: Arc<RwLock<dyn ContentAddressableStorage>> = Arc::new(RwLock::new(cas));
+        let store = Arc::new(MemoryObjectStore::default());
+        let mut storage = ColdStorage::new(
+            local.clone(),
+            compactor.clone(),
+            store.clone(),
+            String::from("instance/"),
+        )
+        .unwrap();
+
+        let cold = content("cold");
+        let hot = content("hot");
+        storage.add(&cold).unwrap();
+        storage.add(&hot).unwrap();
+        assert_eq!(
+            storage
+                .archive(&[cold.address(), Address::from("unknown")])
+                .unwrap(),
+            1
+        );
+
+        assert!(!local.read().unwrap().contains(&cold.address()).unwrap());
+        assert!(store
+            .get(&format!("instance/{}", cold.address()))
+            .unwrap()
+            .is_some());
+        assert!(storage.contains(&cold.address()).unwrap());
+        assert_eq!(
+            storage.fetch(&cold.address()).unwrap(),
+            Some(cold.content())
+        );
+        assert_eq!(storage.fetch(&hot.address()).unwrap(), Some(hot.content()));
+        assert_eq!(
+            storage.compactor().addresses().unwrap(),
+            vec![hot.address()]
+        );
+
+        // The index survives a restart
+        let reopened =
+            ColdStorage::new(local, compactor, store, String::from("instance/")).unwrap();
+        assert_eq!(
+            reopened.fetch(&cold.address()).unwrap(),
+            Some(cold.content())
+        );
+    }
+}
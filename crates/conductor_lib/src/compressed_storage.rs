@@ -0,0 +1,219 @@
+//! Transparent compression of content in a content addressable storage.
+//!
+//! [CompressedStorage] wraps any CAS and compresses content with zstd before it gets written,
+//! if it is at least as big as the configured threshold. Compressed content gets stored as
+//! `{"zstd":"<base64 of the compressed bytes>"}` under the original address and is
+//! decompressed again on fetch, so users of the storage never see it. Smaller content, and
+//! content that was written before compression got enabled, is stored and read as is.
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_locksmith::RwLock;
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    error::{PersistenceError, PersistenceResult},
+    reporting::ReportStorage,
+};
+use std::{fmt, sync::Arc};
+use uuid::Uuid;
+
+const COMPRESSED_PREFIX: &str = "{\"zstd\":\"";
+const COMPRESSED_SUFFIX: &str = "\"}";
+
+/// When and how hard content gets compressed
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct StorageCompression {
+    /// Content smaller than this many bytes is stored uncompressed
+    #[serde(default = "default_threshold")]
+    pub threshold: usize,
+    /// zstd compression level, from 1 (fastest) to 21 (smallest)
+    #[serde(default = "default_level")]
+    pub level: i32,
+}
+
+fn default_threshold() -> usize {
+    1024
+}
+
+fn default_level() -> i32 {
+    3
+}
+
+impl Default for StorageCompression {
+    fn default() -> Self {
+        StorageCompression {
+            threshold: default_threshold(),
+            level: default_level(),
+        }
+    }
+}
+
+fn compression_error<E: fmt::Display>(error: E) -> PersistenceError {
+    PersistenceError::ErrorGeneric(format!("Could not (de)compress content: {}", error))
+}
+
+/// Content as it gets written to the wrapped storage, under the address of the original.
+struct StoredContent {
+    address: Address,
+    content: Content,
+}
+
+impl AddressableContent for StoredContent {
+    fn address(&self) -> Address {
+        self.address.clone()
+    }
+
+    fn content(&self) -> Content {
+        self.content.clone()
+    }
+
+    fn try_from_content(_content: &Content) -> JsonResult<Self> {
+        Err(JsonError::ErrorGeneric(
+            "Stored content can't be restored without its address".to_string(),
+        ))
+    }
+}
+
+#[derive(Clone)]
+pub struct CompressedStorage {
+    inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+    compression: StorageCompression,
+}
+
+impl fmt::Debug for CompressedStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompressedStorage")
+            .field("compression", &self.compression)
+            .finish()
+    }
+}
+
+impl CompressedStorage {
+    pub fn new(
+        inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+        compression: StorageCompression,
+    ) -> Self {
+        CompressedStorage { inner, compression }
+    }
+
+    fn compress(&self, content: String) -> PersistenceResult<String> {
+        if content.len() < self.compression.threshold {
+            return Ok(content);
+        }
+        let compressed = zstd::encode_all(content.as_bytes(), self.compression.level)
+            .map_err(compression_error)?;
+        let encoded = format!(
+            "{}{}{}",
+            COMPRESSED_PREFIX,
+            base64::encode(&compressed),
+            COMPRESSED_SUFFIX
+        );
+        // Content that doesn't compress is better kept as it is
+        if encoded.len() < content.len() {
+            Ok(encoded)
+        } else {
+            Ok(content)
+        }
+    }
+
+    fn decompress(stored: String) -> PersistenceResult<String> {
+        if !(stored.starts_with(COMPRESSED_PREFIX) && stored.ends_with(COMPRESSED_SUFFIX)) {
+            return Ok(stored);
+        }
+        let encoded = &stored[COMPRESSED_PREFIX.len()..stored.len() - COMPRESSED_SUFFIX.len()];
+        let compressed = base64::decode(encoded).map_err(compression_error)?;
+        let decompressed = zstd::decode_all(compressed.as_slice()).map_err(compression_error)?;
+        String::from_utf8(decompressed).map_err(compression_error)
+    }
+}
+
+impl ContentAddressableStorage for CompressedStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        let stored = StoredContent {
+            address: content.address(),
+            content: JsonString::from_json(&self.compress(String::from(content.content()))?),
+        };
+        self.inner.write().unwrap().add(&stored)
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        self.inner.read().unwrap().contains(address)
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        match self.inner.read().unwrap().fetch(address)? {
+            Some(stored) => Ok(Some(JsonString::from_json(&Self::decompress(
+                String::from(stored),
+            )?))),
+            None => Ok(None),
+        }
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.inner.read().unwrap().get_id()
+    }
+}
+
+impl ReportStorage for CompressedStorage {}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_json_api::json::RawString;
+    use holochain_persistence_api::cas::content::ExampleAddressableContent;
+    use holochain_persistence_mem::cas::memory::MemoryStorage;
+
+    fn content(text: &str) -> ExampleAddressableContent {
+        ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from(text)))
+            .unwrap()
+    }
+
+    #[test]
+    fn compresses_big_content_transparently() {
+        let inner: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        let mut storage = CompressedStorage::new(inner.clone(), StorageCompression::default());
+
+        let big = content(&"holochain ".repeat(1000));
+        let small = content("small");
+        storage.add(&big).unwrap();
+        storage.add(&small).unwrap();
+
+        let stored_big = String::from(
+            inner
+                .read()
+                .unwrap()
+                .fetch(&big.address())
+                .unwrap()
+                .unwrap(),
+        );
+        assert!(stored_big.starts_with(COMPRESSED_PREFIX));
+        assert!(stored_big.len() * 5 < String::from(big.content()).len());
+        assert_eq!(
+            inner.read().unwrap().fetch(&small.address()).unwrap(),
+            Some(small.content())
+        );
+
+        assert_eq!(storage.fetch(&big.address()).unwrap(), Some(big.content()));
+        assert_eq!(
+            storage.fetch(&small.address()).unwrap(),
+            Some(small.content())
+        );
+        assert!(storage.contains(&big.address()).unwrap());
+    }
+
+    #[test]
+    fn reads_content_written_without_compression() {
+        let inner: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        let big = content(&"holochain ".repeat(1000));
+        inner.write().unwrap().add(&big).unwrap();
+
+        let storage = CompressedStorage::new(inner, StorageCompression::default());
+        assert_eq!(storage.fetch(&big.address()).unwrap(), Some(big.content()));
+    }
+}
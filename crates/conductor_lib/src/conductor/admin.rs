@@ -41,7 +41,16 @@ pub trait ConductorAdmin {
         dna_id: &String,
         agent_id: &String,
         storage: Option<&str>,
+        membrane_proof: Option<String>,
     ) -> Result<(), HolochainError>;
+    fn clone_instance(
+        &mut self,
+        source_id: &String,
+        id: &String,
+        agent_id: Option<&String>,
+        properties: Option<&serde_json::Value>,
+        uuid: Option<String>,
+    ) -> Result<DnaConfiguration, HolochainError>;
     fn remove_instance(&mut self, id: &String) -> Result<(), HolochainError>;
     fn add_interface(&mut self, new_instance: InterfaceConfiguration)
         -> Result<(), HolochainError>;
@@ -63,6 +72,12 @@ pub trait ConductorAdmin {
         name: String,
         holo_remote_key: Option<&str>,
     ) -> Result<String, HolochainError>;
+    fn add_service_agent(
+        &mut self,
+        id: String,
+        name: String,
+        seed_file: PathBuf,
+    ) -> Result<String, HolochainError>;
     fn remove_agent(&mut self, id: &String) -> Result<(), HolochainError>;
     fn add_bridge(&mut self, new_bridge: Bridge) -> Result<(), HolochainError>;
     fn remove_bridge(
@@ -198,6 +213,7 @@ impl ConductorAdmin for Conductor {
         dna_id: &String,
         agent_id: &String,
         storage: Option<&str>,
+        membrane_proof: Option<String>,
     ) -> Result<(), HolochainError> {
         let mut new_config = self.config.clone();
         let storage_path = self
@@ -217,6 +233,15 @@ impl ConductorAdmin for Conductor {
             Some("memory") => StorageConfiguration::Memory,
             Some("file") => StorageConfiguration::File { path: storage_path },
             Some("pickle") => StorageConfiguration::Pickle { path: storage_path },
+            Some("sqlite") => StorageConfiguration::Sqlite {
+                path: storage_path,
+                indexing: Default::default(),
+            },
+            Some("rocksdb") => StorageConfiguration::Rocksdb {
+                path: storage_path,
+                options: Default::default(),
+                indexing: Default::default(),
+            },
             None | Some("lmdb") => StorageConfiguration::Lmdb {
                 path: storage_path,
                 initial_mmap_bytes: None,
@@ -234,6 +259,20 @@ impl ConductorAdmin for Conductor {
             dna: dna_id.to_string(),
             agent: agent_id.to_string(),
             storage: storage_config,
+            membrane_proof,
+            rate_limits: None,
+            compression: None,
+            encrypt_storage: false,
+            hot_tier: None,
+            cold_storage: None,
+            resource_limits: None,
+            restart_policy: None,
+            logger: None,
+            slow_zome_calls: None,
+            record_actions: None,
+            validation_coverage: None,
+            pending_validation: None,
+            validation_package_cache_size: None,
         };
         new_config.instances.push(new_instance_config);
         new_config.check_consistency(&mut self.dna_loader)?;
@@ -246,6 +285,98 @@ impl ConductorAdmin for Conductor {
         Ok(())
     }
 
+    /// Creates a new instance like the one given by source_id, e.g. for a separate space per
+    /// group or channel of an app. If properties or a uuid are given, the source instance's
+    /// DNA gets installed again with those, as DNA "<source DNA id>-<id>", so the clone joins
+    /// a different network. Otherwise the clone runs the same DNA and needs a different agent.
+    /// The clone gets the source's agent if no agent_id is given and the same kind of storage
+    /// and settings, with its own storage directory. Cold storage is not cloned.
+    /// Like add_instance, the clone is not started nor added to any interface.
+    /// Returns the configuration of the DNA the clone runs.
+    fn clone_instance(
+        &mut self,
+        source_id: &String,
+        id: &String,
+        agent_id: Option<&String>,
+        properties: Option<&serde_json::Value>,
+        uuid: Option<String>,
+    ) -> Result<DnaConfiguration, HolochainError> {
+        let source = self.config.instance_by_id(source_id).ok_or_else(|| {
+            HolochainError::ConfigError(format!("Instance {} not found in config", source_id))
+        })?;
+        if self.config.instance_by_id(id).is_some() {
+            return Err(HolochainError::ConfigError(format!(
+                "Instance {} already exists",
+                id
+            )));
+        }
+        let agent_id = agent_id.cloned().unwrap_or_else(|| source.agent.clone());
+
+        let dna_id = if properties.is_none() && uuid.is_none() {
+            if agent_id == source.agent {
+                return Err(HolochainError::ConfigError(String::from(
+                    "A clone with the same DNA needs a different agent",
+                )));
+            }
+            source.dna.clone()
+        } else {
+            let source_dna = self.config.dna_by_id(&source.dna).ok_or_else(|| {
+                HolochainError::ConfigError(format!("DNA {} not found in config", source.dna))
+            })?;
+            let dna_id = format!("{}-{}", source.dna, id);
+            if self.config.dna_by_id(&dna_id).is_some() {
+                return Err(HolochainError::ConfigError(format!(
+                    "DNA {} already exists",
+                    dna_id
+                )));
+            }
+            self.install_dna_from_file(
+                PathBuf::from(source_dna.file),
+                dna_id.clone(),
+                properties.is_some(),
+                None,
+                properties,
+                uuid.or(source_dna.uuid),
+            )?;
+            dna_id
+        };
+
+        let storage_path = self.instance_storage_dir_path().join(id.clone());
+        let storage = source.storage.at_path(
+            storage_path
+                .to_str()
+                .ok_or_else(|| {
+                    HolochainError::ConfigError(format!("invalid path {:?}", storage_path))
+                })?
+                .into(),
+        );
+        if storage != StorageConfiguration::Memory {
+            fs::create_dir_all(&storage_path)?;
+        }
+
+        let mut new_config = self.config.clone();
+        new_config.instances.push(InstanceConfiguration {
+            id: id.clone(),
+            dna: dna_id.clone(),
+            agent: agent_id,
+            storage,
+            membrane_proof: None,
+            cold_storage: None,
+            ..source
+        });
+        new_config.check_consistency(&mut self.dna_loader)?;
+        self.config = new_config;
+        let instance = self.instantiate_from_config(id)?;
+        self.instances
+            .insert(id.clone(), Arc::new(RwLock::new(instance)));
+        self.save_config()?;
+        let _ = self.start_signal_multiplexer();
+        notify(format!("Cloned instance \"{}\" as \"{}\"", source_id, id));
+        self.config
+            .dna_by_id(&dna_id)
+            .ok_or_else(|| HolochainError::ConfigError(format!("DNA {} not found", dna_id)))
+    }
+
     /// Removes the instance given by id from the config.
     /// Also removes all mentions of that instance from all interfaces to not render the config
     /// invalid.
@@ -270,6 +401,7 @@ impl ConductorAdmin for Conductor {
         if let Some(instance) = self.instances.remove(id) {
             instance.write().unwrap().kill();
         }
+        self.signal_history.forget(id);
         let _ = self.start_signal_multiplexer();
 
         notify(format!("Removed instance \"{}\".", id));
@@ -490,6 +622,10 @@ impl ConductorAdmin for Conductor {
             keystore_file,
             holo_remote_key: holo_remote_key.map(|_| true),
             test_agent: None,
+            seed_file: None,
+            client_keys: None,
+            key_backend: None,
+            tenant: None,
         };
 
         new_config.agents.push(new_agent);
@@ -502,6 +638,51 @@ impl ConductorAdmin for Conductor {
         Ok(public_address)
     }
 
+    /// Adds an agent whose keys get derived from the seed in the given file.
+    /// No passphrase is needed, neither now nor when the conductor loads the agent later,
+    /// which makes this suitable for server-side service agents and test fleets.
+    fn add_service_agent(
+        &mut self,
+        id: String,
+        name: String,
+        seed_file: PathBuf,
+    ) -> Result<String, HolochainError> {
+        let mut new_config = self.config.clone();
+        if new_config.agents.iter().any(|i| i.id == id) {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Agent with ID '{}' already exists",
+                id
+            )));
+        }
+
+        let (keystore, public_address) =
+            Keystore::new_from_seed_file(seed_file.clone(), self.hash_config.clone())?;
+        let seed_file = seed_file.to_string_lossy().into_owned();
+
+        let new_agent = AgentConfiguration {
+            id: id.clone(),
+            name,
+            public_address: public_address.clone(),
+            keystore_file: "::ignored::".to_string(),
+            holo_remote_key: None,
+            test_agent: None,
+            seed_file: Some(seed_file),
+            client_keys: None,
+            key_backend: None,
+            tenant: None,
+        };
+
+        new_config.agents.push(new_agent);
+        new_config.check_consistency(&mut self.dna_loader)?;
+        self.add_agent_keystore(id.clone(), keystore);
+        self.config = new_config;
+        self.save_config()?;
+
+        notify(format!("Added service agent \"{}\"", id));
+
+        Ok(public_address)
+    }
+
     fn remove_agent(&mut self, id: &String) -> Result<(), HolochainError> {
         let mut new_config = self.config.clone();
         if !new_config.agents.iter().any(|i| i.id == *id) {
@@ -629,14 +810,16 @@ pub mod tests {
         keystore::test_hash_config,
     };
     use holochain_common::paths::DNA_EXTENSION;
+    use holochain_core::nucleus::validation::ADMIN_GRANTOR_PROPERTY;
     use holochain_core_types::dna::Dna;
     use holochain_json_api::json::JsonString;
     use std::{
         convert::TryFrom,
         env::current_dir,
         fs::{remove_dir_all, File},
-        io::Read,
+        io::{Read, Write},
     };
+    use test_utils::{commit_admin_grant, test_admin_grantor};
 
     pub fn test_dna_loader() -> DnaLoader {
         let loader = Box::new(|_: &PathBuf| {
@@ -1160,6 +1343,7 @@ id = 'new-dna'"#,
             &String::from("new-dna"),
             &String::from("test-agent-1"),
             None,
+            None,
         );
 
         assert_eq!(add_result, Ok(()));
@@ -1321,6 +1505,11 @@ type = 'websocket'"#,
             admin: false,
             instances: Vec::new(),
             choose_free_port: None,
+            require_signed_calls: false,
+            filter_signals: false,
+            tenant: None,
+            auth: None,
+            tls: None,
         };
 
         assert_eq!(conductor.add_interface(interface_config), Ok(()),);
@@ -1445,6 +1634,7 @@ type = 'http'"#,
                 &String::from("new-dna"),
                 &String::from("test-agent-1"),
                 None,
+                None,
             ),
             Ok(())
         );
@@ -1629,6 +1819,59 @@ public_address = '{}'"#,
         assert_eq!(config_contents, toml,);
     }
 
+    #[test]
+    fn test_add_service_agent() {
+        let test_name = "test_add_service_agent";
+        let mut conductor = create_test_conductor(test_name, 3012);
+
+        create_dir_all(conductor.instance_storage_dir_path()).unwrap();
+        let seed_file = conductor.instance_storage_dir_path().join("service.seed");
+        File::create(&seed_file)
+            .unwrap()
+            .write_all(base64::encode(&[42u8; 32]).as_bytes())
+            .unwrap();
+
+        let pub_key = conductor
+            .add_service_agent(
+                String::from("service-agent"),
+                String::from("Service"),
+                seed_file.clone(),
+            )
+            .expect("Could not add service agent");
+
+        let agent_config = conductor
+            .config()
+            .agent_by_id("service-agent")
+            .expect("Service agent not in config");
+        assert_eq!(agent_config.public_address, pub_key);
+        assert_eq!(
+            agent_config.seed_file,
+            Some(seed_file.to_string_lossy().into_owned())
+        );
+
+        let (_, derived_key) =
+            Keystore::new_from_seed_file(seed_file.clone(), test_hash_config()).unwrap();
+        assert_eq!(derived_key, pub_key);
+
+        let keystore = conductor
+            .get_keystore_for_agent(&String::from("service-agent"))
+            .expect("Could not load service agent keystore");
+        let keybundle = keystore
+            .lock()
+            .unwrap()
+            .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+            .unwrap();
+        assert_eq!(keybundle.get_id(), pub_key);
+
+        assert!(conductor
+            .add_service_agent(
+                String::from("service-agent"),
+                String::from("Service"),
+                seed_file,
+            )
+            .is_err());
+    }
+
     #[test]
     fn test_remove_agent() {
         let test_name = "test_remove_agent";
@@ -1748,4 +1991,168 @@ handle = 'my favourite instance!'"#,
 
         assert_eq!(config_contents, toml,);
     }
+
+    #[test]
+    fn test_migrate_instance_storage() {
+        let test_name = "test_migrate_instance_storage";
+        let mut conductor = create_test_conductor(test_name, 3013);
+        let id = String::from("test-instance-1");
+        let storage_path = current_dir()
+            .expect("Could not get current dir")
+            .join("tmp-test")
+            .join(test_name)
+            .join("storage")
+            .join("sqlite");
+        let _ = remove_dir_all(storage_path.clone());
+        let top_header = conductor
+            .instances
+            .get(&id)
+            .unwrap()
+            .read()
+            .unwrap()
+            .state()
+            .unwrap()
+            .agent()
+            .top_chain_header();
+
+        let storage = StorageConfiguration::Sqlite {
+            path: storage_path.to_str().unwrap().to_string(),
+            indexing: Default::default(),
+        };
+        let report = conductor
+            .instance_migrate_storage(&id, storage.clone())
+            .unwrap();
+        assert!(report.contents > 0);
+        assert!(report.verified > 0);
+
+        assert_eq!(
+            conductor.config().instance_by_id(&id).unwrap().storage,
+            storage
+        );
+        let instance = conductor.instances.get(&id).unwrap().read().unwrap();
+        assert_eq!(
+            instance.state().unwrap().agent().top_chain_header(),
+            top_header
+        );
+        drop(instance);
+
+        assert!(conductor.instance_migrate_storage(&id, storage).is_err());
+    }
+
+    #[test]
+    fn test_clone_instance() {
+        let test_name = "test_clone_instance";
+        let mut conductor = create_test_conductor(test_name, 3015);
+        let source_id = String::from("test-instance-1");
+        let source = conductor.config().instance_by_id(&source_id).unwrap();
+
+        // Same DNA and agent would just be a second copy of the source
+        assert!(conductor
+            .clone_instance(&source_id, &String::from("clone"), None, None, None)
+            .is_err());
+
+        let clone_id = String::from("channel-1");
+        let dna = conductor
+            .clone_instance(
+                &source_id,
+                &clone_id,
+                None,
+                None,
+                Some(String::from("channel-1")),
+            )
+            .unwrap();
+        assert_eq!(dna.id, format!("{}-{}", source.dna, clone_id));
+        assert_eq!(dna.uuid, Some(String::from("channel-1")));
+        assert_ne!(
+            dna.hash,
+            conductor.config().dna_by_id(&source.dna).unwrap().hash
+        );
+
+        let clone = conductor.config().instance_by_id(&clone_id).unwrap();
+        assert_eq!(clone.dna, dna.id);
+        assert_eq!(clone.agent, source.agent);
+        assert!(conductor.instances.contains_key(&clone_id));
+        let instance = conductor.instances.get(&clone_id).unwrap().read().unwrap();
+        assert_eq!(
+            instance
+                .state()
+                .unwrap()
+                .nucleus()
+                .dna()
+                .unwrap()
+                .address()
+                .to_string(),
+            dna.hash
+        );
+        drop(instance);
+
+        assert!(conductor
+            .clone_instance(&source_id, &clone_id, None, None, Some(String::from("x")))
+            .is_err());
+    }
+
+    #[test]
+    fn test_update_instance_dna() {
+        let test_name = "test_update_instance_dna";
+        let mut conductor = create_test_conductor(test_name, 3014);
+        let id = String::from("admin-granted-instance");
+        let dna_id = String::from("admin-granted-dna");
+        let new_dna_id = String::from("new-dna");
+        // Only DNAs naming an admin grantor can be updated
+        let properties = json!({ ADMIN_GRANTOR_PROPERTY: test_admin_grantor().address() });
+        conductor
+            .install_dna_from_file(
+                PathBuf::from("admin-granted-dna.dna.json"),
+                dna_id.clone(),
+                false,
+                None,
+                Some(&properties),
+                None,
+            )
+            .unwrap();
+        conductor
+            .install_dna_from_file(
+                PathBuf::from("new-dna.dna.json"),
+                new_dna_id.clone(),
+                false,
+                None,
+                Some(&properties),
+                Some(String::from("version-2")),
+            )
+            .unwrap();
+        conductor
+            .add_instance(&id, &dna_id, &String::from("test-agent-1"), None, None)
+            .unwrap();
+
+        // The agent needs an admin grant to have its chain migrated
+        assert!(conductor.instance_update_dna(&id, &new_dna_id).is_err());
+        let context = conductor
+            .instances
+            .get(&id)
+            .unwrap()
+            .read()
+            .unwrap()
+            .context()
+            .unwrap();
+        commit_admin_grant(&context);
+
+        let report = conductor.instance_update_dna(&id, &new_dna_id).unwrap();
+        assert_ne!(report.old_dna, report.new_dna);
+        // Only the uuid differs, so no validation rules changed
+        assert!(report.changed_entry_types.is_empty());
+        assert_eq!(report.revalidation.checked, 0);
+
+        assert_eq!(
+            conductor.config().instance_by_id(&id).unwrap().dna,
+            new_dna_id
+        );
+        let instance = conductor.instances.get(&id).unwrap().read().unwrap();
+        assert_eq!(
+            instance.state().unwrap().nucleus().dna().unwrap().address(),
+            report.new_dna
+        );
+        drop(instance);
+
+        assert!(conductor.instance_update_dna(&id, &new_dna_id).is_err());
+    }
 }
@@ -0,0 +1,362 @@
+//! Webhook alerts for operational anomalies, so that operators get paged.
+//!
+//! The instance supervisor calls [Conductor::check_alerts] on every round, which looks at the
+//! running instances every `check_interval_secs` of the [AlertConfiguration]. An instance
+//! above one of its thresholds fires an alert of that kind, which gets posted as JSON to the
+//! webhooks that want it, along with the section of the instance's state dump that shows the
+//! problem. While the instance stays above the threshold, the alert only gets sent again after
+//! `repeat_after_secs`.
+use crate::{
+    conductor::{base::notify, Conductor},
+    config::{AlertConfiguration, AlertKind, WebhookConfiguration},
+};
+use chrono::Utc;
+use holochain_core::state_dump::{DumpOptions, StateDump};
+use reqwest;
+use serde_json::Value;
+use snowflake::ProcessUniqueId;
+use std::{
+    collections::HashMap,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Minutes of network traffic the timeouts get counted over
+pub const NETWORK_TIMEOUT_WINDOW_MINUTES: u64 = 5;
+
+/// How many items of a state dump section go into an alert at most
+const MAX_DUMP_ITEMS: usize = 50;
+
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub instance_id: String,
+    pub message: String,
+    pub value: u64,
+    pub threshold: u64,
+    pub time: String,
+    /// The section of the instance's state dump that shows the problem
+    pub state_dump: Value,
+}
+
+#[derive(Default)]
+pub struct AlertState {
+    last_check: Option<Instant>,
+    /// When the checks first saw each pending validation of an instance
+    pending_since: HashMap<String, HashMap<ProcessUniqueId, Instant>>,
+    /// Alerts that keep firing, and when they got sent last
+    firing: HashMap<(AlertKind, String), Instant>,
+}
+
+impl AlertState {
+    /// Whether an alert should get sent, given whether its threshold is crossed now
+    fn should_send(
+        &mut self,
+        kind: AlertKind,
+        instance_id: &str,
+        crossed: bool,
+        repeat_after: Duration,
+    ) -> bool {
+        let key = (kind, instance_id.to_string());
+        if !crossed {
+            self.firing.remove(&key);
+            return false;
+        }
+        match self.firing.get(&key) {
+            Some(sent) if sent.elapsed() < repeat_after => false,
+            _ => {
+                self.firing.insert(key, Instant::now());
+                true
+            }
+        }
+    }
+}
+
+fn crossed(kind: AlertKind, value: u64, threshold: u64) -> bool {
+    match kind {
+        // An instance that never restarted is fine whatever the threshold
+        AlertKind::InstanceRestarts => value > 0 && value >= threshold,
+        _ => value > threshold,
+    }
+}
+
+fn restarts_message(restarts: u64) -> String {
+    format!("Instance crashed and got restarted {} times", restarts)
+}
+
+/// Keeps the first items of a state dump section
+fn dump_section<T: serde::Serialize>(items: impl Iterator<Item = T>) -> Value {
+    Value::Array(
+        items
+            .take(MAX_DUMP_ITEMS)
+            .filter_map(|item| serde_json::to_value(item).ok())
+            .collect(),
+    )
+}
+
+fn post_alert(webhooks: Vec<WebhookConfiguration>, alert: Alert) {
+    let spawned = thread::Builder::new()
+        .name(format!("alert_webhook/{}", alert.instance_id))
+        .spawn(move || {
+            let client = match reqwest::Client::builder()
+                .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+                .build()
+            {
+                Ok(client) => client,
+                Err(error) => {
+                    notify(format!("Could not build webhook client: {}", error));
+                    return;
+                }
+            };
+            for webhook in webhooks {
+                let mut request = client.post(webhook.url.as_str());
+                for (name, value) in webhook.headers.iter() {
+                    request = request.header(name.as_str(), value.as_str());
+                }
+                if let Err(error) = request
+                    .json(&alert)
+                    .send()
+                    .and_then(|response| response.error_for_status())
+                {
+                    notify(format!(
+                        "Could not send alert to webhook {}: {}",
+                        webhook.url, error
+                    ));
+                }
+            }
+        });
+    if let Err(error) = spawned {
+        notify(format!("Could not spawn webhook thread: {}", error));
+    }
+}
+
+impl Conductor {
+    /// Checks the instances against the alert thresholds, if they are due, and sends the alerts
+    /// that fire to the webhooks. Returns the alerts that got sent.
+    pub fn check_alerts(&mut self) -> Vec<Alert> {
+        let config = match self.config.alerts.clone() {
+            Some(config) => config,
+            None => return Vec::new(),
+        };
+        let interval = Duration::from_secs(config.check_interval_secs);
+        if let Some(last_check) = self.alert_state.last_check {
+            if last_check.elapsed() < interval {
+                return Vec::new();
+            }
+        }
+        self.alert_state.last_check = Some(Instant::now());
+
+        let alerts = self.evaluate_alerts(&config);
+        for alert in alerts.iter() {
+            notify(format!(
+                "Alert {:?} for instance \"{}\": {}",
+                alert.kind, alert.instance_id, alert.message
+            ));
+            let webhooks: Vec<WebhookConfiguration> = config
+                .webhooks
+                .iter()
+                .filter(|webhook| webhook.wants(alert.kind))
+                .cloned()
+                .collect();
+            if !webhooks.is_empty() {
+                post_alert(webhooks, alert.clone());
+            }
+        }
+        alerts
+    }
+
+    fn evaluate_alerts(&mut self, config: &AlertConfiguration) -> Vec<Alert> {
+        let repeat_after = Duration::from_secs(config.repeat_after_secs);
+        let ids: Vec<String> = self.config.instances.iter().map(|i| i.id.clone()).collect();
+        self.alert_state
+            .pending_since
+            .retain(|id, _| ids.contains(id));
+        let mut alerts = Vec::new();
+        for id in ids {
+            let context = match self.instances.get(&id) {
+                Some(instance) if instance.read().unwrap().active() => {
+                    instance.read().unwrap().context().ok()
+                }
+                _ => None,
+            };
+            let restarts = self
+                .restart_states
+                .get(&id)
+                .map(|state| u64::from(state.restarts()))
+                .unwrap_or(0);
+            let (context, state) = match context.and_then(|c| c.state().map(|s| (c.clone(), s))) {
+                Some(context_and_state) => context_and_state,
+                None => {
+                    self.alert_state.pending_since.remove(&id);
+                    // Crashed instances that the supervisor gave up on still count
+                    let kind = AlertKind::InstanceRestarts;
+                    let crossed = crossed(kind, restarts, config.max_instance_restarts);
+                    if self
+                        .alert_state
+                        .should_send(kind, &id, crossed, repeat_after)
+                    {
+                        alerts.push(Alert {
+                            kind,
+                            instance_id: id.clone(),
+                            message: restarts_message(restarts),
+                            value: restarts,
+                            threshold: config.max_instance_restarts,
+                            time: Utc::now().to_rfc3339(),
+                            state_dump: json!({ "restarts": restarts }),
+                        });
+                    }
+                    continue;
+                }
+            };
+
+            let queued = state.dht().queued_holding_workflows().clone();
+            let in_process = state.dht().in_process_holding_workflows().clone();
+            let pending_since = self
+                .alert_state
+                .pending_since
+                .entry(id.clone())
+                .or_insert_with(HashMap::new);
+            let now = Instant::now();
+            let pending: Vec<ProcessUniqueId> = queued
+                .iter()
+                .chain(in_process.iter())
+                .map(|p| p.pending.uuid)
+                .collect();
+            pending_since.retain(|uuid, _| pending.contains(uuid));
+            for uuid in pending {
+                pending_since.entry(uuid).or_insert(now);
+            }
+            let oldest_pending_secs = pending_since
+                .values()
+                .map(|since| since.elapsed().as_secs())
+                .max()
+                .unwrap_or(0);
+            let timeouts = state
+                .network()
+                .traffic
+                .summary(state.clock().now(), NETWORK_TIMEOUT_WINDOW_MINUTES)
+                .total
+                .timeouts;
+            let holding_queue_depth = queued.len() as u64;
+
+            let checks = vec![
+                (
+                    AlertKind::HoldingQueueDepth,
+                    holding_queue_depth,
+                    config.max_holding_queue_depth,
+                    format!("{} holding workflows are waiting", holding_queue_depth),
+                ),
+                (
+                    AlertKind::PendingValidationAge,
+                    oldest_pending_secs,
+                    config.max_pending_validation_age_secs,
+                    format!("A validation has been pending for {}s", oldest_pending_secs),
+                ),
+                (
+                    AlertKind::NetworkTimeouts,
+                    timeouts,
+                    config.max_network_timeouts,
+                    format!(
+                        "{} network requests timed out in the last {} minutes",
+                        timeouts, NETWORK_TIMEOUT_WINDOW_MINUTES
+                    ),
+                ),
+                (
+                    AlertKind::InstanceRestarts,
+                    restarts,
+                    config.max_instance_restarts,
+                    restarts_message(restarts),
+                ),
+            ];
+            let mut dump = None;
+            for (kind, value, threshold, message) in checks {
+                let crossed = crossed(kind, value, threshold);
+                if !self
+                    .alert_state
+                    .should_send(kind, &id, crossed, repeat_after)
+                {
+                    continue;
+                }
+                let dump = dump
+                    .get_or_insert_with(|| StateDump::new(context.clone(), DumpOptions::default()));
+                let state_dump = match kind {
+                    AlertKind::HoldingQueueDepth | AlertKind::PendingValidationAge => json!({
+                        "queued_holding_workflows":
+                            dump_section(dump.queued_holding_workflows.iter()),
+                        "in_process_holding_workflows":
+                            dump_section(dump.in_process_holding_workflows.iter()),
+                    }),
+                    AlertKind::NetworkTimeouts => json!({
+                        "query_flows": dump_section(dump.query_flows.iter()),
+                        "validation_package_flows":
+                            dump_section(dump.validation_package_flows.iter()),
+                        "direct_message_flows": dump_section(dump.direct_message_flows.iter()),
+                        "network_traffic":
+                            state.network().traffic.summary(NETWORK_TIMEOUT_WINDOW_MINUTES),
+                    }),
+                    AlertKind::InstanceRestarts => json!({
+                        "restarts": restarts,
+                        "queued_calls": dump_section(dump.queued_calls.iter()),
+                        "running_calls": dump_section(dump.running_calls.iter()),
+                    }),
+                };
+                alerts.push(Alert {
+                    kind,
+                    instance_id: id.clone(),
+                    message,
+                    value,
+                    threshold,
+                    time: Utc::now().to_rfc3339(),
+                    state_dump,
+                });
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::conductor::admin::tests::create_test_conductor;
+
+    #[test]
+    fn alerts_get_sent_once_until_they_repeat() {
+        let mut state = AlertState::default();
+        let hour = Duration::from_secs(3600);
+        let kind = AlertKind::HoldingQueueDepth;
+        assert!(!state.should_send(kind, "app", false, hour));
+        assert!(state.should_send(kind, "app", true, hour));
+        assert!(!state.should_send(kind, "app", true, hour));
+        assert!(state.should_send(AlertKind::NetworkTimeouts, "app", true, hour));
+        assert!(state.should_send(kind, "other", true, hour));
+
+        // Fires again once it went back below the threshold
+        assert!(!state.should_send(kind, "app", false, hour));
+        assert!(state.should_send(kind, "app", true, hour));
+
+        assert!(state.should_send(kind, "app", true, Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn test_check_alerts() {
+        let test_name = "test_check_alerts";
+        let mut conductor = create_test_conductor(test_name, 3811);
+        conductor.start_all_instances().unwrap();
+        assert!(conductor.check_alerts().is_empty());
+
+        conductor.config.alerts = Some(AlertConfiguration {
+            webhooks: Vec::new(),
+            max_holding_queue_depth: 1000,
+            max_pending_validation_age_secs: 600,
+            max_network_timeouts: 20,
+            max_instance_restarts: 0,
+            check_interval_secs: 0,
+            repeat_after_secs: 3600,
+        });
+        // Healthy instances that never restarted don't fire
+        assert!(conductor.check_alerts().is_empty());
+    }
+}
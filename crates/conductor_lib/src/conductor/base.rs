@@ -1,36 +1,73 @@
 use crate::{
-    conductor::broadcaster::Broadcaster,
+    conductor::{
+        broadcaster::Broadcaster, metrics_endpoint::record_instance_gauges,
+        signal_history::SignalHistory,
+    },
     config::{
-        serialize_configuration, Configuration, InterfaceConfiguration, InterfaceDriver,
+        serialize_configuration, Configuration, InterfaceConfiguration, InterfaceDriver, LogFormat,
         NetworkConfig, StorageConfiguration,
     },
     context_builder::ContextBuilder,
     dpki_instance::DpkiInstance,
+    encrypted_storage::StorageKey,
     error::HolochainInstanceError,
+    json_logger::JsonLogger,
     key_loaders::test_keystore,
     keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
+    log_router::{install_log_router, log_router, parse_level},
     port_utils::{try_with_port, INTERFACE_CONNECT_ATTEMPTS_MAX},
+    signing_backend::{keystore_from_os_keychain, KeystoreBackend, Pkcs11Backend, SigningBackend},
     Holochain,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use holochain_common::paths::DNA_EXTENSION;
 use holochain_core::{
-    logger::Logger, network::handler::fetch::fetch_aspects_for_entry, signal::Signal,
+    action_recording::RecordingHeader,
+    agent::state::AgentState,
+    instance_archive::InstanceArchive,
+    logger::Logger,
+    network::{
+        actions::block_agent::{block_agent, unblock_agent},
+        handler::fetch::fetch_aspects_for_entry,
+    },
+    signal::Signal,
+    storage_compaction::{compact_storage, CompactionReport},
+    storage_migration::{migrate_storage, MigrationReport},
+    storage_scrubber::{scrub_and_restore, ScrubReport},
+    storage_usage::StorageUsage,
+    workflows::{
+        revalidate_held::{
+            changed_entry_types, revalidate_held_entries_workflow, RevalidationReport,
+        },
+        rotate_grant::rotate_grant_workflow,
+    },
 };
 use holochain_core_types::{
-    agent::AgentId,
-    dna::Dna,
+    agent::{AgentId, Base32},
+    dna::{capabilities::CapabilityUseRecord, Dna},
+    entry::entry_type::EntryType,
     error::{HcResult, HolochainError},
     network::entry_aspect::EntryAspect,
 };
-use holochain_dpki::{key_bundle::KeyBundle, password_encryption::PwHashConfig};
+use holochain_dpki::{
+    key_bundle::KeyBundle,
+    password_encryption::{PwHashConfig, SALTBYTES},
+};
 use holochain_json_api::json::JsonString;
 use holochain_locksmith::{Mutex, RwLock};
-use holochain_logging::{rule::RuleFilter, FastLogger, FastLoggerBuilder};
-use holochain_persistence_api::{cas::content::AddressableContent, hash::HashString};
+use holochain_logging::{rule::RuleFilter, FastLoggerBuilder};
+use holochain_persistence_api::{
+    cas::{content::AddressableContent, storage::ContentAddressableStorage},
+    hash::HashString,
+};
+use holochain_persistence_file::cas::file::FilesystemStorage;
+use holochain_persistence_lmdb::cas::lmdb::LmdbStorage;
+use holochain_persistence_mem::cas::memory::MemoryStorage;
+use holochain_persistence_pickle::cas::pickle::PickleStorage;
 use holochain_tracing as ht;
 use jsonrpc_ws_server::jsonrpc_core::IoHandler;
 use lib3h_protocol::types::AspectHash;
+use lib3h_sodium::secbuf::SecBuf;
 use std::{
     clone::Clone,
     collections::HashMap,
@@ -38,28 +75,36 @@ use std::{
     fs::{self, File},
     io::prelude::*,
     option::NoneError,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 #[cfg(unix)]
 use crate::conductor::passphrase_manager::PassphraseServiceUnixSocket;
 use crate::{
+    cold_storage::{archive_cold_content, ArchivalReport, ColdStorage, S3ObjectStore},
     conductor::passphrase_manager::{
         PassphraseManager, PassphraseService, PassphraseServiceCmd, PassphraseServiceMock,
     },
-    config::{AgentConfiguration, PassphraseServiceConfig, TracingConfiguration},
+    conductor::{alerts::AlertState, health::HealthSample, supervisor::RestartState},
+    config::{
+        AgentConfiguration, KeyBackendConfiguration, PassphraseServiceConfig, TracingConfiguration,
+    },
     interface::{ConductorApiBuilder, InstanceMap, Interface},
     port_utils::get_free_port,
-    signal_wrapper::SignalWrapper,
+    rocksdb_storage::RocksDbStorage,
+    signal_wrapper::{DnaUpdateProgress, SignalWrapper},
+    sqlite_storage::SqliteStorage,
     static_file_server::ConductorStaticFileServer,
     static_server_impls::NickelStaticServer as StaticServer,
+    storage_backend::{StorageBackendFactory, StorageBackendParams},
+    trace_export::{OtlpReporter, SpanReporter},
 };
 use boolinator::Boolinator;
-use holochain_core::context::InstanceStats;
+use holochain_core::context::{Context, InstanceStats};
 use holochain_core_types::dna::bridges::BridgePresence;
 use holochain_net::p2p_config::{BackendConfig, P2pBackendKind, P2pConfig};
 
@@ -99,13 +144,7 @@ pub fn mount_conductor_from_config(config: Configuration) {
     CONDUCTOR.lock().unwrap().replace(conductor);
 }
 
-type TraceReporterMap = HashMap<
-    String,
-    (
-        Receiver<ht::FinishedSpan>,
-        ht::reporter::JaegerCompactReporter,
-    ),
->;
+type TraceReporterMap = HashMap<String, (Receiver<ht::FinishedSpan>, Box<dyn SpanReporter>)>;
 
 /// options for GetMeta request
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -120,6 +159,44 @@ pub struct GetMetaResponse {
     link_remove_count: u32,
 }
 
+/// result structure for EntryHolders requests
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EntryHoldersResponse {
+    pub holders: Vec<HashString>,
+    pub holder_count: usize,
+}
+
+/// result structure for ChainHead requests
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChainHeadResponse {
+    pub address: Option<HashString>,
+    pub length: u64,
+    pub sequence: Option<u64>,
+    pub timestamp: Option<String>,
+}
+
+impl From<&AgentState> for ChainHeadResponse {
+    fn from(agent_state: &AgentState) -> Self {
+        let top_header = agent_state.top_chain_header();
+        let length = agent_state.chain_length();
+        ChainHeadResponse {
+            address: top_header.as_ref().map(|header| header.address()),
+            length,
+            sequence: length.checked_sub(1),
+            timestamp: top_header.map(|header| header.timestamp().to_string()),
+        }
+    }
+}
+
+/// result structure for DNA updates
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DnaUpdateReport {
+    pub old_dna: HashString,
+    pub new_dna: HashString,
+    pub changed_entry_types: Vec<EntryType>,
+    pub revalidation: RevalidationReport,
+}
+
 /// Main representation of the conductor.
 /// Holds a `HashMap` of Holochain instances referenced by ID.
 /// A primary point in this struct is
@@ -145,9 +222,20 @@ pub struct Conductor {
     pub(in crate::conductor) dna_loader: DnaLoader,
     pub(in crate::conductor) ui_dir_copier: UiDirCopier,
     signal_tx: Option<SignalSender>,
-    logger: FastLogger,
     p2p_config: Option<P2pConfig>,
+    // DNA entry storages shared by all in-memory instances, respectively by all persisted
+    // instances of the same storage backend
+    memory_dna_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    dna_storages: HashMap<String, Arc<RwLock<dyn ContentAddressableStorage>>>,
+    storage_backends: HashMap<String, StorageBackendFactory>,
+    cold_storages: HashMap<String, ColdStorage>,
     pub passphrase_manager: Arc<PassphraseManager>,
+    tenant_passphrase_managers: HashMap<String, Arc<PassphraseManager>>,
+    pub(in crate::conductor) health_samples: HashMap<String, HealthSample>,
+    pub(in crate::conductor) restart_states: HashMap<String, RestartState>,
+    pub(in crate::conductor) alert_state: AlertState,
+    pub(in crate::conductor) started_at: Instant,
+    pub(in crate::conductor) signal_history: Arc<SignalHistory>,
     pub hash_config: Option<PwHashConfig>, // currently this has to be pub for testing.  would like to remove
 }
 
@@ -157,10 +245,9 @@ impl Drop for Conductor {
             .unwrap_or_else(|err| println!("Error during shutdown, continuing anyway: {:?}", err));
 
         // Flushing the logger's buffer writer
-        self.logger.flush();
+        log::logger().flush();
         // Do not shut down the logging thread if there is multiple concurrent conductor thread
         // like during unit testing because they all use the same registered logger
-        // self.logger.shutdown();
     }
 }
 
@@ -192,20 +279,44 @@ impl Conductor {
     pub fn from_config(config: Configuration) -> Self {
         lib3h_sodium::check_init();
         let _rules = config.logger.rules.clone();
-        let mut logger_builder = FastLoggerBuilder::new();
-        logger_builder.set_level_from_str(&config.logger.logger_level.as_str());
-
-        for rule in config.logger.rules.rules.iter() {
-            logger_builder.add_rule_filter(RuleFilter::new(
-                rule.pattern.as_str(),
-                rule.exclude,
-                rule.color.as_ref().unwrap_or(&String::default()).as_str(),
-            ));
-        }
+        // With the log router in front, the conductor's logger gets all levels and the router
+        // filters them, so that levels can be changed at runtime and per instance.
+        let routed = install_log_router();
+        let level = parse_level(&config.logger.logger_level).unwrap_or(log::LevelFilter::Debug);
+        let logger: Box<dyn log::Log> = match config.logger.format {
+            LogFormat::Json => Box::new(JsonLogger::new(
+                if routed {
+                    log::LevelFilter::Trace
+                } else {
+                    level
+                },
+                config.logger.rules.clone(),
+            )),
+            LogFormat::Text => {
+                let mut logger_builder = FastLoggerBuilder::new();
+                if routed {
+                    logger_builder.set_level_from_str("Trace");
+                } else {
+                    logger_builder.set_level_from_str(&config.logger.logger_level.as_str());
+                }
+
+                for rule in config.logger.rules.rules.iter() {
+                    logger_builder.add_rule_filter(RuleFilter::new(
+                        rule.pattern.as_str(),
+                        rule.exclude,
+                        rule.color.as_ref().unwrap_or(&String::default()).as_str(),
+                    ));
+                }
 
-        let logger = logger_builder
-            .build()
-            .expect("Fail to instanciate the logging factory.");
+                Box::new(
+                    logger_builder
+                        .build()
+                        .expect("Fail to instanciate the logging factory."),
+                )
+            }
+        };
+        log_router().set_json_lines(config.logger.format == LogFormat::Json);
+        log_router().set_conductor_logger(logger, level);
 
         if !config.ui_bundles.is_empty() || !config.ui_interfaces.is_empty() {
             println!();
@@ -215,25 +326,21 @@ impl Conductor {
             println!();
         }
 
-        let passphrase_service: Arc<Mutex<dyn PassphraseService + Send>> =
-            if let PassphraseServiceConfig::UnixSocket { path } = config.passphrase_service.clone()
-            {
-                #[cfg(not(unix))]
-                let _ = path;
-                #[cfg(not(unix))]
-                panic!("Unix domain sockets are not available on non-Unix systems. Can't create a PassphraseServiceUnixSocket.");
+        if let Some(ref path) = config.security_audit_log {
+            holochain_dpki::audit::enable_audit_log(path).unwrap_or_else(|err| {
+                panic!(
+                    "Could not open security audit log {}: {}",
+                    path.display(),
+                    err
+                )
+            });
+        }
 
-                #[cfg(unix)]
-                Arc::new(Mutex::new(PassphraseServiceUnixSocket::new(path)))
-            } else {
-                match config.passphrase_service.clone() {
-                    PassphraseServiceConfig::Cmd => Arc::new(Mutex::new(PassphraseServiceCmd {})),
-                    PassphraseServiceConfig::Mock { passphrase } => {
-                        Arc::new(Mutex::new(PassphraseServiceMock { passphrase }))
-                    }
-                    _ => unreachable!(),
-                }
-            };
+        if let Some(ref path) = config.event_log {
+            holochain_core::event_log::enable_event_log(path).unwrap_or_else(|err| {
+                panic!("Could not open event log {}: {}", path.display(), err)
+            });
+        }
 
         Conductor {
             instances: HashMap::new(),
@@ -246,18 +353,84 @@ impl Conductor {
             signal_multiplexer_kill_switch: None,
             stats_thread_kill_switch: None,
             stats_signal_receiver: None,
-            config,
             key_loader: Arc::new(Box::new(Self::load_key)),
             dna_loader: Arc::new(Box::new(Self::load_dna)),
             ui_dir_copier: Arc::new(Box::new(Self::copy_ui_dir)),
             signal_tx: None,
-            logger,
             p2p_config: None,
-            passphrase_manager: Arc::new(PassphraseManager::new(passphrase_service)),
+            memory_dna_storage: Arc::new(RwLock::new(MemoryStorage::new())),
+            dna_storages: HashMap::new(),
+            storage_backends: HashMap::new(),
+            cold_storages: HashMap::new(),
+            passphrase_manager: Self::make_passphrase_manager(
+                &config.passphrase_service,
+                config.keystore_session_duration,
+            ),
+            tenant_passphrase_managers: HashMap::new(),
+            health_samples: HashMap::new(),
+            restart_states: HashMap::new(),
+            alert_state: AlertState::default(),
+            started_at: Instant::now(),
+            signal_history: Arc::new(SignalHistory::new(config.signals.history_size)),
+            config,
             hash_config: None,
         }
     }
 
+    fn make_passphrase_manager(
+        service_config: &PassphraseServiceConfig,
+        session_duration: Option<u64>,
+    ) -> Arc<PassphraseManager> {
+        let passphrase_service: Arc<Mutex<dyn PassphraseService + Send>> =
+            if let PassphraseServiceConfig::UnixSocket { path } = service_config.clone() {
+                #[cfg(not(unix))]
+                let _ = path;
+                #[cfg(not(unix))]
+                panic!("Unix domain sockets are not available on non-Unix systems. Can't create a PassphraseServiceUnixSocket.");
+
+                #[cfg(unix)]
+                Arc::new(Mutex::new(PassphraseServiceUnixSocket::new(path)))
+            } else {
+                match service_config.clone() {
+                    PassphraseServiceConfig::Cmd => Arc::new(Mutex::new(PassphraseServiceCmd {})),
+                    PassphraseServiceConfig::Mock { passphrase } => {
+                        Arc::new(Mutex::new(PassphraseServiceMock { passphrase }))
+                    }
+                    _ => unreachable!(),
+                }
+            };
+        Arc::new(
+            PassphraseManager::new(passphrase_service)
+                .with_session_duration(session_duration.map(Duration::from_secs)),
+        )
+    }
+
+    /// The passphrase manager for the keystores of the given tenant's agents, or of the
+    /// conductor's own agents if `tenant` is None. Tenants without their own passphrase
+    /// service share the conductor's.
+    pub fn passphrase_manager_for_tenant(
+        &mut self,
+        tenant: Option<&String>,
+    ) -> Arc<PassphraseManager> {
+        let service_config = match tenant
+            .and_then(|id| self.config.tenant_by_id(id))
+            .and_then(|tenant| tenant.passphrase_service)
+        {
+            Some(service_config) => service_config,
+            None => return self.passphrase_manager.clone(),
+        };
+        let session_duration = self.config.keystore_session_duration;
+        self.tenant_passphrase_managers
+            .entry(tenant.unwrap().clone())
+            .or_insert_with(|| Self::make_passphrase_manager(&service_config, session_duration))
+            .clone()
+    }
+
+    /// Forgets the passphrase manager of a removed tenant
+    pub(in crate::conductor) fn drop_tenant_passphrase_manager(&mut self, tenant: &str) {
+        self.tenant_passphrase_managers.remove(tenant);
+    }
+
     pub fn spawn_stats_thread(&mut self) {
         self.stop_stats_thread();
         let instances = self.instances.clone();
@@ -292,6 +465,10 @@ impl Conductor {
                     }
                 }
 
+                for (id, stats) in instance_stats.iter() {
+                    record_instance_gauges(id, stats);
+                }
+
                 if let Err(e) = stats_tx.send(instance_stats) {
                     error!("Could not send stats signal over channel: {:?}", e);
                 }
@@ -320,6 +497,13 @@ impl Conductor {
         self
     }
 
+    /// Makes a custom storage backend available to instances under the given name.
+    /// Instances select it with `type = "custom"` and `backend = "<name>"` in their storage
+    /// config. Needs to happen before those instances get instantiated.
+    pub fn register_storage_backend(&mut self, name: &str, factory: StorageBackendFactory) {
+        self.storage_backends.insert(name.to_string(), factory);
+    }
+
     pub fn config(&self) -> Configuration {
         self.config.clone()
     }
@@ -331,6 +515,7 @@ impl Conductor {
         let broadcasters = self.interface_broadcasters.clone();
         let instance_signal_receivers = self.instance_signal_receivers.clone();
         let signal_tx = self.signal_tx.clone();
+        let signal_history = self.signal_history.clone();
         let config = self.config.clone();
         let (kill_switch_tx, kill_switch_rx) = unbounded();
         self.signal_multiplexer_kill_switch = Some(kill_switch_tx);
@@ -354,6 +539,12 @@ impl Conductor {
                     {
                         if let Ok(signal) = receiver.try_recv() {
                             signal_tx.clone().map(|s| s.send(signal.clone()));
+                            let sequence = match signal {
+                                Signal::User(_) => {
+                                    Some(signal_history.record(instance_id, signal.clone()))
+                                }
+                                _ => None,
+                            };
                             let interfaces_with_instance: Vec<&InterfaceConfiguration> =
                                 match signal {
                                     // Send internal signals only to admin interfaces, if signals.trace is set:
@@ -378,6 +569,9 @@ impl Conductor {
                                         }
                                     }
 
+                                    // Slow zome calls concern whoever administers the conductor:
+                                    Signal::SlowZomeCall(_) => admin_interfaces.clone(),
+
                                     // Pass through user-defined  signals to the according interfaces
                                     // in which the source instance is exposed:
                                     Signal::User(_) => {
@@ -409,6 +603,7 @@ impl Conductor {
                                         broadcaster.send(SignalWrapper::InstanceSignal {
                                             signal: signal.clone(),
                                             instance_id: instance_id.clone(),
+                                            sequence,
                                         })
                                     {
                                         notify(error.to_string());
@@ -447,9 +642,10 @@ impl Conductor {
             .spawn(move || loop {
                 // TODO: try using crossbeam Select?
                 for (rx, reporter) in reporters.read().unwrap().values() {
-                    if let Ok(span) = rx.try_recv() {
-                        if let Err(e) = reporter.report(&[span]) {
-                            warn!("Could not report span: {:?}", e);
+                    let spans: Vec<_> = rx.try_iter().take(reporter.batch_size()).collect();
+                    if !spans.is_empty() {
+                        if let Err(e) = reporter.report(&spans) {
+                            warn!("Could not report spans: {}", e);
                         }
                     }
                 }
@@ -471,14 +667,21 @@ impl Conductor {
     }
 
     pub fn start_all_interfaces(&mut self) {
-        self.interface_threads = self
-            .config
-            .interfaces
-            .iter()
-            .map(|ic| (ic.id.clone(), self.spawn_interface_thread(ic.clone())))
-            .collect();
+        let mut interface_threads = HashMap::new();
+        for ic in self.config.interfaces.clone() {
+            let identity = self.interface_identity(&ic).unwrap_or_else(|err| {
+                error!(
+                    "conductor: Could not load key for interface {}: {}",
+                    ic.id, err
+                );
+                None
+            });
+            interface_threads.insert(ic.id.clone(), self.spawn_interface_thread(ic, identity));
+        }
+        self.interface_threads = interface_threads;
 
         self.start_signal_multiplexer();
+        self.start_metrics_endpoint();
     }
 
     pub fn stop_all_interfaces(&mut self) {
@@ -569,9 +772,424 @@ impl Conductor {
         Ok(response)
     }
 
+    /// Get the peers that reported holding an entry authored by the instance's agent
+    pub fn instance_entry_holders(
+        &self,
+        id: &String,
+        hash: HashString,
+    ) -> Result<EntryHoldersResponse, HolochainInstanceError> {
+        let instance = self.instances.get(id)?.read().unwrap();
+        let agent_state = instance.state()?.agent();
+        Ok(EntryHoldersResponse {
+            holders: agent_state.entry_holders(&hash),
+            holder_count: agent_state.entry_holder_count(&hash),
+        })
+    }
+
+    /// Get the top header address, chain length and last commit time of an instance's source chain
+    pub fn instance_chain_head(
+        &self,
+        id: &String,
+    ) -> Result<ChainHeadResponse, HolochainInstanceError> {
+        let instance = self.instances.get(id)?.read().unwrap();
+        let agent_state = instance.state()?.agent();
+        Ok(ChainHeadResponse::from(&*agent_state))
+    }
+
+    /// Get the bytes used by an instance's chain, CAS, EAV and caches, broken down by entry type
+    pub fn instance_storage_usage(
+        &self,
+        id: &String,
+    ) -> Result<StorageUsage, HolochainInstanceError> {
+        let instance = self.instances.get(id)?.read().unwrap();
+        Ok(StorageUsage::new(&instance.context()?)?)
+    }
+
+    /// Remove content that is no longer referenced from an instance's storage and reclaim the
+    /// disk space it took up
+    pub fn instance_compact_storage(
+        &self,
+        id: &String,
+    ) -> Result<CompactionReport, HolochainInstanceError> {
+        let context = self.instances.get(id)?.read().unwrap().context()?;
+        Ok(compact_storage(&context)?)
+    }
+
+    /// Check all content in an instance's storage against its address, quarantine corrupted
+    /// content and fetch it again from the network if the instance holds it for the DHT
+    pub fn instance_scrub_storage(
+        &self,
+        id: &String,
+    ) -> Result<ScrubReport, HolochainInstanceError> {
+        let context = self.instances.get(id)?.read().unwrap().context()?;
+        Ok(scrub_and_restore(&context)?)
+    }
+
+    /// Move an instance's old source chain content and unreferenced content to the object store
+    /// configured as its cold storage
+    pub fn instance_archive_cold_content(
+        &self,
+        id: &String,
+    ) -> Result<ArchivalReport, HolochainInstanceError> {
+        let context = self.instances.get(id)?.read().unwrap().context()?;
+        let cold_storage = self.cold_storages.get(id).ok_or_else(|| {
+            HolochainError::ConfigError(format!("Instance {} has no cold storage", id))
+        })?;
+        let keep_chain_headers = self
+            .config
+            .instance_by_id(id)
+            .and_then(|instance| instance.cold_storage)
+            .map(|cold_storage| cold_storage.keep_chain_headers)
+            .unwrap_or_default();
+        Ok(archive_cold_content(
+            &context,
+            cold_storage,
+            keep_chain_headers,
+        )?)
+    }
+
+    /// Write an instance's whole state, its source chain, CAS and EAV contents, holding map
+    /// and pending validations, into a single archive file at the given path
+    pub fn instance_export_archive(
+        &self,
+        id: &String,
+        path: &PathBuf,
+    ) -> Result<(), HolochainInstanceError> {
+        let context = self.instances.get(id)?.read().unwrap().context()?;
+        let archive = InstanceArchive::export(&context)?;
+        let json = serde_json::to_string(&archive).map_err(HolochainError::from)?;
+        fs::write(path, json).map_err(HolochainError::from)?;
+        Ok(())
+    }
+
+    /// Restore an instance from an archive written by instance_export_archive(). The archive
+    /// has to be of the same agent and DNA. The instance gets stopped, its storages filled
+    /// with the archive's contents and then it gets loaded from them again. It is started
+    /// again if it was running before.
+    pub fn instance_import_archive(
+        &mut self,
+        id: &String,
+        path: &PathBuf,
+    ) -> Result<(), HolochainInstanceError> {
+        let json = fs::read_to_string(path).map_err(HolochainError::from)?;
+        let archive: InstanceArchive = serde_json::from_str(&json).map_err(HolochainError::from)?;
+
+        let instance = self.instances.get(id)?.clone();
+        let mut instance = instance.write().unwrap();
+        let was_active = instance.active();
+        let context = instance.context()?;
+        let dna =
+            instance
+                .state()?
+                .nucleus()
+                .dna()
+                .ok_or(HolochainInstanceError::InternalFailure(
+                    HolochainError::DnaMissing,
+                ))?;
+        if archive.dna != Some(dna.address()) {
+            return Err(HolochainInstanceError::InternalFailure(
+                HolochainError::ErrorGeneric(format!(
+                    "Instance archive was exported with a different DNA than instance {} runs",
+                    id
+                )),
+            ));
+        }
+
+        notify(format!("Importing archive into instance \"{}\"...", id));
+        // Stops the action loop, so nothing overwrites the imported snapshots
+        instance.kill();
+        archive.import(&context)?;
+        let mut context = (*context).clone();
+        context.reset_instance();
+        *instance = Holochain::load_with_dna(Arc::new(context), dna)?;
+        if was_active {
+            instance.start()?;
+        }
+        Ok(())
+    }
+
+    /// Move an instance to a different storage backend. The instance gets stopped, its data
+    /// copied into the new storage and verified there. Then the instance's config is switched
+    /// to the new storage and the instance loaded from it, and started again if it was running.
+    /// The old storage is left as it is.
+    pub fn instance_migrate_storage(
+        &mut self,
+        id: &String,
+        storage: StorageConfiguration,
+    ) -> Result<MigrationReport, HolochainInstanceError> {
+        let mut target_config = self.config.instance_by_id(id).ok_or_else(|| {
+            HolochainError::ConfigError(format!("Instance {} not found in config", id))
+        })?;
+        if target_config.storage == storage {
+            return Err(HolochainInstanceError::InternalFailure(
+                HolochainError::ConfigError(format!("Instance {} already uses this storage", id)),
+            ));
+        }
+        target_config.storage = storage.clone();
+        // Content archived to cold storage gets migrated into the new storage
+        target_config.cold_storage = None;
+
+        let instance = self.instances.get(id)?.clone();
+        let source = instance.read().unwrap().context()?;
+        let was_active = instance.read().unwrap().active();
+        let target = {
+            let context_builder = ContextBuilder::new()
+                .with_agent(source.agent_id.clone())
+                .with_instance_name(id)
+                .with_conductor_api(IoHandler::new());
+            self.with_instance_storage(context_builder, &target_config)
+                .map_err(HolochainError::ConfigError)?
+                .spawn()
+        };
+
+        notify(format!("Migrating storage of instance \"{}\"...", id));
+        // Stops the action loop, so nothing changes while we copy
+        instance.write().unwrap().kill();
+        let report = migrate_storage(&source, &Arc::new(target))?;
+
+        if let Some(instance_config) = self
+            .config
+            .instances
+            .iter_mut()
+            .find(|instance_config| instance_config.id == *id)
+        {
+            instance_config.storage = storage;
+        }
+        self.save_config()?;
+        let migrated = self
+            .instantiate_from_config(id)
+            .map_err(HolochainError::ErrorGeneric)?;
+        *instance.write().unwrap() = migrated;
+        if was_active {
+            instance.write().unwrap().start()?;
+        }
+        notify(format!(
+            "Migrated {} contents and {} EAVIs of instance \"{}\"",
+            report.contents, report.eavis, id
+        ));
+        Ok(report)
+    }
+
+    /// Switch an instance to another configured DNA, e.g. a new version of its app, in one
+    /// step: the instance is stopped, so zome calls get refused, and loaded again with the new
+    /// DNA, which migrates its source chain. Held entries whose validation rules changed are
+    /// validated again and dropped if they are invalid now. Then the instance is started again
+    /// if it was running. Each step is signaled to admin interfaces. If the instance can't be
+    /// loaded with the new DNA it is restored with its previous one.
+    pub fn instance_update_dna(
+        &mut self,
+        id: &String,
+        dna_id: &String,
+    ) -> Result<DnaUpdateReport, HolochainInstanceError> {
+        let instance_config = self.config.instance_by_id(id).ok_or_else(|| {
+            HolochainError::ConfigError(format!("Instance {} not found in config", id))
+        })?;
+        if instance_config.dna == *dna_id {
+            return Err(HolochainInstanceError::InternalFailure(
+                HolochainError::ConfigError(format!("Instance {} already runs DNA {}", id, dna_id)),
+            ));
+        }
+        let new_dna = self.load_configured_dna(dna_id)?;
+
+        let instance = self.instances.get(id)?.clone();
+        let was_active = instance.read().unwrap().active();
+        let context = instance.read().unwrap().context()?;
+        let old_dna = instance.read().unwrap().state()?.nucleus().dna().ok_or(
+            HolochainInstanceError::InternalFailure(HolochainError::DnaMissing),
+        )?;
+        let changed = changed_entry_types(&old_dna, &new_dna);
+
+        notify(format!(
+            "Updating instance \"{}\" to DNA \"{}\"...",
+            id, dna_id
+        ));
+        self.signal_dna_update(id, DnaUpdateProgress::Paused);
+        // Stops the action loop, so the new one is the only one writing the instance's state
+        instance.write().unwrap().kill();
+        let mut context = (*context).clone();
+        context.reset_instance();
+        let updated = match Holochain::load_with_dna(Arc::new(context.clone()), new_dna.clone()) {
+            Ok(updated) => updated,
+            Err(error) => {
+                *instance.write().unwrap() = Holochain::load_with_dna(Arc::new(context), old_dna)?;
+                if was_active {
+                    instance.write().unwrap().start()?;
+                }
+                self.signal_dna_update(
+                    id,
+                    DnaUpdateProgress::Failed {
+                        error: error.to_string(),
+                    },
+                );
+                return Err(error.into());
+            }
+        };
+        *instance.write().unwrap() = updated;
+        if let Some(instance_config) = self
+            .config
+            .instances
+            .iter_mut()
+            .find(|instance_config| instance_config.id == *id)
+        {
+            instance_config.dna = dna_id.clone();
+        }
+        self.save_config()?;
+        self.signal_dna_update(
+            id,
+            DnaUpdateProgress::ChainMigrated {
+                old_dna: old_dna.address(),
+                new_dna: new_dna.address(),
+            },
+        );
+
+        let context = instance.read().unwrap().context()?;
+        let revalidation =
+            context.block_on(revalidate_held_entries_workflow(&changed, &context))?;
+        self.signal_dna_update(
+            id,
+            DnaUpdateProgress::Revalidated {
+                checked: revalidation.checked,
+                invalid: revalidation.invalid.len(),
+            },
+        );
+
+        if was_active {
+            instance.write().unwrap().start()?;
+        }
+        self.signal_dna_update(id, DnaUpdateProgress::Resumed);
+        notify(format!(
+            "Updated instance \"{}\" to DNA \"{}\", {} held entries invalid now",
+            id,
+            dna_id,
+            revalidation.invalid.len()
+        ));
+        let mut changed_entry_types: Vec<EntryType> = changed.into_iter().collect();
+        changed_entry_types.sort();
+        Ok(DnaUpdateReport {
+            old_dna: old_dna.address(),
+            new_dna: new_dna.address(),
+            changed_entry_types,
+            revalidation,
+        })
+    }
+
+    /// Loads the DNA with the given ID from its configured file, with the configured uuid
+    /// applied, and checks it against the configured hash if there is no uuid.
+    fn load_configured_dna(&mut self, dna_id: &String) -> Result<Dna, HolochainError> {
+        let dna_config = self.config.dna_by_id(dna_id).ok_or_else(|| {
+            HolochainError::ConfigError(format!("DNA {} not found in config", dna_id))
+        })?;
+        let mut dna = Arc::get_mut(&mut self.dna_loader).unwrap()(&PathBuf::from(&dna_config.file))
+            .map_err(|_| {
+                HolochainError::ConfigError(format!(
+                    "Could not load DNA file \"{}\"",
+                    dna_config.file
+                ))
+            })?;
+        match dna_config.uuid {
+            Some(uuid) => dna.uuid = uuid,
+            None => Conductor::check_dna_consistency(
+                &HashString::from(dna_config.hash),
+                &dna.address(),
+            )?,
+        }
+        Ok(dna)
+    }
+
+    fn signal_dna_update(&self, id: &String, progress: DnaUpdateProgress) {
+        self.signal_admin_interfaces(SignalWrapper::DnaUpdate {
+            instance_id: id.clone(),
+            progress,
+        });
+    }
+
+    pub(in crate::conductor) fn signal_admin_interfaces(&self, signal: SignalWrapper) {
+        let broadcasters = self.interface_broadcasters.read().unwrap();
+        for interface in self.config.interfaces.iter().filter(|i| i.admin) {
+            if let Some(broadcaster) = broadcasters.get(&interface.id) {
+                if let Err(error) = broadcaster.send(signal.clone()) {
+                    notify(error.to_string());
+                }
+            }
+        }
+    }
+
+    /// Get the calls made to an instance with capability tokens, optionally only those made
+    /// with the given token
+    pub fn instance_capability_audit_log(
+        &self,
+        id: &String,
+        token: Option<HashString>,
+    ) -> Result<Vec<CapabilityUseRecord>, HolochainInstanceError> {
+        let instance = self.instances.get(id)?.read().unwrap();
+        Ok(instance.state()?.agent().cap_audit_log(token.as_ref()))
+    }
+
+    /// Replace one of an instance's capability grants by one with a new token, revoking the
+    /// old token after the grace period. Returns the new token.
+    pub fn instance_rotate_capability_grant(
+        &self,
+        id: &String,
+        token: HashString,
+        grace_period: Duration,
+        notify_assignees: bool,
+    ) -> Result<HashString, HolochainInstanceError> {
+        let instance = self.instances.get(id)?.read().unwrap();
+        let context = instance.context()?;
+        Ok(context.block_on(rotate_grant_workflow(
+            &token,
+            grace_period,
+            notify_assignees,
+            &context,
+        ))?)
+    }
+
+    /// Block the given agent in all instances: their connections get dropped, their
+    /// traffic refused, their entries rejected in validation and their data hidden from queries.
+    /// The block gets saved to the config so that instances created or started later apply it too.
+    pub fn block_agent(&mut self, agent: &HashString) -> Result<(), HolochainError> {
+        if !self.config.blocked_agents.contains(&agent.to_string()) {
+            self.config.blocked_agents.push(agent.to_string());
+            self.save_config()?;
+        }
+        self.for_each_running_context(|context| block_agent(agent.clone(), context));
+        Ok(())
+    }
+
+    /// Lift a block added with block_agent() in all instances.
+    pub fn unblock_agent(&mut self, agent: &HashString) -> Result<(), HolochainError> {
+        if self.config.blocked_agents.contains(&agent.to_string()) {
+            self.config
+                .blocked_agents
+                .retain(|blocked| *blocked != agent.to_string());
+            self.save_config()?;
+        }
+        self.for_each_running_context(|context| unblock_agent(agent.clone(), context));
+        Ok(())
+    }
+
+    /// Applies the blocks of the config's `blocked_agents` to the given instance
+    fn block_configured_agents(blocked_agents: &[Base32], instance: &Holochain) {
+        if let Ok(context) = instance.context() {
+            for agent in blocked_agents {
+                block_agent(HashString::from(agent.clone()), &context);
+            }
+        }
+    }
+
+    fn for_each_running_context<F: Fn(&Arc<Context>)>(&self, f: F) {
+        for instance in self.instances.values() {
+            if let Ok(context) = instance.read().unwrap().context() {
+                f(&context);
+            }
+        }
+    }
+
     pub fn start_instance(&mut self, id: &String) -> Result<(), HolochainInstanceError> {
         let mut instance = self.instances.get(id)?.write().unwrap();
         notify(format!("Starting instance \"{}\"...", id));
+        // Instances that get started or stopped by hand start over with their restarts
+        self.restart_states.remove(id);
 
         // Get instance DNA so we can read out required bridge definitions:
         let dna =
@@ -608,15 +1226,36 @@ impl Conductor {
                 }
             }
         }
+        Self::block_configured_agents(&self.config.blocked_agents, &instance);
         instance.start()
     }
 
     pub fn stop_instance(&mut self, id: &String) -> Result<(), HolochainInstanceError> {
         let instance = self.instances.get(id)?;
         notify(format!("Stopping instance \"{}\"...", id));
+        self.restart_states.remove(id);
         instance.write().unwrap().stop()
     }
 
+    /// Quiesce a running instance, e.g. for a backup of its storage, without shutting it down:
+    /// new zome calls get refused and queued holding work is left alone, running calls and
+    /// holding workflows are waited for. The instance stays connected to the network.
+    pub fn pause_instance(
+        &self,
+        id: &String,
+        timeout: Duration,
+    ) -> Result<(), HolochainInstanceError> {
+        let instance = self.instances.get(id)?;
+        notify(format!("Pausing instance \"{}\"...", id));
+        instance.read().unwrap().pause(timeout)
+    }
+
+    pub fn resume_instance(&self, id: &String) -> Result<(), HolochainInstanceError> {
+        let instance = self.instances.get(id)?;
+        notify(format!("Resuming instance \"{}\"...", id));
+        instance.read().unwrap().resume()
+    }
+
     /// Starts all instances
     pub fn start_all_instances(&mut self) -> Result<(), HolochainInstanceError> {
         notify("Start all instances".to_string());
@@ -757,16 +1396,19 @@ impl Conductor {
                 backend_kind: P2pBackendKind::GhostEngineMemory,
                 backend_config: BackendConfig::Memory(config),
                 maybe_end_user_config: None,
+                fault_injection: None,
             },
             NetworkConfig::Lib3h(config) => P2pConfig {
                 backend_kind: P2pBackendKind::LIB3H,
                 backend_config: BackendConfig::Lib3h(config),
                 maybe_end_user_config: None,
+                fault_injection: None,
             },
             NetworkConfig::Sim2h(config) => P2pConfig {
                 backend_kind: P2pBackendKind::SIM2H,
                 backend_config: BackendConfig::Sim2h(config),
                 maybe_end_user_config: None,
+                fault_injection: None,
             },
         }
     }
@@ -840,11 +1482,12 @@ impl Conductor {
     ) -> Option<(
         ht::Tracer,
         Receiver<ht::FinishedSpan>,
-        ht::reporter::JaegerCompactReporter,
+        Box<dyn SpanReporter>,
     )> {
-        match self.config.tracing.clone().unwrap_or_default() {
+        let (span_tx, span_rx) = crossbeam_channel::unbounded();
+        let reporter: Box<dyn SpanReporter> = match self.config.tracing.clone().unwrap_or_default()
+        {
             TracingConfiguration::Jaeger(jaeger_config) => {
-                let (span_tx, span_rx) = crossbeam_channel::unbounded();
                 let service_name = format!("{}-{}", jaeger_config.service_name, id);
                 let mut reporter = ht::reporter::JaegerCompactReporter::new(&service_name).unwrap();
                 if let Some(s) = jaeger_config.socket_address {
@@ -854,20 +1497,29 @@ impl Conductor {
                         .set_agent_addr(addr)
                         .expect("Could not set Jaeger socket address");
                 }
-                Some((
-                    ht::Tracer::with_sender(ht::AllSampler, span_tx),
-                    span_rx,
-                    reporter,
-                ))
+                Box::new(reporter)
             }
-            TracingConfiguration::None => None,
-        }
+            TracingConfiguration::Otlp(otlp_config) => match OtlpReporter::new(otlp_config, id) {
+                Ok(reporter) => Box::new(reporter),
+                Err(e) => {
+                    warn!("Not tracing instance {}: {}", id, e);
+                    return None;
+                }
+            },
+            TracingConfiguration::None => return None,
+        };
+        Some((
+            ht::Tracer::with_sender(ht::AllSampler, span_tx),
+            span_rx,
+            reporter,
+        ))
     }
 
     /// Creates one specific Holochain instance from a given Configuration,
     /// id string and DnaLoader.
     pub fn instantiate_from_config(&mut self, id: &String) -> Result<Holochain, String> {
         self.config.check_consistency(&mut self.dna_loader)?;
+        let blocked_agents = self.config.blocked_agents.clone();
         self.config
             .instance_by_id(&id)
             .ok_or_else(|| String::from("Instance not found in config"))
@@ -880,6 +1532,7 @@ impl Conductor {
                 let agent_id = &instance_config.agent;
                 let agent_config = self.config.agent_by_id(agent_id).unwrap();
                 let agent_address = self.agent_config_to_id(&agent_config)?;
+                self.check_agent_key_not_revoked(&instance_name, &agent_config, &agent_address)?;
                 if agent_config.test_agent.unwrap_or_default() {
                     // Modify the config so that the public_address is correct.
                     // (The public_address is simply ignored for test_agents, as
@@ -889,7 +1542,25 @@ impl Conductor {
                     self.save_config()?;
                 }
 
-                context_builder = context_builder.with_agent(agent_address);
+                // The membrane proof rides along with the agent entry committed at genesis
+                context_builder = context_builder.with_agent(
+                    agent_address.with_membrane_proof(instance_config.membrane_proof.clone()),
+                );
+                let client_keys = agent_config
+                    .client_keys
+                    .clone()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(HashString::from)
+                    .collect();
+                context_builder = context_builder.with_client_keys(client_keys);
+                context_builder = context_builder
+                    .with_zome_call_rate_limits(instance_config.rate_limits.clone().unwrap_or_default())
+                    .with_slow_zome_call_config(instance_config.slow_zome_calls.clone().unwrap_or_default())
+                    .with_pending_validation_config(instance_config.pending_validation.clone().unwrap_or_default());
+                if let Some(size) = instance_config.validation_package_cache_size {
+                    context_builder = context_builder.with_validation_package_cache_size(size);
+                }
 
                 context_builder = context_builder.with_p2p_config(self.get_p2p_config());
 
@@ -907,34 +1578,13 @@ impl Conductor {
                     self.trace_reporters.write().unwrap().insert(instance_name.clone(), (span_rx, reporter));
                 }
 
-                // Storage:
-                match instance_config.storage {
-                    StorageConfiguration::File { path } => {
-                        context_builder =
-                            context_builder.with_file_storage(path).map_err(|hc_err| {
-                                format!("Error creating context: {}", hc_err.to_string())
-                            })?
-                    }
-                    StorageConfiguration::Memory => {
-                        context_builder = context_builder.with_memory_storage()
-                    }
-                    StorageConfiguration::Pickle { path } => {
-                        context_builder =
-                            context_builder
-                                .with_pickle_storage(path)
-                                .map_err(|hc_err| {
-                                    format!("Error creating context: {}", hc_err.to_string())
-                                })?
-                    }
-                    StorageConfiguration::Lmdb { path, initial_mmap_bytes } => {
-                        context_builder =
-                            context_builder
-                                .with_lmdb_storage(path, initial_mmap_bytes)
-                                .map_err(|hc_err| {
-                                    format!("Error creating context: {}", hc_err.to_string())
-                                })?
-                    }
-                }
+                // Instances of the same DNA share a single copy of the DNA entry
+                let dna_storage = self
+                    .shared_dna_storage(&instance_config.storage)
+                    .map_err(|hc_err| format!("Error creating DNA storage: {}", hc_err))?;
+                context_builder = context_builder.with_dna_storage(dna_storage);
+
+                context_builder = self.with_instance_storage(context_builder, &instance_config)?;
 
                 // Conductor API
                 let api = self.build_conductor_api(instance_config.id)?;
@@ -945,6 +1595,7 @@ impl Conductor {
                 }
 
                 context_builder = context_builder.with_instance_name(&instance_name);
+                log_router().route_instance(&instance_name, instance_config.logger.as_ref())?;
 
                 if let Some(metric_publisher_config) = &self.config.metric_publisher {
                     debug!("Setting metric publisher in context_builder to: {:?}", metric_publisher_config);
@@ -954,6 +1605,24 @@ impl Conductor {
                 // Spawn context
                 let context = context_builder.spawn();
 
+                if let Some(ref path) = instance_config.record_actions {
+                    let header = RecordingHeader {
+                        instance_name: instance_name.clone(),
+                        agent_id: context.agent_id.clone(),
+                    };
+                    context
+                        .action_recorder
+                        .start(Path::new(path), header)
+                        .map_err(|e| format!("Could not record actions to {}: {}", path, e))?;
+                }
+
+                if let Some(ref path) = instance_config.validation_coverage {
+                    context
+                        .validation_coverage
+                        .start(Path::new(path))
+                        .map_err(|e| format!("Could not record validation coverage: {}", e))?;
+                }
+
                 // Get DNA
 
                 // self.config.dnas.iter_mut().fing(|dna_config| dna_config.id == instance_config.dna)
@@ -1024,12 +1693,13 @@ impl Conductor {
 
                 let mut context_clone = context.clone();
                 let context = Arc::new(context);
-                Holochain::load(context)
+                Holochain::load_with_dna(context, dna.clone())
                     .and_then(|hc| {
                        notify(format!(
                             "Successfully loaded instance {} from storage",
                             id.clone()
                         ));
+                        Conductor::count_stored_content_against_quota(&hc);
                         Ok(hc)
                     })
                     .or_else(|loading_error| {
@@ -1051,6 +1721,29 @@ impl Conductor {
                         .map_err(|hc_err| hc_err.to_string())
                     })
             })
+            .map(|instance| {
+                Self::block_configured_agents(&blocked_agents, &instance);
+                instance
+            })
+    }
+
+    /// The storage limit of an instance counts what it writes, so what it stored before it
+    /// got loaded needs to be counted once up front.
+    fn count_stored_content_against_quota(hc: &Holochain) {
+        if let Ok(context) = hc.context() {
+            if let Some(quota) = context.storage_quota.as_ref() {
+                match StorageUsage::new(&context) {
+                    Ok(usage) => {
+                        quota.set_used_bytes(usage.chain.total.bytes + usage.cas.total.bytes)
+                    }
+                    Err(error) => log_error!(
+                        context,
+                        "Could not count stored content against the storage limit: {}",
+                        error
+                    ),
+                }
+            }
+        }
     }
 
     pub fn build_conductor_api(
@@ -1091,10 +1784,30 @@ impl Conductor {
                     .clone()
                     .expect("holo_remote_key needs decryption_service_uri set"),
             );
-        } else {
-            api_builder = api_builder.with_agent_signature_callback(
-                self.get_keybundle_for_agent(&instance_config.agent)?,
+        } else if let Some(KeyBackendConfiguration::Pkcs11 {
+            module,
+            key_id,
+            slot,
+        }) = agent_config.key_backend.clone()
+        {
+            // The key never leaves the device, so there are no encryption keys and no
+            // keystore functions for this agent.
+            let backend = Pkcs11Backend::new(
+                module,
+                key_id,
+                slot,
+                agent_config.public_address.clone(),
+                self.passphrase_manager.clone(),
             );
+            backend.check_key()?;
+            api_builder = api_builder.with_agent_signing_backend(Arc::new(backend));
+        } else {
+            // Sign through the keystore so that locking it takes the key out of memory:
+            let public_address = self.agent_config_to_id(&agent_config)?.pub_sign_key;
+            api_builder = api_builder.with_agent_signing_backend(Arc::new(KeystoreBackend::new(
+                self.get_keystore_for_agent(&instance_config.agent)?,
+                public_address,
+            )));
 
             api_builder = api_builder.with_agent_encryption_callback(
                 self.get_keybundle_for_agent(&instance_config.agent)?,
@@ -1106,6 +1819,14 @@ impl Conductor {
             api_builder = api_builder.with_agent_keystore_functions(keystore);
         }
 
+        // DPKI registration of the agent key, checked in genesis:
+        if let Some(dpki_instance) = self.dpki_instance_for(&instance_id)? {
+            api_builder = api_builder.with_dpki_key_registration(
+                dpki_instance,
+                self.agent_config_to_id(&agent_config)?.pub_sign_key,
+            );
+        }
+
         // Bridges:
         let id = instance_config.id;
         for bridge in self.config.bridge_dependencies(id.clone()) {
@@ -1139,6 +1860,8 @@ impl Conductor {
             // Holo closed-alpha hack:
             // !!!!!!!!!!!!!!!!!!!!!!!
             AgentId::new(&agent_config.name, agent_config.public_address.clone())
+        } else if let Some(KeyBackendConfiguration::Pkcs11 { .. }) = agent_config.key_backend {
+            AgentId::new(&agent_config.name, agent_config.public_address.clone())
         } else {
             let keybundle_arc = self.get_keybundle_for_agent(&agent_config.id)?;
             let keybundle = keybundle_arc.lock().unwrap();
@@ -1146,6 +1869,64 @@ impl Conductor {
         })
     }
 
+    /// Refuses agents whose key is listed in the config's `revoked_agent_keys` so that no
+    /// instance gets created for (or keeps running with) a key that is known to be compromised.
+    /// With DPKI configured, the key also has to be registered there, as DPKI drops the
+    /// registration of revoked keys. The DPKI instance itself is exempt.
+    /// The check that the key actually signs for the agent's address happens in genesis.
+    fn check_agent_key_not_revoked(
+        &self,
+        instance_id: &str,
+        agent_config: &AgentConfiguration,
+        agent_id: &AgentId,
+    ) -> Result<(), HolochainError> {
+        if self
+            .config
+            .revoked_agent_keys
+            .contains(&agent_id.pub_sign_key)
+        {
+            return Err(HolochainError::ConfigError(format!(
+                "Key {} of agent '{}' has been revoked",
+                agent_id.pub_sign_key, agent_config.id
+            )));
+        }
+        if let Some(dpki_instance) = self.dpki_instance_for(instance_id)? {
+            let registered = dpki_instance
+                .write()
+                .unwrap()
+                .dpki_is_key_registered(agent_id.pub_sign_key.clone())?;
+            if !registered {
+                return Err(HolochainError::ConfigError(format!(
+                    "Key {} of agent '{}' is not registered in DPKI",
+                    agent_id.pub_sign_key, agent_config.id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The DPKI instance that manages the agent key of the given instance, None if DPKI isn't
+    /// configured or the given instance is the DPKI instance
+    fn dpki_instance_for(
+        &self,
+        instance_id: &str,
+    ) -> Result<Option<Arc<RwLock<Holochain>>>, HolochainError> {
+        match self.dpki_instance_id() {
+            Some(ref dpki_instance_id) if dpki_instance_id != instance_id => self
+                .instances
+                .get(dpki_instance_id)
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| {
+                    HolochainError::ConfigError(format!(
+                        "DPKI instance {} is not running, can't check agent keys",
+                        dpki_instance_id
+                    ))
+                }),
+            _ => Ok(None),
+        }
+    }
+
     /// Checks if the key for the given agent can be loaded or was already loaded.
     /// Will trigger loading if key is not loaded yet.
     /// Meant to be used in conductor executable to first try to load all keys (which will trigger
@@ -1262,13 +2043,46 @@ impl Conductor {
                 return Err("agent is holo_remote, no keystore".to_string());
             }
 
-            let mut keystore = match agent_config.test_agent {
-                Some(true) => test_keystore(&agent_config.name),
+            let mut keystore = match (
+                agent_config.test_agent,
+                agent_config.seed_file.clone(),
+                agent_config.key_backend.clone(),
+            ) {
+                (Some(true), _, _) => test_keystore(&agent_config.name),
+                (_, _, Some(KeyBackendConfiguration::Pkcs11 { .. })) => {
+                    return Err("agent key lives on a PKCS#11 device, no keystore".to_string());
+                }
+                (_, _, Some(KeyBackendConfiguration::OsKeychain { service, account })) => {
+                    let (keystore, _) =
+                        keystore_from_os_keychain(&service, &account, self.hash_config.clone())
+                            .map_err(|err| {
+                                HolochainError::ConfigError(format!(
+                                    "Could not load agent seed from OS keychain: {}",
+                                    err
+                                ))
+                            })?;
+                    keystore
+                }
+                (_, Some(seed_file), _) => {
+                    let (keystore, _) = Keystore::new_from_seed_file(
+                        PathBuf::from(seed_file.clone()),
+                        self.hash_config.clone(),
+                    )
+                    .map_err(|err| {
+                        HolochainError::ConfigError(format!(
+                            "Could not create keystore from seed file \"{}\": {}",
+                            seed_file, err,
+                        ))
+                    })?;
+                    keystore
+                }
                 _ => {
                     let keystore_file_path = PathBuf::from(agent_config.keystore_file.clone());
+                    let passphrase_manager =
+                        self.passphrase_manager_for_tenant(agent_config.tenant.as_ref());
                     let keystore = Arc::get_mut(&mut self.key_loader).unwrap()(
                         &keystore_file_path,
-                        self.passphrase_manager.clone(),
+                        passphrase_manager,
                         self.hash_config.clone(),
                     )
                     .map_err(|_| {
@@ -1320,12 +2134,49 @@ impl Conductor {
         Ok(Arc::new(Mutex::new(keybundle)))
     }
 
+    /// Locks the keystore: forgets the passphrase and drops all decrypted agent keys from
+    /// memory. Signing fails with [HolochainError::KeystoreLocked] until [unlock_keystore]
+    /// gets called.
+    pub fn lock_keystore(&mut self) {
+        self.passphrase_manager.lock();
+        for keystore in self.agent_keys.values() {
+            keystore.lock().unwrap().lock();
+        }
+    }
+
+    /// Unlocks the keystore with the given passphrase for the configured session duration.
+    /// Fails and stays locked if the passphrase can't decrypt the agent keys.
+    pub fn unlock_keystore(&mut self, passphrase: String) -> Result<(), HolochainError> {
+        self.passphrase_manager
+            .unlock(SecBuf::with_insecure_from_string(passphrase));
+        let result = self
+            .agent_keys
+            .values()
+            .map(|keystore| {
+                keystore
+                    .lock()
+                    .unwrap()
+                    .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+                    .map(|_| ())
+            })
+            .collect::<Result<Vec<()>, HolochainError>>();
+        if let Err(err) = result {
+            self.lock_keystore();
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Could not unlock keystore: {}",
+                err
+            )));
+        }
+        Ok(())
+    }
+
     fn start_interface(&mut self, config: &InterfaceConfiguration) -> Result<(), String> {
         if self.interface_threads.contains_key(&config.id) {
             return Err(format!("Interface {} already started!", config.id));
         }
         notify(format!("Starting interface '{}'.", config.id));
-        let handle = self.spawn_interface_thread(config.clone());
+        let identity = self.interface_identity(config)?;
+        let handle = self.spawn_interface_thread(config.clone(), identity);
         self.interface_threads.insert(config.id.clone(), handle);
         Ok(())
     }
@@ -1368,7 +2219,9 @@ impl Conductor {
     }
 
     fn make_interface_handler(&self, interface_config: &InterfaceConfiguration) -> IoHandler {
-        let mut conductor_api_builder = ConductorApiBuilder::new();
+        let mut conductor_api_builder = ConductorApiBuilder::new()
+            .with_signed_calls_required(interface_config.require_signed_calls)
+            .with_signal_history(self.signal_history.clone());
         for instance_ref_config in interface_config.instances.iter() {
             let id = &instance_ref_config.id;
             let name = instance_ref_config.alias.as_ref().unwrap_or(id).clone();
@@ -1387,7 +2240,9 @@ impl Conductor {
                 .with_named_instance_config(name.clone(), instance_config)
         }
 
-        if interface_config.admin {
+        if let (true, Some(tenant)) = (interface_config.admin, interface_config.tenant.as_ref()) {
+            conductor_api_builder = conductor_api_builder.with_tenant_admin_functions(tenant);
+        } else if interface_config.admin {
             conductor_api_builder = conductor_api_builder
                 .with_admin_dna_functions()
                 .with_admin_ui_functions()
@@ -1398,20 +2253,47 @@ impl Conductor {
         conductor_api_builder.spawn()
     }
 
-    fn spawn_interface_thread(&self, interface_config: InterfaceConfiguration) -> Sender<()> {
+    /// The key a remote admin interface identifies this conductor with.
+    /// Other interfaces don't need one.
+    fn interface_identity(
+        &mut self,
+        interface_config: &InterfaceConfiguration,
+    ) -> Result<Option<Arc<dyn SigningBackend>>, String> {
+        if let InterfaceDriver::RemoteAdmin { ref agent, .. } = interface_config.driver {
+            let keystore = self.get_keystore_for_agent(agent)?;
+            let agent_config = self
+                .config
+                .agent_by_id(agent)
+                .ok_or_else(|| format!("Agent '{}' not found", agent))?;
+            let public_address = self.agent_config_to_id(&agent_config)?.pub_sign_key;
+            Ok(Some(Arc::new(KeystoreBackend::new(
+                keystore,
+                public_address,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn spawn_interface_thread(
+        &self,
+        interface_config: InterfaceConfiguration,
+        identity: Option<Arc<dyn SigningBackend>>,
+    ) -> Sender<()> {
         let dispatcher = self.make_interface_handler(&interface_config);
         // The "kill switch" is the channel which allows the interface to be stopped from outside its thread
         let (kill_switch_tx, kill_switch_rx) = unbounded();
 
-        let (broadcaster, _handle) = run_interface(&interface_config, dispatcher, kill_switch_rx)
-            .map_err(|error| {
-                error!(
-                    "conductor: Error running interface, even after {} attempts '{}': {}",
-                    INTERFACE_CONNECT_ATTEMPTS_MAX, interface_config.id, error
-                );
-                error
-            })
-            .unwrap();
+        let (broadcaster, _handle) =
+            run_interface(&interface_config, dispatcher, kill_switch_rx, identity)
+                .map_err(|error| {
+                    error!(
+                        "conductor: Error running interface, even after {} attempts '{}': {}",
+                        INTERFACE_CONNECT_ATTEMPTS_MAX, interface_config.id, error
+                    );
+                    error
+                })
+                .unwrap();
 
         debug!("conductor: adding broadcaster to map {:?}", broadcaster);
 
@@ -1429,6 +2311,215 @@ impl Conductor {
         self.config.persistence_dir.join("dna")
     }
 
+    pub fn dna_storage_dir_path(&self) -> PathBuf {
+        self.config.persistence_dir.join("dna_storage")
+    }
+
+    /// Sets up the chain, DHT and EAV storages of an instance as configured, with all storage
+    /// wrappers it asks for
+    fn with_instance_storage(
+        &mut self,
+        mut context_builder: ContextBuilder,
+        instance_config: &InstanceConfiguration,
+    ) -> Result<ContextBuilder, String> {
+        let storage_key = if instance_config.encrypt_storage {
+            Some(
+                self.storage_key(&instance_config.storage)
+                    .map_err(|hc_err| {
+                        format!("Error deriving storage encryption key: {}", hc_err)
+                    })?,
+            )
+        } else {
+            None
+        };
+        match instance_config.storage.clone() {
+            StorageConfiguration::File { path } => {
+                context_builder = context_builder
+                    .with_file_storage(path)
+                    .map_err(|hc_err| format!("Error creating context: {}", hc_err.to_string()))?
+            }
+            StorageConfiguration::Memory => context_builder = context_builder.with_memory_storage(),
+            StorageConfiguration::Pickle { path } => {
+                context_builder = context_builder
+                    .with_pickle_storage(path)
+                    .map_err(|hc_err| format!("Error creating context: {}", hc_err.to_string()))?
+            }
+            StorageConfiguration::Lmdb {
+                path,
+                initial_mmap_bytes,
+            } => {
+                context_builder = context_builder
+                    .with_lmdb_storage(path, initial_mmap_bytes)
+                    .map_err(|hc_err| format!("Error creating context: {}", hc_err.to_string()))?
+            }
+            StorageConfiguration::Sqlite { path, indexing } => {
+                context_builder = context_builder
+                    .with_sqlite_storage(path, &indexing)
+                    .map_err(|hc_err| format!("Error creating context: {}", hc_err.to_string()))?
+            }
+            StorageConfiguration::Rocksdb {
+                path,
+                options,
+                indexing,
+            } => {
+                context_builder = context_builder
+                    .with_rocksdb_storage(path, &options, &indexing)
+                    .map_err(|hc_err| format!("Error creating context: {}", hc_err.to_string()))?
+            }
+            StorageConfiguration::Custom {
+                backend,
+                path,
+                options,
+            } => {
+                let factory = self
+                    .storage_backends
+                    .get(&backend)
+                    .ok_or_else(|| format!("Storage backend \"{}\" is not registered", backend))?;
+                let storage = factory(&StorageBackendParams {
+                    instance_id: instance_config.id.clone(),
+                    path,
+                    options,
+                })
+                .map_err(|hc_err| format!("Error creating context: {}", hc_err.to_string()))?;
+                context_builder = context_builder.with_custom_storage(storage)
+            }
+        }
+
+        // Underneath encryption and compression, so archived content is stored like
+        // local content
+        if let Some(cold_storage_config) = instance_config.cold_storage.clone() {
+            let store = S3ObjectStore::new(&cold_storage_config)
+                .map_err(|hc_err| format!("Error creating cold storage: {}", hc_err))?;
+            context_builder = context_builder
+                .with_cold_storage(Arc::new(store), cold_storage_config.prefix)
+                .map_err(|hc_err| format!("Error creating cold storage: {}", hc_err))?;
+            if let Some(cold_storage) = context_builder.cold_storage() {
+                self.cold_storages
+                    .insert(instance_config.id.clone(), cold_storage);
+            }
+        }
+
+        // Encrypted content doesn't compress, so encryption needs to wrap the storage first
+        if let Some(storage_key) = storage_key {
+            context_builder = context_builder.with_storage_encryption(storage_key);
+        }
+
+        if let Some(compression) = instance_config.compression.clone() {
+            context_builder = context_builder.with_storage_compression(compression);
+        }
+
+        // Counts content as the instance writes it, like storage usage reports it
+        if let Some(resource_limits) = instance_config.resource_limits.clone() {
+            context_builder = context_builder.with_resource_limits(resource_limits);
+        }
+
+        // Outermost, so the memory tier holds content as it is read
+        if let Some(hot_tier) = instance_config.hot_tier.clone() {
+            context_builder = context_builder.with_hot_tier(hot_tier);
+        }
+        Ok(context_builder)
+    }
+
+    /// Content addressable storage for DNA entries shared by the instances of this conductor,
+    /// so that every DNA entry is kept only once no matter how many instances run it.
+    /// Persisted instances share a storage of their own backend, kept in a directory named
+    /// after the backend in the DNA storage dir. In-memory instances share an in-memory storage.
+    fn shared_dna_storage(
+        &mut self,
+        storage: &StorageConfiguration,
+    ) -> Result<Arc<RwLock<dyn ContentAddressableStorage>>, HolochainError> {
+        let backend = match storage {
+            StorageConfiguration::Memory => return Ok(self.memory_dna_storage.clone()),
+            StorageConfiguration::File { .. } => String::from("file"),
+            StorageConfiguration::Pickle { .. } => String::from("pickle"),
+            StorageConfiguration::Lmdb { .. } => String::from("lmdb"),
+            StorageConfiguration::Sqlite { .. } => String::from("sqlite"),
+            StorageConfiguration::Rocksdb { .. } => String::from("rocksdb"),
+            StorageConfiguration::Custom { backend, .. } => format!("custom_{}", backend),
+        };
+        if let Some(dna_storage) = self.dna_storages.get(&backend) {
+            return Ok(dna_storage.clone());
+        }
+        let path = self.dna_storage_dir_path().join(&backend);
+        fs::create_dir_all(&path)?;
+        let dna_storage: Arc<RwLock<dyn ContentAddressableStorage>> = match storage {
+            StorageConfiguration::Memory => Arc::new(RwLock::new(MemoryStorage::new())),
+            StorageConfiguration::File { .. } => {
+                Arc::new(RwLock::new(FilesystemStorage::new(&path)?))
+            }
+            StorageConfiguration::Pickle { .. } => Arc::new(RwLock::new(PickleStorage::new(&path))),
+            StorageConfiguration::Lmdb {
+                initial_mmap_bytes, ..
+            } => Arc::new(RwLock::new(LmdbStorage::new(&path, *initial_mmap_bytes))),
+            StorageConfiguration::Sqlite { .. } => {
+                Arc::new(RwLock::new(SqliteStorage::new(path.join("cas.sqlite"))?))
+            }
+            StorageConfiguration::Rocksdb { options, .. } => {
+                Arc::new(RwLock::new(RocksDbStorage::new(&path, options)?))
+            }
+            StorageConfiguration::Custom {
+                backend, options, ..
+            } => {
+                let factory = self.storage_backends.get(backend).ok_or_else(|| {
+                    HolochainError::ConfigError(format!(
+                        "Storage backend \"{}\" is not registered",
+                        backend
+                    ))
+                })?;
+                factory(&StorageBackendParams {
+                    instance_id: String::from("dna_storage"),
+                    path: Some(path.to_string_lossy().to_string()),
+                    options: options.clone(),
+                })?
+                .cas
+            }
+        };
+        self.dna_storages.insert(backend, dna_storage.clone());
+        Ok(dna_storage)
+    }
+
+    /// Derives the key for encrypting an instance storage from the conductor passphrase.
+    /// Persistent storages keep the salt of the key next to their data, in-memory storages
+    /// get a new random salt since their data doesn't outlive the conductor.
+    fn storage_key(&self, storage: &StorageConfiguration) -> Result<StorageKey, HolochainError> {
+        let path = match storage {
+            StorageConfiguration::Memory => None,
+            StorageConfiguration::File { path }
+            | StorageConfiguration::Pickle { path }
+            | StorageConfiguration::Lmdb { path, .. }
+            | StorageConfiguration::Sqlite { path, .. }
+            | StorageConfiguration::Rocksdb { path, .. } => Some(PathBuf::from(path)),
+            // Custom backends may well be persistent, so without a path for the salt their
+            // data couldn't be decrypted after a restart
+            StorageConfiguration::Custom { path, backend, .. } => match path {
+                Some(path) => Some(PathBuf::from(path)),
+                None => {
+                    return Err(HolochainError::ConfigError(format!(
+                        "Encrypting storage of custom backend \"{}\" needs a path for the salt",
+                        backend
+                    )))
+                }
+            },
+        };
+        let mut passphrase = self.passphrase_manager.get_passphrase()?;
+        match path {
+            Some(path) => {
+                fs::create_dir_all(&path)?;
+                StorageKey::derive(
+                    passphrase,
+                    &path.join("storage_salt"),
+                    self.hash_config.clone(),
+                )
+            }
+            None => {
+                let mut salt = SecBuf::with_insecure(SALTBYTES);
+                salt.randomize();
+                let salt = salt.read_lock().to_vec();
+                StorageKey::derive_with_salt(&mut passphrase, &salt, self.hash_config.clone())
+            }
+        }
+    }
+
     pub fn config_path(&self) -> PathBuf {
         self.config.persistence_dir.join("conductor-config.toml")
     }
@@ -1567,8 +2658,13 @@ fn run_interface(
     interface_config: &InterfaceConfiguration,
     handler: IoHandler,
     kill_switch: Receiver<()>,
+    identity: Option<Arc<dyn SigningBackend>>,
 ) -> Result<(Broadcaster, thread::JoinHandle<()>), String> {
-    use crate::interface_impls::{http::HttpInterface, websocket::WebsocketInterface};
+    use crate::interface_impls::{
+        graphql::GraphqlInterface, grpc::GrpcInterface, http::HttpInterface,
+        remote_admin::RemoteAdminInterface, security::InterfaceSecurity,
+        websocket::WebsocketInterface,
+    };
 
     match interface_config.driver {
         InterfaceDriver::Websocket { port } => {
@@ -1577,7 +2673,11 @@ fn run_interface(
             } else {
                 port
             };
-            let mut interface = WebsocketInterface::new(port);
+            let security =
+                InterfaceSecurity::from_config(&interface_config.auth, &interface_config.tls)?;
+            let mut interface = WebsocketInterface::new(port)
+                .filter_signals(interface_config.filter_signals)
+                .with_security(security);
             let r = interface.run(handler, kill_switch);
             let addr = interface
                 .bound_address()
@@ -1594,7 +2694,9 @@ fn run_interface(
             } else {
                 port
             };
-            let mut interface = HttpInterface::new(port);
+            let security =
+                InterfaceSecurity::from_config(&interface_config.auth, &interface_config.tls)?;
+            let mut interface = HttpInterface::new(port).with_security(security);
             let r = interface.run(handler, kill_switch);
             let addr = interface
                 .bound_address()
@@ -1605,6 +2707,66 @@ fn run_interface(
             );
             r
         }
+        InterfaceDriver::Graphql { port } => {
+            let port = if interface_config.choose_free_port.unwrap_or(false) {
+                0
+            } else {
+                port
+            };
+            let mut interface = GraphqlInterface::new(port);
+            let r = interface.run(handler, kill_switch);
+            if let Some(addr) = interface.bound_address() {
+                println!(
+                    "{}",
+                    magic_port_binding_string(&interface_config.id, addr.port())
+                );
+            }
+            r
+        }
+        InterfaceDriver::Grpc { port } => {
+            // The gRPC server can't tell which port it got bound to, so we pick a free one
+            let port = if interface_config.choose_free_port.unwrap_or(false) {
+                get_free_port(port..MAX_DYNAMIC_PORT)
+                    .ok_or_else(|| String::from("Couldn't find free port"))?
+            } else {
+                port
+            };
+            let mut interface = GrpcInterface::new(port);
+            let r = interface.run(handler, kill_switch);
+            if let Some(addr) = interface.bound_address() {
+                println!(
+                    "{}",
+                    magic_port_binding_string(&interface_config.id, addr.port())
+                );
+            }
+            r
+        }
+        InterfaceDriver::RemoteAdmin {
+            port,
+            ref authorized_keys,
+            ..
+        } => {
+            let port = if interface_config.choose_free_port.unwrap_or(false) {
+                0
+            } else {
+                port
+            };
+            let identity = identity.ok_or_else(|| {
+                format!(
+                    "Remote admin interface {} needs the key of its agent",
+                    interface_config.id
+                )
+            })?;
+            let mut interface = RemoteAdminInterface::new(port, identity, authorized_keys.clone());
+            let r = interface.run(handler, kill_switch);
+            if let Some(addr) = interface.bound_address() {
+                println!(
+                    "{}",
+                    magic_port_binding_string(&interface_config.id, addr.port())
+                );
+            }
+            r
+        }
         _ => unimplemented!(),
     }
 }
@@ -1621,17 +2783,19 @@ pub mod tests {
     use super::*;
     use crate::{
         conductor::{passphrase_manager::PassphraseManager, test_admin::ConductorTestAdmin},
-        config::load_configuration,
+        config::{load_configuration, DpkiConfiguration},
         key_loaders::mock_passphrase_manager,
         keystore::{test_hash_config, Keystore, Secret, PRIMARY_KEYBUNDLE_ID},
+        storage_backend::CustomStorage,
     };
     use holochain_core::{
-        action::Action, nucleus::actions::call_zome_function::make_cap_request_for_call,
-        signal::signal_channel,
+        action::Action, agent::chain_store::ChainStore,
+        nucleus::actions::call_zome_function::make_cap_request_for_call, signal::signal_channel,
     };
     use holochain_core_types::dna;
     use holochain_dpki::{key_bundle::KeyBundle, password_encryption::PwHashConfig, SEED_SIZE};
     use holochain_persistence_api::cas::content::Address;
+    use holochain_persistence_mem::eav::memory::EavMemoryStorage;
     use holochain_wasm_utils::wasm_target_dir;
     use lib3h_sodium::secbuf::SecBuf;
     use std::{
@@ -1927,6 +3091,97 @@ pub mod tests {
         conductor.stop_all_instances().unwrap();
     }
 
+    #[test]
+    fn test_revoked_agent_key_fails_boot() {
+        let mut config = load_configuration::<Configuration>(&test_toml(10021, 10022)).unwrap();
+        config.revoked_agent_keys = vec![test_keybundle(1).get_id()];
+        let mut conductor = Conductor::from_config(config);
+        conductor.dna_loader = test_dna_loader();
+        conductor.key_loader = test_key_loader();
+
+        let result = conductor.instantiate_from_config(&String::from("test-instance-1"));
+        assert_eq!(
+            result.err(),
+            Some(format!(
+                "Key {} of agent 'test-agent-1' has been revoked",
+                test_keybundle(1).get_id()
+            ))
+        );
+        assert!(conductor
+            .instantiate_from_config(&String::from("test-instance-2"))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_agent_key_check_needs_the_dpki_instance() {
+        let mut config = load_configuration::<Configuration>(&test_toml(10021, 10022)).unwrap();
+        config.dpki = Some(DpkiConfiguration {
+            instance_id: String::from("test-instance-1"),
+            init_params: String::from("{}"),
+        });
+        let mut conductor = Conductor::from_config(config);
+        conductor.dna_loader = test_dna_loader();
+        conductor.key_loader = test_key_loader();
+
+        // Without the DPKI instance, nothing can vouch for the agent's key
+        let result = conductor.instantiate_from_config(&String::from("test-instance-2"));
+        assert_eq!(
+            result.err(),
+            Some(String::from(
+                "DPKI instance test-instance-1 is not running, can't check agent keys"
+            ))
+        );
+        // The DPKI instance doesn't check its own key
+        assert!(conductor
+            .instantiate_from_config(&String::from("test-instance-1"))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_custom_storage_backend() {
+        let mut config = load_configuration::<Configuration>(&test_toml(10023, 10024)).unwrap();
+        for instance in config.instances.iter_mut() {
+            instance.storage = StorageConfiguration::Custom {
+                backend: String::from("shared-memory"),
+                path: None,
+                options: Some(json!({"capacity": 10})),
+            };
+        }
+        let mut conductor = Conductor::from_config(config);
+        conductor.dna_loader = test_dna_loader();
+        conductor.key_loader = test_key_loader();
+
+        let instance_id = String::from("test-instance-1");
+        assert_eq!(
+            conductor.instantiate_from_config(&instance_id).err(),
+            Some(String::from(
+                "Storage backend \"shared-memory\" is not registered"
+            ))
+        );
+
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let requested_clone = requested.clone();
+        let factory: StorageBackendFactory =
+            Arc::new(Box::new(move |params: &StorageBackendParams| {
+                requested_clone.lock().unwrap().push(params.clone());
+                Ok(CustomStorage {
+                    cas: Arc::new(RwLock::new(MemoryStorage::new())),
+                    eav: Arc::new(RwLock::new(EavMemoryStorage::new())),
+                    compactor: None,
+                })
+            }));
+        conductor.register_storage_backend("shared-memory", factory);
+        assert!(conductor.instantiate_from_config(&instance_id).is_ok());
+        assert_eq!(
+            *requested.lock().unwrap(),
+            vec![StorageBackendParams {
+                instance_id,
+                path: None,
+                options: Some(json!({"capacity": 10})),
+            }]
+        );
+    }
+
     #[test]
     /// Here we test if we correctly check for consistency in DNA hashes: possible sources are:
     /// - DNA hash from Conductor configuration
@@ -2690,4 +3945,88 @@ pub mod tests {
             Err(HolochainInstanceError::InstanceNotActiveYet),
         );
     }
+
+    #[test]
+    fn test_instance_chain_head() {
+        let conductor = test_conductor(10055, 10056);
+        let id = String::from("test-instance-1");
+        let chain_head = conductor.instance_chain_head(&id).unwrap();
+
+        let instance = conductor.instances.get(&id).unwrap().read().unwrap();
+        let agent_state = instance.state().unwrap().agent();
+        let top_header = agent_state.top_chain_header().unwrap();
+        let length = agent_state.iter_chain().count() as u64;
+        assert_eq!(
+            serde_json::to_value(chain_head).unwrap(),
+            json!({
+                "address": top_header.address(),
+                "length": length,
+                "sequence": length - 1,
+                "timestamp": top_header.timestamp().to_string(),
+            })
+        );
+        drop(instance);
+
+        assert_eq!(
+            conductor
+                .instance_chain_head(&String::from("non-existant-id"))
+                .map(|_| ()),
+            Err(HolochainInstanceError::NoSuchInstance),
+        );
+    }
+
+    #[test]
+    fn test_chain_head_of_empty_chain() {
+        let storage = Arc::new(RwLock::new(MemoryStorage::new()));
+        let agent_state = AgentState::new(ChainStore::new(storage), Address::from("agent"));
+        assert_eq!(
+            serde_json::to_value(ChainHeadResponse::from(&agent_state)).unwrap(),
+            json!({"address": null, "length": 0, "sequence": null, "timestamp": null})
+        );
+    }
+
+    #[test]
+    fn test_blocked_agents_outlive_instance_restarts() {
+        let mut conductor = test_conductor(10053, 10054);
+        let tempdir = tempdir().unwrap();
+        conductor.config.persistence_dir = tempdir.path().to_path_buf();
+        let id = String::from("test-instance-1");
+        let agent = HashString::from(test_keybundle(2).get_id());
+        let is_blocked_in = |instance: &Holochain| {
+            // the block gets applied by the instance's action loop
+            (0..50).any(|_| {
+                let blocked = instance.state().unwrap().network().is_agent_blocked(&agent);
+                if !blocked {
+                    thread::sleep(Duration::from_millis(100));
+                }
+                blocked
+            })
+        };
+
+        conductor.start_instance(&id).unwrap();
+        conductor.block_agent(&agent).unwrap();
+        conductor.block_agent(&agent).unwrap();
+        assert_eq!(conductor.config.blocked_agents, vec![agent.to_string()]);
+        let saved = load_configuration::<Configuration>(
+            &fs::read_to_string(conductor.config_path()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(saved.blocked_agents, vec![agent.to_string()]);
+
+        conductor.stop_instance(&id).unwrap();
+        conductor.start_instance(&id).unwrap();
+        assert!(is_blocked_in(
+            &*conductor.instances.get(&id).unwrap().read().unwrap()
+        ));
+
+        // instances created from the config, like after a conductor restart, apply it too
+        let instance = conductor.instantiate_from_config(&id).unwrap();
+        assert!(is_blocked_in(&instance));
+
+        conductor.unblock_agent(&agent).unwrap();
+        assert!(conductor.config.blocked_agents.is_empty());
+        let instance = conductor.instantiate_from_config(&id).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        assert!(!instance.state().unwrap().network().is_agent_blocked(&agent));
+    }
 }
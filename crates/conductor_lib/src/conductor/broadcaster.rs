@@ -6,6 +6,9 @@ use jsonrpc_ws_server::ws;
 /// over the existing connection.
 pub enum Broadcaster {
     Ws(jsonrpc_ws_server::Broadcaster),
+    /// Hands messages to an interface that delivers them itself, like the GraphQL interface
+    /// does to matching subscriptions
+    Channel(crossbeam_channel::Sender<String>),
     Noop,
 }
 
@@ -13,6 +16,7 @@ impl std::fmt::Debug for Broadcaster {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let variant = match self {
             Broadcaster::Ws(_) => "Ws",
+            Broadcaster::Channel(_) => "Channel",
             Broadcaster::Noop => "Noop",
         };
         write!(f, "Broadcaster::{}", variant)
@@ -31,6 +35,9 @@ impl Broadcaster {
                 .map_err(|e| {
                     HolochainError::ErrorGeneric(format!("Broadcaster::Ws -- {}", e.to_string()))
                 })?,
+            Broadcaster::Channel(sender) => sender.send(msg.into().to_string()).map_err(|e| {
+                HolochainError::ErrorGeneric(format!("Broadcaster::Channel -- {}", e))
+            })?,
             Broadcaster::Noop => (),
         }
         Ok(())
@@ -1,7 +1,13 @@
-use crate::conductor::Conductor;
-use holochain_core::state_dump::{DumpOptions, StateDump};
+use crate::conductor::{base::notify, Conductor};
+use holochain_core::{
+    agent::actions::rollback::rollback_chain,
+    causal_log::CausalLogEntry,
+    profiler::Profiler,
+    state_dump::{DumpOptions, StateDump},
+};
 use holochain_core_types::error::HolochainError;
 use holochain_persistence_api::cas::content::Address;
+use std::sync::Arc;
 
 #[allow(clippy::ptr_arg)]
 pub trait ConductorDebug {
@@ -16,6 +22,18 @@ pub trait ConductorDebug {
         address: &Address,
         instance_id: &String,
     ) -> Result<(String, String), HolochainError>;
+    fn rollback_chain(
+        &self,
+        instance_id: &String,
+        header_address: Address,
+    ) -> Result<Vec<Address>, HolochainError>;
+    fn causal_log_for_instance(
+        &self,
+        instance_id: &String,
+        id: Option<String>,
+        subject: Option<String>,
+    ) -> Result<Vec<CausalLogEntry>, HolochainError>;
+    fn profiler_for_instance(&self, instance_id: &String) -> Result<Arc<Profiler>, HolochainError>;
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
@@ -42,4 +60,56 @@ impl ConductorDebug for Conductor {
         let hc = self.instances.get(instance_id)?;
         Ok(hc.read().unwrap().get_type_and_content_from_cas(address)?)
     }
+
+    /// Truncates an instance's source chain back to the given header.
+    /// Only available if the conductor runs in dev mode.
+    fn rollback_chain(
+        &self,
+        instance_id: &String,
+        header_address: Address,
+    ) -> Result<Vec<Address>, HolochainError> {
+        if !self.config.dev_mode {
+            return Err(HolochainError::ErrorGeneric(
+                "Rolling back a source chain is only allowed in dev mode".to_string(),
+            ));
+        }
+        let hc = self.instances.get(instance_id)?;
+        let context = hc.read().unwrap().context()?;
+        let dropped = context.block_on(rollback_chain(header_address, &context))?;
+        notify(format!(
+            "Rolled back instance \"{}\" by {} headers",
+            instance_id,
+            dropped.len()
+        ));
+        Ok(dropped)
+    }
+
+    /// The recent actions of an instance and what caused them.
+    /// Given an ID, only that entry followed by its chain of causes,
+    /// given a subject, only the entries about it.
+    fn causal_log_for_instance(
+        &self,
+        instance_id: &String,
+        id: Option<String>,
+        subject: Option<String>,
+    ) -> Result<Vec<CausalLogEntry>, HolochainError> {
+        let hc = self.instances.get(instance_id)?;
+        let causal_log = hc.read().unwrap().context()?.causal_log.clone();
+        let entries = match id {
+            Some(id) => causal_log.chain(&id),
+            None => causal_log.entries(),
+        };
+        Ok(match subject {
+            Some(subject) => entries
+                .into_iter()
+                .filter(|entry| entry.subject.as_ref() == Some(&subject))
+                .collect(),
+            None => entries,
+        })
+    }
+
+    fn profiler_for_instance(&self, instance_id: &String) -> Result<Arc<Profiler>, HolochainError> {
+        let hc = self.instances.get(instance_id)?;
+        Ok(hc.read().unwrap().context()?.profiler.clone())
+    }
 }
@@ -0,0 +1,196 @@
+//! Installing DNAs that get downloaded instead of shipped to the conductor's file system.
+//!
+//! A DNA is given by its hash and optionally an HTTPS URL. Without a URL it gets downloaded
+//! from the configured DNA registry. Downloads must have the expected hash before they get
+//! installed and are kept in the DNA cache by hash, so every DNA only gets downloaded once.
+use crate::conductor::{base::notify, Conductor, ConductorAdmin};
+use holochain_common::paths::DNA_EXTENSION;
+use holochain_core_types::{dna::Dna, error::HolochainError};
+use holochain_json_api::json::JsonString;
+use holochain_persistence_api::{cas::content::AddressableContent, hash::HashString};
+use reqwest::{self, Url};
+use std::{convert::TryFrom, fs, io::Read, path::PathBuf, time::Duration};
+
+const DOWNLOAD_TIMEOUT_SECS: u64 = 60;
+/// Downloads that are bigger get refused
+const MAX_DNA_BYTES: u64 = 100 * 1024 * 1024;
+
+fn https_url(url: &str) -> Result<Url, HolochainError> {
+    let url = Url::parse(url)
+        .map_err(|e| HolochainError::ConfigError(format!("Invalid URL {}: {}", url, e)))?;
+    if url.scheme() != "https" {
+        return Err(HolochainError::ConfigError(format!(
+            "DNAs can only be downloaded over HTTPS, not from {}",
+            url
+        )));
+    }
+    Ok(url)
+}
+
+fn download(url: &Url) -> Result<Vec<u8>, HolochainError> {
+    let error = |e: reqwest::Error| {
+        HolochainError::ErrorGeneric(format!("Could not download DNA from {}: {}", url, e))
+    };
+    notify(format!("Downloading DNA from {}", url));
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS))
+        .build()
+        .map_err(error)?
+        .get(url.clone())
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(error)?;
+    let mut bytes = Vec::new();
+    response.take(MAX_DNA_BYTES + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_DNA_BYTES {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "DNA at {} is bigger than {} bytes",
+            url, MAX_DNA_BYTES
+        )));
+    }
+    Ok(bytes)
+}
+
+/// Parses the DNA and makes sure it has the expected hash
+fn verify(bytes: &[u8], expected_hash: &HashString) -> Result<Dna, HolochainError> {
+    let json = String::from_utf8(bytes.to_vec())
+        .map_err(|_| HolochainError::ErrorGeneric(String::from("DNA is not valid UTF-8")))?;
+    let dna = Dna::try_from(JsonString::from_json(&json))?;
+    dna.verify()?;
+    let actual_hash = dna.address();
+    if actual_hash != *expected_hash {
+        return Err(HolochainError::DnaHashMismatch(
+            expected_hash.clone(),
+            actual_hash,
+        ));
+    }
+    Ok(dna)
+}
+
+impl Conductor {
+    pub fn dna_cache_dir_path(&self) -> PathBuf {
+        self.config.persistence_dir.join("dna_cache")
+    }
+
+    /// Installs the DNA with the given hash like `install_dna_from_file` with `copy` set.
+    /// It gets downloaded from the given HTTPS URL, or from the configured DNA registry if
+    /// there is no URL, unless it is in the DNA cache already.
+    pub fn install_dna_from_url(
+        &mut self,
+        url: Option<&str>,
+        hash: HashString,
+        id: String,
+        properties: Option<&serde_json::Value>,
+        uuid: Option<String>,
+    ) -> Result<HashString, HolochainError> {
+        let path = self.fetch_dna(url, &hash)?;
+        self.install_dna_from_file(path, id, true, Some(hash), properties, uuid)
+    }
+
+    /// Returns the path of the DNA with the given hash in the DNA cache, after downloading it
+    /// if it isn't there yet.
+    pub fn fetch_dna(
+        &self,
+        url: Option<&str>,
+        hash: &HashString,
+    ) -> Result<PathBuf, HolochainError> {
+        let path = self
+            .dna_cache_dir_path()
+            .join(hash.to_string())
+            .with_extension(DNA_EXTENSION);
+        if let Ok(bytes) = fs::read(&path) {
+            match verify(&bytes, hash) {
+                Ok(_) => return Ok(path),
+                Err(error) => {
+                    notify(format!(
+                        "Dropping cached DNA {}, downloading it again: {}",
+                        hash, error
+                    ));
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+
+        let url = match url {
+            Some(url) => https_url(url)?,
+            None => {
+                let registry = self.config.dna_registry.as_ref().ok_or_else(|| {
+                    HolochainError::ConfigError(format!(
+                        "DNA {} is not cached and no DNA registry is configured",
+                        hash
+                    ))
+                })?;
+                https_url(&format!(
+                    "{}/{}.{}",
+                    registry.url.trim_end_matches('/'),
+                    hash,
+                    DNA_EXTENSION
+                ))?
+            }
+        };
+        let bytes = download(&url)?;
+        verify(&bytes, hash)?;
+
+        // Written next to the cache and moved in, so the cache never holds partial downloads
+        fs::create_dir_all(self.dna_cache_dir_path())?;
+        let partial_path = path.with_extension("part");
+        fs::write(&partial_path, &bytes)?;
+        fs::rename(&partial_path, &path)?;
+        notify(format!("Cached DNA {} from {}", hash, url));
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::conductor::{admin::tests::create_test_conductor, tests::example_dna_string};
+
+    fn cache_dna(conductor: &Conductor, hash: &HashString, json: &str) -> PathBuf {
+        let path = conductor
+            .dna_cache_dir_path()
+            .join(hash.to_string())
+            .with_extension(DNA_EXTENSION);
+        fs::create_dir_all(conductor.dna_cache_dir_path()).unwrap();
+        fs::write(&path, json).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_install_dna_from_cache() {
+        let test_name = "test_install_dna_from_cache";
+        let mut conductor = create_test_conductor(test_name, 3801);
+        let json = example_dna_string();
+        let hash = Dna::try_from(JsonString::from_json(&json))
+            .unwrap()
+            .address();
+        cache_dna(&conductor, &hash, &json);
+
+        let installed = conductor
+            .install_dna_from_url(None, hash.clone(), String::from("cached-dna"), None, None)
+            .unwrap();
+        assert_eq!(installed, hash);
+        assert_eq!(
+            conductor.config().dna_by_id("cached-dna").unwrap().hash,
+            hash.to_string()
+        );
+    }
+
+    #[test]
+    fn test_fetch_dna_verifies_hash() {
+        let test_name = "test_fetch_dna_verifies_hash";
+        let conductor = create_test_conductor(test_name, 3802);
+        let hash = HashString::from("QmNotTheHashOfTheExampleDna");
+        let path = cache_dna(&conductor, &hash, &example_dna_string());
+
+        // The cached DNA has another hash, so it gets dropped, and there is nowhere to
+        // download it from
+        assert!(conductor.fetch_dna(None, &hash).is_err());
+        assert!(!path.exists());
+
+        assert!(conductor
+            .fetch_dna(Some("http://example.com/dna.json"), &hash)
+            .is_err());
+        assert!(verify(b"not a dna", &hash).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+//! Health of the conductor's instances, for load balancers and orchestrators.
+//!
+//! Whether the holding queue is draining and zome calls are completing can only be told over
+//! time, so the conductor keeps a sample of each instance and compares against it once it is
+//! [HEALTH_WINDOW_SECS] old. Until then, those checks keep their last result.
+use crate::conductor::Conductor;
+use holochain_core::nucleus::ZomeFnCall;
+use std::{
+    cmp::max,
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// How long the holding queue may stay as long as it is, and a zome call may keep running,
+/// before the instance is reported as degraded
+pub const HEALTH_WINDOW_SECS: u64 = 30;
+
+/// Share of the storage limit from which on storage is reported as degraded
+const STORAGE_DEGRADED_PERCENT: usize = 90;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct HealthCheck {
+    pub status: HealthStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl HealthCheck {
+    fn ok() -> Self {
+        HealthCheck {
+            status: HealthStatus::Ok,
+            message: None,
+        }
+    }
+
+    fn degraded(message: String) -> Self {
+        HealthCheck {
+            status: HealthStatus::Degraded,
+            message: Some(message),
+        }
+    }
+
+    fn failed(message: String) -> Self {
+        HealthCheck {
+            status: HealthStatus::Failed,
+            message: Some(message),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct InstanceHealth {
+    /// The worst status of the checks below
+    pub status: HealthStatus,
+    pub network: HealthCheck,
+    pub holding_queue: HealthCheck,
+    pub zome_calls: HealthCheck,
+    pub storage: HealthCheck,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct HealthReport {
+    /// The worst status of all instances
+    pub status: HealthStatus,
+    pub instances: BTreeMap<String, InstanceHealth>,
+}
+
+/// What an instance looked like at the last health check that was at least a window apart
+pub struct HealthSample {
+    taken: Instant,
+    pending_validations: usize,
+    running_zome_calls: HashSet<ZomeFnCall>,
+    holding_queue: HealthCheck,
+    zome_calls: HealthCheck,
+}
+
+fn check_storage_path(path: &str) -> HealthCheck {
+    let probe = PathBuf::from(path).join(".health-probe");
+    match fs::write(&probe, b"ok").and_then(|_| fs::remove_file(&probe)) {
+        Ok(()) => HealthCheck::ok(),
+        Err(error) => {
+            HealthCheck::failed(format!("Storage at {} is not writable: {}", path, error))
+        }
+    }
+}
+
+impl Conductor {
+    /// Checks every configured instance and updates the samples the next check compares to.
+    pub fn health(&mut self) -> HealthReport {
+        let window = Duration::from_secs(HEALTH_WINDOW_SECS);
+        let mut instances = BTreeMap::new();
+        for instance_config in self.config.instances.clone() {
+            let id = instance_config.id.clone();
+            let stopped = |message: &str| InstanceHealth {
+                status: HealthStatus::Failed,
+                network: HealthCheck::failed(message.to_string()),
+                holding_queue: HealthCheck::failed(message.to_string()),
+                zome_calls: HealthCheck::failed(message.to_string()),
+                storage: HealthCheck::failed(message.to_string()),
+            };
+            let context = match self.instances.get(&id) {
+                Some(instance) if instance.read().unwrap().active() => {
+                    instance.read().unwrap().context().ok()
+                }
+                _ => None,
+            };
+            let (context, state) = match context.and_then(|c| c.state().map(|s| (c.clone(), s))) {
+                Some(context_and_state) => context_and_state,
+                None => {
+                    self.health_samples.remove(&id);
+                    instances.insert(id, stopped("Instance is not running"));
+                    continue;
+                }
+            };
+
+            let network = match state.network().initialized() {
+                Ok(()) => HealthCheck::ok(),
+                Err(_) => HealthCheck::failed(String::from("Network is not connected")),
+            };
+
+            let pending_validations = state.dht().queued_holding_workflows().len();
+            let running_zome_calls = state.nucleus().running_zome_calls.clone();
+            let previous = self.health_samples.remove(&id);
+            let fresh = previous
+                .as_ref()
+                .map(|sample| sample.taken.elapsed() < window)
+                .unwrap_or(false);
+            let sample = if fresh {
+                previous.unwrap()
+            } else {
+                let holding_queue = match previous {
+                    _ if pending_validations == 0 => HealthCheck::ok(),
+                    Some(ref previous) if pending_validations < previous.pending_validations => {
+                        HealthCheck::ok()
+                    }
+                    Some(_) => HealthCheck::degraded(format!(
+                        "Holding queue did not drain in {}s, {} validations pending",
+                        HEALTH_WINDOW_SECS, pending_validations
+                    )),
+                    None => HealthCheck::ok(),
+                };
+                let stuck_calls = previous
+                    .as_ref()
+                    .map(|previous| {
+                        previous
+                            .running_zome_calls
+                            .intersection(&running_zome_calls)
+                            .count()
+                    })
+                    .unwrap_or(0);
+                let zome_calls = if stuck_calls == 0 {
+                    HealthCheck::ok()
+                } else {
+                    HealthCheck::degraded(format!(
+                        "{} zome calls running for more than {}s",
+                        stuck_calls, HEALTH_WINDOW_SECS
+                    ))
+                };
+                HealthSample {
+                    taken: Instant::now(),
+                    pending_validations,
+                    running_zome_calls,
+                    holding_queue,
+                    zome_calls,
+                }
+            };
+            // A queue that got emptied is healthy right away
+            let holding_queue = if pending_validations == 0 {
+                HealthCheck::ok()
+            } else {
+                sample.holding_queue.clone()
+            };
+            let zome_calls = sample.zome_calls.clone();
+            self.health_samples.insert(id.clone(), sample);
+
+            let mut storage = instance_config
+                .storage
+                .path()
+                .map(check_storage_path)
+                .unwrap_or_else(HealthCheck::ok);
+            if let (HealthStatus::Ok, Some(quota)) =
+                (storage.status, context.storage_quota.as_ref())
+            {
+                let max_bytes = max(quota.max_bytes(), 1);
+                if quota.used_bytes() >= max_bytes {
+                    storage = HealthCheck::failed(String::from("Storage limit reached"));
+                } else if quota.used_bytes() * 100 / max_bytes >= STORAGE_DEGRADED_PERCENT {
+                    storage = HealthCheck::degraded(format!(
+                        "{} of {} bytes of the storage limit used",
+                        quota.used_bytes(),
+                        quota.max_bytes()
+                    ));
+                }
+            }
+
+            let status = [
+                network.status,
+                holding_queue.status,
+                zome_calls.status,
+                storage.status,
+            ]
+            .iter()
+            .cloned()
+            .max()
+            .unwrap_or(HealthStatus::Ok);
+            instances.insert(
+                id,
+                InstanceHealth {
+                    status,
+                    network,
+                    holding_queue,
+                    zome_calls,
+                    storage,
+                },
+            );
+        }
+        let status = instances
+            .values()
+            .map(|instance| instance.status)
+            .max()
+            .unwrap_or(HealthStatus::Ok);
+        HealthReport { status, instances }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::conductor::{admin::tests::create_test_conductor, ConductorAdmin};
+
+    #[test]
+    fn test_health() {
+        let test_name = "test_health";
+        let mut conductor = create_test_conductor(test_name, 3601);
+        conductor.start_all_instances().unwrap();
+        let report = conductor.health();
+        assert_eq!(report.instances.len(), conductor.config().instances.len());
+        for instance in report.instances.values() {
+            assert_eq!(instance.holding_queue.status, HealthStatus::Ok);
+            assert_eq!(instance.zome_calls.status, HealthStatus::Ok);
+            assert_eq!(instance.storage.status, HealthStatus::Ok);
+        }
+
+        // Instances that are not running fail
+        let config = conductor.config();
+        let id = config.instances[0].id.clone();
+        conductor.remove_instance(&id).unwrap();
+        conductor
+            .add_instance(
+                &id,
+                &config.dnas[0].id,
+                &config.agents[0].id,
+                Some("memory"),
+                None,
+            )
+            .unwrap();
+        let report = conductor.health();
+        assert_eq!(report.status, HealthStatus::Failed);
+        assert_eq!(report.instances[&id].status, HealthStatus::Failed);
+    }
+
+    #[test]
+    fn health_status_serializes_for_load_balancers() {
+        assert_eq!(
+            serde_json::to_string(&HealthStatus::Degraded).unwrap(),
+            "\"DEGRADED\""
+        );
+        assert!(HealthStatus::Failed > HealthStatus::Degraded);
+        assert!(HealthStatus::Degraded > HealthStatus::Ok);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::conductor::{base::notify, Conductor, ConductorAdmin};
+use holochain_core_types::error::HolochainError;
+use holochain_persistence_api::hash::HashString;
+use std::path::PathBuf;
+
+/// An interface an installed app's instance gets added to
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AppInterfaceBinding {
+    pub interface_id: String,
+    /// Name of the instance within the interface, defaults to the instance's ID
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// Everything needed to get a DNA running and reachable with one admin call
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AppInstallRequest {
+    /// ID of the new instance, and of the DNA unless `dna_id` is given
+    pub id: String,
+    /// DNA file to install. It gets copied to the conductor's DNA directory.
+    pub path: PathBuf,
+    #[serde(default)]
+    pub dna_id: Option<String>,
+    #[serde(default)]
+    pub expected_hash: Option<HashString>,
+    #[serde(default)]
+    pub properties: Option<serde_json::Value>,
+    #[serde(default)]
+    pub uuid: Option<String>,
+    /// Agent that runs the instance
+    pub agent_id: String,
+    /// Storage type as in `admin/instance/add`, defaults to lmdb
+    #[serde(default)]
+    pub storage: Option<String>,
+    #[serde(default)]
+    pub membrane_proof: Option<String>,
+    #[serde(default)]
+    pub interfaces: Vec<AppInterfaceBinding>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AppInstallResult {
+    pub dna_id: String,
+    pub dna_hash: HashString,
+    pub instance_id: String,
+}
+
+/// Steps of an app installation that have to be undone if a later one fails
+enum InstallStep {
+    DnaInstalled(String),
+    InstanceAdded(String),
+    AddedToInterface(String, String),
+}
+
+impl Conductor {
+    /// Installs a DNA, adds an instance of it for the given agent, starts the instance and
+    /// adds it to the given interfaces. If any of that fails, what was done so far gets undone
+    /// and the conductor is left as it was.
+    pub fn install_app(
+        &mut self,
+        request: AppInstallRequest,
+    ) -> Result<AppInstallResult, HolochainError> {
+        let mut done = Vec::new();
+        let result = self.try_install_app(&request, &mut done);
+        if let Err(ref error) = result {
+            notify(format!(
+                "Installing app \"{}\" failed, rolling back: {}",
+                request.id, error
+            ));
+            self.roll_back_install(done);
+        }
+        result
+    }
+
+    fn try_install_app(
+        &mut self,
+        request: &AppInstallRequest,
+        done: &mut Vec<InstallStep>,
+    ) -> Result<AppInstallResult, HolochainError> {
+        let dna_id = request.dna_id.clone().unwrap_or_else(|| request.id.clone());
+        if self.config.dna_by_id(&dna_id).is_some() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "DNA with ID '{}' already exists",
+                dna_id
+            )));
+        }
+        if self.config.instance_by_id(&request.id).is_some() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Instance with ID '{}' already exists",
+                request.id
+            )));
+        }
+        for binding in request.interfaces.iter() {
+            if self.config.interface_by_id(&binding.interface_id).is_none() {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Interface with ID {} not found",
+                    binding.interface_id
+                )));
+            }
+        }
+
+        let dna_hash = self.install_dna_from_file(
+            request.path.clone(),
+            dna_id.clone(),
+            true,
+            request.expected_hash.clone(),
+            request.properties.as_ref(),
+            request.uuid.clone(),
+        )?;
+        done.push(InstallStep::DnaInstalled(dna_id.clone()));
+
+        self.add_instance(
+            &request.id,
+            &dna_id,
+            &request.agent_id,
+            request.storage.as_ref().map(String::as_str),
+            request.membrane_proof.clone(),
+        )?;
+        done.push(InstallStep::InstanceAdded(request.id.clone()));
+
+        self.start_instance(&request.id)
+            .map_err(|error| HolochainError::ErrorGeneric(error.to_string()))?;
+
+        for binding in request.interfaces.iter() {
+            self.add_instance_to_interface(&binding.interface_id, &request.id, &binding.alias)?;
+            done.push(InstallStep::AddedToInterface(
+                binding.interface_id.clone(),
+                request.id.clone(),
+            ));
+        }
+
+        notify(format!("Installed app \"{}\"", request.id));
+        Ok(AppInstallResult {
+            dna_id,
+            dna_hash,
+            instance_id: request.id.clone(),
+        })
+    }
+
+    fn roll_back_install(&mut self, done: Vec<InstallStep>) {
+        for step in done.into_iter().rev() {
+            let result = match step {
+                InstallStep::AddedToInterface(ref interface_id, ref instance_id) => {
+                    self.remove_instance_from_interface(interface_id, instance_id)
+                }
+                InstallStep::InstanceAdded(ref id) => self.remove_instance(id),
+                InstallStep::DnaInstalled(ref id) => self.uninstall_dna(id),
+            };
+            if let Err(error) = result {
+                notify(format!(
+                    "Error while rolling back app installation: {}",
+                    error
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::conductor::admin::tests::create_test_conductor;
+
+    fn request(id: &str, interfaces: Vec<AppInterfaceBinding>) -> AppInstallRequest {
+        AppInstallRequest {
+            id: id.to_string(),
+            path: PathBuf::from("new-dna.dna.json"),
+            dna_id: None,
+            expected_hash: None,
+            properties: None,
+            uuid: None,
+            agent_id: String::from("test-agent-1"),
+            storage: Some(String::from("memory")),
+            membrane_proof: None,
+            interfaces,
+        }
+    }
+
+    #[test]
+    fn test_install_app() {
+        let test_name = "test_install_app";
+        let mut conductor = create_test_conductor(test_name, 3501);
+        let interface_id = conductor.config().interfaces[0].id.clone();
+
+        let result = conductor
+            .install_app(request(
+                "new-app",
+                vec![AppInterfaceBinding {
+                    interface_id: interface_id.clone(),
+                    alias: Some(String::from("app")),
+                }],
+            ))
+            .unwrap();
+        assert_eq!(result.dna_id, "new-app");
+        assert_eq!(result.instance_id, "new-app");
+
+        let config = conductor.config();
+        assert_eq!(
+            config.dna_by_id("new-app").unwrap().hash,
+            result.dna_hash.to_string()
+        );
+        assert!(config.instance_by_id("new-app").is_some());
+        assert!(config
+            .interface_by_id(&interface_id)
+            .unwrap()
+            .instances
+            .iter()
+            .any(|instance| instance.id == "new-app"));
+        assert!(conductor
+            .instances
+            .get("new-app")
+            .unwrap()
+            .read()
+            .unwrap()
+            .active());
+    }
+
+    #[test]
+    fn test_install_app_rolls_back() {
+        let test_name = "test_install_app_rolls_back";
+        let mut conductor = create_test_conductor(test_name, 3502);
+        let dnas = conductor.config().dnas.len();
+        let instances = conductor.config().instances.len();
+
+        // Unknown interfaces get refused before anything is installed
+        let mut app = request(
+            "new-app",
+            vec![AppInterfaceBinding {
+                interface_id: String::from("no-such-interface"),
+                alias: None,
+            }],
+        );
+        assert!(conductor.install_app(app.clone()).is_err());
+        assert_eq!(conductor.config().dnas.len(), dnas);
+
+        // The DNA gets installed before adding the instance fails
+        app.interfaces = Vec::new();
+        app.agent_id = String::from("no-such-agent");
+        assert!(conductor.install_app(app).is_err());
+        assert_eq!(conductor.config().dnas.len(), dnas);
+        assert_eq!(conductor.config().instances.len(), instances);
+        assert!(conductor.instances.get("new-app").is_none());
+    }
+}
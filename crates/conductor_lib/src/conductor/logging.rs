@@ -0,0 +1,111 @@
+use crate::{
+    conductor::{base::notify, Conductor},
+    log_router::{log_router, parse_level},
+};
+use holochain_core_types::error::HolochainError;
+use std::collections::BTreeMap;
+
+#[allow(clippy::ptr_arg)]
+impl Conductor {
+    /// Changes the level of the conductor's logs, or of the given instance's logs, right away
+    /// and in the saved config. Instances without their own level follow the conductor's.
+    pub fn set_log_level(
+        &mut self,
+        instance_id: Option<&String>,
+        level: &str,
+    ) -> Result<(), HolochainError> {
+        let filter = parse_level(level).map_err(HolochainError::ConfigError)?;
+        let mut new_config = self.config.clone();
+        match instance_id {
+            Some(id) => {
+                let instance = new_config
+                    .instances
+                    .iter_mut()
+                    .find(|instance| instance.id == *id)
+                    .ok_or_else(|| {
+                        HolochainError::ConfigError(format!("Instance \"{}\" not found", id))
+                    })?;
+                instance.logger.get_or_insert_with(Default::default).level =
+                    Some(level.to_string());
+            }
+            None => new_config.logger.logger_level = level.to_string(),
+        }
+        self.config = new_config;
+        self.save_config()?;
+
+        match instance_id {
+            Some(id) => {
+                log_router().set_instance_level(id, filter);
+                notify(format!("Set log level of instance \"{}\" to {}", id, level));
+            }
+            None => {
+                log_router().set_conductor_level(filter);
+                notify(format!("Set log level to {}", level));
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes the level of the logs of a module and its submodules until the conductor
+    /// restarts, or resets it to the conductor's or instance's level if no level is given
+    pub fn set_module_log_level(
+        &self,
+        module: &str,
+        level: Option<&str>,
+    ) -> Result<(), HolochainError> {
+        if module.is_empty() {
+            return Err(HolochainError::ConfigError(String::from(
+                "Module path must not be empty",
+            )));
+        }
+        match level {
+            Some(level) => {
+                let filter = parse_level(level).map_err(HolochainError::ConfigError)?;
+                log_router().set_module_level(module, Some(filter));
+                notify(format!("Set log level of module {} to {}", module, level));
+            }
+            None => {
+                log_router().set_module_level(module, None);
+                notify(format!("Reset log level of module {}", module));
+            }
+        }
+        Ok(())
+    }
+
+    /// The levels set for modules with [set_module_log_level](Conductor::set_module_log_level)
+    pub fn module_log_levels(&self) -> BTreeMap<String, String> {
+        log_router()
+            .module_levels()
+            .into_iter()
+            .map(|(module, level)| (module, level.to_string().to_lowercase()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::conductor::admin::tests::create_test_conductor;
+
+    #[test]
+    fn test_set_log_level() {
+        let test_name = "test_set_log_level";
+        let mut conductor = create_test_conductor(test_name, 3902);
+        let id = conductor.config().instances[0].id.clone();
+
+        conductor.set_log_level(Some(&id), "warn").unwrap();
+        let instance_config = conductor.config().instance_by_id(&id).unwrap();
+        assert_eq!(
+            instance_config.logger.unwrap().level,
+            Some(String::from("warn"))
+        );
+
+        conductor.set_log_level(None, "info").unwrap();
+        assert_eq!(conductor.config().logger.logger_level, "info");
+
+        assert!(conductor.set_log_level(None, "loud").is_err());
+        assert!(conductor
+            .set_log_level(Some(&String::from("no-such-instance")), "info")
+            .is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! Serving the conductor's metrics to Prometheus.
+//!
+//! Zome calls, holding workflows and the network record into the
+//! [registry](holochain_metrics::prometheus) themselves. The queue depths of instances are
+//! gauges that the stats thread updates every time it collects [InstanceStats].
+use crate::{
+    conductor::{base::notify, Conductor},
+    config::MetricsEndpointConfiguration,
+};
+use holochain_core::context::InstanceStats;
+use holochain_metrics::prometheus;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+use tiny_http::{Header, Method, Response, Server};
+
+/// The endpoint binds its port for the lifetime of the process, so it only gets started once
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Sets the gauges of an instance from its latest stats
+pub(in crate::conductor) fn record_instance_gauges(instance_id: &str, stats: &InstanceStats) {
+    let labels = [("instance", instance_id)];
+    prometheus::set_gauge(
+        "holochain_holding_queue_depth",
+        "Holding workflows waiting to run",
+        &labels,
+        stats.number_pending_validations as f64,
+    );
+    prometheus::set_gauge(
+        "holochain_delayed_validations",
+        "Holding workflows waiting for a dependency to arrive",
+        &labels,
+        stats.number_delayed_validations as f64,
+    );
+    prometheus::set_gauge(
+        "holochain_held_entries",
+        "Entries held in the DHT shard",
+        &labels,
+        stats.number_held_entries as f64,
+    );
+    prometheus::set_gauge(
+        "holochain_held_aspects",
+        "Entry aspects held in the DHT shard",
+        &labels,
+        stats.number_held_aspects as f64,
+    );
+    prometheus::set_gauge(
+        "holochain_running_zome_calls",
+        "Zome function calls currently running",
+        &labels,
+        stats.number_running_zome_calls as f64,
+    );
+}
+
+fn respond(request: tiny_http::Request) {
+    let response = if *request.method() == Method::Get && request.url() == METRICS_PATH {
+        let content_type =
+            Header::from_bytes(&b"Content-Type"[..], prometheus::PROMETHEUS_CONTENT_TYPE)
+                .expect("Content type is a valid header");
+        Response::from_string(prometheus::render()).with_header(content_type)
+    } else {
+        Response::from_string("Not found").with_status_code(404)
+    };
+    if let Err(error) = request.respond(response) {
+        debug!("conductor: Could not respond to metrics request: {}", error);
+    }
+}
+
+impl Conductor {
+    /// Starts recording metrics and serving them if the configuration has a metrics endpoint
+    pub fn start_metrics_endpoint(&mut self) {
+        let config: MetricsEndpointConfiguration = match self.config.metrics_endpoint.clone() {
+            Some(config) => config,
+            None => return,
+        };
+        if STARTED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let address = format!("{}:{}", config.bind_address, config.port);
+        let server = match Server::http(address.as_str()) {
+            Ok(server) => server,
+            Err(error) => {
+                STARTED.store(false, Ordering::SeqCst);
+                error!(
+                    "conductor: Could not serve metrics on {}: {}",
+                    address, error
+                );
+                return;
+            }
+        };
+        prometheus::enable_prometheus();
+        thread::Builder::new()
+            .name(String::from("metrics_endpoint"))
+            .spawn(move || {
+                for request in server.incoming_requests() {
+                    respond(request);
+                }
+            })
+            .expect("Could not spawn metrics endpoint thread");
+        notify(format!(
+            "Serving metrics on http://{}{}",
+            address, METRICS_PATH
+        ));
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{conductor::admin::tests::create_test_conductor, config::Configuration};
+    use std::io::Read;
+
+    #[test]
+    fn serves_recorded_metrics() {
+        let mut conductor = create_test_conductor("serves_recorded_metrics", 3906);
+        conductor.config = Configuration {
+            metrics_endpoint: Some(MetricsEndpointConfiguration {
+                port: 3907,
+                bind_address: String::from("127.0.0.1"),
+            }),
+            ..conductor.config.clone()
+        };
+        conductor.start_metrics_endpoint();
+        prometheus::increment_counter(
+            "test_metrics_endpoint_total",
+            "Counter of the endpoint test",
+            &[],
+        );
+
+        let mut body = String::new();
+        reqwest::get("http://127.0.0.1:3907/metrics")
+            .unwrap()
+            .read_to_string(&mut body)
+            .unwrap();
+        assert!(body.contains("test_metrics_endpoint_total 1\n"));
+
+        let response = reqwest::get("http://127.0.0.1:3907/other").unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
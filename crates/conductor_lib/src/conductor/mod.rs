@@ -1,15 +1,44 @@
 pub mod admin;
+pub mod alerts;
 pub mod base;
 pub mod broadcaster;
+pub mod crash_report;
 pub mod debug;
+pub mod dna_fetch;
+pub mod health;
+pub mod install_app;
+pub mod logging;
+pub mod metrics_endpoint;
 pub mod passphrase_manager;
+pub mod provisioning;
+pub mod reload;
+pub mod signal_history;
+pub mod stats;
+pub mod supervisor;
+pub mod tenant_admin;
 pub mod test_admin;
 pub mod ui_admin;
 
 pub use self::{
     admin::ConductorAdmin,
-    base::{mount_conductor_from_config, Conductor, GetMetaOptions, GetMetaResponse, CONDUCTOR},
+    alerts::Alert,
+    base::{
+        mount_conductor_from_config, ChainHeadResponse, Conductor, EntryHoldersResponse,
+        GetMetaOptions, GetMetaResponse, CONDUCTOR,
+    },
+    crash_report::{record_panic, RecordedPanic},
     debug::ConductorDebug,
+    health::{HealthCheck, HealthReport, HealthStatus, InstanceHealth},
+    install_app::{AppInstallRequest, AppInstallResult, AppInterfaceBinding},
+    provisioning::{
+        AgentSpec, DnaSpec, InstanceSpec, ProvisionedKind, ProvisioningAction, ProvisioningChange,
+        ProvisioningReport, ProvisioningSpec,
+    },
+    reload::ConfigChanges,
+    signal_history::{ReplayedSignal, SignalHistory, SignalReplay},
+    stats::{ConductorStatistics, InstanceStatistics, NetworkStatistics, StorageStatistics},
+    supervisor::{spawn_instance_supervisor, SUPERVISED_THREADS},
+    tenant_admin::ConductorTenantAdmin,
     test_admin::ConductorTestAdmin,
     ui_admin::ConductorUiAdmin,
 };
@@ -2,7 +2,6 @@ use crossbeam_channel::{unbounded, Sender};
 use holochain_core_types::error::HolochainError;
 use holochain_locksmith::Mutex;
 use lib3h_sodium::secbuf::SecBuf;
-#[cfg(unix)]
 use log::Level;
 use std::{
     io::{self, Write},
@@ -23,12 +22,21 @@ pub trait PassphraseService {
     fn request_passphrase(&self) -> Result<SecBuf, HolochainError>;
 }
 
+/// Hands out the passphrase for keystores, asking the [PassphraseService] if needed.
+///
+/// If a session duration is set, the passphrase is only kept for that long after it was
+/// provided. Once the session is over (or [lock] gets called) the manager is locked:
+/// it won't ask the service anymore and every request fails with
+/// [HolochainError::KeystoreLocked] until the passphrase is handed in again through [unlock].
 #[derive(Clone)]
 pub struct PassphraseManager {
     passphrase_cache: Arc<Mutex<Option<SecBuf>>>,
     passphrase_service: Arc<Mutex<dyn PassphraseService + Send>>,
     last_read: Arc<Mutex<Instant>>,
     timeout_kill_switch: Sender<()>,
+    locked: Arc<Mutex<bool>>,
+    session_duration: Arc<Mutex<Option<Duration>>>,
+    session_end: Arc<Mutex<Option<Instant>>>,
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
@@ -40,6 +48,9 @@ impl PassphraseManager {
             passphrase_service,
             last_read: Arc::new(Mutex::new(Instant::now())),
             timeout_kill_switch: kill_switch_tx,
+            locked: Arc::new(Mutex::new(false)),
+            session_duration: Arc::new(Mutex::new(None)),
+            session_end: Arc::new(Mutex::new(None)),
         };
 
         let pm_clone = pm.clone();
@@ -51,7 +62,13 @@ impl PassphraseManager {
                     return;
                 }
 
-                if pm_clone.passphrase_cache.lock().unwrap().is_some() {
+                let session_end = *pm_clone.session_end.lock().unwrap();
+                if let Some(session_end) = session_end {
+                    if Instant::now() > session_end {
+                        log_info!("Keystore session timed out, locking keystore");
+                        pm_clone.lock();
+                    }
+                } else if pm_clone.passphrase_cache.lock().unwrap().is_some() {
                     let duration_since_last_read =
                         Instant::now().duration_since(*pm_clone.last_read.lock().unwrap());
 
@@ -68,7 +85,17 @@ impl PassphraseManager {
         pm
     }
 
+    /// Sets how long the passphrase is kept after it was provided.
+    /// Without a session duration the manager never locks on its own.
+    pub fn with_session_duration(self, session_duration: Option<Duration>) -> Self {
+        *self.session_duration.lock().unwrap() = session_duration;
+        self
+    }
+
     pub fn get_passphrase(&self) -> Result<SecBuf, HolochainError> {
+        if self.is_locked() {
+            return Err(HolochainError::KeystoreLocked);
+        }
         let mut passphrase = self.passphrase_cache.lock().unwrap();
         if passphrase.is_none() {
             *passphrase = Some(
@@ -77,6 +104,7 @@ impl PassphraseManager {
                     .unwrap()
                     .request_passphrase()?,
             );
+            self.start_session();
         }
 
         *(self.last_read.lock().unwrap()) = Instant::now();
@@ -95,6 +123,33 @@ impl PassphraseManager {
         let mut passphrase = self.passphrase_cache.lock().unwrap();
         *passphrase = None;
     }
+
+    fn start_session(&self) {
+        *self.session_end.lock().unwrap() = self
+            .session_duration
+            .lock()
+            .unwrap()
+            .map(|duration| Instant::now() + duration);
+    }
+
+    /// Forgets the passphrase and refuses to hand it out until [unlock] is called.
+    pub fn lock(&self) {
+        self.forget_passphrase();
+        *self.session_end.lock().unwrap() = None;
+        *self.locked.lock().unwrap() = true;
+    }
+
+    /// Takes the passphrase and starts a new session with it.
+    pub fn unlock(&self, passphrase: SecBuf) {
+        *self.passphrase_cache.lock().unwrap() = Some(passphrase);
+        *self.last_read.lock().unwrap() = Instant::now();
+        self.start_session();
+        *self.locked.lock().unwrap() = false;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        *self.locked.lock().unwrap()
+    }
 }
 
 impl Drop for PassphraseManager {
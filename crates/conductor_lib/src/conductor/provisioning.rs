@@ -0,0 +1,676 @@
+//! Converging the conductor to a declarative spec of what it should run.
+//!
+//! A [ProvisioningSpec] lists agents, DNAs, instances, interfaces and bridges. The conductor
+//! compares it to its config and plans the steps that make the config match, which are only
+//! reported for a dry run and applied otherwise. Applying the same spec again plans nothing,
+//! so deployment tools can apply their spec whenever they like.
+//!
+//! Things that are configured but not in the spec are kept, unless the spec sets `prune`.
+//! Admin interfaces and the DPKI instance never get pruned, so that the conductor stays
+//! manageable.
+use crate::{
+    conductor::{base::notify, Conductor, ConductorAdmin},
+    config::{
+        Bridge, InstanceConfiguration, InstanceReferenceConfiguration, InterfaceConfiguration,
+    },
+    keystore::Keystore,
+};
+use holochain_core_types::error::HolochainError;
+use holochain_persistence_api::hash::HashString;
+use std::path::PathBuf;
+
+/// An agent whose keys get derived from a seed, see `admin/agent/add_service`
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AgentSpec {
+    pub id: String,
+    /// Defaults to the ID
+    #[serde(default)]
+    pub name: Option<String>,
+    pub seed_file: PathBuf,
+}
+
+/// A DNA to install from a file, or by its hash from a URL or the DNA registry
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DnaSpec {
+    pub id: String,
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Expected hash of the DNA. Required unless the DNA is installed from a file.
+    #[serde(default)]
+    pub hash: Option<HashString>,
+    #[serde(default)]
+    pub properties: Option<serde_json::Value>,
+    #[serde(default)]
+    pub uuid: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct InstanceSpec {
+    pub id: String,
+    pub dna: String,
+    pub agent: String,
+    /// Storage type as in `admin/instance/add`, defaults to lmdb
+    #[serde(default)]
+    pub storage: Option<String>,
+    #[serde(default)]
+    pub membrane_proof: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProvisioningSpec {
+    #[serde(default)]
+    pub agents: Vec<AgentSpec>,
+    #[serde(default)]
+    pub dnas: Vec<DnaSpec>,
+    #[serde(default)]
+    pub instances: Vec<InstanceSpec>,
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceConfiguration>,
+    #[serde(default)]
+    pub bridges: Vec<Bridge>,
+    /// Remove what is configured but not in the spec
+    #[serde(default)]
+    pub prune: bool,
+    /// Only report what would be done
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvisioningChange {
+    Add,
+    Remove,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvisionedKind {
+    Agent,
+    Dna,
+    Instance,
+    Interface,
+    /// An instance in an interface, with the ID `<interface ID>/<instance ID>`
+    InterfaceInstance,
+    /// A bridge, with the ID `<caller ID>/<callee ID>`
+    Bridge,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProvisioningAction {
+    pub change: ProvisioningChange,
+    pub kind: ProvisionedKind,
+    pub id: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProvisioningReport {
+    pub dry_run: bool,
+    /// Actions taken, or that would be taken for a dry run, in order.
+    /// Something that gets replaced is removed and added again.
+    pub actions: Vec<ProvisioningAction>,
+    /// Why applying the spec stopped before all actions were taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Steps in the order they have to be taken: everything that depends on what gets removed
+/// is removed first, and everything that is added only after what it depends on.
+#[derive(Clone, Debug)]
+enum ProvisioningStep {
+    RemoveBridge(String, String),
+    RemoveFromInterface(String, String),
+    RemoveInterface(String),
+    RemoveInstance(String),
+    RemoveDna(String),
+    RemoveAgent(String),
+    AddAgent(AgentSpec),
+    AddDna(DnaSpec),
+    AddInstance(InstanceSpec),
+    AddInterface(InterfaceConfiguration),
+    AddToInterface(String, InstanceReferenceConfiguration),
+    AddBridge(Bridge),
+}
+
+impl ProvisioningStep {
+    fn rank(&self) -> u8 {
+        match self {
+            ProvisioningStep::RemoveBridge(..) => 0,
+            ProvisioningStep::RemoveFromInterface(..) => 1,
+            ProvisioningStep::RemoveInterface(_) => 2,
+            ProvisioningStep::RemoveInstance(_) => 3,
+            ProvisioningStep::RemoveDna(_) => 4,
+            ProvisioningStep::RemoveAgent(_) => 5,
+            ProvisioningStep::AddAgent(_) => 6,
+            ProvisioningStep::AddDna(_) => 7,
+            ProvisioningStep::AddInstance(_) => 8,
+            ProvisioningStep::AddInterface(_) => 9,
+            ProvisioningStep::AddToInterface(..) => 10,
+            ProvisioningStep::AddBridge(_) => 11,
+        }
+    }
+
+    fn action(&self) -> ProvisioningAction {
+        let (change, kind, id) = match self {
+            ProvisioningStep::RemoveBridge(caller, callee) => (
+                ProvisioningChange::Remove,
+                ProvisionedKind::Bridge,
+                format!("{}/{}", caller, callee),
+            ),
+            ProvisioningStep::RemoveFromInterface(interface, instance) => (
+                ProvisioningChange::Remove,
+                ProvisionedKind::InterfaceInstance,
+                format!("{}/{}", interface, instance),
+            ),
+            ProvisioningStep::RemoveInterface(id) => (
+                ProvisioningChange::Remove,
+                ProvisionedKind::Interface,
+                id.clone(),
+            ),
+            ProvisioningStep::RemoveInstance(id) => (
+                ProvisioningChange::Remove,
+                ProvisionedKind::Instance,
+                id.clone(),
+            ),
+            ProvisioningStep::RemoveDna(id) => {
+                (ProvisioningChange::Remove, ProvisionedKind::Dna, id.clone())
+            }
+            ProvisioningStep::RemoveAgent(id) => (
+                ProvisioningChange::Remove,
+                ProvisionedKind::Agent,
+                id.clone(),
+            ),
+            ProvisioningStep::AddAgent(agent) => (
+                ProvisioningChange::Add,
+                ProvisionedKind::Agent,
+                agent.id.clone(),
+            ),
+            ProvisioningStep::AddDna(dna) => (
+                ProvisioningChange::Add,
+                ProvisionedKind::Dna,
+                dna.id.clone(),
+            ),
+            ProvisioningStep::AddInstance(instance) => (
+                ProvisioningChange::Add,
+                ProvisionedKind::Instance,
+                instance.id.clone(),
+            ),
+            ProvisioningStep::AddInterface(interface) => (
+                ProvisioningChange::Add,
+                ProvisionedKind::Interface,
+                interface.id.clone(),
+            ),
+            ProvisioningStep::AddToInterface(interface, instance) => (
+                ProvisioningChange::Add,
+                ProvisionedKind::InterfaceInstance,
+                format!("{}/{}", interface, instance.id),
+            ),
+            ProvisioningStep::AddBridge(bridge) => (
+                ProvisioningChange::Add,
+                ProvisionedKind::Bridge,
+                format!("{}/{}", bridge.caller_id, bridge.callee_id),
+            ),
+        };
+        ProvisioningAction { change, kind, id }
+    }
+}
+
+/// An interface's config apart from its instances
+fn without_instances(interface: &InterfaceConfiguration) -> InterfaceConfiguration {
+    InterfaceConfiguration {
+        instances: Vec::new(),
+        ..interface.clone()
+    }
+}
+
+impl Conductor {
+    /// Makes the conductor run what the spec lists, see [provisioning](self). Fails without
+    /// changing anything if the spec conflicts with the config. Otherwise the report lists
+    /// the actions taken, up to the one that failed if any did.
+    pub fn provision(
+        &mut self,
+        spec: ProvisioningSpec,
+    ) -> Result<ProvisioningReport, HolochainError> {
+        let steps = self.plan_provisioning(&spec)?;
+        let mut report = ProvisioningReport {
+            dry_run: spec.dry_run,
+            actions: Vec::new(),
+            error: None,
+        };
+        if spec.dry_run {
+            report.actions = steps.iter().map(ProvisioningStep::action).collect();
+            return Ok(report);
+        }
+        for step in steps {
+            match self.take_provisioning_step(&step) {
+                Ok(()) => report.actions.push(step.action()),
+                Err(error) => {
+                    notify(format!(
+                        "Provisioning stopped at {:?}: {}",
+                        step.action(),
+                        error
+                    ));
+                    report.error = Some(error.to_string());
+                    break;
+                }
+            }
+        }
+        notify(format!(
+            "Provisioned conductor with {} actions",
+            report.actions.len()
+        ));
+        Ok(report)
+    }
+
+    fn plan_provisioning(
+        &self,
+        spec: &ProvisioningSpec,
+    ) -> Result<Vec<ProvisioningStep>, HolochainError> {
+        let config = &self.config;
+        let dpki_instance = config.dpki.as_ref().map(|dpki| dpki.instance_id.clone());
+        let dpki_instance_config = dpki_instance
+            .as_ref()
+            .and_then(|id| config.instance_by_id(id));
+        let mut steps = Vec::new();
+
+        // Agents and DNAs can't be changed, only added and removed
+        for agent in spec.agents.iter() {
+            match config.agent_by_id(&agent.id) {
+                None => steps.push(ProvisioningStep::AddAgent(agent.clone())),
+                Some(agent_config) => {
+                    let (_, public_address) = Keystore::new_from_seed_file(
+                        agent.seed_file.clone(),
+                        self.hash_config.clone(),
+                    )?;
+                    if agent_config.public_address != public_address {
+                        return Err(HolochainError::ConfigError(format!(
+                            "Agent \"{}\" exists with another key than the one of its seed",
+                            agent.id
+                        )));
+                    }
+                }
+            }
+        }
+        for dna in spec.dnas.iter() {
+            if dna.path.is_none() && dna.hash.is_none() {
+                return Err(HolochainError::ConfigError(format!(
+                    "DNA \"{}\" needs a path or a hash",
+                    dna.id
+                )));
+            }
+            match config.dna_by_id(&dna.id) {
+                None => steps.push(ProvisioningStep::AddDna(dna.clone())),
+                Some(dna_config) => {
+                    if let Some(ref hash) = dna.hash {
+                        if dna_config.hash != hash.to_string() {
+                            return Err(HolochainError::ConfigError(format!(
+                                "DNA \"{}\" is installed with hash {}, not {}. Install the new DNA with another ID.",
+                                dna.id, dna_config.hash, hash
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Instances of another DNA or agent get replaced
+        let mut gone_instances = Vec::new();
+        let mut pruned_instances = Vec::new();
+        for instance in spec.instances.iter() {
+            match config.instance_by_id(&instance.id) {
+                None => steps.push(ProvisioningStep::AddInstance(instance.clone())),
+                Some(instance_config) => {
+                    if instance_config.dna != instance.dna
+                        || instance_config.agent != instance.agent
+                    {
+                        steps.push(ProvisioningStep::RemoveInstance(instance.id.clone()));
+                        steps.push(ProvisioningStep::AddInstance(instance.clone()));
+                        gone_instances.push(instance.id.clone());
+                    }
+                }
+            }
+        }
+        if spec.prune {
+            for instance_config in config.instances.iter() {
+                let in_spec = spec.instances.iter().any(|i| i.id == instance_config.id);
+                if !in_spec && Some(&instance_config.id) != dpki_instance.as_ref() {
+                    steps.push(ProvisioningStep::RemoveInstance(instance_config.id.clone()));
+                    gone_instances.push(instance_config.id.clone());
+                    pruned_instances.push(instance_config.id.clone());
+                }
+            }
+            // What the remaining instances use stays
+            let dpki_uses = |uses: &dyn Fn(&InstanceConfiguration) -> bool| {
+                dpki_instance_config.as_ref().map(uses).unwrap_or(false)
+            };
+            for dna_config in config.dnas.iter() {
+                let in_spec = spec.dnas.iter().any(|dna| dna.id == dna_config.id);
+                let used = spec.instances.iter().any(|i| i.dna == dna_config.id)
+                    || dpki_uses(&|dpki| dpki.dna == dna_config.id);
+                if !in_spec && !used {
+                    steps.push(ProvisioningStep::RemoveDna(dna_config.id.clone()));
+                }
+            }
+            for agent_config in config.agents.iter() {
+                let in_spec = spec.agents.iter().any(|agent| agent.id == agent_config.id);
+                let used = spec.instances.iter().any(|i| i.agent == agent_config.id)
+                    || dpki_uses(&|dpki| dpki.agent == agent_config.id);
+                if !in_spec && !used {
+                    steps.push(ProvisioningStep::RemoveAgent(agent_config.id.clone()));
+                }
+            }
+        }
+        // Instances that get replaced have to be added to interfaces and bridges again
+        let gone = |id: &String| gone_instances.contains(id);
+        let pruned = |id: &String| pruned_instances.contains(id);
+
+        // Interfaces with other settings get replaced, otherwise only their instances change
+        let mut kept_interfaces = Vec::new();
+        for interface in spec.interfaces.iter() {
+            match config.interface_by_id(&interface.id) {
+                None => steps.push(ProvisioningStep::AddInterface(interface.clone())),
+                Some(ref interface_config)
+                    if without_instances(interface_config) != without_instances(interface) =>
+                {
+                    steps.push(ProvisioningStep::RemoveInterface(interface.id.clone()));
+                    steps.push(ProvisioningStep::AddInterface(interface.clone()));
+                }
+                Some(interface_config) => {
+                    let mut wanted = interface.instances.clone();
+                    if !spec.prune {
+                        wanted.extend(
+                            interface_config
+                                .instances
+                                .iter()
+                                .filter(|r| !interface.instances.iter().any(|w| w.id == r.id))
+                                .cloned(),
+                        );
+                    }
+                    kept_interfaces.push((interface_config, wanted));
+                }
+            }
+        }
+        for interface_config in config.interfaces.iter() {
+            if spec.interfaces.iter().any(|i| i.id == interface_config.id) {
+                continue;
+            }
+            if spec.prune && !interface_config.admin {
+                steps.push(ProvisioningStep::RemoveInterface(
+                    interface_config.id.clone(),
+                ));
+            } else {
+                let wanted = interface_config.instances.clone();
+                kept_interfaces.push((interface_config.clone(), wanted));
+            }
+        }
+        for (interface_config, wanted) in kept_interfaces {
+            let id = &interface_config.id;
+            let wanted: Vec<_> = wanted.into_iter().filter(|r| !pruned(&r.id)).collect();
+            for reference in wanted.iter() {
+                match interface_config
+                    .instances
+                    .iter()
+                    .find(|r| r.id == reference.id)
+                {
+                    Some(existing) if existing == reference && !gone(&reference.id) => (),
+                    Some(_) if !gone(&reference.id) => {
+                        steps.push(ProvisioningStep::RemoveFromInterface(
+                            id.clone(),
+                            reference.id.clone(),
+                        ));
+                        steps.push(ProvisioningStep::AddToInterface(
+                            id.clone(),
+                            reference.clone(),
+                        ));
+                    }
+                    _ => steps.push(ProvisioningStep::AddToInterface(
+                        id.clone(),
+                        reference.clone(),
+                    )),
+                }
+            }
+            for existing in interface_config.instances.iter() {
+                if !wanted.iter().any(|r| r.id == existing.id) && !gone(&existing.id) {
+                    steps.push(ProvisioningStep::RemoveFromInterface(
+                        id.clone(),
+                        existing.id.clone(),
+                    ));
+                }
+            }
+        }
+
+        // Bridges of replaced instances get added again
+        let mut wanted_bridges = spec.bridges.clone();
+        if !spec.prune {
+            wanted_bridges.extend(
+                config
+                    .bridges
+                    .iter()
+                    .filter(|b| {
+                        !spec
+                            .bridges
+                            .iter()
+                            .any(|w| w.caller_id == b.caller_id && w.callee_id == b.callee_id)
+                    })
+                    .cloned(),
+            );
+        }
+        for bridge in config.bridges.iter() {
+            let wanted = wanted_bridges.contains(bridge);
+            let touches_gone = gone(&bridge.caller_id) || gone(&bridge.callee_id);
+            if !wanted || touches_gone {
+                steps.push(ProvisioningStep::RemoveBridge(
+                    bridge.caller_id.clone(),
+                    bridge.callee_id.clone(),
+                ));
+            }
+        }
+        for bridge in wanted_bridges {
+            let touches_gone = gone(&bridge.caller_id) || gone(&bridge.callee_id);
+            if !config.bridges.contains(&bridge) || touches_gone {
+                steps.push(ProvisioningStep::AddBridge(bridge));
+            }
+        }
+
+        steps.sort_by_key(ProvisioningStep::rank);
+        Ok(steps)
+    }
+
+    fn take_provisioning_step(&mut self, step: &ProvisioningStep) -> Result<(), HolochainError> {
+        match step {
+            ProvisioningStep::RemoveBridge(caller, callee) => self.remove_bridge(caller, callee),
+            ProvisioningStep::RemoveFromInterface(interface, instance) => {
+                self.remove_instance_from_interface(interface, instance)
+            }
+            ProvisioningStep::RemoveInterface(id) => self.remove_interface(id),
+            ProvisioningStep::RemoveInstance(id) => self.remove_instance(id),
+            ProvisioningStep::RemoveDna(id) => self.uninstall_dna(id),
+            ProvisioningStep::RemoveAgent(id) => self.remove_agent(id),
+            ProvisioningStep::AddAgent(agent) => self
+                .add_service_agent(
+                    agent.id.clone(),
+                    agent.name.clone().unwrap_or_else(|| agent.id.clone()),
+                    agent.seed_file.clone(),
+                )
+                .map(|_| ()),
+            ProvisioningStep::AddDna(dna) => match (&dna.path, &dna.hash) {
+                (Some(path), _) => self.install_dna_from_file(
+                    path.clone(),
+                    dna.id.clone(),
+                    true,
+                    dna.hash.clone(),
+                    dna.properties.as_ref(),
+                    dna.uuid.clone(),
+                ),
+                (None, Some(hash)) => self.install_dna_from_url(
+                    dna.url.as_ref().map(String::as_str),
+                    hash.clone(),
+                    dna.id.clone(),
+                    dna.properties.as_ref(),
+                    dna.uuid.clone(),
+                ),
+                (None, None) => Err(HolochainError::ConfigError(format!(
+                    "DNA \"{}\" needs a path or a hash",
+                    dna.id
+                ))),
+            }
+            .map(|_| ()),
+            ProvisioningStep::AddInstance(instance) => {
+                self.add_instance(
+                    &instance.id,
+                    &instance.dna,
+                    &instance.agent,
+                    instance.storage.as_ref().map(String::as_str),
+                    instance.membrane_proof.clone(),
+                )?;
+                self.start_instance(&instance.id)
+                    .map_err(|error| HolochainError::ErrorGeneric(error.to_string()))
+            }
+            ProvisioningStep::AddInterface(interface) => self.add_interface(interface.clone()),
+            ProvisioningStep::AddToInterface(interface, reference) => {
+                self.add_instance_to_interface(interface, &reference.id, &reference.alias)
+            }
+            ProvisioningStep::AddBridge(bridge) => self.add_bridge(bridge.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::conductor::admin::tests::create_test_conductor;
+
+    fn instance_spec(id: &str, agent: &str) -> InstanceSpec {
+        InstanceSpec {
+            id: id.to_string(),
+            dna: String::from("test-dna"),
+            agent: agent.to_string(),
+            storage: Some(String::from("memory")),
+            membrane_proof: None,
+        }
+    }
+
+    fn added(kind: ProvisionedKind, id: &str) -> ProvisioningAction {
+        ProvisioningAction {
+            change: ProvisioningChange::Add,
+            kind,
+            id: id.to_string(),
+        }
+    }
+
+    fn removed(kind: ProvisionedKind, id: &str) -> ProvisioningAction {
+        ProvisioningAction {
+            change: ProvisioningChange::Remove,
+            kind,
+            id: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_provision_converges() {
+        let test_name = "test_provision_converges";
+        let mut conductor = create_test_conductor(test_name, 3903);
+        let mut interface = conductor.config().interfaces[0].clone();
+        interface.instances.push(InstanceReferenceConfiguration {
+            id: String::from("provisioned"),
+            alias: Some(String::from("app")),
+        });
+        let spec = ProvisioningSpec {
+            instances: vec![instance_spec("provisioned", "test-agent-1")],
+            interfaces: vec![interface],
+            dry_run: true,
+            ..ProvisioningSpec::default()
+        };
+
+        let expected = vec![
+            added(ProvisionedKind::Instance, "provisioned"),
+            added(
+                ProvisionedKind::InterfaceInstance,
+                "websocket interface/provisioned",
+            ),
+        ];
+        let report = conductor.provision(spec.clone()).unwrap();
+        assert_eq!(report.actions, expected);
+        assert!(conductor.config().instance_by_id("provisioned").is_none());
+
+        let report = conductor
+            .provision(ProvisioningSpec {
+                dry_run: false,
+                ..spec.clone()
+            })
+            .unwrap();
+        assert_eq!(report.actions, expected);
+        assert_eq!(report.error, None);
+        assert!(conductor.config().instance_by_id("provisioned").is_some());
+
+        // Nothing left to do
+        let report = conductor
+            .provision(ProvisioningSpec {
+                dry_run: false,
+                ..spec.clone()
+            })
+            .unwrap();
+        assert_eq!(report.actions, Vec::new());
+
+        // Another agent replaces the instance, which goes back into the interface
+        let report = conductor
+            .provision(ProvisioningSpec {
+                instances: vec![instance_spec("provisioned", "test-agent-2")],
+                dry_run: false,
+                ..spec
+            })
+            .unwrap();
+        assert_eq!(
+            report.actions,
+            vec![
+                removed(ProvisionedKind::Instance, "provisioned"),
+                added(ProvisionedKind::Instance, "provisioned"),
+                added(
+                    ProvisionedKind::InterfaceInstance,
+                    "websocket interface/provisioned"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_provision_prunes() {
+        let test_name = "test_provision_prunes";
+        let mut conductor = create_test_conductor(test_name, 3904);
+        let spec = ProvisioningSpec {
+            instances: vec![instance_spec("test-instance-1", "test-agent-1")],
+            prune: true,
+            dry_run: true,
+            ..ProvisioningSpec::default()
+        };
+        let report = conductor.provision(spec).unwrap();
+        // The admin interface stays, but loses the pruned instance with it
+        assert_eq!(
+            report.actions,
+            vec![
+                removed(ProvisionedKind::Instance, "test-instance-2"),
+                removed(ProvisionedKind::Agent, "test-agent-2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_provision_refuses_conflicts() {
+        let test_name = "test_provision_refuses_conflicts";
+        let mut conductor = create_test_conductor(test_name, 3905);
+        let spec = ProvisioningSpec {
+            dnas: vec![DnaSpec {
+                id: String::from("test-dna"),
+                path: None,
+                url: None,
+                hash: Some(HashString::from("QmAnotherDna")),
+                properties: None,
+                uuid: None,
+            }],
+            ..ProvisioningSpec::default()
+        };
+        assert!(conductor.provision(spec).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    conductor::{base::notify, Conductor},
+    config::{load_configuration, Configuration},
+};
+use holochain_core_types::error::HolochainError;
+use holochain_locksmith::RwLock;
+use serde::Serialize;
+use std::{collections::HashSet, fs, path::PathBuf, sync::Arc, thread::sleep, time::Duration};
+
+/// Time to give a stopped interface to release its port before it gets started again
+const INTERFACE_RESTART_DELAY_MS: u64 = 500;
+
+/// Settings that only take effect when the conductor gets started
+const RESTART_ONLY_SECTIONS: &[&str] = &[
+    "logger",
+    "tracing",
+    "network",
+    "persistence_dir",
+    "signing_service_uri",
+    "encryption_service_uri",
+    "decryption_service_uri",
+    "dpki",
+    "signals",
+    "passphrase_service",
+    "keystore_session_duration",
+    "metric_publisher",
+    "metrics_endpoint",
+    "security_audit_log",
+    "event_log",
+    "ui_bundles",
+    "ui_interfaces",
+    "dev_mode",
+];
+
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// What reloading a configuration changes in the running conductor
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ConfigChanges {
+    pub instances_added: Vec<String>,
+    pub instances_removed: Vec<String>,
+    /// Instances whose configuration, DNA, agent or bridges changed. They get stopped and
+    /// instantiated again, with the same storage.
+    pub instances_restarted: Vec<String>,
+    pub interfaces_added: Vec<String>,
+    pub interfaces_removed: Vec<String>,
+    /// Interfaces whose configuration changed or that serve a restarted instance
+    pub interfaces_restarted: Vec<String>,
+    /// Changed settings that only take effect when the conductor gets restarted
+    pub restart_required: Vec<String>,
+}
+
+impl ConfigChanges {
+    pub fn between(old: &Configuration, new: &Configuration) -> Self {
+        let mut changes = ConfigChanges::default();
+
+        for instance in &new.instances {
+            match old.instance_by_id(&instance.id) {
+                None => changes.instances_added.push(instance.id.clone()),
+                Some(old_instance) => {
+                    let callee_ids = |config: &Configuration| -> Vec<String> {
+                        let mut ids: Vec<String> = config
+                            .bridges
+                            .iter()
+                            .filter(|bridge| bridge.caller_id == instance.id)
+                            .map(|bridge| format!("{}:{}", bridge.handle, bridge.callee_id))
+                            .collect();
+                        ids.sort();
+                        ids
+                    };
+                    let unchanged = same(&old_instance, instance)
+                        && same(&old.dna_by_id(&instance.dna), &new.dna_by_id(&instance.dna))
+                        && same(
+                            &old.agent_by_id(&instance.agent),
+                            &new.agent_by_id(&instance.agent),
+                        )
+                        && callee_ids(old) == callee_ids(new);
+                    if !unchanged {
+                        changes.instances_restarted.push(instance.id.clone());
+                    }
+                }
+            }
+        }
+        changes.instances_removed = old
+            .instances
+            .iter()
+            .filter(|instance| new.instance_by_id(&instance.id).is_none())
+            .map(|instance| instance.id.clone())
+            .collect();
+
+        let restarted: HashSet<&String> = changes.instances_restarted.iter().collect();
+        for interface in &new.interfaces {
+            match old.interface_by_id(&interface.id) {
+                None => changes.interfaces_added.push(interface.id.clone()),
+                Some(old_interface) => {
+                    let serves_restarted_instance = interface
+                        .instances
+                        .iter()
+                        .any(|instance| restarted.contains(&instance.id));
+                    if !same(&old_interface, interface) || serves_restarted_instance {
+                        changes.interfaces_restarted.push(interface.id.clone());
+                    }
+                }
+            }
+        }
+        changes.interfaces_removed = old
+            .interfaces
+            .iter()
+            .filter(|interface| new.interface_by_id(&interface.id).is_none())
+            .map(|interface| interface.id.clone())
+            .collect();
+
+        if let (Ok(old), Ok(new)) = (serde_json::to_value(old), serde_json::to_value(new)) {
+            changes.restart_required = RESTART_ONLY_SECTIONS
+                .iter()
+                .filter(|section| old.get(**section) != new.get(**section))
+                .map(|section| section.to_string())
+                .collect();
+        }
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == ConfigChanges::default()
+    }
+}
+
+impl Conductor {
+    /// Loads the configuration from the given file, or the one the conductor saves its
+    /// configuration to, and applies the changes to the running conductor: new instances
+    /// get started, removed ones stopped and changed ones restarted, and the same for
+    /// interfaces. Instances and interfaces that didn't change keep running, so they stay
+    /// connected to the network and to their clients.
+    /// Changes to settings like the network or the logger are only reported as needing a
+    /// restart of the conductor.
+    pub fn reload_config(
+        &mut self,
+        path: Option<PathBuf>,
+    ) -> Result<ConfigChanges, HolochainError> {
+        let path = path.unwrap_or_else(|| self.config_path());
+        let toml = fs::read_to_string(&path).map_err(|e| {
+            HolochainError::ConfigError(format!("Could not read {:?}: {}", path, e))
+        })?;
+        let mut new_config: Configuration = load_configuration(&toml)?;
+        new_config.check_consistency(&mut self.dna_loader)?;
+        let changes = ConfigChanges::between(&self.config, &new_config);
+        for section in &changes.restart_required {
+            notify(format!(
+                "Configuration of {} changed, which takes effect when the conductor gets restarted",
+                section
+            ));
+        }
+
+        let stopped_interfaces: Vec<&String> = changes
+            .interfaces_removed
+            .iter()
+            .chain(changes.interfaces_restarted.iter())
+            .collect();
+        for id in &stopped_interfaces {
+            let _ = self.stop_interface_by_id(id);
+        }
+        for id in changes
+            .instances_removed
+            .iter()
+            .chain(changes.instances_restarted.iter())
+        {
+            if let Err(error) = self.stop_instance(id) {
+                notify(format!("Error stopping instance {}: {}", id, error));
+            }
+            if let Some(instance) = self.instances.remove(id) {
+                instance.write().unwrap().kill();
+            }
+        }
+
+        self.config = new_config;
+        let new_instances: HashSet<&String> = changes
+            .instances_added
+            .iter()
+            .chain(changes.instances_restarted.iter())
+            .collect();
+        let sorted_ids = self.config.instance_ids_sorted_by_bridge_dependencies()?;
+        for id in sorted_ids.iter().filter(|id| new_instances.contains(id)) {
+            let instance = self.instantiate_from_config(id).map_err(|error| {
+                HolochainError::ConfigError(format!(
+                    "Error while trying to create instance \"{}\": {}",
+                    id, error
+                ))
+            })?;
+            self.instances
+                .insert(id.clone(), Arc::new(RwLock::new(instance)));
+            self.start_instance(id)?;
+        }
+
+        if !stopped_interfaces.is_empty() {
+            sleep(Duration::from_millis(INTERFACE_RESTART_DELAY_MS));
+        }
+        for id in changes
+            .interfaces_added
+            .iter()
+            .chain(changes.interfaces_restarted.iter())
+        {
+            self.start_interface_by_id(id)
+                .map_err(HolochainError::ConfigError)?;
+        }
+        let _ = self.start_signal_multiplexer();
+
+        notify(format!("Reloaded configuration from {:?}", path));
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{conductor::base::tests::test_toml, config::InterfaceDriver};
+
+    #[test]
+    fn test_config_changes_between() {
+        let old: Configuration = load_configuration(&test_toml(10001, 10002)).unwrap();
+        assert!(ConfigChanges::between(&old, &old).is_empty());
+
+        let mut new = old.clone();
+        let changed_instance = new.instances[0].id.clone();
+        new.instances[0].membrane_proof = Some(String::from("proof"));
+        let removed_instance = new.instances.pop().unwrap().id;
+        let mut added_instance = new.instances[0].clone();
+        added_instance.id = String::from("added-instance");
+        new.instances.push(added_instance);
+        new.interfaces[0].driver = InterfaceDriver::Websocket { port: 10003 };
+        new.dev_mode = !old.dev_mode;
+
+        let changes = ConfigChanges::between(&old, &new);
+        assert_eq!(
+            changes.instances_added,
+            vec![String::from("added-instance")]
+        );
+        assert_eq!(changes.instances_removed, vec![removed_instance]);
+        assert_eq!(changes.instances_restarted, vec![changed_instance.clone()]);
+        assert!(changes.interfaces_added.is_empty());
+        assert!(changes.interfaces_removed.is_empty());
+        // The first interface changed, the others are restarted if they serve the
+        // changed instance
+        assert!(changes.interfaces_restarted.contains(&new.interfaces[0].id));
+        for interface in new.interfaces.iter().skip(1) {
+            assert_eq!(
+                changes.interfaces_restarted.contains(&interface.id),
+                interface
+                    .instances
+                    .iter()
+                    .any(|instance| instance.id == changed_instance)
+            );
+        }
+        assert_eq!(changes.restart_required, vec![String::from("dev_mode")]);
+    }
+}
@@ -0,0 +1,153 @@
+//! Recent user signals of each instance, so that clients can catch up after reconnecting.
+//!
+//! The signal multiplexer numbers the user signals of each instance and sends the number
+//! along as `sequence`. A client that lost its connection asks for everything since the last
+//! number it saw with the `signals/replay` method of its interface. Only the last
+//! `signals.history_size` signals of each instance are kept, and the replay says how many of
+//! the requested ones were dropped already.
+use holochain_core::signal::Signal;
+use holochain_locksmith::Mutex;
+use std::collections::{HashMap, VecDeque};
+
+struct InstanceSignals {
+    next_sequence: u64,
+    signals: VecDeque<(u64, Signal)>,
+}
+
+pub struct SignalHistory {
+    capacity: usize,
+    instances: Mutex<HashMap<String, InstanceSignals>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReplayedSignal {
+    pub sequence: u64,
+    pub signal: Signal,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SignalReplay {
+    pub signals: Vec<ReplayedSignal>,
+    /// Sequence number the next signal of the instance will get
+    pub next_sequence: u64,
+    /// How many of the requested signals are no longer in the history
+    pub missed: u64,
+}
+
+impl SignalHistory {
+    pub fn new(capacity: usize) -> Self {
+        SignalHistory {
+            capacity,
+            instances: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keeps the signal, dropping the oldest one of the instance if the history is full,
+    /// and returns its sequence number
+    pub fn record(&self, instance_id: &str, signal: Signal) -> u64 {
+        let mut instances = self.instances.lock().unwrap();
+        let history = instances
+            .entry(instance_id.to_string())
+            .or_insert_with(|| InstanceSignals {
+                next_sequence: 0,
+                signals: VecDeque::new(),
+            });
+        let sequence = history.next_sequence;
+        history.next_sequence += 1;
+        if self.capacity > 0 {
+            if history.signals.len() == self.capacity {
+                history.signals.pop_front();
+            }
+            history.signals.push_back((sequence, signal));
+        }
+        sequence
+    }
+
+    /// The signals of the instance with a sequence number of at least `since`
+    pub fn replay(&self, instance_id: &str, since: u64) -> SignalReplay {
+        let instances = self.instances.lock().unwrap();
+        let history = match instances.get(instance_id) {
+            Some(history) => history,
+            None => {
+                return SignalReplay {
+                    signals: Vec::new(),
+                    next_sequence: 0,
+                    missed: 0,
+                }
+            }
+        };
+        let oldest = history
+            .signals
+            .front()
+            .map(|(sequence, _)| *sequence)
+            .unwrap_or(history.next_sequence);
+        SignalReplay {
+            signals: history
+                .signals
+                .iter()
+                .filter(|(sequence, _)| *sequence >= since)
+                .map(|(sequence, signal)| ReplayedSignal {
+                    sequence: *sequence,
+                    signal: signal.clone(),
+                })
+                .collect(),
+            next_sequence: history.next_sequence,
+            missed: oldest.saturating_sub(since),
+        }
+    }
+
+    /// Drops the history of an instance that got removed
+    pub fn forget(&self, instance_id: &str) {
+        self.instances.lock().unwrap().remove(instance_id);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core::signal::UserSignal;
+    use holochain_json_api::json::JsonString;
+
+    fn user_signal(name: &str) -> Signal {
+        Signal::User(UserSignal {
+            name: name.to_string(),
+            arguments: JsonString::from_json("{}"),
+            zome: String::from("chat"),
+        })
+    }
+
+    fn names(replay: &SignalReplay) -> Vec<String> {
+        replay
+            .signals
+            .iter()
+            .map(|replayed| match replayed.signal {
+                Signal::User(ref signal) => format!("{}:{}", replayed.sequence, signal.name),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replays_recent_signals_since_a_sequence_number() {
+        let history = SignalHistory::new(3);
+        for name in &["a", "b", "c", "d", "e"] {
+            history.record("app", user_signal(name));
+        }
+        history.record("other", user_signal("x"));
+
+        let replay = history.replay("app", 3);
+        assert_eq!(names(&replay), vec!["3:d", "4:e"]);
+        assert_eq!(replay.next_sequence, 5);
+        assert_eq!(replay.missed, 0);
+
+        let replay = history.replay("app", 0);
+        assert_eq!(names(&replay), vec!["2:c", "3:d", "4:e"]);
+        assert_eq!(replay.missed, 2);
+
+        assert!(history.replay("app", 5).signals.is_empty());
+        assert_eq!(names(&history.replay("other", 0)), vec!["0:x"]);
+
+        history.forget("app");
+        assert_eq!(history.replay("app", 0).next_sequence, 0);
+    }
+}
@@ -0,0 +1,243 @@
+//! Statistics of the whole conductor in one response, for dashboards and monitoring.
+//!
+//! Call counts and peers come from the [InstanceActivity](holochain_core::instance_activity)
+//! of each instance, which starts counting when the instance gets instantiated, so rates are
+//! averages over the instance's uptime. Traffic summaries come from the network state of
+//! each instance and cover the last minutes only.
+use crate::conductor::Conductor;
+use holochain_core::{network::traffic::TrafficSummary, storage_usage::StorageUsage};
+use std::{collections::BTreeMap, time::Duration};
+
+/// Peers we heard from within this time count as active
+pub const ACTIVE_PEER_WINDOW_SECS: u64 = 300;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct NetworkStatistics {
+    pub connected: bool,
+    pub active_peers: usize,
+    pub open_direct_messages: usize,
+    pub blocked_agents: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct StorageStatistics {
+    pub chain_bytes: usize,
+    pub cas_bytes: usize,
+    pub eav_bytes: usize,
+    pub cache_bytes: usize,
+    pub total_bytes: usize,
+}
+
+impl From<&StorageUsage> for StorageStatistics {
+    fn from(usage: &StorageUsage) -> Self {
+        let chain_bytes = usage.chain.total.bytes;
+        let cas_bytes = usage.cas.total.bytes;
+        let eav_bytes = usage.eav.total.bytes;
+        let cache_bytes = usage.caches.total.bytes;
+        StorageStatistics {
+            chain_bytes,
+            cas_bytes,
+            eav_bytes,
+            cache_bytes,
+            total_bytes: chain_bytes + cas_bytes + eav_bytes + cache_bytes,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct InstanceStatistics {
+    pub running: bool,
+    pub uptime_secs: u64,
+    pub zome_calls: u64,
+    pub zome_call_errors: u64,
+    pub calls_per_minute: f64,
+    /// Share of zome calls that failed, between 0 and 1
+    pub error_rate: f64,
+    pub running_zome_calls: usize,
+    /// Validations waiting in the holding queue, of which `delayed_validations` wait for
+    /// a retry
+    pub holding_queue: usize,
+    pub delayed_validations: usize,
+    pub held_entries: usize,
+    pub held_aspects: usize,
+    pub network: NetworkStatistics,
+    pub storage: StorageStatistics,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ConductorStatistics {
+    pub uptime_secs: u64,
+    pub running_instances: usize,
+    pub zome_calls: u64,
+    pub zome_call_errors: u64,
+    pub storage_bytes: usize,
+    pub instances: BTreeMap<String, InstanceStatistics>,
+}
+
+fn per_minute(count: u64, uptime: Duration) -> f64 {
+    let millis = uptime.as_millis();
+    if millis == 0 {
+        0.0
+    } else {
+        count as f64 * 60_000.0 / millis as f64
+    }
+}
+
+impl Conductor {
+    /// Collects the statistics of every configured instance. Instances that are not running
+    /// are reported with `running: false` and nothing else.
+    pub fn stats(&self) -> ConductorStatistics {
+        let mut stats = ConductorStatistics {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            ..ConductorStatistics::default()
+        };
+        for instance_config in self.config.instances.iter() {
+            let id = instance_config.id.clone();
+            let context = match self.instances.get(&id) {
+                Some(instance) if instance.read().unwrap().active() => {
+                    instance.read().unwrap().context().ok()
+                }
+                _ => None,
+            };
+            let (context, state) = match context.and_then(|c| c.state().map(|s| (c.clone(), s))) {
+                Some(context_and_state) => context_and_state,
+                None => {
+                    stats.instances.insert(id, InstanceStatistics::default());
+                    continue;
+                }
+            };
+
+            let instance_stats = match context.get_stats() {
+                Ok(instance_stats) => instance_stats,
+                Err(_) => {
+                    stats.instances.insert(id, InstanceStatistics::default());
+                    continue;
+                }
+            };
+            let activity = &context.activity;
+            let uptime = activity.uptime();
+            let zome_calls = activity.zome_calls();
+            let zome_call_errors = activity.zome_call_errors();
+            let network = state.network();
+            let storage = StorageUsage::new(&context)
+                .map(|usage| StorageStatistics::from(&usage))
+                .unwrap_or_default();
+
+            stats.running_instances += 1;
+            stats.zome_calls += zome_calls;
+            stats.zome_call_errors += zome_call_errors;
+            stats.storage_bytes += storage.total_bytes;
+            stats.instances.insert(
+                id,
+                InstanceStatistics {
+                    running: true,
+                    uptime_secs: uptime.as_secs(),
+                    zome_calls,
+                    zome_call_errors,
+                    calls_per_minute: per_minute(zome_calls, uptime),
+                    error_rate: if zome_calls == 0 {
+                        0.0
+                    } else {
+                        zome_call_errors as f64 / zome_calls as f64
+                    },
+                    running_zome_calls: instance_stats.number_running_zome_calls,
+                    holding_queue: instance_stats.number_pending_validations,
+                    delayed_validations: instance_stats.number_delayed_validations,
+                    held_entries: instance_stats.number_held_entries,
+                    held_aspects: instance_stats.number_held_aspects,
+                    network: NetworkStatistics {
+                        connected: network.initialized().is_ok(),
+                        active_peers: activity
+                            .active_peers(Duration::from_secs(ACTIVE_PEER_WINDOW_SECS)),
+                        open_direct_messages: network.direct_message_connections.len(),
+                        blocked_agents: network.blocked_agents.len(),
+                    },
+                    storage,
+                },
+            );
+        }
+        stats
+    }
+
+    /// Traffic of the running instances, or of the given one, over the last `minutes` minutes,
+    /// per peer and per message type
+    pub fn network_traffic(
+        &self,
+        instance_id: Option<&str>,
+        minutes: u64,
+    ) -> BTreeMap<String, TrafficSummary> {
+        self.config
+            .instances
+            .iter()
+            .map(|instance_config| instance_config.id.clone())
+            .filter(|id| instance_id.map_or(true, |instance_id| instance_id == id))
+            .filter_map(|id| {
+                let instance = self.instances.get(&id)?;
+                let state = instance.read().unwrap().context().ok()?.state()?;
+                let summary = state
+                    .network()
+                    .traffic
+                    .summary(state.clock().now(), minutes);
+                Some((id, summary))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::conductor::{admin::tests::create_test_conductor, ConductorAdmin};
+
+    #[test]
+    fn test_stats() {
+        let test_name = "test_stats";
+        let mut conductor = create_test_conductor(test_name, 3901);
+        conductor.start_all_instances().unwrap();
+        let id = conductor.config().instances[0].id.clone();
+        {
+            let instance = conductor.instances.get(&id).unwrap().read().unwrap();
+            let context = instance.context().unwrap();
+            context.activity.record_zome_call(true);
+            context.activity.record_zome_call(false);
+            context.activity.record_peer("some-peer");
+        }
+
+        let stats = conductor.stats();
+        assert_eq!(stats.instances.len(), conductor.config().instances.len());
+        assert_eq!(stats.running_instances, stats.instances.len());
+        let instance = &stats.instances[&id];
+        assert!(instance.running);
+        assert_eq!(instance.zome_calls, 2);
+        assert_eq!(instance.zome_call_errors, 1);
+        assert!((instance.error_rate - 0.5).abs() < std::f64::EPSILON);
+        assert_eq!(instance.network.active_peers, 1);
+        assert!(stats.zome_calls >= 2);
+
+        let traffic = conductor.network_traffic(Some(&id), 5);
+        assert_eq!(traffic.keys().collect::<Vec<_>>(), vec![&id]);
+        assert_eq!(traffic[&id].minutes, 5);
+
+        // Instances that are not running report nothing
+        let config = conductor.config();
+        conductor.remove_instance(&id).unwrap();
+        conductor
+            .add_instance(
+                &id,
+                &config.dnas[0].id,
+                &config.agents[0].id,
+                Some("memory"),
+                None,
+            )
+            .unwrap();
+        let stats = conductor.stats();
+        assert!(!stats.instances[&id].running);
+        assert_eq!(stats.instances[&id].zome_calls, 0);
+    }
+
+    #[test]
+    fn per_minute_averages_over_uptime() {
+        assert!((per_minute(10, Duration::from_secs(120)) - 5.0).abs() < std::f64::EPSILON);
+        assert!(per_minute(10, Duration::from_secs(0)).abs() < std::f64::EPSILON);
+    }
+}
@@ -0,0 +1,311 @@
+//! Supervised restarts of instances whose action loop or network thread died.
+//!
+//! [spawn_instance_supervisor] checks the instances of the mounted conductor every
+//! [SUPERVISION_INTERVAL_MS]. A crashed instance gets instantiated again from its config after
+//! the backoff of its [RestartPolicy], and left stopped once it used up its restarts.
+//! Every step gets logged and sent to admin interfaces as an `InstanceRestart` signal, and
+//! every crash gets a [crash report](super::crash_report). It also checks the
+//! [alert thresholds](super::alerts).
+use crate::{
+    conductor::{base::notify, Conductor, CONDUCTOR},
+    config::RestartPolicy,
+    signal_wrapper::{CrashCause, RestartEvent, SignalWrapper},
+};
+use holochain_core_types::error::HolochainError;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const SUPERVISION_INTERVAL_MS: u64 = 1000;
+
+/// Name prefixes of the threads whose death the supervisor recovers from
+pub const SUPERVISED_THREADS: &[&str] = &["action_loop/", "net_worker_thread/"];
+
+/// Restarts of an instance since it last ran for its policy's `reset_after_secs`
+#[derive(Default)]
+pub struct RestartState {
+    restarts: u32,
+    last_restart: Option<Instant>,
+    /// Crash that waits for its restart, and since when
+    pending: Option<(CrashCause, Instant)>,
+    gave_up: bool,
+}
+
+impl RestartState {
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+}
+
+/// Supervises the instances of the conductor mounted in [CONDUCTOR] until it gets unmounted.
+pub fn spawn_instance_supervisor() -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("instance_supervisor".to_string())
+        .spawn(|| loop {
+            thread::sleep(Duration::from_millis(SUPERVISION_INTERVAL_MS));
+            match CONDUCTOR.lock().unwrap().as_mut() {
+                Some(conductor) => {
+                    conductor.supervise_instances();
+                    conductor.check_alerts();
+                }
+                None => break,
+            }
+        })
+        .expect("Must be able to spawn thread")
+}
+
+#[allow(clippy::ptr_arg)]
+impl Conductor {
+    /// Restarts crashed instances whose backoff is over.
+    pub fn supervise_instances(&mut self) {
+        let ids: Vec<String> = self.config.instances.iter().map(|i| i.id.clone()).collect();
+        self.restart_states.retain(|id, _| ids.contains(id));
+        for id in ids {
+            let policy = self
+                .config
+                .instance_by_id(&id)
+                .and_then(|instance_config| instance_config.restart_policy)
+                .unwrap_or_else(|| self.config.restart_policy.clone());
+            let mut state = self.restart_states.remove(&id).unwrap_or_default();
+            self.supervise_instance(&id, &policy, &mut state);
+            self.restart_states.insert(id, state);
+        }
+    }
+
+    fn supervise_instance(
+        &mut self,
+        id: &String,
+        policy: &RestartPolicy,
+        state: &mut RestartState,
+    ) {
+        let (cause, since) = match (self.crash_cause(id), state.pending) {
+            (None, None) => {
+                let reset_after = Duration::from_secs(policy.reset_after_secs);
+                if state.last_restart.map(|t| t.elapsed() >= reset_after) == Some(true) {
+                    *state = RestartState::default();
+                }
+                return;
+            }
+            (_, Some(pending)) => pending,
+            (Some(_), None) if state.gave_up => return,
+            (Some(cause), None) => {
+                // Before the restart replaces what is left of the crashed instance
+                if let Err(error) = self.write_crash_report(Some(id), &cause.to_string()) {
+                    notify(format!(
+                        "Could not write crash report of instance \"{}\": {}",
+                        id, error
+                    ));
+                }
+                if state.restarts >= policy.max_restarts {
+                    self.give_up_instance(id, cause, state);
+                    return;
+                }
+                let restart = state.restarts + 1;
+                let backoff = policy.backoff(restart);
+                notify(format!(
+                    "Instance \"{}\" crashed because {}, restarting it in {}ms (restart {} of {})",
+                    id,
+                    cause,
+                    backoff.as_millis(),
+                    restart,
+                    policy.max_restarts
+                ));
+                self.signal_restart(
+                    id,
+                    RestartEvent::Crashed {
+                        cause,
+                        restart,
+                        backoff_ms: backoff.as_millis() as u64,
+                    },
+                );
+                let pending = (cause, Instant::now());
+                state.pending = Some(pending);
+                pending
+            }
+        };
+
+        if state.restarts >= policy.max_restarts {
+            self.give_up_instance(id, cause, state);
+            return;
+        }
+        let restart = state.restarts + 1;
+        if since.elapsed() < policy.backoff(restart) {
+            return;
+        }
+        state.restarts = restart;
+        state.last_restart = Some(Instant::now());
+        match self.restart_crashed_instance(id) {
+            Ok(()) => {
+                state.pending = None;
+                notify(format!(
+                    "Restarted instance \"{}\", which crashed because {}",
+                    id, cause
+                ));
+                self.signal_restart(id, RestartEvent::Restarted { restart });
+            }
+            Err(error) => {
+                state.pending = Some((cause, Instant::now()));
+                notify(format!("Restarting instance \"{}\" failed: {}", id, error));
+                self.signal_restart(
+                    id,
+                    RestartEvent::Failed {
+                        restart,
+                        error: error.to_string(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Why the instance is active but not running anymore, if it crashed
+    fn crash_cause(&self, id: &String) -> Option<CrashCause> {
+        let instance = self.instances.get(id)?.read().unwrap();
+        if !instance.active() {
+            return None;
+        }
+        let context = instance.context().ok()?;
+        if !context.instance_still_alive() {
+            return Some(CrashCause::ActionLoopDied);
+        }
+        let network_died = context
+            .state()
+            .and_then(|state| state.network().network.as_ref().map(|n| !n.is_running()))
+            .unwrap_or(false);
+        if network_died {
+            Some(CrashCause::NetworkDied)
+        } else {
+            None
+        }
+    }
+
+    fn restart_crashed_instance(&mut self, id: &String) -> Result<(), HolochainError> {
+        let instance = self.instances.get(id).cloned().ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!("Instance \"{}\" not found", id))
+        })?;
+        instance.write().unwrap().kill();
+        let restarted = self
+            .instantiate_from_config(id)
+            .map_err(HolochainError::ErrorGeneric)?;
+        // Replaced in place, so that interfaces and bridges get to call the new instance
+        *instance.write().unwrap() = restarted;
+        self.start_instance(id)
+            .map_err(|error| HolochainError::ErrorGeneric(error.to_string()))
+    }
+
+    fn give_up_instance(&mut self, id: &String, cause: CrashCause, state: &mut RestartState) {
+        notify(format!(
+            "Instance \"{}\" crashed because {} after {} restarts, leaving it stopped",
+            id, cause, state.restarts
+        ));
+        if let Some(instance) = self.instances.get(id) {
+            let _ = instance.write().unwrap().stop();
+        }
+        state.pending = None;
+        state.gave_up = true;
+        self.signal_restart(
+            id,
+            RestartEvent::GaveUp {
+                cause,
+                restarts: state.restarts,
+            },
+        );
+    }
+
+    fn signal_restart(&self, id: &String, event: RestartEvent) {
+        self.signal_admin_interfaces(SignalWrapper::InstanceRestart {
+            instance_id: id.clone(),
+            event,
+        });
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::conductor::admin::tests::create_test_conductor;
+
+    /// Holochain::start() doesn't bring back the action loop that Holochain::stop() ended,
+    /// which leaves the instance active with a dead action loop, as a crash would.
+    fn crash(conductor: &Conductor, id: &String) {
+        let mut instance = conductor.instances.get(id).unwrap().write().unwrap();
+        instance.stop().unwrap();
+        instance.start().unwrap();
+    }
+
+    fn running(conductor: &Conductor, id: &String) -> bool {
+        let instance = conductor.instances.get(id).unwrap().read().unwrap();
+        instance.active() && instance.context().unwrap().instance_still_alive()
+    }
+
+    #[test]
+    fn test_restart_crashed_instance() {
+        let test_name = "test_restart_crashed_instance";
+        let mut conductor = create_test_conductor(test_name, 3701);
+        conductor.start_all_instances().unwrap();
+        conductor.config.restart_policy = RestartPolicy {
+            max_restarts: 1,
+            initial_backoff_ms: 0,
+            ..RestartPolicy::default()
+        };
+        let id = conductor.config().instances[0].id.clone();
+
+        crash(&conductor, &id);
+        assert!(!running(&conductor, &id));
+        conductor.supervise_instances();
+        assert!(running(&conductor, &id));
+
+        // Out of restarts
+        crash(&conductor, &id);
+        conductor.supervise_instances();
+        assert!(!conductor
+            .instances
+            .get(&id)
+            .unwrap()
+            .read()
+            .unwrap()
+            .active());
+        assert!(conductor.restart_states[&id].gave_up);
+
+        // Starting it by hand hands it back to the supervisor
+        conductor.start_instance(&id).unwrap();
+        assert!(conductor.restart_states.get(&id).is_none());
+    }
+
+    #[test]
+    fn test_restart_waits_for_backoff() {
+        let test_name = "test_restart_waits_for_backoff";
+        let mut conductor = create_test_conductor(test_name, 3702);
+        conductor.start_all_instances().unwrap();
+        conductor.config.restart_policy = RestartPolicy {
+            initial_backoff_ms: 60_000,
+            ..RestartPolicy::default()
+        };
+        let id = conductor.config().instances[0].id.clone();
+
+        crash(&conductor, &id);
+        conductor.supervise_instances();
+        assert!(!running(&conductor, &id));
+        let state = &conductor.restart_states[&id];
+        assert_eq!(state.restarts, 0);
+        assert_eq!(
+            state.pending.map(|(cause, _)| cause),
+            Some(CrashCause::ActionLoopDied)
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = RestartPolicy {
+            max_restarts: 100,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 5000,
+            reset_after_secs: 300,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2000));
+        assert_eq!(policy.backoff(3), Duration::from_millis(4000));
+        assert_eq!(policy.backoff(4), Duration::from_millis(5000));
+        assert_eq!(policy.backoff(100), Duration::from_millis(5000));
+    }
+}
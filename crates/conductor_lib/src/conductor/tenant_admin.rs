@@ -0,0 +1,652 @@
+//! Tenants let a hosting provider run many users on one conductor. A tenant's agents,
+//! instances and interfaces are kept in the conductor's config like all others, with IDs
+//! prefixed by the tenant's ID (`<tenant>/<id>`) so that tenants can't collide with each
+//! other. The functions of this trait take and return the IDs without that prefix and check
+//! that whatever they touch belongs to the tenant, which is what tenant admin interfaces
+//! expose, authenticated by the tenant's token.
+//! DPKI is not supported for tenant agents.
+use crate::{
+    conductor::{admin::ConductorAdmin, base::notify, Conductor},
+    config::{
+        hash_token, AgentConfiguration, InstanceConfiguration, InstanceReferenceConfiguration,
+        InterfaceConfiguration, InterfaceDriver, TenantConfiguration,
+    },
+    keystore::Keystore,
+};
+use holochain_core_types::error::HolochainError;
+use std::{fs::create_dir_all, path::PathBuf};
+
+/// The ID a tenant's agent, instance or interface has in the conductor config
+pub fn tenant_scoped_id(tenant: &str, id: &str) -> String {
+    format!("{}/{}", tenant, id)
+}
+
+/// The ID the tenant knows one of its agents, instances or interfaces by
+pub fn tenant_local_id(tenant: &str, scoped_id: &str) -> Option<String> {
+    let prefix = format!("{}/", tenant);
+    if scoped_id.starts_with(&prefix) {
+        Some(scoped_id[prefix.len()..].to_string())
+    } else {
+        None
+    }
+}
+
+fn check_local_id(id: &str) -> Result<(), HolochainError> {
+    if id.is_empty() || id.contains('/') {
+        Err(HolochainError::ErrorGeneric(format!(
+            "Invalid ID '{}', IDs of tenants and their agents, instances and interfaces can't be empty or contain '/'",
+            id
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+#[allow(clippy::ptr_arg)]
+pub trait ConductorTenantAdmin {
+    fn add_tenant(&mut self, id: String, token: &str) -> Result<(), HolochainError>;
+    fn remove_tenant(&mut self, id: &String) -> Result<(), HolochainError>;
+    fn authenticate_tenant(&self, id: &String, token: &str) -> Result<(), HolochainError>;
+    fn tenant_add_agent(
+        &mut self,
+        tenant: &String,
+        id: String,
+        name: String,
+    ) -> Result<String, HolochainError>;
+    fn tenant_add_instance(
+        &mut self,
+        tenant: &String,
+        id: &String,
+        dna_id: &String,
+        agent_id: &String,
+        storage: Option<&str>,
+    ) -> Result<(), HolochainError>;
+    fn tenant_remove_instance(
+        &mut self,
+        tenant: &String,
+        id: &String,
+    ) -> Result<(), HolochainError>;
+    fn tenant_start_instance(&mut self, tenant: &String, id: &String)
+        -> Result<(), HolochainError>;
+    fn tenant_stop_instance(&mut self, tenant: &String, id: &String) -> Result<(), HolochainError>;
+    fn tenant_add_interface(
+        &mut self,
+        tenant: &String,
+        interface: InterfaceConfiguration,
+    ) -> Result<(), HolochainError>;
+    fn tenant_remove_interface(
+        &mut self,
+        tenant: &String,
+        id: &String,
+    ) -> Result<(), HolochainError>;
+    fn tenant_add_instance_to_interface(
+        &mut self,
+        tenant: &String,
+        interface_id: &String,
+        instance_id: &String,
+        alias: &Option<String>,
+    ) -> Result<(), HolochainError>;
+    fn tenant_agents(&self, tenant: &String) -> Vec<AgentConfiguration>;
+    fn tenant_instances(&self, tenant: &String) -> Vec<InstanceConfiguration>;
+    fn tenant_running_instances(&self, tenant: &String) -> Vec<String>;
+    fn tenant_interfaces(&self, tenant: &String) -> Vec<InterfaceConfiguration>;
+}
+
+impl Conductor {
+    fn check_tenant_instance(
+        &self,
+        tenant: &String,
+        id: &String,
+    ) -> Result<String, HolochainError> {
+        let scoped_id = tenant_scoped_id(tenant, id);
+        if self.config.instance_tenant(&scoped_id).as_ref() == Some(tenant) {
+            Ok(scoped_id)
+        } else {
+            Err(HolochainError::ErrorGeneric(format!(
+                "Tenant '{}' has no instance '{}'",
+                tenant, id
+            )))
+        }
+    }
+
+    fn check_tenant_interface(
+        &self,
+        tenant: &String,
+        id: &String,
+    ) -> Result<String, HolochainError> {
+        let scoped_id = tenant_scoped_id(tenant, id);
+        match self.config.interface_by_id(&scoped_id) {
+            Some(ref interface) if interface.tenant.as_ref() == Some(tenant) => Ok(scoped_id),
+            _ => Err(HolochainError::ErrorGeneric(format!(
+                "Tenant '{}' has no interface '{}'",
+                tenant, id
+            ))),
+        }
+    }
+
+    /// Where the keystores of the tenant's agents get saved
+    pub fn tenant_keystore_dir_path(&self, tenant: &str) -> PathBuf {
+        self.config
+            .persistence_dir
+            .join("tenants")
+            .join(tenant)
+            .join("keys")
+    }
+}
+
+impl ConductorTenantAdmin for Conductor {
+    fn add_tenant(&mut self, id: String, token: &str) -> Result<(), HolochainError> {
+        check_local_id(&id)?;
+        let mut new_config = self.config.clone();
+        if new_config.tenant_by_id(&id).is_some() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Tenant with ID '{}' already exists",
+                id
+            )));
+        }
+        new_config.tenants.push(TenantConfiguration {
+            id: id.clone(),
+            token_hash: hash_token(token),
+            passphrase_service: None,
+        });
+        new_config.check_consistency(&mut self.dna_loader)?;
+        self.config = new_config;
+        self.save_config()?;
+        notify(format!("Added tenant \"{}\"", id));
+        Ok(())
+    }
+
+    /// Removes the tenant with all its interfaces, agents and their instances.
+    fn remove_tenant(&mut self, id: &String) -> Result<(), HolochainError> {
+        if self.config.tenant_by_id(id).is_none() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Tenant with ID '{}' does not exist",
+                id
+            )));
+        }
+        let interface_ids: Vec<String> = self
+            .config
+            .interfaces
+            .iter()
+            .filter(|interface| interface.tenant.as_ref() == Some(id))
+            .map(|interface| interface.id.clone())
+            .collect();
+        for interface_id in interface_ids.iter() {
+            self.remove_interface(interface_id)?;
+        }
+        let agent_ids: Vec<String> = self
+            .config
+            .agents
+            .iter()
+            .filter(|agent| agent.tenant.as_ref() == Some(id))
+            .map(|agent| agent.id.clone())
+            .collect();
+        for agent_id in agent_ids.iter() {
+            self.remove_agent(agent_id)?;
+        }
+
+        let mut new_config = self.config.clone();
+        new_config.tenants.retain(|tenant| tenant.id != *id);
+        new_config.check_consistency(&mut self.dna_loader)?;
+        self.config = new_config;
+        self.save_config()?;
+        self.drop_tenant_passphrase_manager(id);
+        notify(format!("Removed tenant \"{}\"", id));
+        Ok(())
+    }
+
+    fn authenticate_tenant(&self, id: &String, token: &str) -> Result<(), HolochainError> {
+        match self.config.tenant_by_id(id) {
+            Some(ref tenant) if tenant.token_hash == hash_token(token) => Ok(()),
+            _ => Err(HolochainError::ErrorGeneric(format!(
+                "Invalid token for tenant '{}'",
+                id
+            ))),
+        }
+    }
+
+    /// Creates a keystore for a new agent of the tenant, encrypted with the passphrase of the
+    /// tenant's passphrase service. Returns the agent's public address.
+    fn tenant_add_agent(
+        &mut self,
+        tenant: &String,
+        id: String,
+        name: String,
+    ) -> Result<String, HolochainError> {
+        check_local_id(&id)?;
+        let scoped_id = tenant_scoped_id(tenant, &id);
+        let mut new_config = self.config.clone();
+        if new_config.agent_by_id(&scoped_id).is_some() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Agent with ID '{}' already exists",
+                id
+            )));
+        }
+
+        let passphrase_manager = self.passphrase_manager_for_tenant(Some(tenant));
+        let (mut keystore, public_address) =
+            Keystore::new_standalone(passphrase_manager, self.hash_config.clone())?;
+        let keystore_dir = self.tenant_keystore_dir_path(tenant);
+        create_dir_all(&keystore_dir)?;
+        let keystore_file = keystore_dir.join(public_address.clone());
+        keystore.save(keystore_file.clone())?;
+
+        new_config.agents.push(AgentConfiguration {
+            id: scoped_id.clone(),
+            name,
+            public_address: public_address.clone(),
+            keystore_file: keystore_file.to_string_lossy().into_owned(),
+            holo_remote_key: None,
+            test_agent: None,
+            seed_file: None,
+            client_keys: None,
+            key_backend: None,
+            tenant: Some(tenant.clone()),
+        });
+        new_config.check_consistency(&mut self.dna_loader)?;
+        self.config = new_config;
+        self.add_agent_keystore(scoped_id.clone(), keystore);
+        self.save_config()?;
+        notify(format!("Added agent \"{}\"", scoped_id));
+        Ok(public_address)
+    }
+
+    /// Adds an instance of one of the conductor's DNAs, run by one of the tenant's agents.
+    fn tenant_add_instance(
+        &mut self,
+        tenant: &String,
+        id: &String,
+        dna_id: &String,
+        agent_id: &String,
+        storage: Option<&str>,
+    ) -> Result<(), HolochainError> {
+        check_local_id(id)?;
+        let scoped_agent_id = tenant_scoped_id(tenant, agent_id);
+        match self.config.agent_by_id(&scoped_agent_id) {
+            Some(ref agent) if agent.tenant.as_ref() == Some(tenant) => (),
+            _ => {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Tenant '{}' has no agent '{}'",
+                    tenant, agent_id
+                )))
+            }
+        }
+        self.add_instance(
+            &tenant_scoped_id(tenant, id),
+            dna_id,
+            &scoped_agent_id,
+            storage,
+            None,
+        )
+    }
+
+    fn tenant_remove_instance(
+        &mut self,
+        tenant: &String,
+        id: &String,
+    ) -> Result<(), HolochainError> {
+        let scoped_id = self.check_tenant_instance(tenant, id)?;
+        self.remove_instance(&scoped_id)
+    }
+
+    fn tenant_start_instance(
+        &mut self,
+        tenant: &String,
+        id: &String,
+    ) -> Result<(), HolochainError> {
+        let scoped_id = self.check_tenant_instance(tenant, id)?;
+        self.start_instance(&scoped_id)
+            .map_err(|error| HolochainError::ErrorGeneric(error.to_string()))
+    }
+
+    fn tenant_stop_instance(&mut self, tenant: &String, id: &String) -> Result<(), HolochainError> {
+        let scoped_id = self.check_tenant_instance(tenant, id)?;
+        self.stop_instance(&scoped_id)
+            .map_err(|error| HolochainError::ErrorGeneric(error.to_string()))
+    }
+
+    /// Adds an interface that belongs to the tenant. Its ID and the IDs of its instances are
+    /// the tenant's local ones, and instances are served under their local IDs unless they
+    /// have an alias. Admin interfaces of a tenant only offer the tenant's admin functions.
+    fn tenant_add_interface(
+        &mut self,
+        tenant: &String,
+        mut interface: InterfaceConfiguration,
+    ) -> Result<(), HolochainError> {
+        check_local_id(&interface.id)?;
+        // Tenants can't make the conductor read or create files of their choosing
+        let reject = |reason: &str| {
+            Err(HolochainError::ErrorGeneric(format!(
+                "Interface '{}' of tenant '{}' can't {}",
+                interface.id, tenant, reason
+            )))
+        };
+        match interface.driver {
+            InterfaceDriver::DomainSocket { .. } => return reject("be a domain socket"),
+            InterfaceDriver::Custom(_) => return reject("use a custom driver"),
+            _ => (),
+        }
+        if interface.tls.is_some() {
+            return reject("use tls");
+        }
+        if let Some(ref auth) = interface.auth {
+            if auth.client_ca_file.is_some() {
+                return reject("check client certificates");
+            }
+        }
+        if let InterfaceDriver::RemoteAdmin { ref mut agent, .. } = interface.driver {
+            let scoped_agent = tenant_scoped_id(tenant, agent);
+            let is_tenants_agent = self
+                .config
+                .agent_by_id(&scoped_agent)
+                .map_or(false, |config| config.tenant.as_ref() == Some(tenant));
+            if !is_tenants_agent {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Tenant '{}' has no agent '{}'",
+                    tenant, agent
+                )));
+            }
+            *agent = scoped_agent;
+        }
+        interface.id = tenant_scoped_id(tenant, &interface.id);
+        interface.tenant = Some(tenant.clone());
+        for instance in interface.instances.iter_mut() {
+            instance.alias = instance.alias.clone().or_else(|| Some(instance.id.clone()));
+            instance.id = tenant_scoped_id(tenant, &instance.id);
+        }
+        self.add_interface(interface)
+    }
+
+    fn tenant_remove_interface(
+        &mut self,
+        tenant: &String,
+        id: &String,
+    ) -> Result<(), HolochainError> {
+        let scoped_id = self.check_tenant_interface(tenant, id)?;
+        self.remove_interface(&scoped_id)
+    }
+
+    fn tenant_add_instance_to_interface(
+        &mut self,
+        tenant: &String,
+        interface_id: &String,
+        instance_id: &String,
+        alias: &Option<String>,
+    ) -> Result<(), HolochainError> {
+        let scoped_interface_id = self.check_tenant_interface(tenant, interface_id)?;
+        let scoped_instance_id = self.check_tenant_instance(tenant, instance_id)?;
+        let alias = alias.clone().or_else(|| Some(instance_id.clone()));
+        self.add_instance_to_interface(&scoped_interface_id, &scoped_instance_id, &alias)
+    }
+
+    fn tenant_agents(&self, tenant: &String) -> Vec<AgentConfiguration> {
+        self.config
+            .agents
+            .iter()
+            .filter(|agent| agent.tenant.as_ref() == Some(tenant))
+            .filter_map(|agent| {
+                tenant_local_id(tenant, &agent.id).map(|id| AgentConfiguration {
+                    id,
+                    ..agent.clone()
+                })
+            })
+            .collect()
+    }
+
+    fn tenant_instances(&self, tenant: &String) -> Vec<InstanceConfiguration> {
+        self.config
+            .instances
+            .iter()
+            .filter(|instance| self.config.instance_tenant(&instance.id).as_ref() == Some(tenant))
+            .filter_map(|instance| {
+                let id = tenant_local_id(tenant, &instance.id)?;
+                let agent = tenant_local_id(tenant, &instance.agent)?;
+                Some(InstanceConfiguration {
+                    id,
+                    agent,
+                    ..instance.clone()
+                })
+            })
+            .collect()
+    }
+
+    fn tenant_running_instances(&self, tenant: &String) -> Vec<String> {
+        self.instances
+            .iter()
+            .filter(|(_, instance)| instance.read().unwrap().active())
+            .filter(|(id, _)| self.config.instance_tenant(id).as_ref() == Some(tenant))
+            .filter_map(|(id, _)| tenant_local_id(tenant, id))
+            .collect()
+    }
+
+    fn tenant_interfaces(&self, tenant: &String) -> Vec<InterfaceConfiguration> {
+        self.config
+            .interfaces
+            .iter()
+            .filter(|interface| interface.tenant.as_ref() == Some(tenant))
+            .filter_map(|interface| {
+                let id = tenant_local_id(tenant, &interface.id)?;
+                let instances = interface
+                    .instances
+                    .iter()
+                    .filter_map(|instance| {
+                        tenant_local_id(tenant, &instance.id).map(|id| {
+                            InstanceReferenceConfiguration {
+                                id,
+                                alias: instance.alias.clone(),
+                            }
+                        })
+                    })
+                    .collect();
+                let mut driver = interface.driver.clone();
+                if let InterfaceDriver::RemoteAdmin { ref mut agent, .. } = driver {
+                    *agent = tenant_local_id(tenant, agent)?;
+                }
+                Some(InterfaceConfiguration {
+                    id,
+                    driver,
+                    instances,
+                    ..interface.clone()
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        conductor::admin::tests::create_test_conductor,
+        config::{InterfaceAuthConfiguration, PassphraseServiceConfig, TlsConfiguration},
+    };
+
+    #[test]
+    fn test_tenants_are_isolated() {
+        let test_name = "test_tenants_are_isolated";
+        let mut conductor = create_test_conductor(test_name, 3401);
+        let alice = String::from("alice");
+        let bob = String::from("bob");
+        conductor.add_tenant(alice.clone(), "alice-token").unwrap();
+        conductor.add_tenant(bob.clone(), "bob-token").unwrap();
+        assert!(conductor.add_tenant(alice.clone(), "again").is_err());
+        assert!(conductor.add_tenant(String::from("a/b"), "token").is_err());
+
+        assert!(conductor.authenticate_tenant(&alice, "alice-token").is_ok());
+        assert!(conductor.authenticate_tenant(&alice, "bob-token").is_err());
+        assert!(conductor
+            .authenticate_tenant(&String::from("eve"), "alice-token")
+            .is_err());
+        assert!(!conductor
+            .config()
+            .tenants
+            .iter()
+            .any(|tenant| tenant.token_hash.contains("alice-token")));
+
+        // Both tenants can use the same local IDs
+        let agent = String::from("agent");
+        conductor
+            .tenant_add_agent(&alice, agent.clone(), String::from("Alice"))
+            .unwrap();
+        conductor
+            .tenant_add_agent(&bob, agent.clone(), String::from("Bob"))
+            .unwrap();
+        let keystore_file = conductor
+            .config()
+            .agent_by_id("alice/agent")
+            .unwrap()
+            .keystore_file;
+        assert!(
+            PathBuf::from(keystore_file).starts_with(conductor.tenant_keystore_dir_path("alice"))
+        );
+
+        let instance = String::from("app");
+        let dna = String::from("test-dna");
+        conductor
+            .tenant_add_instance(&alice, &instance, &dna, &agent, Some("memory"))
+            .unwrap();
+        assert_eq!(
+            conductor.config().instance_tenant("alice/app"),
+            Some(alice.clone())
+        );
+        // Bob can't use Alice's agent, nor see or touch her instance
+        assert!(conductor
+            .tenant_add_instance(
+                &bob,
+                &instance,
+                &dna,
+                &String::from("other"),
+                Some("memory")
+            )
+            .is_err());
+        assert!(conductor.tenant_instances(&bob).is_empty());
+        assert!(conductor.tenant_stop_instance(&bob, &instance).is_err());
+        assert!(conductor.tenant_remove_instance(&bob, &instance).is_err());
+
+        let instances = conductor.tenant_instances(&alice);
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].id, instance);
+        assert_eq!(instances[0].agent, agent);
+        assert_eq!(conductor.tenant_agents(&bob).len(), 1);
+        assert_eq!(conductor.tenant_agents(&bob)[0].id, agent);
+
+        conductor
+            .tenant_add_interface(
+                &alice,
+                InterfaceConfiguration {
+                    id: String::from("websocket"),
+                    driver: InterfaceDriver::Websocket { port: 3402 },
+                    admin: true,
+                    instances: vec![InstanceReferenceConfiguration {
+                        id: instance.clone(),
+                        alias: None,
+                    }],
+                    choose_free_port: None,
+                    require_signed_calls: false,
+                    filter_signals: false,
+                    tenant: None,
+                    auth: None,
+                    tls: None,
+                },
+            )
+            .unwrap();
+        let interfaces = conductor.tenant_interfaces(&alice);
+        assert_eq!(interfaces.len(), 1);
+        assert_eq!(interfaces[0].instances[0].id, instance);
+        assert!(conductor.tenant_interfaces(&bob).is_empty());
+        assert!(conductor
+            .tenant_remove_interface(&bob, &String::from("websocket"))
+            .is_err());
+
+        // Interfaces of tenants can only use the tenant's agents and no files
+        let interface = |id: &str, driver: InterfaceDriver| InterfaceConfiguration {
+            id: String::from(id),
+            driver,
+            admin: true,
+            instances: Vec::new(),
+            choose_free_port: None,
+            require_signed_calls: false,
+            filter_signals: false,
+            tenant: None,
+            auth: None,
+            tls: None,
+        };
+        let remote_admin = |agent: &str| InterfaceDriver::RemoteAdmin {
+            port: 3404,
+            agent: String::from(agent),
+            authorized_keys: Vec::new(),
+        };
+        assert!(conductor
+            .tenant_add_interface(&bob, interface("remote", remote_admin("agent")))
+            .is_ok());
+        let remote = conductor
+            .config()
+            .interface_by_id("bob/remote")
+            .unwrap()
+            .driver;
+        assert_eq!(remote, remote_admin("bob/agent"));
+        assert_eq!(
+            conductor.tenant_interfaces(&bob)[0].driver,
+            remote_admin("agent")
+        );
+        assert!(conductor
+            .tenant_add_interface(&bob, interface("stolen", remote_admin("alice/agent")))
+            .is_err());
+        assert!(conductor
+            .tenant_add_interface(&bob, interface("unknown", remote_admin("other")))
+            .is_err());
+        let domain_socket = InterfaceDriver::DomainSocket {
+            file: String::from("/tmp/bob.sock"),
+        };
+        assert!(conductor
+            .tenant_add_interface(&bob, interface("socket", domain_socket))
+            .is_err());
+        let mut with_tls = interface("tls", InterfaceDriver::Websocket { port: 3405 });
+        with_tls.tls = Some(TlsConfiguration {
+            certificate_file: PathBuf::from("/etc/ssl/cert.pem"),
+            private_key_file: PathBuf::from("/etc/ssl/key.pem"),
+        });
+        assert!(conductor.tenant_add_interface(&bob, with_tls).is_err());
+        let mut with_client_ca = interface("ca", InterfaceDriver::Websocket { port: 3405 });
+        with_client_ca.auth = Some(InterfaceAuthConfiguration {
+            token_hashes: Vec::new(),
+            client_ca_file: Some(PathBuf::from("/etc/ssl/ca.pem")),
+        });
+        assert!(conductor
+            .tenant_add_interface(&bob, with_client_ca)
+            .is_err());
+        assert_eq!(conductor.tenant_interfaces(&bob).len(), 1);
+
+        conductor.remove_tenant(&alice).unwrap();
+        assert!(conductor.config().instance_by_id("alice/app").is_none());
+        assert!(conductor.config().agent_by_id("alice/agent").is_none());
+        assert!(conductor
+            .config()
+            .interface_by_id("alice/websocket")
+            .is_none());
+        assert_eq!(conductor.tenant_agents(&bob).len(), 1);
+    }
+
+    #[test]
+    fn test_tenant_passphrase_manager() {
+        let test_name = "test_tenant_passphrase_manager";
+        let mut conductor = create_test_conductor(test_name, 3403);
+        conductor
+            .add_tenant(String::from("alice"), "token")
+            .unwrap();
+        conductor.add_tenant(String::from("bob"), "token").unwrap();
+        conductor.config.tenants[1].passphrase_service = Some(PassphraseServiceConfig::Mock {
+            passphrase: String::from("bob's passphrase"),
+        });
+
+        let alice = conductor.passphrase_manager_for_tenant(Some(&String::from("alice")));
+        assert!(std::sync::Arc::ptr_eq(
+            &alice,
+            &conductor.passphrase_manager
+        ));
+        let bob = conductor.passphrase_manager_for_tenant(Some(&String::from("bob")));
+        assert!(!std::sync::Arc::ptr_eq(&bob, &conductor.passphrase_manager));
+        let bob_again = conductor.passphrase_manager_for_tenant(Some(&String::from("bob")));
+        assert!(std::sync::Arc::ptr_eq(&bob, &bob_again));
+    }
+}
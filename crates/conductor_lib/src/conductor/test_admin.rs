@@ -31,6 +31,10 @@ impl ConductorTestAdmin for Conductor {
             keystore_file: name,
             holo_remote_key: None,
             test_agent: Some(true),
+            seed_file: None,
+            client_keys: None,
+            key_backend: None,
+            tenant: None,
         };
         new_config.agents.push(new_agent);
         new_config.check_consistency(&mut self.dna_loader)?;
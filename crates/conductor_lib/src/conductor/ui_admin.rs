@@ -332,6 +332,9 @@ id = 'test-bundle-id'"#,
                 port: 4000,
                 bundle: "test-bundle-id".into(),
                 dna_interface: None,
+                host: None,
+                path_prefix: None,
+                instance: None,
                 reroute_to_root: true,
                 bind_address: Ipv4Addr::LOCALHOST.to_string()
             }),
@@ -356,6 +359,9 @@ id = 'test-bundle-id'"#,
                 port: 4000,
                 bundle: "test-bundle-id".into(),
                 dna_interface: None,
+                host: None,
+                path_prefix: None,
+                instance: None,
                 reroute_to_root: true,
                 bind_address: Ipv4Addr::LOCALHOST.to_string()
             }),
@@ -426,6 +432,9 @@ reroute_to_root = true"#,
                 port: 4000,
                 bundle: "test-bundle-id".into(),
                 dna_interface: None,
+                host: None,
+                path_prefix: None,
+                instance: None,
                 reroute_to_root: true,
                 bind_address: Ipv4Addr::LOCALHOST.to_string()
             }),
@@ -490,6 +499,9 @@ root_dir = '.'"#,
                 port: 4100,
                 bundle: "test-bundle-id".into(),
                 dna_interface: None,
+                host: None,
+                path_prefix: None,
+                instance: None,
                 reroute_to_root: true,
                 bind_address: Ipv4Addr::LOCALHOST.to_string()
             }),
@@ -524,6 +536,9 @@ root_dir = '.'"#,
                 port: 4101,
                 bundle: "test-bundle-id".into(),
                 dna_interface: None,
+                host: None,
+                path_prefix: None,
+                instance: None,
                 reroute_to_root: true,
                 bind_address: Ipv4Addr::LOCALHOST.to_string()
             }),
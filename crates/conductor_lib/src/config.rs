@@ -1,4 +1,8 @@
-use crate::{conductor::base::DnaLoader, logger::LogRules};
+use crate::{
+    cold_storage::ColdStorageConfig, compressed_storage::StorageCompression,
+    conductor::base::DnaLoader, eav_indexing::EavIndexing, logger::LogRules,
+    rocksdb_storage::RocksDbStorageOptions, tiered_storage::HotTier,
+};
 /// Conductor Configuration
 /// This module provides structs that represent the different aspects of how
 /// a conductor can be configured.
@@ -11,6 +15,11 @@ use crate::{conductor::base::DnaLoader, logger::LogRules};
 ///   the conductor
 /// * bridges, which are
 use boolinator::*;
+use holochain_core::{
+    dht::pending_validations::PendingValidationConfig,
+    nucleus::{rate_limit::ZomeCallRateLimits, slow_calls::SlowZomeCallConfig},
+    resource_limits::ResourceLimits,
+};
 use holochain_core_types::{
     agent::{AgentId, Base32},
     dna::{
@@ -22,19 +31,22 @@ use holochain_core_types::{
 use holochain_json_api::json::JsonString;
 use holochain_metrics::MetricPublisherConfig;
 use holochain_net::sim2h_worker::Sim2hConfig;
-use holochain_persistence_api::cas::content::AddressableContent;
+use holochain_persistence_api::{cas::content::AddressableContent, hash::HashString};
 use lib3h::engine::EngineConfig;
+use multihash::Hash;
 
 use petgraph::{algo::toposort, graph::DiGraph, prelude::NodeIndex};
 use serde::Deserialize;
 use std::{
-    collections::{HashMap, HashSet},
+    cmp::min,
+    collections::{BTreeMap, HashMap, HashSet},
     convert::TryFrom,
     fs::File,
     io::prelude::*,
     net::Ipv4Addr,
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 use toml;
 /// Main conductor configuration struct
@@ -74,10 +86,16 @@ pub struct Configuration {
     #[serde(default)]
     pub logger: LoggerConfiguration,
 
-    /// Configures Jaeger tracing. Optional.
+    /// Configures where trace spans get exported to, over Jaeger's agent protocol or OTLP.
+    /// Optional.
     #[serde(default)]
     pub tracing: Option<TracingConfiguration>,
 
+    /// Serves metrics of zome calls, holding and the network at `/metrics` for Prometheus
+    /// to scrape. Metrics only get recorded if this is set. Optional.
+    #[serde(default)]
+    pub metrics_endpoint: Option<MetricsEndpointConfiguration>,
+
     /// Configuration options for the network module. Optional.
     #[serde(default)]
     pub network: Option<NetworkConfig>,
@@ -108,6 +126,16 @@ pub struct Configuration {
     /// keys for new instances
     pub dpki: Option<DpkiConfiguration>,
 
+    /// Agent public keys that have been revoked and must not be used to create or run
+    /// instances anymore. Instantiation fails for any instance whose agent key is listed here.
+    #[serde(default)]
+    pub revoked_agent_keys: Vec<Base32>,
+
+    /// Agents blocked in all instances through `admin/agent/block`. Instances block them
+    /// again whenever they get created or started, so blocks outlive restarts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_agents: Vec<Base32>,
+
     /// Which signals to emit
     #[serde(default)]
     pub signals: SignalConfig,
@@ -119,8 +147,84 @@ pub struct Configuration {
     #[serde(default)]
     pub passphrase_service: PassphraseServiceConfig,
 
+    /// Number of seconds the keystore stays unlocked after the passphrase was provided.
+    /// When the session is over the passphrase and all decrypted agent keys get dropped from
+    /// memory and signing fails until the keystore gets unlocked again through the
+    /// `admin/keystore/unlock` admin function.
+    /// Optional. If not set, the keystore only gets locked through `admin/keystore/lock`.
+    #[serde(default)]
+    pub keystore_session_duration: Option<u64>,
+
     #[serde(default)]
     pub metric_publisher: Option<MetricPublisherConfig>,
+
+    /// Path of a file that every signature verification (entry provenances, signed zome calls,
+    /// capability delegations, handshakes, ...) gets appended to, with outcome, key and context.
+    /// Optional. For deployments that need forensic traceability of authentication decisions.
+    #[serde(default)]
+    pub security_audit_log: Option<PathBuf>,
+
+    /// Path of a hash-chained file that commits, held entries, failed validations, uses of
+    /// capability grants and admin operations get appended to. Optional. `hc event-log`
+    /// verifies and exports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_log: Option<PathBuf>,
+
+    /// Enables development-only operations that would be unsafe on a live network,
+    /// like rolling back an instance's source chain. Optional, defaults to false.
+    #[serde(default)]
+    pub dev_mode: bool,
+
+    /// Users of a conductor that hosts many of them, e.g. at a hosting provider. Each tenant
+    /// has its own agents and keystores, instances and interfaces, which it manages through
+    /// its own admin interfaces. Optional.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tenants: Vec<TenantConfiguration>,
+
+    /// How instances whose action loop or network thread died get restarted, unless an
+    /// instance sets its own policy. Optional.
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+
+    /// Where DNAs that get installed by their hash alone get downloaded from. Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dna_registry: Option<DnaRegistryConfiguration>,
+
+    /// Where crash reports of crashed instances and panics get written to.
+    /// Optional, defaults to `crash_reports` in the persistence dir.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crash_report_dir: Option<PathBuf>,
+
+    /// Webhooks that get called when an instance crosses one of the alert thresholds,
+    /// like a holding queue that keeps growing. Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<AlertConfiguration>,
+}
+
+/// A DNA registry serves DNA files by their hash, at `<url>/<hash>.dna.json`, over HTTPS.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct DnaRegistryConfiguration {
+    pub url: String,
+}
+
+/// A tenant of the conductor. Agents and interfaces belong to a tenant if their `tenant` is
+/// set to its ID, and instances belong to the tenant of their agent.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TenantConfiguration {
+    pub id: String,
+    /// Hash of the token the tenant authenticates its admin calls with, see [hash_token]
+    pub token_hash: String,
+    /// Where the passphrase of the tenant's keystores comes from, so they don't have to be
+    /// encrypted with the conductor's passphrase. Optional, defaults to the conductor's
+    /// passphrase service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase_service: Option<PassphraseServiceConfig>,
+}
+
+/// How the tokens of tenants and interface clients are stored in the config, so that
+/// reading the config doesn't reveal the tokens.
+pub fn hash_token(token: &str) -> String {
+    HashString::encode_from_str(token, Hash::SHA2256).to_string()
 }
 
 /// The default passphrase service is `Cmd` which will ask for a passphrase via stdout stdin.
@@ -169,6 +273,7 @@ pub fn default_persistence_dir() -> PathBuf {
 ///     logger_level: "debug".to_string(),
 ///     rules: rules,
 ///     state_dump: true,
+///     format: config::LogFormat::Text,
 ///     };
 /// ```
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -180,6 +285,24 @@ pub struct LoggerConfiguration {
     //    pub file: Option<String>,
     #[serde(default)]
     pub state_dump: bool,
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+/// How log lines are written
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Free-form text, colored by the rules
+    Text,
+    /// One JSON object per line, see [json_logger](crate::json_logger)
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> LogFormat {
+        LogFormat::Text
+    }
 }
 
 impl Default for LoggerConfiguration {
@@ -188,6 +311,47 @@ impl Default for LoggerConfiguration {
             logger_level: "debug".into(),
             rules: Default::default(),
             state_dump: false,
+            format: LogFormat::Text,
+        }
+    }
+}
+
+/// Logging of a single instance, see [log_router](crate::log_router)
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct InstanceLoggerConfiguration {
+    /// Level of the instance's logs, like the conductor's `type`. Optional, defaults to
+    /// the conductor's level.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    /// File the instance's logs get written to instead of the conductor's log. Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    #[serde(default)]
+    pub rotation: LogRotation,
+}
+
+/// When a log file gets rotated, and how many rotated files are kept
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct LogRotation {
+    #[serde(default = "default_log_max_bytes")]
+    pub max_bytes: u64,
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+fn default_log_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        LogRotation {
+            max_bytes: default_log_max_bytes(),
+            max_files: default_log_max_files(),
         }
     }
 }
@@ -197,6 +361,9 @@ impl Default for LoggerConfiguration {
 pub enum TracingConfiguration {
     None,
     Jaeger(JaegerTracingConfiguration),
+    /// Exports spans over OTLP/HTTP with JSON encoding, which Jaeger, Tempo and the
+    /// OpenTelemetry collector accept
+    Otlp(OtlpTracingConfiguration),
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -205,6 +372,42 @@ pub struct JaegerTracingConfiguration {
     pub socket_address: Option<String>,
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct MetricsEndpointConfiguration {
+    pub port: u16,
+    /// Defaults to 127.0.0.1, so that only local scrapers get the metrics
+    #[serde(default = "default_metrics_bind_address")]
+    pub bind_address: String,
+}
+
+fn default_metrics_bind_address() -> String {
+    String::from("127.0.0.1")
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct OtlpTracingConfiguration {
+    /// Name of the service spans are reported for. Spans of each instance also carry the
+    /// instance ID as attribute `holochain.instance_id`.
+    pub service_name: String,
+    /// URL spans get posted to
+    #[serde(default = "default_otlp_endpoint")]
+    pub endpoint: String,
+    /// Headers to send with each export, e.g. for authorization
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Maximal number of spans exported per request
+    #[serde(default = "default_otlp_batch_size")]
+    pub batch_size: usize,
+}
+
+fn default_otlp_endpoint() -> String {
+    String::from("http://localhost:4318/v1/traces")
+}
+
+fn default_otlp_batch_size() -> usize {
+    512
+}
+
 impl Default for TracingConfiguration {
     fn default() -> Self {
         TracingConfiguration::None
@@ -248,6 +451,27 @@ impl Configuration {
         self.check_instances_storage()?;
 
         detect_dupes("interface", self.interfaces.iter().map(|c| &c.id))?;
+        detect_dupes("tenant", self.tenants.iter().map(|c| &c.id))?;
+        self.check_tenants()?;
+
+        if let Some(ref registry) = self.dna_registry {
+            let url = url::Url::parse(&registry.url)
+                .map_err(|e| format!("Invalid DNA registry URL {}: {}", registry.url, e))?;
+            (url.scheme() == "https")
+                .ok_or_else(|| format!("DNA registry {} does not use HTTPS", registry.url))?;
+        }
+
+        if let Some(TracingConfiguration::Otlp(ref otlp)) = self.tracing {
+            url::Url::parse(&otlp.endpoint)
+                .map_err(|e| format!("Invalid OTLP endpoint {}: {}", otlp.endpoint, e))?;
+            (otlp.batch_size > 0)
+                .ok_or_else(|| String::from("OTLP batch size has to be at least 1"))?;
+        }
+
+        for webhook in self.alerts.iter().flat_map(|alerts| alerts.webhooks.iter()) {
+            url::Url::parse(&webhook.url)
+                .map_err(|e| format!("Invalid alert webhook URL {}: {}", webhook.url, e))?;
+        }
 
         for ref instance in self.instances.iter() {
             self.agent_by_id(&instance.agent).is_some().ok_or_else(|| {
@@ -256,6 +480,14 @@ impl Configuration {
                     instance.agent, instance.id
                 )
             })?;
+            if let Some(ref level) = instance.logger.as_ref().and_then(|l| l.level.as_ref()) {
+                level.parse::<log::LevelFilter>().map_err(|_| {
+                    format!(
+                        "Invalid log level \"{}\" of instance \"{}\"",
+                        level, instance.id
+                    )
+                })?;
+            }
             let dna_config = self.dna_by_id(&instance.dna);
             dna_config.is_some().ok_or_else(|| {
                 format!(
@@ -288,6 +520,41 @@ impl Configuration {
         }
 
         for ref interface in self.interfaces.iter() {
+            if let InterfaceDriver::RemoteAdmin { ref agent, .. } = interface.driver {
+                self.agent_by_id(agent).is_some().ok_or_else(|| {
+                    format!(
+                        "Agent configuration {} not found, mentioned in interface {}",
+                        agent, interface.id
+                    )
+                })?;
+            }
+            if interface.auth.is_some() || interface.tls.is_some() {
+                match interface.driver {
+                    InterfaceDriver::Websocket { .. } | InterfaceDriver::Http { .. } => (),
+                    _ => {
+                        return Err(format!(
+                            "Interface {} sets auth or tls, which only websocket and HTTP interfaces support",
+                            interface.id
+                        ))
+                    }
+                }
+            }
+            if let Some(ref auth) = interface.auth {
+                (auth.client_ca_file.is_none() || interface.tls.is_some()).ok_or_else(|| {
+                    format!(
+                        "Interface {} can only check client certificates if it uses tls",
+                        interface.id
+                    )
+                })?;
+                (auth.client_ca_file.is_some() || !auth.token_hashes.is_empty()).ok_or_else(
+                    || {
+                        format!(
+                            "The auth section of interface {} needs token_hashes or a client_ca_file",
+                            interface.id
+                        )
+                    },
+                )?;
+            }
             for ref instance in interface.instances.iter() {
                 self.instance_by_id(&instance.id).is_some().ok_or_else(|| {
                     format!(
@@ -322,6 +589,48 @@ impl Configuration {
                         )
                     })?;
             }
+
+            if let Some(ref prefix) = ui_interface.path_prefix {
+                prefix.starts_with('/').ok_or_else(|| {
+                    format!(
+                        "Path prefix \"{}\" of UI interface \"{}\" has to start with \"/\"",
+                        prefix, ui_interface.id,
+                    )
+                })?;
+            }
+
+            if let Some(ref instance_id) = ui_interface.instance {
+                self.ui_interface_instance_is_exposed(ui_interface, instance_id)
+                    .ok_or_else(|| {
+                        format!(
+                            "Instance \"{}\" of UI interface \"{}\" is not part of its DNA interface",
+                            instance_id, ui_interface.id,
+                        )
+                    })?;
+            }
+
+            // UIs on a shared port are told apart by host and path
+            for other in self.ui_interfaces.iter() {
+                if other.id == ui_interface.id || other.port != ui_interface.port {
+                    continue;
+                }
+                (other.bind_address == ui_interface.bind_address).ok_or_else(|| {
+                    format!(
+                        "UI interfaces \"{}\" and \"{}\" share port {} but bind to different addresses",
+                        other.id, ui_interface.id, ui_interface.port,
+                    )
+                })?;
+                let same_host = other.host.as_ref().map(|host| host.to_lowercase())
+                    == ui_interface.host.as_ref().map(|host| host.to_lowercase());
+                (!same_host || other.path_prefix() != ui_interface.path_prefix()).ok_or_else(
+                    || {
+                        format!(
+                            "UI interfaces \"{}\" and \"{}\" share port {} with the same host and path",
+                            other.id, ui_interface.id, ui_interface.port,
+                        )
+                    },
+                )?;
+            }
         }
         if let Some(ref dpki_config) = self.dpki {
             self.instance_by_id(&dpki_config.instance_id)
@@ -349,6 +658,51 @@ impl Configuration {
         Ok(())
     }
 
+    /// Tenants must exist, and can only use their own instances in their interfaces and
+    /// bridges.
+    fn check_tenants(&self) -> Result<(), String> {
+        for agent in self.agents.iter() {
+            if let Some(ref tenant) = agent.tenant {
+                self.tenant_by_id(tenant).is_some().ok_or_else(|| {
+                    format!(
+                        "Tenant \"{}\" not found, mentioned in agent \"{}\"",
+                        tenant, agent.id
+                    )
+                })?;
+            }
+        }
+        for interface in self.interfaces.iter() {
+            let tenant = match interface.tenant {
+                Some(ref tenant) => tenant,
+                None => continue,
+            };
+            self.tenant_by_id(tenant).is_some().ok_or_else(|| {
+                format!(
+                    "Tenant \"{}\" not found, mentioned in interface \"{}\"",
+                    tenant, interface.id
+                )
+            })?;
+            for instance in interface.instances.iter() {
+                (self.instance_tenant(&instance.id).as_ref() == Some(tenant)).ok_or_else(|| {
+                    format!(
+                        "Interface \"{}\" of tenant \"{}\" can not use instance \"{}\" of another tenant",
+                        interface.id, tenant, instance.id
+                    )
+                })?;
+            }
+        }
+        for bridge in self.bridges.iter() {
+            (self.instance_tenant(&bridge.caller_id) == self.instance_tenant(&bridge.callee_id))
+                .ok_or_else(|| {
+                    format!(
+                        "Bridge from \"{}\" to \"{}\" connects instances of different tenants",
+                        bridge.caller_id, bridge.callee_id
+                    )
+                })?;
+        }
+        Ok(())
+    }
+
     fn check_bridge_requirements(
         &self,
         bridge_config: &Bridge,
@@ -526,6 +880,35 @@ impl Configuration {
         self.interfaces.iter().find(|ic| ic.id == id).cloned()
     }
 
+    fn ui_interface_instance_is_exposed(
+        &self,
+        ui_interface: &UiInterfaceConfiguration,
+        instance_id: &str,
+    ) -> bool {
+        ui_interface
+            .dna_interface
+            .as_ref()
+            .and_then(|id| self.interface_by_id(id))
+            .map(|interface| {
+                interface.instances.iter().any(|instance| {
+                    instance.id == instance_id
+                        || instance.alias.as_ref().map(String::as_str) == Some(instance_id)
+                })
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn tenant_by_id(&self, id: &str) -> Option<TenantConfiguration> {
+        self.tenants.iter().find(|tc| tc.id == id).cloned()
+    }
+
+    /// The tenant an instance belongs to, which is the tenant of its agent
+    pub fn instance_tenant(&self, instance_id: &str) -> Option<String> {
+        self.instance_by_id(instance_id)
+            .and_then(|instance| self.agent_by_id(&instance.agent))
+            .and_then(|agent| agent.tenant)
+    }
+
     pub fn ui_bundle_by_id(&self, id: &str) -> Option<UiBundleConfiguration> {
         self.ui_bundles.iter().find(|ic| ic.id == id).cloned()
     }
@@ -647,7 +1030,13 @@ impl Configuration {
             .filter_map(|stg_config| match stg_config.storage {
                 StorageConfiguration::File { ref path }
                 | StorageConfiguration::Lmdb { ref path, .. }
-                | StorageConfiguration::Pickle { ref path } => Some(path.as_str()),
+                | StorageConfiguration::Pickle { ref path }
+                | StorageConfiguration::Sqlite { ref path, .. }
+                | StorageConfiguration::Rocksdb { ref path, .. }
+                | StorageConfiguration::Custom {
+                    path: Some(ref path),
+                    ..
+                } => Some(path.as_str()),
                 _ => None,
             })
             .collect();
@@ -682,6 +1071,42 @@ pub struct AgentConfiguration {
     pub holo_remote_key: Option<bool>,
     /// If true this agent will use dummy keys rather than a keystore file
     pub test_agent: Option<bool>,
+    /// If set, conductor will ignore keystore_file and derive this agent's keys from the
+    /// seed stored in the given file without asking for a passphrase.
+    /// Meant for server-side service agents and test fleets.
+    pub seed_file: Option<String>,
+    /// Public signing keys of clients allowed to make zome calls in the name of this agent,
+    /// e.g. a UI holding its own key pair. Calls using the agent's own token have to be
+    /// signed by the agent key or one of these.
+    pub client_keys: Option<Vec<Base32>>,
+    /// If set, the agent's private key is not read from keystore_file but held by the
+    /// given backend. See [KeyBackendConfiguration].
+    pub key_backend: Option<KeyBackendConfiguration>,
+    /// The tenant this agent and its instances belong to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+/// Places other than a keystore file that can hold an agent's private key.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeyBackendConfiguration {
+    /// The agent's root seed (base64 encoded) is stored in the OS keychain, i.e. the macOS
+    /// Keychain or the freedesktop Secret Service on Linux, under the given service and account
+    /// names. Keys get derived from it in secure memory when the agent is loaded.
+    OsKeychain { service: String, account: String },
+    /// The agent's signing key lives on a PKCS#11 token or HSM and never leaves it.
+    /// The device has to support EdDSA (Ed25519) signatures. public_address has to be set to
+    /// the HCID encoded public key of that device key. The token PIN is requested through the
+    /// conductor's passphrase service.
+    Pkcs11 {
+        /// Path of the PKCS#11 module (shared library) of the device
+        module: String,
+        /// Hex encoded CKA_ID of the signing key on the device
+        key_id: String,
+        /// Slot of the token, the first slot with a token if not set
+        slot: Option<u64>,
+    },
 }
 
 impl From<AgentConfiguration> for AgentId {
@@ -720,6 +1145,66 @@ pub struct InstanceConfiguration {
     pub dna: String,
     pub agent: String,
     pub storage: StorageConfiguration,
+    /// Proof of membership (e.g. an invitation) that gets committed with the agent entry at
+    /// genesis, for DNAs whose agent validation only admits invited agents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub membrane_proof: Option<String>,
+    /// Limits for incoming zome calls, counted per capability token and per caller.
+    /// Calls over the limit fail with a RateLimited error before they get queued.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limits: Option<ZomeCallRateLimits>,
+    /// If set, content in the instance's chain and DHT storage gets compressed with zstd
+    /// when it is bigger than the given threshold. Works with all storage types.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<StorageCompression>,
+    /// If true, all chain, DHT and EAV data of the instance gets encrypted before it is
+    /// written to the storage, with a key derived from the conductor's passphrase.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypt_storage: bool,
+    /// If set, recently used content of the instance's chain and DHT storage is kept in
+    /// memory, up to the given size, so reading it again doesn't touch the disk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hot_tier: Option<HotTier>,
+    /// If set, old source chain content and content nothing references anymore can be moved
+    /// from the instance's storage to an S3-compatible object store, which serves it on
+    /// demand. Needs a sqlite, rocksdb or custom storage that supports compaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cold_storage: Option<ColdStorageConfig>,
+    /// Limits of the WASM memory, storage, concurrent zome calls and network bandwidth the
+    /// instance may use, so it can't degrade the other instances of the conductor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_limits: Option<ResourceLimits>,
+    /// How the conductor restarts the instance when its action loop or network thread dies.
+    /// Optional, defaults to the conductor's `restart_policy`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart_policy: Option<RestartPolicy>,
+    /// Where the instance's logs go and from which level on. Optional, defaults to the
+    /// conductor's `logger`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logger: Option<InstanceLoggerConfiguration>,
+    /// From how long on zome calls get reported as slow, in the log, with a signal to admin
+    /// interfaces and in metrics. Optional, defaults to 10 seconds for all functions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_zome_calls: Option<SlowZomeCallConfig>,
+    /// If set, every action the instance reduces gets recorded to this file, which
+    /// `hc replay-actions` can replay offline. Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_actions: Option<String>,
+    /// If set, the instance counts which validation branches of its DNA it runs and keeps
+    /// a coverage report of them in this file, which `hc validation-coverage` summarizes.
+    /// Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation_coverage: Option<String>,
+    /// How entries and links whose validation dependencies are missing get retried, and
+    /// when they get given up on. Optional, defaults to retrying forever with a backoff
+    /// from 15 seconds up to an hour.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_validation: Option<PendingValidationConfig>,
+    /// How many validation packages of other agents the instance keeps, so that validating
+    /// many entries of one author doesn't ask the author for the same package again.
+    /// Optional, defaults to 256. 0 turns the cache off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation_package_cache_size: Option<usize>,
 }
 
 /// This configures the Content Addressable Storage (CAS) that
@@ -727,6 +1212,11 @@ pub struct InstanceConfiguration {
 /// There are two storage implementations in cas_implementations so far:
 /// * memory
 /// * file
+/// * pickle
+/// * lmdb
+/// * sqlite
+/// * rocksdb
+/// * custom, for backends registered by the application embedding the conductor
 ///
 /// Projected are various DB adapters.
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
@@ -743,6 +1233,80 @@ pub enum StorageConfiguration {
         path: String,
         initial_mmap_bytes: Option<usize>,
     },
+    Sqlite {
+        path: String,
+        /// Which attribute classes of the EAV storage get indexed how, see [EavIndexing]
+        #[serde(default)]
+        indexing: EavIndexing,
+    },
+    Rocksdb {
+        path: String,
+        /// Compaction and write buffer tuning, see [RocksDbStorageOptions]
+        #[serde(default)]
+        options: RocksDbStorageOptions,
+        /// Which attribute classes of the EAV storage get indexed how, see [EavIndexing]
+        #[serde(default)]
+        indexing: EavIndexing,
+    },
+    Custom {
+        /// Name the backend got registered under with `Conductor::register_storage_backend`
+        backend: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+        /// Handed to the backend as they are
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        options: Option<serde_json::Value>,
+    },
+}
+
+impl StorageConfiguration {
+    /// Where the storage keeps its data, if on disk
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            StorageConfiguration::Memory => None,
+            StorageConfiguration::File { path }
+            | StorageConfiguration::Pickle { path }
+            | StorageConfiguration::Lmdb { path, .. }
+            | StorageConfiguration::Sqlite { path, .. }
+            | StorageConfiguration::Rocksdb { path, .. } => Some(path),
+            StorageConfiguration::Custom { path, .. } => path.as_ref().map(String::as_str),
+        }
+    }
+
+    /// The same kind of storage with the same settings, but at the given path.
+    /// Memory storage has no path and custom backends only get one if they had one before.
+    pub fn at_path(&self, path: String) -> Self {
+        match self.clone() {
+            StorageConfiguration::Memory => StorageConfiguration::Memory,
+            StorageConfiguration::File { .. } => StorageConfiguration::File { path },
+            StorageConfiguration::Pickle { .. } => StorageConfiguration::Pickle { path },
+            StorageConfiguration::Lmdb {
+                initial_mmap_bytes, ..
+            } => StorageConfiguration::Lmdb {
+                path,
+                initial_mmap_bytes,
+            },
+            StorageConfiguration::Sqlite { indexing, .. } => {
+                StorageConfiguration::Sqlite { path, indexing }
+            }
+            StorageConfiguration::Rocksdb {
+                options, indexing, ..
+            } => StorageConfiguration::Rocksdb {
+                path,
+                options,
+                indexing,
+            },
+            StorageConfiguration::Custom {
+                backend,
+                path: old_path,
+                options,
+            } => StorageConfiguration::Custom {
+                backend,
+                path: old_path.map(|_| path),
+                options,
+            },
+        }
+    }
 }
 
 /// Here, interfaces are user facing and make available zome functions to
@@ -771,14 +1335,82 @@ pub struct InterfaceConfiguration {
     /// is not aware of this logic and is not tracking the new port (which gets printed on stdout).
     /// Use at your own risk...
     pub choose_free_port: Option<bool>,
+    /// If set, zome calls over this interface have to come with the provenance of the caller
+    /// and a signature of the call made by it. The conductor does not sign calls in the name
+    /// of the agent for this interface.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_signed_calls: bool,
+    /// If set, clients of a websocket interface only receive the signals they subscribed to
+    /// with `signals/subscribe`, instead of all signals of the interface's instances.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub filter_signals: bool,
+    /// The tenant this interface belongs to, if any. It can only serve the tenant's
+    /// instances, and if it is an admin interface, it only offers the tenant's admin
+    /// functions, scoped to the tenant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Who may use a websocket or HTTP interface. Optional, defaults to every client that
+    /// can connect to its port.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<InterfaceAuthConfiguration>,
+    /// Serves a websocket or HTTP interface over TLS (`wss://` or `https://`). Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfiguration>,
+}
+
+/// How clients of an interface authenticate. If both are set, clients need a valid
+/// certificate and a token.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct InterfaceAuthConfiguration {
+    /// Hashes of the bearer tokens clients may use, see [hash_token]. Clients send their token
+    /// in an `Authorization: Bearer <token>` header or, since browsers can't set headers on
+    /// websockets, as the `access_token` query parameter.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub token_hashes: Vec<String>,
+    /// PEM file of the certificate authority that client certificates have to be signed by.
+    /// Needs `tls`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca_file: Option<PathBuf>,
+}
+
+/// Certificate and key of an interface that is served over TLS, as PEM files
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct TlsConfiguration {
+    /// The server's certificate, followed by the chain of intermediate certificates if any
+    pub certificate_file: PathBuf,
+    pub private_key_file: PathBuf,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum InterfaceDriver {
-    Websocket { port: u16 },
-    Http { port: u16 },
-    DomainSocket { file: String },
+    Websocket {
+        port: u16,
+    },
+    Http {
+        port: u16,
+    },
+    /// GraphQL over websockets for web frontends (see [interface_impls::graphql])
+    Graphql {
+        port: u16,
+    },
+    /// gRPC service of proto/conductor.proto for backend services (see
+    /// [interface_impls::grpc])
+    Grpc {
+        port: u16,
+    },
+    DomainSocket {
+        file: String,
+    },
+    /// Mutually authenticated and encrypted TCP transport for administering this conductor
+    /// from other conductors (see [interface_impls::remote_admin]).
+    /// The conductor identifies itself with the key of `agent` and only accepts clients
+    /// whose public address is listed in `authorized_keys`.
+    RemoteAdmin {
+        port: u16,
+        agent: String,
+        authorized_keys: Vec<Base32>,
+    },
     Custom(toml::value::Value),
 }
 
@@ -846,6 +1478,23 @@ pub struct UiInterfaceConfiguration {
     #[serde(default)]
     pub dna_interface: Option<String>,
 
+    /// Host name to serve this UI for, so that the UIs of several hApps can share a port.
+    /// Requests for other host names get the UI on the same port that has no host set.
+    /// (Optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+
+    /// Path to serve this UI under, e.g. "/chat", which can also be used to share a port.
+    /// Default = "/"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_prefix: Option<String>,
+
+    /// Instance the UI calls over its DNA interface, by ID or by alias in the interface.
+    /// It is injected into served HTML pages together with the URL of the DNA interface,
+    /// see [static_file_server](crate::static_file_server). (Optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+
     #[serde(default = "default_reroute")]
     /// Re-route any failed HTTP Gets to /index.html
     /// This is required for SPAs using virtual routing
@@ -859,6 +1508,20 @@ pub struct UiInterfaceConfiguration {
     pub bind_address: String,
 }
 
+impl UiInterfaceConfiguration {
+    /// The path prefix without trailing slash, or "/"
+    pub fn path_prefix(&self) -> String {
+        match self
+            .path_prefix
+            .as_ref()
+            .map(|prefix| prefix.trim_end_matches('/'))
+        {
+            None | Some("") => String::from("/"),
+            Some(prefix) => prefix.to_string(),
+        }
+    }
+}
+
 fn default_reroute() -> bool {
     true
 }
@@ -912,10 +1575,158 @@ pub struct DpkiConfiguration {
 }
 
 /// Configure which signals to emit, to reduce unwanted signal volume
-#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub struct SignalConfig {
     pub trace: bool,
     pub consistency: bool,
+    /// How many user signals of each instance are kept for clients to replay after they
+    /// reconnected, see [signal_history](crate::conductor::signal_history)
+    #[serde(default = "default_signal_history_size")]
+    pub history_size: usize,
+}
+
+fn default_signal_history_size() -> usize {
+    100
+}
+
+impl Default for SignalConfig {
+    fn default() -> Self {
+        SignalConfig {
+            trace: false,
+            consistency: false,
+            history_size: default_signal_history_size(),
+        }
+    }
+}
+
+/// Thresholds above which the conductor alerts the webhooks, checked every
+/// `check_interval_secs`. An alert that keeps firing gets sent again every `repeat_after_secs`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct AlertConfiguration {
+    pub webhooks: Vec<WebhookConfiguration>,
+    /// Holding workflows waiting to run
+    #[serde(default = "default_max_holding_queue_depth")]
+    pub max_holding_queue_depth: u64,
+    /// How long a validation may stay pending
+    #[serde(default = "default_max_pending_validation_age_secs")]
+    pub max_pending_validation_age_secs: u64,
+    /// Network requests that timed out in the last five minutes
+    #[serde(default = "default_max_network_timeouts")]
+    pub max_network_timeouts: u64,
+    /// Restarts of a crashed instance in a row, see [RestartPolicy]
+    #[serde(default = "default_max_instance_restarts")]
+    pub max_instance_restarts: u64,
+    #[serde(default = "default_alert_check_interval_secs")]
+    pub check_interval_secs: u64,
+    #[serde(default = "default_alert_repeat_after_secs")]
+    pub repeat_after_secs: u64,
+}
+
+fn default_max_holding_queue_depth() -> u64 {
+    1000
+}
+
+fn default_max_pending_validation_age_secs() -> u64 {
+    600
+}
+
+fn default_max_network_timeouts() -> u64 {
+    20
+}
+
+fn default_max_instance_restarts() -> u64 {
+    3
+}
+
+fn default_alert_check_interval_secs() -> u64 {
+    30
+}
+
+fn default_alert_repeat_after_secs() -> u64 {
+    3600
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    HoldingQueueDepth,
+    PendingValidationAge,
+    NetworkTimeouts,
+    InstanceRestarts,
+}
+
+/// Alerts get posted as JSON to `url`
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct WebhookConfiguration {
+    pub url: String,
+    /// Headers to send with each alert, e.g. for authorization
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Only these kinds of alerts get sent, all of them if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<AlertKind>,
+}
+
+impl WebhookConfiguration {
+    pub fn wants(&self, kind: AlertKind) -> bool {
+        self.alerts.is_empty() || self.alerts.contains(&kind)
+    }
+}
+
+/// Restarts of a crashed instance get delayed exponentially, starting at `initial_backoff_ms`
+/// and doubling with every restart up to `max_backoff_ms`. After `max_restarts` restarts the
+/// instance is left stopped. An instance that kept running for `reset_after_secs` since its
+/// last restart starts over with the initial backoff.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct RestartPolicy {
+    /// 0 disables restarts
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    #[serde(default = "default_reset_after_secs")]
+    pub reset_after_secs: u64,
+}
+
+fn default_max_restarts() -> u32 {
+    5
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_max_backoff_ms() -> u64 {
+    60_000
+}
+
+fn default_reset_after_secs() -> u64 {
+    300
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            max_restarts: default_max_restarts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            reset_after_secs: default_reset_after_secs(),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// How long to wait before the given restart, counted from 1
+    pub fn backoff(&self, restart: u32) -> Duration {
+        let factor = 1u64.checked_shl(restart.saturating_sub(1)).unwrap_or(0);
+        let backoff_ms = match self.initial_backoff_ms.checked_mul(factor) {
+            Some(backoff_ms) if factor > 0 => min(backoff_ms, self.max_backoff_ms),
+            _ => self.max_backoff_ms,
+        };
+        Duration::from_millis(backoff_ms)
+    }
 }
 
 #[cfg(test)]
@@ -924,6 +1735,8 @@ pub mod tests {
     use crate::{
         conductor::tests::test_dna_loader,
         config::{load_configuration, Configuration, NetworkConfig},
+        eav_indexing::EavIndexStrategy,
+        rocksdb_storage::RocksDbCompactionStyle,
     };
     use holochain_net::p2p_config::P2pConfig;
 
@@ -1275,6 +2088,200 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_remote_admin_interface_config() {
+        let toml = r#"
+    [[agents]]
+    id = "test agent"
+    name = "Holo Tester 1"
+    public_address = "HoloTester1-------------------------------------------------------------------------AHi1"
+    keystore_file = "holo_tester.key"
+
+    [[interfaces]]
+    id = "remote admin interface"
+    admin = true
+        [interfaces.driver]
+        type = "remoteadmin"
+        port = 9000
+        agent = "test agent"
+        authorized_keys = ["HoloTester2-------------------------------------------------------------------------AHi2"]
+    "#;
+
+        let mut config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(config.check_consistency(&mut test_dna_loader()), Ok(()));
+        assert_eq!(
+            config.interfaces[0].driver,
+            InterfaceDriver::RemoteAdmin {
+                port: 9000,
+                agent: "test agent".to_string(),
+                authorized_keys: vec![
+                    "HoloTester2-------------------------------------------------------------------------AHi2".to_string()
+                ],
+            }
+        );
+
+        config.agents.clear();
+        assert_eq!(
+            config.check_consistency(&mut test_dna_loader()),
+            Err(
+                "Agent configuration test agent not found, mentioned in interface remote admin interface"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_rocksdb_storage_config() {
+        let toml = r#"
+    type = "rocksdb"
+    path = "/tmp/rocks"
+        [options]
+        compaction_style = "universal"
+        disable_auto_compactions = true
+    "#;
+
+        let storage = load_configuration::<StorageConfiguration>(toml).unwrap();
+        assert_eq!(
+            storage,
+            StorageConfiguration::Rocksdb {
+                path: "/tmp/rocks".to_string(),
+                options: RocksDbStorageOptions {
+                    compaction_style: RocksDbCompactionStyle::Universal,
+                    disable_auto_compactions: true,
+                    ..Default::default()
+                },
+                indexing: EavIndexing::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_custom_storage_config() {
+        let toml = r#"
+    type = "custom"
+    backend = "postgres"
+        [options]
+        schema = "holochain"
+        pool_size = 4
+    "#;
+
+        let storage = load_configuration::<StorageConfiguration>(toml).unwrap();
+        assert_eq!(
+            storage,
+            StorageConfiguration::Custom {
+                backend: "postgres".to_string(),
+                path: None,
+                options: Some(json!({"schema": "holochain", "pool_size": 4})),
+            }
+        );
+    }
+
+    #[test]
+    fn test_sqlite_storage_indexing_config() {
+        let toml = r#"
+    type = "sqlite"
+    path = "/tmp/sqlite"
+        [indexing]
+        default = "entity"
+        crud_status = "none"
+        link_tag = "full"
+    "#;
+
+        let storage = load_configuration::<StorageConfiguration>(toml).unwrap();
+        assert_eq!(
+            storage,
+            StorageConfiguration::Sqlite {
+                path: "/tmp/sqlite".to_string(),
+                indexing: EavIndexing {
+                    default: EavIndexStrategy::Entity,
+                    crud_status: Some(EavIndexStrategy::None),
+                    link_tag: Some(EavIndexStrategy::Full),
+                    ..Default::default()
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_instance_storage_encryption_config() {
+        let toml = r#"
+    id = "encrypted instance"
+    dna = "app spec rust"
+    agent = "test agent"
+    encrypt_storage = true
+        [storage]
+        type = "memory"
+    "#;
+
+        let instance = load_configuration::<InstanceConfiguration>(toml).unwrap();
+        assert!(instance.encrypt_storage);
+
+        let toml = r#"
+    id = "plain instance"
+    dna = "app spec rust"
+    agent = "test agent"
+        [storage]
+        type = "memory"
+    "#;
+        let instance = load_configuration::<InstanceConfiguration>(toml).unwrap();
+        assert!(!instance.encrypt_storage);
+    }
+
+    #[test]
+    fn test_instance_cold_storage_config() {
+        let toml = r#"
+    id = "archiving instance"
+    dna = "app spec rust"
+    agent = "test agent"
+        [storage]
+        type = "sqlite"
+        path = "/tmp/archiving"
+        [cold_storage]
+        bucket = "holochain-archive"
+        endpoint = "http://localhost:9000"
+        prefix = "archiving/"
+    "#;
+
+        let instance = load_configuration::<InstanceConfiguration>(toml).unwrap();
+        assert_eq!(
+            instance.cold_storage,
+            Some(ColdStorageConfig {
+                bucket: String::from("holochain-archive"),
+                region: String::from("us-east-1"),
+                endpoint: Some(String::from("http://localhost:9000")),
+                prefix: String::from("archiving/"),
+                keep_chain_headers: 1000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_storage_at_path() {
+        let rocksdb = StorageConfiguration::Rocksdb {
+            path: String::from("/tmp/source"),
+            options: Default::default(),
+            indexing: Default::default(),
+        };
+        assert_eq!(
+            rocksdb.at_path(String::from("/tmp/clone")),
+            StorageConfiguration::Rocksdb {
+                path: String::from("/tmp/clone"),
+                options: Default::default(),
+                indexing: Default::default(),
+            }
+        );
+        assert_eq!(
+            StorageConfiguration::Memory.at_path(String::from("/tmp/clone")),
+            StorageConfiguration::Memory
+        );
+        let custom = StorageConfiguration::Custom {
+            backend: String::from("shared-memory"),
+            path: None,
+            options: None,
+        };
+        assert_eq!(custom.at_path(String::from("/tmp/clone")), custom);
+    }
+
     #[test]
     fn test_invalid_toml_1() {
         let toml = &format!(
@@ -1675,4 +2682,263 @@ pub mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_restart_policy() {
+        let toml = r#"
+    [[agents]]
+    id = "test agent"
+    name = "Holo Tester 1"
+    public_address = "HoloTester1-------------------------------------------------------------------------AHi1"
+    keystore_file = "holo_tester.key"
+
+    [[dnas]]
+    id = "app spec rust"
+    file = "app_spec.dna.json"
+    hash = "Qm328wyq38924y"
+
+    [[instances]]
+    id = "app spec instance"
+    dna = "app spec rust"
+    agent = "test agent"
+        [instances.storage]
+        type = "memory"
+        [instances.restart_policy]
+        max_restarts = 0
+
+    [restart_policy]
+    initial_backoff_ms = 500
+    "#;
+
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(
+            config.restart_policy,
+            RestartPolicy {
+                initial_backoff_ms: 500,
+                ..RestartPolicy::default()
+            }
+        );
+        let instance_policy = config.instances[0].restart_policy.clone().unwrap();
+        assert_eq!(instance_policy.max_restarts, 0);
+        assert_eq!(instance_policy.max_backoff_ms, 60_000);
+    }
+
+    #[test]
+    fn test_slow_zome_calls_config() {
+        let toml = r#"
+    [[agents]]
+    id = "test agent"
+    name = "Holo Tester 1"
+    public_address = "HoloTester1-------------------------------------------------------------------------AHi1"
+    keystore_file = "holo_tester.key"
+
+    [[dnas]]
+    id = "app spec rust"
+    file = "app_spec.dna.json"
+    hash = "Qm328wyq38924y"
+
+    [[instances]]
+    id = "app spec instance"
+    dna = "app spec rust"
+    agent = "test agent"
+        [instances.storage]
+        type = "memory"
+        [instances.slow_zome_calls.functions]
+        "blog/create_post" = 500
+    "#;
+
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        let slow_zome_calls = config.instances[0].slow_zome_calls.clone().unwrap();
+        assert_eq!(slow_zome_calls.threshold_ms, 10_000);
+        assert_eq!(
+            slow_zome_calls.functions.get("blog/create_post"),
+            Some(&500)
+        );
+    }
+
+    #[test]
+    fn test_dna_registry_must_use_https() {
+        let mut config = Configuration::default();
+        config.dna_registry = Some(DnaRegistryConfiguration {
+            url: String::from("http://dnas.example.com"),
+        });
+        let result = config.check_consistency(&mut test_dna_loader());
+        assert!(result.unwrap_err().contains("HTTPS"));
+
+        config.dna_registry = Some(DnaRegistryConfiguration {
+            url: String::from("https://dnas.example.com"),
+        });
+        assert_eq!(config.check_consistency(&mut test_dna_loader()), Ok(()));
+    }
+
+    #[test]
+    fn test_otlp_tracing_config() {
+        let toml = r#"
+    agents = []
+    dnas = []
+    instances = []
+    interfaces = []
+
+    [tracing]
+    type = "otlp"
+    service_name = "conductor"
+
+    [tracing.headers]
+    authorization = "Bearer token"
+    "#;
+        let mut config = load_configuration::<Configuration>(toml).unwrap();
+        let otlp = match config.tracing {
+            Some(TracingConfiguration::Otlp(ref otlp)) => otlp.clone(),
+            ref other => panic!("Expected OTLP tracing, got {:?}", other),
+        };
+        assert_eq!(otlp.endpoint, "http://localhost:4318/v1/traces");
+        assert_eq!(otlp.batch_size, 512);
+        assert_eq!(otlp.headers["authorization"], "Bearer token");
+        assert_eq!(config.check_consistency(&mut test_dna_loader()), Ok(()));
+
+        config.tracing = Some(TracingConfiguration::Otlp(OtlpTracingConfiguration {
+            endpoint: String::from("not a url"),
+            ..otlp
+        }));
+        assert!(config
+            .check_consistency(&mut test_dna_loader())
+            .unwrap_err()
+            .contains("OTLP endpoint"));
+    }
+
+    #[test]
+    fn test_alert_config() {
+        let toml = r#"
+    agents = []
+    dnas = []
+    instances = []
+    interfaces = []
+
+    [alerts]
+    max_holding_queue_depth = 500
+
+    [[alerts.webhooks]]
+    url = "https://alerts.example.com/hook"
+    alerts = ["holding_queue_depth", "instance_restarts"]
+
+    [[alerts.webhooks]]
+    url = "not a url"
+    "#;
+        let mut config = load_configuration::<Configuration>(toml).unwrap();
+        let alerts = config.alerts.clone().unwrap();
+        assert_eq!(alerts.max_holding_queue_depth, 500);
+        assert_eq!(alerts.max_pending_validation_age_secs, 600);
+        assert!(alerts.webhooks[0].wants(AlertKind::InstanceRestarts));
+        assert!(!alerts.webhooks[0].wants(AlertKind::NetworkTimeouts));
+        assert!(alerts.webhooks[1].wants(AlertKind::NetworkTimeouts));
+        assert!(config
+            .check_consistency(&mut test_dna_loader())
+            .unwrap_err()
+            .contains("alert webhook"));
+
+        config.alerts.as_mut().unwrap().webhooks.pop();
+        assert_eq!(config.check_consistency(&mut test_dna_loader()), Ok(()));
+    }
+
+    #[test]
+    fn test_json_log_format_config() {
+        let toml = r#"
+    agents = []
+    dnas = []
+    instances = []
+    interfaces = []
+
+    [logger]
+    type = "info"
+    format = "json"
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(config.logger.format, LogFormat::Json);
+        assert_eq!(Configuration::default().logger.format, LogFormat::Text);
+    }
+
+    #[test]
+    fn test_metrics_endpoint_config() {
+        let toml = r#"
+    agents = []
+    dnas = []
+    instances = []
+    interfaces = []
+
+    [metrics_endpoint]
+    port = 9100
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(
+            config.metrics_endpoint,
+            Some(MetricsEndpointConfiguration {
+                port: 9100,
+                bind_address: String::from("127.0.0.1"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_ui_interfaces_sharing_a_port() {
+        let ui_interface =
+            |id: &str, host: Option<&str>, path_prefix: Option<&str>| UiInterfaceConfiguration {
+                id: id.to_string(),
+                bundle: String::from("bundle"),
+                port: 3000,
+                dna_interface: None,
+                host: host.map(String::from),
+                path_prefix: path_prefix.map(String::from),
+                instance: None,
+                reroute_to_root: true,
+                bind_address: String::from("127.0.0.1"),
+            };
+        let mut config = Configuration::default();
+        config.ui_bundles = vec![UiBundleConfiguration {
+            id: String::from("bundle"),
+            root_dir: String::from("."),
+            hash: None,
+        }];
+        config.ui_interfaces = vec![
+            ui_interface("default", None, None),
+            ui_interface("chat", Some("chat.example.com"), None),
+            ui_interface("wiki", None, Some("/wiki/")),
+        ];
+        assert_eq!(config.check_consistency(&mut test_dna_loader()), Ok(()));
+        assert_eq!(config.ui_interfaces[2].path_prefix(), "/wiki");
+
+        config
+            .ui_interfaces
+            .push(ui_interface("chat2", Some("Chat.example.com"), Some("/")));
+        assert!(config
+            .check_consistency(&mut test_dna_loader())
+            .unwrap_err()
+            .contains("the same host and path"));
+
+        config.ui_interfaces.pop();
+        config
+            .ui_interfaces
+            .push(ui_interface("relative", None, Some("wiki")));
+        assert!(config
+            .check_consistency(&mut test_dna_loader())
+            .unwrap_err()
+            .contains("has to start with"));
+
+        config.ui_interfaces.pop();
+        let mut other_address = ui_interface("other-address", Some("other.example.com"), None);
+        other_address.bind_address = String::from("0.0.0.0");
+        config.ui_interfaces.push(other_address);
+        assert!(config
+            .check_consistency(&mut test_dna_loader())
+            .unwrap_err()
+            .contains("different addresses"));
+
+        config.ui_interfaces.pop();
+        let mut with_instance = ui_interface("with-instance", Some("app.example.com"), None);
+        with_instance.instance = Some(String::from("app"));
+        config.ui_interfaces.push(with_instance);
+        assert!(config
+            .check_consistency(&mut test_dna_loader())
+            .unwrap_err()
+            .contains("not part of its DNA interface"));
+    }
 }
@@ -1,9 +1,33 @@
-use holochain_core::{context::Context, persister::SimplePersister, signal::SignalSender};
+use crate::{
+    cold_storage::{ColdStorage, ObjectStore},
+    compressed_storage::{CompressedStorage, StorageCompression},
+    eav_indexing::EavIndexing,
+    encrypted_storage::{EavEncryptedStorage, EncryptedStorage, StorageKey},
+    quota_storage::QuotaStorage,
+    rocksdb_storage::{EavRocksDbStorage, RocksDbCompactor, RocksDbStorage, RocksDbStorageOptions},
+    sqlite_storage::{EavSqliteStorage, SqliteCompactor, SqliteStorage},
+    storage_backend::CustomStorage,
+    tiered_storage::{HotTier, TieredStorage},
+};
+use holochain_core::{
+    context::Context,
+    dht::pending_validations::PendingValidationConfig,
+    network::validation_package_cache::DEFAULT_VALIDATION_PACKAGE_CACHE_SIZE,
+    nucleus::{
+        rate_limit::{ZomeCallRateLimiter, ZomeCallRateLimits},
+        slow_calls::{SlowZomeCallConfig, ZomeCallWatchdog},
+    },
+    persister::SimplePersister,
+    resource_limits::{ResourceLimits, StorageQuota},
+    signal::SignalSender,
+    storage_compaction::CompactStorage,
+};
 use holochain_core_types::{agent::AgentId, eav::Attribute, error::HolochainError};
 use holochain_locksmith::RwLock;
 use holochain_net::p2p_config::P2pConfig;
 use holochain_persistence_api::{
-    cas::storage::ContentAddressableStorage, eav::EntityAttributeValueStorage,
+    cas::{content::Address, storage::ContentAddressableStorage},
+    eav::EntityAttributeValueStorage,
 };
 use holochain_persistence_file::{cas::file::FilesystemStorage, eav::file::EavFileStorage};
 use holochain_persistence_lmdb::{cas::lmdb::LmdbStorage, eav::lmdb::EavLmdbStorage};
@@ -37,6 +61,16 @@ pub struct ContextBuilder {
     chain_storage: Option<Arc<RwLock<dyn ContentAddressableStorage>>>,
     dht_storage: Option<Arc<RwLock<dyn ContentAddressableStorage>>>,
     eav_storage: Option<Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>>,
+    dna_storage: Option<Arc<RwLock<dyn ContentAddressableStorage>>>,
+    storage_compactor: Option<Arc<dyn CompactStorage>>,
+    cold_storage: Option<ColdStorage>,
+    client_keys: Vec<Address>,
+    zome_call_rate_limits: ZomeCallRateLimits,
+    slow_zome_call_config: SlowZomeCallConfig,
+    pending_validation_config: PendingValidationConfig,
+    validation_package_cache_size: usize,
+    resource_limits: ResourceLimits,
+    storage_quota: Option<Arc<StorageQuota>>,
     p2p_config: Option<P2pConfig>,
     conductor_api: Option<Arc<RwLock<IoHandler>>>,
     signal_tx: Option<SignalSender>,
@@ -54,6 +88,16 @@ impl ContextBuilder {
             chain_storage: None,
             dht_storage: None,
             eav_storage: None,
+            dna_storage: None,
+            storage_compactor: None,
+            cold_storage: None,
+            client_keys: Vec::new(),
+            zome_call_rate_limits: ZomeCallRateLimits::default(),
+            slow_zome_call_config: SlowZomeCallConfig::default(),
+            pending_validation_config: PendingValidationConfig::default(),
+            validation_package_cache_size: DEFAULT_VALIDATION_PACKAGE_CACHE_SIZE,
+            resource_limits: ResourceLimits::default(),
+            storage_quota: None,
             p2p_config: None,
             conductor_api: None,
             signal_tx: None,
@@ -143,6 +187,236 @@ impl ContextBuilder {
         Ok(self)
     }
 
+    /// Sets all three storages, chain, DHT and EAV storage, to persistent SQLite based
+    /// implementations. Chain and DHT storages get set to the same SQLite CAS.
+    /// Orphaned content in it can be removed with storage compaction.
+    /// The EAV storage indexes each attribute class as the given indexing says.
+    /// Returns an error if the SQLite databases could not be opened on the given path.
+    pub fn with_sqlite_storage<P: AsRef<Path>>(
+        mut self,
+        path: P,
+        indexing: &EavIndexing,
+    ) -> Result<Self, HolochainError> {
+        let base_path: PathBuf = path.as_ref().into();
+        fs::create_dir_all(&base_path)?;
+
+        let cas_storage = SqliteStorage::new(base_path.join("cas.sqlite"))?;
+        let eav_storage =
+            EavSqliteStorage::new(base_path.join("eav.sqlite"))?.with_indexing(indexing.clone());
+        self.storage_compactor = Some(Arc::new(SqliteCompactor::new(
+            cas_storage.clone(),
+            eav_storage.clone(),
+        )));
+        let cas_storage = Arc::new(RwLock::new(cas_storage));
+        let eav_storage = Arc::new(RwLock::new(eav_storage));
+        self.chain_storage = Some(cas_storage.clone());
+        self.dht_storage = Some(cas_storage);
+        self.eav_storage = Some(eav_storage);
+        Ok(self)
+    }
+
+    /// Sets all three storages, chain, DHT and EAV storage, to persistent RocksDB based
+    /// implementations tuned with the given options. Chain and DHT storages get set to the same
+    /// RocksDB CAS. Orphaned content in it can be removed with storage compaction.
+    /// Returns an error if the RocksDB databases could not be opened on the given path.
+    pub fn with_rocksdb_storage<P: AsRef<Path>>(
+        mut self,
+        path: P,
+        options: &RocksDbStorageOptions,
+        indexing: &EavIndexing,
+    ) -> Result<Self, HolochainError> {
+        let base_path: PathBuf = path.as_ref().into();
+        let cas_path = base_path.join("cas");
+        let eav_path = base_path.join("eav");
+        fs::create_dir_all(&cas_path)?;
+        fs::create_dir_all(&eav_path)?;
+
+        let cas_storage = RocksDbStorage::new(&cas_path, options)?;
+        let eav_storage =
+            EavRocksDbStorage::new(&eav_path, options)?.with_indexing(indexing.clone());
+        self.storage_compactor = Some(Arc::new(RocksDbCompactor::new(
+            cas_storage.clone(),
+            eav_storage.clone(),
+        )));
+        let cas_storage = Arc::new(RwLock::new(cas_storage));
+        let eav_storage = Arc::new(RwLock::new(eav_storage));
+        self.chain_storage = Some(cas_storage.clone());
+        self.dht_storage = Some(cas_storage);
+        self.eav_storage = Some(eav_storage);
+        Ok(self)
+    }
+
+    /// Sets all three storages, chain, DHT and EAV storage, to the ones a custom storage
+    /// backend created. Chain and DHT storages get set to its CAS.
+    pub fn with_custom_storage(mut self, storage: CustomStorage) -> Self {
+        self.storage_compactor = storage.compactor;
+        self.chain_storage = Some(storage.cas.clone());
+        self.dht_storage = Some(storage.cas);
+        self.eav_storage = Some(storage.eav);
+        self
+    }
+
+    /// Lets the chain and DHT storage set so far move cold content to the given object store,
+    /// under keys starting with `prefix`. Needs a storage that chain and DHT share and that
+    /// supports compaction, and needs to be called before the other storage wrappers so that
+    /// archived content is encrypted and compressed like local content.
+    pub fn with_cold_storage(
+        mut self,
+        store: Arc<dyn ObjectStore>,
+        prefix: String,
+    ) -> Result<Self, HolochainError> {
+        let (local, compactor) = match (
+            &self.chain_storage,
+            &self.dht_storage,
+            &self.storage_compactor,
+        ) {
+            (Some(chain_storage), Some(dht_storage), Some(compactor))
+                if Arc::ptr_eq(chain_storage, dht_storage) =>
+            {
+                (chain_storage.clone(), compactor.clone())
+            }
+            _ => {
+                return Err(HolochainError::ConfigError(String::from(
+                    "Cold storage needs a sqlite, rocksdb or custom storage with compaction",
+                )))
+            }
+        };
+        let cold_storage = ColdStorage::new(local, compactor, store, prefix)?;
+        self.storage_compactor = Some(cold_storage.compactor());
+        let storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(cold_storage.clone()));
+        self.chain_storage = Some(storage.clone());
+        self.dht_storage = Some(storage);
+        self.cold_storage = Some(cold_storage);
+        Ok(self)
+    }
+
+    /// The cold storage set up by [with_cold_storage](ContextBuilder::with_cold_storage),
+    /// for archiving content of the spawned context's instance
+    pub fn cold_storage(&self) -> Option<ColdStorage> {
+        self.cold_storage.clone()
+    }
+
+    /// Makes the chain, DHT and EAV storages set so far encrypt everything they write with the
+    /// given key. Needs to be called after one of the storage setters above and before
+    /// [with_storage_compression](ContextBuilder::with_storage_compression), since encrypted
+    /// content doesn't compress.
+    pub fn with_storage_encryption(mut self, key: StorageKey) -> Self {
+        self.wrap_content_storages(|storage| {
+            Arc::new(RwLock::new(EncryptedStorage::new(storage, key.clone())))
+        });
+        self.eav_storage = self.eav_storage.take().map(|storage| {
+            let encrypted: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+                Arc::new(RwLock::new(EavEncryptedStorage::new(storage, key.clone())));
+            encrypted
+        });
+        self
+    }
+
+    /// Makes the chain and DHT storages set so far compress their content with the given
+    /// settings. Needs to be called after one of the storage setters above.
+    pub fn with_storage_compression(mut self, compression: StorageCompression) -> Self {
+        self.wrap_content_storages(|storage| {
+            Arc::new(RwLock::new(CompressedStorage::new(
+                storage,
+                compression.clone(),
+            )))
+        });
+        self
+    }
+
+    /// Puts a memory tier of the given size in front of the chain and DHT storages set so far,
+    /// which keeps recently used content so reading it again doesn't touch the disk.
+    /// Should be called last of all storage wrappers so the memory tier holds content as it
+    /// is read, not compressed or encrypted.
+    pub fn with_hot_tier(mut self, hot_tier: HotTier) -> Self {
+        self.wrap_content_storages(|storage| {
+            Arc::new(RwLock::new(TieredStorage::new(storage, &hot_tier)))
+        });
+        self
+    }
+
+    /// Sets the limits of the resources the instance may use. If they limit storage, the chain
+    /// and DHT storages set so far refuse content beyond the limit, so this needs to be called
+    /// after the storage setters above. Call it before
+    /// [with_hot_tier](ContextBuilder::with_hot_tier) so content gets counted as the instance
+    /// writes it, and after the other storage wrappers.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        if let Some(max_bytes) = limits.max_storage_bytes {
+            let quota = Arc::new(StorageQuota::new(max_bytes));
+            self.wrap_content_storages(|storage| {
+                Arc::new(RwLock::new(QuotaStorage::new(storage, quota.clone())))
+            });
+            self.storage_quota = Some(quota);
+        }
+        self.resource_limits = limits;
+        self
+    }
+
+    /// Replaces the chain and DHT storages by what `wrap` makes of them.
+    fn wrap_content_storages<F>(&mut self, wrap: F)
+    where
+        F: Fn(
+            Arc<RwLock<dyn ContentAddressableStorage>>,
+        ) -> Arc<RwLock<dyn ContentAddressableStorage>>,
+    {
+        match (self.chain_storage.take(), self.dht_storage.take()) {
+            // Keep sharing a single storage between chain and DHT
+            (Some(chain_storage), Some(dht_storage))
+                if Arc::ptr_eq(&chain_storage, &dht_storage) =>
+            {
+                let wrapped = wrap(chain_storage);
+                self.chain_storage = Some(wrapped.clone());
+                self.dht_storage = Some(wrapped);
+            }
+            (chain_storage, dht_storage) => {
+                self.chain_storage = chain_storage.map(&wrap);
+                self.dht_storage = dht_storage.map(&wrap);
+            }
+        }
+    }
+
+    /// Sets a storage for DNA entries that is shared with other contexts.
+    /// Instances built from these contexts keep a single copy of their DNA entry in there
+    /// instead of each storing it in their own chain and DHT storage.
+    pub fn with_dna_storage(
+        mut self,
+        dna_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    ) -> Self {
+        self.dna_storage = Some(dna_storage);
+        self
+    }
+
+    /// Sets the public keys of clients that may sign zome calls in the name of the agent.
+    pub fn with_client_keys(mut self, client_keys: Vec<Address>) -> Self {
+        self.client_keys = client_keys;
+        self
+    }
+
+    /// Sets the limits for incoming zome calls, per capability token and per caller.
+    pub fn with_zome_call_rate_limits(mut self, limits: ZomeCallRateLimits) -> Self {
+        self.zome_call_rate_limits = limits;
+        self
+    }
+
+    /// Sets when zome calls get reported as slow.
+    pub fn with_slow_zome_call_config(mut self, config: SlowZomeCallConfig) -> Self {
+        self.slow_zome_call_config = config;
+        self
+    }
+
+    /// Sets how holding workflows with unresolved dependencies get retried and given up on.
+    pub fn with_pending_validation_config(mut self, config: PendingValidationConfig) -> Self {
+        self.pending_validation_config = config;
+        self
+    }
+
+    /// Sets how many validation packages of other agents get cached, 0 turns caching off.
+    pub fn with_validation_package_cache_size(mut self, size: usize) -> Self {
+        self.validation_package_cache_size = size;
+        self
+    }
+
     /// Sets the network config.
     pub fn with_p2p_config(mut self, p2p_config: P2pConfig) -> Self {
         self.p2p_config = Some(p2p_config);
@@ -213,7 +487,7 @@ impl ContextBuilder {
             .metric_publisher
             .unwrap_or_else(|| Arc::new(RwLock::new(DefaultMetricPublisher::default())));
 
-        Context::new(
+        let mut context = Context::new(
             &self
                 .instance_name
                 .unwrap_or_else(|| "Anonymous-instance".to_string()),
@@ -234,7 +508,20 @@ impl ContextBuilder {
                 self.tracer
                     .unwrap_or_else(|| holochain_tracing::null_tracer()),
             ),
-        )
+        );
+        context.dna_storage = self.dna_storage;
+        context.storage_compactor = self.storage_compactor;
+        context.client_keys = self.client_keys;
+        context.zome_call_rate_limiter = Arc::new(
+            ZomeCallRateLimiter::new(self.zome_call_rate_limits)
+                .with_max_concurrent_calls(self.resource_limits.max_concurrent_calls),
+        );
+        context.zome_call_watchdog = Arc::new(ZomeCallWatchdog::new(self.slow_zome_call_config));
+        context.pending_validation_config = self.pending_validation_config;
+        context.validation_package_cache_size = self.validation_package_cache_size;
+        context.resource_limits = self.resource_limits;
+        context.storage_quota = self.storage_quota;
+        context
     }
 }
 
@@ -242,6 +529,7 @@ impl ContextBuilder {
 mod tests {
     use self::tempfile::tempdir;
     use super::*;
+    use crate::cold_storage::MemoryObjectStore;
     use holochain_net::p2p_config::P2pBackendKind;
     use tempfile;
     use test_utils::mock_signing::mock_conductor_api;
@@ -292,5 +580,80 @@ mod tests {
             .expect("Filestorage should get instantiated with tempdir")
             .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
             .spawn();
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let _ = ContextBuilder::new()
+            .with_sqlite_storage(temp.path(), &EavIndexing::default())
+            .expect("SQLite storage should get instantiated with tempdir")
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let _ = ContextBuilder::new()
+            .with_rocksdb_storage(
+                temp.path(),
+                &RocksDbStorageOptions::default(),
+                &EavIndexing::default(),
+            )
+            .expect("RocksDB storage should get instantiated with tempdir")
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+    }
+
+    #[test]
+    fn with_resource_limits() {
+        let limits = ResourceLimits {
+            max_storage_bytes: Some(1000),
+            max_concurrent_calls: Some(4),
+            ..Default::default()
+        };
+        let context = ContextBuilder::new()
+            .with_memory_storage()
+            .with_resource_limits(limits.clone())
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+        assert_eq!(context.resource_limits, limits);
+        assert_eq!(
+            context
+                .storage_quota
+                .as_ref()
+                .map(|quota| quota.max_bytes()),
+            Some(1000)
+        );
+        assert!(Arc::ptr_eq(&context.chain_storage, &context.dht_storage));
+    }
+
+    #[test]
+    fn storage_wrappers_keep_chain_and_dht_storage_shared() {
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let context = ContextBuilder::new()
+            .with_sqlite_storage(temp.path(), &EavIndexing::default())
+            .expect("SQLite storage should get instantiated with tempdir")
+            .with_storage_compression(StorageCompression::default())
+            .with_hot_tier(HotTier::default())
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+        assert!(Arc::ptr_eq(&context.chain_storage, &context.dht_storage));
+        assert!(context.storage_compactor.is_some());
+    }
+
+    #[test]
+    fn cold_storage_needs_compactable_storage() {
+        let store: Arc<dyn ObjectStore> = Arc::new(MemoryObjectStore::default());
+        assert!(ContextBuilder::new()
+            .with_memory_storage()
+            .with_cold_storage(store.clone(), String::new())
+            .is_err());
+
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let builder = ContextBuilder::new()
+            .with_sqlite_storage(temp.path(), &EavIndexing::default())
+            .expect("SQLite storage should get instantiated with tempdir")
+            .with_cold_storage(store, String::from("alice/"))
+            .expect("Cold storage should wrap SQLite storage");
+        assert!(builder.cold_storage().is_some());
+        let context = builder
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+        assert!(Arc::ptr_eq(&context.chain_storage, &context.dht_storage));
+        assert!(context.storage_compactor.is_some());
     }
 }
@@ -13,6 +13,7 @@ pub const DPKI_ZOME_NAME: &str = "dpki";
 pub const DPKI_TRAIT_FN_ADD_AGENT: &str = "create_agent_key";
 pub const DPKI_TRAIT_FN_INIT: &str = "init_dpki";
 pub const DPKI_TRAIT_FN_IS_INITIALIZED: &str = "is_initialized";
+pub const DPKI_TRAIT_FN_IS_KEY_REGISTERED: &str = "is_key_registered";
 
 use std::{convert::TryInto, sync::Arc};
 
@@ -20,6 +21,7 @@ pub trait DpkiInstance {
     fn dpki_create_agent_key(&mut self, agent_name: String) -> Result<(), HolochainError>;
     fn dpki_init(&mut self, params: String) -> Result<(), HolochainError>;
     fn dpki_is_initialized(&mut self) -> Result<bool, HolochainError>;
+    fn dpki_is_key_registered(&mut self, key: String) -> Result<bool, HolochainError>;
 }
 
 /// create a capability request for a given dpki call
@@ -85,4 +87,23 @@ impl DpkiInstance for Holochain {
         }
         result
     }
+
+    // wrapper for the dpki is_key_registered trait function, DPKI drops the registration of
+    // revoked keys
+    fn dpki_is_key_registered(&mut self, key: String) -> Result<bool, HolochainError> {
+        let params = json!({ "key": key }).to_string();
+        let cap_request =
+            dpki_cap_request(self.context()?, DPKI_TRAIT_FN_IS_KEY_REGISTERED, &params)?;
+        let result = Holochain::call_zome_function(
+            self.context()?,
+            DPKI_ZOME_NAME,
+            cap_request,
+            DPKI_TRAIT_FN_IS_KEY_REGISTERED,
+            &params,
+        )?;
+        result
+            .try_into()
+            .map_err(HolochainError::from)
+            .and_then(|registered: Result<bool, HolochainError>| registered)
+    }
 }
@@ -0,0 +1,107 @@
+//! Per attribute class indexing of the persistent EAV storages.
+//!
+//! Indexing every EAVI by entity and by value makes every meta query fast but also makes every
+//! write pay for two index updates. Which attributes are worth that depends on the hApp: a chat
+//! app queries its link tags all the time, an archive mostly writes CRUD status it rarely asks
+//! for. [EavIndexing] lets the storage configuration pick an [EavIndexStrategy] per attribute
+//! class. The SQLite and RocksDB EAV storages then keep each EAVI only in the indices its class
+//! asks for. EAVIs of less indexed classes still get found, just by scanning.
+use holochain_core_types::eav::Attribute;
+
+/// How the EAVIs of an attribute class get indexed
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EavIndexStrategy {
+    /// Indexed by entity and by value. Fastest queries, most expensive writes.
+    Full,
+    /// Indexed by entity only. Queries by value scan the EAVIs of this class.
+    Entity,
+    /// Not indexed. Every query scans the EAVIs of this class.
+    None,
+}
+
+impl Default for EavIndexStrategy {
+    fn default() -> Self {
+        EavIndexStrategy::Full
+    }
+}
+
+/// Indexing strategy per attribute class. Classes that aren't set use `default`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EavIndexing {
+    #[serde(default)]
+    pub default: EavIndexStrategy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crud_status: Option<EavIndexStrategy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crud_link: Option<EavIndexStrategy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_header: Option<EavIndexStrategy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<EavIndexStrategy>,
+    /// Link types and tags of added links
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_tag: Option<EavIndexStrategy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed_link: Option<EavIndexStrategy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_entry: Option<EavIndexStrategy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<EavIndexStrategy>,
+}
+
+impl EavIndexing {
+    /// The strategy for EAVIs with the given attribute
+    pub fn strategy(&self, attribute: &Attribute) -> EavIndexStrategy {
+        let class = match attribute {
+            Attribute::CrudStatus => self.crud_status,
+            Attribute::CrudLink => self.crud_link,
+            Attribute::EntryHeader => self.entry_header,
+            Attribute::Link => self.link,
+            Attribute::LinkTag(_, _) => self.link_tag,
+            Attribute::RemovedLink(_, _, _) => self.removed_link,
+            Attribute::PendingEntry => self.pending_entry,
+            Attribute::Target => self.target,
+        };
+        class.unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn classes_fall_back_to_default() {
+        let indexing: EavIndexing = toml::from_str(
+            r#"
+            default = "entity"
+            crud_status = "none"
+            link_tag = "full"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            indexing.strategy(&Attribute::CrudStatus),
+            EavIndexStrategy::None
+        );
+        assert_eq!(
+            indexing.strategy(&Attribute::LinkTag("friend".into(), "".into())),
+            EavIndexStrategy::Full
+        );
+        assert_eq!(
+            indexing.strategy(&Attribute::EntryHeader),
+            EavIndexStrategy::Entity
+        );
+        assert_eq!(
+            EavIndexing::default().strategy(&Attribute::CrudStatus),
+            EavIndexStrategy::Full
+        );
+    }
+
+    #[test]
+    fn unknown_classes_are_rejected() {
+        assert!(toml::from_str::<EavIndexing>("crud_stats = \"none\"").is_err());
+    }
+}
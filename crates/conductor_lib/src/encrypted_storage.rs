@@ -0,0 +1,598 @@
+//! Encryption at rest for instance storages.
+//!
+//! [EncryptedStorage] and [EavEncryptedStorage] wrap any CAS and EAV storage and encrypt
+//! everything that could expose user data before it gets written, with a [StorageKey] derived
+//! from the conductor passphrase:
+//! * CAS content gets stored as `{"encrypted":"<base64 of nonce and cipher>"}` under the
+//!   original address.
+//! * Link types and tags in EAV attributes get replaced by `enc:<hex of nonce and cipher>`.
+//!   Their nonces are derived from the plain text, so equal link types and tags have equal
+//!   ciphers and EAVIs can still be compared, deduplicated and grouped by attribute.
+//!
+//! Addresses, i.e. entities and values in the EAV, stay as they are since they are hashes
+//! that storages and queries depend on.
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::{HcResult, HolochainError},
+};
+use holochain_dpki::{
+    password_encryption::{derive_secret_key, PwHashConfig, SALTBYTES},
+    utils::{generate_derived_seed_buf, SeedContext},
+};
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_locksmith::{Mutex, RwLock};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::{EavFilter, EntityAttributeValueStorage, IndexFilter},
+    error::{PersistenceError, PersistenceResult},
+    reporting::ReportStorage,
+};
+use lib3h_sodium::{aead, hash::sha256, secbuf::SecBuf};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    path::Path,
+    sync::Arc,
+};
+use uuid::Uuid;
+
+const ENCRYPTED_PREFIX: &str = "{\"encrypted\":\"";
+const ENCRYPTED_SUFFIX: &str = "\"}";
+const ENCRYPTED_ATTRIBUTE_PREFIX: &str = "enc:";
+const NONCE_KEY_CONTEXT: [u8; 8] = *b"STORNONC";
+const NONCE_KEY_SIZE: usize = 32;
+const SHA256_SIZE: usize = 32;
+const HMAC_BLOCK_SIZE: usize = 64;
+
+fn encryption_error<E: fmt::Display>(error: E) -> PersistenceError {
+    PersistenceError::ErrorGeneric(format!("Could not en-/decrypt storage data: {}", error))
+}
+
+/// Symmetric key that instance storages get encrypted with, plus the key that nonces of
+/// deterministic ciphers get derived with.
+#[derive(Clone)]
+pub struct StorageKey {
+    key: Arc<Mutex<SecBuf>>,
+    nonce_key: Arc<Mutex<SecBuf>>,
+}
+
+impl StorageKey {
+    /// Derives the key from the given passphrase and the salt stored in the file at
+    /// `salt_path`. A new random salt gets created and stored there if there is none yet,
+    /// so the same passphrase keeps resulting in the same key for the same storage.
+    pub fn derive(
+        mut passphrase: SecBuf,
+        salt_path: &Path,
+        config: Option<PwHashConfig>,
+    ) -> HcResult<StorageKey> {
+        let salt = if salt_path.exists() {
+            fs::read(salt_path)?
+        } else {
+            let mut salt = SecBuf::with_insecure(SALTBYTES);
+            salt.randomize();
+            let salt = salt.read_lock().to_vec();
+            fs::write(salt_path, &salt)?;
+            salt
+        };
+        if salt.len() != SALTBYTES {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Invalid storage encryption salt in {}",
+                salt_path.display()
+            )));
+        }
+        Self::derive_with_salt(&mut passphrase, &salt, config)
+    }
+
+    pub fn derive_with_salt(
+        passphrase: &mut SecBuf,
+        salt: &[u8],
+        config: Option<PwHashConfig>,
+    ) -> HcResult<StorageKey> {
+        let mut key = derive_secret_key(passphrase, salt, config)?;
+        let nonce_key = generate_derived_seed_buf(
+            &mut key,
+            &SeedContext::new(NONCE_KEY_CONTEXT),
+            1,
+            NONCE_KEY_SIZE,
+        )?;
+        Ok(StorageKey {
+            key: Arc::new(Mutex::new(key)),
+            nonce_key: Arc::new(Mutex::new(nonce_key)),
+        })
+    }
+
+    /// Encrypts the data with a random nonce, which gets prepended to the cipher.
+    fn encrypt(&self, data: &[u8]) -> PersistenceResult<Vec<u8>> {
+        let mut nonce = SecBuf::with_insecure(aead::NONCEBYTES);
+        nonce.randomize();
+        self.encrypt_with_nonce(data, nonce)
+    }
+
+    /// Encrypts the data with a nonce derived from it, so the same data always results in
+    /// the same cipher. Only reveals whether two ciphers have the same plain text.
+    fn encrypt_deterministic(&self, data: &[u8]) -> PersistenceResult<Vec<u8>> {
+        let hash = self.nonce_hash(data)?;
+        let mut nonce = SecBuf::with_insecure(aead::NONCEBYTES);
+        nonce
+            .from_array(&hash[..aead::NONCEBYTES])
+            .map_err(encryption_error)?;
+        self.encrypt_with_nonce(data, nonce)
+    }
+
+    /// HMAC-SHA256 of the data, keyed with the nonce key
+    fn nonce_hash(&self, data: &[u8]) -> PersistenceResult<Vec<u8>> {
+        let hash = |message: Vec<u8>| -> PersistenceResult<Vec<u8>> {
+            let mut input = SecBuf::with_insecure(message.len());
+            input.from_array(&message).map_err(encryption_error)?;
+            let mut output = SecBuf::with_insecure(SHA256_SIZE);
+            sha256(&mut input, &mut output).map_err(encryption_error)?;
+            let output = output.read_lock().to_vec();
+            Ok(output)
+        };
+        let mut nonce_key = self.nonce_key.lock().unwrap();
+        let nonce_key = nonce_key.read_lock();
+        let padded_key = |pad: u8| {
+            let mut padded = vec![pad; HMAC_BLOCK_SIZE];
+            for (byte, key_byte) in padded.iter_mut().zip(nonce_key.iter()) {
+                *byte ^= key_byte;
+            }
+            padded
+        };
+        let mut inner = padded_key(0x36);
+        inner.extend_from_slice(data);
+        let mut outer = padded_key(0x5c);
+        outer.extend(hash(inner)?);
+        hash(outer)
+    }
+
+    fn encrypt_with_nonce(&self, data: &[u8], mut nonce: SecBuf) -> PersistenceResult<Vec<u8>> {
+        let mut plain = SecBuf::with_insecure(data.len());
+        plain.from_array(data).map_err(encryption_error)?;
+        let mut cipher = SecBuf::with_insecure(data.len() + aead::ABYTES);
+        aead::enc(
+            &mut plain,
+            &mut *self.key.lock().unwrap(),
+            None,
+            &mut nonce,
+            &mut cipher,
+        )
+        .map_err(encryption_error)?;
+        let mut encrypted = nonce.read_lock().to_vec();
+        encrypted.extend_from_slice(&cipher.read_lock());
+        Ok(encrypted)
+    }
+
+    fn decrypt(&self, encrypted: &[u8]) -> PersistenceResult<Vec<u8>> {
+        if encrypted.len() < aead::NONCEBYTES + aead::ABYTES {
+            return Err(encryption_error("cipher too short"));
+        }
+        let (nonce_bytes, cipher_bytes) = encrypted.split_at(aead::NONCEBYTES);
+        let mut nonce = SecBuf::with_insecure(aead::NONCEBYTES);
+        nonce.from_array(nonce_bytes).map_err(encryption_error)?;
+        let mut cipher = SecBuf::with_insecure(cipher_bytes.len());
+        cipher.from_array(cipher_bytes).map_err(encryption_error)?;
+        let mut plain = SecBuf::with_insecure(cipher_bytes.len() - aead::ABYTES);
+        aead::dec(
+            &mut plain,
+            &mut *self.key.lock().unwrap(),
+            None,
+            &mut nonce,
+            &mut cipher,
+        )
+        .map_err(|_| encryption_error("wrong key or corrupted data"))?;
+        let plain = plain.read_lock().to_vec();
+        Ok(plain)
+    }
+
+    fn encrypt_string(&self, text: &str) -> PersistenceResult<String> {
+        Ok(format!(
+            "{}{}",
+            ENCRYPTED_ATTRIBUTE_PREFIX,
+            to_hex(&self.encrypt_deterministic(text.as_bytes())?)
+        ))
+    }
+
+    /// Strings without the prefix of encrypted ones got stored before the storage was
+    /// encrypted and are returned as they are.
+    fn decrypt_string(&self, stored: &str) -> PersistenceResult<String> {
+        if !stored.starts_with(ENCRYPTED_ATTRIBUTE_PREFIX) {
+            return Ok(stored.to_string());
+        }
+        let hex = &stored[ENCRYPTED_ATTRIBUTE_PREFIX.len()..];
+        let plain = self.decrypt(&from_hex(hex)?)?;
+        String::from_utf8(plain).map_err(encryption_error)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> PersistenceResult<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return Err(encryption_error("odd length hex string"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(encryption_error))
+        .collect()
+}
+
+/// Content as it gets written to the wrapped storage, under the address of the original.
+struct StoredContent {
+    address: Address,
+    content: Content,
+}
+
+impl AddressableContent for StoredContent {
+    fn address(&self) -> Address {
+        self.address.clone()
+    }
+
+    fn content(&self) -> Content {
+        self.content.clone()
+    }
+
+    fn try_from_content(_content: &Content) -> JsonResult<Self> {
+        Err(JsonError::ErrorGeneric(
+            "Stored content can't be restored without its address".to_string(),
+        ))
+    }
+}
+
+/// Content addressable storage that encrypts all content written to the wrapped storage.
+#[derive(Clone)]
+pub struct EncryptedStorage {
+    inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+    key: StorageKey,
+}
+
+impl fmt::Debug for EncryptedStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncryptedStorage").finish()
+    }
+}
+
+impl EncryptedStorage {
+    pub fn new(inner: Arc<RwLock<dyn ContentAddressableStorage>>, key: StorageKey) -> Self {
+        EncryptedStorage { inner, key }
+    }
+}
+
+impl ContentAddressableStorage for EncryptedStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        let encrypted = self
+            .key
+            .encrypt(String::from(content.content()).as_bytes())?;
+        let stored = StoredContent {
+            address: content.address(),
+            content: JsonString::from_json(&format!(
+                "{}{}{}",
+                ENCRYPTED_PREFIX,
+                base64::encode(&encrypted),
+                ENCRYPTED_SUFFIX
+            )),
+        };
+        self.inner.write().unwrap().add(&stored)
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        self.inner.read().unwrap().contains(address)
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        let stored = match self.inner.read().unwrap().fetch(address)? {
+            Some(stored) => String::from(stored),
+            None => return Ok(None),
+        };
+        if !(stored.starts_with(ENCRYPTED_PREFIX) && stored.ends_with(ENCRYPTED_SUFFIX)) {
+            return Err(encryption_error(format!(
+                "unencrypted content at {} in encrypted storage",
+                address
+            )));
+        }
+        let encoded = &stored[ENCRYPTED_PREFIX.len()..stored.len() - ENCRYPTED_SUFFIX.len()];
+        let encrypted = base64::decode(encoded).map_err(encryption_error)?;
+        let plain = String::from_utf8(self.key.decrypt(&encrypted)?).map_err(encryption_error)?;
+        Ok(Some(JsonString::from_json(&plain)))
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.inner.read().unwrap().get_id()
+    }
+}
+
+impl ReportStorage for EncryptedStorage {}
+
+/// EAV storage that encrypts link types and tags in the attributes written to the wrapped
+/// storage.
+/// Queries filter by entity, value and attribute in the wrapped storage, with the attributes
+/// decrypted once per distinct cipher, and then run on the decrypted EAVIs that match.
+/// Link types and tags that got stored before the storage was encrypted stay readable.
+#[derive(Clone)]
+pub struct EavEncryptedStorage {
+    inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    key: StorageKey,
+}
+
+impl fmt::Debug for EavEncryptedStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EavEncryptedStorage").finish()
+    }
+}
+
+impl EavEncryptedStorage {
+    pub fn new(
+        inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+        key: StorageKey,
+    ) -> Self {
+        EavEncryptedStorage { inner, key }
+    }
+
+    fn encrypt_attribute(&self, attribute: &Attribute) -> PersistenceResult<Attribute> {
+        Ok(match attribute {
+            Attribute::LinkTag(link_type, tag) => Attribute::LinkTag(
+                self.key.encrypt_string(link_type)?,
+                self.key.encrypt_string(tag)?,
+            ),
+            Attribute::RemovedLink(address, link_type, tag) => Attribute::RemovedLink(
+                address.clone(),
+                self.key.encrypt_string(link_type)?,
+                self.key.encrypt_string(tag)?,
+            ),
+            attribute => attribute.clone(),
+        })
+    }
+
+    fn decrypt_attribute(&self, attribute: &Attribute) -> PersistenceResult<Attribute> {
+        Ok(match attribute {
+            Attribute::LinkTag(link_type, tag) => Attribute::LinkTag(
+                self.key.decrypt_string(link_type)?,
+                self.key.decrypt_string(tag)?,
+            ),
+            Attribute::RemovedLink(address, link_type, tag) => Attribute::RemovedLink(
+                address.clone(),
+                self.key.decrypt_string(link_type)?,
+                self.key.decrypt_string(tag)?,
+            ),
+            attribute => attribute.clone(),
+        })
+    }
+
+    fn with_attribute(
+        eavi: &EntityAttributeValueIndex,
+        attribute: &Attribute,
+    ) -> PersistenceResult<EntityAttributeValueIndex> {
+        EntityAttributeValueIndex::new_with_index(
+            &eavi.entity(),
+            attribute,
+            &eavi.value(),
+            eavi.index(),
+        )
+    }
+}
+
+impl EntityAttributeValueStorage<Attribute> for EavEncryptedStorage {
+    fn add_eavi(
+        &mut self,
+        eavi: &EntityAttributeValueIndex,
+    ) -> PersistenceResult<Option<EntityAttributeValueIndex>> {
+        let encrypted = Self::with_attribute(eavi, &self.encrypt_attribute(&eavi.attribute())?)?;
+        match self.inner.write().unwrap().add_eavi(&encrypted)? {
+            // The storage might have moved it to another index
+            Some(stored) => Ok(Some(Self::with_attribute(&stored, &eavi.attribute())?)),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_eavi(
+        &self,
+        query: &EaviQuery,
+    ) -> PersistenceResult<BTreeSet<EntityAttributeValueIndex>> {
+        // Equal attributes have equal ciphers, so each one only needs decrypting once
+        let decrypted_attributes = RefCell::new(BTreeMap::new());
+        let decrypt = |attribute: &Attribute| -> PersistenceResult<Attribute> {
+            if let Some(decrypted) = decrypted_attributes.borrow().get(attribute) {
+                return Ok(decrypted.clone());
+            }
+            let decrypted = self.decrypt_attribute(attribute)?;
+            decrypted_attributes
+                .borrow_mut()
+                .insert(attribute.clone(), decrypted.clone());
+            Ok(decrypted)
+        };
+        // Index filters compare EAVIs with each other, so the query itself runs on the
+        // decrypted candidates.
+        let candidates = self.inner.read().unwrap().fetch_eavi(&EaviQuery::new(
+            EavFilter::predicate(|entity| query.entity().check(entity)),
+            EavFilter::predicate(|attribute: Attribute| {
+                let matches_tombstone = query.tombstone().as_ref().map_or(false, |tombstone| {
+                    decrypt(&attribute).map_or(false, |decrypted| tombstone.check(decrypted))
+                });
+                matches_tombstone
+                    || decrypt(&attribute)
+                        .map_or(true, |decrypted| query.attribute().check(decrypted))
+            }),
+            EavFilter::predicate(|value| query.value().check(value)),
+            IndexFilter::Range(None, None),
+            None,
+        ))?;
+        let decrypted = candidates
+            .iter()
+            .map(|eavi| Self::with_attribute(eavi, &decrypt(&eavi.attribute())?))
+            .collect::<PersistenceResult<Vec<_>>>()?;
+        Ok(query.run(decrypted.into_iter()))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_json_api::json::RawString;
+    use holochain_persistence_api::cas::content::ExampleAddressableContent;
+    use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+    use lib3h_sodium::pwhash;
+    use tempfile::tempdir;
+
+    const TEST_CONFIG: Option<PwHashConfig> = Some(PwHashConfig(
+        pwhash::OPSLIMIT_INTERACTIVE,
+        pwhash::MEMLIMIT_INTERACTIVE,
+        pwhash::ALG_ARGON2ID13,
+    ));
+
+    fn passphrase(text: &str) -> SecBuf {
+        let mut passphrase = SecBuf::with_insecure(text.len());
+        passphrase.from_array(text.as_bytes()).unwrap();
+        passphrase
+    }
+
+    pub fn test_key() -> StorageKey {
+        StorageKey::derive_with_salt(&mut passphrase("secret"), &[0; SALTBYTES], TEST_CONFIG)
+            .unwrap()
+    }
+
+    #[test]
+    fn content_gets_encrypted_at_rest() {
+        let inner: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        let mut storage = EncryptedStorage::new(inner.clone(), test_key());
+        let content = ExampleAddressableContent::try_from_content(&JsonString::from(
+            RawString::from("very private"),
+        ))
+        .unwrap();
+        storage.add(&content).unwrap();
+
+        let stored = String::from(
+            inner
+                .read()
+                .unwrap()
+                .fetch(&content.address())
+                .unwrap()
+                .unwrap(),
+        );
+        assert!(stored.starts_with(ENCRYPTED_PREFIX));
+        assert!(!stored.contains("very private"));
+        assert_eq!(
+            storage.fetch(&content.address()).unwrap(),
+            Some(content.content())
+        );
+
+        let other_key = StorageKey::derive_with_salt(
+            &mut passphrase("other secret"),
+            &[0; SALTBYTES],
+            TEST_CONFIG,
+        )
+        .unwrap();
+        assert!(EncryptedStorage::new(inner, other_key)
+            .fetch(&content.address())
+            .is_err());
+    }
+
+    #[test]
+    fn link_tags_get_encrypted_at_rest() {
+        let inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+            Arc::new(RwLock::new(EavMemoryStorage::new()));
+        let mut storage = EavEncryptedStorage::new(inner.clone(), test_key());
+        let base = Address::from("base");
+        let target = Address::from("target");
+        let attribute = Attribute::LinkTag("friend".to_string(), "private tag".to_string());
+        let eavi = EntityAttributeValueIndex::new(&base, &attribute, &target).unwrap();
+        storage.add_eavi(&eavi).unwrap();
+
+        let stored = inner
+            .read()
+            .unwrap()
+            .fetch_eavi(&EaviQuery::default())
+            .unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_ne!(stored.iter().next().unwrap().attribute(), attribute);
+
+        let query = EaviQuery::new(
+            Some(base).into(),
+            Some(attribute.clone()).into(),
+            None.into(),
+            IndexFilter::LatestByAttribute,
+            None,
+        );
+        let fetched = storage.fetch_eavi(&query).unwrap();
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched.iter().next().unwrap().attribute(), attribute);
+    }
+
+    #[test]
+    fn equal_link_tags_have_equal_ciphers() {
+        let inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+            Arc::new(RwLock::new(EavMemoryStorage::new()));
+        let mut storage = EavEncryptedStorage::new(inner.clone(), test_key());
+        let attribute = Attribute::LinkTag("friend".to_string(), "private tag".to_string());
+        for base in &["alice", "bob"] {
+            let eavi =
+                EntityAttributeValueIndex::new(&Address::from(*base), &attribute, &"target".into())
+                    .unwrap();
+            storage.add_eavi(&eavi).unwrap();
+        }
+        let other = Attribute::LinkTag("friend".to_string(), "other tag".to_string());
+        let eavi =
+            EntityAttributeValueIndex::new(&"carol".into(), &other, &"target".into()).unwrap();
+        storage.add_eavi(&eavi).unwrap();
+
+        let stored: BTreeSet<_> = inner
+            .read()
+            .unwrap()
+            .fetch_eavi(&EaviQuery::default())
+            .unwrap()
+            .iter()
+            .map(|eavi| eavi.attribute())
+            .collect();
+        assert_eq!(stored.len(), 2);
+        assert!(!stored.contains(&attribute));
+
+        let query = EaviQuery::new(
+            None.into(),
+            Some(attribute.clone()).into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        assert_eq!(storage.fetch_eavi(&query).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn link_tags_stored_before_encryption_stay_readable() {
+        let inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+            Arc::new(RwLock::new(EavMemoryStorage::new()));
+        let attribute = Attribute::LinkTag("friend".to_string(), "old tag".to_string());
+        let eavi =
+            EntityAttributeValueIndex::new(&"base".into(), &attribute, &"target".into()).unwrap();
+        inner.write().unwrap().add_eavi(&eavi).unwrap();
+
+        let storage = EavEncryptedStorage::new(inner, test_key());
+        let query = EaviQuery::new(
+            None.into(),
+            Some(attribute.clone()).into(),
+            None.into(),
+            IndexFilter::LatestByAttribute,
+            None,
+        );
+        let fetched = storage.fetch_eavi(&query).unwrap();
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched.iter().next().unwrap().attribute(), attribute);
+    }
+
+    #[test]
+    fn key_derivation_reuses_stored_salt() {
+        let temp = tempdir().unwrap();
+        let salt_path = temp.path().join("storage_salt");
+        let key = StorageKey::derive(passphrase("secret"), &salt_path, TEST_CONFIG).unwrap();
+        let same_key = StorageKey::derive(passphrase("secret"), &salt_path, TEST_CONFIG).unwrap();
+        let encrypted = key.encrypt(b"data").unwrap();
+        assert_eq!(same_key.decrypt(&encrypted).unwrap(), b"data".to_vec());
+    }
+}
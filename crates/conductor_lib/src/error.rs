@@ -9,6 +9,8 @@ pub enum HolochainInstanceError {
     InternalFailure(HolochainError),
     InstanceNotActiveYet,
     InstanceAlreadyActive,
+    InstanceAlreadyPaused,
+    InstanceNotPaused,
     InstanceNotInitialized,
     NoSuchInstance,
     RequiredBridgeMissing(String),
@@ -22,6 +24,8 @@ impl Error for HolochainInstanceError {
             HolochainInstanceError::InternalFailure(ref err)  => Some(err),
             HolochainInstanceError::InstanceNotActiveYet => None,
             HolochainInstanceError::InstanceAlreadyActive => None,
+            HolochainInstanceError::InstanceAlreadyPaused => None,
+            HolochainInstanceError::InstanceNotPaused => None,
             HolochainInstanceError::InstanceNotInitialized => None,
             HolochainInstanceError::NoSuchInstance => None,
             HolochainInstanceError::RequiredBridgeMissing(_) => None,
@@ -40,6 +44,12 @@ impl fmt::Display for HolochainInstanceError {
             HolochainInstanceError::InstanceAlreadyActive => {
                 write!(f, "{}: Holochain instance is already active.", prefix)
             }
+            HolochainInstanceError::InstanceAlreadyPaused => {
+                write!(f, "{}: Holochain instance is already paused.", prefix)
+            }
+            HolochainInstanceError::InstanceNotPaused => {
+                write!(f, "{}: Holochain instance is not paused.", prefix)
+            }
             HolochainInstanceError::InstanceNotInitialized => {
                 write!(f, "{}: Holochain instance is not initialized.", prefix)
             }
@@ -95,6 +105,14 @@ pub mod tests {
                 HolochainInstanceError::InstanceAlreadyActive,
                 "Holochain instance is already active.",
             ),
+            (
+                HolochainInstanceError::InstanceAlreadyPaused,
+                "Holochain instance is already paused.",
+            ),
+            (
+                HolochainInstanceError::InstanceNotPaused,
+                "Holochain instance is not paused.",
+            ),
             (
                 HolochainInstanceError::InternalFailure(HolochainError::DnaMissing),
                 "DNA is missing",
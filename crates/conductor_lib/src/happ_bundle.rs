@@ -125,6 +125,20 @@ impl HappBundle {
                 dna: happ_instance.id.clone(),
                 agent: agent_config.id.clone(),
                 storage: storage.clone(),
+                membrane_proof: None,
+                rate_limits: None,
+                compression: None,
+                encrypt_storage: false,
+                hot_tier: None,
+                cold_storage: None,
+                resource_limits: None,
+                restart_policy: None,
+                logger: None,
+                slow_zome_calls: None,
+                record_actions: None,
+                validation_coverage: None,
+                pending_validation: None,
+                validation_package_cache_size: None,
             })
             .collect::<Vec<_>>();
 
@@ -146,6 +160,11 @@ impl HappBundle {
                 driver: InterfaceDriver::Websocket { port },
                 admin: false,
                 choose_free_port: Some(false),
+                require_signed_calls: false,
+                filter_signals: false,
+                tenant: None,
+                auth: None,
+                tls: None,
                 instances: ui
                     .instance_references
                     .iter()
@@ -170,6 +189,9 @@ impl HappBundle {
                 bundle: ui.id(),
                 port,
                 dna_interface: Some(ui.id()),
+                host: None,
+                path_prefix: None,
+                instance: None,
                 reroute_to_root: false,
                 bind_address: String::from("127.0.0.1"),
             });
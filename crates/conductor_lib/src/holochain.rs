@@ -107,14 +107,19 @@ use holochain_core_types::{
 use holochain_json_api::json::JsonString;
 
 use holochain_core::{
+    instance_activity::TimedWorkflow,
     state::StateWrapper,
     state_dump::{address_to_content_and_type, DumpOptions, StateDump},
 };
 use holochain_persistence_api::cas::content::Address;
+use holochain_tracing as ht;
 use jsonrpc_core::IoHandler;
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use holochain_metrics::with_latency_publishing;
+use holochain_metrics::{prometheus, with_latency_publishing};
 
 /// contains a Holochain application instance
 pub struct Holochain {
@@ -189,6 +194,23 @@ impl Holochain {
         })
     }
 
+    /// Loads the instance from storage like load() does, but runs it with the given DNA.
+    /// If the loaded chain was created with a different DNA version, it gets migrated:
+    /// a ChainMigrate entry is committed and the zomes' migrate_chain callbacks are called.
+    pub fn load_with_dna(context: Arc<Context>, dna: Dna) -> Result<Self, HolochainError> {
+        let persister = SimplePersister::new(context.dht_storage.clone());
+        let loaded_state = persister.load(context.clone())?.ok_or_else(|| {
+            HolochainError::ErrorGeneric("State could not be loaded due to NoneError".to_string())
+        })?;
+        let mut instance = Instance::from_state(loaded_state, context.clone());
+        let new_context = instance.initialize_with_dna_update(dna, context)?;
+        Ok(Holochain {
+            instance: Some(instance),
+            context: Some(new_context),
+            active: false,
+        })
+    }
+
     pub fn check_instance(&self) -> Result<(), HolochainInstanceError> {
         if self.instance.is_none() || self.context.is_none() {
             Err(HolochainInstanceError::InstanceNotInitialized)
@@ -228,8 +250,11 @@ impl Holochain {
         self.check_active()?;
 
         let context = self.context.as_ref().unwrap();
-        if let Err(err) = context.block_on(self.instance.as_ref().unwrap().shutdown_network()) {
-            log_error!(context, "Error shutting down network: {:?}", err);
+        // A dead action loop can't process the network shutdown
+        if context.instance_still_alive() {
+            if let Err(err) = context.block_on(self.instance.as_ref().unwrap().shutdown_network()) {
+                log_error!(context, "Error shutting down network: {:?}", err);
+            }
         }
         self.instance.as_ref().unwrap().stop_action_loop();
         self.active = false;
@@ -247,6 +272,43 @@ impl Holochain {
         Ok(context.block_on(call_zome_function(zome_call, context.clone()))?)
     }
 
+    /// Pause the instance: zome calls get refused and the holding queue is left alone until
+    /// resume() is called. Waits for calls and holding workflows that are running to finish,
+    /// and resumes the instance again if that takes longer than the given timeout.
+    /// The instance stays connected to the network.
+    pub fn pause(&self, timeout: Duration) -> Result<(), HolochainInstanceError> {
+        self.check_instance()?;
+        self.check_active()?;
+        let context = self.context()?;
+        if context.instance_pause.is_paused() {
+            return Err(HolochainInstanceError::InstanceAlreadyPaused);
+        }
+        context.instance_pause.pause();
+        if let Err(error) = context.instance_pause.wait_until_idle(timeout) {
+            context.instance_pause.resume();
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    /// Resume an instance paused with pause()
+    pub fn resume(&self) -> Result<(), HolochainInstanceError> {
+        let context = self.context()?;
+        if !context.instance_pause.is_paused() {
+            return Err(HolochainInstanceError::InstanceNotPaused);
+        }
+        context.instance_pause.resume();
+        Ok(())
+    }
+
+    /// checks to see if an instance is paused
+    pub fn paused(&self) -> bool {
+        self.context
+            .as_ref()
+            .map(|context| context.instance_pause.is_paused())
+            .unwrap_or(false)
+    }
+
     /// call a function in a zome
     pub fn call_zome_function(
         context: Arc<Context>,
@@ -255,8 +317,19 @@ impl Holochain {
         fn_name: &str,
         params: &str,
     ) -> HolochainResult<JsonString> {
+        // The root of the spans of everything the call leads to
+        let span = context
+            .tracer
+            .span(format!("zome call {}/{}", zome, fn_name))
+            .tag(ht::Tag::new("zome", zome.to_string()))
+            .tag(ht::Tag::new("function", fn_name.to_string()))
+            .start()
+            .into();
+        let _spanguard = ht::push_span(span);
+        let _timer = context.time_workflow(TimedWorkflow::ZomeCall);
+        let started = Instant::now();
         let metric_name = format!("call_zome_function.{}.{}", zome, fn_name);
-        with_latency_publishing!(
+        let result = with_latency_publishing!(
             metric_name,
             context.metric_publisher,
             Self::call_inner,
@@ -265,7 +338,28 @@ impl Holochain {
             cap,
             fn_name,
             params
-        )
+        );
+        context.activity.record_zome_call(result.is_ok());
+        let instance = context.get_instance_name();
+        let labels = [
+            ("instance", instance.as_str()),
+            ("zome", zome),
+            ("function", fn_name),
+        ];
+        prometheus::observe(
+            "holochain_zome_call_duration_seconds",
+            "Time zome function calls took",
+            &labels,
+            prometheus::seconds(started.elapsed()),
+        );
+        if result.is_err() {
+            prometheus::increment_counter(
+                "holochain_zome_call_errors_total",
+                "Zome function calls that failed",
+                &labels,
+            );
+        }
+        result
     }
 
     /// checks to see if an instance is active
@@ -331,14 +425,23 @@ mod tests {
     use holochain_core_types::dna::capabilities::CapabilityRequest;
     use holochain_json_api::json::RawString;
     use holochain_locksmith::Mutex;
-    use holochain_persistence_api::cas::content::{Address, AddressableContent};
+    use holochain_persistence_api::cas::{
+        content::{Address, AddressableContent},
+        storage::ContentAddressableStorage,
+    };
+    use holochain_persistence_mem::cas::memory::MemoryStorage;
     use holochain_wasm_utils::wasm_target_dir;
-    use std::{path::PathBuf, sync::Arc};
+    use std::{
+        path::PathBuf,
+        sync::{Arc, RwLock},
+    };
     use tempfile;
     use test_utils::{
-        create_arbitrary_test_dna, create_test_defs_with_fn_name, create_test_dna_with_defs,
-        create_test_dna_with_wat, create_wasm_from_file, expect_action, hc_setup_and_call_zome_fn,
+        commit_admin_grant, create_arbitrary_test_dna, create_test_defs_with_fn_name,
+        create_test_dna_with_defs, create_test_dna_with_wat, create_wasm_from_file, expect_action,
+        hc_setup_and_call_zome_fn,
         mock_signing::{mock_conductor_api, registered_test_agent},
+        with_admin_grantor,
     };
 
     fn test_context(agent_name: &str) -> (Arc<Context>, Arc<Mutex<TestLogger>>, SignalReceiver) {
@@ -414,6 +517,48 @@ mod tests {
         // assert!(format!("{:?}", *test_logger).contains("\"debug/conductor: TestApp instantiated\""));
     }
 
+    #[test]
+    fn instances_share_dna_storage() {
+        let dna = create_arbitrary_test_dna();
+        let dna_storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+
+        let contexts: Vec<Arc<Context>> = ["alice", "bob"]
+            .iter()
+            .map(|name| {
+                let agent = registered_test_agent(name);
+                Arc::new(
+                    ContextBuilder::new()
+                        .with_agent(agent.clone())
+                        .with_conductor_api(mock_conductor_api(agent))
+                        .with_memory_storage()
+                        .with_dna_storage(dna_storage.clone())
+                        .spawn(),
+                )
+            })
+            .collect();
+
+        for context in contexts.iter() {
+            let hc = Holochain::new(dna.clone(), context.clone()).unwrap();
+            let instance = hc.instance.as_ref().unwrap();
+            assert_eq!(instance.state().nucleus().dna(), Some(dna.clone()));
+            assert!(context
+                .chain_storage
+                .read()
+                .unwrap()
+                .fetch(&dna.address())
+                .unwrap()
+                .is_none());
+        }
+
+        assert!(dna_storage
+            .read()
+            .unwrap()
+            .fetch(&dna.address())
+            .unwrap()
+            .is_some());
+    }
+
     #[test]
     fn can_persistant_and_load() {
         let temp = tempdir().unwrap();
@@ -473,6 +618,50 @@ mod tests {
         assert_eq!(network_state.dna_address.is_some(), true);
     }
 
+    #[test]
+    fn can_load_and_migrate_to_new_dna() {
+        let temp = tempdir().unwrap();
+        let temp_filestorage_dir = temp.path().to_str().unwrap();
+        let agent = registered_test_agent("migrator");
+        let (signal_tx, _signal_rx) = signal_channel();
+        let mut dna = with_admin_grantor(create_arbitrary_test_dna());
+        dna.name = "TestApp".to_string();
+
+        {
+            let context_new = Arc::new(
+                ContextBuilder::new()
+                    .with_agent(agent.clone())
+                    .with_signals(signal_tx.clone())
+                    .with_conductor_api(mock_conductor_api(agent.clone()))
+                    .with_file_storage(temp_filestorage_dir)
+                    .unwrap()
+                    .spawn(),
+            );
+            let hc = Holochain::new(dna.clone(), context_new).unwrap();
+            commit_admin_grant(hc.context.as_ref().unwrap());
+        }
+
+        let mut new_dna = dna.clone();
+        new_dna.uuid = "TestApp v2".to_string();
+        let context_load = Arc::new(
+            ContextBuilder::new()
+                .with_agent(agent.clone())
+                .with_signals(signal_tx)
+                .with_conductor_api(mock_conductor_api(agent.clone()))
+                .with_file_storage(temp_filestorage_dir)
+                .unwrap()
+                .spawn(),
+        );
+
+        let hc = Holochain::load_with_dna(context_load, new_dna.clone())
+            .expect("Error during Holochain::load_with_dna");
+        let instance = hc.instance.as_ref().unwrap();
+        let context = hc.context.as_ref().unwrap().clone();
+        assert_eq!(instance.state().nucleus().dna(), Some(new_dna.clone()));
+        let network_state = context.state().unwrap().network().clone();
+        assert_eq!(network_state.dna_address, Some(new_dna.address()));
+    }
+
     #[test]
     fn fails_instantiate_if_init_fails() {
         let dna = create_test_dna_with_wat(
@@ -605,6 +794,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_pause_and_resume() {
+        let wat = r#"
+(module
+ (memory 1)
+ (export "memory" (memory 0))
+ (export "public_test_fn" (func $func0))
+ (func $func0 (param $p0 i64) (result i64)
+       i64.const 16
+       )
+ (data (i32.const 0)
+       "{\"holo\":\"world\"}"
+       )
+ )
+"#;
+        let dna = create_test_dna_with_wat("test_zome", Some(wat));
+        let (context, _, _) = test_context("bob");
+        let mut hc = Holochain::new(dna.clone(), context.clone()).unwrap();
+        let timeout = Duration::from_secs(1);
+
+        // pause when not active returns error
+        assert_eq!(
+            HolochainInstanceError::InstanceNotActiveYet,
+            hc.pause(timeout).err().unwrap()
+        );
+        hc.start().expect("couldn't start");
+        assert_eq!(
+            HolochainInstanceError::InstanceNotPaused,
+            hc.resume().err().unwrap()
+        );
+
+        hc.pause(timeout).expect("couldn't pause");
+        assert!(hc.paused());
+        assert!(hc.active());
+        assert_eq!(
+            HolochainInstanceError::InstanceAlreadyPaused,
+            hc.pause(timeout).err().unwrap()
+        );
+        let call = || {
+            Holochain::call_zome_function(
+                hc.context().unwrap(),
+                "test_zome",
+                cap_call(context.clone(), "public_test_fn", ""),
+                "public_test_fn",
+                "",
+            )
+        };
+        assert!(call().is_err());
+
+        hc.resume().expect("couldn't resume");
+        assert!(!hc.paused());
+        let result = call();
+        assert!(result.is_ok(), "result = {:?}", result);
+    }
+
     #[test]
     fn can_get_state() {
         let dna = create_arbitrary_test_dna();
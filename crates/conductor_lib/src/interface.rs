@@ -1,13 +1,21 @@
-use crate::{conductor::broadcaster::Broadcaster, holo_signing_service::request_service};
+use crate::{
+    conductor::broadcaster::Broadcaster, dpki_instance::DpkiInstance,
+    holo_signing_service::request_service,
+};
 use base64;
 use crossbeam_channel::Receiver;
 use holochain_core::{
-    nucleus::actions::call_zome_function::make_cap_request_for_call, state_dump::DumpOptions,
+    event_log::{record_event, EVENT_ADMIN},
+    network::traffic::TrafficSummary,
+    nucleus::actions::call_zome_function::make_cap_request_for_call,
+    profiler,
+    state_dump::DumpOptions,
 };
 
 use crate::Holochain;
 use holochain_core_types::{
-    agent::AgentId, dna::capabilities::CapabilityRequest, signature::Provenance,
+    agent::AgentId, dna::capabilities::CapabilityRequest, entry::cap_entries::CapTokenDelegation,
+    signature::Provenance,
 };
 use holochain_dpki::key_bundle::KeyBundle;
 use holochain_json_api::json::JsonString;
@@ -16,18 +24,28 @@ use holochain_persistence_api::cas::content::Address;
 use lib3h_sodium::secbuf::SecBuf;
 
 use jsonrpc_core::{self, types::params::Params, IoHandler, Value};
-use std::{collections::HashMap, convert::TryFrom, path::PathBuf, sync::Arc, thread};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
 
 use crate::{
     conductor::{
-        ConductorAdmin, ConductorDebug, ConductorTestAdmin, ConductorUiAdmin, GetMetaOptions,
-        CONDUCTOR,
+        crash_report::redact_config, AppInstallRequest, ConductorAdmin, ConductorDebug,
+        ConductorStatistics, ConductorTenantAdmin, ConductorTestAdmin, ConductorUiAdmin,
+        GetMetaOptions, HealthReport, ProvisioningSpec, SignalHistory, CONDUCTOR,
     },
     config::{
-        AgentConfiguration, Bridge, DnaConfiguration, InstanceConfiguration,
-        InterfaceConfiguration, InterfaceDriver, UiBundleConfiguration, UiInterfaceConfiguration,
+        hash_token, AgentConfiguration, Bridge, DnaConfiguration, InstanceConfiguration,
+        InterfaceAuthConfiguration, InterfaceConfiguration, InterfaceDriver, StorageConfiguration,
+        UiBundleConfiguration, UiInterfaceConfiguration,
     },
     keystore::{KeyType, Keystore, Secret},
+    signing_backend::{KeyBundleBackend, SigningBackend},
 };
 use holochain_dpki::utils::SeedContext;
 use serde_json::{self, map::Map};
@@ -35,6 +53,12 @@ use serde_json::{self, map::Map};
 pub type InterfaceError = String;
 pub type InstanceMap = HashMap<String, Arc<RwLock<Holochain>>>;
 
+/// How long admin/instance/pause waits for running zome calls and validations by default
+const DEFAULT_PAUSE_TIMEOUT_SECONDS: u64 = 30;
+
+/// How long admin/instance/profile samples by default
+const DEFAULT_PROFILE_DURATION_SECONDS: u64 = 10;
+
 /// An identifier for an instance that is usable by UI in making calls to the conductor
 /// this type allows us to implement this identifier differently, i.e. as a DNA/agent ID pair, etc
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Hash, Eq)]
@@ -101,6 +125,8 @@ pub struct ConductorApiBuilder {
     instance_ids_map: PublicInstanceMap,
     instance_configs: HashMap<String, InstanceConfiguration>,
     io: Box<IoHandler>,
+    require_signed_calls: bool,
+    signal_history: Option<Arc<SignalHistory>>,
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
@@ -112,6 +138,8 @@ impl ConductorApiBuilder {
             instance_ids_map: HashMap::new(),
             instance_configs: HashMap::new(),
             io: Box::new(IoHandler::new()),
+            require_signed_calls: false,
+            signal_history: None,
         }
     }
 
@@ -119,6 +147,7 @@ impl ConductorApiBuilder {
     pub fn spawn(mut self) -> IoHandler {
         self.setup_info_api();
         self.setup_call_api();
+        self.setup_signal_api();
         *self.io
     }
 
@@ -128,6 +157,7 @@ impl ConductorApiBuilder {
         params: jsonrpc_core::Params,
         instances: InstanceMap,
         instance_ids_map: PublicInstanceMap,
+        require_signed_calls: bool,
     ) -> Result<JsonString, jsonrpc_core::Error> {
         // We need to place this one here in order to avoid compiler lifetime issue
         let default_call_args = json!({});
@@ -135,6 +165,11 @@ impl ConductorApiBuilder {
         let instance_id = Self::get_as_string("instance_id", &params_map)?;
         let zome_name = Self::get_as_string("zome", &params_map)?;
         let func_name = Self::get_as_string("function", &params_map)?;
+        if require_signed_calls && params_map.get("provenance").is_none() {
+            return Err(jsonrpc_core::Error::invalid_params(
+                "This interface only accepts signed zome calls: `provenance` param not provided",
+            ));
+        }
         let mut call_args = params_map.get("args");
 
         // For a consistent error behavior, we check if the passed value is 'null',
@@ -182,8 +217,17 @@ impl ConductorApiBuilder {
                 Ok(token) => Address::from(token),
             };
 
+            // Delegated sub-tokens come with the chain of delegations they were minted through.
+            let delegation: Vec<CapTokenDelegation> = match params_map.get("delegation") {
+                None => Vec::new(),
+                Some(json_delegation) => serde_json::from_value(json_delegation.to_owned())
+                    .map_err(|e| {
+                        jsonrpc_core::Error::invalid_params(format!("invalid delegation: {}", e))
+                    })?,
+            };
+
             let maybe_provenance = params_map.get("provenance");
-            match maybe_provenance {
+            let cap_request = match maybe_provenance {
                 None => make_cap_request_for_call(
                     context.clone(),
                     token,
@@ -200,7 +244,8 @@ impl ConductorApiBuilder {
                         })?;
                     CapabilityRequest::new(token, provenance.source(), provenance.signature())
                 }
-            }
+            };
+            cap_request.with_delegation(delegation)
         };
 
         Holochain::call_zome_function(context, &zome_name, cap_request, &func_name, &args_string)
@@ -211,17 +256,21 @@ impl ConductorApiBuilder {
     fn setup_call_api(&mut self) {
         let instances = self.instances.clone();
         let instance_ids_map = self.instance_ids_map.clone();
+        let require_signed_calls = self.require_signed_calls;
 
         self.io.add_method("call", move |params| {
             let instances = instances.clone();
             let instance_ids_map = instance_ids_map.clone();
-            let response = Self::method_call(params, instances, instance_ids_map)?;
+            let response =
+                Self::method_call(params, instances, instance_ids_map, require_signed_calls)?;
             Ok(Value::String(response.to_string()))
         });
     }
 
     /// Adds a "info/instances" method that returns a JSON object describing all registered
     /// instances we have a config for.
+    /// Also adds "info/zome_functions", which returns the function declarations of the zomes
+    /// of all instances, keyed by instance and zome name.
     fn setup_info_api(&mut self) {
         let instance_configs = self.instance_configs.clone();
 
@@ -242,6 +291,63 @@ impl ConductorApiBuilder {
         self.io.add_method("info/instances", move |_| {
             Ok(serde_json::Value::Array(configs.clone()))
         });
+
+        // Read from the instances on every call since their DNA can get updated
+        let instances = self.instances.clone();
+        let instance_ids_map = self.instance_ids_map.clone();
+        self.io.add_method("info/zome_functions", move |_| {
+            let mut functions = Map::new();
+            for (public_id, id) in instance_ids_map.iter() {
+                let dna = instances
+                    .get(id)
+                    .and_then(|instance| instance.read().unwrap().state().ok())
+                    .and_then(|state| state.nucleus().dna());
+                if let Some(dna) = dna {
+                    let zomes: Map<String, Value> = dna
+                        .zomes
+                        .iter()
+                        .map(|(name, zome)| (name.clone(), json!(zome.fn_declarations)))
+                        .collect();
+                    functions.insert(public_id.0.clone(), Value::Object(zomes));
+                }
+            }
+            Ok(Value::Object(functions))
+        });
+    }
+
+    /// Adds a "signals/replay" method if the interface's clients can catch up on signals.
+    /// It takes the `instance_id` and the sequence number to replay from as `since`, and
+    /// returns the instance's signals from that number on that are still in the history.
+    fn setup_signal_api(&mut self) {
+        let signal_history = match self.signal_history.clone() {
+            Some(signal_history) => signal_history,
+            None => return,
+        };
+        let instance_ids_map = self.instance_ids_map.clone();
+        self.io.add_method("signals/replay", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let instance_id = Self::get_as_string("instance_id", &params_map)?;
+            let since = Self::get_as_int("since", &params_map)?;
+            // Signals carry the instance's ID, aliases work too
+            let id = instance_ids_map
+                .get(&PublicInstanceIdentifier::from(instance_id.clone()))
+                .or_else(|| instance_ids_map.values().find(|id| **id == instance_id))
+                .ok_or_else(|| {
+                    jsonrpc_core::Error::invalid_params(format!(
+                        "instance identifier invalid: {}",
+                        instance_id
+                    ))
+                })?;
+            let replay = signal_history.replay(id, since.max(0) as u64);
+            Ok(serde_json::to_value(replay)
+                .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?)
+        });
+    }
+
+    /// Lets clients replay the recent signals of the interface's instances
+    pub fn with_signal_history(mut self, signal_history: Arc<SignalHistory>) -> Self {
+        self.signal_history = Some(signal_history);
+        self
     }
 
     /// Add a [InstanceConfig](struct.InstanceConfig.html) for a custom named instance
@@ -263,6 +369,13 @@ impl ConductorApiBuilder {
         self
     }
 
+    /// Makes the "call" method refuse zome calls that don't come with the caller's
+    /// provenance, instead of signing them with the agent's key.
+    pub fn with_signed_calls_required(mut self, require_signed_calls: bool) -> Self {
+        self.require_signed_calls = require_signed_calls;
+        self
+    }
+
     /// Add several instances with the names given in the InstanceMap
     pub fn with_instances(mut self, instances: InstanceMap) -> Self {
         for (instance_id, hc_lock) in instances {
@@ -285,6 +398,31 @@ impl ConductorApiBuilder {
         self
     }
 
+    /// Adds an admin method whose successful calls get recorded in the event log,
+    /// without parameters that might hold secrets
+    fn add_audited_method<F>(&mut self, name: &'static str, method: F)
+    where
+        F: Fn(Params) -> Result<Value, jsonrpc_core::Error> + Send + Sync + 'static,
+    {
+        self.io.add_method(name, move |params: Params| {
+            let mut logged_params = serde_json::to_value(&params).unwrap_or(Value::Null);
+            redact_config(&mut logged_params);
+            let result = method(params);
+            if result.is_ok() {
+                // The conductor has no clock of its own, only its instances do
+                if let Err(err) = record_event(
+                    SystemTime::now(),
+                    EVENT_ADMIN,
+                    None,
+                    json!({"method": name, "params": logged_params}),
+                ) {
+                    error!("Could not record admin event: {}", err);
+                }
+            }
+            result
+        });
+    }
+
     fn unwrap_params_map(params: Params) -> Result<Map<String, Value>, jsonrpc_core::Error> {
         match params {
             Params::Map(map) => Ok(map),
@@ -350,6 +488,31 @@ impl ConductorApiBuilder {
             })?)
     }
 
+    /// Reads the optional `options` param of state dumps, which defaults to dumping neither
+    /// EAVIs nor storage usage
+    fn get_dump_options(
+        params_map: &Map<String, Value>,
+    ) -> Result<DumpOptions, jsonrpc_core::Error> {
+        match params_map.get("options") {
+            None => Ok(DumpOptions::default()),
+            Some(options) => serde_json::from_value(options.clone()).map_err(|e| {
+                jsonrpc_core::Error::invalid_params(format!("invalid options: {}", e))
+            }),
+        }
+    }
+
+    /// Like get_dump_options, but also honours the older `storage_usage` flag of
+    /// `debug/state_dump`
+    fn get_debug_dump_options(
+        params_map: &Map<String, Value>,
+    ) -> Result<DumpOptions, jsonrpc_core::Error> {
+        let mut options = Self::get_dump_options(params_map)?;
+        if Ok(true) == Self::get_as_bool("storage_usage", params_map) {
+            options.include_storage_usage = true;
+        }
+        Ok(options)
+    }
+
     fn get_as_int<T: Into<String>>(
         key: T,
         params_map: &Map<String, Value>,
@@ -383,6 +546,23 @@ impl ConductorApiBuilder {
     ///     * `uuid`: [string] (optional) value to override "uuid" section of the DNA
     ///     * `copy`: [bool] (optional) copy DNA file to storage directory
     ///
+    ///  * `admin/dna/install_from_url`
+    ///     Downloads a DNA over HTTPS and installs it like `admin/dna/install_from_file` with
+    ///     `copy` set. The download must have the given hash and gets cached by it.
+    ///     Params:
+    ///     * `id`: [string] internal handle/name of the newly created DNA config
+    ///     * `url`: [string] HTTPS URL of the DNA file
+    ///     * `expected_hash`: [string] the hash of the DNA
+    ///     * `properties`, `uuid`: (optional) as in `admin/dna/install_from_file`
+    ///
+    ///  * `admin/dna/install_from_hash`
+    ///     Like `admin/dna/install_from_url`, but takes the DNA from the cache or downloads it
+    ///     from the configured `dna_registry`.
+    ///     Params:
+    ///     * `id`: [string] internal handle/name of the newly created DNA config
+    ///     * `hash`: [string] the hash of the DNA
+    ///     * `properties`, `uuid`: (optional) as in `admin/dna/install_from_file`
+    ///
     ///  * `admin/dna/uninstall`
     ///     Uninstalls a DNA from the conductor config. Recursively also removes (and stops)
     ///     all instances this DNA is used in.
@@ -400,6 +580,22 @@ impl ConductorApiBuilder {
     ///     * `id`: [string] Name for the new instance
     ///     * `agent_id`: [string] Agent to run this instance with
     ///     * `dna_id`: [string] DNA to run in this instance
+    ///     * `membrane_proof`: [string] (optional) Proof of membership committed with the
+    ///       agent entry at genesis
+    ///
+    ///  * `admin/instance/clone`
+    ///     Creates a new instance like an existing one, with the same kind of storage and
+    ///     settings, e.g. for a separate space per group or channel. If `properties` or a `uuid`
+    ///     are given, the source's DNA is installed again with those as DNA
+    ///     `<source DNA id>-<id>`, otherwise the clone needs a different agent.
+    ///     Does not start the clone nor add it to an interface.
+    ///     Returns the config of the DNA the clone runs, `{id, file, hash, uuid}`.
+    ///     Params:
+    ///     * `source_id`: [string] Which instance to clone?
+    ///     * `id`: [string] Name for the new instance
+    ///     * `agent_id`: [string] (optional) Agent to run the clone with, defaults to the source's
+    ///     * `properties`: [object] (optional) DNA properties to override
+    ///     * `uuid`: [string] (optional) uuid to give the clone's DNA
     ///
     ///  * `admin/instance/remove`
     ///     Removes an instance. Also remove its any uses of it in interfaces.
@@ -416,18 +612,146 @@ impl ConductorApiBuilder {
     ///     Params:
     ///     * `id`: [string] Which instance to stop?
     ///
+    ///  * `admin/instance/pause`
+    ///     Pauses a running instance without stopping it, e.g. to back up its storage: zome
+    ///     calls get refused and no held data gets validated until it is resumed. Waits for
+    ///     running calls and validations to finish and fails if they take too long.
+    ///     The instance stays connected to the network.
+    ///     Params:
+    ///     * `id`: [string] Which instance to pause?
+    ///     * `timeout`: [number] (optional) seconds to wait for running work, defaults to 30
+    ///
+    ///  * `admin/instance/resume`
+    ///     Resumes a paused instance.
+    ///     Params:
+    ///     * `id`: [string] Which instance to resume?
+    ///
     ///  * `admin/instance/get_meta`
     ///     Gets meta-data about a hash on an instance
     ///     Params:
     ///     * `id`: [string] Which instance to get data from?
     ///     * `hash`: [string] hash to get data about
     ///
+    ///  * `admin/instance/entry_holders`
+    ///     Returns the peers that reported holding an entry authored by the instance's agent
+    ///     as `{holders: [<agent address>], holder_count: <number>}`
+    ///     Params:
+    ///     * `id`: [string] Which instance to get data from?
+    ///     * `hash`: [string] address of the authored entry
+    ///
+    ///  * `admin/instance/chain_head`
+    ///     Returns the head of the instance's source chain as
+    ///     `{address: <header address>, length: <number>, sequence: <number>, timestamp: <iso8601>}`.
+    ///     `address`, `sequence` and `timestamp` are null if nothing was committed yet.
+    ///     Params:
+    ///     * `id`: [string] Which instance to get data from?
+    ///
+    ///  * `admin/instance/storage_usage`
+    ///     Returns the storage used by the instance as `{chain, cas, eav, caches}`, each of the
+    ///     form `{total: {count, bytes}, by_type: {<entry type or attribute>: {count, bytes}}}`
+    ///     Params:
+    ///     * `id`: [string] Which instance to get data from?
+    ///
+    ///  * `admin/instance/compact_storage`
+    ///     Removes content that is no longer referenced by the chain, the DHT or caches from the
+    ///     instance's storage and reclaims its disk space. Returns `{removed, kept}` content
+    ///     counts. Only supported by the sqlite and rocksdb storages, which also get compacted
+    ///     once an hour in the background.
+    ///     Params:
+    ///     * `id`: [string] Which instance's storage to compact?
+    ///
+    ///  * `admin/instance/scrub_storage`
+    ///     Re-hashes all entries and headers in the instance's storage and quarantines those that
+    ///     don't match their address. Quarantined entries held for the DHT get fetched again
+    ///     from the network. Returns `{checked, skipped, quarantined, held, restored}`.
+    ///     Supported by the same storages as `compact_storage` and also run once a day.
+    ///     Params:
+    ///     * `id`: [string] Which instance's storage to check?
+    ///
+    ///  * `admin/instance/archive_cold_content`
+    ///     Moves the instance's source chain content, except for the `keep_chain_headers`
+    ///     most recent headers, and content nothing references anymore to the object store
+    ///     configured as its `cold_storage`. Archived content is fetched from there on demand.
+    ///     Returns `{archived, kept}` content counts.
+    ///     Params:
+    ///     * `id`: [string] Which instance's content to archive?
+    ///
+    ///  * `admin/instance/export_archive`
+    ///     Writes the instance's whole state into a single, versioned archive file that
+    ///     `import_archive` can restore it from, e.g. on another machine.
+    ///     Params:
+    ///     * `id`: [string] Which instance to export?
+    ///     * `path`: [string] Path of the archive file to write
+    ///
+    ///  * `admin/instance/import_archive`
+    ///     Replaces the instance's state with the one in an archive written by
+    ///     `export_archive`. The instance has to be configured with the same agent and DNA
+    ///     and its storage should be empty. It gets restarted if it was running.
+    ///     Params:
+    ///     * `id`: [string] Which instance to restore?
+    ///     * `path`: [string] Path of the archive file to read
+    ///
+    ///  * `admin/instance/migrate_storage`
+    ///     Copies the instance's data into a different storage backend, verifies the copy by
+    ///     content hashes and counts and switches the instance over to it. The instance gets
+    ///     stopped meanwhile and restarted if it was running. The old storage is kept.
+    ///     Returns `{contents, verified, eavis}` counts.
+    ///     Params:
+    ///     * `id`: [string] Which instance to migrate?
+    ///     * `storage`: [object] The new storage config, as in the `storage` section of an
+    ///       instance's config, e.g. `{"type": "rocksdb", "path": "..."}`
+    ///
+    ///  * `admin/instance/update_dna`
+    ///     Switches the instance to another installed DNA, e.g. a new version of its app. The
+    ///     instance is paused, its chain migrated and held entries of types whose validation
+    ///     rules changed validated again, dropping those that are invalid now. Progress is
+    ///     sent to admin interfaces as `DnaUpdate` signals. Returns
+    ///     `{old_dna, new_dna, changed_entry_types, revalidation: {checked, invalid, pending}}`.
+    ///     Params:
+    ///     * `id`: [string] Which instance to update?
+    ///     * `dna_id`: [string] ID of the DNA to switch to
+    ///
+    ///  * `admin/instance/capability_audit_log`
+    ///     Returns the most recent calls made to the instance with capability tokens, oldest
+    ///     first, as `[{token, caller, zome, function, timestamp, outcome}]` where `outcome`
+    ///     is `"Granted"` or `"Denied"`.
+    ///     Params:
+    ///     * `id`: [string] Which instance to get data from?
+    ///     * `token`: [string] (optional) only return calls made with this token
+    ///
+    ///  * `admin/instance/rotate_capability_grant`
+    ///     Replaces a capability grant of the instance by one with the same scope and a new
+    ///     token, revoking the old token after a grace period. Returns the new token.
+    ///     Params:
+    ///     * `id`: [string] Which instance's grant to rotate?
+    ///     * `token`: [string] token of the grant to rotate
+    ///     * `grace_period`: [number] (optional) seconds the old token keeps working, defaults to 0
+    ///     * `notify_assignees`: [bool] (optional) send the new token to the grant's assignees
+    ///
     ///  * `admin/instance/list`
     ///     Returns an array of all instances that are configured.
     ///
     ///  * `admin/instance/running`
     ///     Returns an array of all instances that are running.
     ///
+    ///  * `admin/instance/dump_state`
+    ///     Returns a JSON object with all relevant fields of an instance's state, like
+    ///     `debug/state_dump` does on debug interfaces.
+    ///     Params:
+    ///     * `id`: [string] Which instance's state to dump?
+    ///     * `options`: [object] (optional) `{include_eavis, include_storage_usage}`, both
+    ///       default to false
+    ///
+    ///  * `admin/instance/profile`
+    ///     Samples what the instance's zome calls, HDK functions, WASM executions, holding
+    ///     workflows and actions are doing for a while and returns the profile as
+    ///     `{duration_ms, frequency_hz, samples, stacks: {<stack>: <count>}, folded}`, where
+    ///     `folded` is the profile in the folded stack format of flamegraph tools.
+    ///     Params:
+    ///     * `id`: [string] Which instance to profile?
+    ///     * `duration`: [number] (optional) seconds to sample for, defaults to 10, at most 300
+    ///     * `frequency`: [number] (optional) samples per second, defaults to 99, at most 1000
+    ///
     ///  * `admin/interface/add`
     ///     Adds a new DNA / zome / conductor interface (that provides access to zome functions
     ///     of selected instances and conductor functions, depending on the interfaces config).
@@ -436,8 +760,14 @@ impl ConductorApiBuilder {
     ///     Params:
     ///     * `id`: [string] ID for the new interface
     ///     * `admin`: [bool] Grant access to (these) admin functions?
-    ///     * `type`: [string] Either "websocket" or "http"
+    ///     * `type`: [string] One of "websocket", "http", "graphql" or "grpc"
     ///     * `port`:  [number] Port to bind the server to.
+    ///     * `require_signed_calls`: [bool] (optional) Only accept zome calls signed by
+    ///       their caller?
+    ///     * `filter_signals`: [bool] (optional) Only send websocket clients the signals they
+    ///       subscribed to with `signals/subscribe`?
+    ///     * `tokens`: [array of strings] (optional) Bearer tokens clients of a websocket or
+    ///       HTTP interface have to authenticate with. Only their hashes get stored.
     ///
     ///  * `admin/interface/remove`
     ///     Remove an interface from config. This automatically stops the interface as well.
@@ -471,6 +801,14 @@ impl ConductorApiBuilder {
     ///         given key file.
     ///     * `keystore_file`: Local path to the file that holds this agent configuration's private key
     ///
+    ///  * `admin/agent/add_service`
+    ///     Add a service agent whose keys get derived from a seed file without any passphrase
+    ///     prompt. Returns the agent's public address.
+    ///     Params:
+    ///     * `id`: Handle of this agent configuration as used in the config / other function calls
+    ///     * `name`: Nickname of this agent configuration
+    ///     * `seed_file`: Local path to a file holding the agent's seed (base64 or raw bytes)
+    ///
     ///  * `admin/agent/remove`
     ///     Remove an agent from the conductor config.
     ///     Params:
@@ -479,6 +817,28 @@ impl ConductorApiBuilder {
     ///  * `admin/agent/list`
     ///     Returns an array of all configured agents.
     ///
+    ///  * `admin/agent/block`
+    ///     Block a remote agent in all running instances: drops connections with them, refuses
+    ///     their direct messages, store and query traffic, rejects entries they signed in
+    ///     validation and hides data they authored from fetch and query results.
+    ///     Params:
+    ///     * `agent_address`: Address (public key) of the agent to block
+    ///
+    ///  * `admin/agent/unblock`
+    ///     Lift a block added with `admin/agent/block`.
+    ///     Params:
+    ///     * `agent_address`: Address (public key) of the agent to unblock
+    ///
+    ///  * `admin/keystore/lock`
+    ///     Lock the keystore: the passphrase and all decrypted agent keys get dropped from memory
+    ///     and signing fails with a KeystoreLocked error until the keystore gets unlocked.
+    ///
+    ///  * `admin/keystore/unlock`
+    ///     Unlock the keystore for the session duration set in the conductor config
+    ///     (`keystore_session_duration`).
+    ///     Params:
+    ///     * `passphrase`: Passphrase of the agent keystores
+    ///
     ///  * `admin/bridge/add`
     ///     Add a bridge between two instances to enable the caller to call the callee's
     ///     zome functions.
@@ -497,44 +857,181 @@ impl ConductorApiBuilder {
     ///  * `admin/bridge/list`
     ///     Returns an array of all bridges.
     ///
+    ///  * `admin/config/reload`
+    ///     Reads the conductor config file again and applies what changed without restarting
+    ///     the conductor: added instances and interfaces get started, removed ones stopped and
+    ///     changed ones restarted. Returns the IDs of all of those and the settings that only
+    ///     take effect after a restart as `{instances_added, instances_removed,
+    ///     instances_restarted, interfaces_added, interfaces_removed, interfaces_restarted,
+    ///     restart_required}`.
+    ///     Params:
+    ///     * `path`: [string] (optional) config file to read, defaults to the one the conductor
+    ///       saves its config to
+    ///
+    ///  * `admin/health`
+    ///     Reports per instance whether the network is connected, the holding queue is
+    ///     draining, zome calls are completing and storage is writable, each as
+    ///     `{status, message}` with status `OK`, `DEGRADED` or `FAILED`. Queues and calls
+    ///     are degraded if they didn't move within 30 seconds. Returns
+    ///     `{status, instances: {<id>: {status, network, holding_queue, zome_calls, storage}}}`
+    ///     with the worst status of all checks as overall status.
+    ///
+    ///  * `admin/stats`
+    ///     Reports the conductor's uptime and, per instance, zome call counts, call and error
+    ///     rates, holding queue depth, held entries, active network peers and storage bytes.
+    ///     Returns `{uptime_secs, running_instances, zome_calls, zome_call_errors,
+    ///     storage_bytes, instances: {<id>: {running, uptime_secs, zome_calls, ...}}}`.
+    ///     Rates are averages since the instance got started.
+    ///
+    ///  * `admin/network_traffic`
+    ///     Reports the recent network traffic of running instances, in total, per peer and
+    ///     per message type, with bytes and messages sent and received, errors, timeouts and
+    ///     their rates per sent message.
+    ///     Params:
+    ///     * `minutes`: [number] (optional) how many of the last minutes to sum up, defaults
+    ///       to 5, at most 60
+    ///     * `instance_id`: [string] (optional) only report this instance
+    ///     Returns `{<id>: {minutes, total, peers: {<agent>: {...}}, message_types: {...}}}`.
+    ///
+    ///  * `admin/logger/set_level`
+    ///     Changes the level of the conductor's logs, or of one instance's logs, right away
+    ///     and in the saved config.
+    ///     Params:
+    ///     * `level`: [string] one of `off`, `error`, `warn`, `info`, `debug` or `trace`
+    ///     * `instance_id`: [string] (optional) instance whose logs to change, defaults to
+    ///       the conductor's logs, which includes instances without their own level
+    ///
+    ///  * `admin/logger/set_module_level`
+    ///     Changes the level of the logs of a module and its submodules, overriding the
+    ///     conductor's and the instances' levels until the conductor restarts.
+    ///     Params:
+    ///     * `module`: [string] module path, leading crates and modules can be left out,
+    ///       e.g. `network::handler::fetch`
+    ///     * `level`: [string] (optional) new level, resets the module if left out
+    ///
+    ///  * `admin/logger/module_levels`
+    ///     Returns the levels set for modules as `{<module>: <level>}`.
+    ///
+    ///  * `admin/app/install`
+    ///     Installs a DNA, adds an instance of it, starts the instance and adds it to
+    ///     interfaces, all in one call. If any step fails, the previous ones get undone.
+    ///     Returns `{dna_id, dna_hash, instance_id}`.
+    ///     Params:
+    ///     * `id`: [string] ID of the new instance, and of its DNA unless `dna_id` is given
+    ///     * `path`: [string] Path of the DNA file, which gets copied to the conductor
+    ///     * `agent_id`: [string] Which agent runs the instance?
+    ///     * `dna_id`: [string] (optional) ID of the DNA
+    ///     * `expected_hash`, `properties`, `uuid`: (optional) as in `admin/dna/install_from_file`
+    ///     * `storage`, `membrane_proof`: (optional) as in `admin/instance/add`
+    ///     * `interfaces`: [array] (optional) `[{interface_id, alias}]` interfaces to add the
+    ///       instance to, `alias` being optional
+    ///
+    ///  * `admin/provision`
+    ///     Converges the conductor to a spec of the agents, DNAs, instances, interfaces and
+    ///     bridges it should have, adding and starting what is missing and replacing what
+    ///     differs. Applying the same spec again changes nothing. Fails without changes if the
+    ///     spec conflicts with the config, e.g. with another DNA hash for an installed DNA ID.
+    ///     Returns `{dry_run, actions: [{change, kind, id}], error}`, `error` being set if
+    ///     applying stopped at a failing action.
+    ///     Params:
+    ///     * `agents`: [array] (optional) `[{id, name, seed_file}]` agents with keys derived
+    ///       from a seed, as in `admin/agent/add_service`
+    ///     * `dnas`: [array] (optional) `[{id, path, url, hash, properties, uuid}]`, each with
+    ///       a `path` to install from or a `hash` to fetch by, as in `admin/dna/install_from_url`
+    ///     * `instances`: [array] (optional) `[{id, dna, agent, storage, membrane_proof}]`
+    ///     * `interfaces`: [array] (optional) interface configs as in `admin/interface/add`,
+    ///       with their `instances`
+    ///     * `bridges`: [array] (optional) `[{caller_id, callee_id, handle}]`
+    ///     * `prune`: [bool] (optional) remove what is not in the spec, apart from admin
+    ///       interfaces and the DPKI instance
+    ///     * `dry_run`: [bool] (optional) only report the actions
+    ///
+    ///  * `admin/tenant/add`
+    ///     Add a tenant, which can manage its own agents, instances and interfaces through
+    ///     admin interfaces of its own (see [with_tenant_admin_functions]).
+    ///     Params:
+    ///     * `id`: [string] ID of the new tenant
+    ///     * `token`: [string] Token the tenant authenticates its admin calls with. Only its
+    ///       hash gets stored.
+    ///
+    ///  * `admin/tenant/remove`
+    ///     Remove a tenant with all its interfaces, agents and instances.
+    ///     Params:
+    ///     * `id`: [string] Which tenant to remove?
+    ///
+    ///  * `admin/tenant/list`
+    ///     Returns an array of the IDs of all tenants.
+    ///
     pub fn with_admin_dna_functions(mut self) -> Self {
-        self.io
-            .add_method("admin/dna/install_from_file", move |params| {
-                let params_map = Self::unwrap_params_map(params)?;
-                let id = Self::get_as_string("id", &params_map)?;
-                let path = Self::get_as_string("path", &params_map)?;
-                let copy = Self::get_as_bool("copy", &params_map).unwrap_or(false);
-                let expected_hash = match params_map.get("expected_hash") {
-                    Some(value) => Some(
-                        value
-                            .as_str()
-                            .ok_or_else(|| {
-                                jsonrpc_core::Error::invalid_params(format!(
-                                    "`{}` is not a valid json string",
-                                    &value
-                                ))
-                            })?
-                            .into(),
-                    ),
-                    None => None,
-                };
-                let properties = params_map.get("properties");
-                let uuid = params_map
-                    .get("uuid")
-                    .and_then(|v| v.as_str())
-                    .map(|v| v.to_string());
-                let dna_hash = conductor_call!(|c| c.install_dna_from_file(
-                    PathBuf::from(path),
-                    id,
-                    copy,
-                    expected_hash,
-                    properties,
-                    uuid,
-                ))?;
-                Ok(json!({ "success": true, "dna_hash": dna_hash }))
-            });
+        self.add_audited_method("admin/dna/install_from_file", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let path = Self::get_as_string("path", &params_map)?;
+            let copy = Self::get_as_bool("copy", &params_map).unwrap_or(false);
+            let expected_hash = match params_map.get("expected_hash") {
+                Some(value) => Some(
+                    value
+                        .as_str()
+                        .ok_or_else(|| {
+                            jsonrpc_core::Error::invalid_params(format!(
+                                "`{}` is not a valid json string",
+                                &value
+                            ))
+                        })?
+                        .into(),
+                ),
+                None => None,
+            };
+            let properties = params_map.get("properties");
+            let uuid = params_map
+                .get("uuid")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string());
+            let dna_hash = conductor_call!(|c| c.install_dna_from_file(
+                PathBuf::from(path),
+                id,
+                copy,
+                expected_hash,
+                properties,
+                uuid,
+            ))?;
+            Ok(json!({ "success": true, "dna_hash": dna_hash }))
+        });
+
+        self.add_audited_method("admin/dna/install_from_url", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let url = Self::get_as_string("url", &params_map)?;
+            let hash = Self::get_as_string("expected_hash", &params_map)?;
+            let properties = params_map.get("properties");
+            let uuid = Self::get_as_string("uuid", &params_map).ok();
+            let dna_hash = conductor_call!(|c| c.install_dna_from_url(
+                Some(url.as_str()),
+                hash.into(),
+                id,
+                properties,
+                uuid,
+            ))?;
+            Ok(json!({ "success": true, "dna_hash": dna_hash }))
+        });
 
-        self.io.add_method("admin/dna/uninstall", move |params| {
+        self.add_audited_method("admin/dna/install_from_hash", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let hash = Self::get_as_string("hash", &params_map)?;
+            let properties = params_map.get("properties");
+            let uuid = Self::get_as_string("uuid", &params_map).ok();
+            let dna_hash = conductor_call!(|c| c.install_dna_from_url(
+                None,
+                hash.into(),
+                id,
+                properties,
+                uuid,
+            ))?;
+            Ok(json!({ "success": true, "dna_hash": dna_hash }))
+        });
+
+        self.add_audited_method("admin/dna/uninstall", move |params| {
             let params_map = Self::unwrap_params_map(params)?;
             let id = Self::get_as_string("id", &params_map)?;
             conductor_call!(|c| c.uninstall_dna(&id))?;
@@ -551,30 +1048,49 @@ impl ConductorApiBuilder {
             ))
         });
 
-        self.io.add_method("admin/instance/add", move |params| {
+        self.add_audited_method("admin/instance/add", move |params| {
             let params_map = Self::unwrap_params_map(params)?;
 
             let id = Self::get_as_string("id", &params_map)?;
             let dna_id = Self::get_as_string("dna_id", &params_map)?;
             let agent_id = Self::get_as_string("agent_id", &params_map)?;
             let storage = Self::get_as_string("storage", &params_map).ok();
+            let membrane_proof = Self::get_as_string("membrane_proof", &params_map).ok();
             conductor_call!(|c| c.add_instance(
                 &id,
                 &dna_id,
                 &agent_id,
-                storage.as_ref().map(String::as_str)
+                storage.as_ref().map(String::as_str),
+                membrane_proof.clone()
             ))?;
             Ok(json!({"success": true}))
         });
 
-        self.io.add_method("admin/instance/remove", move |params| {
+        self.add_audited_method("admin/instance/clone", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let source_id = Self::get_as_string("source_id", &params_map)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let agent_id = Self::get_as_string("agent_id", &params_map).ok();
+            let properties = params_map.get("properties");
+            let uuid = Self::get_as_string("uuid", &params_map).ok();
+            let dna = conductor_call!(|c| c.clone_instance(
+                &source_id,
+                &id,
+                agent_id.as_ref(),
+                properties,
+                uuid.clone()
+            ))?;
+            Ok(serde_json::to_value(dna).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.add_audited_method("admin/instance/remove", move |params| {
             let params_map = Self::unwrap_params_map(params)?;
             let id = Self::get_as_string("id", &params_map)?;
             conductor_call!(|c| c.remove_instance(&id))?;
             Ok(json!({"success": true}))
         });
 
-        self.io.add_method("admin/instance/start", move |params| {
+        self.add_audited_method("admin/instance/start", move |params| {
             let params_map = Self::unwrap_params_map(params)?;
             let id = Self::get_as_string("id", &params_map)?;
             conductor_call!(|c| c.start_instance(&id))?;
@@ -592,13 +1108,164 @@ impl ConductorApiBuilder {
                     .map_err(|_| jsonrpc_core::Error::internal_error())?)
             });
 
-        self.io.add_method("admin/instance/stop", move |params| {
+        self.io
+            .add_method("admin/instance/entry_holders", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let hash = Self::get_as_string("hash", &params_map)?;
+                let response = conductor_call!(|c| c.instance_entry_holders(&id, hash.into()))?;
+                Ok(serde_json::to_value(response)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?)
+            });
+
+        self.io
+            .add_method("admin/instance/chain_head", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let response = conductor_call!(|c| c.instance_chain_head(&id))?;
+                Ok(serde_json::to_value(response)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?)
+            });
+
+        self.io
+            .add_method("admin/instance/storage_usage", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let response = conductor_call!(|c| c.instance_storage_usage(&id))?;
+                Ok(serde_json::to_value(response)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?)
+            });
+
+        self.add_audited_method("admin/instance/compact_storage", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let response = conductor_call!(|c| c.instance_compact_storage(&id))?;
+            Ok(
+                serde_json::to_value(response)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?,
+            )
+        });
+
+        self.add_audited_method("admin/instance/scrub_storage", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let response = conductor_call!(|c| c.instance_scrub_storage(&id))?;
+            Ok(
+                serde_json::to_value(response)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?,
+            )
+        });
+
+        self.add_audited_method("admin/instance/archive_cold_content", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let response = conductor_call!(|c| c.instance_archive_cold_content(&id))?;
+            Ok(
+                serde_json::to_value(response)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?,
+            )
+        });
+
+        self.add_audited_method("admin/instance/export_archive", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let path = PathBuf::from(Self::get_as_string("path", &params_map)?);
+            conductor_call!(|c| c.instance_export_archive(&id, &path))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/instance/import_archive", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let path = PathBuf::from(Self::get_as_string("path", &params_map)?);
+            conductor_call!(|c| c.instance_import_archive(&id, &path))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/instance/migrate_storage", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let storage: StorageConfiguration =
+                serde_json::from_value(params_map.get("storage").cloned().ok_or_else(|| {
+                    jsonrpc_core::Error::invalid_params("`storage` param not provided")
+                })?)
+                .map_err(|e| {
+                    jsonrpc_core::Error::invalid_params(format!("invalid storage: {}", e))
+                })?;
+            let response = conductor_call!(|c| c.instance_migrate_storage(&id, storage))?;
+            Ok(
+                serde_json::to_value(response)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?,
+            )
+        });
+
+        self.add_audited_method("admin/instance/update_dna", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let dna_id = Self::get_as_string("dna_id", &params_map)?;
+            let response = conductor_call!(|c| c.instance_update_dna(&id, &dna_id))?;
+            Ok(
+                serde_json::to_value(response)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?,
+            )
+        });
+
+        self.io
+            .add_method("admin/instance/capability_audit_log", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let token = Self::get_as_string("token", &params_map)
+                    .ok()
+                    .map(Address::from);
+                let response = conductor_call!(|c| c.instance_capability_audit_log(&id, token))?;
+                Ok(serde_json::to_value(response)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?)
+            });
+
+        self.add_audited_method("admin/instance/rotate_capability_grant", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let token = Address::from(Self::get_as_string("token", &params_map)?);
+            let grace_period = params_map
+                .get("grace_period")
+                .and_then(Value::as_u64)
+                .unwrap_or(0);
+            let notify_assignees =
+                Self::get_as_bool("notify_assignees", &params_map).unwrap_or(false);
+            let new_token = conductor_call!(|c| c.instance_rotate_capability_grant(
+                &id,
+                token,
+                Duration::from_secs(grace_period),
+                notify_assignees
+            ))?;
+            Ok(json!({ "token": new_token }))
+        });
+
+        self.add_audited_method("admin/instance/stop", move |params| {
             let params_map = Self::unwrap_params_map(params)?;
             let id = Self::get_as_string("id", &params_map)?;
             conductor_call!(|c| c.stop_instance(&id))?;
             Ok(json!({"success": true}))
         });
 
+        self.add_audited_method("admin/instance/pause", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let timeout = params_map
+                .get("timeout")
+                .and_then(Value::as_u64)
+                .unwrap_or(DEFAULT_PAUSE_TIMEOUT_SECONDS);
+            conductor_call!(|c| c.pause_instance(&id, Duration::from_secs(timeout)))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/instance/resume", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            conductor_call!(|c| c.resume_instance(&id))?;
+            Ok(json!({"success": true}))
+        });
+
         self.io.add_method("admin/instance/list", move |_params| {
             let instances = conductor_call!(
                 |c| Ok(c.config().instances) as Result<Vec<InstanceConfiguration>, String>
@@ -645,9 +1312,503 @@ impl ConductorApiBuilder {
                 ))
             });
 
+        self.io
+            .add_method("admin/instance/dump_state", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let options = Self::get_dump_options(&params_map)?;
+                let dump = conductor_call!(|c| c.state_dump_for_instance(&id, options))?;
+                Ok(
+                    serde_json::to_value(dump)
+                        .map_err(|_| jsonrpc_core::Error::internal_error())?,
+                )
+            });
+
+        self.io.add_method("admin/instance/profile", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let duration = params_map
+                .get("duration")
+                .and_then(Value::as_u64)
+                .unwrap_or(DEFAULT_PROFILE_DURATION_SECONDS);
+            let frequency = params_map
+                .get("frequency")
+                .and_then(Value::as_u64)
+                .unwrap_or(profiler::DEFAULT_FREQUENCY_HZ);
+            let profiler = conductor_call!(|c| c.profiler_for_instance(&id))?;
+            // Sampling happens without the conductor lock, which other calls need meanwhile
+            let profile = profiler
+                .profile(Duration::from_secs(duration), frequency)
+                .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+            let folded = profile.folded();
+            let mut result =
+                serde_json::to_value(profile).map_err(|_| jsonrpc_core::Error::internal_error())?;
+            result["folded"] = Value::String(folded);
+            Ok(result)
+        });
+
+        self.add_audited_method("admin/interface/add", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+
+            let id = Self::get_as_string("id", &params_map)?;
+            let admin = Self::get_as_bool("admin", &params_map)?;
+            let require_signed_calls =
+                Self::get_as_bool("require_signed_calls", &params_map).unwrap_or(false);
+            let filter_signals = Self::get_as_bool("filter_signals", &params_map).unwrap_or(false);
+            let driver_type = Self::get_as_string("type", &params_map)?;
+            let port = u16::try_from(Self::get_as_int("port", &params_map)?).map_err(|_| {
+                jsonrpc_core::Error::invalid_params(String::from(
+                    "`port` has to be a 16bit integer",
+                ))
+            })?;
+            let auth = match params_map.get("tokens") {
+                None => None,
+                Some(tokens) => {
+                    let tokens: Vec<String> =
+                        serde_json::from_value(tokens.clone()).map_err(|_| {
+                            jsonrpc_core::Error::invalid_params(
+                                "`tokens` has to be an array of strings",
+                            )
+                        })?;
+                    Some(InterfaceAuthConfiguration {
+                        token_hashes: tokens.iter().map(|token| hash_token(token)).collect(),
+                        client_ca_file: None,
+                    })
+                }
+            };
+
+            let new_interface = InterfaceConfiguration {
+                id,
+                admin,
+                driver: match driver_type.as_ref() {
+                    "websocket" => InterfaceDriver::Websocket { port },
+                    "http" => InterfaceDriver::Http { port },
+                    "graphql" => InterfaceDriver::Graphql { port },
+                    "grpc" => InterfaceDriver::Grpc { port },
+                    _ => {
+                        return Err(jsonrpc_core::Error::invalid_params(String::from(
+                            "`type` has to be either `websocket`, `http`, `graphql` or `grpc`",
+                        )));
+                    }
+                },
+                instances: Vec::new(),
+                choose_free_port: None,
+                require_signed_calls,
+                filter_signals,
+                tenant: None,
+                auth,
+                tls: None,
+            };
+
+            conductor_call!(|c| c.add_interface(new_interface))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/interface/remove", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            conductor_call!(|c| c.remove_interface(&id))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/interface/add_instance", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let interface_id = Self::get_as_string("interface_id", &params_map)?;
+            let instance_id = Self::get_as_string("instance_id", &params_map)?;
+            let alias = Self::get_as_string("alias", &params_map).ok();
+            conductor_call!(|c| c.add_instance_to_interface(&interface_id, &instance_id, &alias))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/interface/remove_instance", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let interface_id = Self::get_as_string("interface_id", &params_map)?;
+            let instance_id = Self::get_as_string("instance_id", &params_map)?;
+            conductor_call!(|c| c.remove_instance_from_interface(&interface_id, &instance_id))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.io.add_method("admin/interface/list", move |_params| {
+            let interfaces = conductor_call!(
+                |c| Ok(c.config().interfaces) as Result<Vec<InterfaceConfiguration>, String>
+            )?;
+            Ok(serde_json::to_value(interfaces)
+                .map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.add_audited_method("admin/agent/add", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let name = Self::get_as_string("name", &params_map)?;
+
+            let holo_remote_key = params_map
+                .get("holo_remote_key")
+                .map(|k| {
+                    k.as_str()
+                        .ok_or("holo_remote_key must be a string")
+                        .map_err(|e| jsonrpc_core::Error::invalid_params(e))
+                }) // Option<Result<_, _>>
+                .transpose()?; // Result<Option<_>, _>
+
+            conductor_call!(|c| c.add_agent(id, name, holo_remote_key))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/agent/add_service", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let name = Self::get_as_string("name", &params_map)?;
+            let seed_file = PathBuf::from(Self::get_as_string("seed_file", &params_map)?);
+            let public_address = conductor_call!(|c| c.add_service_agent(id, name, seed_file))?;
+            Ok(json!({"success": true, "public_address": public_address}))
+        });
+
+        self.add_audited_method("admin/agent/remove", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            conductor_call!(|c| c.remove_agent(&id))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.io.add_method("admin/agent/list", move |_params| {
+            let agents = conductor_call!(
+                |c| Ok(c.config().agents) as Result<Vec<AgentConfiguration>, String>
+            )?;
+            Ok(serde_json::to_value(agents).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.add_audited_method("admin/agent/block", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let agent = Address::from(Self::get_as_string("agent_address", &params_map)?);
+            conductor_call!(|c| c.block_agent(&agent))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/agent/unblock", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let agent = Address::from(Self::get_as_string("agent_address", &params_map)?);
+            conductor_call!(|c| c.unblock_agent(&agent))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/keystore/lock", move |_params| {
+            conductor_call!(|c| Ok(c.lock_keystore()) as Result<(), String>)?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/keystore/unlock", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let passphrase = Self::get_as_string("passphrase", &params_map)?;
+            conductor_call!(|c| c.unlock_keystore(passphrase.clone()))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/bridge/add", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let caller_id = Self::get_as_string("caller_id", &params_map)?;
+            let callee_id = Self::get_as_string("callee_id", &params_map)?;
+            let handle = Self::get_as_string("handle", &params_map)?;
+
+            let bridge = Bridge {
+                caller_id,
+                callee_id,
+                handle,
+            };
+            conductor_call!(|c| c.add_bridge(bridge))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/bridge/remove", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let caller_id = Self::get_as_string("caller_id", &params_map)?;
+            let callee_id = Self::get_as_string("callee_id", &params_map)?;
+            conductor_call!(|c| c.remove_bridge(&caller_id, &callee_id))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.io.add_method("admin/bridge/list", move |_params| {
+            let bridges =
+                conductor_call!(|c| Ok(c.config().bridges) as Result<Vec<Bridge>, String>)?;
+            Ok(serde_json::to_value(bridges).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.add_audited_method("admin/config/reload", move |params| {
+            let path = match params {
+                Params::None => None,
+                params => Self::get_as_string("path", &Self::unwrap_params_map(params)?)
+                    .ok()
+                    .map(PathBuf::from),
+            };
+            let changes = conductor_call!(|c| c.reload_config(path))?;
+            Ok(serde_json::to_value(changes).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.io.add_method("admin/health", move |_params| {
+            let report = conductor_call!(|c| Ok(c.health()) as Result<HealthReport, String>)?;
+            Ok(serde_json::to_value(report).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.add_audited_method("admin/logger/set_level", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let level = Self::get_as_string("level", &params_map)?;
+            let instance_id = Self::get_as_string("instance_id", &params_map).ok();
+            conductor_call!(|c| c.set_log_level(instance_id.as_ref(), &level))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/logger/set_module_level", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let module = Self::get_as_string("module", &params_map)?;
+            let level = Self::get_as_string("level", &params_map).ok();
+            conductor_call!(
+                |c| c.set_module_log_level(&module, level.as_ref().map(String::as_str))
+            )?;
+            Ok(json!({"success": true}))
+        });
+
+        self.io
+            .add_method("admin/logger/module_levels", move |_params| {
+                let levels = conductor_call!(
+                    |c| Ok(c.module_log_levels()) as Result<BTreeMap<String, String>, String>
+                )?;
+                Ok(serde_json::to_value(levels)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?)
+            });
+
+        self.io.add_method("admin/stats", move |_params| {
+            let stats = conductor_call!(|c| Ok(c.stats()) as Result<ConductorStatistics, String>)?;
+            Ok(serde_json::to_value(stats).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.io.add_method("admin/network_traffic", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let minutes = Self::get_as_int("minutes", &params_map).unwrap_or(5).max(1) as u64;
+            let instance_id = Self::get_as_string("instance_id", &params_map).ok();
+            let instance_id = instance_id.as_ref().map(String::as_str);
+            let traffic = conductor_call!(|c| Ok(c.network_traffic(instance_id, minutes))
+                as Result<BTreeMap<String, TrafficSummary>, String>)?;
+            Ok(serde_json::to_value(traffic).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.add_audited_method("admin/app/install", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let request: AppInstallRequest = serde_json::from_value(Value::Object(params_map))
+                .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+            let result = conductor_call!(|c| c.install_app(request))?;
+            Ok(serde_json::to_value(result).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.add_audited_method("admin/provision", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let spec: ProvisioningSpec = serde_json::from_value(Value::Object(params_map))
+                .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+            let report = conductor_call!(|c| c.provision(spec))?;
+            Ok(serde_json::to_value(report).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.add_audited_method("admin/tenant/add", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let token = Self::get_as_string("token", &params_map)?;
+            conductor_call!(|c| c.add_tenant(id, &token))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.add_audited_method("admin/tenant/remove", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            conductor_call!(|c| c.remove_tenant(&id))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.io.add_method("admin/tenant/list", move |_params| {
+            let tenants = conductor_call!(|c| Ok(c
+                .config()
+                .tenants
+                .into_iter()
+                .map(|tenant| tenant.id)
+                .collect())
+                as Result<Vec<String>, String>)?;
+            Ok(json!(tenants))
+        });
+
+        self
+    }
+
+    /// Checks the `tenant_token` param of a tenant admin call and returns the other params.
+    fn authenticate_tenant_call(
+        tenant: &String,
+        params: Params,
+    ) -> Result<Map<String, Value>, jsonrpc_core::Error> {
+        let params_map = Self::unwrap_params_map(params)?;
+        let token = Self::get_as_string("tenant_token", &params_map)?;
+        conductor_call!(|c| c.authenticate_tenant(tenant, &token))?;
+        Ok(params_map)
+    }
+
+    /// Adds the admin functions a tenant can use to manage its own agents, instances and
+    /// interfaces. IDs are the tenant's own, and nothing of other tenants or the conductor
+    /// itself can be seen or changed. Every call needs the tenant's token as the
+    /// `tenant_token` param, in addition to the params listed here.
+    ///
+    ///  * `admin/dna/list`
+    ///     Returns an array of the DNAs the tenant can run instances of.
+    ///
+    ///  * `admin/agent/add`
+    ///     Add an agent with a new keystore, encrypted with the passphrase of the tenant's
+    ///     passphrase service. Returns the agent's public address.
+    ///     Params:
+    ///     * `id`: [string] ID of the new agent
+    ///     * `name`: [string] Nickname of the agent
+    ///
+    ///  * `admin/agent/list`
+    ///     Returns an array of the tenant's agents.
+    ///
+    ///  * `admin/instance/add`
+    ///     Params:
+    ///     * `id`: [string] ID of the new instance
+    ///     * `dna_id`: [string] Which DNA to run?
+    ///     * `agent_id`: [string] Which of the tenant's agents runs the instance?
+    ///     * `storage`: [string] (optional) as for the conductor's `admin/instance/add`
+    ///
+    ///  * `admin/instance/remove`, `admin/instance/start`, `admin/instance/stop`
+    ///     Params:
+    ///     * `id`: [string] Which instance?
+    ///
+    ///  * `admin/instance/list`
+    ///     Returns an array of the tenant's instances.
+    ///
+    ///  * `admin/instance/running`
+    ///     Returns an array of the IDs of the tenant's running instances.
+    ///
+    ///  * `admin/interface/add`
+    ///     Adds and starts an interface of the tenant. Admin interfaces of a tenant offer
+    ///     these functions.
+    ///     Params:
+    ///     * `id`: [string] ID for the new interface
+    ///     * `admin`: [bool] Offer the tenant's admin functions?
+    ///     * `type`: [string] One of "websocket", "http", "graphql" or "grpc"
+    ///     * `port`:  [number] Port to bind the server to.
+    ///
+    ///  * `admin/interface/remove`
+    ///     Params:
+    ///     * `id`: [string] Which interface to remove?
+    ///
+    ///  * `admin/interface/add_instance`
+    ///     Params:
+    ///     * `interface_id`: [string] Which interface to add the instance to?
+    ///     * `instance_id`: [string] Which instance to add?
+    ///     * `alias`: [string] (optional) Name of the instance within this interface
+    ///
+    ///  * `admin/interface/list`
+    ///     Returns an array of the tenant's interfaces.
+    ///
+    pub fn with_tenant_admin_functions(mut self, tenant: &str) -> Self {
+        let tenant = tenant.to_string();
+
+        let t = tenant.clone();
+        self.io.add_method("admin/dna/list", move |params| {
+            Self::authenticate_tenant_call(&t, params)?;
+            let dnas =
+                conductor_call!(|c| Ok(c.config().dnas) as Result<Vec<DnaConfiguration>, String>)?;
+            Ok(serde_json::Value::Array(
+                dnas.iter()
+                    .map(|dna| json!({"id": dna.id, "hash": dna.hash}))
+                    .collect(),
+            ))
+        });
+
+        let t = tenant.clone();
+        self.io.add_method("admin/agent/add", move |params| {
+            let params_map = Self::authenticate_tenant_call(&t, params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let name = Self::get_as_string("name", &params_map)?;
+            let public_address = conductor_call!(|c| c.tenant_add_agent(&t, id, name))?;
+            Ok(json!({"success": true, "public_address": public_address}))
+        });
+
+        let t = tenant.clone();
+        self.io.add_method("admin/agent/list", move |params| {
+            Self::authenticate_tenant_call(&t, params)?;
+            let agents = conductor_call!(
+                |c| Ok(c.tenant_agents(&t)) as Result<Vec<AgentConfiguration>, String>
+            )?;
+            Ok(serde_json::to_value(agents).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        let t = tenant.clone();
+        self.io.add_method("admin/instance/add", move |params| {
+            let params_map = Self::authenticate_tenant_call(&t, params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let dna_id = Self::get_as_string("dna_id", &params_map)?;
+            let agent_id = Self::get_as_string("agent_id", &params_map)?;
+            let storage = Self::get_as_string("storage", &params_map).ok();
+            conductor_call!(|c| c.tenant_add_instance(
+                &t,
+                &id,
+                &dna_id,
+                &agent_id,
+                storage.as_ref().map(String::as_str)
+            ))?;
+            Ok(json!({"success": true}))
+        });
+
+        let t = tenant.clone();
+        self.io.add_method("admin/instance/remove", move |params| {
+            let params_map = Self::authenticate_tenant_call(&t, params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            conductor_call!(|c| c.tenant_remove_instance(&t, &id))?;
+            Ok(json!({"success": true}))
+        });
+
+        let t = tenant.clone();
+        self.io.add_method("admin/instance/start", move |params| {
+            let params_map = Self::authenticate_tenant_call(&t, params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            conductor_call!(|c| c.tenant_start_instance(&t, &id))?;
+            Ok(json!({"success": true}))
+        });
+
+        let t = tenant.clone();
+        self.io.add_method("admin/instance/stop", move |params| {
+            let params_map = Self::authenticate_tenant_call(&t, params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            conductor_call!(|c| c.tenant_stop_instance(&t, &id))?;
+            Ok(json!({"success": true}))
+        });
+
+        let t = tenant.clone();
+        self.io.add_method("admin/instance/list", move |params| {
+            Self::authenticate_tenant_call(&t, params)?;
+            let instances = conductor_call!(
+                |c| Ok(c.tenant_instances(&t)) as Result<Vec<InstanceConfiguration>, String>
+            )?;
+            Ok(serde_json::Value::Array(
+                instances
+                    .iter()
+                    .map(|instance| {
+                        json!({
+                            "id": instance.id,
+                            "dna": instance.dna,
+                            "agent": instance.agent,
+                        })
+                    })
+                    .collect(),
+            ))
+        });
+
+        let t = tenant.clone();
+        self.io.add_method("admin/instance/running", move |params| {
+            Self::authenticate_tenant_call(&t, params)?;
+            let running = conductor_call!(
+                |c| Ok(c.tenant_running_instances(&t)) as Result<Vec<String>, String>
+            )?;
+            Ok(json!(running))
+        });
+
+        let t = tenant.clone();
         self.io.add_method("admin/interface/add", move |params| {
-            let params_map = Self::unwrap_params_map(params)?;
-
+            let params_map = Self::authenticate_tenant_call(&t, params)?;
             let id = Self::get_as_string("id", &params_map)?;
             let admin = Self::get_as_bool("admin", &params_map)?;
             let driver_type = Self::get_as_string("type", &params_map)?;
@@ -656,41 +1817,49 @@ impl ConductorApiBuilder {
                     "`port` has to be a 16bit integer",
                 ))
             })?;
-
             let new_interface = InterfaceConfiguration {
                 id,
                 admin,
                 driver: match driver_type.as_ref() {
                     "websocket" => InterfaceDriver::Websocket { port },
                     "http" => InterfaceDriver::Http { port },
+                    "graphql" => InterfaceDriver::Graphql { port },
+                    "grpc" => InterfaceDriver::Grpc { port },
                     _ => {
                         return Err(jsonrpc_core::Error::invalid_params(String::from(
-                            "`type` has to be either `websocket` or `http`",
+                            "`type` has to be either `websocket`, `http`, `graphql` or `grpc`",
                         )));
                     }
                 },
                 instances: Vec::new(),
                 choose_free_port: None,
+                require_signed_calls: false,
+                filter_signals: false,
+                tenant: None,
+                auth: None,
+                tls: None,
             };
-
-            conductor_call!(|c| c.add_interface(new_interface))?;
+            conductor_call!(|c| c.tenant_add_interface(&t, new_interface))?;
             Ok(json!({"success": true}))
         });
 
+        let t = tenant.clone();
         self.io.add_method("admin/interface/remove", move |params| {
-            let params_map = Self::unwrap_params_map(params)?;
+            let params_map = Self::authenticate_tenant_call(&t, params)?;
             let id = Self::get_as_string("id", &params_map)?;
-            conductor_call!(|c| c.remove_interface(&id))?;
+            conductor_call!(|c| c.tenant_remove_interface(&t, &id))?;
             Ok(json!({"success": true}))
         });
 
+        let t = tenant.clone();
         self.io
             .add_method("admin/interface/add_instance", move |params| {
-                let params_map = Self::unwrap_params_map(params)?;
+                let params_map = Self::authenticate_tenant_call(&t, params)?;
                 let interface_id = Self::get_as_string("interface_id", &params_map)?;
                 let instance_id = Self::get_as_string("instance_id", &params_map)?;
                 let alias = Self::get_as_string("alias", &params_map).ok();
-                conductor_call!(|c| c.add_instance_to_interface(
+                conductor_call!(|c| c.tenant_add_instance_to_interface(
+                    &t,
                     &interface_id,
                     &instance_id,
                     &alias
@@ -698,84 +1867,16 @@ impl ConductorApiBuilder {
                 Ok(json!({"success": true}))
             });
 
-        self.io
-            .add_method("admin/interface/remove_instance", move |params| {
-                let params_map = Self::unwrap_params_map(params)?;
-                let interface_id = Self::get_as_string("interface_id", &params_map)?;
-                let instance_id = Self::get_as_string("instance_id", &params_map)?;
-                conductor_call!(|c| c.remove_instance_from_interface(&interface_id, &instance_id))?;
-                Ok(json!({"success": true}))
-            });
-
-        self.io.add_method("admin/interface/list", move |_params| {
+        let t = tenant;
+        self.io.add_method("admin/interface/list", move |params| {
+            Self::authenticate_tenant_call(&t, params)?;
             let interfaces = conductor_call!(
-                |c| Ok(c.config().interfaces) as Result<Vec<InterfaceConfiguration>, String>
+                |c| Ok(c.tenant_interfaces(&t)) as Result<Vec<InterfaceConfiguration>, String>
             )?;
             Ok(serde_json::to_value(interfaces)
                 .map_err(|_| jsonrpc_core::Error::internal_error())?)
         });
 
-        self.io.add_method("admin/agent/add", move |params| {
-            let params_map = Self::unwrap_params_map(params)?;
-            let id = Self::get_as_string("id", &params_map)?;
-            let name = Self::get_as_string("name", &params_map)?;
-
-            let holo_remote_key = params_map
-                .get("holo_remote_key")
-                .map(|k| {
-                    k.as_str()
-                        .ok_or("holo_remote_key must be a string")
-                        .map_err(|e| jsonrpc_core::Error::invalid_params(e))
-                }) // Option<Result<_, _>>
-                .transpose()?; // Result<Option<_>, _>
-
-            conductor_call!(|c| c.add_agent(id, name, holo_remote_key))?;
-            Ok(json!({"success": true}))
-        });
-
-        self.io.add_method("admin/agent/remove", move |params| {
-            let params_map = Self::unwrap_params_map(params)?;
-            let id = Self::get_as_string("id", &params_map)?;
-            conductor_call!(|c| c.remove_agent(&id))?;
-            Ok(json!({"success": true}))
-        });
-
-        self.io.add_method("admin/agent/list", move |_params| {
-            let agents = conductor_call!(
-                |c| Ok(c.config().agents) as Result<Vec<AgentConfiguration>, String>
-            )?;
-            Ok(serde_json::to_value(agents).map_err(|_| jsonrpc_core::Error::internal_error())?)
-        });
-
-        self.io.add_method("admin/bridge/add", move |params| {
-            let params_map = Self::unwrap_params_map(params)?;
-            let caller_id = Self::get_as_string("caller_id", &params_map)?;
-            let callee_id = Self::get_as_string("callee_id", &params_map)?;
-            let handle = Self::get_as_string("handle", &params_map)?;
-
-            let bridge = Bridge {
-                caller_id,
-                callee_id,
-                handle,
-            };
-            conductor_call!(|c| c.add_bridge(bridge))?;
-            Ok(json!({"success": true}))
-        });
-
-        self.io.add_method("admin/bridge/remove", move |params| {
-            let params_map = Self::unwrap_params_map(params)?;
-            let caller_id = Self::get_as_string("caller_id", &params_map)?;
-            let callee_id = Self::get_as_string("callee_id", &params_map)?;
-            conductor_call!(|c| c.remove_bridge(&caller_id, &callee_id))?;
-            Ok(json!({"success": true}))
-        });
-
-        self.io.add_method("admin/bridge/list", move |_params| {
-            let bridges =
-                conductor_call!(|c| Ok(c.config().bridges) as Result<Vec<Bridge>, String>)?;
-            Ok(serde_json::to_value(bridges).map_err(|_| jsonrpc_core::Error::internal_error())?)
-        });
-
         self
     }
 
@@ -792,6 +1893,9 @@ impl ConductorApiBuilder {
     ///   - `source_chain` [bool] (optional) If set to false, will exclude source chain headers
     ///   - `held_aspects` [bool] (optional) If set to false, will exclude the holding map entries
     ///   - `queued_holding_workflows` [bool] (optional If set to false, will exclude contents of the validation queue
+    ///   - `storage_usage` [bool] (optional) If set to true, will include the instance's storage usage
+    ///   - `options` [object] (optional) `{include_eavis, include_storage_usage}`, both default
+    ///     to false
     ///
     /// - `debug/fetch_cas`
     ///   Returns content of a given instance's CAS.
@@ -800,6 +1904,24 @@ impl ConductorApiBuilder {
     ///   - `address` Address (hash) of the content that is requests
     ///   Returns an object of the form: {type:"<entry type>", content: "<content>"}
     ///
+    /// - `debug/rollback_chain`
+    ///   Truncates an instance's source chain back to the given header so that entries committed
+    ///   after it can be committed again. Only available if the conductor config sets `dev_mode`.
+    ///   Params:
+    ///   - `instance_id` ID of the instance of which the chain should be rolled back
+    ///   - `header_address` Address of the header that should become the new chain head
+    ///   Returns an array with the addresses of the dropped headers, newest first.
+    ///
+    /// - `debug/causal_log`
+    ///   Returns the last actions of an instance together with the ID of what caused them,
+    ///   which is another action, a zome call, a network message or a holding workflow.
+    ///   Params:
+    ///   - `instance_id` ID of the instance
+    ///   - `id` [optional] ID of an entry; returns only it followed by its chain of causes
+    ///   - `subject` [optional] Returns only entries about this, like an entry address
+    ///   Returns an array of objects of the form:
+    ///   {id, cause, name: "<action type or description>", subject, time}
+    ///
     pub fn with_debug_functions(mut self) -> Self {
         self.io
             .add_method("debug/running_instances", move |_params| {
@@ -812,12 +1934,8 @@ impl ConductorApiBuilder {
             let params_map = Self::unwrap_params_map(params)?;
             let instance_id = Self::get_as_string("instance_id", &params_map)?;
 
-            let mut dump = conductor_call!(|c| c.state_dump_for_instance(
-                &instance_id,
-                DumpOptions {
-                    include_eavis: false
-                }
-            ))?;
+            let options = Self::get_debug_dump_options(&params_map)?;
+            let mut dump = conductor_call!(|c| c.state_dump_for_instance(&instance_id, options))?;
 
             if Ok(false) == Self::get_as_bool("source_chain", &params_map) {
                 dump.source_chain.clear()
@@ -850,6 +1968,24 @@ impl ConductorApiBuilder {
             }))
         });
 
+        self.io.add_method("debug/rollback_chain", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let instance_id = Self::get_as_string("instance_id", &params_map)?;
+            let header_address = Address::from(Self::get_as_string("header_address", &params_map)?);
+            let dropped = conductor_call!(|c| c.rollback_chain(&instance_id, header_address))?;
+            Ok(serde_json::to_value(dropped).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
+        self.io.add_method("debug/causal_log", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let instance_id = Self::get_as_string("instance_id", &params_map)?;
+            let id = Self::get_as_string("id", &params_map).ok();
+            let subject = Self::get_as_string("subject", &params_map).ok();
+            let entries =
+                conductor_call!(|c| c.causal_log_for_instance(&instance_id, id, subject))?;
+            Ok(serde_json::to_value(entries).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
         self
     }
 
@@ -882,6 +2018,11 @@ impl ConductorApiBuilder {
     ///     - `port` Port to host the HTTP server on
     ///     - `bundle` UI bundle to serve on this port
     ///     - `dna_interface` DNA interface this UI can connect to (Optional)
+    ///     - `host` Host name to serve the UI for, so it can share the port with other UIs
+    ///       (Optional)
+    ///     - `path_prefix` Path to serve the UI under, e.g. "/chat" (Optional)
+    ///     - `instance` Instance of the DNA interface the UI calls, injected into its HTML
+    ///       pages (Optional)
     ///
     /// - `admin/ui_interface/remove`
     ///     Remove an interface by ID
@@ -940,12 +2081,18 @@ impl ConductorApiBuilder {
             })?;
             let bundle = Self::get_as_string("bundle", &params_map)?;
             let dna_interface = Self::get_as_string("dna_interface", &params_map).ok();
+            let host = Self::get_as_string("host", &params_map).ok();
+            let path_prefix = Self::get_as_string("path_prefix", &params_map).ok();
+            let instance = Self::get_as_string("instance", &params_map).ok();
 
             conductor_call!(|c| c.add_ui_interface(UiInterfaceConfiguration {
                 id,
                 port,
                 bundle,
                 dna_interface,
+                host,
+                path_prefix,
+                instance,
                 reroute_to_root: true,
                 bind_address: "127.0.0.1".to_string(),
             }))?;
@@ -991,26 +2138,27 @@ impl ConductorApiBuilder {
         self
     }
 
-    pub fn with_agent_signature_callback(mut self, keybundle: Arc<Mutex<KeyBundle>>) -> Self {
+    pub fn with_agent_signature_callback(self, keybundle: Arc<Mutex<KeyBundle>>) -> Self {
+        self.with_agent_signing_backend(Arc::new(KeyBundleBackend::new(keybundle)))
+    }
+
+    /// Routes all signing requests of the instance (`agent/sign`) through the given backend.
+    pub fn with_agent_signing_backend(mut self, backend: Arc<dyn SigningBackend>) -> Self {
         self.io.add_method("agent/sign", move |params| {
             let params_map = Self::unwrap_params_map(params)?;
             let payload = Self::get_as_crypto_string("payload", &params_map)?;
-            // Convert payload string into a SecBuf
-            let mut message = SecBuf::with_insecure_from_string(payload);
-
-            // Get write lock on the key since we need a mutuble reference to lock the
-            // secure memory the key is in:
-            let mut message_signature = keybundle
-                .lock()
-                .unwrap()
-                .sign(&mut message)
-                .expect("Failed to sign with keybundle.");
 
-            let message_signature = message_signature.read_lock();
-            // Return as base64 encoded string
-            let signature = base64::encode(&**message_signature);
+            let signature = backend.sign(&payload).map_err(|holochain_error| {
+                let err = format!("Signing backend error: {}", holochain_error);
+                error!("{}", err);
+                jsonrpc_core::Error {
+                    code: jsonrpc_core::types::error::ErrorCode::ServerError(1),
+                    message: err,
+                    data: None,
+                }
+            })?;
 
-            Ok(json!({ "signature": signature }))
+            Ok(json!({ "signature": String::from(signature) }))
         });
         self
     }
@@ -1169,6 +2317,33 @@ impl ConductorApiBuilder {
         self
     }
 
+    /// Lets the instance ask the DPKI instance whether its agent key is registered
+    /// (`agent/is_key_registered`), which genesis checks before committing anything.
+    pub fn with_dpki_key_registration(
+        mut self,
+        dpki_instance: Arc<RwLock<Holochain>>,
+        agent_key: String,
+    ) -> Self {
+        self.io
+            .add_method("agent/is_key_registered", move |_params| {
+                let registered = dpki_instance
+                    .write()
+                    .unwrap()
+                    .dpki_is_key_registered(agent_key.clone())
+                    .map_err(|holochain_error| {
+                        let err = format!("DPKI error: {}", holochain_error);
+                        error!("{}", err);
+                        jsonrpc_core::Error {
+                            code: jsonrpc_core::types::error::ErrorCode::ServerError(1),
+                            message: err,
+                            data: None,
+                        }
+                    })?;
+                Ok(json!({ "registered": registered }))
+            });
+        self
+    }
+
     pub fn with_agent_keystore_functions(mut self, keystore: Arc<Mutex<Keystore>>) -> Self {
         let k = keystore.clone();
         self.io.add_method("agent/keystore/list", move |_params| {
@@ -1417,6 +2592,48 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_rpc_call_method_requires_signed_calls() {
+        let (config, instances) = example_config_and_instances();
+        let handler = ConductorApiBuilder::new()
+            .with_instances(instances.clone())
+            .with_instance_configs(config.instances)
+            .with_signed_calls_required(true)
+            .spawn();
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "call",
+                Some(json!({
+                    "instance_id" : "test-instance-1",
+                    "zome" : "greeter",
+                    "function" : "hello",
+                })),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"This interface only accepts signed zome calls: `provenance` param not provided"},"id":"0"}"#
+        );
+
+        // signed calls get through to the instance
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "call",
+                Some(json!({
+                    "instance_id" : "test-instance-1",
+                    "zome" : "greeter",
+                    "function" : "hello",
+                    "provenance" : ["some_source", "some_signature"],
+                })),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Holochain Instance Error: Holochain instance is not active yet."},"id":"0"}"#
+        );
+    }
+
     #[test]
     fn test_rpc_call_method() {
         let (config, instances) = example_config_and_instances();
@@ -1547,4 +2764,189 @@ pub mod tests {
             r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Holochain Instance Error: Holochain instance is not active yet."},"id":"0"}"#
         );
     }
+
+    #[test]
+    fn test_rpc_chain_head_params() {
+        let (config, instances) = example_config_and_instances();
+        let handler = ConductorApiBuilder::new()
+            .with_instances(instances.clone())
+            .with_instance_configs(config.instances)
+            .with_admin_dna_functions()
+            .spawn();
+        assert!(format!("{:?}", handler).contains("admin/instance/chain_head"));
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str("admin/instance/chain_head", None))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"expected params map"},"id":"0"}"#
+        );
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "admin/instance/chain_head",
+                Some(json!({})),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"`id` param not provided"},"id":"0"}"#
+        );
+    }
+
+    #[test]
+    fn test_get_dump_options() {
+        let params_map = |params: serde_json::Value| params.as_object().unwrap().clone();
+        assert_eq!(
+            ConductorApiBuilder::get_dump_options(&params_map(json!({"id": "test-instance-1"})))
+                .unwrap(),
+            DumpOptions::default()
+        );
+        assert_eq!(
+            ConductorApiBuilder::get_dump_options(&params_map(
+                json!({"options": {"include_eavis": true}})
+            ))
+            .unwrap(),
+            DumpOptions {
+                include_eavis: true,
+                include_storage_usage: false,
+            }
+        );
+        assert!(ConductorApiBuilder::get_dump_options(&params_map(
+            json!({"options": {"include_eavis": "yes"}})
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_rpc_dump_state_params() {
+        let (config, instances) = example_config_and_instances();
+        let handler = ConductorApiBuilder::new()
+            .with_instances(instances.clone())
+            .with_instance_configs(config.instances)
+            .with_admin_dna_functions()
+            .spawn();
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "admin/instance/dump_state",
+                Some(json!({})),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"`id` param not provided"},"id":"0"}"#
+        );
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "admin/instance/dump_state",
+                Some(json!({
+                    "id": "test-instance-1",
+                    "options": {"include_eavis": "yes"},
+                })),
+            ))
+            .expect("Invalid call to handler");
+        assert!(response_str.starts_with(
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"invalid options: "#
+        ));
+
+        // Valid params get through to the conductor, which isn't mounted in this test setup
+        for params in vec![
+            json!({"id": "test-instance-1"}),
+            json!({"id": "test-instance-1", "options": {"include_storage_usage": true}}),
+        ] {
+            let response_str = handler
+                .handle_request_sync(&create_call_str("admin/instance/dump_state", Some(params)))
+                .expect("Invalid call to handler");
+            assert_eq!(
+                response_str,
+                r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Admin conductor function called without a conductor mounted as singleton!"},"id":"0"}"#
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_debug_dump_options() {
+        let params_map = |params: serde_json::Value| params.as_object().unwrap().clone();
+        let options = |params: serde_json::Value| {
+            ConductorApiBuilder::get_debug_dump_options(&params_map(params))
+        };
+        assert_eq!(
+            options(json!({"instance_id": "test-instance-1"})).unwrap(),
+            DumpOptions::default()
+        );
+        assert_eq!(
+            options(json!({"options": {"include_eavis": true, "include_storage_usage": true}}))
+                .unwrap(),
+            DumpOptions {
+                include_eavis: true,
+                include_storage_usage: true,
+            }
+        );
+        // The older flag still works, also next to options
+        assert_eq!(
+            options(json!({"storage_usage": true})).unwrap(),
+            DumpOptions {
+                include_eavis: false,
+                include_storage_usage: true,
+            }
+        );
+        assert_eq!(
+            options(json!({"storage_usage": true, "options": {"include_eavis": true}})).unwrap(),
+            DumpOptions {
+                include_eavis: true,
+                include_storage_usage: true,
+            }
+        );
+        assert_eq!(
+            options(json!({"storage_usage": false, "options": {"include_storage_usage": true}}))
+                .unwrap(),
+            DumpOptions {
+                include_eavis: false,
+                include_storage_usage: true,
+            }
+        );
+        assert!(options(json!({"options": true})).is_err());
+        assert!(options(json!({"options": {"include_storage_usage": 1}})).is_err());
+    }
+
+    #[test]
+    fn test_rpc_debug_state_dump_options() {
+        let (config, instances) = example_config_and_instances();
+        let handler = ConductorApiBuilder::new()
+            .with_instances(instances.clone())
+            .with_instance_configs(config.instances)
+            .with_debug_functions()
+            .spawn();
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "debug/state_dump",
+                Some(json!({
+                    "instance_id": "test-instance-1",
+                    "options": ["include_eavis"],
+                })),
+            ))
+            .expect("Invalid call to handler");
+        assert!(response_str.starts_with(
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"invalid options: "#
+        ));
+
+        // Valid options get through to the conductor, which isn't mounted in this test setup
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "debug/state_dump",
+                Some(json!({
+                    "instance_id": "test-instance-1",
+                    "options": {"include_eavis": true},
+                })),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Admin conductor function called without a conductor mounted as singleton!"},"id":"0"}"#
+        );
+    }
 }
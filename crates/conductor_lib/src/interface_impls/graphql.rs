@@ -0,0 +1,1006 @@
+//! GraphQL interface for web frontends.
+//!
+//! Exposes the instances of an interface with a schema that gets generated from the function
+//! declarations of their zomes: each instance is a field of the root types, with a field per
+//! zome, which in turn has a field per zome function that takes the function's inputs as
+//! arguments. Functions whose names mark them as reads (see [is_query_function]) are fields of
+//! `Query`, all others are fields of `Mutation`. Arguments and results are of the scalar type
+//! `JSON`. Results of `{"Ok": ..}` get unwrapped and results of `{"Err": ..}` become errors.
+//! Signals of the instances get delivered to subscriptions of `signals(instance: String)`.
+//!
+//! Operations are sent over a websocket with the graphql-ws protocol of
+//! subscriptions-transport-ws, which GraphQL clients support for queries and mutations as well
+//! as for subscriptions. A capability token for the zome calls can be passed as `token` in the
+//! payload of `connection_init`. The schema is available in SDL as `_service { sdl }`.
+use crate::{conductor::broadcaster::Broadcaster, interface::Interface};
+use crossbeam_channel::{unbounded, Receiver};
+use graphql_parser::query::{
+    parse_query, Definition, Document, Field, FragmentDefinition, OperationDefinition, Selection,
+    SelectionSet, Value as GraphqlValue, VariableDefinition,
+};
+use holochain_core_types::dna::fn_declarations::FnDeclaration;
+use holochain_locksmith::Mutex;
+use jsonrpc_core::IoHandler;
+use jsonrpc_ws_server::ws;
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::Arc,
+    thread,
+};
+
+/// Subprotocol of subscriptions-transport-ws
+pub const GRAPHQL_WS_PROTOCOL: &str = "graphql-ws";
+
+/// Zome functions that read but don't change anything start with one of these, followed by
+/// an underscore or nothing at all
+const QUERY_PREFIXES: &[&str] = &[
+    "get", "list", "query", "fetch", "find", "count", "read", "search", "is", "has",
+];
+
+/// Zome function declarations by public instance ID and zome name, as returned by the
+/// `info/zome_functions` method of interfaces
+pub type ZomeFunctions = BTreeMap<String, BTreeMap<String, Vec<FnDeclaration>>>;
+
+pub fn is_query_function(name: &str) -> bool {
+    QUERY_PREFIXES
+        .iter()
+        .any(|prefix| name == *prefix || name.starts_with(&format!("{}_", prefix)))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OperationType {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+impl OperationType {
+    fn of_function(name: &str) -> Self {
+        if is_query_function(name) {
+            OperationType::Query
+        } else {
+            OperationType::Mutation
+        }
+    }
+
+    fn type_name(self) -> &'static str {
+        match self {
+            OperationType::Query => "Query",
+            OperationType::Mutation => "Mutation",
+            OperationType::Subscription => "Subscription",
+        }
+    }
+}
+
+/// GraphQL names can only contain letters, digits and underscores and can't start with a digit
+fn field_name(name: &str) -> String {
+    let mut field: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if field.starts_with(|c: char| c.is_ascii_digit()) {
+        field.insert(0, '_');
+    }
+    field
+}
+
+fn type_name(name: &str) -> String {
+    field_name(name)
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn object_type(name: &str, fields: &[String]) -> String {
+    let fields: String = fields
+        .iter()
+        .map(|field| format!("  {}\n", field))
+        .collect();
+    format!("type {} {{\n{}}}\n\n", name, fields)
+}
+
+fn function_field(function: &FnDeclaration) -> String {
+    if function.inputs.is_empty() {
+        format!("{}: JSON", field_name(&function.name))
+    } else {
+        let arguments: Vec<String> = function
+            .inputs
+            .iter()
+            .map(|input| format!("{}: JSON", field_name(&input.name)))
+            .collect();
+        format!(
+            "{}({}): JSON",
+            field_name(&function.name),
+            arguments.join(", ")
+        )
+    }
+}
+
+/// The GraphQL schema of the instances of an interface
+pub struct GraphqlSchema {
+    functions: ZomeFunctions,
+}
+
+impl GraphqlSchema {
+    pub fn new(functions: ZomeFunctions) -> Self {
+        GraphqlSchema { functions }
+    }
+
+    /// The schema in GraphQL's schema definition language
+    pub fn sdl(&self) -> String {
+        let mut sdl = String::from("scalar JSON\n\n");
+        let mut query_fields = self.operation_types(OperationType::Query, &mut sdl);
+        query_fields.push(String::from("_service: _Service!"));
+        sdl.push_str(&object_type("Query", &query_fields));
+        let mutation_fields = self.operation_types(OperationType::Mutation, &mut sdl);
+        if !mutation_fields.is_empty() {
+            sdl.push_str(&object_type("Mutation", &mutation_fields));
+        }
+        sdl.push_str(&object_type(
+            "Subscription",
+            &[String::from("signals(instance: String): JSON")],
+        ));
+        sdl.push_str(&object_type("_Service", &[String::from("sdl: String!")]));
+        sdl.trim_end().to_string() + "\n"
+    }
+
+    /// Adds the instance and zome types of the given operation type to the SDL and returns
+    /// the fields of its root type. Types without fields are left out since GraphQL doesn't
+    /// allow empty types.
+    fn operation_types(&self, operation: OperationType, sdl: &mut String) -> Vec<String> {
+        let mut root_fields = Vec::new();
+        for (instance, zomes) in &self.functions {
+            let instance_type = format!("{}{}", type_name(instance), operation.type_name());
+            let mut zome_fields = Vec::new();
+            for (zome, functions) in zomes {
+                let function_fields: Vec<String> = functions
+                    .iter()
+                    .filter(|function| OperationType::of_function(&function.name) == operation)
+                    .map(function_field)
+                    .collect();
+                if function_fields.is_empty() {
+                    continue;
+                }
+                let zome_type = format!(
+                    "{}{}{}",
+                    type_name(instance),
+                    type_name(zome),
+                    operation.type_name()
+                );
+                sdl.push_str(&object_type(&zome_type, &function_fields));
+                zome_fields.push(format!("{}: {}", field_name(zome), zome_type));
+            }
+            if !zome_fields.is_empty() {
+                sdl.push_str(&object_type(&instance_type, &zome_fields));
+                root_fields.push(format!("{}: {}", field_name(instance), instance_type));
+            }
+        }
+        root_fields
+    }
+
+    fn instance(&self, field: &str) -> Option<(&String, &BTreeMap<String, Vec<FnDeclaration>>)> {
+        self.functions
+            .iter()
+            .find(|(instance, _)| field_name(instance) == field)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct GraphqlRequest {
+    pub query: String,
+    #[serde(default)]
+    pub variables: Option<Map<String, Value>>,
+    #[serde(default, rename = "operationName")]
+    pub operation_name: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphqlOutcome {
+    /// The `data` and `errors` of a query or mutation
+    Response(Value),
+    /// Subscription to the signals of the instance with the given ID, or of all instances if
+    /// None. Signals get delivered as the value of the field `key`.
+    Subscription {
+        key: String,
+        instance: Option<String>,
+    },
+}
+
+/// Executes GraphQL operations with zome calls through the handler of an interface
+pub struct GraphqlExecutor {
+    handler: Arc<IoHandler>,
+    token: Option<String>,
+}
+
+impl GraphqlExecutor {
+    pub fn new(handler: Arc<IoHandler>, token: Option<String>) -> Self {
+        GraphqlExecutor { handler, token }
+    }
+
+    pub fn execute(&self, request: &GraphqlRequest) -> GraphqlOutcome {
+        self.try_execute(request).unwrap_or_else(|message| {
+            GraphqlOutcome::Response(json!({
+                "data": null,
+                "errors": [{ "message": message }],
+            }))
+        })
+    }
+
+    fn try_execute(&self, request: &GraphqlRequest) -> Result<GraphqlOutcome, String> {
+        let document = parse_query(&request.query).map_err(|e| e.to_string())?;
+        let (operation, variable_definitions, selection_set) =
+            select_operation(&document, request.operation_name.as_ref())?;
+
+        let mut variables = request.variables.clone().unwrap_or_default();
+        for definition in variable_definitions {
+            if let (false, Some(default)) = (
+                variables.contains_key(&definition.name),
+                &definition.default_value,
+            ) {
+                let default = to_json(default, &Map::new())?;
+                variables.insert(definition.name.clone(), default);
+            }
+        }
+        let schema = GraphqlSchema::new(
+            serde_json::from_value(self.request("info/zome_functions", json!({}))?)
+                .map_err(|e| e.to_string())?,
+        );
+        let resolver = Resolver {
+            executor: self,
+            schema: &schema,
+            fragments: document
+                .definitions
+                .iter()
+                .filter_map(|definition| match definition {
+                    Definition::Fragment(fragment) => Some((fragment.name.as_str(), fragment)),
+                    _ => None,
+                })
+                .collect(),
+            variables,
+        };
+
+        if operation == OperationType::Subscription {
+            return resolver.subscription(selection_set);
+        }
+        let mut errors = Vec::new();
+        let data = resolver.root(operation, selection_set, &mut errors)?;
+        Ok(GraphqlOutcome::Response(if errors.is_empty() {
+            json!({ "data": data })
+        } else {
+            json!({ "data": data, "errors": errors })
+        }))
+    }
+
+    fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let request = json!({"jsonrpc": "2.0", "id": 0, "method": method, "params": params});
+        let response = self
+            .handler
+            .handle_request_sync(&request.to_string())
+            .ok_or_else(|| format!("No response to {}", method))?;
+        let mut response: Value = serde_json::from_str(&response).map_err(|e| e.to_string())?;
+        if let Some(error) = response.get("error") {
+            return Err(error["message"]
+                .as_str()
+                .unwrap_or("Internal error")
+                .to_string());
+        }
+        Ok(response["result"].take())
+    }
+
+    fn call_zome_function(
+        &self,
+        instance: &str,
+        zome: &str,
+        function: &str,
+        args: Value,
+    ) -> Result<Value, String> {
+        let mut params = json!({
+            "instance_id": instance,
+            "zome": zome,
+            "function": function,
+            "args": args,
+        });
+        if let Some(token) = &self.token {
+            params["token"] = json!(token);
+        }
+        let result = match self.request("call", params)? {
+            Value::String(result) => serde_json::from_str(&result).unwrap_or(Value::String(result)),
+            result => result,
+        };
+        if let Value::Object(object) = &result {
+            if object.len() == 1 {
+                if let Some(ok) = object.get("Ok") {
+                    return Ok(ok.clone());
+                }
+                if let Some(error) = object.get("Err") {
+                    return Err(match error {
+                        Value::String(error) => error.clone(),
+                        error => error.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn select_operation<'a>(
+    document: &'a Document,
+    name: Option<&String>,
+) -> Result<(OperationType, &'a [VariableDefinition], &'a SelectionSet), String> {
+    let operations: Vec<(Option<&String>, _)> = document
+        .definitions
+        .iter()
+        .filter_map(|definition| match definition {
+            Definition::Operation(operation) => Some(operation),
+            _ => None,
+        })
+        .map(|operation| match operation {
+            OperationDefinition::SelectionSet(set) => (None, (OperationType::Query, &[][..], set)),
+            OperationDefinition::Query(query) => (
+                query.name.as_ref(),
+                (
+                    OperationType::Query,
+                    &query.variable_definitions[..],
+                    &query.selection_set,
+                ),
+            ),
+            OperationDefinition::Mutation(mutation) => (
+                mutation.name.as_ref(),
+                (
+                    OperationType::Mutation,
+                    &mutation.variable_definitions[..],
+                    &mutation.selection_set,
+                ),
+            ),
+            OperationDefinition::Subscription(subscription) => (
+                subscription.name.as_ref(),
+                (
+                    OperationType::Subscription,
+                    &subscription.variable_definitions[..],
+                    &subscription.selection_set,
+                ),
+            ),
+        })
+        .collect();
+    match (name, operations.as_slice()) {
+        (Some(name), _) => operations
+            .iter()
+            .find(|(operation_name, _)| *operation_name == Some(name))
+            .map(|(_, operation)| *operation)
+            .ok_or_else(|| format!("Unknown operation \"{}\"", name)),
+        (None, [(_, operation)]) => Ok(*operation),
+        (None, []) => Err(String::from("No operation given")),
+        (None, _) => Err(String::from(
+            "operationName is required for documents with several operations",
+        )),
+    }
+}
+
+fn to_json(value: &GraphqlValue, variables: &Map<String, Value>) -> Result<Value, String> {
+    Ok(match value {
+        GraphqlValue::Variable(name) => variables.get(name).cloned().unwrap_or(Value::Null),
+        GraphqlValue::Int(number) => json!(number
+            .as_i64()
+            .ok_or_else(|| String::from("Int out of range"))?),
+        GraphqlValue::Float(number) => json!(number),
+        GraphqlValue::String(string) => json!(string),
+        GraphqlValue::Boolean(boolean) => json!(boolean),
+        GraphqlValue::Null => Value::Null,
+        GraphqlValue::Enum(name) => json!(name),
+        GraphqlValue::List(list) => Value::Array(
+            list.iter()
+                .map(|item| to_json(item, variables))
+                .collect::<Result<_, _>>()?,
+        ),
+        GraphqlValue::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, item)| Ok((key.clone(), to_json(item, variables)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+    })
+}
+
+fn response_key(field: &Field) -> String {
+    field.alias.clone().unwrap_or_else(|| field.name.clone())
+}
+
+fn field_error(message: String, path: &[String]) -> Value {
+    json!({ "message": message, "path": path })
+}
+
+struct Resolver<'a> {
+    executor: &'a GraphqlExecutor,
+    schema: &'a GraphqlSchema,
+    fragments: HashMap<&'a str, &'a FragmentDefinition>,
+    variables: Map<String, Value>,
+}
+
+impl<'a> Resolver<'a> {
+    /// The fields of a selection set, with those of fragments spread into it
+    fn fields(&self, selection_set: &'a SelectionSet) -> Result<Vec<&'a Field>, String> {
+        let mut fields = Vec::new();
+        for selection in &selection_set.items {
+            match selection {
+                Selection::Field(field) => fields.push(field),
+                Selection::InlineFragment(fragment) => {
+                    fields.extend(self.fields(&fragment.selection_set)?)
+                }
+                Selection::FragmentSpread(spread) => {
+                    let fragment = self
+                        .fragments
+                        .get(spread.fragment_name.as_str())
+                        .ok_or_else(|| format!("Unknown fragment \"{}\"", spread.fragment_name))?;
+                    fields.extend(self.fields(&fragment.selection_set)?)
+                }
+            }
+        }
+        Ok(fields)
+    }
+
+    fn root(
+        &self,
+        operation: OperationType,
+        selection_set: &'a SelectionSet,
+        errors: &mut Vec<Value>,
+    ) -> Result<Value, String> {
+        let mut data = Map::new();
+        for field in self.fields(selection_set)? {
+            let key = response_key(field);
+            let path = [key.clone()];
+            let value = match field.name.as_str() {
+                "__typename" => json!(operation.type_name()),
+                "_service" if operation == OperationType::Query => {
+                    let mut service = Map::new();
+                    for service_field in self.fields(&field.selection_set)? {
+                        let value = match service_field.name.as_str() {
+                            "sdl" => json!(self.schema.sdl()),
+                            "__typename" => json!("_Service"),
+                            name => {
+                                return Err(format!(
+                                    "Cannot query field \"{}\" on type \"_Service\"",
+                                    name
+                                ))
+                            }
+                        };
+                        service.insert(response_key(service_field), value);
+                    }
+                    Value::Object(service)
+                }
+                name => match self.schema.instance(name) {
+                    Some((instance, zomes)) => {
+                        self.instance(operation, instance, zomes, field, &path, errors)?
+                    }
+                    None => {
+                        errors.push(field_error(
+                            format!(
+                                "Cannot query field \"{}\" on type \"{}\"",
+                                name,
+                                operation.type_name()
+                            ),
+                            &path,
+                        ));
+                        Value::Null
+                    }
+                },
+            };
+            data.insert(key, value);
+        }
+        Ok(Value::Object(data))
+    }
+
+    fn instance(
+        &self,
+        operation: OperationType,
+        instance: &str,
+        zomes: &BTreeMap<String, Vec<FnDeclaration>>,
+        field: &'a Field,
+        path: &[String],
+        errors: &mut Vec<Value>,
+    ) -> Result<Value, String> {
+        let mut object = Map::new();
+        for zome_field in self.fields(&field.selection_set)? {
+            let key = response_key(zome_field);
+            let mut path = path.to_vec();
+            path.push(key.clone());
+            let zome = zomes
+                .iter()
+                .find(|(zome, _)| field_name(zome) == zome_field.name);
+            let value = match (zome_field.name.as_str(), zome) {
+                ("__typename", _) => json!(type_name(instance) + operation.type_name()),
+                (_, Some((zome, functions))) => self.zome(
+                    operation, instance, zome, functions, zome_field, &path, errors,
+                )?,
+                (name, None) => {
+                    errors.push(field_error(
+                        format!("Instance \"{}\" has no zome \"{}\"", instance, name),
+                        &path,
+                    ));
+                    Value::Null
+                }
+            };
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn zome(
+        &self,
+        operation: OperationType,
+        instance: &str,
+        zome: &str,
+        functions: &[FnDeclaration],
+        field: &'a Field,
+        path: &[String],
+        errors: &mut Vec<Value>,
+    ) -> Result<Value, String> {
+        let mut object = Map::new();
+        for function_field in self.fields(&field.selection_set)? {
+            let key = response_key(function_field);
+            let mut path = path.to_vec();
+            path.push(key.clone());
+            let function = functions.iter().find(|function| {
+                field_name(&function.name) == function_field.name
+                    && OperationType::of_function(&function.name) == operation
+            });
+            let result = match (function_field.name.as_str(), function) {
+                ("__typename", _) => Ok(json!(
+                    type_name(instance) + &type_name(zome) + operation.type_name()
+                )),
+                (_, Some(function)) => self.call(instance, zome, function, function_field),
+                (name, None) => Err(format!(
+                    "Zome \"{}\" has no {} function \"{}\"",
+                    zome,
+                    operation.type_name().to_lowercase(),
+                    name
+                )),
+            };
+            let value = result.unwrap_or_else(|message| {
+                errors.push(field_error(message, &path));
+                Value::Null
+            });
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn call(
+        &self,
+        instance: &str,
+        zome: &str,
+        function: &FnDeclaration,
+        field: &Field,
+    ) -> Result<Value, String> {
+        if !field.selection_set.items.is_empty() {
+            return Err(format!(
+                "Field \"{}\" is of type JSON and can't have a selection",
+                field.name
+            ));
+        }
+        let mut args = Map::new();
+        for (name, value) in &field.arguments {
+            let input = function
+                .inputs
+                .iter()
+                .find(|input| field_name(&input.name) == *name)
+                .ok_or_else(|| format!("Unknown argument \"{}\" of \"{}\"", name, field.name))?;
+            args.insert(input.name.clone(), to_json(value, &self.variables)?);
+        }
+        self.executor
+            .call_zome_function(instance, zome, &function.name, Value::Object(args))
+    }
+
+    fn subscription(&self, selection_set: &'a SelectionSet) -> Result<GraphqlOutcome, String> {
+        match self.fields(selection_set)?.as_slice() {
+            [field] if field.name == "signals" => {
+                let instance = match field.arguments.iter().find(|(name, _)| name == "instance") {
+                    None => None,
+                    Some((_, value)) => match to_json(value, &self.variables)? {
+                        Value::String(instance) => Some(instance),
+                        Value::Null => None,
+                        _ => return Err(String::from("Argument \"instance\" has to be a String")),
+                    },
+                };
+                Ok(GraphqlOutcome::Subscription {
+                    key: response_key(field),
+                    instance,
+                })
+            }
+            _ => Err(String::from(
+                "Subscriptions have to select the single field \"signals\"",
+            )),
+        }
+    }
+}
+
+struct Subscriber {
+    out: ws::Sender,
+    id: String,
+    key: String,
+    instance: Option<String>,
+}
+
+type Subscribers = Arc<Mutex<Vec<Subscriber>>>;
+
+/// A client connection speaking the graphql-ws protocol
+struct Connection {
+    out: ws::Sender,
+    handler: Arc<IoHandler>,
+    token: Option<String>,
+    subscribers: Subscribers,
+}
+
+impl Connection {
+    fn send(&self, message: Value) -> ws::Result<()> {
+        self.out.send(message.to_string())
+    }
+
+    fn start(&self, id: String, payload: &Value) -> ws::Result<()> {
+        let request: GraphqlRequest = match serde_json::from_value(payload.clone()) {
+            Ok(request) => request,
+            Err(error) => {
+                return self.send(json!({
+                    "type": "error",
+                    "id": id,
+                    "payload": { "message": error.to_string() },
+                }))
+            }
+        };
+        let executor = GraphqlExecutor::new(self.handler.clone(), self.token.clone());
+        let out = self.out.clone();
+        let subscribers = self.subscribers.clone();
+        // Zome calls can take a while and must not block the event loop of the socket
+        thread::Builder::new()
+            .name(format!("graphql_operation/{}", id))
+            .spawn(move || match executor.execute(&request) {
+                GraphqlOutcome::Response(payload) => {
+                    let _ =
+                        out.send(json!({"type": "data", "id": id, "payload": payload}).to_string());
+                    let _ = out.send(json!({"type": "complete", "id": id}).to_string());
+                }
+                GraphqlOutcome::Subscription { key, instance } => {
+                    subscribers.lock().unwrap().push(Subscriber {
+                        out,
+                        id,
+                        key,
+                        instance,
+                    })
+                }
+            })?;
+        Ok(())
+    }
+
+    fn stop(&self, id: String) -> ws::Result<()> {
+        let connection = self.out.connection_id();
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            subscriber.out.connection_id() != connection || subscriber.id != id
+        });
+        self.send(json!({"type": "complete", "id": id}))
+    }
+}
+
+impl ws::Handler for Connection {
+    fn on_request(&mut self, request: &ws::Request) -> ws::Result<ws::Response> {
+        let mut response = ws::Response::from_request(request)?;
+        if request.protocols()?.contains(&GRAPHQL_WS_PROTOCOL) {
+            response.set_protocol(GRAPHQL_WS_PROTOCOL);
+        }
+        Ok(response)
+    }
+
+    fn on_message(&mut self, message: ws::Message) -> ws::Result<()> {
+        let message: Value = match serde_json::from_str(&message.into_text()?) {
+            Ok(message) => message,
+            Err(error) => {
+                return self.send(json!({
+                    "type": "connection_error",
+                    "payload": { "message": error.to_string() },
+                }))
+            }
+        };
+        let id = message["id"].as_str().unwrap_or_default().to_string();
+        match message["type"].as_str() {
+            Some("connection_init") => {
+                self.token = message["payload"]["token"].as_str().map(String::from);
+                self.send(json!({"type": "connection_ack"}))
+            }
+            Some("start") => self.start(id, &message["payload"]),
+            Some("stop") => self.stop(id),
+            Some("connection_terminate") => self.out.close(ws::CloseCode::Normal),
+            _ => self.send(json!({
+                "type": "error",
+                "id": id,
+                "payload": { "message": "Unknown message type" },
+            })),
+        }
+    }
+
+    fn on_close(&mut self, _code: ws::CloseCode, _reason: &str) {
+        let connection = self.out.connection_id();
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.out.connection_id() != connection);
+    }
+}
+
+/// Delivers the instance signals the conductor broadcasts to the matching subscriptions,
+/// until the conductor drops the broadcaster of the interface
+fn dispatch_signals(signals: Receiver<String>, subscribers: Subscribers) {
+    for signal in signals.iter() {
+        let mut signal: Value = match serde_json::from_str(&signal) {
+            Ok(signal) => signal,
+            Err(_) => continue,
+        };
+        if signal["type"] != "InstanceSignal" {
+            continue;
+        }
+        let instance_id = signal["instance_id"].as_str().map(String::from);
+        let signal = signal["signal"].take();
+        for subscriber in subscribers.lock().unwrap().iter() {
+            if subscriber.instance.is_some() && subscriber.instance != instance_id {
+                continue;
+            }
+            let mut data = Map::new();
+            data.insert(subscriber.key.clone(), signal.clone());
+            let message = json!({
+                "type": "data",
+                "id": subscriber.id,
+                "payload": { "data": data },
+            });
+            if let Err(error) = subscriber.out.send(message.to_string()) {
+                warn!("GraphQL interface: could not send signal: {}", error);
+            }
+        }
+    }
+}
+
+pub struct GraphqlInterface {
+    port: u16,
+    bound_address: Option<SocketAddr>,
+}
+
+impl GraphqlInterface {
+    pub fn new(port: u16) -> Self {
+        GraphqlInterface {
+            port,
+            bound_address: None,
+        }
+    }
+
+    pub fn bound_address(&self) -> Option<SocketAddr> {
+        self.bound_address
+    }
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
+impl Interface for GraphqlInterface {
+    fn run(
+        &mut self,
+        handler: IoHandler,
+        kill_switch: Receiver<()>,
+    ) -> Result<(Broadcaster, thread::JoinHandle<()>), String> {
+        let url = format!("0.0.0.0:{}", self.port);
+        let handler = Arc::new(handler);
+        let subscribers: Subscribers = Arc::new(Mutex::new(Vec::new()));
+        let connection_subscribers = subscribers.clone();
+        let socket = ws::Builder::new()
+            .build(move |out| Connection {
+                out,
+                handler: handler.clone(),
+                token: None,
+                subscribers: connection_subscribers.clone(),
+            })
+            .and_then(|socket| socket.bind(url.as_str()))
+            .map_err(|e| e.to_string())?;
+        self.bound_address = socket.local_addr().ok();
+        let shutdown = socket.broadcaster();
+
+        let (signal_sender, signal_receiver) = unbounded();
+        thread::Builder::new()
+            .name(format!("graphql_interface/{}/signals", url))
+            .spawn(move || dispatch_signals(signal_receiver, subscribers))
+            .map_err(|e| e.to_string())?;
+        let server = thread::Builder::new()
+            .name(format!("graphql_interface/{}/socket", url))
+            .spawn(move || {
+                if let Err(error) = socket.run() {
+                    error!("GraphQL interface stopped: {}", error);
+                }
+            })
+            .map_err(|e| e.to_string())?;
+        let handle = thread::Builder::new()
+            .name(format!("graphql_interface/{}", url))
+            .spawn(move || {
+                let _ = kill_switch.recv();
+                let _ = shutdown.shutdown();
+                let _ = server.join();
+            })
+            .expect("Could not spawn thread for GraphQL interface");
+        Ok((Broadcaster::Channel(signal_sender), handle))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use graphql_parser::parse_schema;
+    use holochain_core_types::dna::fn_declarations::FnParameter;
+
+    fn function(name: &str, inputs: &[&str]) -> FnDeclaration {
+        FnDeclaration {
+            name: name.to_string(),
+            inputs: inputs
+                .iter()
+                .map(|input| FnParameter::new(*input, "String"))
+                .collect(),
+            outputs: Vec::new(),
+        }
+    }
+
+    fn test_functions() -> ZomeFunctions {
+        let mut zomes = BTreeMap::new();
+        zomes.insert(
+            String::from("blog"),
+            vec![
+                function("get_post", &["address"]),
+                function("list_posts", &[]),
+                function("create_post", &["content", "in_reply_to"]),
+            ],
+        );
+        let mut functions = BTreeMap::new();
+        functions.insert(String::from("test-instance"), zomes);
+        functions
+    }
+
+    /// Handler whose zome calls return their params as result
+    fn test_executor() -> GraphqlExecutor {
+        let mut handler = IoHandler::new();
+        handler.add_method("info/zome_functions", |_| Ok(json!(test_functions())));
+        handler.add_method("call", |params| {
+            let params: Value = serde_json::to_value(params).unwrap();
+            if params["function"] == "create_post" && params["args"]["content"] == "" {
+                return Ok(Value::String(json!({"Err": "Empty post"}).to_string()));
+            }
+            Ok(Value::String(json!({ "Ok": params }).to_string()))
+        });
+        GraphqlExecutor::new(Arc::new(handler), Some(String::from("test token")))
+    }
+
+    fn execute(query: &str, variables: Value) -> GraphqlOutcome {
+        test_executor().execute(&GraphqlRequest {
+            query: query.to_string(),
+            variables: serde_json::from_value(variables).unwrap(),
+            operation_name: None,
+        })
+    }
+
+    #[test]
+    fn reading_functions_are_queries() {
+        assert!(is_query_function("get_post"));
+        assert!(is_query_function("list"));
+        assert!(is_query_function("is_member"));
+        assert!(!is_query_function("create_post"));
+        assert!(!is_query_function("gettext"));
+        assert!(!is_query_function("island_update"));
+    }
+
+    #[test]
+    fn schema_is_generated_from_function_declarations() {
+        let sdl = GraphqlSchema::new(test_functions()).sdl();
+        assert!(parse_schema(&sdl).is_ok(), "invalid SDL:\n{}", sdl);
+        assert!(sdl.contains(
+            "type TestInstanceBlogQuery {\n  get_post(address: JSON): JSON\n  list_posts: JSON\n}"
+        ));
+        assert!(sdl.contains("type TestInstanceBlogMutation {\n  create_post(content: JSON, in_reply_to: JSON): JSON\n}"));
+        assert!(sdl.contains(
+            "type Query {\n  test_instance: TestInstanceQuery\n  _service: _Service!\n}"
+        ));
+        assert!(sdl.contains("type Mutation {\n  test_instance: TestInstanceMutation\n}"));
+        assert!(sdl.contains("signals(instance: String): JSON"));
+    }
+
+    #[test]
+    fn queries_and_mutations_call_zome_functions() {
+        let outcome = execute(
+            "query Post($address: JSON) {
+                test_instance { blog { post: get_post(address: $address) __typename } }
+            }",
+            json!({"address": "Qm123"}),
+        );
+        assert_eq!(
+            outcome,
+            GraphqlOutcome::Response(json!({"data": {"test_instance": {"blog": {
+                "post": {
+                    "instance_id": "test-instance",
+                    "zome": "blog",
+                    "function": "get_post",
+                    "args": {"address": "Qm123"},
+                    "token": "test token",
+                },
+                "__typename": "TestInstanceBlogQuery",
+            }}}}))
+        );
+
+        let outcome = execute(
+            "mutation { test_instance { blog {
+                ok: create_post(content: \"hi\", in_reply_to: null)
+                err: create_post(content: \"\")
+            } } }",
+            json!(null),
+        );
+        assert_eq!(
+            outcome,
+            GraphqlOutcome::Response(json!({
+                "data": {"test_instance": {"blog": {
+                    "ok": {
+                        "instance_id": "test-instance",
+                        "zome": "blog",
+                        "function": "create_post",
+                        "args": {"content": "hi", "in_reply_to": null},
+                        "token": "test token",
+                    },
+                    "err": null,
+                }}},
+                "errors": [{"message": "Empty post", "path": ["test_instance", "blog", "err"]}],
+            }))
+        );
+    }
+
+    #[test]
+    fn mutations_are_not_queries() {
+        let outcome = execute(
+            "{ test_instance { blog { create_post(content: \"hi\") } } }",
+            json!(null),
+        );
+        assert_eq!(
+            outcome,
+            GraphqlOutcome::Response(json!({
+                "data": {"test_instance": {"blog": {"create_post": null}}},
+                "errors": [{
+                    "message": "Zome \"blog\" has no query function \"create_post\"",
+                    "path": ["test_instance", "blog", "create_post"],
+                }],
+            }))
+        );
+    }
+
+    #[test]
+    fn sdl_and_subscriptions() {
+        match execute("{ _service { sdl } }", json!(null)) {
+            GraphqlOutcome::Response(response) => assert_eq!(
+                response["data"]["_service"]["sdl"],
+                json!(GraphqlSchema::new(test_functions()).sdl())
+            ),
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        assert_eq!(
+            execute(
+                "subscription { s: signals(instance: \"test-instance\") }",
+                json!(null)
+            ),
+            GraphqlOutcome::Subscription {
+                key: String::from("s"),
+                instance: Some(String::from("test-instance")),
+            }
+        );
+        assert_eq!(
+            execute("subscription { signals other: signals }", json!(null)),
+            GraphqlOutcome::Response(json!({
+                "data": null,
+                "errors": [{"message": "Subscriptions have to select the single field \"signals\""}],
+            }))
+        );
+    }
+}
@@ -0,0 +1,457 @@
+//! gRPC interface for backend services.
+//!
+//! Implements the `Conductor` service of `proto/conductor.proto` on top of the JSON-RPC
+//! handler of an interface, so it offers exactly the zome functions and admin methods the
+//! interface's configuration grants. Clients can be generated from the proto file for any
+//! language gRPC supports. The messages get generated by the build script; the service is
+//! defined by hand below since that only takes a few lines with the `grpc` crate and saves
+//! the build from needing `protoc`.
+use crate::{conductor::broadcaster::Broadcaster, interface::Interface};
+use crossbeam_channel::{unbounded, Receiver};
+use futures::{
+    sync::mpsc::{self, UnboundedSender},
+    Stream,
+};
+use grpc::{
+    protobuf::MarshallerProtobuf,
+    rt::{
+        GrpcStreaming, MethodDescriptor, MethodHandlerServerStreaming, MethodHandlerUnary,
+        ServerMethod, ServerServiceDefinition,
+    },
+    GrpcMessageError, RequestOptions, SingleResponse, StreamingResponse,
+};
+use holochain_locksmith::Mutex;
+use jsonrpc_core::IoHandler;
+use serde_json::Value;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    thread,
+};
+
+#[allow(
+    box_pointers,
+    clippy::all,
+    dead_code,
+    missing_docs,
+    non_camel_case_types,
+    non_snake_case,
+    non_upper_case_globals,
+    trivial_casts,
+    unknown_lints,
+    unused_imports,
+    unused_results
+)]
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/conductor.rs"));
+}
+
+use self::proto::{
+    MethodCallRequest, MethodCallResponse, Signal, SignalsRequest, ZomeCallRequest,
+    ZomeCallResponse,
+};
+
+const SERVICE_PATH: &str = "/holochain.conductor.Conductor";
+
+/// gRPC status codes
+const STATUS_INVALID_ARGUMENT: i32 = 3;
+const STATUS_UNIMPLEMENTED: i32 = 12;
+const STATUS_INTERNAL: i32 = 13;
+
+fn status_error(grpc_status: i32, grpc_message: String) -> grpc::Error {
+    grpc::Error::GrpcMessage(GrpcMessageError {
+        grpc_status,
+        grpc_message,
+    })
+}
+
+/// Maps a JSON-RPC error object to the gRPC status that fits its code
+fn rpc_error(error: &Value) -> grpc::Error {
+    let message = error["message"]
+        .as_str()
+        .unwrap_or("Internal error")
+        .to_string();
+    let status = match error["code"].as_i64() {
+        Some(-32602) => STATUS_INVALID_ARGUMENT,
+        Some(-32601) => STATUS_UNIMPLEMENTED,
+        _ => STATUS_INTERNAL,
+    };
+    status_error(status, message)
+}
+
+fn parse_json(field: &str, json: &str, default: Value) -> Result<Value, grpc::Error> {
+    if json.is_empty() {
+        return Ok(default);
+    }
+    serde_json::from_str(json).map_err(|e| {
+        status_error(
+            STATUS_INVALID_ARGUMENT,
+            format!("`{}` is not valid JSON: {}", field, e),
+        )
+    })
+}
+
+fn respond<T: Send + 'static>(result: Result<T, grpc::Error>) -> SingleResponse<T> {
+    match result {
+        Ok(response) => SingleResponse::completed(response),
+        Err(error) => SingleResponse::err(error),
+    }
+}
+
+struct Subscriber {
+    instance_id: Option<String>,
+    sender: UnboundedSender<Signal>,
+}
+
+type Subscribers = Arc<Mutex<Vec<Subscriber>>>;
+
+/// The `Conductor` service, making JSON-RPC calls to the handler of an interface
+pub struct GrpcService {
+    handler: Arc<IoHandler>,
+    subscribers: Subscribers,
+}
+
+impl GrpcService {
+    pub fn new(handler: IoHandler) -> Self {
+        GrpcService {
+            handler: Arc::new(handler),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn request(&self, method: &str, params: Value) -> Result<Value, grpc::Error> {
+        let request = json!({"jsonrpc": "2.0", "id": 0, "method": method, "params": params});
+        let response = self
+            .handler
+            .handle_request_sync(&request.to_string())
+            .ok_or_else(|| status_error(STATUS_INTERNAL, format!("No response to {}", method)))?;
+        let mut response: Value = serde_json::from_str(&response)
+            .map_err(|e| status_error(STATUS_INTERNAL, e.to_string()))?;
+        match response.get("error") {
+            Some(error) => Err(rpc_error(error)),
+            None => Ok(response["result"].take()),
+        }
+    }
+
+    pub fn call_zome_function(
+        &self,
+        _options: RequestOptions,
+        request: ZomeCallRequest,
+    ) -> SingleResponse<ZomeCallResponse> {
+        respond(self.zome_call(request))
+    }
+
+    fn zome_call(&self, request: ZomeCallRequest) -> Result<ZomeCallResponse, grpc::Error> {
+        let mut params = json!({
+            "instance_id": request.instance_id,
+            "zome": request.zome,
+            "function": request.function,
+            "args": parse_json("args_json", &request.args_json, json!({}))?,
+        });
+        if !request.token.is_empty() {
+            params["token"] = json!(request.token);
+        }
+        if !request.provenance_json.is_empty() {
+            params["provenance"] =
+                parse_json("provenance_json", &request.provenance_json, Value::Null)?;
+        }
+        // The "call" method returns the zome function's JSON as a string
+        let result_json = match self.request("call", params)? {
+            Value::String(result) => result,
+            result => result.to_string(),
+        };
+        Ok(ZomeCallResponse {
+            result_json,
+            ..Default::default()
+        })
+    }
+
+    pub fn call_method(
+        &self,
+        _options: RequestOptions,
+        request: MethodCallRequest,
+    ) -> SingleResponse<MethodCallResponse> {
+        respond(self.method_call(request))
+    }
+
+    fn method_call(&self, request: MethodCallRequest) -> Result<MethodCallResponse, grpc::Error> {
+        if request.method == "call" {
+            return Err(status_error(
+                STATUS_INVALID_ARGUMENT,
+                String::from("Zome functions are called with CallZomeFunction"),
+            ));
+        }
+        let params = parse_json("params_json", &request.params_json, json!({}))?;
+        let result = self.request(&request.method, params)?;
+        Ok(MethodCallResponse {
+            result_json: result.to_string(),
+            ..Default::default()
+        })
+    }
+
+    pub fn signals(
+        &self,
+        _options: RequestOptions,
+        request: SignalsRequest,
+    ) -> StreamingResponse<Signal> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.subscribers.lock().unwrap().push(Subscriber {
+            instance_id: Some(request.instance_id).filter(|id| !id.is_empty()),
+            sender,
+        });
+        StreamingResponse::no_metadata(
+            receiver.map_err(|()| grpc::Error::Other("signal stream closed")),
+        )
+    }
+
+    /// Streams the signals the conductor broadcasts to the matching subscribers, until the
+    /// conductor drops the broadcaster of the interface
+    fn dispatch_signals(&self, signals: Receiver<String>) {
+        for json in signals.iter() {
+            let wrapper: Value = match serde_json::from_str(&json) {
+                Ok(wrapper) => wrapper,
+                Err(_) => continue,
+            };
+            let signal = Signal {
+                signal_type: wrapper["type"].as_str().unwrap_or_default().to_string(),
+                instance_id: wrapper["instance_id"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                json,
+                ..Default::default()
+            };
+            // Subscribers whose client cancelled the call get dropped
+            self.subscribers
+                .lock()
+                .unwrap()
+                .retain(|subscriber| match &subscriber.instance_id {
+                    Some(id) if *id != signal.instance_id => true,
+                    _ => subscriber.sender.unbounded_send(signal.clone()).is_ok(),
+                });
+        }
+    }
+
+    fn definition(service: Arc<GrpcService>) -> ServerServiceDefinition {
+        ServerServiceDefinition::new(
+            SERVICE_PATH,
+            vec![
+                ServerMethod::new(
+                    Arc::new(MethodDescriptor {
+                        name: format!("{}/CallZomeFunction", SERVICE_PATH),
+                        streaming: GrpcStreaming::Unary,
+                        req_marshaller: Box::new(MarshallerProtobuf),
+                        resp_marshaller: Box::new(MarshallerProtobuf),
+                    }),
+                    {
+                        let service = service.clone();
+                        MethodHandlerUnary::new(move |o, p| service.call_zome_function(o, p))
+                    },
+                ),
+                ServerMethod::new(
+                    Arc::new(MethodDescriptor {
+                        name: format!("{}/CallMethod", SERVICE_PATH),
+                        streaming: GrpcStreaming::Unary,
+                        req_marshaller: Box::new(MarshallerProtobuf),
+                        resp_marshaller: Box::new(MarshallerProtobuf),
+                    }),
+                    {
+                        let service = service.clone();
+                        MethodHandlerUnary::new(move |o, p| service.call_method(o, p))
+                    },
+                ),
+                ServerMethod::new(
+                    Arc::new(MethodDescriptor {
+                        name: format!("{}/Signals", SERVICE_PATH),
+                        streaming: GrpcStreaming::ServerStreaming,
+                        req_marshaller: Box::new(MarshallerProtobuf),
+                        resp_marshaller: Box::new(MarshallerProtobuf),
+                    }),
+                    MethodHandlerServerStreaming::new(move |o, p| service.signals(o, p)),
+                ),
+            ],
+        )
+    }
+}
+
+pub struct GrpcInterface {
+    port: u16,
+    bound_address: Option<SocketAddr>,
+}
+
+impl GrpcInterface {
+    pub fn new(port: u16) -> Self {
+        GrpcInterface {
+            port,
+            bound_address: None,
+        }
+    }
+
+    pub fn bound_address(&self) -> Option<SocketAddr> {
+        self.bound_address
+    }
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
+impl Interface for GrpcInterface {
+    fn run(
+        &mut self,
+        handler: IoHandler,
+        kill_switch: Receiver<()>,
+    ) -> Result<(Broadcaster, thread::JoinHandle<()>), String> {
+        let url = format!("0.0.0.0:{}", self.port);
+        let service = Arc::new(GrpcService::new(handler));
+        let mut builder = grpc::ServerBuilder::new_plain();
+        builder
+            .http
+            .set_addr(url.as_str())
+            .map_err(|e| e.to_string())?;
+        builder.add_service(GrpcService::definition(service.clone()));
+        let server = builder.build().map_err(|e| e.to_string())?;
+        self.bound_address = Some(SocketAddr::from((Ipv4Addr::UNSPECIFIED, self.port)));
+
+        let (signal_sender, signal_receiver) = unbounded();
+        thread::Builder::new()
+            .name(format!("grpc_interface/{}/signals", url))
+            .spawn(move || service.dispatch_signals(signal_receiver))
+            .map_err(|e| e.to_string())?;
+        let handle = thread::Builder::new()
+            .name(format!("grpc_interface/{}", url))
+            .spawn(move || {
+                let _ = kill_switch.recv();
+                // Shuts the server down
+                drop(server);
+            })
+            .expect("Could not spawn thread for gRPC interface");
+        Ok((Broadcaster::Channel(signal_sender), handle))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn test_service() -> GrpcService {
+        let mut handler = IoHandler::new();
+        handler.add_method("call", |params| {
+            let params: Value = serde_json::to_value(params).unwrap();
+            Ok(Value::String(json!({ "Ok": params }).to_string()))
+        });
+        handler.add_method("info/instances", |_| Ok(json!([{"id": "test-instance"}])));
+        GrpcService::new(handler)
+    }
+
+    #[test]
+    fn zome_calls_go_to_the_call_method() {
+        let service = test_service();
+        let response = service
+            .call_zome_function(
+                RequestOptions::new(),
+                ZomeCallRequest {
+                    instance_id: String::from("test-instance"),
+                    zome: String::from("blog"),
+                    function: String::from("get_post"),
+                    args_json: String::from(r#"{"address":"Qm123"}"#),
+                    ..Default::default()
+                },
+            )
+            .wait_drop_metadata()
+            .unwrap();
+        let result: Value = serde_json::from_str(&response.result_json).unwrap();
+        assert_eq!(
+            result,
+            json!({"Ok": {
+                "instance_id": "test-instance",
+                "zome": "blog",
+                "function": "get_post",
+                "args": {"address": "Qm123"},
+            }})
+        );
+
+        let error = service
+            .call_zome_function(
+                RequestOptions::new(),
+                ZomeCallRequest {
+                    args_json: String::from("{"),
+                    ..Default::default()
+                },
+            )
+            .wait_drop_metadata()
+            .unwrap_err();
+        match error {
+            grpc::Error::GrpcMessage(error) => {
+                assert_eq!(error.grpc_status, STATUS_INVALID_ARGUMENT)
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn methods_are_called_with_json_params() {
+        let service = test_service();
+        let response = service
+            .call_method(
+                RequestOptions::new(),
+                MethodCallRequest {
+                    method: String::from("info/instances"),
+                    ..Default::default()
+                },
+            )
+            .wait_drop_metadata()
+            .unwrap();
+        assert_eq!(response.result_json, r#"[{"id":"test-instance"}]"#);
+
+        let error = service
+            .call_method(
+                RequestOptions::new(),
+                MethodCallRequest {
+                    method: String::from("admin/instance/list"),
+                    ..Default::default()
+                },
+            )
+            .wait_drop_metadata()
+            .unwrap_err();
+        match error {
+            grpc::Error::GrpcMessage(error) => assert_eq!(error.grpc_status, STATUS_UNIMPLEMENTED),
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn signals_are_streamed_to_matching_subscribers() {
+        let service = Arc::new(test_service());
+        let all = service.signals(RequestOptions::new(), SignalsRequest::default());
+        let other = service.signals(
+            RequestOptions::new(),
+            SignalsRequest {
+                instance_id: String::from("other-instance"),
+                ..Default::default()
+            },
+        );
+        let (sender, receiver) = unbounded();
+        let dispatcher = {
+            let service = service.clone();
+            thread::spawn(move || service.dispatch_signals(receiver))
+        };
+        let json = json!({
+            "type": "InstanceSignal",
+            "instance_id": "test-instance",
+            "signal": {"User": {"name": "test", "arguments": "{}"}},
+        })
+        .to_string();
+        sender.send(json.clone()).unwrap();
+        drop(sender);
+        dispatcher.join().unwrap();
+        drop(service);
+
+        let signals = all
+            .wait_drop_metadata()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].signal_type, "InstanceSignal");
+        assert_eq!(signals[0].instance_id, "test-instance");
+        assert_eq!(signals[0].json, json);
+        assert_eq!(other.wait_drop_metadata().count(), 0);
+    }
+}
@@ -1,12 +1,121 @@
-use crate::{conductor::broadcaster::Broadcaster, interface::Interface};
-use crossbeam_channel::Receiver;
-use jsonrpc_core::IoHandler;
-use jsonrpc_http_server::ServerBuilder;
-use std::{net::SocketAddr, thread};
+use crate::{
+    conductor::broadcaster::Broadcaster,
+    interface::Interface,
+    interface_impls::{security::InterfaceSecurity, websocket::SignalFilter},
+};
+use crossbeam_channel::{unbounded, Receiver};
+use holochain_locksmith::Mutex;
+use jsonrpc_core::{
+    futures::{
+        future,
+        sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+        Stream,
+    },
+    IoHandler, Value,
+};
+use jsonrpc_http_server::{
+    hyper::{
+        header::{
+            ACCESS_CONTROL_ALLOW_ORIGIN, AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE,
+            WWW_AUTHENTICATE,
+        },
+        Body, Method, Request, Response, StatusCode,
+    },
+    RequestMiddlewareAction, ServerBuilder,
+};
+use std::{net::SocketAddr, sync::Arc, thread};
 use tokio::runtime::Runtime;
 
+/// Path of the server-sent events stream of signals. Query parameters `instance_id`, `zome`
+/// and `name` filter the signals like the fields of `signals/subscribe` on websocket
+/// interfaces do.
+pub const SIGNALS_PATH: &str = "/signals";
+
+fn filter_from_query(query: Option<&str>) -> SignalFilter {
+    let mut filter = SignalFilter::default();
+    for (key, value) in url::form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        let value = Some(value.into_owned());
+        match key.as_ref() {
+            "instance_id" => filter.instance_id = value,
+            "zome" => filter.zome = value,
+            "name" => filter.name = value,
+            _ => (),
+        }
+    }
+    filter
+}
+
+struct SignalStream {
+    filter: SignalFilter,
+    sender: UnboundedSender<String>,
+}
+
+/// Open server-sent events streams of signals
+#[derive(Default)]
+struct SignalStreams {
+    streams: Mutex<Vec<SignalStream>>,
+}
+
+impl SignalStreams {
+    fn open(&self, filter: SignalFilter) -> UnboundedReceiver<String> {
+        let (sender, receiver) = mpsc::unbounded();
+        // A comment, so that clients see the stream open before the first signal
+        let _ = sender.unbounded_send(String::from(": signals\n\n"));
+        self.streams
+            .lock()
+            .unwrap()
+            .push(SignalStream { filter, sender });
+        receiver
+    }
+
+    /// Sends the signal as an event named after its type to the matching streams. Streams
+    /// whose client went away get dropped.
+    fn send(&self, signal: &str) {
+        let value: Value = match serde_json::from_str(signal) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        let event = format!(
+            "event: {}\ndata: {}\n\n",
+            value["type"].as_str().unwrap_or("signal"),
+            signal
+        );
+        self.streams.lock().unwrap().retain(|stream| {
+            !stream.filter.matches(&value) || stream.sender.unbounded_send(event.clone()).is_ok()
+        });
+    }
+
+    fn dispatch(&self, signals: Receiver<String>) {
+        for signal in signals.iter() {
+            self.send(&signal);
+        }
+    }
+
+    fn respond(&self, request: &Request<Body>) -> Response<Body> {
+        let events = self
+            .open(filter_from_query(request.uri().query()))
+            .map_err(|()| "signal stream closed");
+        Response::builder()
+            .header(CONTENT_TYPE, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache")
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .body(Body::wrap_stream(events))
+            .expect("Static headers are valid")
+    }
+}
+
+fn unauthorized() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(WWW_AUTHENTICATE, "Bearer")
+        .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .body(Body::empty())
+        .expect("Static headers are valid")
+}
+
 pub struct HttpInterface {
     port: u16,
+    security: InterfaceSecurity,
     bound_address: Option<SocketAddr>,
 }
 
@@ -14,10 +123,17 @@ impl HttpInterface {
     pub fn new(port: u16) -> Self {
         HttpInterface {
             port,
+            security: InterfaceSecurity::default(),
             bound_address: None,
         }
     }
 
+    /// Only handle requests with a valid token, and serve over TLS if configured.
+    pub fn with_security(mut self, security: InterfaceSecurity) -> Self {
+        self.security = security;
+        self
+    }
+
     pub fn bound_address(&self) -> Option<SocketAddr> {
         self.bound_address
     }
@@ -30,22 +146,105 @@ impl Interface for HttpInterface {
         handler: IoHandler,
         kill_switch: Receiver<()>,
     ) -> Result<(Broadcaster, thread::JoinHandle<()>), String> {
-        let url = format!("0.0.0.0:{}", self.port);
+        let url = self.security.server_address(self.port);
         let runtime = Runtime::new().map_err(|e| e.to_string())?;
+        let signal_streams = Arc::new(SignalStreams::default());
+        let streams = signal_streams.clone();
+        let security = self.security.clone();
         let server = ServerBuilder::new(handler)
+            .request_middleware(move |request: Request<Body>| {
+                let authorization = request
+                    .headers()
+                    .get(AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok());
+                // CORS preflight requests can't carry credentials and don't call anything
+                if request.method() != Method::OPTIONS
+                    && !security.authorize(authorization, request.uri().query())
+                {
+                    RequestMiddlewareAction::Respond {
+                        should_validate_hosts: true,
+                        response: Box::new(future::ok(unauthorized())),
+                    }
+                } else if request.method() == Method::GET && request.uri().path() == SIGNALS_PATH {
+                    RequestMiddlewareAction::Respond {
+                        should_validate_hosts: true,
+                        response: Box::new(future::ok(streams.respond(&request))),
+                    }
+                } else {
+                    RequestMiddlewareAction::Proceed {
+                        should_continue_on_invalid_cors: false,
+                        request,
+                    }
+                }
+            })
             .event_loop_executor(runtime.executor())
             .start_http(&url.parse().expect("Invalid URL!"))
             .map_err(|e| e.to_string())?;
-        self.bound_address = Some(*server.address());
-        let broadcaster = Broadcaster::Noop;
+        let tls_terminator = self.security.terminate_tls(self.port, *server.address())?;
+        self.bound_address = Some(
+            tls_terminator
+                .as_ref()
+                .map(|terminator| terminator.address())
+                .unwrap_or(*server.address()),
+        );
+        let (signal_sender, signal_receiver) = unbounded();
+        thread::Builder::new()
+            .name(format!("http_interface/{}/signals", url))
+            .spawn(move || signal_streams.dispatch(signal_receiver))
+            .map_err(|e| e.to_string())?;
+        let broadcaster = Broadcaster::Channel(signal_sender);
         let handle = thread::Builder::new()
             .name(format!("http_interface/{}", url))
             .spawn(move || {
                 let _ = server; // move `server` into this thread
                 let _ = runtime; // move tokio runtime for RPC futures into this thread
                 let _ = kill_switch.recv();
+                drop(tls_terminator);
             })
             .expect("Could not spawn thread for HTTP interface");
         Ok((broadcaster, handle))
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn query_parameters_filter_signals() {
+        assert_eq!(filter_from_query(None), SignalFilter::default());
+        assert_eq!(
+            filter_from_query(Some("instance_id=test-instance&name=new%20post&other=1")),
+            SignalFilter {
+                instance_id: Some(String::from("test-instance")),
+                zome: None,
+                name: Some(String::from("new post")),
+            }
+        );
+    }
+
+    #[test]
+    fn signals_are_streamed_as_events() {
+        let streams = SignalStreams::default();
+        let all = streams.open(SignalFilter::default());
+        let other = streams.open(filter_from_query(Some("instance_id=other-instance")));
+        let signal = json!({
+            "type": "InstanceSignal",
+            "instance_id": "test-instance",
+            "signal": {"signal_type": "User", "name": "test", "arguments": "{}", "zome": "z"},
+        })
+        .to_string();
+        streams.send(&signal);
+        drop(streams);
+
+        let events: Vec<String> = all.wait().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            events,
+            vec![
+                String::from(": signals\n\n"),
+                format!("event: InstanceSignal\ndata: {}\n\n", signal),
+            ]
+        );
+        assert_eq!(other.wait().count(), 1);
+    }
+}
@@ -1,4 +1,8 @@
+pub mod graphql;
+pub mod grpc;
 pub mod http;
+pub mod remote_admin;
+pub mod security;
 pub mod websocket;
 
-pub use self::{http::*, websocket::*};
+pub use self::{graphql::*, grpc::*, http::*, remote_admin::*, websocket::*};
@@ -0,0 +1,591 @@
+//! Admin interface for fleet setups in which one conductor administers others over the network.
+//!
+//! Both ends authenticate with the signing key of one of their agents: the server only accepts
+//! clients whose public address is listed in the interface's `authorized_keys` and the client
+//! only talks to the server key it expects. During the handshake each side sends an ephemeral
+//! key exchange key plus a random challenge and signs the peer's challenge together with both
+//! ephemeral keys, which binds the session to the two identities.
+//! All JSON-RPC traffic after the handshake is encrypted with the derived session keys. Nonces
+//! are message counters, so replayed or reordered frames fail to decrypt.
+use crate::{
+    conductor::broadcaster::Broadcaster, interface::Interface, signing_backend::SigningBackend,
+};
+use crossbeam_channel::{Receiver, TryRecvError};
+use holochain_core_types::{
+    agent::Base32,
+    error::{HcResult, HolochainError},
+    signature::{Provenance, Signature},
+};
+use holochain_dpki::audit::verify_audited;
+use jsonrpc_core::IoHandler;
+use lib3h_sodium::{aead, kx, secbuf::SecBuf};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Frames bigger than this get refused so that a peer can't make us allocate arbitrary memory.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+/// Handshake messages are small, and before the handshake we don't know who we talk to.
+const MAX_HANDSHAKE_FRAME_SIZE: usize = 4 * 1024;
+/// Clients that don't complete the handshake within this time get disconnected.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Connections beyond this many get closed right away.
+const MAX_CONNECTIONS: usize = 32;
+const CHALLENGE_SIZE: usize = 32;
+const KX_SEED_SIZE: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+struct Hello {
+    public_address: Base32,
+    kx_public_key: String,
+    challenge: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Proof {
+    signature: Signature,
+}
+
+fn write_frame(stream: &mut TcpStream, payload: &[u8]) -> HcResult<()> {
+    stream.write_all(&(payload.len() as u32).to_be_bytes())?;
+    stream.write_all(payload)?;
+    Ok(())
+}
+
+fn read_frame(stream: &mut TcpStream, max_size: usize) -> HcResult<Vec<u8>> {
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length) as usize;
+    if length > max_size {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Remote admin frame of {} bytes is too big",
+            length
+        )));
+    }
+    let mut payload = vec![0u8; length];
+    stream.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+fn send_json<T: Serialize>(stream: &mut TcpStream, value: &T) -> HcResult<()> {
+    write_frame(stream, serde_json::to_string(value)?.as_bytes())
+}
+
+fn receive_json<T: DeserializeOwned>(stream: &mut TcpStream) -> HcResult<T> {
+    let frame = read_frame(stream, MAX_HANDSHAKE_FRAME_SIZE)?;
+    Ok(serde_json::from_slice(&frame)?)
+}
+
+fn random_challenge() -> String {
+    let mut challenge = SecBuf::with_insecure(CHALLENGE_SIZE);
+    challenge.randomize();
+    let challenge = challenge.read_lock();
+    base64::encode(&**challenge)
+}
+
+/// Creates the ephemeral key exchange key pair for one session.
+/// Returns the public key, the secret key and the base64 encoded public key.
+fn ephemeral_keypair() -> HcResult<(SecBuf, SecBuf, String)> {
+    let mut seed = SecBuf::with_secure(KX_SEED_SIZE);
+    seed.randomize();
+    let mut public_key = SecBuf::with_insecure(kx::PUBLICKEYBYTES);
+    let mut secret_key = SecBuf::with_secure(kx::SECRETKEYBYTES);
+    kx::seed_keypair(&mut public_key, &mut secret_key, &mut seed)?;
+    let encoded = base64::encode(&**public_key.read_lock());
+    Ok((public_key, secret_key, encoded))
+}
+
+fn decode_kx_public_key(encoded: &str) -> HcResult<SecBuf> {
+    let bytes = base64::decode(encoded).map_err(|_| {
+        HolochainError::ErrorGeneric("Remote admin peer sent invalid key".to_string())
+    })?;
+    if bytes.len() != kx::PUBLICKEYBYTES {
+        return Err(HolochainError::ErrorGeneric(
+            "Remote admin peer sent invalid key".to_string(),
+        ));
+    }
+    let mut public_key = SecBuf::with_insecure(kx::PUBLICKEYBYTES);
+    public_key.from_array(&bytes)?;
+    Ok(public_key)
+}
+
+/// What each side signs: the challenge of the peer plus both ephemeral keys.
+fn proof_payload(
+    peer_challenge: &str,
+    own_kx_public_key: &str,
+    peer_kx_public_key: &str,
+) -> String {
+    format!(
+        "remote_admin:{}:{}:{}",
+        peer_challenge, own_kx_public_key, peer_kx_public_key
+    )
+}
+
+fn prove_identity(
+    identity: &dyn SigningBackend,
+    peer_hello: &Hello,
+    own_kx_public_key: &str,
+) -> HcResult<Proof> {
+    let payload = proof_payload(
+        &peer_hello.challenge,
+        own_kx_public_key,
+        &peer_hello.kx_public_key,
+    );
+    Ok(Proof {
+        signature: identity.sign(&payload)?,
+    })
+}
+
+fn verify_identity(
+    peer_hello: &Hello,
+    peer_proof: &Proof,
+    own_challenge: &str,
+    own_kx_public_key: &str,
+) -> HcResult<()> {
+    let payload = proof_payload(own_challenge, &peer_hello.kx_public_key, own_kx_public_key);
+    let provenance = Provenance::new(
+        peer_hello.public_address.clone().into(),
+        peer_proof.signature.clone(),
+    );
+    if verify_audited(
+        &provenance,
+        payload,
+        "remote_admin_handshake",
+        "remote admin connection",
+    )? {
+        Ok(())
+    } else {
+        Err(HolochainError::ErrorGeneric(format!(
+            "Remote admin peer {} failed to prove its identity",
+            peer_hello.public_address
+        )))
+    }
+}
+
+fn nonce(counter: u64) -> HcResult<SecBuf> {
+    let mut bytes = vec![0u8; aead::NONCEBYTES];
+    bytes[..8].copy_from_slice(&counter.to_le_bytes());
+    let mut nonce = SecBuf::with_insecure(aead::NONCEBYTES);
+    nonce.from_array(&bytes)?;
+    Ok(nonce)
+}
+
+/// An authenticated connection that encrypts every message with the session keys.
+struct SecureChannel {
+    stream: TcpStream,
+    rx_key: SecBuf,
+    tx_key: SecBuf,
+    rx_counter: u64,
+    tx_counter: u64,
+}
+
+impl SecureChannel {
+    fn new(stream: TcpStream, rx_key: SecBuf, tx_key: SecBuf) -> Self {
+        SecureChannel {
+            stream,
+            rx_key,
+            tx_key,
+            rx_counter: 0,
+            tx_counter: 0,
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> HcResult<()> {
+        let mut plain = SecBuf::with_insecure(message.len());
+        plain.from_array(message)?;
+        let mut cipher = SecBuf::with_insecure(message.len() + aead::ABYTES);
+        aead::enc(
+            &mut plain,
+            &mut self.tx_key,
+            None,
+            &mut nonce(self.tx_counter)?,
+            &mut cipher,
+        )?;
+        self.tx_counter += 1;
+        let cipher = cipher.read_lock();
+        write_frame(&mut self.stream, &**cipher)
+    }
+
+    fn receive(&mut self) -> HcResult<Vec<u8>> {
+        let frame = read_frame(&mut self.stream, MAX_FRAME_SIZE)?;
+        if frame.len() <= aead::ABYTES {
+            return Err(HolochainError::ErrorGeneric(
+                "Remote admin frame too short".to_string(),
+            ));
+        }
+        let mut cipher = SecBuf::with_insecure(frame.len());
+        cipher.from_array(&frame)?;
+        let mut plain = SecBuf::with_insecure(frame.len() - aead::ABYTES);
+        aead::dec(
+            &mut plain,
+            &mut self.rx_key,
+            None,
+            &mut nonce(self.rx_counter)?,
+            &mut cipher,
+        )
+        .map_err(|_| {
+            HolochainError::ErrorGeneric("Could not decrypt remote admin message".to_string())
+        })?;
+        self.rx_counter += 1;
+        let plain = plain.read_lock();
+        Ok(plain.to_vec())
+    }
+}
+
+fn session_key_buffers() -> (SecBuf, SecBuf) {
+    (
+        SecBuf::with_secure(kx::SESSIONKEYBYTES),
+        SecBuf::with_secure(kx::SESSIONKEYBYTES),
+    )
+}
+
+fn accept_handshake(
+    mut stream: TcpStream,
+    identity: &dyn SigningBackend,
+    authorized_keys: &[Base32],
+) -> HcResult<SecureChannel> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let client_hello: Hello = receive_json(&mut stream)?;
+    if !authorized_keys.contains(&client_hello.public_address) {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Remote admin client {} is not authorized",
+            client_hello.public_address
+        )));
+    }
+
+    let (mut public_key, mut secret_key, kx_public_key) = ephemeral_keypair()?;
+    let challenge = random_challenge();
+    send_json(
+        &mut stream,
+        &Hello {
+            public_address: identity.public_address(),
+            kx_public_key: kx_public_key.clone(),
+            challenge: challenge.clone(),
+        },
+    )?;
+    send_json(
+        &mut stream,
+        &prove_identity(identity, &client_hello, &kx_public_key)?,
+    )?;
+
+    let client_proof: Proof = receive_json(&mut stream)?;
+    verify_identity(&client_hello, &client_proof, &challenge, &kx_public_key)?;
+
+    let mut client_public_key = decode_kx_public_key(&client_hello.kx_public_key)?;
+    let (mut rx_key, mut tx_key) = session_key_buffers();
+    kx::server_session(
+        &mut public_key,
+        &mut secret_key,
+        &mut client_public_key,
+        &mut rx_key,
+        &mut tx_key,
+    )?;
+    // Authenticated clients may stay connected as long as they want
+    stream.set_read_timeout(None)?;
+    Ok(SecureChannel::new(stream, rx_key, tx_key))
+}
+
+/// Counts a connection as open until it gets dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn take(open_connections: &Arc<AtomicUsize>) -> Option<Self> {
+        if open_connections.fetch_add(1, Ordering::SeqCst) < MAX_CONNECTIONS {
+            Some(ConnectionSlot(open_connections.clone()))
+        } else {
+            open_connections.fetch_sub(1, Ordering::SeqCst);
+            None
+        }
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn serve_connection(
+    stream: TcpStream,
+    handler: Arc<IoHandler>,
+    identity: Arc<dyn SigningBackend>,
+    authorized_keys: Arc<Vec<Base32>>,
+) {
+    let mut channel = match accept_handshake(stream, &*identity, &authorized_keys) {
+        Ok(channel) => channel,
+        Err(err) => {
+            warn!("Refused remote admin connection: {}", err);
+            return;
+        }
+    };
+    // Runs until the client disconnects or sends something we can't decrypt.
+    while let Ok(request) = channel.receive() {
+        let request = String::from_utf8_lossy(&request).to_string();
+        if let Some(response) = handler.handle_request_sync(&request) {
+            if channel.send(response.as_bytes()).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+pub struct RemoteAdminInterface {
+    port: u16,
+    identity: Arc<dyn SigningBackend>,
+    authorized_keys: Vec<Base32>,
+    bound_address: Option<SocketAddr>,
+}
+
+impl RemoteAdminInterface {
+    pub fn new(port: u16, identity: Arc<dyn SigningBackend>, authorized_keys: Vec<Base32>) -> Self {
+        RemoteAdminInterface {
+            port,
+            identity,
+            authorized_keys,
+            bound_address: None,
+        }
+    }
+
+    pub fn bound_address(&self) -> Option<SocketAddr> {
+        self.bound_address
+    }
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
+impl Interface for RemoteAdminInterface {
+    fn run(
+        &mut self,
+        handler: IoHandler,
+        kill_switch: Receiver<()>,
+    ) -> Result<(Broadcaster, thread::JoinHandle<()>), String> {
+        let url = format!("0.0.0.0:{}", self.port);
+        let listener = TcpListener::bind(&url).map_err(|e| e.to_string())?;
+        // Non-blocking so that the accept loop gets to check the kill switch.
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        self.bound_address = Some(listener.local_addr().map_err(|e| e.to_string())?);
+        let handler = Arc::new(handler);
+        let identity = self.identity.clone();
+        let authorized_keys = Arc::new(self.authorized_keys.clone());
+        let open_connections = Arc::new(AtomicUsize::new(0));
+        let handle = thread::Builder::new()
+            .name(format!("remote_admin_interface/{}", url))
+            .spawn(move || loop {
+                match kill_switch.try_recv() {
+                    Err(TryRecvError::Empty) => (),
+                    _ => return,
+                }
+                match listener.accept() {
+                    Ok((stream, peer)) => {
+                        let slot = match ConnectionSlot::take(&open_connections) {
+                            Some(slot) => slot,
+                            None => {
+                                warn!(
+                                    "Refused remote admin connection from {}: too many connections",
+                                    peer
+                                );
+                                continue;
+                            }
+                        };
+                        if stream.set_nonblocking(false).is_err() {
+                            continue;
+                        }
+                        let handler = handler.clone();
+                        let identity = identity.clone();
+                        let authorized_keys = authorized_keys.clone();
+                        let _ = thread::Builder::new()
+                            .name("remote_admin_connection".to_string())
+                            .spawn(move || {
+                                serve_connection(stream, handler, identity, authorized_keys);
+                                drop(slot);
+                            });
+                    }
+                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(100))
+                    }
+                    Err(err) => {
+                        error!(
+                            "Remote admin interface stopped accepting connections: {}",
+                            err
+                        );
+                        return;
+                    }
+                }
+            })
+            .expect("Could not spawn thread for remote admin interface");
+        Ok((Broadcaster::Noop, handle))
+    }
+}
+
+/// Client side of the remote admin interface, used by the administering conductor.
+pub struct RemoteAdminClient {
+    channel: SecureChannel,
+    next_id: u64,
+}
+
+impl RemoteAdminClient {
+    /// Connects to the remote admin interface at `address` (`host:port`), authenticating with
+    /// `identity`. Fails if the server can't prove that it holds the key for
+    /// `server_public_address` or if it does not accept our key.
+    pub fn connect(
+        address: &str,
+        identity: Arc<dyn SigningBackend>,
+        server_public_address: &Base32,
+    ) -> HcResult<Self> {
+        let mut stream = TcpStream::connect(address)?;
+        let (mut public_key, mut secret_key, kx_public_key) = ephemeral_keypair()?;
+        let challenge = random_challenge();
+        send_json(
+            &mut stream,
+            &Hello {
+                public_address: identity.public_address(),
+                kx_public_key: kx_public_key.clone(),
+                challenge: challenge.clone(),
+            },
+        )?;
+
+        let server_hello: Hello = receive_json(&mut stream)?;
+        if server_hello.public_address != *server_public_address {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Remote admin server identified as {}, expected {}",
+                server_hello.public_address, server_public_address
+            )));
+        }
+        let server_proof: Proof = receive_json(&mut stream)?;
+        verify_identity(&server_hello, &server_proof, &challenge, &kx_public_key)?;
+        send_json(
+            &mut stream,
+            &prove_identity(&*identity, &server_hello, &kx_public_key)?,
+        )?;
+
+        let mut server_public_key = decode_kx_public_key(&server_hello.kx_public_key)?;
+        let (mut rx_key, mut tx_key) = session_key_buffers();
+        kx::client_session(
+            &mut public_key,
+            &mut secret_key,
+            &mut server_public_key,
+            &mut rx_key,
+            &mut tx_key,
+        )?;
+        Ok(RemoteAdminClient {
+            channel: SecureChannel::new(stream, rx_key, tx_key),
+            next_id: 0,
+        })
+    }
+
+    /// Calls a JSON-RPC method of the remote conductor and returns its result.
+    pub fn call(&mut self, method: &str, params: serde_json::Value) -> HcResult<serde_json::Value> {
+        self.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        });
+        self.channel.send(request.to_string().as_bytes())?;
+        let response: serde_json::Value = serde_json::from_slice(&self.channel.receive()?)?;
+        if let Some(error) = response.get("error") {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Remote admin call {} failed: {}",
+                method, error
+            )));
+        }
+        Ok(response
+            .get("result")
+            .cloned()
+            .unwrap_or(serde_json::Value::Null))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        key_loaders::test_keystore, keystore::PRIMARY_KEYBUNDLE_ID,
+        signing_backend::KeyBundleBackend,
+    };
+    use crossbeam_channel::unbounded;
+    use holochain_locksmith::Mutex;
+
+    fn test_identity(name: &str) -> Arc<dyn SigningBackend> {
+        let keybundle = test_keystore(name)
+            .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+            .unwrap();
+        Arc::new(KeyBundleBackend::new(Arc::new(Mutex::new(keybundle))))
+    }
+
+    fn start_server(
+        identity: Arc<dyn SigningBackend>,
+        authorized_keys: Vec<Base32>,
+    ) -> (String, crossbeam_channel::Sender<()>) {
+        let mut handler = IoHandler::new();
+        handler.add_method("ping", |_| Ok(serde_json::Value::String("pong".into())));
+        let mut interface = RemoteAdminInterface::new(0, identity, authorized_keys);
+        let (kill_switch_tx, kill_switch_rx) = unbounded();
+        interface.run(handler, kill_switch_rx).unwrap();
+        let address = format!("127.0.0.1:{}", interface.bound_address().unwrap().port());
+        (address, kill_switch_tx)
+    }
+
+    #[test]
+    fn authorized_client_can_call_admin_methods() {
+        let server = test_identity("server");
+        let client = test_identity("admin");
+        let (address, _kill_switch) = start_server(server.clone(), vec![client.public_address()]);
+
+        let mut connection =
+            RemoteAdminClient::connect(&address, client, &server.public_address()).unwrap();
+        assert_eq!(
+            connection.call("ping", json!({})).unwrap(),
+            serde_json::Value::String("pong".into())
+        );
+        assert!(connection.call("unknown", json!({})).is_err());
+    }
+
+    #[test]
+    fn unauthorized_client_and_wrong_server_get_refused() {
+        let server = test_identity("server");
+        let client = test_identity("admin");
+        let intruder = test_identity("intruder");
+        let (address, _kill_switch) = start_server(server.clone(), vec![client.public_address()]);
+
+        assert!(
+            RemoteAdminClient::connect(&address, intruder.clone(), &server.public_address())
+                .and_then(|mut connection| connection.call("ping", json!({})))
+                .is_err()
+        );
+        assert!(RemoteAdminClient::connect(&address, client, &intruder.public_address()).is_err());
+    }
+
+    #[test]
+    fn big_handshake_frames_get_refused() {
+        let server = test_identity("server");
+        let (address, _kill_switch) = start_server(server, Vec::new());
+
+        let mut stream = TcpStream::connect(&address).unwrap();
+        let length = (MAX_HANDSHAKE_FRAME_SIZE as u32 + 1).to_be_bytes();
+        stream.write_all(&length).unwrap();
+        // The server hangs up instead of waiting for the rest of the frame
+        let mut response = Vec::new();
+        assert_eq!(stream.read_to_end(&mut response).unwrap_or(0), 0);
+    }
+
+    #[test]
+    fn connections_beyond_the_limit_get_closed() {
+        let open_connections = Arc::new(AtomicUsize::new(0));
+        let slots: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|_| ConnectionSlot::take(&open_connections).unwrap())
+            .collect();
+        assert!(ConnectionSlot::take(&open_connections).is_none());
+        drop(slots);
+        assert_eq!(open_connections.load(Ordering::SeqCst), 0);
+        assert!(ConnectionSlot::take(&open_connections).is_some());
+    }
+}
@@ -0,0 +1,407 @@
+//! Authentication and TLS of websocket and HTTP interfaces, so that they can be exposed
+//! beyond localhost without a reverse proxy in front of them.
+//!
+//! Tokens get checked by the servers' request middlewares before a connection gets upgraded
+//! to a websocket, respectively before an HTTP request gets handled.
+//! The JSON-RPC servers can't speak TLS themselves, so a secured interface runs its server on
+//! a loopback port and a [TlsTerminator] accepts the TLS connections on the interface's port,
+//! checks client certificates and forwards the decrypted streams to the server.
+//! Each connection gets a thread per direction that blocks on its socket until data arrives.
+use crate::config::{hash_token, InterfaceAuthConfiguration, TlsConfiguration};
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslStream, SslVerifyMode};
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How long the terminator waits for new connections before it checks whether it got stopped
+const POLL_INTERVAL_MS: u64 = 10;
+/// How long the threads of a connection block on a socket before they check whether the
+/// terminator got stopped
+const STOP_CHECK_INTERVAL_MS: u64 = 1000;
+const BUFFER_SIZE: usize = 16 * 1024;
+/// Clients that don't finish the TLS handshake within this time get disconnected
+const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+/// Connections beyond this number get closed right away
+const MAX_CONNECTIONS: usize = 256;
+
+/// Query parameter for clients that can't set an `Authorization` header
+pub const ACCESS_TOKEN_PARAMETER: &str = "access_token";
+
+#[derive(Clone, Default)]
+pub struct InterfaceSecurity {
+    token_hashes: Vec<String>,
+    tls: Option<Arc<SslAcceptor>>,
+}
+
+impl InterfaceSecurity {
+    pub fn from_config(
+        auth: &Option<InterfaceAuthConfiguration>,
+        tls: &Option<TlsConfiguration>,
+    ) -> Result<Self, String> {
+        let token_hashes = auth
+            .as_ref()
+            .map(|auth| auth.token_hashes.clone())
+            .unwrap_or_default();
+        let tls = match tls {
+            Some(tls) => {
+                let client_ca_file = auth.as_ref().and_then(|auth| auth.client_ca_file.as_ref());
+                let error = |e: openssl::error::ErrorStack| format!("Invalid TLS setup: {}", e);
+                let mut builder =
+                    SslAcceptor::mozilla_intermediate(SslMethod::tls()).map_err(error)?;
+                builder
+                    .set_certificate_chain_file(&tls.certificate_file)
+                    .map_err(error)?;
+                builder
+                    .set_private_key_file(&tls.private_key_file, SslFiletype::PEM)
+                    .map_err(error)?;
+                builder.check_private_key().map_err(error)?;
+                if let Some(ca_file) = client_ca_file {
+                    builder.set_ca_file(ca_file).map_err(error)?;
+                    builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+                }
+                Some(Arc::new(builder.build()))
+            }
+            None => None,
+        };
+        Ok(InterfaceSecurity { token_hashes, tls })
+    }
+
+    pub fn uses_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    /// Checks the token of a request, given its `Authorization` header and its query string.
+    /// Every request is authorized if the interface doesn't use tokens.
+    pub fn authorize(&self, authorization: Option<&str>, query: Option<&str>) -> bool {
+        if self.token_hashes.is_empty() {
+            return true;
+        }
+        let from_header = authorization
+            .filter(|value| value.starts_with("Bearer "))
+            .map(|value| value["Bearer ".len()..].trim().to_string());
+        let from_query = || {
+            url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+                .find(|(key, _)| key == ACCESS_TOKEN_PARAMETER)
+                .map(|(_, value)| value.into_owned())
+        };
+        from_header
+            .or_else(from_query)
+            .map(|token| self.token_hashes.contains(&hash_token(&token)))
+            .unwrap_or(false)
+    }
+
+    /// Where the interface's server should listen: on the interface's port, or on a
+    /// loopback port the [TlsTerminator] forwards to.
+    pub fn server_address(&self, port: u16) -> String {
+        if self.uses_tls() {
+            String::from("127.0.0.1:0")
+        } else {
+            format!("0.0.0.0:{}", port)
+        }
+    }
+
+    /// Starts accepting TLS connections on the given port if the interface uses TLS.
+    pub fn terminate_tls(
+        &self,
+        port: u16,
+        server_address: SocketAddr,
+    ) -> Result<Option<TlsTerminator>, String> {
+        match self.tls {
+            Some(ref acceptor) => {
+                TlsTerminator::start(acceptor.clone(), port, server_address).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn timed_out(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut
+}
+
+fn lock(client: &Mutex<SslStream<TcpStream>>) -> io::Result<MutexGuard<SslStream<TcpStream>>> {
+    client
+        .lock()
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "TLS stream lock poisoned"))
+}
+
+/// Accepts TLS connections and forwards them to a server on a loopback port until it gets
+/// stopped.
+pub struct TlsTerminator {
+    address: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl TlsTerminator {
+    fn start(
+        acceptor: Arc<SslAcceptor>,
+        port: u16,
+        server_address: SocketAddr,
+    ) -> Result<Self, String> {
+        let listener = TcpListener::bind(format!("0.0.0.0:{}", port)).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let address = listener.local_addr().map_err(|e| e.to_string())?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = stopped.clone();
+        let connections = Arc::new(AtomicUsize::new(0));
+        thread::Builder::new()
+            .name(format!("tls_terminator/{}", address))
+            .spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((client, _)) => {
+                            if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                                connections.fetch_sub(1, Ordering::SeqCst);
+                                continue;
+                            }
+                            let acceptor = acceptor.clone();
+                            let stop = stop.clone();
+                            let open_connections = connections.clone();
+                            let spawned = thread::Builder::new()
+                                .name(format!("tls_terminator/{}/connection", address))
+                                .spawn(move || {
+                                    let _ = Self::forward(&acceptor, client, server_address, stop);
+                                    open_connections.fetch_sub(1, Ordering::SeqCst);
+                                });
+                            if spawned.is_err() {
+                                connections.fetch_sub(1, Ordering::SeqCst);
+                            }
+                        }
+                        Err(ref error) if timed_out(error) => {
+                            thread::sleep(Duration::from_millis(POLL_INTERVAL_MS))
+                        }
+                        Err(_) => break,
+                    }
+                }
+            })
+            .map_err(|e| e.to_string())?;
+        Ok(TlsTerminator { address, stopped })
+    }
+
+    /// Forwards the server's responses to the client on this thread and the client's
+    /// requests to the server on a second one, until either side closes the connection.
+    fn forward(
+        acceptor: &SslAcceptor,
+        client: TcpStream,
+        server_address: SocketAddr,
+        stopped: Arc<AtomicBool>,
+    ) -> io::Result<()> {
+        client.set_nonblocking(false)?;
+        client.set_read_timeout(Some(Duration::from_secs(HANDSHAKE_TIMEOUT_SECS)))?;
+        let client = acceptor
+            .accept(client)
+            .map_err(|e| io::Error::new(io::ErrorKind::ConnectionAborted, e.to_string()))?;
+        let server = TcpStream::connect(server_address)?;
+        let stop_check_interval = Some(Duration::from_millis(STOP_CHECK_INTERVAL_MS));
+        client.get_ref().set_read_timeout(stop_check_interval)?;
+        server.set_read_timeout(stop_check_interval)?;
+
+        let client_socket = client.get_ref().try_clone()?;
+        let client = Arc::new(Mutex::new(client));
+        let requests = {
+            let client = client.clone();
+            let client_socket = client_socket.try_clone()?;
+            let mut server = server.try_clone()?;
+            let stopped = stopped.clone();
+            thread::Builder::new()
+                .name(format!("tls_terminator/{}/requests", server_address))
+                .spawn(move || {
+                    let result =
+                        Self::forward_requests(&client, &client_socket, &mut server, &stopped);
+                    // Closing the server socket ends the responses as well
+                    let _ = server.shutdown(Shutdown::Both);
+                    result
+                })?
+        };
+        let result = Self::forward_responses(&client, server.try_clone()?, &stopped);
+
+        // Closing the client socket ends the requests as well
+        if let Ok(mut client) = client.lock() {
+            let _ = client.shutdown();
+        }
+        let _ = client_socket.shutdown(Shutdown::Both);
+        let _ = server.shutdown(Shutdown::Both);
+        let _ = requests.join();
+        result
+    }
+
+    fn forward_requests(
+        client: &Mutex<SslStream<TcpStream>>,
+        client_socket: &TcpStream,
+        server: &mut TcpStream,
+        stopped: &AtomicBool,
+    ) -> io::Result<()> {
+        let mut buffer = [0u8; BUFFER_SIZE];
+        let mut peeked = [0u8; 1];
+        while !stopped.load(Ordering::Relaxed) {
+            // Wait for the client without holding the lock, so that responses can be written
+            // in the meantime. Data that OpenSSL already decrypted is not on the socket anymore.
+            let decrypted = lock(client)?.ssl().pending() > 0;
+            if !decrypted {
+                match client_socket.peek(&mut peeked) {
+                    Ok(0) => break,
+                    Ok(_) => (),
+                    Err(ref error) if timed_out(error) => continue,
+                    Err(error) => return Err(error),
+                }
+            }
+            let read = lock(client)?.read(&mut buffer);
+            match read {
+                Ok(0) => break,
+                Ok(n) => server.write_all(&buffer[..n])?,
+                Err(ref error) if timed_out(error) => (),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    fn forward_responses(
+        client: &Mutex<SslStream<TcpStream>>,
+        mut server: TcpStream,
+        stopped: &AtomicBool,
+    ) -> io::Result<()> {
+        let mut buffer = [0u8; BUFFER_SIZE];
+        while !stopped.load(Ordering::Relaxed) {
+            match server.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => lock(client)?.write_all(&buffer[..n])?,
+                Err(ref error) if timed_out(error) => (),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Stops accepting connections and closes the open ones.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for TlsTerminator {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use openssl::{
+        asn1::Asn1Time,
+        bn::BigNum,
+        hash::MessageDigest,
+        pkey::PKey,
+        rsa::Rsa,
+        ssl::SslConnector,
+        x509::{X509Builder, X509NameBuilder},
+    };
+    use std::path::{Path, PathBuf};
+    use tempfile::tempdir;
+
+    fn self_signed_tls(dir: &Path) -> TlsConfiguration {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "localhost").unwrap();
+        let name = name.build();
+        let mut certificate = X509Builder::new().unwrap();
+        certificate.set_version(2).unwrap();
+        certificate
+            .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        certificate.set_subject_name(&name).unwrap();
+        certificate.set_issuer_name(&name).unwrap();
+        certificate.set_pubkey(&key).unwrap();
+        certificate
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        certificate
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        certificate.sign(&key, MessageDigest::sha256()).unwrap();
+
+        let certificate_file = dir.join("certificate.pem");
+        let private_key_file = dir.join("private_key.pem");
+        std::fs::write(&certificate_file, certificate.build().to_pem().unwrap()).unwrap();
+        std::fs::write(&private_key_file, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        TlsConfiguration {
+            certificate_file,
+            private_key_file,
+        }
+    }
+
+    #[test]
+    fn tokens_are_taken_from_header_or_query() {
+        let open = InterfaceSecurity::default();
+        assert!(open.authorize(None, None));
+
+        let security = InterfaceSecurity::from_config(
+            &Some(InterfaceAuthConfiguration {
+                token_hashes: vec![hash_token("secret")],
+                client_ca_file: None,
+            }),
+            &None,
+        )
+        .unwrap();
+        assert!(!security.uses_tls());
+        assert!(security.authorize(Some("Bearer secret"), None));
+        assert!(security.authorize(None, Some("id=1&access_token=secret")));
+        assert!(!security.authorize(None, None));
+        assert!(!security.authorize(Some("Bearer wrong"), Some("access_token=secret")));
+        assert!(!security.authorize(Some("Basic secret"), None));
+        assert!(!security.authorize(None, Some("token=secret")));
+    }
+
+    #[test]
+    fn missing_certificates_fail() {
+        let result = InterfaceSecurity::from_config(
+            &None,
+            &Some(TlsConfiguration {
+                certificate_file: PathBuf::from("/does/not/exist.pem"),
+                private_key_file: PathBuf::from("/does/not/exist.key"),
+            }),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn tls_terminator_forwards_requests_and_responses() {
+        let dir = tempdir().unwrap();
+        let security =
+            InterfaceSecurity::from_config(&None, &Some(self_signed_tls(dir.path()))).unwrap();
+
+        // A server that answers a request with its upper case version
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server_address = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = server.accept().unwrap();
+            let mut request = [0u8; 5];
+            stream.read_exact(&mut request).unwrap();
+            stream.write_all(&request.to_ascii_uppercase()).unwrap();
+        });
+
+        let terminator = security.terminate_tls(0, server_address).unwrap().unwrap();
+        let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+        connector.set_verify(SslVerifyMode::NONE);
+        let socket = TcpStream::connect(("127.0.0.1", terminator.address().port())).unwrap();
+        let mut client = connector.build().connect("localhost", socket).unwrap();
+
+        client.write_all(b"hello").unwrap();
+        let mut response = [0u8; 5];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, b"HELLO");
+        terminator.stop();
+    }
+}
@@ -1,12 +1,222 @@
-use crate::{conductor::broadcaster::Broadcaster, interface::Interface};
-use crossbeam_channel::Receiver;
-use jsonrpc_core::IoHandler;
-use jsonrpc_ws_server::ServerBuilder;
-use std::{net::SocketAddr, thread};
+use crate::{
+    conductor::broadcaster::Broadcaster, interface::Interface,
+    interface_impls::security::InterfaceSecurity,
+};
+use crossbeam_channel::{unbounded, Receiver};
+use holochain_locksmith::Mutex;
+use jsonrpc_core::{
+    futures::{
+        future::{self, Either},
+        Future, Sink as _,
+    },
+    Call, FutureOutput, FutureResponse, IoHandler, MetaIoHandler, Metadata, MethodCall, Middleware,
+    Output, Params, Value,
+};
+use jsonrpc_ws_server::{ws, RequestContext, ServerBuilder, Sink};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+};
 use tokio::runtime::Runtime;
 
+/// Signals a client subscribed to with `signals/subscribe`. Fields that are not set match
+/// any signal. Only signals emitted by zomes have a zome and a name.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct SignalFilter {
+    #[serde(default)]
+    pub instance_id: Option<String>,
+    #[serde(default)]
+    pub zome: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+impl SignalFilter {
+    /// Checks a signal in the JSON it gets sent to clients as, i.e. a serialized SignalWrapper
+    pub fn matches(&self, signal: &Value) -> bool {
+        fn field_matches(filter: &Option<String>, value: &Value) -> bool {
+            filter
+                .as_ref()
+                .map(|wanted| value.as_str() == Some(wanted.as_str()))
+                .unwrap_or(true)
+        }
+        field_matches(&self.instance_id, &signal["instance_id"])
+            && field_matches(&self.zome, &signal["signal"]["zome"])
+            && field_matches(&self.name, &signal["signal"]["name"])
+    }
+}
+
+/// Metadata of the requests of a websocket connection
+#[derive(Clone)]
+pub struct WebsocketSession {
+    id: usize,
+    sink: Sink,
+}
+
+impl Metadata for WebsocketSession {}
+
+struct Subscription {
+    id: u64,
+    session: usize,
+    sink: Sink,
+    filter: SignalFilter,
+}
+
+/// Signal subscriptions of the clients of a websocket interface
+#[derive(Default)]
+pub struct SignalSubscriptions {
+    next_id: AtomicU64,
+    subscriptions: Mutex<Vec<Subscription>>,
+}
+
+impl SignalSubscriptions {
+    pub fn subscribe(&self, session: &WebsocketSession, filter: SignalFilter) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.subscriptions.lock().unwrap().push(Subscription {
+            id,
+            session: session.id,
+            sink: session.sink.clone(),
+            filter,
+        });
+        id
+    }
+
+    /// Returns false if the session has no subscription with the given ID.
+    pub fn unsubscribe(&self, session: &WebsocketSession, id: u64) -> bool {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let count = subscriptions.len();
+        subscriptions
+            .retain(|subscription| subscription.id != id || subscription.session != session.id);
+        subscriptions.len() < count
+    }
+
+    /// Sends the signal to every session that has a matching subscription, once per session.
+    /// Subscriptions of closed connections get dropped.
+    pub fn send(&self, signal: &str) {
+        let value: Value = match serde_json::from_str(signal) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        let mut closed_sessions = Vec::new();
+        let mut sent_to_sessions = Vec::new();
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        for subscription in subscriptions.iter_mut() {
+            if sent_to_sessions.contains(&subscription.session)
+                || closed_sessions.contains(&subscription.session)
+                || !subscription.filter.matches(&value)
+            {
+                continue;
+            }
+            match subscription.sink.start_send(signal.to_string()) {
+                Ok(_) => sent_to_sessions.push(subscription.session),
+                Err(_) => closed_sessions.push(subscription.session),
+            }
+        }
+        subscriptions.retain(|subscription| !closed_sessions.contains(&subscription.session));
+    }
+
+    fn dispatch(&self, signals: Receiver<String>) {
+        for signal in signals.iter() {
+            self.send(&signal);
+        }
+    }
+}
+
+/// Handles the subscription methods itself and passes all other calls on to the handler
+/// the conductor built for the interface, which doesn't know about sessions.
+struct SubscriptionMiddleware {
+    handler: IoHandler,
+    subscriptions: Option<Arc<SignalSubscriptions>>,
+}
+
+impl SubscriptionMiddleware {
+    fn subscription_call(
+        &self,
+        method_call: &MethodCall,
+        session: &WebsocketSession,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        let subscriptions = self.subscriptions.as_ref().ok_or_else(|| {
+            jsonrpc_core::Error::invalid_params(
+                "This interface sends all signals to every client. Set `filter_signals` in its configuration to subscribe to signals.",
+            )
+        })?;
+        let params = match &method_call.params {
+            Params::None => Value::Object(Default::default()),
+            Params::Map(map) => Value::Object(map.clone()),
+            Params::Array(_) => {
+                return Err(jsonrpc_core::Error::invalid_params("expected params map"))
+            }
+        };
+        if method_call.method == "signals/subscribe" {
+            let filter: SignalFilter = serde_json::from_value(params)
+                .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+            Ok(json!({ "subscription": subscriptions.subscribe(session, filter) }))
+        } else {
+            let id = params["subscription"].as_u64().ok_or_else(|| {
+                jsonrpc_core::Error::invalid_params("`subscription` has to be a subscription ID")
+            })?;
+            Ok(json!({ "success": subscriptions.unsubscribe(session, id) }))
+        }
+    }
+}
+
+impl Middleware<WebsocketSession> for SubscriptionMiddleware {
+    type Future = FutureResponse;
+    type CallFuture = FutureOutput;
+
+    fn on_call<F, X>(
+        &self,
+        call: Call,
+        session: WebsocketSession,
+        _next: F,
+    ) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, WebsocketSession) -> X + Send + Sync,
+        X: Future<Item = Option<Output>, Error = ()> + Send + 'static,
+    {
+        let future: FutureOutput = match call {
+            Call::MethodCall(ref method_call)
+                if method_call.method == "signals/subscribe"
+                    || method_call.method == "signals/unsubscribe" =>
+            {
+                let result = self.subscription_call(method_call, &session);
+                Box::new(future::ok(Some(Output::from(
+                    result,
+                    method_call.id.clone(),
+                    method_call.jsonrpc,
+                ))))
+            }
+            call => Box::new(self.handler.handle_call(call, ())),
+        };
+        Either::A(future)
+    }
+}
+
+/// Refuses to upgrade connections that don't come with a valid token
+fn authorize_upgrade(
+    security: InterfaceSecurity,
+) -> impl Fn(&ws::Request) -> Option<ws::Response> + Send + Sync + 'static {
+    move |request: &ws::Request| {
+        let authorization = request
+            .header("authorization")
+            .and_then(|value| std::str::from_utf8(value).ok());
+        let query = request.resource().splitn(2, '?').nth(1);
+        if security.authorize(authorization, query) {
+            None
+        } else {
+            Some(ws::Response::new(401, "Unauthorized", Vec::new()))
+        }
+    }
+}
+
 pub struct WebsocketInterface {
     port: u16,
+    filter_signals: bool,
+    security: InterfaceSecurity,
     bound_address: Option<SocketAddr>,
 }
 
@@ -14,10 +224,25 @@ impl WebsocketInterface {
     pub fn new(port: u16) -> Self {
         WebsocketInterface {
             port,
+            filter_signals: false,
+            security: InterfaceSecurity::default(),
             bound_address: None,
         }
     }
 
+    /// Only accept connections with a valid token, and serve over TLS if configured.
+    pub fn with_security(mut self, security: InterfaceSecurity) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Only send clients the signals they subscribed to with `signals/subscribe`, instead of
+    /// sending all signals to all of them.
+    pub fn filter_signals(mut self, filter_signals: bool) -> Self {
+        self.filter_signals = filter_signals;
+        self
+    }
+
     pub fn bound_address(&self) -> Option<SocketAddr> {
         self.bound_address
     }
@@ -30,22 +255,102 @@ impl Interface for WebsocketInterface {
         handler: IoHandler,
         kill_switch: Receiver<()>,
     ) -> Result<(Broadcaster, thread::JoinHandle<()>), String> {
-        let url = format!("0.0.0.0:{}", self.port);
+        let url = self.security.server_address(self.port);
         let runtime = Runtime::new().map_err(|e| e.to_string())?;
-        let server = ServerBuilder::new(handler)
-            .event_loop_executor(runtime.executor())
-            .start(&url.parse().expect("Invalid URL!"))
-            .map_err(|e| e.to_string())?;
-        self.bound_address = Some(*server.addr());
-        let broadcaster = Broadcaster::Ws(server.broadcaster());
+        let subscriptions = if self.filter_signals {
+            Some(Arc::new(SignalSubscriptions::default()))
+        } else {
+            None
+        };
+        let handler = MetaIoHandler::with_middleware(SubscriptionMiddleware {
+            handler,
+            subscriptions: subscriptions.clone(),
+        });
+        let server = ServerBuilder::with_meta_extractor(handler, |context: &RequestContext| {
+            WebsocketSession {
+                id: context.session_id as usize,
+                sink: context.sender(),
+            }
+        })
+        .request_middleware(authorize_upgrade(self.security.clone()))
+        .event_loop_executor(runtime.executor())
+        .start(&url.parse().expect("Invalid URL!"))
+        .map_err(|e| e.to_string())?;
+        let tls_terminator = self.security.terminate_tls(self.port, *server.addr())?;
+        self.bound_address = Some(
+            tls_terminator
+                .as_ref()
+                .map(|terminator| terminator.address())
+                .unwrap_or(*server.addr()),
+        );
+        let broadcaster = match subscriptions {
+            Some(subscriptions) => {
+                let (sender, receiver) = unbounded();
+                thread::Builder::new()
+                    .name(format!("websocket_interface/{}/signals", url))
+                    .spawn(move || subscriptions.dispatch(receiver))
+                    .map_err(|e| e.to_string())?;
+                Broadcaster::Channel(sender)
+            }
+            None => Broadcaster::Ws(server.broadcaster()),
+        };
         let handle = thread::Builder::new()
             .name(format!("websocket_interface/{}", url))
             .spawn(move || {
                 let _ = server; // move `server` into this thread
                 let _ = runtime; // move tokio runtime for RPC futures into this thread
                 let _ = kill_switch.recv();
+                drop(tls_terminator);
             })
             .expect("Could not spawn thread for websocket interface");
         Ok((broadcaster, handle))
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_filters_match_instance_zome_and_name() {
+        let signal = json!({
+            "type": "InstanceSignal",
+            "instance_id": "test-instance",
+            "signal": {
+                "signal_type": "User",
+                "name": "new_post",
+                "arguments": "{}",
+                "zome": "blog",
+            },
+        });
+        let stats = json!({"type": "InstanceStats", "instance_stats": {}});
+
+        let all = SignalFilter::default();
+        assert!(all.matches(&signal));
+        assert!(all.matches(&stats));
+
+        let instance = SignalFilter {
+            instance_id: Some(String::from("test-instance")),
+            ..Default::default()
+        };
+        assert!(instance.matches(&signal));
+        assert!(!instance.matches(&stats));
+
+        let new_posts = SignalFilter {
+            zome: Some(String::from("blog")),
+            name: Some(String::from("new_post")),
+            ..instance.clone()
+        };
+        assert!(new_posts.matches(&signal));
+        let other_zome = SignalFilter {
+            zome: Some(String::from("chat")),
+            ..new_posts.clone()
+        };
+        assert!(!other_zome.matches(&signal));
+        let other_instance = SignalFilter {
+            instance_id: Some(String::from("other-instance")),
+            ..new_posts
+        };
+        assert!(!other_instance.matches(&signal));
+    }
+}
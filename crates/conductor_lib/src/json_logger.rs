@@ -0,0 +1,178 @@
+//! Logging one JSON object per line, for log pipelines like ELK or Loki.
+//!
+//! Each line has the `timestamp` (UTC, RFC 3339), `level`, `instance_id` (null for logs that
+//! don't belong to an instance), `module`, `message` and the structured `fields` of the
+//! record, if it has key-value pairs.
+use crate::{log_router::instance_id, logger::LogRules};
+use chrono::Utc;
+use log::{
+    kv::{self, Key, Value, Visitor},
+    LevelFilter, Log, Metadata, Record,
+};
+use std::{
+    io::{self, Write},
+    sync::Mutex,
+};
+
+struct FieldCollector(serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> Visitor<'kvs> for FieldCollector {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.insert(
+            key.as_str().to_string(),
+            serde_json::Value::String(value.to_string()),
+        );
+        Ok(())
+    }
+}
+
+/// The JSON object of a log record
+pub fn json_log_line(record: &Record) -> serde_json::Value {
+    let mut fields = FieldCollector(serde_json::Map::new());
+    if let Err(error) = record.key_values().visit(&mut fields) {
+        fields.0.insert(
+            String::from("fields_error"),
+            serde_json::Value::String(error.to_string()),
+        );
+    }
+    json!({
+        "timestamp": Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+        "level": record.level().to_string(),
+        "instance_id": instance_id(record.target()),
+        "module": record.module_path().unwrap_or_else(|| record.target()),
+        "message": record.args().to_string(),
+        "fields": fields.0,
+    })
+}
+
+/// Writes the JSON lines of all records that the exclusion rules let through to stdout
+pub struct JsonLogger {
+    level: LevelFilter,
+    rules: LogRules,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLogger {
+    pub fn new(level: LevelFilter, rules: LogRules) -> Self {
+        Self::with_output(level, rules, Box::new(io::stdout()))
+    }
+
+    pub fn with_output(level: LevelFilter, rules: LogRules, out: Box<dyn Write + Send>) -> Self {
+        JsonLogger {
+            level,
+            rules,
+            out: Mutex::new(out),
+        }
+    }
+
+    /// The first rule whose pattern matches the target decides, like for the text loggers.
+    /// Colors make no sense in JSON, so only exclusions apply.
+    fn excluded(&self, target: &str) -> bool {
+        self.rules
+            .rules
+            .iter()
+            .find(|rule| rule.pattern.is_match(target))
+            .map(|rule| rule.exclude)
+            .unwrap_or(false)
+    }
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && !self.excluded(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = json_log_line(record).to_string();
+        let mut out = self.out.lock().unwrap();
+        if let Err(error) = writeln!(out, "{}", line) {
+            eprintln!("Could not write log: {}", error);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.out.lock().unwrap().flush();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use log::Level;
+    use std::sync::Arc;
+
+    /// Output that can be read after the logger wrote to it
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn logs_one_json_object_per_line() {
+        let buffer = SharedBuffer::default();
+        let mut rules = LogRules::new();
+        rules.add_rule("^tokio", true, None).unwrap();
+        let logger = JsonLogger::with_output(LevelFilter::Info, rules, Box::new(buffer.clone()));
+        let fields: &[(&str, &str)] = &[("entry", "Qm123"), ("peer", "HcScAgent")];
+
+        logger.log(
+            &Record::builder()
+                .target("holochain::app")
+                .module_path(Some("holochain_core::workflows"))
+                .level(Level::Warn)
+                .args(format_args!("Could not hold {}", "entry"))
+                .key_values(&fields)
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .target("holochain::app")
+                .level(Level::Debug)
+                .args(format_args!("too verbose"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .target("tokio::reactor")
+                .level(Level::Error)
+                .args(format_args!("excluded"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .target("conductor")
+                .level(Level::Info)
+                .args(format_args!("started"))
+                .build(),
+        );
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "WARN");
+        assert_eq!(lines[0]["instance_id"], "app");
+        assert_eq!(lines[0]["module"], "holochain_core::workflows");
+        assert_eq!(lines[0]["message"], "Could not hold entry");
+        assert_eq!(
+            lines[0]["fields"],
+            json!({ "entry": "Qm123", "peer": "HcScAgent" })
+        );
+        assert!(lines[0]["timestamp"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(lines[1]["instance_id"], serde_json::Value::Null);
+        assert_eq!(lines[1]["module"], "conductor");
+        assert_eq!(lines[1]["fields"], json!({}));
+    }
+}
@@ -21,7 +21,7 @@ use lib3h_sodium::{
     secbuf::SecBuf,
 };
 
-use crate::conductor::passphrase_manager::PassphraseManager;
+use crate::conductor::passphrase_manager::{PassphraseManager, PassphraseServiceMock};
 use holochain_dpki::{password_encryption::PwHashConfig, seed::SeedType};
 use std::{
     collections::{BTreeMap, HashMap},
@@ -149,6 +149,53 @@ impl Keystore {
         Ok((keystore, pub_key))
     }
 
+    /// Create a keystore for a service agent from a provided root seed.
+    /// Secrets get encrypted with [holochain_common::DEFAULT_PASSPHRASE] so neither creating
+    /// nor using this keystore will ever query a passphrase service.
+    /// Returns the keystore and the public address of the agent key derived from the seed.
+    pub fn new_from_seed(
+        seed: &[u8],
+        hash_config: Option<PwHashConfig>,
+    ) -> HcResult<(Self, Base32)> {
+        if seed.len() != SEED_SIZE {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Seed has to be {} bytes long, got {}",
+                SEED_SIZE,
+                seed.len()
+            )));
+        }
+        let passphrase_manager = Arc::new(PassphraseManager::new(Arc::new(Mutex::new(
+            PassphraseServiceMock {
+                passphrase: holochain_common::DEFAULT_PASSPHRASE.to_string(),
+            },
+        ))));
+        let mut keystore = Keystore::new(passphrase_manager, hash_config)?;
+        keystore.add_seed(STANDALONE_ROOT_SEED, seed)?;
+        let (pub_key, _) =
+            keystore.add_keybundle_from_seed(STANDALONE_ROOT_SEED, PRIMARY_KEYBUNDLE_ID)?;
+        Ok((keystore, pub_key))
+    }
+
+    /// Create a service agent keystore from a seed file.
+    /// The file has to contain the seed either base64 encoded or as raw bytes.
+    pub fn new_from_seed_file(
+        path: PathBuf,
+        hash_config: Option<PwHashConfig>,
+    ) -> HcResult<(Self, Base32)> {
+        let mut file = File::open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let seed = if contents.len() == SEED_SIZE {
+            contents
+        } else {
+            let encoded = String::from_utf8_lossy(&contents).trim().to_string();
+            base64::decode(&encoded).map_err(|e| {
+                HolochainError::ErrorGeneric(format!("Could not decode seed file: {}", e))
+            })?
+        };
+        Self::new_from_seed(&seed, hash_config)
+    }
+
     /// Load a keystore from file.
     /// This won't ask for a passphrase until a secret is used via the other functions.
     /// Secrets will get loaded to memory instantly but stay encrypted until requested.
@@ -323,8 +370,27 @@ impl Keystore {
         Ok(dst_id)
     }
 
+    /// Whether the passphrase manager of this keystore got locked.
+    /// A locked keystore refuses to hand out any secret.
+    pub fn is_locked(&self) -> bool {
+        self.passphrase_manager
+            .as_ref()
+            .map(|passphrase_manager| passphrase_manager.is_locked())
+            .unwrap_or(false)
+    }
+
+    /// Drops all decrypted secrets from memory.
+    /// They get decrypted again on next use, which requires the passphrase.
+    pub fn lock(&mut self) {
+        self.cache.clear();
+    }
+
     /// gets a secret from the keystore
     pub fn get(&mut self, src_id_str: &str) -> HcResult<Arc<Mutex<Secret>>> {
+        if self.is_locked() {
+            self.lock();
+            return Err(HolochainError::KeystoreLocked);
+        }
         let src_id = src_id_str.to_string();
         if !self.secrets.contains_key(&src_id) {
             return Err(HolochainError::ErrorGeneric(
@@ -532,6 +598,19 @@ impl Keystore {
             )),
         }
     }
+
+    /// signs some data using the signing key of a keybundle in the keystore
+    /// returns the signature
+    pub fn sign_with_keybundle(
+        &mut self,
+        src_id_prefix_str: &str,
+        data: String,
+    ) -> HcResult<Signature> {
+        self.sign(
+            &[src_id_prefix_str, KEYBUNDLE_SIGNKEY_SUFFIX].join(""),
+            data,
+        )
+    }
 }
 
 pub fn test_hash_config() -> Option<PwHashConfig> {
@@ -580,6 +659,22 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_keystore_new_from_seed_is_deterministic() {
+        let seed = [7u8; SEED_SIZE];
+        let (mut keystore1, pub_key1) = Keystore::new_from_seed(&seed, test_hash_config()).unwrap();
+        let (_, pub_key2) = Keystore::new_from_seed(&seed, test_hash_config()).unwrap();
+        assert_eq!(pub_key1, pub_key2);
+        assert_eq!(
+            keystore1
+                .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+                .unwrap()
+                .get_id(),
+            pub_key1
+        );
+        assert!(Keystore::new_from_seed(&[0u8; 3], test_hash_config()).is_err());
+    }
+
     #[test]
     fn test_save_load_roundtrip() {
         let random_passphrase = random_test_passphrase();
@@ -820,6 +915,47 @@ pub mod tests {
         assert!(key_bundle.enc_keys.is_same(&mut key_bundle_copy.enc_keys));
     }
 
+    #[test]
+    fn test_keystore_refuses_secrets_while_locked() {
+        let passphrase = random_test_passphrase();
+        let passphrase_manager = mock_passphrase_manager(passphrase.clone());
+        let mut keystore = Keystore::new(passphrase_manager.clone(), test_hash_config()).unwrap();
+        keystore.add_random_seed("my_root_seed", SEED_SIZE).unwrap();
+        keystore
+            .add_keybundle_from_seed("my_root_seed", "my_keybundle")
+            .unwrap();
+        let data = base64::encode("the data to sign");
+        assert!(keystore
+            .sign_with_keybundle("my_keybundle", data.clone())
+            .is_ok());
+
+        passphrase_manager.lock();
+        assert_eq!(
+            keystore.sign_with_keybundle("my_keybundle", data.clone()),
+            Err(HolochainError::KeystoreLocked)
+        );
+        assert!(keystore.cache.is_empty());
+
+        passphrase_manager.unlock(SecBuf::with_insecure_from_string(passphrase));
+        assert!(keystore.sign_with_keybundle("my_keybundle", data).is_ok());
+    }
+
+    #[test]
+    fn test_passphrase_manager_locks_after_session() {
+        let passphrase_manager =
+            PassphraseManager::new(Arc::new(Mutex::new(PassphraseServiceMock {
+                passphrase: random_test_passphrase(),
+            })))
+            .with_session_duration(Some(std::time::Duration::from_millis(100)));
+        assert!(passphrase_manager.get_passphrase().is_ok());
+        std::thread::sleep(std::time::Duration::from_millis(2500));
+        assert!(passphrase_manager.is_locked());
+        assert_eq!(
+            passphrase_manager.get_passphrase().map(|_| ()),
+            Err(HolochainError::KeystoreLocked)
+        );
+    }
+
     #[test]
     /// Tests if the keystore encrypted with holochain_common::DEFAULT_PASSPHRASE can be decrypted,
     /// no matter what passphrase we get from the passphrase manager
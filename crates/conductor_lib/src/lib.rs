@@ -108,6 +108,20 @@ extern crate nickel;
 #[macro_use]
 extern crate holochain_common;
 
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
+pub mod cold_storage;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
+pub mod compressed_storage;
 #[allow(
     clippy::suspicious_else_formatting,
     clippy::redundant_closure,
@@ -149,6 +163,20 @@ pub mod dpki_instance;
     clippy::let_and_return,
     clippy::collapsible_if
 )]
+pub mod eav_indexing;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
+pub mod encrypted_storage;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
 pub mod error;
 #[allow(
     clippy::suspicious_else_formatting,
@@ -191,6 +219,13 @@ pub mod interface_impls;
     clippy::let_and_return,
     clippy::collapsible_if
 )]
+pub mod json_logger;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
 pub mod key_loaders;
 #[allow(
     clippy::suspicious_else_formatting,
@@ -205,6 +240,13 @@ pub mod keystore;
     clippy::let_and_return,
     clippy::collapsible_if
 )]
+pub mod log_router;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
 pub mod logger;
 #[allow(
     clippy::suspicious_else_formatting,
@@ -219,6 +261,20 @@ pub mod port_utils;
     clippy::let_and_return,
     clippy::collapsible_if
 )]
+pub mod quota_storage;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
+pub mod rocksdb_storage;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
 pub mod signal_wrapper;
 #[allow(
     clippy::suspicious_else_formatting,
@@ -226,6 +282,20 @@ pub mod signal_wrapper;
     clippy::let_and_return,
     clippy::collapsible_if
 )]
+pub mod signing_backend;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
+pub mod sqlite_storage;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
 pub mod static_file_server;
 #[allow(
     clippy::suspicious_else_formatting,
@@ -234,6 +304,27 @@ pub mod static_file_server;
     clippy::collapsible_if
 )]
 pub mod static_server_impls;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
+pub mod storage_backend;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
+pub mod tiered_storage;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
+pub mod trace_export;
 
 pub use crate::holochain::Holochain;
 
@@ -0,0 +1,530 @@
+//! Routing the logs of each instance to its own level and file.
+//!
+//! Instances log with the target `holochain::<instance id>` (see the log macros of
+//! holochain_core). The [LogRouter] gets installed as the global logger in front of the
+//! conductor's logger. Logs of instances that have an [InstanceLoggerConfiguration] go
+//! through the instance's own level filter and, if it has a file, into that file, which gets
+//! rotated as configured. All other logs go to the conductor's logger. Instance files get
+//! the same lines as the conductor's log, text or [JSON](crate::json_logger).
+//!
+//! Levels set for a module path override both while the conductor runs, so that a single
+//! module like `network::handler::fetch` can log at debug level while the rest stays at info.
+//! A module level applies to the logs of the module and of its submodules, whether they belong
+//! to an instance or not. If levels of several modules apply, the most specific one wins.
+//!
+//! The router uses the locks of std instead of holochain_locksmith, because the locksmith
+//! logs itself.
+use crate::{
+    config::{InstanceLoggerConfiguration, LogRotation},
+    json_logger::json_log_line,
+};
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{
+    cmp::max,
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, Once, RwLock,
+    },
+};
+
+/// Logs with this target prefix, followed by an instance ID, are logs of that instance
+pub const INSTANCE_TARGET_PREFIX: &str = "holochain::";
+
+lazy_static! {
+    static ref LOG_ROUTER: LogRouter = LogRouter::new();
+}
+static INSTALL: Once = Once::new();
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the router as the global logger, unless some other logger was installed before.
+/// Returns whether the router is the global logger.
+pub fn install_log_router() -> bool {
+    INSTALL.call_once(|| {
+        if log::set_logger(&*LOG_ROUTER).is_ok() {
+            INSTALLED.store(true, Ordering::SeqCst);
+            LOG_ROUTER.update_max_level();
+        }
+    });
+    INSTALLED.load(Ordering::SeqCst)
+}
+
+/// The router of the global logger, see [install_log_router]
+pub fn log_router() -> &'static LogRouter {
+    &*LOG_ROUTER
+}
+
+/// Rotates the log file once it would grow beyond `max_bytes`, keeping `max_files` old
+/// files next to it as `<file>.1` (the newest) to `<file>.<max_files>`.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, rotation: LogRotation) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path,
+            rotation,
+            file,
+            size,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let bytes = line.len() as u64 + 1;
+        if self.size > 0 && self.size + bytes > self.rotation.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += bytes;
+        Ok(())
+    }
+
+    fn rotated_path(&self, number: usize) -> PathBuf {
+        PathBuf::from(format!("{}.{}", self.path.display(), number))
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let max_files = self.rotation.max_files;
+        if max_files > 0 {
+            let _ = fs::remove_file(self.rotated_path(max_files));
+            for number in (1..max_files).rev() {
+                let rotated = self.rotated_path(number);
+                if rotated.exists() {
+                    fs::rename(rotated, self.rotated_path(number + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+struct InstanceLog {
+    /// None follows the conductor's level
+    level: Option<LevelFilter>,
+    file: Option<Mutex<RotatingFile>>,
+}
+
+pub struct LogRouter {
+    conductor_logger: RwLock<Option<Box<dyn Log>>>,
+    conductor_level: RwLock<LevelFilter>,
+    instances: RwLock<HashMap<String, InstanceLog>>,
+    module_levels: RwLock<BTreeMap<String, LevelFilter>>,
+    json: AtomicBool,
+}
+
+/// The ID of the instance a log with this target belongs to
+pub fn instance_id(target: &str) -> Option<&str> {
+    if target.starts_with(INSTANCE_TARGET_PREFIX) {
+        Some(&target[INSTANCE_TARGET_PREFIX.len()..])
+    } else {
+        None
+    }
+}
+
+/// Whether the module path is the given module or one of its submodules. The module can
+/// leave out leading crates and modules, so `network::handler` matches
+/// `holochain_core::network::handler::fetch`.
+pub fn module_matches(path: &str, module: &str) -> bool {
+    let path: Vec<&str> = path.split("::").collect();
+    let module: Vec<&str> = module.split("::").collect();
+    path.windows(module.len())
+        .any(|components| components == &module[..])
+}
+
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .parse::<LevelFilter>()
+        .map_err(|_| format!("Invalid log level \"{}\"", level))
+}
+
+impl LogRouter {
+    pub fn new() -> Self {
+        LogRouter {
+            conductor_logger: RwLock::new(None),
+            conductor_level: RwLock::new(LevelFilter::Info),
+            instances: RwLock::new(HashMap::new()),
+            module_levels: RwLock::new(BTreeMap::new()),
+            json: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the logger that gets all logs which are not routed to an instance's file
+    pub fn set_conductor_logger(&self, logger: Box<dyn Log>, level: LevelFilter) {
+        *self.conductor_logger.write().unwrap() = Some(logger);
+        self.set_conductor_level(level);
+    }
+
+    /// Whether instance files get JSON lines instead of text
+    pub fn set_json_lines(&self, json: bool) {
+        self.json.store(json, Ordering::SeqCst);
+    }
+
+    pub fn set_conductor_level(&self, level: LevelFilter) {
+        *self.conductor_level.write().unwrap() = level;
+        self.update_max_level();
+    }
+
+    /// Routes the logs of the given instance as configured, or to the conductor's logger
+    /// with the conductor's level if there is no configuration.
+    pub fn route_instance(
+        &self,
+        id: &str,
+        config: Option<&InstanceLoggerConfiguration>,
+    ) -> Result<(), String> {
+        let config = match config {
+            Some(config) => config,
+            None => {
+                self.remove_instance(id);
+                return Ok(());
+            }
+        };
+        let level = match config.level {
+            Some(ref level) => Some(parse_level(level)?),
+            None => None,
+        };
+        let file = match config.file {
+            Some(ref path) => Some(Mutex::new(
+                RotatingFile::open(path.clone(), config.rotation.clone())
+                    .map_err(|e| format!("Could not open log file {}: {}", path.display(), e))?,
+            )),
+            None => None,
+        };
+        self.instances
+            .write()
+            .unwrap()
+            .insert(id.to_string(), InstanceLog { level, file });
+        self.update_max_level();
+        Ok(())
+    }
+
+    pub fn remove_instance(&self, id: &str) {
+        self.instances.write().unwrap().remove(id);
+        self.update_max_level();
+    }
+
+    /// Changes the level of an instance's logs, keeping where they go
+    pub fn set_instance_level(&self, id: &str, level: LevelFilter) {
+        self.instances
+            .write()
+            .unwrap()
+            .entry(id.to_string())
+            .or_insert(InstanceLog {
+                level: None,
+                file: None,
+            })
+            .level = Some(level);
+        self.update_max_level();
+    }
+
+    /// Sets the level of the logs of a module and its submodules, or removes it with None
+    pub fn set_module_level(&self, module: &str, level: Option<LevelFilter>) {
+        {
+            let mut module_levels = self.module_levels.write().unwrap();
+            match level {
+                Some(level) => module_levels.insert(module.to_string(), level),
+                None => module_levels.remove(module),
+            };
+        }
+        self.update_max_level();
+    }
+
+    pub fn module_levels(&self) -> BTreeMap<String, LevelFilter> {
+        self.module_levels.read().unwrap().clone()
+    }
+
+    /// The level of the most specific module that the path belongs to, if any
+    fn module_level(&self, path: &str) -> Option<LevelFilter> {
+        self.module_levels
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(module, _)| module_matches(path, module))
+            .max_by_key(|(module, _)| module.split("::").count())
+            .map(|(_, level)| *level)
+    }
+
+    fn level(&self, target: &str) -> LevelFilter {
+        let conductor_level = *self.conductor_level.read().unwrap();
+        instance_id(target)
+            .and_then(|id| {
+                self.instances
+                    .read()
+                    .unwrap()
+                    .get(id)
+                    .map(|instance| instance.level.unwrap_or(conductor_level))
+            })
+            .unwrap_or(conductor_level)
+    }
+
+    /// The log macros skip everything above this level, so it has to be the highest of all
+    fn update_max_level(&self) {
+        if !std::ptr::eq(self, &*LOG_ROUTER) || !INSTALLED.load(Ordering::SeqCst) {
+            return;
+        }
+        let instance_levels = self
+            .instances
+            .read()
+            .unwrap()
+            .values()
+            .filter_map(|instance| instance.level)
+            .max()
+            .unwrap_or(LevelFilter::Off);
+        let module_levels = self
+            .module_levels
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .max()
+            .unwrap_or(LevelFilter::Off);
+        log::set_max_level(max(
+            *self.conductor_level.read().unwrap(),
+            max(instance_levels, module_levels),
+        ));
+    }
+}
+
+impl Default for LogRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Log for LogRouter {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let target = metadata.target();
+        metadata.level()
+            <= self
+                .module_level(target)
+                .unwrap_or_else(|| self.level(target))
+    }
+
+    fn log(&self, record: &Record) {
+        // Instance logs have the instance as target, so their module is only in the record
+        let level = record
+            .module_path()
+            .and_then(|path| self.module_level(path))
+            .or_else(|| self.module_level(record.target()))
+            .unwrap_or_else(|| self.level(record.target()));
+        if record.level() > level {
+            return;
+        }
+        if let Some(id) = instance_id(record.target()) {
+            if let Some(InstanceLog {
+                file: Some(file), ..
+            }) = self.instances.read().unwrap().get(id)
+            {
+                let line = if self.json.load(Ordering::Relaxed) {
+                    json_log_line(record).to_string()
+                } else {
+                    format!(
+                        "{} {:5} {}: {}",
+                        Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                        record.level(),
+                        record.target(),
+                        record.args()
+                    )
+                };
+                if let Err(error) = file.lock().unwrap().write_line(&line) {
+                    eprintln!("Could not write log of instance {}: {}", id, error);
+                }
+                return;
+            }
+        }
+        if let Some(ref logger) = *self.conductor_logger.read().unwrap() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(ref logger) = *self.conductor_logger.read().unwrap() {
+            logger.flush();
+        }
+        for instance in self.instances.read().unwrap().values() {
+            if let Some(ref file) = instance.file {
+                let _ = file.lock().unwrap().file.flush();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use log::Level;
+    use std::sync::Arc;
+
+    /// Collects the messages it gets
+    #[derive(Clone, Default)]
+    struct TestLog(Arc<Mutex<Vec<String>>>);
+
+    impl Log for TestLog {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+        fn log(&self, record: &Record) {
+            self.0.lock().unwrap().push(format!("{}", record.args()));
+        }
+        fn flush(&self) {}
+    }
+
+    fn log(router: &LogRouter, target: &str, level: Level, message: &str) {
+        router.log(
+            &Record::builder()
+                .target(target)
+                .level(level)
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn routes_instance_logs_by_level_and_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("logs").join("app.log");
+        let conductor_log = TestLog::default();
+        let router = LogRouter::new();
+        router.set_conductor_logger(Box::new(conductor_log.clone()), LevelFilter::Info);
+        router
+            .route_instance(
+                "app",
+                Some(&InstanceLoggerConfiguration {
+                    level: Some(String::from("debug")),
+                    file: Some(file.clone()),
+                    rotation: LogRotation::default(),
+                }),
+            )
+            .unwrap();
+        router
+            .route_instance(
+                "quiet",
+                Some(&InstanceLoggerConfiguration {
+                    level: Some(String::from("error")),
+                    ..InstanceLoggerConfiguration::default()
+                }),
+            )
+            .unwrap();
+
+        log(&router, "holochain::app", Level::Debug, "app debug");
+        log(&router, "holochain::quiet", Level::Warn, "quiet warning");
+        log(&router, "holochain::quiet", Level::Error, "quiet error");
+        log(&router, "holochain::other", Level::Debug, "other debug");
+        log(&router, "holochain::other", Level::Info, "other info");
+        router.flush();
+
+        assert_eq!(
+            *conductor_log.0.lock().unwrap(),
+            vec![String::from("quiet error"), String::from("other info")]
+        );
+        let written = fs::read_to_string(&file).unwrap();
+        assert!(written.contains("DEBUG holochain::app: app debug"));
+
+        router.set_instance_level("app", LevelFilter::Warn);
+        log(&router, "holochain::app", Level::Info, "app info");
+        router.flush();
+        assert_eq!(fs::read_to_string(&file).unwrap(), written);
+
+        router.route_instance("quiet", None).unwrap();
+        log(&router, "holochain::quiet", Level::Warn, "quiet warning");
+        assert_eq!(conductor_log.0.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn module_levels_override_the_others() {
+        let conductor_log = TestLog::default();
+        let router = LogRouter::new();
+        router.set_conductor_logger(Box::new(conductor_log.clone()), LevelFilter::Info);
+        router.set_module_level("network::handler", Some(LevelFilter::Warn));
+        router.set_module_level("network::handler::fetch", Some(LevelFilter::Debug));
+        let log_from = |module: &str, target: &str, message: &str| {
+            router.log(
+                &Record::builder()
+                    .target(target)
+                    .module_path(Some(module))
+                    .level(Level::Debug)
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+        };
+
+        log_from(
+            "holochain_core::network::handler::fetch",
+            "holochain::app",
+            "fetch",
+        );
+        log_from(
+            "holochain_core::network::handler::store",
+            "holochain_core::network::handler::store",
+            "store",
+        );
+        log_from(
+            "holochain_core::network",
+            "holochain_core::network",
+            "network",
+        );
+        log_from(
+            "holochain_core::network::handler::fetcher",
+            "holochain::app",
+            "fetcher",
+        );
+        assert_eq!(
+            *conductor_log.0.lock().unwrap(),
+            vec![String::from("fetch")]
+        );
+
+        router.set_module_level("network::handler::fetch", None);
+        log_from(
+            "holochain_core::network::handler::fetch",
+            "holochain::app",
+            "fetch again",
+        );
+        assert_eq!(conductor_log.0.lock().unwrap().len(), 1);
+        assert_eq!(router.module_levels().len(), 1);
+        assert!(module_matches("holochain_core::network", "holochain_core"));
+        assert!(!module_matches("holochain_core::network", "work"));
+    }
+
+    #[test]
+    fn rotates_log_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let mut file = RotatingFile::open(
+            path.clone(),
+            LogRotation {
+                max_bytes: 10,
+                max_files: 2,
+            },
+        )
+        .unwrap();
+        for line in &["one", "two", "three", "four", "five"] {
+            file.write_line(line).unwrap();
+        }
+        file.file.flush().unwrap();
+
+        let read = |suffix: &str| fs::read_to_string(format!("{}{}", path.display(), suffix));
+        assert_eq!(read("").unwrap(), "four\nfive\n");
+        assert_eq!(read(".1").unwrap(), "three\n");
+        assert_eq!(read(".2").unwrap(), "one\ntwo\n");
+        assert!(read(".3").is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! Storage limit of an instance.
+//!
+//! [QuotaStorage] wraps a CAS and counts the size of new content against the instance's
+//! [StorageQuota] before it gets written. Content that doesn't fit is refused, content that
+//! is in the storage already is written again without being counted twice.
+use holochain_core::resource_limits::StorageQuota;
+use holochain_locksmith::RwLock;
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    error::{PersistenceError, PersistenceResult},
+    reporting::ReportStorage,
+};
+use std::{fmt, sync::Arc};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct QuotaStorage {
+    inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+    quota: Arc<StorageQuota>,
+}
+
+impl fmt::Debug for QuotaStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("QuotaStorage")
+            .field("quota", &self.quota)
+            .finish()
+    }
+}
+
+impl QuotaStorage {
+    pub fn new(
+        inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+        quota: Arc<StorageQuota>,
+    ) -> Self {
+        QuotaStorage { inner, quota }
+    }
+}
+
+impl ContentAddressableStorage for QuotaStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        if !self.inner.read().unwrap().contains(&content.address())? {
+            let bytes = String::from(content.content()).len();
+            self.quota
+                .reserve(bytes)
+                .map_err(|error| PersistenceError::ErrorGeneric(error.to_string()))?;
+        }
+        self.inner.write().unwrap().add(content)
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        self.inner.read().unwrap().contains(address)
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        self.inner.read().unwrap().fetch(address)
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.inner.read().unwrap().get_id()
+    }
+}
+
+impl ReportStorage for QuotaStorage {}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_json_api::json::{JsonString, RawString};
+    use holochain_persistence_api::cas::content::ExampleAddressableContent;
+    use holochain_persistence_mem::cas::memory::MemoryStorage;
+
+    fn content(text: &str) -> ExampleAddressableContent {
+        ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from(text)))
+            .unwrap()
+    }
+
+    #[test]
+    fn refuses_content_beyond_the_quota() {
+        let inner: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        let quota = Arc::new(StorageQuota::new(100));
+        let mut storage = QuotaStorage::new(inner, quota.clone());
+
+        let small = content("small");
+        storage.add(&small).unwrap();
+        let used = quota.used_bytes();
+        assert!(used > 0);
+
+        // Writing the same content again doesn't count
+        storage.add(&small).unwrap();
+        assert_eq!(quota.used_bytes(), used);
+
+        let big = content(&"holochain ".repeat(20));
+        assert!(storage.add(&big).is_err());
+        assert!(!storage.contains(&big.address()).unwrap());
+        assert_eq!(quota.used_bytes(), used);
+    }
+}
@@ -0,0 +1,631 @@
+//! RocksDB based implementations of the content addressable storage and the EAV storage.
+//!
+//! RocksDB is a log-structured merge tree, so writes are cheap appends that get merged into
+//! sorted files in the background. That suits nodes that hold large DHT shards and mostly
+//! receive and store data. How and when that merging (compaction) happens can be tuned with
+//! [RocksDbStorageOptions], which are exposed in the instance's storage configuration.
+use crate::eav_indexing::{EavIndexStrategy, EavIndexing};
+use holochain_core::storage_compaction::CompactStorage;
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::HcResult,
+};
+use holochain_json_api::json::JsonString;
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::EntityAttributeValueStorage,
+    error::{PersistenceError, PersistenceResult},
+    reporting::ReportStorage,
+};
+use rocksdb::{DBCompactionStyle, Direction, IteratorMode, Options, WriteBatch, DB};
+use std::{
+    collections::BTreeSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use uuid::Uuid;
+
+/// Compaction strategy of a RocksDB storage, see
+/// https://github.com/facebook/rocksdb/wiki/Compaction
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RocksDbCompactionStyle {
+    /// Lowest read and space amplification, at the cost of more rewriting
+    Level,
+    /// Least rewriting, at the cost of more space while compacting
+    Universal,
+    /// Drops the oldest files instead of compacting. Only for data that may get lost.
+    Fifo,
+}
+
+/// Tuning options of a RocksDB storage. Defaults favour write-heavy workloads.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct RocksDbStorageOptions {
+    #[serde(default = "default_compaction_style")]
+    pub compaction_style: RocksDbCompactionStyle,
+    /// Size of a single memtable in bytes before it gets flushed to disk
+    #[serde(default = "default_write_buffer_size")]
+    pub write_buffer_size: usize,
+    /// Number of memtables that can fill up while older ones are still being flushed
+    #[serde(default = "default_max_write_buffer_number")]
+    pub max_write_buffer_number: i32,
+    /// Number of level 0 files that triggers a compaction into level 1
+    #[serde(default = "default_level_zero_compaction_trigger")]
+    pub level_zero_file_num_compaction_trigger: i32,
+    /// Target size of the files compaction produces in level 1, in bytes
+    #[serde(default = "default_target_file_size_base")]
+    pub target_file_size_base: u64,
+    /// Turns off background compaction entirely, e.g. for bulk imports
+    #[serde(default)]
+    pub disable_auto_compactions: bool,
+}
+
+fn default_compaction_style() -> RocksDbCompactionStyle {
+    RocksDbCompactionStyle::Level
+}
+
+fn default_write_buffer_size() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_max_write_buffer_number() -> i32 {
+    4
+}
+
+fn default_level_zero_compaction_trigger() -> i32 {
+    8
+}
+
+fn default_target_file_size_base() -> u64 {
+    64 * 1024 * 1024
+}
+
+impl Default for RocksDbStorageOptions {
+    fn default() -> Self {
+        RocksDbStorageOptions {
+            compaction_style: default_compaction_style(),
+            write_buffer_size: default_write_buffer_size(),
+            max_write_buffer_number: default_max_write_buffer_number(),
+            level_zero_file_num_compaction_trigger: default_level_zero_compaction_trigger(),
+            target_file_size_base: default_target_file_size_base(),
+            disable_auto_compactions: false,
+        }
+    }
+}
+
+impl RocksDbStorageOptions {
+    fn db_options(&self) -> Options {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.set_compaction_style(match self.compaction_style {
+            RocksDbCompactionStyle::Level => DBCompactionStyle::Level,
+            RocksDbCompactionStyle::Universal => DBCompactionStyle::Universal,
+            RocksDbCompactionStyle::Fifo => DBCompactionStyle::Fifo,
+        });
+        options.set_write_buffer_size(self.write_buffer_size);
+        options.set_max_write_buffer_number(self.max_write_buffer_number);
+        options.set_level_zero_file_num_compaction_trigger(
+            self.level_zero_file_num_compaction_trigger,
+        );
+        options.set_target_file_size_base(self.target_file_size_base);
+        options.set_disable_auto_compactions(self.disable_auto_compactions);
+        options
+    }
+}
+
+fn to_persistence_error(error: rocksdb::Error) -> PersistenceError {
+    PersistenceError::ErrorGeneric(format!("RocksDB storage error: {}", error))
+}
+
+fn open_database(path: &Path, options: &RocksDbStorageOptions) -> PersistenceResult<Arc<DB>> {
+    Ok(Arc::new(
+        DB::open(&options.db_options(), path).map_err(to_persistence_error)?,
+    ))
+}
+
+fn to_string(bytes: &[u8]) -> PersistenceResult<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|error| {
+        PersistenceError::ErrorGeneric(format!("Invalid data in RocksDB storage: {}", error))
+    })
+}
+
+/// Content addressable storage that keeps content in a RocksDB database, keyed by address.
+#[derive(Clone)]
+pub struct RocksDbStorage {
+    id: Uuid,
+    path: PathBuf,
+    db: Arc<DB>,
+}
+
+impl fmt::Debug for RocksDbStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RocksDbStorage")
+            .field("id", &self.id)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl RocksDbStorage {
+    /// Opens the database in the given directory, creating it if it doesn't exist yet.
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        options: &RocksDbStorageOptions,
+    ) -> PersistenceResult<RocksDbStorage> {
+        let path = path.as_ref().to_path_buf();
+        Ok(RocksDbStorage {
+            id: Uuid::new_v4(),
+            db: open_database(&path, options)?,
+            path,
+        })
+    }
+}
+
+impl ContentAddressableStorage for RocksDbStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        self.db
+            .put(
+                String::from(content.address()),
+                String::from(content.content()),
+            )
+            .map_err(to_persistence_error)
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        Ok(self
+            .db
+            .get(String::from(address.clone()))
+            .map_err(to_persistence_error)?
+            .is_some())
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        match self
+            .db
+            .get(String::from(address.clone()))
+            .map_err(to_persistence_error)?
+        {
+            Some(content) => Ok(Some(JsonString::from_json(&to_string(&content)?))),
+            None => Ok(None),
+        }
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl ReportStorage for RocksDbStorage {}
+
+const INDEX_PREFIX: &str = "i\u{0}";
+const ENTITY_PREFIX: &str = "e\u{0}";
+const VALUE_PREFIX: &str = "v\u{0}";
+const ENTITY_ONLY_PREFIX: &str = "o\u{0}";
+const UNINDEXED_PREFIX: &str = "u\u{0}";
+
+/// Hex encoding of the index that sorts like the (signed) index itself
+fn sortable_index(index: i64) -> String {
+    format!("{:016x}", (index as u64) ^ (1 << 63))
+}
+
+/// EAV storage that keeps every EAVI twice in a RocksDB database, once keyed by entity and
+/// once keyed by value (both followed by the index), so that queries can seek to the
+/// entities or values they are about. Attribute classes with a cheaper [EavIndexing] are
+/// only written once, keyed by entity or just by index under a prefix of their own.
+#[derive(Clone)]
+pub struct EavRocksDbStorage {
+    path: PathBuf,
+    db: Arc<DB>,
+    indexing: EavIndexing,
+}
+
+impl fmt::Debug for EavRocksDbStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EavRocksDbStorage")
+            .field("path", &self.path)
+            .field("indexing", &self.indexing)
+            .finish()
+    }
+}
+
+impl EavRocksDbStorage {
+    /// Opens the database in the given directory, creating it if it doesn't exist yet.
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        options: &RocksDbStorageOptions,
+    ) -> PersistenceResult<EavRocksDbStorage> {
+        let path = path.as_ref().to_path_buf();
+        Ok(EavRocksDbStorage {
+            db: open_database(&path, options)?,
+            path,
+            indexing: EavIndexing::default(),
+        })
+    }
+
+    /// Sets how EAVIs added from now on get indexed. EAVIs that were added with a different
+    /// indexing before are still found.
+    pub fn with_indexing(mut self, indexing: EavIndexing) -> Self {
+        self.indexing = indexing;
+        self
+    }
+
+    /// Iterates over all key/value pairs whose key starts with the given prefix.
+    fn scan<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a {
+        self.db
+            .iterator(IteratorMode::From(prefix.as_bytes(), Direction::Forward))
+            .take_while(move |(key, _)| key.starts_with(prefix.as_bytes()))
+    }
+
+    /// Returns the distinct addresses found in the keys under the given prefix, and which of
+    /// them pass the filter.
+    fn matching_addresses<F>(
+        &self,
+        prefix: &str,
+        filter: F,
+    ) -> PersistenceResult<(usize, Vec<String>)>
+    where
+        F: Fn(Address) -> bool,
+    {
+        let mut distinct = 0;
+        let mut matching = Vec::new();
+        let mut last: Option<String> = None;
+        for (key, _) in self.scan(prefix) {
+            let key = to_string(&key[prefix.len()..])?;
+            let address = key.split('\u{0}').next().unwrap_or_default().to_string();
+            if last.as_ref() != Some(&address) {
+                distinct += 1;
+                if filter(Address::from(address.as_str())) {
+                    matching.push(address.clone());
+                }
+                last = Some(address);
+            }
+        }
+        Ok((distinct, matching))
+    }
+
+    fn load_eavis(
+        &self,
+        prefix: &str,
+        addresses: &[String],
+    ) -> PersistenceResult<Vec<EntityAttributeValueIndex>> {
+        let mut eavis = Vec::new();
+        for address in addresses {
+            let address_prefix = format!("{}{}\u{0}", prefix, address);
+            for (_, content) in self.scan(&address_prefix) {
+                eavis.push(EntityAttributeValueIndex::try_from_content(
+                    &JsonString::from_json(&to_string(&content)?),
+                )?);
+            }
+        }
+        Ok(eavis)
+    }
+}
+
+impl EntityAttributeValueStorage<Attribute> for EavRocksDbStorage {
+    fn add_eavi(
+        &mut self,
+        eavi: &EntityAttributeValueIndex,
+    ) -> PersistenceResult<Option<EntityAttributeValueIndex>> {
+        // Like the other storages we make room for EAVIs that were added with the same index
+        // by moving the new one to the next free index.
+        let mut eavi = eavi.clone();
+        while self
+            .db
+            .get(format!("{}{}", INDEX_PREFIX, sortable_index(eavi.index())))
+            .map_err(to_persistence_error)?
+            .is_some()
+        {
+            let index = eavi.index() + 1;
+            eavi.set_index(index);
+        }
+
+        let index = sortable_index(eavi.index());
+        let content = String::from(eavi.content());
+        let mut batch = WriteBatch::default();
+        batch
+            .put(format!("{}{}", INDEX_PREFIX, index), "")
+            .map_err(to_persistence_error)?;
+        let keys = match self.indexing.strategy(&eavi.attribute()) {
+            EavIndexStrategy::Full => vec![
+                format!("{}{}\u{0}{}", ENTITY_PREFIX, eavi.entity(), index),
+                format!("{}{}\u{0}{}", VALUE_PREFIX, eavi.value(), index),
+            ],
+            EavIndexStrategy::Entity => vec![format!(
+                "{}{}\u{0}{}",
+                ENTITY_ONLY_PREFIX,
+                eavi.entity(),
+                index
+            )],
+            EavIndexStrategy::None => vec![format!("{}{}", UNINDEXED_PREFIX, index)],
+        };
+        for key in keys {
+            batch.put(key, &content).map_err(to_persistence_error)?;
+        }
+        self.db.write(batch).map_err(to_persistence_error)?;
+        Ok(Some(eavi))
+    }
+
+    fn fetch_eavi(
+        &self,
+        query: &EaviQuery,
+    ) -> PersistenceResult<BTreeSet<EntityAttributeValueIndex>> {
+        // Filters are arbitrary predicates, so we can't seek to what they match directly.
+        // Instead they get checked once per distinct entity (or value) in the keys and only
+        // the EAVIs of the matching ones get deserialized. The query itself then runs on those.
+        let (entities, matching_entities) =
+            self.matching_addresses(ENTITY_PREFIX, |entity| query.entity().check(entity))?;
+        let mut eavis = if matching_entities.len() < entities {
+            self.load_eavis(ENTITY_PREFIX, &matching_entities)?
+        } else {
+            let (_, matching_values) =
+                self.matching_addresses(VALUE_PREFIX, |value| query.value().check(value))?;
+            self.load_eavis(VALUE_PREFIX, &matching_values)?
+        };
+        // Classes that are indexed less can only be narrowed down by entity, if at all
+        let (_, matching_entities) =
+            self.matching_addresses(ENTITY_ONLY_PREFIX, |entity| query.entity().check(entity))?;
+        eavis.extend(self.load_eavis(ENTITY_ONLY_PREFIX, &matching_entities)?);
+        for (_, content) in self.scan(UNINDEXED_PREFIX) {
+            eavis.push(EntityAttributeValueIndex::try_from_content(
+                &JsonString::from_json(&to_string(&content)?),
+            )?);
+        }
+        Ok(query.run(eavis.into_iter()))
+    }
+}
+
+/// Compaction of a RocksDB CAS together with its EAV storage.
+#[derive(Clone)]
+pub struct RocksDbCompactor {
+    cas: RocksDbStorage,
+    eav: EavRocksDbStorage,
+}
+
+impl RocksDbCompactor {
+    pub fn new(cas: RocksDbStorage, eav: EavRocksDbStorage) -> Self {
+        RocksDbCompactor { cas, eav }
+    }
+}
+
+impl CompactStorage for RocksDbCompactor {
+    fn addresses(&self) -> HcResult<Vec<Address>> {
+        let mut addresses = Vec::new();
+        for (key, _) in self.cas.db.iterator(IteratorMode::Start) {
+            addresses.push(Address::from(to_string(&key)?));
+        }
+        Ok(addresses)
+    }
+
+    fn remove(&self, addresses: &[Address]) -> HcResult<()> {
+        let mut batch = WriteBatch::default();
+        for address in addresses {
+            batch
+                .delete(String::from(address.clone()))
+                .map_err(to_persistence_error)?;
+        }
+        self.cas.db.write(batch).map_err(to_persistence_error)?;
+        Ok(())
+    }
+
+    /// Quarantined content gets written to a file named by its address in the `quarantine`
+    /// directory next to the CAS database.
+    fn quarantine(&self, address: &Address) -> HcResult<()> {
+        let key = String::from(address.clone());
+        if let Some(content) = self.cas.db.get(&key).map_err(to_persistence_error)? {
+            let quarantine_path = self.cas.path.with_file_name("quarantine");
+            fs::create_dir_all(&quarantine_path)?;
+            fs::write(quarantine_path.join(&key), &*content)?;
+            self.cas.db.delete(&key).map_err(to_persistence_error)?;
+        }
+        Ok(())
+    }
+
+    fn vacuum(&self) -> HcResult<()> {
+        // Deletes only write tombstones, the space gets freed when compaction rewrites the
+        // files they are in
+        self.cas.db.compact_range::<&[u8], &[u8]>(None, None);
+        self.eav.db.compact_range::<&[u8], &[u8]>(None, None);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::crud_status::CrudStatus;
+    use holochain_json_api::json::RawString;
+    use holochain_persistence_api::{
+        cas::{
+            content::{AddressableContentTestSuite, ExampleAddressableContent},
+            storage::EavTestSuite,
+        },
+        eav::IndexFilter,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn rocksdb_content_round_trip_test() {
+        let temp = tempdir().unwrap();
+        let storage = RocksDbStorage::new(temp.path(), &RocksDbStorageOptions::default()).unwrap();
+        AddressableContentTestSuite::addressable_content_round_trip::<CrudStatus, RocksDbStorage>(
+            vec![CrudStatus::Live, CrudStatus::Deleted],
+            storage,
+        );
+    }
+
+    fn test_eav_storage() -> (tempfile::TempDir, EavRocksDbStorage) {
+        let temp = tempdir().unwrap();
+        let storage =
+            EavRocksDbStorage::new(temp.path(), &RocksDbStorageOptions::default()).unwrap();
+        (temp, storage)
+    }
+
+    #[test]
+    fn rocksdb_eav_round_trip() {
+        let (_temp, eav_storage) = test_eav_storage();
+        let entity =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("foo")))
+                .unwrap();
+        let attribute = Attribute::LinkTag("abc".to_string(), "favourite-color".to_string());
+        let value =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("blue")))
+                .unwrap();
+        EavTestSuite::test_round_trip(eav_storage, entity, attribute, value)
+    }
+
+    #[test]
+    fn rocksdb_eav_one_to_many() {
+        let (_temp, eav_storage) = test_eav_storage();
+        EavTestSuite::test_one_to_many::<ExampleAddressableContent, Attribute, EavRocksDbStorage>(
+            eav_storage,
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn rocksdb_eav_many_to_one() {
+        let (_temp, eav_storage) = test_eav_storage();
+        EavTestSuite::test_many_to_one::<ExampleAddressableContent, Attribute, EavRocksDbStorage>(
+            eav_storage,
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn rocksdb_eav_range() {
+        let (_temp, eav_storage) = test_eav_storage();
+        EavTestSuite::test_range::<ExampleAddressableContent, Attribute, EavRocksDbStorage>(
+            eav_storage,
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn rocksdb_eav_moves_colliding_indices() {
+        let (_temp, mut eav_storage) = test_eav_storage();
+        let entity = Address::from("entity");
+        let eavi =
+            EntityAttributeValueIndex::new_with_index(&entity, &Attribute::EntryHeader, &entity, 7)
+                .unwrap();
+        let first = eav_storage.add_eavi(&eavi).unwrap().unwrap();
+        let second = eav_storage.add_eavi(&eavi).unwrap().unwrap();
+        assert_eq!(first.index(), 7);
+        assert_eq!(second.index(), 8);
+
+        let query = EaviQuery::new(
+            Some(entity).into(),
+            None.into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        assert_eq!(eav_storage.fetch_eavi(&query).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rocksdb_eav_finds_eavis_of_every_indexing() {
+        let temp = tempdir().unwrap();
+        let mut eav_storage =
+            EavRocksDbStorage::new(temp.path(), &RocksDbStorageOptions::default())
+                .unwrap()
+                .with_indexing(EavIndexing {
+                    crud_status: Some(EavIndexStrategy::None),
+                    crud_link: Some(EavIndexStrategy::Entity),
+                    ..Default::default()
+                });
+        let (entity, value) = (Address::from("entity"), Address::from("value"));
+        for attribute in &[
+            Attribute::EntryHeader,
+            Attribute::CrudStatus,
+            Attribute::CrudLink,
+        ] {
+            let eavi = EntityAttributeValueIndex::new(&entity, attribute, &value).unwrap();
+            eav_storage.add_eavi(&eavi).unwrap();
+        }
+        assert_eq!(eav_storage.scan(VALUE_PREFIX).count(), 1);
+        assert_eq!(eav_storage.scan(UNINDEXED_PREFIX).count(), 1);
+
+        let by_entity = EaviQuery::new(
+            Some(entity.clone()).into(),
+            None.into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        assert_eq!(eav_storage.fetch_eavi(&by_entity).unwrap().len(), 3);
+        let by_value = EaviQuery::new(
+            None.into(),
+            None.into(),
+            Some(value).into(),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        assert_eq!(eav_storage.fetch_eavi(&by_value).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn sortable_index_keeps_order() {
+        let indices = vec![i64::min_value(), -5, -1, 0, 1, 42, i64::max_value()];
+        let encoded: Vec<String> = indices.iter().map(|i| sortable_index(*i)).collect();
+        let mut sorted = encoded.clone();
+        sorted.sort();
+        assert_eq!(encoded, sorted);
+    }
+
+    #[test]
+    fn storage_options_default_when_omitted() {
+        let options: RocksDbStorageOptions =
+            serde_json::from_str(r#"{"compaction_style": "universal"}"#).unwrap();
+        assert_eq!(options.compaction_style, RocksDbCompactionStyle::Universal);
+        assert_eq!(
+            options.write_buffer_size,
+            RocksDbStorageOptions::default().write_buffer_size
+        );
+    }
+
+    #[test]
+    fn rocksdb_compactor_removes_content() {
+        let temp = tempdir().unwrap();
+        let mut cas =
+            RocksDbStorage::new(temp.path().join("cas"), &RocksDbStorageOptions::default())
+                .unwrap();
+        let eav =
+            EavRocksDbStorage::new(temp.path().join("eav"), &RocksDbStorageOptions::default())
+                .unwrap();
+        let kept =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("kept")))
+                .unwrap();
+        let removed = ExampleAddressableContent::try_from_content(&JsonString::from(
+            RawString::from("removed"),
+        ))
+        .unwrap();
+        cas.add(&kept).unwrap();
+        cas.add(&removed).unwrap();
+
+        let compactor = RocksDbCompactor::new(cas.clone(), eav);
+        let mut addresses = compactor.addresses().unwrap();
+        addresses.sort();
+        let mut expected = vec![kept.address(), removed.address()];
+        expected.sort();
+        assert_eq!(addresses, expected);
+
+        compactor.remove(&[removed.address()]).unwrap();
+        compactor.vacuum().unwrap();
+        assert_eq!(compactor.addresses().unwrap(), vec![kept.address()]);
+        assert!(!cas.contains(&removed.address()).unwrap());
+        assert_eq!(cas.fetch(&kept.address()).unwrap(), Some(kept.content()));
+
+        compactor.quarantine(&kept.address()).unwrap();
+        assert!(compactor.addresses().unwrap().is_empty());
+        assert!(!cas.contains(&kept.address()).unwrap());
+        assert!(temp
+            .path()
+            .join("quarantine")
+            .join(String::from(kept.address()))
+            .exists());
+    }
+}
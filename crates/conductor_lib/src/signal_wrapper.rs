@@ -1,18 +1,88 @@
 use holochain_core::{context::InstanceStats, signal::Signal};
 use holochain_json_api::{error::JsonError, json::JsonString};
+use holochain_persistence_api::cas::content::Address;
 use std::collections::HashMap;
 
 /// This enum wraps a Signal from core before serializing and sending over
 /// an interface to the UI or other client.
-#[derive(Serialize, Deserialize, Debug, DefaultJson)]
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
 #[allow(clippy::large_enum_variant)]
 #[serde(tag = "type")]
 pub enum SignalWrapper {
     InstanceSignal {
         signal: Signal,
         instance_id: String,
+        /// Number of a user signal in the instance's signal history, to replay from
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sequence: Option<u64>,
     },
     InstanceStats {
         instance_stats: HashMap<String, InstanceStats>,
     },
+    DnaUpdate {
+        instance_id: String,
+        progress: DnaUpdateProgress,
+    },
+    InstanceRestart {
+        instance_id: String,
+        event: RestartEvent,
+    },
+}
+
+/// Steps of an instance's update to a new DNA version, as reported to admin interfaces
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "step")]
+pub enum DnaUpdateProgress {
+    /// The instance got stopped, zome calls are refused until it is resumed
+    Paused,
+    /// The instance runs the new DNA and its chain got migrated
+    ChainMigrated { old_dna: Address, new_dna: Address },
+    /// Held entries whose validation rules changed got validated again
+    Revalidated { checked: usize, invalid: usize },
+    /// The instance is back to its previous state of activity
+    Resumed,
+    /// The update failed and the instance got restored with its previous DNA
+    Failed { error: String },
+}
+
+/// Why the conductor restarts an instance
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashCause {
+    ActionLoopDied,
+    NetworkDied,
+}
+
+impl std::fmt::Display for CrashCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CrashCause::ActionLoopDied => write!(f, "its action loop died"),
+            CrashCause::NetworkDied => write!(f, "its network thread died"),
+        }
+    }
+}
+
+/// Steps of the restart of a crashed instance, as reported to admin interfaces
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "step")]
+pub enum RestartEvent {
+    /// The instance crashed and gets restarted once the backoff is over
+    Crashed {
+        cause: CrashCause,
+        restart: u32,
+        backoff_ms: u64,
+    },
+    Restarted {
+        restart: u32,
+    },
+    /// The restart failed, it gets tried again after the next backoff
+    Failed {
+        restart: u32,
+        error: String,
+    },
+    /// The instance crashed after its last allowed restart and got stopped
+    GaveUp {
+        cause: CrashCause,
+        restarts: u32,
+    },
 }
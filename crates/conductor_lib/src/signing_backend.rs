@@ -0,0 +1,293 @@
+//! Backends for agent signatures.
+//!
+//! Every signature an agent produces - entry provenances, signed zome calls, capability
+//! delegations, the key check at genesis and the sim2h JoinSpace handshake - is requested
+//! through the `agent/sign` method of the instance's conductor API. That method is backed by a
+//! [SigningBackend], so the private key does not have to sit in a keystore file:
+//! * [KeyBundleBackend] signs with a key bundle in secure memory. The bundle can come from a
+//!   keystore file, a seed file or a seed kept in the OS keychain (see [seed_from_os_keychain]).
+//! * [KeystoreBackend] signs through the agent's [Keystore], so signing fails with
+//!   [HolochainError::KeystoreLocked] while the keystore is locked.
+//! * [Pkcs11Backend] lets a PKCS#11 token or HSM sign, the key never leaves the device.
+use crate::{
+    conductor::passphrase_manager::PassphraseManager,
+    keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
+};
+use holochain_core_types::{
+    agent::Base32,
+    error::{HcResult, HolochainError},
+    signature::{Provenance, Signature},
+};
+use holochain_dpki::{
+    audit::verify_audited, key_bundle::KeyBundle, password_encryption::PwHashConfig, utils::Verify,
+};
+use holochain_locksmith::Mutex;
+use lib3h_sodium::secbuf::SecBuf;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::Arc,
+};
+
+/// Something that holds an agent's private signing key and can sign with it.
+pub trait SigningBackend: Send + Sync {
+    /// The HCID encoded public signing key, i.e. the agent's address
+    fn public_address(&self) -> Base32;
+
+    /// Signs the payload, returning the base64 encoded signature
+    fn sign(&self, payload: &str) -> HcResult<Signature>;
+
+    /// Makes sure the backend actually holds the key for its public address by
+    /// signing a random challenge and verifying the signature.
+    fn check_key(&self) -> HcResult<()> {
+        let mut challenge = SecBuf::with_insecure(32);
+        challenge.randomize();
+        let challenge = base64::encode(&**challenge.read_lock());
+        let signature = self.sign(&challenge)?;
+        let provenance = Provenance::new(self.public_address().into(), signature);
+        if verify_audited(
+            &provenance,
+            challenge,
+            "signing_backend_key_check",
+            "signing backend setup",
+        )? {
+            Ok(())
+        } else {
+            Err(HolochainError::ConfigError(format!(
+                "Signing backend does not hold the key for {}",
+                self.public_address()
+            )))
+        }
+    }
+}
+
+/// Signs with a key bundle held in secure memory.
+pub struct KeyBundleBackend {
+    keybundle: Arc<Mutex<KeyBundle>>,
+}
+
+impl KeyBundleBackend {
+    pub fn new(keybundle: Arc<Mutex<KeyBundle>>) -> Self {
+        KeyBundleBackend { keybundle }
+    }
+}
+
+impl SigningBackend for KeyBundleBackend {
+    fn public_address(&self) -> Base32 {
+        self.keybundle.lock().unwrap().get_id()
+    }
+
+    fn sign(&self, payload: &str) -> HcResult<Signature> {
+        let mut message = SecBuf::with_insecure_from_string(payload.to_string());
+        // Get write lock on the key since we need a mutuble reference to lock the
+        // secure memory the key is in:
+        let mut message_signature = self.keybundle.lock().unwrap().sign(&mut message)?;
+        let message_signature = message_signature.read_lock();
+        Ok(Signature::from(base64::encode(&**message_signature)))
+    }
+}
+
+/// Signs with the primary keybundle of a keystore. Other than [KeyBundleBackend] this does not
+/// keep its own copy of the key, so locking the keystore takes the key out of memory.
+pub struct KeystoreBackend {
+    keystore: Arc<Mutex<Keystore>>,
+    public_address: Base32,
+}
+
+impl KeystoreBackend {
+    pub fn new(keystore: Arc<Mutex<Keystore>>, public_address: Base32) -> Self {
+        KeystoreBackend {
+            keystore,
+            public_address,
+        }
+    }
+}
+
+impl SigningBackend for KeystoreBackend {
+    fn public_address(&self) -> Base32 {
+        self.public_address.clone()
+    }
+
+    fn sign(&self, payload: &str) -> HcResult<Signature> {
+        self.keystore
+            .lock()
+            .unwrap()
+            .sign_with_keybundle(PRIMARY_KEYBUNDLE_ID, payload.to_string())
+    }
+}
+
+fn run_command(program: &str, args: &[&str]) -> HcResult<Vec<u8>> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| HolochainError::ErrorGeneric(format!("Could not run {}: {}", program, e)))?;
+    if !output.status.success() {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Reads an agent's root seed (stored base64 encoded) from the OS keychain:
+/// the macOS Keychain through `security` or the freedesktop Secret Service through `secret-tool`.
+pub fn seed_from_os_keychain(service: &str, account: &str) -> HcResult<Vec<u8>> {
+    let output = if cfg!(target_os = "macos") {
+        run_command(
+            "security",
+            &["find-generic-password", "-s", service, "-a", account, "-w"],
+        )
+    } else if cfg!(target_os = "linux") {
+        run_command(
+            "secret-tool",
+            &["lookup", "service", service, "account", account],
+        )
+    } else {
+        Err(HolochainError::NotImplemented(
+            "OS keychain key backend is only supported on macOS and Linux".to_string(),
+        ))
+    }?;
+    let encoded = String::from_utf8_lossy(&output).trim().to_string();
+    base64::decode(&encoded).map_err(|e| {
+        HolochainError::ErrorGeneric(format!("Could not decode seed from OS keychain: {}", e))
+    })
+}
+
+/// Creates the agent's keystore from the seed in the OS keychain.
+/// The keystore only lives in memory, nothing gets written to disk.
+pub fn keystore_from_os_keychain(
+    service: &str,
+    account: &str,
+    hash_config: Option<PwHashConfig>,
+) -> HcResult<(Keystore, Base32)> {
+    let seed = seed_from_os_keychain(service, account)?;
+    Keystore::new_from_seed(&seed, hash_config)
+}
+
+/// Name of the environment variable the PIN is handed to pkcs11-tool with,
+/// so it does not show up in the process list.
+const PKCS11_PIN_ENV: &str = "HC_PKCS11_PIN";
+
+/// Signs on a PKCS#11 token or HSM using OpenSC's `pkcs11-tool` with the EDDSA mechanism.
+pub struct Pkcs11Backend {
+    module: String,
+    key_id: String,
+    slot: Option<u64>,
+    public_address: Base32,
+    passphrase_manager: Arc<PassphraseManager>,
+}
+
+impl Pkcs11Backend {
+    pub fn new(
+        module: String,
+        key_id: String,
+        slot: Option<u64>,
+        public_address: Base32,
+        passphrase_manager: Arc<PassphraseManager>,
+    ) -> Self {
+        Pkcs11Backend {
+            module,
+            key_id,
+            slot,
+            public_address,
+            passphrase_manager,
+        }
+    }
+}
+
+impl SigningBackend for Pkcs11Backend {
+    fn public_address(&self) -> Base32 {
+        self.public_address.clone()
+    }
+
+    fn sign(&self, payload: &str) -> HcResult<Signature> {
+        let pin = self.passphrase_manager.get_passphrase()?;
+        let pin = String::from_utf8_lossy(&**pin.read_lock()).to_string();
+        let slot = self.slot.map(|slot| slot.to_string());
+        let mut args = vec!["--module", self.module.as_str()];
+        if let Some(ref slot) = slot {
+            args.extend_from_slice(&["--slot", slot.as_str()]);
+        }
+        let pin_arg = format!("env:{}", PKCS11_PIN_ENV);
+        args.extend_from_slice(&[
+            "--login",
+            "--pin",
+            pin_arg.as_str(),
+            "--sign",
+            "--mechanism",
+            "EDDSA",
+            "--id",
+            self.key_id.as_str(),
+        ]);
+        let mut child = Command::new("pkcs11-tool")
+            .args(&args)
+            .env(PKCS11_PIN_ENV, pin)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                HolochainError::ErrorGeneric(format!("Could not run pkcs11-tool: {}", e))
+            })?;
+        child
+            .stdin
+            .as_mut()
+            .expect("stdin is piped")
+            .write_all(payload.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "PKCS#11 signing failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(Signature::from(base64::encode(&output.stdout)))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{key_loaders::test_keystore, keystore::test_hash_config};
+    use holochain_dpki::SEED_SIZE;
+
+    #[test]
+    fn keybundle_backend_signs_verifiably() {
+        let mut keystore = test_keystore("alice");
+        let keybundle = keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID).unwrap();
+        let backend = KeyBundleBackend::new(Arc::new(Mutex::new(keybundle)));
+
+        let signature = backend.sign("some payload").unwrap();
+        let provenance = Provenance::new(backend.public_address().into(), signature);
+        assert!(provenance.verify("some payload".to_string()).unwrap());
+        assert_eq!(backend.check_key(), Ok(()));
+    }
+
+    #[test]
+    fn check_key_fails_for_wrong_public_address() {
+        let mut keystore = test_keystore("alice");
+        let keybundle = keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID).unwrap();
+        let bob_address = Keystore::new_from_seed(&[1; SEED_SIZE], test_hash_config())
+            .unwrap()
+            .1;
+
+        struct WrongAddress(KeyBundleBackend, Base32);
+        impl SigningBackend for WrongAddress {
+            fn public_address(&self) -> Base32 {
+                self.1.clone()
+            }
+            fn sign(&self, payload: &str) -> HcResult<Signature> {
+                self.0.sign(payload)
+            }
+        }
+
+        let backend = WrongAddress(
+            KeyBundleBackend::new(Arc::new(Mutex::new(keybundle))),
+            bob_address,
+        );
+        assert!(backend.check_key().is_err());
+    }
+}
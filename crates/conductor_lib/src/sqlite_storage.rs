@@ -0,0 +1,641 @@
+//! SQLite based implementations of the content addressable storage and the EAV storage.
+//!
+//! Both keep their data in a single SQLite database file each, so they don't need one file
+//! per entry like the file storages nor hold everything in memory like the memory and pickle
+//! storages. The EAV table has indices on entity, value and index, which lets meta queries
+//! only load the rows of the entities (or values) they are about instead of the whole table.
+//! Attribute classes that are rarely queried can be kept out of those indices with
+//! [EavIndexing](crate::eav_indexing::EavIndexing).
+use crate::eav_indexing::{EavIndexStrategy, EavIndexing};
+use holochain_core::storage_compaction::CompactStorage;
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::HcResult,
+};
+use holochain_json_api::json::JsonString;
+use holochain_locksmith::Mutex;
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::EntityAttributeValueStorage,
+    error::{PersistenceError, PersistenceResult},
+    reporting::ReportStorage,
+};
+use rusqlite::{Connection, OptionalExtension, NO_PARAMS};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use uuid::Uuid;
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn to_persistence_error(error: rusqlite::Error) -> PersistenceError {
+    PersistenceError::ErrorGeneric(format!("SQLite storage error: {}", error))
+}
+
+fn open_database(path: &Path, schema: &str) -> PersistenceResult<Connection> {
+    let connection = Connection::open(path).map_err(to_persistence_error)?;
+    connection
+        .busy_timeout(BUSY_TIMEOUT)
+        .and_then(|_| connection.execute_batch(schema))
+        .map_err(to_persistence_error)?;
+    Ok(connection)
+}
+
+/// Content addressable storage that keeps all content in one table of a SQLite database.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    id: Uuid,
+    path: PathBuf,
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl fmt::Debug for SqliteStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SqliteStorage")
+            .field("id", &self.id)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl SqliteStorage {
+    /// Opens the database at the given path, creating it if it doesn't exist yet.
+    pub fn new<P: AsRef<Path>>(path: P) -> PersistenceResult<SqliteStorage> {
+        let path = path.as_ref().to_path_buf();
+        let connection = open_database(
+            &path,
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS content (
+                 address TEXT PRIMARY KEY NOT NULL,
+                 content TEXT NOT NULL
+             );",
+        )?;
+        Ok(SqliteStorage {
+            id: Uuid::new_v4(),
+            path,
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+}
+
+impl ContentAddressableStorage for SqliteStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO content (address, content) VALUES (?1, ?2)",
+                &[
+                    String::from(content.address()),
+                    String::from(content.content()),
+                ],
+            )
+            .map_err(to_persistence_error)?;
+        Ok(())
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        Ok(self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT 1 FROM content WHERE address = ?1",
+                &[String::from(address.clone())],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(to_persistence_error)?
+            .is_some())
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        let content: Option<String> = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT content FROM content WHERE address = ?1",
+                &[String::from(address.clone())],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(to_persistence_error)?;
+        Ok(content.map(|content| JsonString::from_json(&content)))
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl ReportStorage for SqliteStorage {}
+
+/// EAV storage that keeps every EAVI in a row of a SQLite table, with its entity, attribute,
+/// value and index in separate columns. Which table depends on the [EavIndexing] of its
+/// attribute: `eavi` has indices on entity and value, `eavi_by_entity` only on entity and
+/// `eavi_unindexed` on neither, so that writing to it doesn't touch any index.
+#[derive(Clone)]
+pub struct EavSqliteStorage {
+    path: PathBuf,
+    connection: Arc<Mutex<Connection>>,
+    indexing: EavIndexing,
+}
+
+impl fmt::Debug for EavSqliteStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EavSqliteStorage")
+            .field("path", &self.path)
+            .field("indexing", &self.indexing)
+            .finish()
+    }
+}
+
+impl EavSqliteStorage {
+    /// Opens the database at the given path, creating it if it doesn't exist yet.
+    pub fn new<P: AsRef<Path>>(path: P) -> PersistenceResult<EavSqliteStorage> {
+        let path = path.as_ref().to_path_buf();
+        let connection = open_database(
+            &path,
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS eavi (
+                 idx INTEGER PRIMARY KEY NOT NULL,
+                 entity TEXT NOT NULL,
+                 attribute TEXT NOT NULL,
+                 value TEXT NOT NULL,
+                 content TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS eavi_entity ON eavi (entity, attribute);
+             CREATE INDEX IF NOT EXISTS eavi_value ON eavi (value, attribute);
+             CREATE TABLE IF NOT EXISTS eavi_by_entity (
+                 idx INTEGER PRIMARY KEY NOT NULL,
+                 entity TEXT NOT NULL,
+                 attribute TEXT NOT NULL,
+                 value TEXT NOT NULL,
+                 content TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS eavi_by_entity_entity
+                 ON eavi_by_entity (entity, attribute);
+             CREATE TABLE IF NOT EXISTS eavi_unindexed (
+                 idx INTEGER PRIMARY KEY NOT NULL,
+                 entity TEXT NOT NULL,
+                 attribute TEXT NOT NULL,
+                 value TEXT NOT NULL,
+                 content TEXT NOT NULL
+             );",
+        )?;
+        Ok(EavSqliteStorage {
+            path,
+            connection: Arc::new(Mutex::new(connection)),
+            indexing: EavIndexing::default(),
+        })
+    }
+
+    /// Sets how EAVIs added from now on get indexed. EAVIs that were added with a different
+    /// indexing before are still found.
+    pub fn with_indexing(mut self, indexing: EavIndexing) -> Self {
+        self.indexing = indexing;
+        self
+    }
+
+    fn load_column(connection: &Connection, sql: &str) -> PersistenceResult<Vec<String>> {
+        let mut statement = connection.prepare(sql).map_err(to_persistence_error)?;
+        let rows = statement
+            .query_map(NO_PARAMS, |row| row.get(0))
+            .map_err(to_persistence_error)?;
+        rows.collect::<Result<Vec<String>, _>>()
+            .map_err(to_persistence_error)
+    }
+
+    fn load_eavis(
+        connection: &Connection,
+        sql: &str,
+        keys: &[String],
+    ) -> PersistenceResult<Vec<EntityAttributeValueIndex>> {
+        let mut statement = connection.prepare(sql).map_err(to_persistence_error)?;
+        let mut eavis = Vec::new();
+        for key in keys {
+            let rows = statement
+                .query_map(&[key], |row| row.get(0))
+                .map_err(to_persistence_error)?;
+            for content in rows {
+                let content: String = content.map_err(to_persistence_error)?;
+                eavis.push(EntityAttributeValueIndex::try_from_content(
+                    &JsonString::from_json(&content),
+                )?);
+            }
+        }
+        Ok(eavis)
+    }
+
+    fn load_all(
+        connection: &Connection,
+        table: &str,
+    ) -> PersistenceResult<Vec<EntityAttributeValueIndex>> {
+        Self::load_column(connection, &format!("SELECT content FROM {}", table))?
+            .iter()
+            .map(|content| {
+                EntityAttributeValueIndex::try_from_content(&JsonString::from_json(content))
+            })
+            .collect()
+    }
+
+    /// Loads the EAVIs of the given table that the query could match, using the entity index
+    /// if the entity filter is selective and the value index if the table has one.
+    fn load_candidates(
+        connection: &Connection,
+        table: &str,
+        by_value: bool,
+        query: &EaviQuery,
+    ) -> PersistenceResult<Vec<EntityAttributeValueIndex>> {
+        // Filters are arbitrary predicates, so we can't hand them to SQLite. But checking
+        // them against the distinct entities (or values), which come straight from the index,
+        // is much cheaper than loading every row. Only the rows of the matching ones get
+        // loaded and the query itself then runs on those.
+        let entities = Self::load_column(
+            connection,
+            &format!("SELECT DISTINCT entity FROM {}", table),
+        )?;
+        let matching_entities: Vec<String> = entities
+            .iter()
+            .filter(|entity| query.entity().check(Address::from(entity.as_str())))
+            .cloned()
+            .collect();
+        if matching_entities.len() < entities.len() {
+            Self::load_eavis(
+                connection,
+                &format!("SELECT content FROM {} WHERE entity = ?1", table),
+                &matching_entities,
+            )
+        } else if by_value {
+            let values =
+                Self::load_column(connection, &format!("SELECT DISTINCT value FROM {}", table))?;
+            let matching_values: Vec<String> = values
+                .into_iter()
+                .filter(|value| query.value().check(Address::from(value.as_str())))
+                .collect();
+            Self::load_eavis(
+                connection,
+                &format!("SELECT content FROM {} WHERE value = ?1", table),
+                &matching_values,
+            )
+        } else {
+            Self::load_all(connection, table)
+        }
+    }
+}
+
+impl EntityAttributeValueStorage<Attribute> for EavSqliteStorage {
+    fn add_eavi(
+        &mut self,
+        eavi: &EntityAttributeValueIndex,
+    ) -> PersistenceResult<Option<EntityAttributeValueIndex>> {
+        let connection = self.connection.lock().unwrap();
+        // Like the other storages we make room for EAVIs that were added with the same index
+        // by moving the new one to the next free index.
+        let mut eavi = eavi.clone();
+        while connection
+            .query_row(
+                "SELECT 1 FROM eavi WHERE idx = ?1 \
+                 UNION ALL SELECT 1 FROM eavi_by_entity WHERE idx = ?1 \
+                 UNION ALL SELECT 1 FROM eavi_unindexed WHERE idx = ?1",
+                &[eavi.index()],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(to_persistence_error)?
+            .is_some()
+        {
+            let index = eavi.index() + 1;
+            eavi.set_index(index);
+        }
+        let table = match self.indexing.strategy(&eavi.attribute()) {
+            EavIndexStrategy::Full => "eavi",
+            EavIndexStrategy::Entity => "eavi_by_entity",
+            EavIndexStrategy::None => "eavi_unindexed",
+        };
+        connection
+            .execute(
+                &format!(
+                    "INSERT INTO {} (idx, entity, attribute, value, content) \
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    table
+                ),
+                &[
+                    &eavi.index() as &dyn rusqlite::ToSql,
+                    &String::from(eavi.entity()),
+                    &eavi.attribute().to_string(),
+                    &String::from(eavi.value()),
+                    &String::from(eavi.content()),
+                ],
+            )
+            .map_err(to_persistence_error)?;
+        Ok(Some(eavi))
+    }
+
+    fn fetch_eavi(
+        &self,
+        query: &EaviQuery,
+    ) -> PersistenceResult<BTreeSet<EntityAttributeValueIndex>> {
+        let connection = self.connection.lock().unwrap();
+        let mut eavis = Self::load_candidates(&connection, "eavi", true, query)?;
+        eavis.extend(Self::load_candidates(
+            &connection,
+            "eavi_by_entity",
+            false,
+            query,
+        )?);
+        eavis.extend(Self::load_all(&connection, "eavi_unindexed")?);
+        Ok(query.run(eavis.into_iter()))
+    }
+}
+
+/// Compaction of a SQLite CAS together with its EAV storage.
+#[derive(Clone)]
+pub struct SqliteCompactor {
+    cas: SqliteStorage,
+    eav: EavSqliteStorage,
+}
+
+impl SqliteCompactor {
+    pub fn new(cas: SqliteStorage, eav: EavSqliteStorage) -> Self {
+        SqliteCompactor { cas, eav }
+    }
+}
+
+impl CompactStorage for SqliteCompactor {
+    fn addresses(&self) -> HcResult<Vec<Address>> {
+        let connection = self.cas.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT address FROM content")
+            .map_err(to_persistence_error)?;
+        let addresses = statement
+            .query_map(NO_PARAMS, |row| row.get(0))
+            .map_err(to_persistence_error)?
+            .map(|address: rusqlite::Result<String>| address.map(Address::from))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(to_persistence_error)?;
+        Ok(addresses)
+    }
+
+    fn remove(&self, addresses: &[Address]) -> HcResult<()> {
+        let mut connection = self.cas.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(to_persistence_error)?;
+        for address in addresses {
+            transaction
+                .execute(
+                    "DELETE FROM content WHERE address = ?1",
+                    &[String::from(address.clone())],
+                )
+                .map_err(to_persistence_error)?;
+        }
+        transaction.commit().map_err(to_persistence_error)?;
+        Ok(())
+    }
+
+    fn quarantine(&self, address: &Address) -> HcResult<()> {
+        let mut connection = self.cas.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(to_persistence_error)?;
+        transaction
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS quarantine (
+                     address TEXT NOT NULL,
+                     content TEXT NOT NULL
+                 );",
+            )
+            .and_then(|_| {
+                transaction.execute(
+                    "INSERT INTO quarantine (address, content) \
+                     SELECT address, content FROM content WHERE address = ?1",
+                    &[String::from(address.clone())],
+                )
+            })
+            .and_then(|_| {
+                transaction.execute(
+                    "DELETE FROM content WHERE address = ?1",
+                    &[String::from(address.clone())],
+                )
+            })
+            .map_err(to_persistence_error)?;
+        transaction.commit().map_err(to_persistence_error)?;
+        Ok(())
+    }
+
+    fn vacuum(&self) -> HcResult<()> {
+        // VACUUM can't shrink the file while the write-ahead log still holds pages
+        self.cas
+            .connection
+            .lock()
+            .unwrap()
+            .execute_batch("PRAGMA wal_checkpoint(TRUNCATE); VACUUM;")
+            .map_err(to_persistence_error)?;
+        self.eav
+            .connection
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "REINDEX eavi; REINDEX eavi_by_entity; \
+                 PRAGMA wal_checkpoint(TRUNCATE); VACUUM;",
+            )
+            .map_err(to_persistence_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::crud_status::CrudStatus;
+    use holochain_json_api::json::RawString;
+    use holochain_persistence_api::{
+        cas::{
+            content::{AddressableContentTestSuite, ExampleAddressableContent},
+            storage::EavTestSuite,
+        },
+        eav::IndexFilter,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn sqlite_content_round_trip_test() {
+        let temp = tempdir().unwrap();
+        let storage = SqliteStorage::new(temp.path().join("cas.sqlite")).unwrap();
+        AddressableContentTestSuite::addressable_content_round_trip::<CrudStatus, SqliteStorage>(
+            vec![CrudStatus::Live, CrudStatus::Deleted],
+            storage,
+        );
+    }
+
+    #[test]
+    fn sqlite_content_survives_reopening() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("cas.sqlite");
+        let content = ExampleAddressableContent::try_from_content(&JsonString::from(
+            RawString::from("persisted"),
+        ))
+        .unwrap();
+        SqliteStorage::new(&path).unwrap().add(&content).unwrap();
+
+        let storage = SqliteStorage::new(&path).unwrap();
+        assert!(storage.contains(&content.address()).unwrap());
+        assert_eq!(
+            storage.fetch(&content.address()).unwrap(),
+            Some(content.content())
+        );
+    }
+
+    fn test_eav_storage() -> (tempfile::TempDir, EavSqliteStorage) {
+        let temp = tempdir().unwrap();
+        let storage = EavSqliteStorage::new(temp.path().join("eav.sqlite")).unwrap();
+        (temp, storage)
+    }
+
+    #[test]
+    fn sqlite_eav_round_trip() {
+        let (_temp, eav_storage) = test_eav_storage();
+        let entity =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("foo")))
+                .unwrap();
+        let attribute = Attribute::LinkTag("abc".to_string(), "favourite-color".to_string());
+        let value =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("blue")))
+                .unwrap();
+        EavTestSuite::test_round_trip(eav_storage, entity, attribute, value)
+    }
+
+    #[test]
+    fn sqlite_eav_one_to_many() {
+        let (_temp, eav_storage) = test_eav_storage();
+        EavTestSuite::test_one_to_many::<ExampleAddressableContent, Attribute, EavSqliteStorage>(
+            eav_storage,
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn sqlite_eav_many_to_one() {
+        let (_temp, eav_storage) = test_eav_storage();
+        EavTestSuite::test_many_to_one::<ExampleAddressableContent, Attribute, EavSqliteStorage>(
+            eav_storage,
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn sqlite_eav_range() {
+        let (_temp, eav_storage) = test_eav_storage();
+        EavTestSuite::test_range::<ExampleAddressableContent, Attribute, EavSqliteStorage>(
+            eav_storage,
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn sqlite_eav_moves_colliding_indices() {
+        let (_temp, mut eav_storage) = test_eav_storage();
+        let entity = Address::from("entity");
+        let eavi =
+            EntityAttributeValueIndex::new_with_index(&entity, &Attribute::EntryHeader, &entity, 7)
+                .unwrap();
+        let first = eav_storage.add_eavi(&eavi).unwrap().unwrap();
+        let second = eav_storage.add_eavi(&eavi).unwrap().unwrap();
+        assert_eq!(first.index(), 7);
+        assert_eq!(second.index(), 8);
+
+        let query = EaviQuery::new(
+            Some(entity).into(),
+            None.into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        assert_eq!(eav_storage.fetch_eavi(&query).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn sqlite_eav_finds_eavis_of_every_indexing() {
+        let temp = tempdir().unwrap();
+        let mut eav_storage = EavSqliteStorage::new(temp.path().join("eav.sqlite"))
+            .unwrap()
+            .with_indexing(EavIndexing {
+                crud_status: Some(EavIndexStrategy::None),
+                crud_link: Some(EavIndexStrategy::Entity),
+                ..Default::default()
+            });
+        let (entity, value) = (Address::from("entity"), Address::from("value"));
+        for attribute in &[
+            Attribute::EntryHeader,
+            Attribute::CrudStatus,
+            Attribute::CrudLink,
+        ] {
+            let eavi = EntityAttributeValueIndex::new(&entity, attribute, &value).unwrap();
+            eav_storage.add_eavi(&eavi).unwrap();
+        }
+        let unindexed: i64 = eav_storage
+            .connection
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM eavi_unindexed", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(unindexed, 1);
+
+        let by_entity = EaviQuery::new(
+            Some(entity.clone()).into(),
+            None.into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        assert_eq!(eav_storage.fetch_eavi(&by_entity).unwrap().len(), 3);
+        let by_value = EaviQuery::new(
+            None.into(),
+            None.into(),
+            Some(value).into(),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        assert_eq!(eav_storage.fetch_eavi(&by_value).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn sqlite_compactor_removes_content() {
+        let temp = tempdir().unwrap();
+        let mut cas = SqliteStorage::new(temp.path().join("cas.sqlite")).unwrap();
+        let eav = EavSqliteStorage::new(temp.path().join("eav.sqlite")).unwrap();
+        let kept =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("kept")))
+                .unwrap();
+        let removed = ExampleAddressableContent::try_from_content(&JsonString::from(
+            RawString::from("removed"),
+        ))
+        .unwrap();
+        cas.add(&kept).unwrap();
+        cas.add(&removed).unwrap();
+
+        let compactor = SqliteCompactor::new(cas.clone(), eav);
+        let mut addresses = compactor.addresses().unwrap();
+        addresses.sort();
+        let mut expected = vec![kept.address(), removed.address()];
+        expected.sort();
+        assert_eq!(addresses, expected);
+
+        compactor.remove(&[removed.address()]).unwrap();
+        compactor.vacuum().unwrap();
+        assert_eq!(compactor.addresses().unwrap(), vec![kept.address()]);
+        assert!(!cas.contains(&removed.address()).unwrap());
+        assert_eq!(cas.fetch(&kept.address()).unwrap(), Some(kept.content()));
+
+        compactor.quarantine(&kept.address()).unwrap();
+        assert!(compactor.addresses().unwrap().is_empty());
+        assert!(!cas.contains(&kept.address()).unwrap());
+    }
+}
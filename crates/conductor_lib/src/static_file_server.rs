@@ -1,11 +1,24 @@
+//! Serving UI bundles over HTTP.
+//!
+//! UI interfaces that are configured with the same port share one server, which picks the
+//! UI to serve by the host name and path of each request (see [find_route]). HTML pages get
+//! the connection of the UI's DNA interface injected as `window.__HC_CONNECTION__`, which is
+//! also served as JSON at [DNA_CONFIG_ROUTE] under the UI's path, so that UIs don't need to
+//! know which port the conductor serves their hApp on.
 use crate::{
-    config::{InterfaceConfiguration, UiBundleConfiguration, UiInterfaceConfiguration},
+    config::{
+        InterfaceConfiguration, InterfaceDriver, UiBundleConfiguration, UiInterfaceConfiguration,
+    },
     error::HolochainResult,
 };
 use hyper::{http::uri, Request};
+use std::path::{Component, Path, PathBuf};
 
 pub const DNA_CONFIG_ROUTE: &str = "/_dna_connections.json";
 
+/// Global variable that holds the injected connection in served HTML pages
+pub const INJECTED_CONNECTION_VARIABLE: &str = "__HC_CONNECTION__";
+
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
 pub fn redirect_request_to_root<T>(req: &mut Request<T>) {
     let mut original_parts: uri::Parts = req.uri().to_owned().into();
@@ -13,13 +26,142 @@ pub fn redirect_request_to_root<T>(req: &mut Request<T>) {
     *req.uri_mut() = uri::Uri::from_parts(original_parts).unwrap();
 }
 
+/// Describes the DNA interface a UI connects to. `url` is the interface's address on the
+/// host the UI got requested from, if the interface is reachable from browsers.
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
-pub fn dna_connections_response(config: &Option<InterfaceConfiguration>) -> serde_json::Value {
-    let interface = match config {
-        Some(config) => json!(config),
-        None => serde_json::Value::Null,
+pub fn dna_connections_response(
+    config: &Option<InterfaceConfiguration>,
+    instance_id: &Option<String>,
+    host: &str,
+) -> serde_json::Value {
+    let (interface, url) = match config {
+        Some(config) => (json!(config), json!(interface_url(config, host))),
+        None => (serde_json::Value::Null, serde_json::Value::Null),
+    };
+    json!({ "dna_interface": interface, "url": url, "instance_id": instance_id })
+}
+
+/// URL under which browsers reach the given interface on the given host
+pub fn interface_url(interface: &InterfaceConfiguration, host: &str) -> Option<String> {
+    let tls = interface.tls.is_some();
+    let (scheme, port) = match interface.driver {
+        InterfaceDriver::Websocket { port } if tls => ("wss", port),
+        InterfaceDriver::Websocket { port } | InterfaceDriver::Graphql { port } => ("ws", port),
+        InterfaceDriver::Http { port } if tls => ("https", port),
+        InterfaceDriver::Http { port } => ("http", port),
+        _ => return None,
     };
-    json!({ "dna_interface": interface })
+    Some(format!("{}://{}:{}", scheme, host, port))
+}
+
+/// Adds a script that sets [INJECTED_CONNECTION_VARIABLE] to the given connection at the end
+/// of the page's head, or at its start if it has no head.
+pub fn inject_connection(html: &str, connection: &serde_json::Value) -> String {
+    // "</" would end the script early if it came up in a string of the connection
+    let script = format!(
+        "<script>window.{} = {};</script>",
+        INJECTED_CONNECTION_VARIABLE,
+        connection.to_string().replace("</", "<\\/")
+    );
+    let at = html.to_ascii_lowercase().find("</head>").unwrap_or(0);
+    format!("{}{}{}", &html[..at], script, &html[at..])
+}
+
+/// A UI bundle served by a server that might be shared with other UI interfaces
+#[derive(Clone, Debug, PartialEq)]
+pub struct UiRoute {
+    pub interface_id: String,
+    /// Lowercase host name, or None for requests of any host
+    pub host: Option<String>,
+    pub path_prefix: String,
+    pub root_dir: PathBuf,
+    pub reroute_to_root: bool,
+    pub connected_dna_interface: Option<InterfaceConfiguration>,
+    pub instance_id: Option<String>,
+}
+
+impl UiRoute {
+    pub fn from_configs(
+        config: &UiInterfaceConfiguration,
+        bundle_config: &UiBundleConfiguration,
+        connected_dna_interface: Option<InterfaceConfiguration>,
+    ) -> Self {
+        UiRoute {
+            interface_id: config.id.clone(),
+            host: config.host.as_ref().map(|host| host.to_lowercase()),
+            path_prefix: config.path_prefix(),
+            root_dir: PathBuf::from(&bundle_config.root_dir),
+            reroute_to_root: config.reroute_to_root,
+            connected_dna_interface,
+            instance_id: config.instance.clone(),
+        }
+    }
+
+    /// The path within the bundle if the given request path is under this route's prefix
+    fn path_in_bundle(&self, path: &str) -> Option<String> {
+        if self.path_prefix == "/" {
+            return Some(path.to_string());
+        }
+        let rest = path.get(self.path_prefix.len()..)?;
+        if !path.starts_with(&self.path_prefix) {
+            None
+        } else if rest.is_empty() {
+            Some(String::from("/"))
+        } else if rest.starts_with('/') {
+            Some(rest.to_string())
+        } else {
+            None
+        }
+    }
+
+    pub fn connection(&self, host: &str) -> serde_json::Value {
+        dna_connections_response(&self.connected_dna_interface, &self.instance_id, host)
+    }
+}
+
+/// Picks the route that serves a request and returns it with the path within its bundle.
+/// Routes for the request's host go before routes for any host, and longer path prefixes
+/// before shorter ones.
+pub fn find_route<'a>(
+    routes: &'a [UiRoute],
+    host: Option<&str>,
+    path: &str,
+) -> Option<(&'a UiRoute, String)> {
+    let host = host.map(|host| host.to_lowercase());
+    let path = path.split('?').next().unwrap_or("/");
+    routes
+        .iter()
+        .filter(|route| route.host.is_none() || route.host == host)
+        .filter_map(|route| route.path_in_bundle(path).map(|rest| (route, rest)))
+        .max_by_key(|(route, _)| (route.host.is_some(), route.path_prefix.len()))
+}
+
+/// The file in the bundle at the given path, or its index.html if it is a directory.
+/// Paths that would lead out of the bundle resolve to nothing.
+pub fn resolve_file(root_dir: &Path, path: &str) -> Option<PathBuf> {
+    let relative = Path::new(path.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|component| component != Component::CurDir && !is_normal(component))
+    {
+        return None;
+    }
+    let mut file = root_dir.join(relative);
+    if file.is_dir() {
+        file = file.join("index.html");
+    }
+    if file.is_file() {
+        Some(file)
+    } else {
+        None
+    }
+}
+
+fn is_normal(component: Component) -> bool {
+    match component {
+        Component::Normal(_) => true,
+        _ => false,
+    }
 }
 
 pub trait ConductorStaticFileServer {
@@ -31,3 +173,95 @@ pub trait ConductorStaticFileServer {
     fn start(&mut self) -> HolochainResult<()>;
     fn stop(&mut self) -> HolochainResult<()>;
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::fs;
+
+    fn route(id: &str, host: Option<&str>, path_prefix: Option<&str>) -> UiRoute {
+        let config = UiInterfaceConfiguration {
+            id: id.to_string(),
+            bundle: String::from("bundle"),
+            port: 3000,
+            dna_interface: None,
+            host: host.map(String::from),
+            path_prefix: path_prefix.map(String::from),
+            instance: None,
+            reroute_to_root: true,
+            bind_address: String::from("127.0.0.1"),
+        };
+        let bundle_config = UiBundleConfiguration {
+            id: String::from("bundle"),
+            root_dir: String::from("."),
+            hash: None,
+        };
+        UiRoute::from_configs(&config, &bundle_config, None)
+    }
+
+    #[test]
+    fn routes_by_host_and_path() {
+        let routes = vec![
+            route("default", None, None),
+            route("chat", Some("Chat.example.com"), None),
+            route("chat-admin", Some("chat.example.com"), Some("/admin/")),
+            route("wiki", None, Some("/wiki")),
+        ];
+        let found = |host: Option<&str>, path: &str| {
+            find_route(&routes, host, path).map(|(route, rest)| (route.interface_id.clone(), rest))
+        };
+
+        assert_eq!(
+            found(Some("chat.example.com"), "/app.js"),
+            Some((String::from("chat"), String::from("/app.js")))
+        );
+        assert_eq!(
+            found(Some("CHAT.example.com"), "/admin?tab=1"),
+            Some((String::from("chat-admin"), String::from("/")))
+        );
+        assert_eq!(
+            found(Some("other.example.com"), "/wiki/page.html"),
+            Some((String::from("wiki"), String::from("/page.html")))
+        );
+        assert_eq!(
+            found(None, "/wikipedia"),
+            Some((String::from("default"), String::from("/wikipedia")))
+        );
+        assert_eq!(find_route(&routes[1..2], Some("localhost"), "/"), None);
+    }
+
+    #[test]
+    fn injects_connection_into_head() {
+        let connection = json!({ "url": "ws://localhost:8888", "evil": "</script>" });
+        let html = inject_connection("<html><HEAD><title>UI</title></HEAD></html>", &connection);
+        assert!(html.starts_with("<html><HEAD><title>UI</title><script>window.__HC_CONNECTION__"));
+        assert!(html.ends_with("</script></HEAD></html>"));
+        assert!(html.contains("<\\/script>"));
+
+        let html = inject_connection("<p>no head</p>", &connection);
+        assert!(html.starts_with("<script>"));
+        assert!(html.ends_with("<p>no head</p>"));
+    }
+
+    #[test]
+    fn resolves_files_within_the_bundle_only() {
+        let root_dir = tempfile::tempdir().unwrap();
+        fs::write(root_dir.path().join("index.html"), "index").unwrap();
+        fs::create_dir(root_dir.path().join("docs")).unwrap();
+        fs::write(root_dir.path().join("docs").join("index.html"), "docs").unwrap();
+
+        assert_eq!(
+            resolve_file(root_dir.path(), "/"),
+            Some(root_dir.path().join("index.html"))
+        );
+        assert_eq!(
+            resolve_file(root_dir.path(), "/docs"),
+            Some(root_dir.path().join("docs").join("index.html"))
+        );
+        assert_eq!(resolve_file(root_dir.path(), "/missing.js"), None);
+        assert_eq!(
+            resolve_file(root_dir.path(), "/docs/../../index.html"),
+            None
+        );
+    }
+}
@@ -2,22 +2,33 @@ use crate::{
     conductor::base::notify,
     config::{InterfaceConfiguration, UiBundleConfiguration, UiInterfaceConfiguration},
     error::HolochainResult,
-    static_file_server::{dna_connections_response, ConductorStaticFileServer, DNA_CONFIG_ROUTE},
+    static_file_server::{
+        find_route, inject_connection, resolve_file, ConductorStaticFileServer, UiRoute,
+        DNA_CONFIG_ROUTE,
+    },
 };
-use crossbeam_channel::{self, Sender};
 use holochain_core_types::error::HolochainError;
-use std::{net::SocketAddr, thread};
+use holochain_locksmith::{Mutex, RwLock};
+use std::{collections::HashMap, fs, net::SocketAddr, sync::Arc, thread};
 
 use nickel::{
-    hyper::uri::RequestUri, HttpRouter, Middleware, MiddlewareResult, Mountable, Nickel, Request,
-    Response, StaticFilesHandler,
+    hyper::{header::Host, method::Method, uri::RequestUri},
+    status::StatusCode,
+    MediaType, Middleware, MiddlewareResult, Nickel, Request, Response,
 };
 
+type Routes = Arc<RwLock<Vec<UiRoute>>>;
+
+lazy_static! {
+    /// Routes of the servers that are listening, by address. UI interfaces on the same
+    /// address share the server, which keeps listening once they are all stopped so that
+    /// they can get started again.
+    static ref LISTENERS: Mutex<HashMap<SocketAddr, Routes>> = Mutex::new(HashMap::new());
+}
+
 pub struct NickelStaticServer {
-    shutdown_signal: Option<Sender<()>>,
     config: UiInterfaceConfiguration,
-    bundle_config: UiBundleConfiguration,
-    connected_dna_interface: Option<InterfaceConfiguration>,
+    route: UiRoute,
     running: bool,
 }
 
@@ -28,101 +39,139 @@ impl ConductorStaticFileServer for NickelStaticServer {
         bundle_config: UiBundleConfiguration,
         connected_dna_interface: Option<InterfaceConfiguration>,
     ) -> Self {
+        let route = UiRoute::from_configs(&config, &bundle_config, connected_dna_interface);
         Self {
-            shutdown_signal: None,
             config,
-            bundle_config,
-            connected_dna_interface,
+            route,
             running: false,
         }
     }
 
     fn start(&mut self) -> HolochainResult<()> {
-        let (tx, rx) = crossbeam_channel::unbounded();
+        let addr: SocketAddr = format!("{}:{}", self.config.bind_address, self.config.port)
+            .parse()
+            .expect("invalid address or port");
+
+        notify(format!(
+            "About to serve path \"{}\" at http://{}{} for {}",
+            self.route.root_dir.display(),
+            addr,
+            self.route.path_prefix,
+            self.route
+                .host
+                .as_ref()
+                .map(|host| format!("host {}", host))
+                .unwrap_or_else(|| String::from("any host"))
+        ));
+
+        let mut listeners = LISTENERS.lock().unwrap();
+        if let Some(routes) = listeners.get(&addr) {
+            let mut routes = routes.write().unwrap();
+            routes.retain(|route| route.interface_id != self.route.interface_id);
+            routes.push(self.route.clone());
+            self.running = true;
+            return Ok(());
+        }
 
-        self.shutdown_signal = Some(tx);
+        let routes: Routes = Arc::new(RwLock::new(vec![self.route.clone()]));
+        let mut server = Nickel::new();
+        server.utilize(VirtualHostRouter {
+            routes: routes.clone(),
+        });
+        thread::Builder::new()
+            .name("conductor_api::static_file_server".to_string())
+            .spawn(move || {
+                server
+                    .listen(addr)
+                    .map_err(|e| notify(format!("server error: {}", e)))
+                    .expect("Could not start static file server");
+            })
+            .unwrap();
+        notify(format!("Listening on http://{}", addr));
+        listeners.insert(addr, routes);
         self.running = true;
-
-        {
-            let mut server = Nickel::new();
-
-            let static_file_handler =
-                StaticFilesHandler::new(self.bundle_config.root_dir.to_owned());
-            server.mount("/", static_file_handler.clone());
-
-            // if required, reroute failed routes to index.html
-            // This is required for SPAs with virtual routing
-            if self.config.reroute_to_root {
-                server.mount("/", FallbackFileRouteHandler::new(static_file_handler));
-            }
-
-            // provide a virtual route for inspecting the configed DNA interfaces for this UI
-            // let connected_dna_interface = ;
-            let connected_dna_interface = self.connected_dna_interface.clone();
-            server.get(
-                DNA_CONFIG_ROUTE,
-                middleware! { |_|
-                    dna_connections_response(&connected_dna_interface)
-                },
-            );
-
-            let addr: SocketAddr = format!("{}:{}", self.config.bind_address, self.config.port)
-                .parse()
-                .expect("invalid address or port");
-
-            notify(format!(
-                "About to serve path \"{}\" at http://{}",
-                &self.bundle_config.root_dir, &addr
-            ));
-
-            thread::Builder::new()
-                .name("conductor_api::static_file_server".to_string())
-                .spawn(move || {
-                    let _server_thread = thread::Builder::new()
-                        .name("conductor_api::static_file_server_inner".to_string())
-                        .spawn(move || {
-                            server
-                                .listen(addr)
-                                .map_err(|e| notify(format!("server error: {}", e)))
-                                .expect("Could not start static file server");
-                        });
-                    notify(format!("Listening on http://{}", addr));
-                    // block waiting for a shutdown signal after which the server thread goes out of scope
-                    rx.recv().unwrap();
-                })
-                .unwrap();
-        };
-
         Ok(())
     }
 
     fn stop(&mut self) -> HolochainResult<()> {
-        match self.shutdown_signal.clone() {
-            Some(shutdown_signal) => {
-                shutdown_signal.send(()).unwrap();
-                Ok(())
-            }
-            None => Err(HolochainError::ErrorGeneric("server is already stopped".into()).into()),
+        if !self.running {
+            return Err(HolochainError::ErrorGeneric("server is already stopped".into()).into());
+        }
+        let addr: SocketAddr = format!("{}:{}", self.config.bind_address, self.config.port)
+            .parse()
+            .expect("invalid address or port");
+        if let Some(routes) = LISTENERS.lock().unwrap().get(&addr) {
+            routes
+                .write()
+                .unwrap()
+                .retain(|route| route.interface_id != self.route.interface_id);
         }
+        self.running = false;
+        Ok(())
     }
 }
 
-#[derive(Clone)]
-struct FallbackFileRouteHandler(StaticFilesHandler);
-
-impl FallbackFileRouteHandler {
-    pub fn new(static_file_handler: StaticFilesHandler) -> Self {
-        Self(static_file_handler)
-    }
+/// Serves the UI bundle of the route that matches the request's host and path
+struct VirtualHostRouter {
+    routes: Routes,
 }
 
-impl<D> Middleware<D> for FallbackFileRouteHandler {
+impl<D> Middleware<D> for VirtualHostRouter {
     fn invoke<'mw, 'conn>(
         &'mw self,
         req: &mut Request<'mw, 'conn, D>,
-        res: Response<'mw, D>,
+        mut res: Response<'mw, D>,
     ) -> MiddlewareResult<'mw, D> {
-        req.origin.uri = RequestUri::AbsolutePath("/".to_string());
-        self.0.invoke(req, res)
+        if req.origin.method != Method::Get && req.origin.method != Method::Head {
+            return res.next_middleware();
+        }
+        let host = req
+            .origin
+            .headers
+            .get::<Host>()
+            .map(|host| host.hostname.clone());
+        let path = match req.origin.uri {
+            RequestUri::AbsolutePath(ref path) => path.clone(),
+            _ => String::from("/"),
+        };
+
+        let found = find_route(
+            &self.routes.read().unwrap(),
+            host.as_ref().map(String::as_str),
+            &path,
+        )
+        .map(|(route, path)| (route.clone(), path));
+        let (route, path) = match found {
+            Some(found) => found,
+            None => return res.error(StatusCode::NotFound, "No UI is served here"),
+        };
+        let connection = route.connection(host.as_ref().map(String::as_str).unwrap_or("localhost"));
+        if path == DNA_CONFIG_ROUTE {
+            res.set(MediaType::Json);
+            return res.send(connection.to_string());
+        }
+
+        // if required, reroute failed routes to index.html
+        // This is required for SPAs with virtual routing
+        let file = resolve_file(&route.root_dir, &path).or_else(|| {
+            if route.reroute_to_root {
+                resolve_file(&route.root_dir, "/")
+            } else {
+                None
+            }
+        });
+        match file {
+            Some(ref file) if file.extension().map(|ext| ext == "html") == Some(true) => {
+                match fs::read_to_string(file) {
+                    Ok(html) => {
+                        res.set(MediaType::Html);
+                        res.send(inject_connection(&html, &connection))
+                    }
+                    Err(_) => res.error(StatusCode::InternalServerError, "Could not read file"),
+                }
+            }
+            Some(file) => res.send_file(file),
+            None => res.error(StatusCode::NotFound, "File not found"),
+        }
     }
 }
@@ -0,0 +1,39 @@
+//! Storage backends supplied by the application embedding the conductor.
+//!
+//! Embedders that already run a database can keep instance data in it by implementing the
+//! CAS and EAV storage traits and registering a [StorageBackendFactory] under a name with
+//! [Conductor::register_storage_backend](crate::conductor::Conductor::register_storage_backend).
+//! Instances then select it in their config with `type = "custom"` and `backend = "<name>"`.
+//! The factory gets called once per instance with the instance's `path` and `options` from the
+//! config and returns the storages the instance should use.
+use holochain_core::storage_compaction::CompactStorage;
+use holochain_core_types::{eav::Attribute, error::HolochainError};
+use holochain_locksmith::RwLock;
+use holochain_persistence_api::{
+    cas::storage::ContentAddressableStorage, eav::EntityAttributeValueStorage,
+};
+use std::sync::Arc;
+
+/// What a custom backend gets to know about the instance it creates storages for
+#[derive(Clone, Debug, PartialEq)]
+pub struct StorageBackendParams {
+    pub instance_id: String,
+    /// The `path` of the instance's storage config, if it has one
+    pub path: Option<String>,
+    /// The `options` of the instance's storage config, if it has any
+    pub options: Option<serde_json::Value>,
+}
+
+/// The storages a custom backend created for an instance
+#[derive(Clone)]
+pub struct CustomStorage {
+    /// Content addressable storage, used as chain and DHT storage
+    pub cas: Arc<RwLock<dyn ContentAddressableStorage>>,
+    pub eav: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    /// Backends that can list and remove their content get storage compaction and
+    /// integrity scrubbing by setting this
+    pub compactor: Option<Arc<dyn CompactStorage>>,
+}
+
+pub type StorageBackendFactory =
+    Arc<Box<dyn Fn(&StorageBackendParams) -> Result<CustomStorage, HolochainError> + Send + Sync>>;
@@ -0,0 +1,224 @@
+//! Two-tiered content addressable storage.
+//!
+//! [TieredStorage] keeps recently used content in a memory tier of bounded size in front of
+//! a persistent storage (the cold tier). Writes go through to the cold tier, reads of content
+//! in the memory tier, like anchors or recently committed entries, don't touch the disk.
+//! When the memory tier is full, the least recently used content gets dropped from it.
+use holochain_locksmith::{Mutex, RwLock};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    error::PersistenceResult,
+    reporting::ReportStorage,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Arc,
+};
+use uuid::Uuid;
+
+/// Size of the memory tier
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct HotTier {
+    /// Upper bound for the size of the content kept in memory, in bytes
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: usize,
+}
+
+fn default_max_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+impl Default for HotTier {
+    fn default() -> Self {
+        HotTier {
+            max_bytes: default_max_bytes(),
+        }
+    }
+}
+
+/// Least recently used cache of content, bounded by the total size of the content.
+struct MemoryTier {
+    max_bytes: usize,
+    bytes: usize,
+    tick: u64,
+    content: HashMap<Address, (Content, u64)>,
+    last_used: BTreeMap<u64, Address>,
+}
+
+impl MemoryTier {
+    fn new(max_bytes: usize) -> Self {
+        MemoryTier {
+            max_bytes,
+            bytes: 0,
+            tick: 0,
+            content: HashMap::new(),
+            last_used: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, address: &Address) -> Option<Content> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (content, last_used) = self.content.get_mut(address)?;
+        self.last_used.remove(last_used);
+        self.last_used.insert(tick, address.clone());
+        *last_used = tick;
+        Some(content.clone())
+    }
+
+    fn insert(&mut self, address: Address, content: Content) {
+        let size = String::from(content.clone()).len();
+        if size > self.max_bytes {
+            return;
+        }
+        self.remove(&address);
+        while self.bytes + size > self.max_bytes {
+            let oldest = match self.last_used.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            if let Some(address) = self.last_used.remove(&oldest) {
+                self.remove(&address);
+            }
+        }
+        self.tick += 1;
+        self.bytes += size;
+        self.last_used.insert(self.tick, address.clone());
+        self.content.insert(address, (content, self.tick));
+    }
+
+    fn remove(&mut self, address: &Address) {
+        if let Some((content, last_used)) = self.content.remove(address) {
+            self.bytes -= String::from(content).len();
+            self.last_used.remove(&last_used);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TieredStorage {
+    cold: Arc<RwLock<dyn ContentAddressableStorage>>,
+    hot: Arc<Mutex<MemoryTier>>,
+}
+
+impl fmt::Debug for TieredStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hot = self.hot.lock().unwrap();
+        f.debug_struct("TieredStorage")
+            .field("max_bytes", &hot.max_bytes)
+            .field("bytes", &hot.bytes)
+            .finish()
+    }
+}
+
+impl TieredStorage {
+    pub fn new(cold: Arc<RwLock<dyn ContentAddressableStorage>>, hot_tier: &HotTier) -> Self {
+        TieredStorage {
+            cold,
+            hot: Arc::new(Mutex::new(MemoryTier::new(hot_tier.max_bytes))),
+        }
+    }
+}
+
+impl ContentAddressableStorage for TieredStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        self.cold.write().unwrap().add(content)?;
+        self.hot
+            .lock()
+            .unwrap()
+            .insert(content.address(), content.content());
+        Ok(())
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        if self.hot.lock().unwrap().content.contains_key(address) {
+            return Ok(true);
+        }
+        self.cold.read().unwrap().contains(address)
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        if let Some(content) = self.hot.lock().unwrap().get(address) {
+            return Ok(Some(content));
+        }
+        let content = self.cold.read().unwrap().fetch(address)?;
+        if let Some(ref content) = content {
+            self.hot
+                .lock()
+                .unwrap()
+                .insert(address.clone(), content.clone());
+        }
+        Ok(content)
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.cold.read().unwrap().get_id()
+    }
+}
+
+impl ReportStorage for TieredStorage {}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_json_api::json::{JsonString, RawString};
+    use holochain_persistence_api::cas::content::ExampleAddressableContent;
+    use holochain_persistence_mem::cas::memory::MemoryStorage;
+
+    fn content(text: &str) -> ExampleAddressableContent {
+        ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from(text)))
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_hit_memory_tier_until_evicted() {
+        let cold: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        let first = content("first");
+        let second = content("second");
+        let third = content("third");
+        let size = String::from(second.content()).len();
+        let mut storage = TieredStorage::new(
+            cold.clone(),
+            &HotTier {
+                max_bytes: size * 2,
+            },
+        );
+
+        storage.add(&first).unwrap();
+        storage.add(&second).unwrap();
+        // Using the first makes the second the least recently used
+        assert_eq!(
+            storage.fetch(&first.address()).unwrap(),
+            Some(first.content())
+        );
+        storage.add(&third).unwrap();
+
+        {
+            let hot = storage.hot.lock().unwrap();
+            assert!(hot.content.contains_key(&first.address()));
+            assert!(!hot.content.contains_key(&second.address()));
+            assert!(hot.content.contains_key(&third.address()));
+            assert!(hot.bytes <= size * 2);
+        }
+
+        // Everything got written through to the cold tier
+        for content in &[&first, &second, &third] {
+            assert!(cold.read().unwrap().contains(&content.address()).unwrap());
+        }
+        assert_eq!(
+            storage.fetch(&second.address()).unwrap(),
+            Some(second.content())
+        );
+        assert!(storage
+            .hot
+            .lock()
+            .unwrap()
+            .content
+            .contains_key(&second.address()));
+    }
+}
@@ -0,0 +1,283 @@
+//! Exporting the trace spans of instances.
+//!
+//! Each instance gets its own tracer whose finished spans are reported by the conductor's
+//! trace reporter thread, either to a Jaeger agent or over OTLP (see [OtlpReporter]).
+//! OTLP spans are posted as JSON, so that no gRPC or protobuf stack is needed, and land in
+//! any backend that speaks OTLP/HTTP, like Jaeger, Tempo or the OpenTelemetry collector.
+use crate::config::OtlpTracingConfiguration;
+use holochain_core_types::error::HolochainError;
+use holochain_tracing as ht;
+use reqwest::{self, Url};
+use rustracing::tag::TagValue;
+use rustracing_jaeger::span::SpanContextState;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const EXPORT_TIMEOUT_SECS: u64 = 10;
+
+/// Name of the attribute that holds the ID of the instance a span belongs to
+pub const INSTANCE_ID_ATTRIBUTE: &str = "holochain.instance_id";
+
+/// Something finished spans get reported to
+pub trait SpanReporter: Send + Sync {
+    fn report(&self, spans: &[ht::FinishedSpan]) -> Result<(), String>;
+
+    /// How many spans to report at once at most
+    fn batch_size(&self) -> usize {
+        1
+    }
+}
+
+impl SpanReporter for ht::reporter::JaegerCompactReporter {
+    fn report(&self, spans: &[ht::FinishedSpan]) -> Result<(), String> {
+        ht::reporter::JaegerCompactReporter::report(self, spans).map_err(|e| format!("{:?}", e))
+    }
+}
+
+/// Posts spans to an OTLP/HTTP endpoint
+pub struct OtlpReporter {
+    client: reqwest::Client,
+    endpoint: Url,
+    config: OtlpTracingConfiguration,
+    instance_id: String,
+}
+
+impl OtlpReporter {
+    pub fn new(
+        config: OtlpTracingConfiguration,
+        instance_id: &str,
+    ) -> Result<Self, HolochainError> {
+        let endpoint = Url::parse(&config.endpoint).map_err(|e| {
+            HolochainError::ConfigError(format!("Invalid OTLP endpoint {}: {}", config.endpoint, e))
+        })?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(EXPORT_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| HolochainError::ErrorGeneric(format!("Could not build client: {}", e)))?;
+        Ok(OtlpReporter {
+            client,
+            endpoint,
+            config,
+            instance_id: instance_id.to_string(),
+        })
+    }
+
+    /// The export request for the given spans
+    pub fn export_request(&self, spans: &[ht::FinishedSpan]) -> serde_json::Value {
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        attribute("service.name", string_value(&self.config.service_name)),
+                        attribute(INSTANCE_ID_ATTRIBUTE, string_value(&self.instance_id)),
+                    ]
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "holochain" },
+                    "spans": spans.iter().map(otlp_span).collect::<Vec<_>>(),
+                }]
+            }]
+        })
+    }
+}
+
+impl SpanReporter for OtlpReporter {
+    fn report(&self, spans: &[ht::FinishedSpan]) -> Result<(), String> {
+        let mut request = self.client.post(self.endpoint.clone());
+        for (name, value) in self.config.headers.iter() {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+            .json(&self.export_request(spans))
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|e| format!("Could not export spans to {}: {}", self.endpoint, e))
+    }
+
+    fn batch_size(&self) -> usize {
+        self.config.batch_size
+    }
+}
+
+fn trace_id(state: &SpanContextState) -> String {
+    let trace_id = state.trace_id();
+    format!("{:016x}{:016x}", trace_id.high, trace_id.low)
+}
+
+fn span_id(state: &SpanContextState) -> String {
+    format!("{:016x}", state.span_id())
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since_epoch.as_secs() * 1_000_000_000 + u64::from(since_epoch.subsec_nanos())).to_string()
+}
+
+fn string_value(value: &str) -> serde_json::Value {
+    json!({ "stringValue": value })
+}
+
+fn attribute(key: &str, value: serde_json::Value) -> serde_json::Value {
+    json!({ "key": key, "value": value })
+}
+
+fn tag_value(value: &TagValue) -> serde_json::Value {
+    match value {
+        TagValue::String(value) => string_value(value),
+        TagValue::Boolean(value) => json!({ "boolValue": value }),
+        // 64 bit integers are strings in OTLP's JSON
+        TagValue::Integer(value) => json!({ "intValue": value.to_string() }),
+        TagValue::Float(value) => json!({ "doubleValue": value }),
+    }
+}
+
+/// Converts a span to OTLP. OpenTelemetry has no "follows from" relation, so a span that
+/// follows another without being its child becomes a child of the first span it follows,
+/// which keeps network round trips in the trace of the call that started them. Any other
+/// references become links.
+pub fn otlp_span(span: &ht::FinishedSpan) -> serde_json::Value {
+    let state = span.context().state();
+    let parent = span
+        .references()
+        .iter()
+        .position(|reference| reference.is_child_of())
+        .or_else(|| {
+            if span.references().is_empty() {
+                None
+            } else {
+                Some(0)
+            }
+        });
+    let parent_span_id = parent
+        .map(|index| span_id(span.references()[index].span()))
+        .unwrap_or_default();
+    let links: Vec<_> = span
+        .references()
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| Some(*index) != parent)
+        .map(|(_, reference)| {
+            json!({
+                "traceId": trace_id(reference.span()),
+                "spanId": span_id(reference.span()),
+            })
+        })
+        .collect();
+    let events: Vec<_> = span
+        .logs()
+        .iter()
+        .map(|log| {
+            let name = log
+                .fields()
+                .iter()
+                .find(|field| field.name() == "event")
+                .map(|field| field.value().to_string())
+                .unwrap_or_else(|| String::from("log"));
+            let attributes: Vec<_> = log
+                .fields()
+                .iter()
+                .filter(|field| field.name() != "event")
+                .map(|field| attribute(field.name(), string_value(field.value())))
+                .collect();
+            json!({
+                "timeUnixNano": unix_nanos(log.time()),
+                "name": name,
+                "attributes": attributes,
+            })
+        })
+        .collect();
+    let attributes: Vec<_> = span
+        .tags()
+        .iter()
+        .map(|tag| attribute(tag.name(), tag_value(tag.value())))
+        .collect();
+    json!({
+        "traceId": trace_id(state),
+        "spanId": span_id(state),
+        "parentSpanId": parent_span_id,
+        "name": span.operation_name(),
+        // SPAN_KIND_INTERNAL
+        "kind": 1,
+        "startTimeUnixNano": unix_nanos(span.start_time()),
+        "endTimeUnixNano": unix_nanos(span.finish_time()),
+        "attributes": attributes,
+        "events": events,
+        "links": links,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn finished_spans() -> Vec<ht::FinishedSpan> {
+        let (span_tx, span_rx) = crossbeam_channel::unbounded();
+        let tracer = ht::Tracer::with_sender(ht::AllSampler, span_tx);
+        {
+            let call = tracer
+                .span("zome call")
+                .tag(ht::Tag::new("zome", "chat"))
+                .start();
+            let _validation = tracer.span("validation").child_of(&call).start();
+            let _message = tracer.span("network message").follows_from(&call).start();
+        }
+        span_rx.try_iter().collect()
+    }
+
+    fn reporter() -> OtlpReporter {
+        OtlpReporter::new(
+            OtlpTracingConfiguration {
+                service_name: String::from("conductor"),
+                endpoint: String::from("http://localhost:4318/v1/traces"),
+                headers: BTreeMap::new(),
+                batch_size: 512,
+            },
+            "app",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn exports_spans_with_their_parents() {
+        let request = reporter().export_request(&finished_spans());
+        let resource_spans = &request["resourceSpans"][0];
+        assert_eq!(
+            resource_spans["resource"]["attributes"][1],
+            json!({ "key": INSTANCE_ID_ATTRIBUTE, "value": { "stringValue": "app" } })
+        );
+        let spans = resource_spans["scopeSpans"][0]["spans"].as_array().unwrap();
+        let by_name = |name: &str| {
+            spans
+                .iter()
+                .find(|span| span["name"] == name)
+                .unwrap()
+                .clone()
+        };
+        let call = by_name("zome call");
+        let validation = by_name("validation");
+        let message = by_name("network message");
+
+        assert_eq!(call["parentSpanId"], "");
+        assert_eq!(call["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(call["spanId"].as_str().unwrap().len(), 16);
+        assert_eq!(
+            call["attributes"][0],
+            json!({ "key": "zome", "value": { "stringValue": "chat" } })
+        );
+        for child in &[validation, message] {
+            assert_eq!(child["traceId"], call["traceId"]);
+            assert_eq!(child["parentSpanId"], call["spanId"]);
+            assert_eq!(child["links"], json!([]));
+        }
+    }
+
+    #[test]
+    fn refuses_invalid_endpoints() {
+        let config = OtlpTracingConfiguration {
+            endpoint: String::from("localhost without scheme"),
+            ..reporter().config
+        };
+        assert!(OtlpReporter::new(config, "app").is_err());
+    }
+}
@@ -0,0 +1,19 @@
+//! Committing an entry through the whole authoring workflow: local validation, writing it to
+//! the chain and publishing it
+mod fixtures;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn commit(c: &mut Criterion) {
+    let agent = fixtures::agent("commit");
+    let mut number = 0;
+    c.bench_function("commit app entry", |b| {
+        b.iter(|| {
+            number += 1;
+            agent.commit(&fixtures::app_entry(number)).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, commit);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+//! Assembling the aspects of an entry that answer a fetch request of the network, by number
+//! of links of the entry, for requests of all aspects and of only the content aspect
+mod fixtures;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use holochain_core::network::handler::fetch::{
+    fetch_aspects_for_entry, fetch_requested_aspects_for_entry,
+};
+use holochain_core_types::network::entry_aspect::EntryAspect;
+use holochain_persistence_api::cas::content::AddressableContent;
+use lib3h_protocol::types::{AspectHash, EntryHash};
+use std::collections::HashSet;
+
+fn fetch(c: &mut Criterion) {
+    let agent = fixtures::agent("fetch");
+    let mut group = c.benchmark_group("fetch_aspects_for_entry");
+    for links in [0, 10, 100].iter() {
+        let entry_hash: EntryHash = fixtures::commit_with_links(&agent, *links).into();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(links),
+            &entry_hash,
+            |b, entry_hash| b.iter(|| fetch_aspects_for_entry(entry_hash, agent.context.clone())),
+        );
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("fetch_requested_aspects_for_entry/content");
+    for links in [0, 10, 100].iter() {
+        let entry_hash: EntryHash = fixtures::commit_with_links(&agent, *links).into();
+        let content: HashSet<AspectHash> =
+            fetch_aspects_for_entry(&entry_hash, agent.context.clone())
+                .into_iter()
+                .filter(|aspect| match aspect {
+                    EntryAspect::Content(_, _) => true,
+                    _ => false,
+                })
+                .map(|aspect| AspectHash::from(aspect.address()))
+                .collect();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(links),
+            &entry_hash,
+            |b, entry_hash| {
+                b.iter(|| {
+                    fetch_requested_aspects_for_entry(
+                        entry_hash,
+                        Some(&content),
+                        agent.context.clone(),
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, fetch);
+criterion_main!(benches);
@@ -0,0 +1,153 @@
+//! Fixtures shared by the benchmarks: instances of a DNA whose validation callbacks accept
+//! everything, entries to commit to them and meta data to query.
+//!
+//! Not every benchmark uses every fixture.
+#![allow(dead_code)]
+use holochain_core::{
+    dht::dht_store::DhtStore, full_consistency::FullConsistencyWaiter, test_harness::TestAgent,
+    workflows::author_entry::author_entry,
+};
+use holochain_core_types::{
+    eav::{Attribute, EntityAttributeValueIndex},
+    entry::{deletion_entry::DeletionEntry, entry_type::test_app_entry_type, Entry},
+    link::link_data::LinkData,
+    validation::{EntryLifecycle, ValidationData, ValidationPackage},
+};
+use holochain_json_api::json::{JsonString, RawString};
+use holochain_locksmith::RwLock;
+use holochain_persistence_api::{
+    cas::content::{Address, AddressableContent},
+    eav::EntityAttributeValueStorage,
+};
+use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+use std::{sync::Arc, time::Duration};
+use test_utils::create_test_dna_with_wat;
+
+pub const LINK_TYPE: &str = "test-link";
+
+const CONSISTENCY_TIMEOUT: Duration = Duration::from_secs(120);
+
+const ALWAYS_VALID_WAT: &str = r#"
+(module
+    (memory 1)
+    (export "memory" (memory 0))
+    (func (export "__hdk_validate_app_entry") (param $allocation i64) (result i64) (i64.const 0))
+    (func (export "__hdk_validate_agent_entry") (param $allocation i64) (result i64) (i64.const 0))
+    (func (export "__hdk_validate_link") (param $allocation i64) (result i64) (i64.const 0))
+    (func (export "__list_traits") (param $allocation i64) (result i64) (i64.const 0))
+    (func (export "__list_functions") (param $allocation i64) (result i64) (i64.const 0))
+)
+"#;
+
+/// Starts an agent on an in-memory network of its own
+pub fn agent(name: &str) -> TestAgent {
+    let dna = create_test_dna_with_wat("test_zome", Some(ALWAYS_VALID_WAT));
+    let network_name = format!("bench-{}-{}", name, snowflake::ProcessUniqueId::new());
+    TestAgent::new(dna, name, &network_name).expect("Could not start agent")
+}
+
+/// App entries that differ by their number
+pub fn app_entry(number: usize) -> Entry {
+    Entry::App(
+        test_app_entry_type(),
+        JsonString::from(RawString::from(format!("bench entry {}", number))),
+    )
+}
+
+/// A link from the base to itself, which differs from the other links of the base by its tag
+pub fn link_entry(agent: &TestAgent, base: &Address, number: usize) -> Entry {
+    let top_chain_header = agent
+        .context
+        .state()
+        .expect("Agent has no state")
+        .agent()
+        .top_chain_header()
+        .expect("Agent has no chain");
+    Entry::LinkAdd(LinkData::new_add(
+        base,
+        base,
+        &format!("link {}", number),
+        LINK_TYPE,
+        top_chain_header,
+        agent.agent_id(),
+    ))
+}
+
+/// Commits the entries and waits until the agent holds all of them
+pub fn commit_all(agent: &TestAgent, entries: &[Entry]) -> Vec<Address> {
+    let waiter = FullConsistencyWaiter::new(&[agent.context.clone()]);
+    let addresses = entries
+        .iter()
+        .map(|entry| agent.commit(entry).expect("Could not commit"))
+        .collect();
+    waiter
+        .wait(CONSISTENCY_TIMEOUT)
+        .expect("Commits did not settle");
+    addresses
+}
+
+/// Commits an app entry with the number of links to it, returns the address of the entry
+pub fn commit_with_links(agent: &TestAgent, links: usize) -> Address {
+    let base = commit_all(agent, &[app_entry(links)]).remove(0);
+    let link_entries: Vec<Entry> = (0..links)
+        .map(|number| link_entry(agent, &base, number))
+        .collect();
+    commit_all(agent, &link_entries);
+    base
+}
+
+/// Commits the deletion of the entry, returns the deletion entry
+pub fn commit_deletion(agent: &TestAgent, deleted: &Address) -> Entry {
+    let deletion = Entry::Deletion(DeletionEntry::new(deleted.clone()));
+    let waiter = FullConsistencyWaiter::new(&[agent.context.clone()]);
+    agent
+        .context
+        .block_on(author_entry(
+            &deletion,
+            Some(deleted.clone()),
+            &agent.context,
+            &vec![],
+        ))
+        .expect("Could not commit deletion");
+    waiter
+        .wait(CONSISTENCY_TIMEOUT)
+        .expect("Deletion did not settle");
+    deletion
+}
+
+/// What validating the entry takes, as the agent that committed it publishes it
+pub fn validation_data(agent: &TestAgent, address: &Address) -> ValidationData {
+    let header = agent
+        .context
+        .state()
+        .expect("Agent has no state")
+        .agent()
+        .iter_chain()
+        .find(|header| header.entry_address() == address)
+        .expect("Entry is not in the agent's chain");
+    ValidationData {
+        package: ValidationPackage::only_header(header),
+        lifecycle: EntryLifecycle::Dht,
+    }
+}
+
+/// A DHT store that holds the number of links from one base, without the link entries
+pub fn dht_with_links(links: usize) -> (DhtStore, Address) {
+    let base = app_entry(links).address();
+    let meta_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+        Arc::new(RwLock::new(EavMemoryStorage::new()));
+    {
+        let mut meta = meta_storage.write().unwrap();
+        for number in 0..links {
+            let eavi = EntityAttributeValueIndex::new(
+                &base,
+                &Attribute::LinkTag(LINK_TYPE.to_string(), format!("link {}", number)),
+                &app_entry(number).address(),
+            )
+            .unwrap();
+            meta.add_eavi(&eavi).unwrap();
+        }
+    }
+    let store = DhtStore::new(Arc::new(RwLock::new(MemoryStorage::new())), meta_storage);
+    (store, base)
+}
@@ -0,0 +1,29 @@
+//! Querying the links of a base from the meta data of the DHT store, by number of links
+mod fixtures;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use holochain_core_types::network::query::GetLinksQueryConfiguration;
+
+fn get_links(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_links");
+    for links in [10, 100, 1000, 10_000].iter() {
+        let (store, base) = fixtures::dht_with_links(*links);
+        group.bench_with_input(BenchmarkId::from_parameter(links), &base, |b, base| {
+            b.iter(|| {
+                store
+                    .get_links(
+                        base.clone(),
+                        Some(fixtures::LINK_TYPE.to_string()),
+                        None,
+                        None,
+                        GetLinksQueryConfiguration::default(),
+                    )
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, get_links);
+criterion_main!(benches);
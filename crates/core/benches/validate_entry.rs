@@ -0,0 +1,52 @@
+//! Validating entries as the holding workflows do, per entry type
+mod fixtures;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use holochain_core::nucleus::validation::{validate_entry, ValidationContext};
+use holochain_core_types::entry::Entry;
+use holochain_persistence_api::cas::content::AddressableContent;
+
+fn validate(c: &mut Criterion) {
+    let agent = fixtures::agent("validate");
+    let app_entry = fixtures::app_entry(0);
+    let base = fixtures::commit_all(&agent, &[app_entry.clone()]).remove(0);
+    let link_entry = fixtures::link_entry(&agent, &base, 0);
+    fixtures::commit_all(&agent, &[link_entry.clone()]);
+    let deleted = fixtures::commit_all(&agent, &[fixtures::app_entry(1)]).remove(0);
+    let deletion_entry = fixtures::commit_deletion(&agent, &deleted);
+    let agent_entry = Entry::AgentId(agent.agent_id());
+
+    let mut group = c.benchmark_group("validate_entry");
+    for (entry_type, entry) in [
+        ("app", app_entry),
+        ("agent_id", agent_entry),
+        ("link_add", link_entry),
+        ("deletion", deletion_entry),
+    ]
+    .iter()
+    {
+        let validation_data = fixtures::validation_data(&agent, &entry.address());
+        group.bench_with_input(
+            BenchmarkId::from_parameter(entry_type),
+            entry,
+            |b, entry| {
+                b.iter(|| {
+                    agent
+                        .context
+                        .block_on(validate_entry(
+                            entry.clone(),
+                            None,
+                            validation_data.clone(),
+                            &agent.context,
+                            ValidationContext::Holding,
+                        ))
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, validate);
+criterion_main!(benches);
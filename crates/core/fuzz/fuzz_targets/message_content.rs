@@ -0,0 +1,7 @@
+#![no_main]
+use holochain_core::fuzzing::parse_message_content;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    parse_message_content(data);
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use holochain_core_fuzz::with_target;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    with_target(|target| target.handle_network_message(data));
+});
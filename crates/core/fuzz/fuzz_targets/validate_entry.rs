@@ -0,0 +1,9 @@
+#![no_main]
+use holochain_core_fuzz::with_target;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    with_target(|target| {
+        let _ = target.validate_entry(data);
+    });
+});
@@ -0,0 +1,28 @@
+//! Shared setup of the fuzz targets: one instance per fuzzing process, of a DNA whose
+//! validation callbacks accept everything, so that input reaches as far as it can.
+use holochain_core::fuzzing::FuzzTarget;
+use std::cell::RefCell;
+use test_utils::create_test_dna_with_wat;
+
+const ALWAYS_VALID_WAT: &str = r#"
+(module
+    (memory 1)
+    (export "memory" (memory 0))
+    (func (export "__hdk_validate_app_entry") (param $allocation i64) (result i64) (i64.const 0))
+    (func (export "__hdk_validate_agent_entry") (param $allocation i64) (result i64) (i64.const 0))
+    (func (export "__hdk_validate_link") (param $allocation i64) (result i64) (i64.const 0))
+    (func (export "__list_traits") (param $allocation i64) (result i64) (i64.const 0))
+    (func (export "__list_functions") (param $allocation i64) (result i64) (i64.const 0))
+)
+"#;
+
+thread_local! {
+    static TARGET: RefCell<FuzzTarget> = RefCell::new(
+        FuzzTarget::new(create_test_dna_with_wat("test_zome", Some(ALWAYS_VALID_WAT)))
+            .expect("Could not start the instance to fuzz"),
+    );
+}
+
+pub fn with_target<F: FnOnce(&mut FuzzTarget)>(f: F) {
+    TARGET.with(|target| f(&mut target.borrow_mut()))
+}
@@ -1,5 +1,6 @@
 use crate::{
-    agent::state::AgentState,
+    agent::state::{AgentState, StagedEntry},
+    causal_log::current_cause,
     dht::{
         actions::remove_queued_holding_workflow::HoldingWorkflowQueueing,
         dht_store::HoldAspectAttemptId, pending_validations::PendingValidation,
@@ -20,8 +21,12 @@ use crate::{
 };
 
 use holochain_core_types::{
-    chain_header::ChainHeader, crud_status::CrudStatus, dna::Dna, entry::Entry,
-    signature::Provenance, validation::ValidationPackage,
+    chain_header::ChainHeader,
+    crud_status::CrudStatus,
+    dna::{capabilities::CapabilityUseRecord, Dna},
+    entry::Entry,
+    signature::Provenance,
+    validation::ValidationPackage,
 };
 use holochain_net::{connection::net_connection::NetHandler, p2p_config::P2pConfig};
 use holochain_persistence_api::cas::content::Address;
@@ -38,10 +43,12 @@ use std::{
 /// The standard approach is to drop the ActionWrapper into the key of a state history HashMap and
 /// use the convenience unwrap_to! macro to extract the action data in a reducer.
 /// All reducer functions must accept an ActionWrapper so all dispatchers take an ActionWrapper.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ActionWrapper {
     action: Action,
     id: String,
+    /// ID of the action, workflow or message that caused this one, see [causal_log](crate::causal_log)
+    cause: Option<String>,
 }
 
 impl ActionWrapper {
@@ -52,6 +59,16 @@ impl ActionWrapper {
             action: a,
             // auto generate id
             id: nanoid::simple(),
+            cause: current_cause(),
+        }
+    }
+
+    /// the same wrapper, with the same ID and cause, around another action
+    pub fn with_action(&self, action: Action) -> Self {
+        ActionWrapper {
+            action,
+            id: self.id.clone(),
+            cause: self.cause.clone(),
         }
     }
 
@@ -64,6 +81,11 @@ impl ActionWrapper {
     pub fn id(&self) -> &String {
         &self.id
     }
+
+    /// read only access to the ID of the cause
+    pub fn cause(&self) -> Option<&String> {
+        self.cause.as_ref()
+    }
 }
 
 impl PartialEq for ActionWrapper {
@@ -81,21 +103,21 @@ impl Hash for ActionWrapper {
 }
 
 ///This describes a key for the actions
-#[derive(Clone, PartialEq, Debug, Serialize, Eq, Hash)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Eq, Hash)]
 pub enum QueryKey {
     Entry(GetEntryKey),
     Links(GetLinksKey),
 }
 
 ///This is a payload for the Get Method
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum QueryPayload {
     Entry,
     Links((Option<CrudStatus>, GetLinksNetworkQuery)),
 }
 
 /// All Actions for the Holochain Instance Store, according to Redux pattern.
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(tag = "action_type", content = "data")]
 #[allow(clippy::large_enum_variant)]
 pub enum Action {
@@ -110,6 +132,41 @@ pub enum Action {
     /// Does not validate, assumes entry is valid.
     Commit((Entry, Option<Address>, Vec<Provenance>)),
 
+    /// Writes several entries to the source chain in the given order within a single
+    /// reduction, so the resulting state gets persisted once for the whole group.
+    /// Does not validate, assumes entries are valid.
+    CommitBatch(Vec<StagedEntry>),
+
+    /// Records that the given peer (second address) reported holding
+    /// the authored entry of the header with the given address (first address).
+    AddHoldingReceipt((Address, Address)),
+
+    /// Records that the grantor of a capability we hold a claim for revoked the grant
+    /// with the given token.
+    AddRevokedCapToken(Address),
+
+    /// Records that the grantor of a capability we hold a claim for rotated the grant,
+    /// replacing the first token with the second.
+    AddRotatedCapToken((Address, Address)),
+
+    /// Adds a capability-gated zome call to the capability audit log.
+    RecordCapabilityUse(CapabilityUseRecord),
+
+    /// Puts an entry that passed authoring validation into the staging area.
+    /// Staged entries are neither chained nor published until committed.
+    StageEntry(StagedEntry),
+
+    /// Removes all entries from the staging area.
+    /// The reducer responds with the removed entries so they can get committed or dropped.
+    TakeStagedEntries,
+
+    /// Truncates the source chain back to the given header (first element).
+    /// The second element holds the headers that get dropped, newest first, the third one the
+    /// addresses of the dropped headers and of their entries that aren't also committed
+    /// further down the chain.
+    /// Only meant for development, see agent::actions::rollback.
+    RollbackChain((ChainHeader, Vec<ChainHeader>, Vec<Address>)),
+
     // -------------
     // DHT actions:
     // -------------
@@ -125,6 +182,11 @@ pub enum Action {
     /// Does not validate, assumes referenced entry is valid.
     HoldAspect((EntryAspect, HoldAspectAttemptId)),
 
+    /// Stops holding the entries with the given addresses, e.g. because they are invalid
+    /// under the validation rules of a new DNA version.
+    /// CAS and EAV are append-only, so their content stays but won't be gossiped anymore.
+    DropHeldEntries(Vec<Address>),
+
     //action for updating crudstatus
     CrudStatus((EntryWithHeader, CrudStatus)),
 
@@ -212,6 +274,13 @@ pub enum Action {
     /// Sends the given data as JsonProtocol::HandleGetGossipEntryListResult
     RespondGossipList(EntryListData),
 
+    /// Blocks the given agent: drops open direct connections with them and makes the
+    /// network handler, validation and query responses ignore them from then on.
+    BlockAgent(Address),
+
+    /// Lifts a block added with BlockAgent.
+    UnblockAgent(Address),
+
     // ----------------
     // Nucleus actions:
     // ----------------
@@ -222,6 +291,9 @@ pub enum Action {
     /// return the result of an InitializeChain action
     /// the result is an initialization structure which include the generated public token if any
     ReturnInitializationResult(Result<Initialization, String>),
+    /// replace the DNA of an already initialized nucleus with a new version of it,
+    /// used when migrating a loaded chain
+    UpdateDna(Dna),
 
     /// Gets dispatched when a zome function call starts.
     QueueZomeFunctionCall(ZomeFnCall),
@@ -252,7 +324,7 @@ pub type ReduceFn<S> = fn(&mut S, &State, &ActionWrapper);
 
 /// The unique key that represents a GetLinks request, used to associate the eventual
 /// response with this GetLinks request
-#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub struct GetLinksKey {
     /// The address of the Link base
     pub base_address: Address,
@@ -269,7 +341,7 @@ pub struct GetLinksKey {
 
 /// The unique key that represents a Get request, used to associate the eventual
 /// response with this Get request
-#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub struct GetEntryKey {
     /// The address of the entry to get
     pub address: Address,
@@ -289,7 +361,7 @@ pub struct ValidationKey {
 
 /// Everything the network module needs to know in order to send a
 /// direct message.
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct DirectMessageData {
     /// The address of the node to send a message to
     pub address: Address,
@@ -307,7 +379,7 @@ pub struct DirectMessageData {
 }
 
 /// Everything the network needs to initialize
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct NetworkSettings {
     /// P2pConfig that gets passed to [P2pNetwork](struct.P2pNetwork.html)
     /// determines how to connect to the network module.
@@ -322,8 +394,20 @@ pub struct NetworkSettings {
     pub agent_id: String,
 
     /// This is a closure of the code that gets called by the network
-    /// module to have us process incoming messages
+    /// module to have us process incoming messages.
+    /// Can't be recorded, so replayed settings get one that drops all messages.
+    #[serde(skip_deserializing)]
     pub handler: NetHandler,
+
+    /// Maximum average number of bytes per second the instance may send
+    pub bandwidth_limit: Option<u64>,
+
+    /// Name of the instance, which its network metrics are labelled with
+    pub instance_name: String,
+
+    /// How many validation packages of other agents to keep, 0 turns caching them off
+    #[serde(default)]
+    pub validation_package_cache_size: usize,
 }
 
 #[cfg(test)]
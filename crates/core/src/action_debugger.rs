@@ -0,0 +1,238 @@
+//! Stepping forward and backward through an action recording.
+//!
+//! An [ActionDebugger] replays a recording one action at a time, like [replay] does all at
+//! once, and can go back to the state before any action. Looking at the state, or a
+//! [StateDump] of it, after each step shows which action broke the holding map or left a
+//! workflow stalled, and [ActionDebugger::run_until] finds the first action after which a
+//! condition on the state holds.
+//!
+//! Going back doesn't reduce anything in reverse. The debugger keeps the state after every
+//! `checkpoint_interval`-th action and replays forward from the closest checkpoint before the
+//! position it goes back to. Reducers that write to storage, like those of commits and held
+//! aspects, leave their content there when the debugger goes back, so content addressed
+//! lookups can find entries that the state at the current position doesn't know about yet.
+//!
+//! [replay]: ../action_recording/fn.replay.html
+use crate::{
+    action_recording::{replayed, RecordedAction},
+    clock::{MockClock, SharedClock},
+    context::Context,
+    state::StateWrapper,
+    state_dump::{DumpOptions, StateDump},
+};
+use holochain_locksmith::RwLock;
+use std::{sync::Arc, time::SystemTime};
+
+/// How many actions lie between two states the debugger keeps by default
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 64;
+
+pub struct ActionDebugger {
+    context: Arc<Context>,
+    clock: MockClock,
+    actions: Vec<RecordedAction>,
+    checkpoint_interval: usize,
+    /// States after every `checkpoint_interval`-th action, starting with the fresh state
+    checkpoints: Vec<StateWrapper>,
+    state: StateWrapper,
+    position: usize,
+}
+
+impl ActionDebugger {
+    /// A debugger at the fresh state of the given context, before the first of the actions.
+    /// Its clock tells the time of each action while the action gets reduced.
+    pub fn new(context: Arc<Context>, actions: Vec<RecordedAction>) -> Self {
+        let clock = MockClock::new(
+            actions
+                .first()
+                .map(|recorded| recorded.time)
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+        );
+        let mut replay_context = (*context).clone();
+        replay_context.clock = SharedClock::new(clock.clone());
+        let context = Arc::new(replay_context);
+        let state = StateWrapper::new(context.clone());
+        ActionDebugger {
+            context,
+            clock,
+            actions,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            checkpoints: vec![state.clone()],
+            state,
+            position: 0,
+        }
+    }
+
+    /// Keeps the state after every `interval`-th action instead of every 64th. Shorter
+    /// intervals make going back faster and take more memory.
+    pub fn with_checkpoint_interval(mut self, interval: usize) -> Self {
+        assert!(interval > 0, "checkpoint interval must not be 0");
+        self.checkpoint_interval = interval;
+        self.checkpoints.truncate(1);
+        let position = self.position;
+        self.seek(0);
+        self.seek(position);
+        self
+    }
+
+    pub fn actions(&self) -> &[RecordedAction] {
+        &self.actions
+    }
+
+    /// Number of actions that have been reduced to get to the current state
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.position == self.actions.len()
+    }
+
+    /// The action that led to the current state, if any
+    pub fn current_action(&self) -> Option<&RecordedAction> {
+        self.position
+            .checked_sub(1)
+            .and_then(|index| self.actions.get(index))
+    }
+
+    /// The action the next step forward reduces, if any
+    pub fn next_action(&self) -> Option<&RecordedAction> {
+        self.actions.get(self.position)
+    }
+
+    pub fn state(&self) -> &StateWrapper {
+        &self.state
+    }
+
+    /// A dump of the current state, like the ones taken of running instances
+    pub fn state_dump(&self, options: DumpOptions) -> StateDump {
+        let mut context = (*self.context).clone();
+        context.set_state(Arc::new(RwLock::new(self.state.clone())));
+        StateDump::new(Arc::new(context), options)
+    }
+
+    /// Reduces the next action. Returns it, or None if there are no more actions.
+    pub fn step_forward(&mut self) -> Option<&RecordedAction> {
+        let recorded = self.actions.get(self.position)?;
+        self.clock.set(recorded.time);
+        self.state = self.state.reduce(replayed(&recorded.action));
+        self.position += 1;
+        if self.position == self.checkpoints.len() * self.checkpoint_interval {
+            self.checkpoints.push(self.state.clone());
+        }
+        self.current_action()
+    }
+
+    /// Goes back to the state before the current action. Returns that action, or None if
+    /// the debugger is at the fresh state already.
+    pub fn step_backward(&mut self) -> Option<&RecordedAction> {
+        let position = self.position.checked_sub(1)?;
+        self.seek(position);
+        self.next_action()
+    }
+
+    /// Goes to the state after the given number of actions, or after all of them if there
+    /// are fewer
+    pub fn seek(&mut self, position: usize) {
+        let position = position.min(self.actions.len());
+        if position < self.position {
+            let checkpoint = (position / self.checkpoint_interval).min(self.checkpoints.len() - 1);
+            self.state = self.checkpoints[checkpoint].clone();
+            self.position = checkpoint * self.checkpoint_interval;
+        }
+        while self.position < position {
+            self.step_forward();
+        }
+    }
+
+    /// Steps forward until the condition holds for the state after an action, and returns
+    /// that action. Returns None if the condition doesn't hold after any of the remaining
+    /// actions, which leaves the debugger at the end of the recording.
+    pub fn run_until<F>(&mut self, mut condition: F) -> Option<&RecordedAction>
+    where
+        F: FnMut(&RecordedAction, &StateWrapper) -> bool,
+    {
+        while !self.is_at_end() {
+            self.step_forward();
+            let index = self.position - 1;
+            if condition(&self.actions[index], &self.state) {
+                return self.current_action();
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        action::{Action, ActionWrapper},
+        action_recording::{replay_context, RecordingHeader},
+    };
+    use holochain_core_types::{
+        agent::AgentId,
+        entry::{test_entry_with_value, Entry},
+    };
+    use holochain_persistence_api::cas::content::AddressableContent;
+    use std::time::Duration;
+
+    fn commits(entries: &[Entry]) -> Vec<RecordedAction> {
+        entries
+            .iter()
+            .enumerate()
+            .map(|(seq, entry)| RecordedAction {
+                seq: seq as u64,
+                time: SystemTime::UNIX_EPOCH + Duration::from_secs(seq as u64),
+                action: ActionWrapper::new(Action::Commit((entry.clone(), None, Vec::new()))),
+            })
+            .collect()
+    }
+
+    fn top_entry(debugger: &ActionDebugger) -> Option<String> {
+        debugger
+            .state()
+            .agent()
+            .top_chain_header()
+            .map(|header| header.entry_address().to_string())
+    }
+
+    #[test]
+    fn steps_forward_and_backward_through_a_recording() {
+        let header = RecordingHeader {
+            instance_name: String::from("app"),
+            agent_id: AgentId::generate_fake("alice"),
+        };
+        let entries: Vec<Entry> = ["0", "1", "2", "3", "4"]
+            .iter()
+            .map(|value| test_entry_with_value(*value))
+            .collect();
+        let address = |i: usize| Some(entries[i].address().to_string());
+        let mut debugger = ActionDebugger::new(replay_context(&header), commits(&entries))
+            .with_checkpoint_interval(2);
+        assert_eq!(top_entry(&debugger), None);
+        assert!(debugger.step_backward().is_none());
+
+        assert_eq!(debugger.step_forward().map(|a| a.seq), Some(0));
+        assert_eq!(top_entry(&debugger), address(0));
+        debugger.seek(5);
+        assert!(debugger.is_at_end());
+        assert!(debugger.step_forward().is_none());
+        assert_eq!(top_entry(&debugger), address(4));
+
+        assert_eq!(debugger.step_backward().map(|a| a.seq), Some(4));
+        assert_eq!(debugger.position(), 4);
+        assert_eq!(top_entry(&debugger), address(3));
+        debugger.seek(1);
+        assert_eq!(top_entry(&debugger), address(0));
+        assert_eq!(debugger.state().agent().iter_chain().count(), 1);
+
+        let found = debugger
+            .run_until(|_, state| state.agent().iter_chain().count() == 3)
+            .map(|a| a.seq);
+        assert_eq!(found, Some(2));
+        let dump = debugger.state_dump(DumpOptions::default());
+        assert_eq!(dump.source_chain.len(), 3);
+        assert!(debugger.run_until(|_, _| false).is_none());
+        assert!(debugger.is_at_end());
+    }
+}
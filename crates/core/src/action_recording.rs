@@ -0,0 +1,288 @@
+//! Recording of the actions an instance reduces, and replay of such recordings.
+//!
+//! While an instance is being recorded, its [ActionRecorder] appends every action it reduces
+//! to a file, with its payload and the time of the instance's clock, in the order they got
+//! reduced. The file holds one JSON object per line, the first one saying which instance and
+//! agent the recording is of.
+//!
+//! [replay] reduces the actions of a recording again, on the fresh state of a separate context
+//! whose clock tells the recorded times, which takes the reducers through the same states as
+//! the recorded instance without running it, its workflows or its network. Replaying the
+//! recording up to a given action narrows down which action a bug came in with, and an
+//! [ActionDebugger] steps through it forward and backward. Recorded network initializations
+//! get a private in-memory network instead of the recorded one.
+//!
+//! Reducers that sign, like those of commits, sign with the conductor API of the context they
+//! replay on. The one of [replay_context] doesn't have the agent's key and signs everything
+//! with the same placeholder, so replayed headers get other addresses than the recorded ones.
+use crate::{
+    action::{Action, ActionWrapper, NetworkSettings},
+    action_debugger::ActionDebugger,
+    context::Context,
+    persister::SimplePersister,
+    state::StateWrapper,
+};
+use holochain_core_types::{agent::AgentId, error::HolochainError};
+use holochain_locksmith::{Mutex, RwLock};
+use holochain_net::p2p_config::P2pConfig;
+use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+use jsonrpc_core::{types::params::Params, IoHandler};
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+/// What replay contexts sign with
+pub const REPLAY_SIGNATURE: &str = "replayed";
+
+/// First line of a recording
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub instance_name: String,
+    pub agent_id: AgentId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedAction {
+    /// Position in the recording, starting at 0
+    pub seq: u64,
+    /// Time of the instance's clock when the action got reduced
+    pub time: SystemTime,
+    pub action: ActionWrapper,
+}
+
+#[derive(Clone, Debug)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub actions: Vec<RecordedAction>,
+}
+
+struct RecordingFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    next_seq: u64,
+}
+
+#[derive(Default)]
+pub struct ActionRecorder {
+    file: Mutex<Option<RecordingFile>>,
+}
+
+fn write_line<T: Serialize>(writer: &mut BufWriter<File>, value: &T) -> Result<(), HolochainError> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writer.write_all(b"\n")?;
+    // Flushed right away, so that the recording of a crashed instance ends with the action
+    // it crashed on
+    writer.flush()?;
+    Ok(())
+}
+
+impl ActionRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.file.lock().unwrap().is_some()
+    }
+
+    /// Starts writing the reduced actions to a new file at the given path, replacing any file
+    /// that is there already
+    pub fn start(&self, path: &Path, header: RecordingHeader) -> Result<(), HolochainError> {
+        let mut file = self.file.lock().unwrap();
+        if let Some(ref recording) = *file {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Instance is already being recorded to {}",
+                recording.path.display()
+            )));
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_line(&mut writer, &header)?;
+        *file = Some(RecordingFile {
+            path: path.to_path_buf(),
+            writer,
+            next_seq: 0,
+        });
+        Ok(())
+    }
+
+    /// Stops recording. Returns the path of the recording, if there was one.
+    pub fn stop(&self) -> Option<PathBuf> {
+        self.file
+            .lock()
+            .unwrap()
+            .take()
+            .map(|recording| recording.path)
+    }
+
+    /// Appends the action to the recording, if there is one
+    pub fn record(&self, time: SystemTime, action: &ActionWrapper) -> Result<(), HolochainError> {
+        if let Some(ref mut recording) = *self.file.lock().unwrap() {
+            let recorded = RecordedAction {
+                seq: recording.next_seq,
+                time,
+                action: action.clone(),
+            };
+            write_line(&mut recording.writer, &recorded)?;
+            recording.next_seq += 1;
+        }
+        Ok(())
+    }
+}
+
+pub fn read_recording(path: &Path) -> Result<Recording, HolochainError> {
+    let content = fs::read_to_string(path)?;
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let broken = |line_number: usize, reason: &str| {
+        HolochainError::ErrorGeneric(format!(
+            "Recording {} is broken at line {}: {}",
+            path.display(),
+            line_number + 1,
+            reason
+        ))
+    };
+    let header: RecordingHeader = match lines.next() {
+        Some((line_number, line)) => {
+            serde_json::from_str(line).map_err(|e| broken(line_number, &e.to_string()))?
+        }
+        None => {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Recording {} is empty",
+                path.display()
+            )))
+        }
+    };
+    let mut actions: Vec<RecordedAction> = Vec::new();
+    for (line_number, line) in lines {
+        let action: RecordedAction =
+            serde_json::from_str(line).map_err(|e| broken(line_number, &e.to_string()))?;
+        if action.seq != actions.len() as u64 {
+            return Err(broken(
+                line_number,
+                &format!("expected action {} but found {}", actions.len(), action.seq),
+            ));
+        }
+        actions.push(action);
+    }
+    Ok(Recording { header, actions })
+}
+
+/// The `agent/sign` method of the conductor API, for replays without the agent's key
+fn placeholder_signing_api() -> IoHandler {
+    let mut handler = IoHandler::new();
+    handler.add_method("agent/sign", |params: Params| {
+        let params: serde_json::Map<String, serde_json::Value> = params.parse()?;
+        let payload = params
+            .get("payload")
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        Ok(json!({"payload": payload, "signature": REPLAY_SIGNATURE}))
+    });
+    handler
+}
+
+/// A context with empty in-memory storage for the agent of the recording to replay it on
+pub fn replay_context(header: &RecordingHeader) -> Arc<Context> {
+    let storage = Arc::new(RwLock::new(MemoryStorage::new()));
+    Arc::new(Context::new(
+        &header.instance_name,
+        header.agent_id.clone(),
+        Arc::new(RwLock::new(SimplePersister::new(storage.clone()))),
+        storage.clone(),
+        storage,
+        Arc::new(RwLock::new(EavMemoryStorage::new())),
+        P2pConfig::new_with_unique_memory_backend(),
+        Some(Arc::new(RwLock::new(placeholder_signing_api()))),
+        None,
+        false,
+        holochain_metrics::config::MetricPublisherConfig::default().create_metric_publisher(),
+        Arc::new(ht::null_tracer()),
+    ))
+}
+
+/// The recorded action as it gets replayed
+pub(crate) fn replayed(action_wrapper: &ActionWrapper) -> ActionWrapper {
+    match action_wrapper.action() {
+        Action::InitNetwork(settings) => {
+            action_wrapper.with_action(Action::InitNetwork(NetworkSettings {
+                p2p_config: P2pConfig::new_with_unique_memory_backend(),
+                ..settings.clone()
+            }))
+        }
+        _ => action_wrapper.clone(),
+    }
+}
+
+/// Reduces the recorded actions on a fresh state of the given context, up to and including
+/// the action with the sequence number `until` if one is given, and returns the state
+/// they lead to
+pub fn replay(
+    context: Arc<Context>,
+    actions: &[RecordedAction],
+    until: Option<u64>,
+) -> StateWrapper {
+    let position = actions
+        .iter()
+        .take_while(|recorded| until.map_or(true, |until| recorded.seq <= until))
+        .count();
+    let mut debugger = ActionDebugger::new(context, actions[..position].to_vec());
+    debugger.seek(position);
+    debugger.state().clone()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{entry::test_entry_with_value, time::Iso8601};
+    use holochain_persistence_api::cas::content::AddressableContent;
+    use std::time::Duration;
+
+    #[test]
+    fn recordings_replay_to_the_recorded_chain() {
+        let path = std::env::temp_dir().join("holochain_core_action_recording_test");
+        let header = RecordingHeader {
+            instance_name: String::from("app"),
+            agent_id: AgentId::generate_fake("alice"),
+        };
+        let recorder = ActionRecorder::new();
+        assert!(recorder
+            .record(SystemTime::now(), &ActionWrapper::new(Action::Ping))
+            .is_ok());
+        recorder.start(&path, header.clone()).unwrap();
+        assert!(recorder.is_recording());
+        assert!(recorder.start(&path, header.clone()).is_err());
+
+        let first = test_entry_with_value("{\"stuff\":\"first\"}");
+        let second = test_entry_with_value("{\"stuff\":\"second\"}");
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        for (i, entry) in [first.clone(), second.clone()].iter().enumerate() {
+            let commit = Action::Commit((entry.clone(), None, Vec::new()));
+            let time = start + Duration::from_secs(i as u64);
+            recorder.record(time, &ActionWrapper::new(commit)).unwrap();
+        }
+        assert_eq!(recorder.stop(), Some(path.clone()));
+        assert!(!recorder.is_recording());
+
+        let recording = read_recording(&path).unwrap();
+        assert_eq!(recording.header, header);
+        assert_eq!(recording.actions.len(), 2);
+
+        let state = replay(replay_context(&header), &recording.actions, None);
+        let top = state.agent().top_chain_header().unwrap();
+        assert_eq!(top.entry_address(), &second.address());
+        assert_eq!(top.timestamp(), &Iso8601::new(1_001, 0));
+
+        let state = replay(replay_context(&header), &recording.actions, Some(0));
+        let top = state.agent().top_chain_header().unwrap();
+        assert_eq!(top.entry_address(), &first.address());
+        assert_eq!(top.timestamp(), &Iso8601::new(1_000, 0));
+    }
+}
@@ -1,12 +1,14 @@
 use crate::{
     action::{Action, ActionWrapper},
-    agent::state::AgentActionResponse,
+    agent::state::{AgentActionResponse, StagedEntry},
     context::Context,
+    event_log::{record_instance_event, EVENT_COMMIT},
     instance::dispatch_action,
 };
 use futures::{future::Future, task::Poll};
 use holochain_core_types::{entry::Entry, error::HolochainError};
 use holochain_persistence_api::cas::content::Address;
+use serde_json::json;
 use snowflake::ProcessUniqueId;
 use std::{pin::Pin, sync::Arc};
 
@@ -27,13 +29,64 @@ pub async fn commit_entry(
         vec![],
     )));
     dispatch_action(context.action_channel(), action_wrapper.clone());
-    let id = ProcessUniqueId::new();
-    CommitFuture {
+    let id = context.id_generator.next_id();
+    let result = CommitFuture {
         context: context.clone(),
         action: action_wrapper,
         id,
     }
-    .await
+    .await;
+    if let Ok(ref address) = result {
+        if let Err(e) = record_instance_event(
+            context,
+            EVENT_COMMIT,
+            json!({
+                "address": address,
+                "entry_type": entry.entry_type().to_string(),
+            }),
+        ) {
+            log_error!(context, "commit: could not record event: {:?}", e);
+        }
+    }
+    result
+}
+
+/// CommitBatch Action Creator
+/// Commits several entries in one go, chaining them in the given order.
+/// All entries are written within a single state transition, which means the state only
+/// gets persisted once for the whole batch instead of once per entry.
+///
+/// Returns a future that resolves to the addresses of the committed entries.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn commit_entries(
+    entries: Vec<StagedEntry>,
+    context: &Arc<Context>,
+) -> Result<Vec<Address>, HolochainError> {
+    let entry_types: Vec<String> = entries
+        .iter()
+        .map(|staged| staged.0.entry_type().to_string())
+        .collect();
+    let action_wrapper = ActionWrapper::new(Action::CommitBatch(entries));
+    dispatch_action(context.action_channel(), action_wrapper.clone());
+    let id = context.id_generator.next_id();
+    let result = CommitBatchFuture {
+        context: context.clone(),
+        action: action_wrapper,
+        id,
+    }
+    .await;
+    if let Ok(ref addresses) = result {
+        for (address, entry_type) in addresses.iter().zip(entry_types) {
+            if let Err(e) = record_instance_event(
+                context,
+                EVENT_COMMIT,
+                json!({"address": address, "entry_type": entry_type}),
+            ) {
+                log_error!(context, "commit_entries: could not record event: {:?}", e);
+            }
+        }
+    }
+    result
 }
 
 /// CommitFuture resolves to ActionResponse
@@ -76,3 +129,43 @@ impl Future for CommitFuture {
         }
     }
 }
+
+/// CommitBatchFuture resolves to the addresses of all entries of a CommitBatch action
+pub struct CommitBatchFuture {
+    context: Arc<Context>,
+    action: ActionWrapper,
+    id: ProcessUniqueId,
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+impl Future for CommitBatchFuture {
+    type Output = Result<Vec<Address>, HolochainError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context) -> Poll<Self::Output> {
+        if let Some(err) = self.context.action_channel_error("CommitBatchFuture") {
+            return Poll::Ready(Err(err));
+        }
+        self.context
+            .register_waker(self.id.clone(), cx.waker().clone());
+        if let Some(state) = self.context.try_state() {
+            match state.agent().actions().get(&self.action) {
+                Some(r) => match r.response() {
+                    AgentActionResponse::CommitBatch(result) => {
+                        dispatch_action(
+                            self.context.action_channel(),
+                            ActionWrapper::new(Action::ClearActionResponse(
+                                self.action.id().to_string(),
+                            )),
+                        );
+                        self.context.unregister_waker(self.id.clone());
+                        Poll::Ready(result.clone())
+                    }
+                    _ => unreachable!(),
+                },
+                None => Poll::Pending,
+            }
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -1 +1,3 @@
 pub mod commit;
+pub mod rollback;
+pub mod staging;
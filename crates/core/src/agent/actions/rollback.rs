@@ -0,0 +1,221 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    agent::state::AgentActionResponse,
+    context::Context,
+    instance::dispatch_action,
+};
+use futures::{future::Future, task::Poll};
+use holochain_core_types::{entry::entry_type::EntryType, error::HolochainError};
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use snowflake::ProcessUniqueId;
+use std::{collections::HashSet, pin::Pin, sync::Arc};
+
+/// RollbackChain Action Creator
+/// Truncates the local source chain so that the header with the given address becomes the top
+/// of the chain again. Entries committed after that header are dropped from the chain and are no
+/// longer reported as held, so the same entries can get committed again. Until then, getting
+/// them, their headers or links from or to them comes up empty.
+///
+/// This is a development tool: it rewrites history without telling the network, so it must never
+/// be used on instances that take part in a real network.
+/// Rolling back across the DNA or agent entry is refused.
+///
+/// Returns a future that resolves to the addresses of the dropped headers, newest first.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn rollback_chain(
+    target: Address,
+    context: &Arc<Context>,
+) -> Result<Vec<Address>, HolochainError> {
+    let state = context
+        .state()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Could not get state".to_string()))?;
+
+    let mut dropped_headers = Vec::new();
+    let mut target_header = None;
+    let mut chain = state.agent().iter_chain();
+    for header in &mut chain {
+        if header.address() == target {
+            target_header = Some(header);
+            break;
+        }
+        match header.entry_type() {
+            EntryType::Dna | EntryType::AgentId => {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Can not roll back to {} without removing the genesis entries",
+                    target
+                )));
+            }
+            _ => dropped_headers.push(header),
+        }
+    }
+    let target_header = target_header.ok_or_else(|| {
+        HolochainError::ErrorGeneric(format!("Header {} is not part of the source chain", target))
+    })?;
+
+    log_warn!(
+        context,
+        "agent/rollback_chain: dropping {} headers on top of {}",
+        dropped_headers.len(),
+        target
+    );
+
+    // Entries that are also committed further down the chain stay
+    let kept_entries: HashSet<Address> = std::iter::once(target_header.clone())
+        .chain(chain)
+        .map(|header| header.entry_address().clone())
+        .collect();
+    let mut orphaned: Vec<Address> = Vec::new();
+    for header in dropped_headers.iter() {
+        orphaned.push(header.address());
+        let entry_address = header.entry_address();
+        if !kept_entries.contains(entry_address) && !orphaned.contains(entry_address) {
+            orphaned.push(entry_address.clone());
+        }
+    }
+
+    let action_wrapper = ActionWrapper::new(Action::RollbackChain((
+        target_header,
+        dropped_headers,
+        orphaned,
+    )));
+    dispatch_action(context.action_channel(), action_wrapper.clone());
+    RollbackFuture {
+        context: context.clone(),
+        action: action_wrapper,
+        id: context.id_generator.next_id(),
+    }
+    .await
+}
+
+/// RollbackFuture resolves to the addresses of the dropped headers
+pub struct RollbackFuture {
+    context: Arc<Context>,
+    action: ActionWrapper,
+    id: ProcessUniqueId,
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+impl Future for RollbackFuture {
+    type Output = Result<Vec<Address>, HolochainError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context) -> Poll<Self::Output> {
+        if let Some(err) = self.context.action_channel_error("RollbackFuture") {
+            return Poll::Ready(Err(err));
+        }
+        self.context
+            .register_waker(self.id.clone(), cx.waker().clone());
+        if let Some(state) = self.context.try_state() {
+            match state.agent().actions().get(&self.action) {
+                Some(r) => match r.response() {
+                    AgentActionResponse::RollbackChain(dropped) => {
+                        dispatch_action(
+                            self.context.action_channel(),
+                            ActionWrapper::new(Action::ClearActionResponse(
+                                self.action.id().to_string(),
+                            )),
+                        );
+                        self.context.unregister_waker(self.id.clone());
+                        Poll::Ready(Ok(dropped.clone()))
+                    }
+                    _ => unreachable!(),
+                },
+                None => Poll::Pending,
+            }
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        agent::actions::commit::commit_entry,
+        full_consistency::FullConsistencyWaiter,
+        network::test_utils::test_wat_always_valid,
+        nucleus::actions::{get_entry::get_entry_with_meta, tests::instance_by_name},
+        workflows::author_entry::author_entry,
+    };
+    use holochain_core_types::{
+        agent::test_agent_id,
+        chain_header::test_chain_header,
+        entry::{test_entry, Entry},
+        link::link_data::LinkData,
+    };
+    use std::time::Duration;
+    use test_utils::create_test_dna_with_wat;
+
+    #[test]
+    fn rolled_back_entries_and_links_cannot_be_found() {
+        let mut dna = create_test_dna_with_wat("test_zome", Some(&test_wat_always_valid()));
+        dna.uuid = String::from("rolled_back_entries_and_links_cannot_be_found");
+        let netname = Some("rolled_back_entries_and_links_cannot_be_found, the network");
+        let (_instance, context) = instance_by_name("jane", dna, netname);
+        let waiter = FullConsistencyWaiter::new(&[context.clone()]);
+        let target = context.state().unwrap().agent().top_chain_header().unwrap();
+
+        let entry = test_entry();
+        let link_entry = Entry::LinkAdd(LinkData::new_add(
+            &entry.address(),
+            &entry.address(),
+            "test-tag",
+            "test-link",
+            test_chain_header(),
+            test_agent_id(),
+        ));
+        context
+            .block_on(author_entry(&entry, None, &context, &vec![]))
+            .unwrap();
+        context
+            .block_on(author_entry(&link_entry, None, &context, &vec![]))
+            .unwrap();
+        waiter.wait(Duration::from_secs(10)).unwrap();
+        let links = |context: &Arc<Context>| {
+            context
+                .state()
+                .unwrap()
+                .dht()
+                .get_links(
+                    entry.address(),
+                    Some("test-link".to_string()),
+                    None,
+                    None,
+                    Default::default(),
+                )
+                .unwrap()
+        };
+        assert!(get_entry_with_meta(&context, entry.address())
+            .unwrap()
+            .is_some());
+        assert_eq!(links(&context).len(), 1);
+
+        let dropped = context
+            .block_on(rollback_chain(target.address(), &context))
+            .unwrap();
+        assert_eq!(dropped.len(), 2);
+        assert_eq!(
+            context.state().unwrap().agent().top_chain_header(),
+            Some(target)
+        );
+        assert_eq!(
+            get_entry_with_meta(&context, entry.address()).unwrap(),
+            None
+        );
+        assert!(context
+            .state()
+            .unwrap()
+            .get_headers(entry.address())
+            .unwrap()
+            .is_empty());
+        assert!(links(&context).is_empty());
+
+        // Committing the entry again brings it back
+        context
+            .block_on(commit_entry(entry.clone(), None, &context))
+            .unwrap();
+        assert!(get_entry_with_meta(&context, entry.address())
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    agent::state::{AgentActionResponse, StagedEntry},
+    context::Context,
+    instance::dispatch_action,
+};
+use futures::{future::Future, task::Poll};
+use holochain_core_types::{entry::Entry, error::HolochainError, signature::Provenance};
+use holochain_persistence_api::cas::content::Address;
+use snowflake::ProcessUniqueId;
+use std::{pin::Pin, sync::Arc};
+
+/// StageEntry Action Creator
+/// Puts the given entry into the agent's staging area.
+/// Does not validate, assumes entry is valid.
+///
+/// Returns a future that resolves to the address of the staged entry.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn stage_entry(
+    entry: Entry,
+    maybe_link_update_delete: Option<Address>,
+    provenances: Vec<Provenance>,
+    context: &Arc<Context>,
+) -> Result<Address, HolochainError> {
+    let action_wrapper = ActionWrapper::new(Action::StageEntry((
+        entry,
+        maybe_link_update_delete,
+        provenances,
+    )));
+    dispatch_action(context.action_channel(), action_wrapper.clone());
+    match (StagingFuture {
+        context: context.clone(),
+        action: action_wrapper,
+        id: context.id_generator.next_id(),
+    })
+    .await?
+    {
+        AgentActionResponse::StageEntry(address) => Ok(address),
+        _ => unreachable!(),
+    }
+}
+
+/// TakeStagedEntries Action Creator
+/// Empties the agent's staging area.
+///
+/// Returns a future that resolves to the entries that were staged, in staging order.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn take_staged_entries(
+    context: &Arc<Context>,
+) -> Result<Vec<StagedEntry>, HolochainError> {
+    let action_wrapper = ActionWrapper::new(Action::TakeStagedEntries);
+    dispatch_action(context.action_channel(), action_wrapper.clone());
+    match (StagingFuture {
+        context: context.clone(),
+        action: action_wrapper,
+        id: context.id_generator.next_id(),
+    })
+    .await?
+    {
+        AgentActionResponse::TakeStagedEntries(staged_entries) => Ok(staged_entries),
+        _ => unreachable!(),
+    }
+}
+
+/// StagingFuture resolves to the AgentActionResponse of a staging action
+pub struct StagingFuture {
+    context: Arc<Context>,
+    action: ActionWrapper,
+    id: ProcessUniqueId,
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+impl Future for StagingFuture {
+    type Output = Result<AgentActionResponse, HolochainError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context) -> Poll<Self::Output> {
+        if let Some(err) = self.context.action_channel_error("StagingFuture") {
+            return Poll::Ready(Err(err));
+        }
+        self.context
+            .register_waker(self.id.clone(), cx.waker().clone());
+        if let Some(state) = self.context.try_state() {
+            match state.agent().actions().get(&self.action) {
+                Some(r) => {
+                    dispatch_action(
+                        self.context.action_channel(),
+                        ActionWrapper::new(Action::ClearActionResponse(
+                            self.action.id().to_string(),
+                        )),
+                    );
+                    self.context.unregister_waker(self.id.clone());
+                    Poll::Ready(Ok(r.response().clone()))
+                }
+                None => Poll::Pending,
+            }
+        } else {
+            Poll::Pending
+        }
+    }
+}
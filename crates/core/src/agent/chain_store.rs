@@ -1,4 +1,7 @@
-use crate::content_store::{AddContent, GetContent};
+use crate::content_store::{
+    add_with_shared_dna, fetch_with_shared_dna, AddContent, GetContent, SharedDnaStorage,
+    StorageWriteBatch,
+};
 use globset::{GlobBuilder, GlobSetBuilder};
 use holochain_core_types::{
     chain_header::ChainHeader,
@@ -19,6 +22,11 @@ use std::{str::FromStr, sync::Arc};
 pub struct ChainStore {
     // Storages holding local shard data
     content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    // Conductor wide storage that DNA entries get written to instead, if set
+    dna_storage: Option<SharedDnaStorage>,
+    // Writes of the current action, if they get batched (see begin_write_batch).
+    // Shared with clones, like the ones iterators hold, and only copied when a clone writes.
+    write_batch: Option<Arc<StorageWriteBatch>>,
 }
 
 impl PartialEq for ChainStore {
@@ -47,11 +55,51 @@ pub enum ChainStoreQueryResult {
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 impl ChainStore {
     pub fn new(content_storage: Arc<RwLock<dyn ContentAddressableStorage>>) -> Self {
-        ChainStore { content_storage }
+        ChainStore {
+            content_storage,
+            dna_storage: None,
+            write_batch: None,
+        }
+    }
+
+    /// Keep DNA entries in the given shared storage instead of the chain's own storage.
+    pub fn with_dna_storage(mut self, dna_storage: Option<SharedDnaStorage>) -> Self {
+        self.dna_storage = dna_storage;
+        self
+    }
+
+    /// Starts collecting all writes in a batch, until it gets committed with
+    /// [commit_write_batch](ChainStore::commit_write_batch) or the store gets dropped.
+    /// Returns false if a batch was already started, in which case that one keeps collecting.
+    pub(crate) fn begin_write_batch(&mut self) -> bool {
+        if self.write_batch.is_some() {
+            return false;
+        }
+        self.write_batch = Some(Arc::new(StorageWriteBatch::new()));
+        true
+    }
+
+    /// Writes everything collected since [begin_write_batch](ChainStore::begin_write_batch)
+    /// to the storage at once.
+    pub(crate) fn commit_write_batch(&mut self) -> HcResult<()> {
+        match self.write_batch.take() {
+            Some(batch) => Arc::try_unwrap(batch)
+                .unwrap_or_else(|shared| (*shared).clone())
+                .commit(&self.content_storage, None, &self.dna_storage),
+            None => Ok(()),
+        }
+    }
+
+    /// Content that got added since [begin_write_batch](ChainStore::begin_write_batch) and
+    /// is not written to the storage yet.
+    pub(crate) fn get_uncommitted_raw(&self, address: &Address) -> Option<Content> {
+        self.write_batch
+            .as_ref()
+            .and_then(|batch| batch.get_raw(address))
     }
 
     pub fn iter(&self, start_chain_header: &Option<ChainHeader>) -> ChainStoreIterator {
-        ChainStoreIterator::new(self.content_storage.clone(), start_chain_header.clone())
+        ChainStoreIterator::new(self.clone(), start_chain_header.clone())
     }
 
     /// Scans the local chain for the first Entry of EntryType, and then creates a
@@ -63,7 +111,7 @@ impl ChainStore {
         entry_type: &EntryType,
     ) -> ChainStoreTypeIterator {
         ChainStoreTypeIterator::new(
-            self.content_storage.clone(),
+            self.clone(),
             self.iter(start_chain_header)
                 .find(|chain_header| chain_header.entry_type() == entry_type),
         )
@@ -187,15 +235,22 @@ impl ChainStore {
 
 impl GetContent for ChainStore {
     fn get_raw(&self, address: &Address) -> HcResult<Option<Content>> {
-        Ok((*self.content_storage.read().unwrap()).fetch(address)?)
+        if let Some(content) = self.get_uncommitted_raw(address) {
+            return Ok(Some(content));
+        }
+        fetch_with_shared_dna(&self.content_storage, &self.dna_storage, address)
     }
 }
 
 impl AddContent for ChainStore {
     fn add<T: AddressableContent>(&mut self, content: &T) -> HcResult<()> {
-        (*self.content_storage.write().unwrap())
-            .add(content)
-            .map_err(|e| e.into())
+        match self.write_batch {
+            Some(ref mut batch) => {
+                Arc::make_mut(batch).add(content);
+                Ok(())
+            }
+            None => add_with_shared_dna(&self.content_storage, &self.dna_storage, content),
+        }
     }
 }
 
@@ -203,20 +258,18 @@ impl AddContent for ChainStore {
 ///
 /// # Remarks
 ///
-/// Locates the next Entry by following ChainHeader's .link
+/// Locates the next Entry by following ChainHeader's .link, also through headers that are
+/// still in the chain store's write batch
 ///
 pub struct ChainStoreIterator {
-    content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    chain_store: ChainStore,
     current: Option<ChainHeader>,
 }
 
 impl ChainStoreIterator {
-    pub fn new(
-        content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
-        current: Option<ChainHeader>,
-    ) -> ChainStoreIterator {
+    pub fn new(chain_store: ChainStore, current: Option<ChainHeader>) -> ChainStoreIterator {
         ChainStoreIterator {
-            content_storage,
+            chain_store,
             current,
         }
     }
@@ -230,7 +283,6 @@ impl Iterator for ChainStoreIterator {
     /// May panic if there is an underlying error in the table
     fn next(&mut self) -> Option<ChainHeader> {
         let previous = self.current.take();
-        let storage = &self.content_storage.clone();
         self.current = previous
             .as_ref()
             .and_then(|chain_header| chain_header.link())
@@ -238,10 +290,8 @@ impl Iterator for ChainStoreIterator {
             // @TODO should this panic?
             // @see https://github.com/holochain/holochain-rust/issues/146
             .and_then(|linked_chain_header_address| {
-                storage
-                    .read()
-                    .unwrap()
-                    .fetch(linked_chain_header_address)
+                self.chain_store
+                    .get_raw(linked_chain_header_address)
                     .expect("failed to fetch from CAS")
                     .map(|content| {
                         ChainHeader::try_from_content(&content)
@@ -262,17 +312,14 @@ impl Iterator for ChainStoreIterator {
 /// multiple EntryType queries.
 ///
 pub struct ChainStoreTypeIterator {
-    content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    chain_store: ChainStore,
     current: Option<ChainHeader>,
 }
 
 impl ChainStoreTypeIterator {
-    pub fn new(
-        content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
-        current: Option<ChainHeader>,
-    ) -> ChainStoreTypeIterator {
+    pub fn new(chain_store: ChainStore, current: Option<ChainHeader>) -> ChainStoreTypeIterator {
         ChainStoreTypeIterator {
-            content_storage,
+            chain_store,
             current,
         }
     }
@@ -285,7 +332,6 @@ impl Iterator for ChainStoreTypeIterator {
     /// May panic if there is an underlying error in the table
     fn next(&mut self) -> Option<ChainHeader> {
         let previous = self.current.take();
-        let storage = &self.content_storage.clone();
         self.current = previous
             .as_ref()
             .and_then(|chain_header| chain_header.link_same_type())
@@ -293,10 +339,8 @@ impl Iterator for ChainStoreTypeIterator {
             // @TODO should this panic?
             // @see https://github.com/holochain/holochain-rust/issues/146
             .and_then(|linked_chain_header_address| {
-                storage
-                    .read()
-                    .unwrap()
-                    .fetch(linked_chain_header_address)
+                self.chain_store
+                    .get_raw(linked_chain_header_address)
                     .expect("failed to fetch from CAS")
                     .map(|content| {
                         ChainHeader::try_from_content(&content)
@@ -310,7 +354,10 @@ impl Iterator for ChainStoreTypeIterator {
 #[cfg(test)]
 pub mod tests {
     use self::tempfile::tempdir;
-    use crate::agent::chain_store::{ChainStore, ChainStoreQueryOptions, ChainStoreQueryResult};
+    use crate::{
+        agent::chain_store::{ChainStore, ChainStoreQueryOptions, ChainStoreQueryResult},
+        content_store::{AddContent, GetContent},
+    };
     use holochain_core_types::{
         chain_header::{test_chain_header, test_provenances, ChainHeader},
         entry::{
@@ -323,6 +370,7 @@ pub mod tests {
     use holochain_locksmith::RwLock;
     use holochain_persistence_api::cas::content::AddressableContent;
     use holochain_persistence_file::cas::file::FilesystemStorage;
+    use std::sync::Arc;
     use tempfile;
 
     pub fn test_chain_store() -> ChainStore {
@@ -332,6 +380,45 @@ pub mod tests {
         )))
     }
 
+    #[test]
+    /// iterators walk through headers of the write batch without copying it
+    fn iterators_share_the_write_batch() {
+        let mut chain_store = test_chain_store();
+        let chain_header = test_chain_header();
+        assert!(chain_store.begin_write_batch());
+        chain_store.add(&chain_header).unwrap();
+
+        let iter = chain_store.iter(&Some(chain_header.clone()));
+        assert!(Arc::ptr_eq(
+            chain_store.write_batch.as_ref().unwrap(),
+            iter.chain_store.write_batch.as_ref().unwrap()
+        ));
+        assert_eq!(
+            iter.collect::<Vec<ChainHeader>>(),
+            vec![chain_header.clone()]
+        );
+
+        // Writing while an iterator holds the batch leaves the iterator's batch as it was
+        let iter = chain_store.iter(&Some(chain_header.clone()));
+        chain_store.add(&test_entry()).unwrap();
+        assert!(iter
+            .chain_store
+            .get_raw(&test_entry().address())
+            .unwrap()
+            .is_none());
+        assert!(chain_store
+            .get_raw(&test_entry().address())
+            .unwrap()
+            .is_some());
+
+        chain_store.commit_write_batch().unwrap();
+        assert!(chain_store.write_batch.is_none());
+        assert!(chain_store
+            .get_raw(&chain_header.address())
+            .unwrap()
+            .is_some());
+    }
+
     #[test]
     /// show Iterator implementation for chain store
     fn iterator_test() {
@@ -14,19 +14,27 @@ use bitflags::_core::time::Duration;
 use holochain_core_types::{
     agent::AgentId,
     chain_header::ChainHeader,
+    dna::capabilities::CapabilityUseRecord,
     entry::{entry_type::EntryType, Entry},
     error::{HcResult, HolochainError},
     signature::{Provenance, Signature},
-    time::Iso8601,
 };
 use holochain_json_api::{
     error::{JsonError, JsonResult},
     json::JsonString,
 };
 use holochain_wasm_utils::api_serialization::crypto::CryptoMethod;
-use im::HashMap;
+use im::{HashMap, HashSet};
 use serde_json;
-use std::{convert::TryFrom, ops::Deref, sync::Arc, time::SystemTime};
+use std::{collections::VecDeque, convert::TryFrom, ops::Deref, sync::Arc, time::SystemTime};
+
+/// An entry that passed authoring validation but is not yet part of the source chain,
+/// together with the arguments it will get committed with.
+pub type StagedEntry = (Entry, Option<Address>, Vec<Provenance>);
+
+/// Number of capability-gated calls kept in the capability audit log.
+/// Older records get dropped first.
+pub const CAP_AUDIT_LOG_CAPACITY: usize = 1000;
 
 /// The state-slice for the Agent.
 /// Holds the agent's source chain and keys.
@@ -36,7 +44,20 @@ pub struct AgentState {
     actions: HashMap<ActionWrapper, Response>,
     chain_store: ChainStore,
     top_chain_header: Option<ChainHeader>,
+    /// number of headers in the source chain, kept in sync with top_chain_header
+    chain_length: u64,
     initial_agent_address: Address,
+    /// peers that sent us a holding receipt, per address of the authored header
+    header_holders: HashMap<Address, HashSet<Address>>,
+    /// validated entries waiting to be committed or discarded, in staging order
+    staged_entries: Vec<StagedEntry>,
+    /// tokens of capability grants made to us that their grantor told us are revoked
+    revoked_cap_tokens: HashSet<Address>,
+    /// tokens of capability grants made to us that their grantor replaced, mapped to
+    /// the replacement token
+    rotated_cap_tokens: HashMap<Address, Address>,
+    /// the most recent calls made with capability tokens, oldest first
+    cap_audit_log: VecDeque<CapabilityUseRecord>,
 }
 
 #[autotrace]
@@ -48,7 +69,13 @@ impl AgentState {
             actions: HashMap::new(),
             chain_store,
             top_chain_header: None,
+            chain_length: 0,
             initial_agent_address,
+            header_holders: HashMap::new(),
+            staged_entries: Vec::new(),
+            revoked_cap_tokens: HashSet::new(),
+            rotated_cap_tokens: HashMap::new(),
+            cap_audit_log: VecDeque::new(),
         }
     }
 
@@ -57,11 +84,18 @@ impl AgentState {
         chain_header: Option<ChainHeader>,
         initial_agent_address: Address,
     ) -> AgentState {
+        let chain_length = chain_store.iter(&chain_header).count() as u64;
         AgentState {
             actions: HashMap::new(),
             chain_store,
             top_chain_header: chain_header,
+            chain_length,
             initial_agent_address,
+            header_holders: HashMap::new(),
+            staged_entries: Vec::new(),
+            revoked_cap_tokens: HashSet::new(),
+            rotated_cap_tokens: HashMap::new(),
+            cap_audit_log: VecDeque::new(),
         }
     }
 
@@ -83,17 +117,33 @@ impl AgentState {
         self.chain_store.iter(&self.top_chain_header)
     }
 
-    pub fn get_agent_address(&self) -> HcResult<Address> {
+    /// Number of headers in the source chain.
+    /// The sequence number of the top chain header is this minus one.
+    pub fn chain_length(&self) -> u64 {
+        self.chain_length
+    }
+
+    fn latest_agent_entry_address(&self) -> Option<Address> {
         self.chain_store()
             .iter_type(&self.top_chain_header, &EntryType::AgentId)
             .nth(0)
             .map(|chain_header| chain_header.entry_address().clone())
-            .or_else(|| Some(self.initial_agent_address.clone()))
-            .ok_or_else(|| HolochainError::ErrorGeneric("Agent entry not found".to_string()))
     }
 
+    /// The agent address, i.e. the public signing key. It stays the same through identity
+    /// updates, which are stored under addresses of their own.
+    pub fn get_agent_address(&self) -> HcResult<Address> {
+        match self.latest_agent_entry_address() {
+            Some(_) => Ok(self.get_agent()?.address()),
+            None => Ok(self.initial_agent_address.clone()),
+        }
+    }
+
+    /// The latest revision of the agent's identity entry
     pub fn get_agent(&self) -> HcResult<AgentId> {
-        let agent_entry_address = self.get_agent_address()?;
+        let agent_entry_address = self
+            .latest_agent_entry_address()
+            .unwrap_or_else(|| self.initial_agent_address.clone());
         let agent_entry = self
             .chain_store()
             .get(&agent_entry_address)?
@@ -105,6 +155,102 @@ impl AgentState {
         }
     }
 
+    /// Restores the holding receipts of a persisted agent state
+    pub fn with_header_holders(
+        mut self,
+        header_holders: HashMap<Address, HashSet<Address>>,
+    ) -> Self {
+        self.header_holders = header_holders;
+        self
+    }
+
+    /// Peers that sent us a holding receipt, per address of the authored header
+    pub fn header_holders(&self) -> HashMap<Address, HashSet<Address>> {
+        self.header_holders.clone()
+    }
+
+    /// Peers that reported holding the authored entry with the given address, through
+    /// any of the headers that committed it.
+    fn holders_of_entry(&self, entry_address: &Address) -> HashSet<Address> {
+        self.iter_chain()
+            .filter(|header| header.entry_address() == entry_address)
+            .filter_map(|header| self.header_holders.get(&header.address()))
+            .fold(HashSet::new(), |all, holders| all.union(holders.clone()))
+    }
+
+    /// All peers that reported holding the authored entry with the given address.
+    pub fn entry_holders(&self, entry_address: &Address) -> Vec<Address> {
+        self.holders_of_entry(entry_address).into_iter().collect()
+    }
+
+    /// Number of distinct peers that reported holding the given authored entry.
+    pub fn entry_holder_count(&self, entry_address: &Address) -> usize {
+        self.holders_of_entry(entry_address).len()
+    }
+
+    /// true if the grantor of the capability with the given token told us it got revoked
+    pub fn is_cap_token_revoked(&self, token: &Address) -> bool {
+        self.revoked_cap_tokens.contains(token)
+    }
+
+    /// The token to use in place of the given one: the latest replacement if its grantor
+    /// rotated the grant, the token itself otherwise.
+    pub fn current_cap_token(&self, token: &Address) -> Address {
+        let mut current = token.clone();
+        // bounded in case of a rotation cycle
+        for _ in 0..self.rotated_cap_tokens.len() {
+            match self.rotated_cap_tokens.get(&current) {
+                Some(replacement) => current = replacement.clone(),
+                None => break,
+            }
+        }
+        current
+    }
+
+    /// Recorded capability-gated calls, oldest first, optionally only those made
+    /// with the given token.
+    pub fn cap_audit_log(&self, token: Option<&Address>) -> Vec<CapabilityUseRecord> {
+        self.cap_audit_log
+            .iter()
+            .filter(|record| token.map(|token| &record.token == token).unwrap_or(true))
+            .cloned()
+            .collect()
+    }
+
+    /// Entries currently in the staging area, in the order they were staged.
+    pub fn staged_entries(&self) -> Vec<StagedEntry> {
+        self.staged_entries.clone()
+    }
+
+    /// Returns a copy of this state with the staged entry appended to its chain, for
+    /// validating the entries that follow it in the same batch.
+    /// Nothing gets written to the storage: the entry and its header only live in the write
+    /// batch of the copy's chain store.
+    pub(crate) fn with_uncommitted_entry(
+        &self,
+        root_state: &StateWrapper,
+        staged_entry: &StagedEntry,
+    ) -> Result<AgentState, HolochainError> {
+        let (entry, maybe_link_update_delete, provenances) = staged_entry;
+        let mut new_state = self.clone();
+        new_state.chain_store.begin_write_batch();
+        append_to_chain(
+            &mut new_state,
+            root_state,
+            entry,
+            maybe_link_update_delete,
+            provenances,
+        )?;
+        Ok(new_state)
+    }
+
+    /// An entry that got appended with [with_uncommitted_entry](AgentState::with_uncommitted_entry)
+    pub fn uncommitted_entry(&self, address: &Address) -> Option<Entry> {
+        self.chain_store
+            .get_uncommitted_raw(address)
+            .and_then(|content| Entry::try_from_content(&content).ok())
+    }
+
     pub fn get_most_recent_header_for_entry(&self, entry: &Entry) -> Option<ChainHeader> {
         self.chain_store()
             .iter_type(&self.top_chain_header(), &entry.entry_type())
@@ -115,27 +261,40 @@ impl AgentState {
 #[derive(Clone, Debug, Deserialize, Serialize, DefaultJson)]
 pub struct AgentStateSnapshot {
     top_chain_header: Option<ChainHeader>,
+    #[serde(default)]
+    header_holders: HashMap<Address, HashSet<Address>>,
 }
 
 impl AgentStateSnapshot {
     pub fn new(chain_header: Option<ChainHeader>) -> AgentStateSnapshot {
         AgentStateSnapshot {
             top_chain_header: chain_header,
+            header_holders: HashMap::new(),
         }
     }
+    pub fn with_header_holders(
+        mut self,
+        header_holders: HashMap<Address, HashSet<Address>>,
+    ) -> AgentStateSnapshot {
+        self.header_holders = header_holders;
+        self
+    }
     pub fn from_json_str(header_str: &str) -> serde_json::Result<Self> {
         serde_json::from_str(header_str)
     }
     pub fn top_chain_header(&self) -> Option<&ChainHeader> {
         self.top_chain_header.as_ref()
     }
+    pub fn header_holders(&self) -> &HashMap<Address, HashSet<Address>> {
+        &self.header_holders
+    }
 }
 
 impl From<&StateWrapper> for AgentStateSnapshot {
     fn from(state: &StateWrapper) -> Self {
         let agent = &*(state.agent());
         let top_chain = agent.top_chain_header();
-        AgentStateSnapshot::new(top_chain)
+        AgentStateSnapshot::new(top_chain).with_header_holders(agent.header_holders())
     }
 }
 
@@ -164,6 +323,10 @@ pub enum AgentActionResponse {
     FetchEntry(Option<Entry>),
     GetLinks(Result<Vec<Address>, HolochainError>),
     LinkEntries(Result<Entry, HolochainError>),
+    StageEntry(Address),
+    TakeStagedEntries(Vec<StagedEntry>),
+    RollbackChain(Vec<Address>),
+    CommitBatch(Result<Vec<Address>, HolochainError>),
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, DefaultJson)]
@@ -176,9 +339,9 @@ impl Deref for Response {
     }
 }
 
-impl From<AgentActionResponse> for Response {
-    fn from(r: AgentActionResponse) -> Self {
-        Response(ActionResponse::new(r))
+impl Response {
+    pub fn new(r: AgentActionResponse, created_at: SystemTime) -> Self {
+        Response(ActionResponse::new(r, created_at))
     }
 }
 
@@ -200,10 +363,6 @@ pub fn create_new_chain_header(
         // TODO: pull in the expect below after removing the Holo signing hack again
         //.expect("Must be able to create signatures!"),
     );
-    let duration_since_epoch = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .expect("System time must not be before UNIX EPOCH");
-
     let mut provenances: Vec<Provenance> = provenances.to_vec();
     provenances.push(Provenance::new(agent_address, signature));
 
@@ -221,10 +380,7 @@ pub fn create_new_chain_header(
             .nth(0)
             .map(|chain_header| chain_header.address()),
         crud_link,
-        &Iso8601::new(
-            duration_since_epoch.as_secs() as i64,
-            duration_since_epoch.subsec_nanos(),
-        ),
+        &root_state.clock().iso8601_now(),
     ))
 }
 
@@ -275,38 +431,226 @@ fn reduce_commit_entry(
     let action = action_wrapper.action();
     let (entry, maybe_link_update_delete, provenances) = unwrap_to!(action => Action::Commit);
 
-    let result = create_new_chain_header(
-        &entry,
+    let result = append_to_chain(
         agent_state,
         &StateWrapper::from(root_state.clone()),
-        &maybe_link_update_delete,
+        entry,
+        maybe_link_update_delete,
         provenances,
-    )
-    .and_then(|chain_header| {
-        agent_state.chain_store.add(entry)?;
-        agent_state.chain_store.add(&chain_header)?;
-        Ok((chain_header, entry.address()))
-    })
-    .and_then(|(chain_header, address)| {
-        agent_state.top_chain_header = Some(chain_header);
-        Ok(address)
-    });
+    );
 
     agent_state.actions.insert(
         action_wrapper.clone(),
-        Response::from(AgentActionResponse::Commit(result)),
+        Response::new(AgentActionResponse::Commit(result), root_state.clock.now()),
     );
 }
 
+/// Do a CommitBatch Action against an agent state.
+/// Entries get chained in the given order. If any of them fails, the chain head is left
+/// untouched so the batch is either committed as a whole or not at all.
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
-fn reduce_prune(agent_state: &mut AgentState, _root_state: &State, action_wrapper: &ActionWrapper) {
+fn reduce_commit_batch(
+    agent_state: &mut AgentState,
+    root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let entries = unwrap_to!(action => Action::CommitBatch);
+    let root_state = StateWrapper::from(root_state.clone());
+
+    // All entries and headers of the batch get written in one go at the end
+    let mut new_state = agent_state.clone();
+    new_state.chain_store.begin_write_batch();
+    let result = entries
+        .iter()
+        .map(|(entry, maybe_link_update_delete, provenances)| {
+            append_to_chain(
+                &mut new_state,
+                &root_state,
+                entry,
+                maybe_link_update_delete,
+                provenances,
+            )
+        })
+        .collect::<Result<Vec<Address>, HolochainError>>()
+        .and_then(|addresses| {
+            new_state.chain_store.commit_write_batch()?;
+            Ok(addresses)
+        });
+
+    if result.is_ok() {
+        agent_state.top_chain_header = new_state.top_chain_header;
+        agent_state.chain_length = new_state.chain_length;
+    }
+
+    agent_state.actions.insert(
+        action_wrapper.clone(),
+        Response::new(
+            AgentActionResponse::CommitBatch(result),
+            root_state.clock().now(),
+        ),
+    );
+}
+
+/// Creates the header for the given entry, stores both and moves the chain head.
+#[allow(clippy::ptr_arg)]
+fn append_to_chain(
+    agent_state: &mut AgentState,
+    root_state: &StateWrapper,
+    entry: &Entry,
+    maybe_link_update_delete: &Option<Address>,
+    provenances: &Vec<Provenance>,
+) -> Result<Address, HolochainError> {
+    let chain_header = create_new_chain_header(
+        entry,
+        agent_state,
+        root_state,
+        maybe_link_update_delete,
+        provenances,
+    )?;
+    // Entry and header get written together, or not at all
+    let started_batch = agent_state.chain_store.begin_write_batch();
+    agent_state.chain_store.add(entry)?;
+    agent_state.chain_store.add(&chain_header)?;
+    if started_batch {
+        agent_state.chain_store.commit_write_batch()?;
+    }
+    agent_state.top_chain_header = Some(chain_header);
+    agent_state.chain_length += 1;
+    Ok(entry.address())
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn reduce_stage_entry(
+    agent_state: &mut AgentState,
+    root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let staged_entry = unwrap_to!(action => Action::StageEntry);
+    let address = staged_entry.0.address();
+    agent_state.staged_entries.push(staged_entry.clone());
+    agent_state.actions.insert(
+        action_wrapper.clone(),
+        Response::new(
+            AgentActionResponse::StageEntry(address),
+            root_state.clock.now(),
+        ),
+    );
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn reduce_take_staged_entries(
+    agent_state: &mut AgentState,
+    root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let staged_entries = std::mem::replace(&mut agent_state.staged_entries, Vec::new());
+    agent_state.actions.insert(
+        action_wrapper.clone(),
+        Response::new(
+            AgentActionResponse::TakeStagedEntries(staged_entries),
+            root_state.clock.now(),
+        ),
+    );
+}
+
+/// Moves the top of the chain back to the rollback target.
+/// Headers and entries stay in the CAS but are not reachable from the chain anymore.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn reduce_rollback_chain(
+    agent_state: &mut AgentState,
+    root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (target, dropped_headers, _) = unwrap_to!(action => Action::RollbackChain);
+
+    for header in dropped_headers {
+        agent_state.header_holders.remove(&header.address());
+    }
+    agent_state.top_chain_header = Some(target.clone());
+    agent_state.chain_length = agent_state
+        .chain_length
+        .saturating_sub(dropped_headers.len() as u64);
+
+    agent_state.actions.insert(
+        action_wrapper.clone(),
+        Response::new(
+            AgentActionResponse::RollbackChain(
+                dropped_headers
+                    .iter()
+                    .map(|header| header.address())
+                    .collect(),
+            ),
+            root_state.clock.now(),
+        ),
+    );
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn reduce_add_holding_receipt(
+    agent_state: &mut AgentState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (header_address, holder) = unwrap_to!(action => Action::AddHoldingReceipt);
+
+    agent_state
+        .header_holders
+        .entry(header_address.clone())
+        .or_insert_with(HashSet::new)
+        .insert(holder.clone());
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn reduce_add_revoked_cap_token(
+    agent_state: &mut AgentState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let token = unwrap_to!(action => Action::AddRevokedCapToken);
+    agent_state.revoked_cap_tokens.insert(token.clone());
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn reduce_add_rotated_cap_token(
+    agent_state: &mut AgentState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (old_token, new_token) = unwrap_to!(action => Action::AddRotatedCapToken);
+    agent_state
+        .rotated_cap_tokens
+        .insert(old_token.clone(), new_token.clone());
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn reduce_record_capability_use(
+    agent_state: &mut AgentState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let record = unwrap_to!(action => Action::RecordCapabilityUse);
+    if agent_state.cap_audit_log.len() >= CAP_AUDIT_LOG_CAPACITY {
+        agent_state.cap_audit_log.pop_front();
+    }
+    agent_state.cap_audit_log.push_back(record.clone());
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn reduce_prune(agent_state: &mut AgentState, root_state: &State, action_wrapper: &ActionWrapper) {
     assert_eq!(action_wrapper.action(), &Action::Prune);
 
     agent_state
         .actions
         .iter()
         .filter_map(|(action, response)| {
-            if let Ok(elapsed) = response.created_at.elapsed() {
+            if let Ok(elapsed) = root_state.clock.elapsed(response.created_at) {
                 if elapsed > Duration::from_millis(ACTION_PRUNE_MS) {
                     return Some(action);
                 }
@@ -344,6 +688,14 @@ fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<AgentReduceFn> {
     match action_wrapper.action() {
         Action::ClearActionResponse(_) => Some(reduce_clear_action_response),
         Action::Commit(_) => Some(reduce_commit_entry),
+        Action::CommitBatch(_) => Some(reduce_commit_batch),
+        Action::AddHoldingReceipt(_) => Some(reduce_add_holding_receipt),
+        Action::AddRevokedCapToken(_) => Some(reduce_add_revoked_cap_token),
+        Action::AddRotatedCapToken(_) => Some(reduce_add_rotated_cap_token),
+        Action::RecordCapabilityUse(_) => Some(reduce_record_capability_use),
+        Action::StageEntry(_) => Some(reduce_stage_entry),
+        Action::TakeStagedEntries => Some(reduce_take_staged_entries),
+        Action::RollbackChain(_) => Some(reduce_rollback_chain),
         Action::Prune => Some(reduce_prune),
         _ => None,
     }
@@ -371,14 +723,19 @@ pub fn reduce(
 pub mod tests {
     use super::*;
     use crate::{
-        action::tests::test_action_wrapper_commit, agent::chain_store::tests::test_chain_store,
-        instance::tests::test_context, state::State,
+        action::tests::test_action_wrapper_commit,
+        agent::chain_store::tests::test_chain_store,
+        clock::{MockClock, SharedClock},
+        instance::tests::test_context,
+        state::State,
     };
     use holochain_core_types::{
         chain_header::{test_chain_header, ChainHeader},
-        entry::{expected_entry_address, test_entry, Entry},
+        dna::capabilities::CapabilityCheckOutcome,
+        entry::{expected_entry_address, test_entry, test_entry_a, test_entry_b, Entry},
         error::HolochainError,
         signature::Signature,
+        time::Iso8601,
     };
     use holochain_json_api::json::JsonString;
     use holochain_persistence_api::cas::content::AddressableContent;
@@ -421,11 +778,267 @@ pub mod tests {
         let mut agent_state = test_agent_state(Some(context.agent_id.address()));
         let state = State::new_with_agent(context, agent_state.clone());
         let action_wrapper = test_action_wrapper_commit();
+        assert_eq!(agent_state.chain_length(), 0);
 
         reduce_commit_entry(&mut agent_state, &state, &action_wrapper);
 
         let response = agent_state.actions().get(&action_wrapper).unwrap().clone();
         assert_eq!(response.response(), &test_action_response_commit(),);
+        assert_eq!(agent_state.chain_length(), 1);
+    }
+
+    #[test]
+    /// test that responses get pruned by the age the clock of the state gives them
+    fn test_reduce_prune_by_clock() {
+        let mut context = (*test_context("bob", None)).clone();
+        let clock = MockClock::at_unix_secs(1_500_000_000);
+        context.clock = SharedClock::new(clock.clone());
+        let context = Arc::new(context);
+        let mut agent_state = test_agent_state(Some(context.agent_id.address()));
+        let state = State::new_with_agent(context, agent_state.clone());
+
+        let old_commit = test_action_wrapper_commit();
+        reduce_commit_entry(&mut agent_state, &state, &old_commit);
+        clock.advance(Duration::from_millis(ACTION_PRUNE_MS / 2));
+        let new_commit = test_action_wrapper_commit();
+        reduce_commit_entry(&mut agent_state, &state, &new_commit);
+
+        let prune = ActionWrapper::new(Action::Prune);
+        reduce_prune(&mut agent_state, &state, &prune);
+        assert_eq!(agent_state.actions().len(), 2);
+
+        clock.advance(Duration::from_millis(ACTION_PRUNE_MS / 2 + 1));
+        reduce_prune(&mut agent_state, &state, &prune);
+        assert!(agent_state.actions().get(&old_commit).is_none());
+        assert!(agent_state.actions().get(&new_commit).is_some());
+    }
+
+    #[test]
+    /// test that holding receipts are kept per header and counted per distinct peer of the entry
+    fn test_reduce_add_holding_receipt() {
+        let netname = Some("test_reduce_add_holding_receipt");
+        let context = test_context("bob", netname);
+        let mut agent_state = test_agent_state(Some(context.agent_id.address()));
+        let state = State::new_with_agent(context, agent_state.clone());
+
+        reduce_commit_entry(&mut agent_state, &state, &test_action_wrapper_commit());
+        let first = agent_state.top_chain_header().unwrap();
+        reduce_commit_entry(&mut agent_state, &state, &test_action_wrapper_commit());
+        let second = agent_state.top_chain_header().unwrap();
+        assert_ne!(first.address(), second.address());
+        assert_eq!(first.entry_address(), second.entry_address());
+
+        for (header, holder) in &[(&first, "alice"), (&first, "carol"), (&second, "alice")] {
+            let action_wrapper = ActionWrapper::new(Action::AddHoldingReceipt((
+                header.address(),
+                Address::from(*holder),
+            )));
+            reduce_add_holding_receipt(&mut agent_state, &state, &action_wrapper);
+        }
+
+        assert_eq!(agent_state.header_holders()[&first.address()].len(), 2);
+        assert_eq!(agent_state.header_holders()[&second.address()].len(), 1);
+        assert_eq!(agent_state.entry_holder_count(first.entry_address()), 2);
+        assert_eq!(agent_state.entry_holder_count(&Address::from("unknown")), 0);
+        assert!(agent_state
+            .entry_holders(first.entry_address())
+            .contains(&Address::from("carol")));
+    }
+
+    #[test]
+    fn test_reduce_add_revoked_cap_token() {
+        let netname = Some("test_reduce_add_revoked_cap_token");
+        let context = test_context("bob", netname);
+        let mut agent_state = test_agent_state(Some(context.agent_id.address()));
+        let state = State::new_with_agent(context, agent_state.clone());
+        let token = Address::from("revoked token");
+        assert!(!agent_state.is_cap_token_revoked(&token));
+
+        let action_wrapper = ActionWrapper::new(Action::AddRevokedCapToken(token.clone()));
+        reduce_add_revoked_cap_token(&mut agent_state, &state, &action_wrapper);
+
+        assert!(agent_state.is_cap_token_revoked(&token));
+        assert!(!agent_state.is_cap_token_revoked(&Address::from("other token")));
+    }
+
+    #[test]
+    fn test_reduce_add_rotated_cap_token() {
+        let netname = Some("test_reduce_add_rotated_cap_token");
+        let context = test_context("bob", netname);
+        let mut agent_state = test_agent_state(Some(context.agent_id.address()));
+        let state = State::new_with_agent(context, agent_state.clone());
+        let token = Address::from("token");
+        assert_eq!(agent_state.current_cap_token(&token), token);
+
+        for (old_token, new_token) in vec![("token", "rotated"), ("rotated", "rotated again")] {
+            let action_wrapper = ActionWrapper::new(Action::AddRotatedCapToken((
+                Address::from(old_token),
+                Address::from(new_token),
+            )));
+            reduce_add_rotated_cap_token(&mut agent_state, &state, &action_wrapper);
+        }
+
+        assert_eq!(
+            agent_state.current_cap_token(&token),
+            Address::from("rotated again")
+        );
+        assert_eq!(
+            agent_state.current_cap_token(&Address::from("other token")),
+            Address::from("other token")
+        );
+    }
+
+    #[test]
+    fn test_reduce_record_capability_use() {
+        let netname = Some("test_reduce_record_capability_use");
+        let context = test_context("bob", netname);
+        let mut agent_state = test_agent_state(Some(context.agent_id.address()));
+        let state = State::new_with_agent(context, agent_state.clone());
+
+        let record = |token: &str, outcome| CapabilityUseRecord {
+            token: Address::from(token),
+            caller: Address::from("alice"),
+            zome: "test_zome".to_string(),
+            function: "test".to_string(),
+            timestamp: Iso8601::from(0),
+            outcome,
+        };
+        for outcome in vec![
+            CapabilityCheckOutcome::Granted,
+            CapabilityCheckOutcome::Denied,
+        ] {
+            let action_wrapper =
+                ActionWrapper::new(Action::RecordCapabilityUse(record("token", outcome)));
+            reduce_record_capability_use(&mut agent_state, &state, &action_wrapper);
+        }
+        let action_wrapper = ActionWrapper::new(Action::RecordCapabilityUse(record(
+            "other token",
+            CapabilityCheckOutcome::Granted,
+        )));
+        reduce_record_capability_use(&mut agent_state, &state, &action_wrapper);
+
+        assert_eq!(agent_state.cap_audit_log(None).len(), 3);
+        assert_eq!(
+            agent_state.cap_audit_log(Some(&Address::from("token"))),
+            vec![
+                record("token", CapabilityCheckOutcome::Granted),
+                record("token", CapabilityCheckOutcome::Denied)
+            ]
+        );
+
+        // the log is bounded, dropping the oldest records
+        for _ in 0..CAP_AUDIT_LOG_CAPACITY {
+            reduce_record_capability_use(&mut agent_state, &state, &action_wrapper);
+        }
+        assert_eq!(
+            agent_state.cap_audit_log(None).len(),
+            CAP_AUDIT_LOG_CAPACITY
+        );
+        assert!(agent_state
+            .cap_audit_log(Some(&Address::from("token")))
+            .is_empty());
+    }
+
+    #[test]
+    /// test that staged entries stay out of the chain and get handed out exactly once
+    fn test_reduce_stage_and_take_entries() {
+        let netname = Some("test_reduce_stage_and_take_entries");
+        let context = test_context("bob", netname);
+        let mut agent_state = test_agent_state(Some(context.agent_id.address()));
+        let state = State::new_with_agent(context, agent_state.clone());
+
+        let stage_action = ActionWrapper::new(Action::StageEntry((test_entry(), None, vec![])));
+        reduce_stage_entry(&mut agent_state, &state, &stage_action);
+        assert_eq!(
+            agent_state.actions().get(&stage_action).unwrap().response(),
+            &AgentActionResponse::StageEntry(test_entry().address()),
+        );
+        assert_eq!(agent_state.staged_entries().len(), 1);
+        assert_eq!(agent_state.chain_length(), 0);
+        assert_eq!(agent_state.top_chain_header(), None);
+
+        let take_action = ActionWrapper::new(Action::TakeStagedEntries);
+        reduce_take_staged_entries(&mut agent_state, &state, &take_action);
+        assert_eq!(
+            agent_state.actions().get(&take_action).unwrap().response(),
+            &AgentActionResponse::TakeStagedEntries(vec![(test_entry(), None, vec![])]),
+        );
+        assert!(agent_state.staged_entries().is_empty());
+    }
+
+    #[test]
+    /// test that a rollback moves the chain head back and forgets receipts of dropped entries
+    fn test_reduce_rollback_chain() {
+        let netname = Some("test_reduce_rollback_chain");
+        let context = test_context("bob", netname);
+        let mut agent_state = test_agent_state(Some(context.agent_id.address()));
+        let state = State::new_with_agent(context, agent_state.clone());
+
+        reduce_commit_entry(&mut agent_state, &state, &test_action_wrapper_commit());
+        let target = agent_state.top_chain_header().unwrap();
+        reduce_commit_entry(&mut agent_state, &state, &test_action_wrapper_commit());
+        let dropped = agent_state.top_chain_header().unwrap();
+        assert_eq!(agent_state.chain_length(), 2);
+
+        let receipt_action = ActionWrapper::new(Action::AddHoldingReceipt((
+            dropped.address(),
+            Address::from("alice"),
+        )));
+        reduce_add_holding_receipt(&mut agent_state, &state, &receipt_action);
+
+        let rollback_action = ActionWrapper::new(Action::RollbackChain((
+            target.clone(),
+            vec![dropped.clone()],
+            vec![dropped.address()],
+        )));
+        reduce_rollback_chain(&mut agent_state, &state, &rollback_action);
+
+        assert_eq!(agent_state.top_chain_header(), Some(target));
+        assert_eq!(agent_state.chain_length(), 1);
+        assert!(agent_state
+            .header_holders()
+            .get(&dropped.address())
+            .is_none());
+        assert_eq!(
+            agent_state
+                .actions()
+                .get(&rollback_action)
+                .unwrap()
+                .response(),
+            &AgentActionResponse::RollbackChain(vec![dropped.address()]),
+        );
+    }
+
+    #[test]
+    /// test that a batch gets chained in order within a single reduction
+    fn test_reduce_commit_batch() {
+        let netname = Some("test_reduce_commit_batch");
+        let context = test_context("bob", netname);
+        let mut agent_state = test_agent_state(Some(context.agent_id.address()));
+        let state = State::new_with_agent(context, agent_state.clone());
+        let first = test_entry_a();
+        let second = test_entry_b();
+
+        let action_wrapper = ActionWrapper::new(Action::CommitBatch(vec![
+            (first.clone(), None, vec![]),
+            (second.clone(), None, vec![]),
+        ]));
+        reduce_commit_batch(&mut agent_state, &state, &action_wrapper);
+
+        assert_eq!(
+            agent_state
+                .actions()
+                .get(&action_wrapper)
+                .unwrap()
+                .response(),
+            &AgentActionResponse::CommitBatch(Ok(vec![first.address(), second.address()])),
+        );
+        assert_eq!(agent_state.chain_length(), 2);
+        let chained: Vec<Address> = agent_state
+            .iter_chain()
+            .map(|header| header.entry_address().clone())
+            .collect();
+        assert_eq!(chained, vec![second.address(), first.address()]);
     }
 
     #[test]
@@ -484,10 +1097,24 @@ pub mod tests {
     #[test]
     pub fn serialize_round_trip_agent_state() {
         let header = test_chain_header();
-        let agent_snap = AgentStateSnapshot::new(Some(header));
+        let mut header_holders = HashMap::new();
+        header_holders.insert(header.address(), HashSet::unit(Address::from("alice")));
+        let agent_snap = AgentStateSnapshot::new(Some(header)).with_header_holders(header_holders);
         let json = serde_json::to_string(&agent_snap).unwrap();
         let agent_from_json = AgentStateSnapshot::from_json_str(&json).unwrap();
         assert_eq!(agent_snap.address(), agent_from_json.address());
+        assert_eq!(
+            agent_snap.header_holders(),
+            agent_from_json.header_holders()
+        );
+
+        // snapshots persisted before holding receipts were kept load without them
+        let legacy_json = format!(
+            "{{\"top_chain_header\":{}}}",
+            serde_json::to_string(&test_chain_header()).unwrap()
+        );
+        let legacy = AgentStateSnapshot::from_json_str(&legacy_json).unwrap();
+        assert!(legacy.header_holders().is_empty());
     }
 
     #[test]
@@ -539,4 +1166,23 @@ pub mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_chain_header_timestamp_comes_from_clock() {
+        let mut context = (*test_context("bob", None)).clone();
+        let clock = MockClock::at_unix_secs(1_500_000_000);
+        context.clock = SharedClock::new(clock.clone());
+        let context = Arc::new(context);
+        let agent_state = test_agent_state(Some(context.agent_id.address()));
+        let state = StateWrapper::from(State::new_with_agent(context, agent_state.clone()));
+
+        let header =
+            create_new_chain_header(&test_entry(), &agent_state, &state, &None, &vec![]).unwrap();
+        assert_eq!(header.timestamp(), &Iso8601::from(1_500_000_000));
+
+        clock.advance(Duration::from_secs(60));
+        let header =
+            create_new_chain_header(&test_entry(), &agent_state, &state, &None, &vec![]).unwrap();
+        assert_eq!(header.timestamp(), &Iso8601::from(1_500_000_060));
+    }
 }
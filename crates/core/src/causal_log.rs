@@ -0,0 +1,301 @@
+//! Which action, workflow or network message caused which action.
+//!
+//! Every [ActionWrapper](crate::action::ActionWrapper) gets the ID of its cause when it is
+//! created, which is whatever was [pushed](push_cause) last on the creating thread, like
+//! spans are with holochain_tracing. Zome calls, network messages and holding workflows
+//! push themselves as causes, and so does the action loop while it reduces an action.
+//! The instance records every processed action and every cause in a bounded [CausalLog],
+//! so that the chain of events that led to an action can be followed back.
+use crate::action::{Action, ActionWrapper};
+use holochain_core_types::time::Iso8601;
+use holochain_locksmith::Mutex;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::{self, Debug, Write},
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// How many actions and causes an instance remembers
+pub const CAUSAL_LOG_SIZE: usize = 1000;
+
+thread_local! {
+    static CAUSES: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
+/// Removes its cause from the thread's causes when dropped
+pub struct CauseGuard(String);
+
+impl Drop for CauseGuard {
+    fn drop(&mut self) {
+        CAUSES.with(|causes| {
+            let mut causes = causes.borrow_mut();
+            if let Some(position) = causes.iter().rposition(|cause| *cause == self.0) {
+                causes.remove(position);
+            }
+        });
+    }
+}
+
+/// Makes the given ID the cause of the actions created on this thread until the returned
+/// guard gets dropped
+pub fn push_cause(id: String) -> CauseGuard {
+    CAUSES.with(|causes| causes.borrow_mut().push(id.clone()));
+    CauseGuard(id)
+}
+
+pub fn current_cause() -> Option<String> {
+    CAUSES.with(|causes| causes.borrow().last().cloned())
+}
+
+/// Future that makes its cause the current one whenever it gets polled, since tasks move
+/// between threads and other tasks run on the same thread while it waits
+pub struct Caused<F> {
+    cause: Option<String>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Caused<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let _guard = self.cause.clone().map(push_cause);
+        self.future.as_mut().poll(cx)
+    }
+}
+
+/// Runs the future with the given cause, if any
+pub fn with_cause<F: Future>(cause: Option<String>, future: F) -> Caused<F> {
+    Caused {
+        cause,
+        future: Box::pin(future),
+    }
+}
+
+/// Name of the variant of an enum, without formatting its fields
+pub fn variant_name<T: Debug>(value: &T) -> String {
+    struct UntilFields(String);
+    impl Write for UntilFields {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            match s.find(|c: char| !(c.is_alphanumeric() || c == '_')) {
+                Some(end) => {
+                    self.0.push_str(&s[..end]);
+                    // Stops formatting the rest
+                    Err(fmt::Error)
+                }
+                None => {
+                    self.0.push_str(s);
+                    Ok(())
+                }
+            }
+        }
+    }
+    let mut name = UntilFields(String::new());
+    let _ = write!(name, "{:?}", value);
+    name.0
+}
+
+/// What an entry of the log is about, for the actions whose subject is worth searching for
+pub fn action_subject(action: &Action) -> Option<String> {
+    match action {
+        Action::QueueHoldingWorkflow((pending, _)) => Some(pending.uuid.to_string()),
+        Action::RemoveQueuedHoldingWorkflow((_, pending)) => Some(pending.uuid.to_string()),
+        Action::Publish(address) | Action::PublishHeaderEntry(address) => Some(address.to_string()),
+        Action::HoldAspect((aspect, _)) => aspect
+            .entry_address()
+            .ok()
+            .map(|address| address.to_string()),
+        Action::QueueZomeFunctionCall(call) => Some(call.id().to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CausalLogEntry {
+    pub id: String,
+    /// ID of what caused this, if known
+    pub cause: Option<String>,
+    /// Type of an action, or a description of a zome call, network message or workflow
+    pub name: String,
+    /// What the entry is about, like the address of a published entry
+    pub subject: Option<String>,
+    pub time: Iso8601,
+}
+
+pub struct CausalLog {
+    capacity: usize,
+    entries: Mutex<VecDeque<CausalLogEntry>>,
+}
+
+impl Default for CausalLog {
+    fn default() -> Self {
+        Self::new(CAUSAL_LOG_SIZE)
+    }
+}
+
+impl CausalLog {
+    pub fn new(capacity: usize) -> Self {
+        CausalLog {
+            capacity,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// `time` comes from the clock of the instance's context
+    pub fn record(
+        &self,
+        time: Iso8601,
+        id: String,
+        cause: Option<String>,
+        name: String,
+        subject: Option<String>,
+    ) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(CausalLogEntry {
+            id,
+            cause,
+            name,
+            subject,
+            time,
+        });
+    }
+
+    /// Records a workflow or message that causes actions, and returns its new ID
+    pub fn record_cause(
+        &self,
+        time: Iso8601,
+        cause: Option<String>,
+        name: String,
+        subject: Option<String>,
+    ) -> String {
+        let id = nanoid::simple();
+        self.record(time, id.clone(), cause, name, subject);
+        id
+    }
+
+    pub fn record_action(&self, time: Iso8601, action_wrapper: &ActionWrapper) {
+        self.record(
+            time,
+            action_wrapper.id().clone(),
+            action_wrapper.cause().cloned(),
+            variant_name(action_wrapper.action()),
+            action_subject(action_wrapper.action()),
+        );
+    }
+
+    pub fn entries(&self) -> Vec<CausalLogEntry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// The latest entry with the given name and subject
+    pub fn find(&self, name: &str, subject: &str) -> Option<CausalLogEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|entry| {
+                entry.name == name && entry.subject.as_ref().map(String::as_str) == Some(subject)
+            })
+            .cloned()
+    }
+
+    /// The entry with the given ID followed by its cause, the cause's cause and so on, as far
+    /// as they are still in the log
+    pub fn chain(&self, id: &str) -> Vec<CausalLogEntry> {
+        let entries = self.entries.lock().unwrap();
+        let mut chain: Vec<CausalLogEntry> = Vec::new();
+        let mut next = Some(id.to_string());
+        while let Some(id) = next.take() {
+            let found = entries.iter().rev().find(|entry| entry.id == id);
+            if let Some(entry) = found {
+                if chain.iter().any(|seen| seen.id == entry.id) {
+                    break;
+                }
+                next = entry.cause.clone();
+                chain.push(entry.clone());
+            }
+        }
+        chain
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_persistence_api::cas::content::Address;
+
+    #[test]
+    fn actions_get_the_cause_of_their_thread() {
+        assert_eq!(current_cause(), None);
+        {
+            let _message = push_cause(String::from("message"));
+            let _workflow = push_cause(String::from("workflow"));
+            let action = ActionWrapper::new(Action::Publish(Address::from("QmEntry")));
+            assert_eq!(action.cause(), Some(&String::from("workflow")));
+        }
+        assert_eq!(current_cause(), None);
+        assert_eq!(ActionWrapper::new(Action::Ping).cause(), None);
+    }
+
+    #[test]
+    fn names_variants_without_their_fields() {
+        assert_eq!(
+            variant_name(&Action::Publish(Address::from("QmEntry"))),
+            "Publish"
+        );
+        assert_eq!(variant_name(&Action::Ping), "Ping");
+    }
+
+    #[test]
+    fn follows_causes_back() {
+        let log = CausalLog::new(3);
+        let time = Iso8601::new(0, 0);
+        log.record(
+            time.clone(),
+            String::from("old"),
+            None,
+            String::from("Ping"),
+            None,
+        );
+        log.record(
+            time.clone(),
+            String::from("message"),
+            None,
+            String::from("network message HandleStoreEntryAspect"),
+            None,
+        );
+        log.record(
+            time.clone(),
+            String::from("queue"),
+            Some(String::from("message")),
+            String::from("QueueHoldingWorkflow"),
+            Some(String::from("pending-1")),
+        );
+        log.record(
+            time,
+            String::from("hold"),
+            Some(String::from("queue")),
+            String::from("HoldAspect"),
+            Some(String::from("QmEntry")),
+        );
+
+        assert_eq!(log.entries().len(), 3);
+        let chain: Vec<String> = log
+            .chain("hold")
+            .into_iter()
+            .map(|entry| entry.id)
+            .collect();
+        assert_eq!(chain, vec!["hold", "queue", "message"]);
+        assert_eq!(
+            log.find("QueueHoldingWorkflow", "pending-1").unwrap().id,
+            "queue"
+        );
+        assert!(log.chain("old").is_empty());
+    }
+}
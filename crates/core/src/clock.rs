@@ -0,0 +1,135 @@
+//! The clock that an instance reads the time from.
+//!
+//! Header timestamps, timeouts of network requests, delays of re-queued validations and
+//! expiry of capability grants all go through the [SharedClock] of the instance's context
+//! instead of reading the system time directly. Instances use the [SystemClock], while tests
+//! can put in a [MockClock] and move it forward by hand, so that timeouts and retries fire
+//! when the test says so instead of after sleeping long enough.
+use holochain_core_types::time::Iso8601;
+use holochain_locksmith::Mutex;
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, SystemTimeError},
+};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// Reads the system time
+#[derive(Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when it gets told to. Clones share the same time.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    pub fn new(now: SystemTime) -> Self {
+        MockClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// A clock that starts at the given number of seconds after the UNIX epoch
+    pub fn at_unix_secs(secs: u64) -> Self {
+        Self::new(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// The clock of an instance, shared by its context and state
+#[derive(Clone)]
+pub struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    pub fn new<C: Clock + 'static>(clock: C) -> Self {
+        SharedClock(Arc::new(clock))
+    }
+
+    pub fn system() -> Self {
+        Self::new(SystemClock)
+    }
+
+    pub fn now(&self) -> SystemTime {
+        self.0.now()
+    }
+
+    /// Time since `earlier`, or an error if that is later than now, like SystemTime::elapsed
+    pub fn elapsed(&self, earlier: SystemTime) -> Result<Duration, SystemTimeError> {
+        self.now().duration_since(earlier)
+    }
+
+    pub fn since_epoch(&self) -> Duration {
+        self.now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("System time must not be before UNIX EPOCH")
+    }
+
+    pub fn iso8601_now(&self) -> Iso8601 {
+        let since_epoch = self.since_epoch();
+        Iso8601::new(since_epoch.as_secs() as i64, since_epoch.subsec_nanos())
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SharedClock").finish()
+    }
+}
+
+/// States don't differ by the clock they read the time from
+impl PartialEq for SharedClock {
+    fn eq(&self, _other: &SharedClock) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock_only_moves_when_told() {
+        let mock = MockClock::at_unix_secs(1_000);
+        let clock = SharedClock::new(mock.clone());
+        let start = clock.now();
+        assert_eq!(clock.since_epoch(), Duration::from_secs(1_000));
+        assert_eq!(clock.iso8601_now(), Iso8601::new(1_000, 0));
+        assert_eq!(clock.elapsed(start).unwrap(), Duration::from_secs(0));
+
+        mock.advance(Duration::from_secs(30));
+        assert_eq!(clock.elapsed(start).unwrap(), Duration::from_secs(30));
+
+        mock.set(start - Duration::from_secs(1));
+        assert!(clock.elapsed(start).is_err());
+    }
+}
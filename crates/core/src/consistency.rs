@@ -1,6 +1,9 @@
-use crate::{action::Action, context::Context, entry::CanPublish, nucleus::ZomeFnCall};
+use crate::{
+    action::Action, context::Context, entry::CanPublish, nucleus::ZomeFnCall, signal::Signal,
+};
 use holochain_core_types::{
-    entry::Entry, link::link_data::LinkData, network::entry_aspect::EntryAspect,
+    entry::Entry, error::HolochainError, link::link_data::LinkData,
+    network::entry_aspect::EntryAspect,
 };
 use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use serde::Serialize;
@@ -30,6 +33,15 @@ impl<E: Serialize> ConsistencySignal<E> {
             .collect();
         Self { event, pending }
     }
+
+    pub fn event(&self) -> &E {
+        &self.event
+    }
+
+    /// The events this signal expects to follow, and which group of nodes should emit them
+    pub fn pending(&self) -> impl Iterator<Item = (&E, &ConsistencyGroup)> {
+        self.pending.iter().map(|p| (&p.event, &p.group))
+    }
 }
 
 impl From<ConsistencySignalE> for ConsistencySignal<String> {
@@ -60,6 +72,7 @@ pub enum ConsistencyEvent {
     InitializeNetwork, // -> Hold (the AgentId if initialize chain happend)
     InitializeChain,   // -> prepare to hold AgentId
     SignalZomeFunctionCall(String, snowflake::ProcessUniqueId), // -> ReturnZomeFunctionResult
+    ValidationPending(Address), // -> ValidationResolved or ValidationFailed
 
     // EFFECTS
     Hold(Address),                                                // <- Publish
@@ -68,6 +81,8 @@ pub enum ConsistencyEvent {
     AddLink(LinkData),                                            // <- Publish, entry_type=LinkAdd
     RemoveLink(Address), // <- Publish, entry_type=LinkRemove
     ReturnZomeFunctionResult(String, snowflake::ProcessUniqueId), // <- SignalZomeFunctionCall
+    ValidationResolved(Address), // <- ValidationPending
+    ValidationFailed(Address, String), // <- ValidationPending
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -105,47 +120,58 @@ impl ConsistencyModel {
         }
     }
 
+    /// Remembers what to expect once a committed entry gets published
+    #[allow(clippy::ptr_arg)]
+    fn cache_commit(&mut self, entry: &Entry, crud_link: &Option<Address>) {
+        use ConsistencyEvent::*;
+        use ConsistencyGroup::*;
+        // XXX: Since can_publish relies on a properly initialized Context, there are a few ways
+        // can_publish can fail. If we hit the possiblity of failure, just add the commit to the cache
+        // anyway. The only reason to check is to avoid filling up the cache unnecessarily with
+        // commits that will never be published.
+        let do_cache = self.context.state().is_none()
+            || self.context.get_dna().is_none()
+            || entry.entry_type().can_publish(&self.context);
+
+        // If entry is publishable, construct the ConsistencySignal that should be emitted
+        // when the entry is finally published, and save it for later
+        if do_cache {
+            let address = entry.address();
+            let hold = Hold(address.clone());
+            let meta = match entry {
+                Entry::App(_, _) => crud_link
+                    .clone()
+                    .map(|crud| UpdateEntry(crud, address.clone())),
+                Entry::Deletion(_) => crud_link
+                    .clone()
+                    .map(|crud| RemoveEntry(crud, address.clone())),
+                Entry::LinkAdd(link_data) => Some(AddLink(link_data.clone())),
+                Entry::LinkRemove(_) => Some(RemoveLink(address.clone())),
+                // Question: Why does Entry::LinkAdd take LinkData instead of Link?
+                // as of now, link data contains more information than just the link
+                _ => None,
+            };
+            let mut pending = vec![hold];
+            if let Some(m) = meta {
+                pending.push(m)
+            }
+            let signal =
+                ConsistencySignal::new_pending(Publish(address.clone()), Validators, pending);
+            self.commit_cache.insert(address, signal);
+        }
+    }
+
     pub fn process_action(&mut self, action: &Action) -> Option<ConsistencySignalE> {
         use ConsistencyEvent::*;
         use ConsistencyGroup::*;
         match action {
             Action::Commit((entry, crud_link, _)) => {
-                // XXX: Since can_publish relies on a properly initialized Context, there are a few ways
-                // can_publish can fail. If we hit the possiblity of failure, just add the commit to the cache
-                // anyway. The only reason to check is to avoid filling up the cache unnecessarily with
-                // commits that will never be published.
-                let do_cache = self.context.state().is_none()
-                    || self.context.get_dna().is_none()
-                    || entry.entry_type().can_publish(&self.context);
-
-                // If entry is publishable, construct the ConsistencySignal that should be emitted
-                // when the entry is finally published, and save it for later
-                if do_cache {
-                    let address = entry.address();
-                    let hold = Hold(address.clone());
-                    let meta = match entry {
-                        Entry::App(_, _) => crud_link
-                            .clone()
-                            .map(|crud| UpdateEntry(crud, address.clone())),
-                        Entry::Deletion(_) => crud_link
-                            .clone()
-                            .map(|crud| RemoveEntry(crud, address.clone())),
-                        Entry::LinkAdd(link_data) => Some(AddLink(link_data.clone())),
-                        Entry::LinkRemove(_) => Some(RemoveLink(address.clone())),
-                        // Question: Why does Entry::LinkAdd take LinkData instead of Link?
-                        // as of now, link data contains more information than just the link
-                        _ => None,
-                    };
-                    let mut pending = vec![hold];
-                    if let Some(m) = meta {
-                        pending.push(m)
-                    }
-                    let signal = ConsistencySignal::new_pending(
-                        Publish(address.clone()),
-                        Validators,
-                        pending,
-                    );
-                    self.commit_cache.insert(address, signal);
+                self.cache_commit(entry, crud_link);
+                None
+            }
+            Action::CommitBatch(entries) => {
+                for (entry, crud_link, _) in entries {
+                    self.cache_commit(entry, crud_link);
                 }
                 None
             }
@@ -230,6 +256,77 @@ impl ConsistencyModel {
     }
 }
 
+/// The signal about the outcome of a holding workflow for the entry at the given address.
+/// Validations that have to wait for their dependencies are pending until they get resolved
+/// or fail in a later run.
+pub fn holding_workflow_signal(
+    address: Address,
+    result: &Result<(), HolochainError>,
+) -> ConsistencySignalE {
+    use ConsistencyEvent::*;
+    match result {
+        Ok(()) => ConsistencySignal::new_terminal(ValidationResolved(address)),
+        Err(HolochainError::ValidationPending) => ConsistencySignal::new_pending(
+            ValidationPending(address.clone()),
+            ConsistencyGroup::Validators,
+            vec![ValidationResolved(address)],
+        ),
+        Err(error) => ConsistencySignal::new_terminal(ValidationFailed(address, error.to_string())),
+    }
+}
+
+/// Sends a consistency signal that doesn't follow from an action
+pub fn emit_consistency_signal(context: &Context, signal: ConsistencySignalE) {
+    if let Some(tx) = context.signal_tx() {
+        tx.send(Signal::Consistency(signal.into()))
+            .unwrap_or_else(|e| {
+                log_warn!(
+                    context,
+                    "consistency: Signal channel is closed! No signals can be sent ({:?}).",
+                    e
+                );
+            });
+    }
+}
+
 fn display_zome_fn_call(call: &ZomeFnCall) -> String {
     format!("{}/{}", call.zome_name, call.fn_name)
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn events(signal: ConsistencySignalE) -> ConsistencySignal<String> {
+        signal.into()
+    }
+
+    #[test]
+    fn signals_outcomes_of_holding_workflows() {
+        let address = Address::from("QmEntry");
+
+        let pending = events(holding_workflow_signal(
+            address.clone(),
+            &Err(HolochainError::ValidationPending),
+        ));
+        assert_eq!(pending.event, r#"{"ValidationPending":"QmEntry"}"#);
+        assert_eq!(pending.pending.len(), 1);
+        assert_eq!(
+            pending.pending[0].event,
+            r#"{"ValidationResolved":"QmEntry"}"#
+        );
+
+        let resolved = events(holding_workflow_signal(address.clone(), &Ok(())));
+        assert_eq!(resolved.event, r#"{"ValidationResolved":"QmEntry"}"#);
+        assert!(resolved.pending.is_empty());
+
+        let failed = events(holding_workflow_signal(
+            address,
+            &Err(HolochainError::ValidationFailed("too long".into())),
+        ));
+        assert_eq!(
+            failed.event,
+            r#"{"ValidationFailed":["QmEntry","too long"]}"#
+        );
+    }
+}
@@ -1,5 +1,25 @@
-use holochain_core_types::{entry::Entry, error::HcResult};
-use holochain_persistence_api::cas::content::{Address, AddressableContent, Content};
+use holochain_core_types::{
+    eav::{Attribute, EntityAttributeValueIndex},
+    entry::{entry_type::EntryType, Entry},
+    error::{HcResult, HolochainError},
+};
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_locksmith::RwLock;
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::EntityAttributeValueStorage,
+};
+use std::{convert::TryFrom, sync::Arc};
+
+/// Storage for DNA entries that is shared between all instances of a conductor
+/// (see Context::dna_storage), so many instances of the same DNA only keep one copy of it.
+pub type SharedDnaStorage = Arc<RwLock<dyn ContentAddressableStorage>>;
 
 pub trait GetContent {
     /// Return the content at this address, do not attempt to convert to an entry
@@ -23,3 +43,348 @@ pub trait GetContent {
 pub trait AddContent {
     fn add<T: AddressableContent>(&mut self, content: &T) -> HcResult<()>;
 }
+
+/// Whether the content is a DNA entry. Content that isn't an entry at all, like chain headers,
+/// stays in the instance's own storage.
+fn is_dna_content(content: &Content) -> bool {
+    match Entry::try_from_content(content) {
+        Ok(entry) => entry.entry_type() == EntryType::Dna,
+        Err(_) => false,
+    }
+}
+
+/// Fetches content from the given storage, falling back to the shared DNA storage
+/// for DNA entries that only got stored there.
+pub(crate) fn fetch_with_shared_dna(
+    storage: &Arc<RwLock<dyn ContentAddressableStorage>>,
+    dna_storage: &Option<SharedDnaStorage>,
+    address: &Address,
+) -> HcResult<Option<Content>> {
+    let content = (*storage.read().unwrap()).fetch(address)?;
+    match (content, dna_storage) {
+        (None, Some(dna_storage)) => Ok((*dna_storage.read().unwrap()).fetch(address)?),
+        (content, _) => Ok(content),
+    }
+}
+
+/// Adds content to the given storage, or to the shared DNA storage if it is a DNA entry
+/// and such a storage is set.
+pub(crate) fn add_with_shared_dna<T: AddressableContent>(
+    storage: &Arc<RwLock<dyn ContentAddressableStorage>>,
+    dna_storage: &Option<SharedDnaStorage>,
+    content: &T,
+) -> HcResult<()> {
+    let target = match dna_storage {
+        Some(dna_storage) if is_dna_content(&content.content()) => dna_storage,
+        _ => storage,
+    };
+    (*target.write().unwrap())
+        .add(content)
+        .map_err(|e| e.into())
+}
+
+/// Content that got added to a [StorageWriteBatch], kept together with its address.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct BatchedContent {
+    address: Address,
+    content: Content,
+}
+
+impl AddressableContent for BatchedContent {
+    fn address(&self) -> Address {
+        self.address.clone()
+    }
+
+    fn content(&self) -> Content {
+        self.content.clone()
+    }
+
+    fn try_from_content(_content: &Content) -> JsonResult<Self> {
+        Err(JsonError::ErrorGeneric(
+            "Batched content can't be restored without its address".to_string(),
+        ))
+    }
+}
+
+/// The writes of a single action to a CAS and an EAV storage, collected so that they can be
+/// committed together.
+/// Each storage gets locked once per commit instead of once per write, and if the action
+/// fails before committing its batch, none of its writes happen.
+///
+/// The persistence API has no transactions that span several writes, let alone a CAS and an
+/// EAV storage, so a commit journals the whole batch with a single write first, like the
+/// [write-ahead log](crate::write_ahead_log) does for actions. A commit that fails or gets
+/// interrupted after that is completed from the journal by [recover_write_batch] when the
+/// instance gets set up again, so either all writes of a batch end up in the storages or none.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StorageWriteBatch {
+    contents: Vec<BatchedContent>,
+    eavis: Vec<EntityAttributeValueIndex>,
+}
+
+impl StorageWriteBatch {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add<T: AddressableContent>(&mut self, content: &T) {
+        self.contents.push(BatchedContent {
+            address: content.address(),
+            content: content.content(),
+        });
+    }
+
+    pub fn add_eavi(&mut self, eavi: &EntityAttributeValueIndex) {
+        self.eavis.push(eavi.clone());
+    }
+
+    /// Returns the content most recently added to the batch at the given address, if any.
+    pub fn get_raw(&self, address: &Address) -> Option<Content> {
+        self.contents
+            .iter()
+            .rev()
+            .find(|batched| &batched.address == address)
+            .map(|batched| batched.content.clone())
+    }
+
+    pub fn eavis(&self) -> &[EntityAttributeValueIndex] {
+        &self.eavis
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty() && self.eavis.is_empty()
+    }
+
+    /// Journals the batch in the content storage, writes all collected content and then all
+    /// collected EAVIs, in the order they got added, and clears the journal again.
+    /// DNA entries go to the shared DNA storage if one is set, like in [add_with_shared_dna].
+    pub(crate) fn commit(
+        self,
+        content_storage: &Arc<RwLock<dyn ContentAddressableStorage>>,
+        meta_storage: Option<&Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>>,
+        dna_storage: &Option<SharedDnaStorage>,
+    ) -> HcResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        if !self.eavis.is_empty() && meta_storage.is_none() {
+            return Err(HolochainError::ErrorGeneric(
+                "Can't commit EAVIs of a write batch without an EAV storage".to_string(),
+            ));
+        }
+        let journal = WriteBatchJournal { batch: Some(self) };
+        (*content_storage.write().unwrap()).add(&journal)?;
+        if let Some(batch) = journal.batch.as_ref() {
+            batch.apply(content_storage, meta_storage, dna_storage)?;
+        }
+        clear_write_batch_journal(content_storage)
+    }
+
+    fn apply(
+        &self,
+        content_storage: &Arc<RwLock<dyn ContentAddressableStorage>>,
+        meta_storage: Option<&Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>>,
+        dna_storage: &Option<SharedDnaStorage>,
+    ) -> HcResult<()> {
+        let (dna_contents, contents): (Vec<_>, Vec<_>) = self
+            .contents
+            .iter()
+            .partition(|batched| dna_storage.is_some() && is_dna_content(&batched.content));
+        if !contents.is_empty() {
+            let mut storage = content_storage.write().unwrap();
+            for batched in contents {
+                storage.add(batched)?;
+            }
+        }
+        if let (Some(dna_storage), false) = (dna_storage, dna_contents.is_empty()) {
+            let mut storage = dna_storage.write().unwrap();
+            for batched in dna_contents {
+                storage.add(batched)?;
+            }
+        }
+        if !self.eavis.is_empty() {
+            let meta_storage = meta_storage.ok_or_else(|| {
+                HolochainError::ErrorGeneric(
+                    "Can't commit EAVIs of a write batch without an EAV storage".to_string(),
+                )
+            })?;
+            let mut storage = meta_storage.write().unwrap();
+            for eavi in self.eavis.iter() {
+                storage.add_eavi(eavi)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The address at which a [StorageWriteBatch] gets journaled in the content storage it is
+/// committed to.
+pub static WRITE_BATCH_JOURNAL_ADDRESS: &str = "WriteBatchJournal";
+
+/// The batch that is being committed, or nothing between commits
+#[derive(Clone, Debug, Serialize, Deserialize, DefaultJson)]
+struct WriteBatchJournal {
+    batch: Option<StorageWriteBatch>,
+}
+
+impl AddressableContent for WriteBatchJournal {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+
+    fn address(&self) -> Address {
+        WRITE_BATCH_JOURNAL_ADDRESS.into()
+    }
+}
+
+fn clear_write_batch_journal(
+    content_storage: &Arc<RwLock<dyn ContentAddressableStorage>>,
+) -> HcResult<()> {
+    (*content_storage.write().unwrap()).add(&WriteBatchJournal { batch: None })?;
+    Ok(())
+}
+
+/// Completes the write batch whose commit to the given content storage got interrupted, if
+/// there is one. Returns whether there was.
+pub(crate) fn recover_write_batch(
+    content_storage: &Arc<RwLock<dyn ContentAddressableStorage>>,
+    meta_storage: Option<&Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>>,
+    dna_storage: &Option<SharedDnaStorage>,
+) -> HcResult<bool> {
+    let content =
+        (*content_storage.read().unwrap()).fetch(&Address::from(WRITE_BATCH_JOURNAL_ADDRESS))?;
+    let batch = match content {
+        Some(content) => WriteBatchJournal::try_from_content(&content)?.batch,
+        None => None,
+    };
+    match batch {
+        Some(batch) => {
+            if !batch.eavis.is_empty() && meta_storage.is_none() {
+                return Err(HolochainError::ErrorGeneric(
+                    "Can't recover EAVIs of a write batch without an EAV storage".to_string(),
+                ));
+            }
+            batch.apply(content_storage, meta_storage, dna_storage)?;
+            clear_write_batch_journal(content_storage)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{
+        chain_header::test_chain_header,
+        crud_status::{create_crud_status_eav, CrudStatus},
+        dna::Dna,
+        eav::EaviQuery,
+        entry::test_entry,
+    };
+    use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+
+    #[test]
+    fn dna_entries_go_to_shared_storage() {
+        let storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        let shared: SharedDnaStorage = Arc::new(RwLock::new(MemoryStorage::new()));
+        let dna_storage = Some(shared.clone());
+
+        let dna_entry = Entry::Dna(Box::new(Dna::new()));
+        let app_entry = test_entry();
+        add_with_shared_dna(&storage, &dna_storage, &dna_entry).unwrap();
+        add_with_shared_dna(&storage, &dna_storage, &app_entry).unwrap();
+
+        let local = storage.read().unwrap();
+        assert!(!local.contains(&dna_entry.address()).unwrap());
+        assert!(local.contains(&app_entry.address()).unwrap());
+        assert!(shared
+            .read()
+            .unwrap()
+            .contains(&dna_entry.address())
+            .unwrap());
+        drop(local);
+
+        assert!(is_dna_content(&dna_entry.content()));
+        assert!(!is_dna_content(&app_entry.content()));
+        assert!(!is_dna_content(&test_chain_header().content()));
+
+        assert_eq!(
+            Some(dna_entry.content()),
+            fetch_with_shared_dna(&storage, &dna_storage, &dna_entry.address()).unwrap()
+        );
+        assert_eq!(
+            None,
+            fetch_with_shared_dna(&storage, &None, &dna_entry.address()).unwrap()
+        );
+    }
+
+    #[test]
+    fn write_batch_only_writes_on_commit() {
+        let storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        let meta_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+            Arc::new(RwLock::new(EavMemoryStorage::new()));
+        let entry = test_entry();
+        let eavi = create_crud_status_eav(&entry.address(), CrudStatus::Live).unwrap();
+
+        let mut batch = StorageWriteBatch::new();
+        batch.add(&entry);
+        batch.add_eavi(&eavi);
+        assert_eq!(batch.get_raw(&entry.address()), Some(entry.content()));
+        assert!(!storage.read().unwrap().contains(&entry.address()).unwrap());
+
+        batch.commit(&storage, Some(&meta_storage), &None).unwrap();
+        assert!(storage.read().unwrap().contains(&entry.address()).unwrap());
+        assert_eq!(
+            meta_storage
+                .read()
+                .unwrap()
+                .fetch_eavi(&EaviQuery::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        // the journal got cleared, so there is nothing to recover
+        assert!(!recover_write_batch(&storage, Some(&meta_storage), &None).unwrap());
+    }
+
+    #[test]
+    fn write_batch_is_all_or_nothing() {
+        let storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        let meta_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+            Arc::new(RwLock::new(EavMemoryStorage::new()));
+        let entry = test_entry();
+        let eavi = create_crud_status_eav(&entry.address(), CrudStatus::Live).unwrap();
+        let mut batch = StorageWriteBatch::new();
+        batch.add(&entry);
+        batch.add_eavi(&eavi);
+
+        // a batch that can't be committed completely doesn't write anything
+        assert!(batch.clone().commit(&storage, None, &None).is_err());
+        assert!(!storage.read().unwrap().contains(&entry.address()).unwrap());
+        assert!(!recover_write_batch(&storage, Some(&meta_storage), &None).unwrap());
+
+        // a commit that got interrupted after journaling the batch gets completed
+        (*storage.write().unwrap())
+            .add(&WriteBatchJournal { batch: Some(batch) })
+            .unwrap();
+        assert!(recover_write_batch(&storage, Some(&meta_storage), &None).unwrap());
+        assert!(storage.read().unwrap().contains(&entry.address()).unwrap());
+        assert_eq!(
+            meta_storage
+                .read()
+                .unwrap()
+                .fetch_eavi(&EaviQuery::default())
+                .unwrap(),
+            vec![eavi].into_iter().collect()
+        );
+        assert!(!recover_write_batch(&storage, Some(&meta_storage), &None).unwrap());
+    }
+}
@@ -1,11 +1,29 @@
 use crate::{
     action::{Action, ActionWrapper},
-    content_store::GetContent,
+    action_recording::ActionRecorder,
+    causal_log::CausalLog,
+    clock::SharedClock,
+    content_store::{GetContent, SharedDnaStorage},
+    dht::pending_validations::PendingValidationConfig,
+    holding_chaos::HoldingChaos,
+    id_generator::SharedIdGenerator,
     instance::Observer,
-    network::state::NetworkState,
+    instance_activity::{InstanceActivity, TimedWorkflow, WorkflowTimer},
+    instance_pause::InstancePause,
+    network::{
+        state::NetworkState, validation_package_cache::DEFAULT_VALIDATION_PACKAGE_CACHE_SIZE,
+    },
+    nucleus::{
+        rate_limit::{ZomeCallRateLimiter, ZomeCallRateLimits},
+        slow_calls::{SlowZomeCallConfig, ZomeCallWatchdog},
+    },
     persister::Persister,
+    profiler::Profiler,
+    resource_limits::{ResourceLimits, StorageQuota},
     signal::{Signal, SignalSender},
     state::StateWrapper,
+    storage_compaction::CompactStorage,
+    validation_coverage::ValidationCoverage,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use futures::{
@@ -101,6 +119,46 @@ pub struct Context {
     pub chain_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
     pub dht_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
     pub eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    /// Conductor level storage for DNA entries, shared by all instances that got it set.
+    /// If None, DNA entries are stored in chain_storage and dht_storage like any other entry.
+    pub dna_storage: Option<SharedDnaStorage>,
+    /// Set if the storage backend supports removing orphaned content, see storage_compaction.
+    pub storage_compactor: Option<Arc<dyn CompactStorage>>,
+    /// Public keys of clients that may sign zome calls made with the agent's own token,
+    /// in addition to the agent key itself.
+    pub client_keys: Vec<Address>,
+    /// Limits for incoming zome calls, per capability token and per caller.
+    pub zome_call_rate_limiter: Arc<ZomeCallRateLimiter>,
+    /// Limits of the memory, storage, calls and network traffic the instance may use
+    pub resource_limits: ResourceLimits,
+    /// Set if the storages count their content against `resource_limits.max_storage_bytes`
+    pub storage_quota: Option<Arc<StorageQuota>>,
+    /// Set while the instance is paused, see instance_pause
+    pub instance_pause: Arc<InstancePause>,
+    /// Zome calls and peers of the instance, for the conductor's statistics
+    pub activity: Arc<InstanceActivity>,
+    /// Recent actions and what caused them
+    pub causal_log: Arc<CausalLog>,
+    /// Running zome calls, to report those that take too long
+    pub zome_call_watchdog: Arc<ZomeCallWatchdog>,
+    /// Samples what the instance is doing while a profile is being taken
+    pub profiler: Arc<Profiler>,
+    /// Where the instance reads the time from, so that tests can control it
+    pub clock: SharedClock,
+    /// Where the instance gets the ids of its requests from, so that tests can make them
+    /// the same in every run
+    pub id_generator: SharedIdGenerator,
+    /// Writes the reduced actions to a file while the instance is being recorded
+    pub action_recorder: Arc<ActionRecorder>,
+    /// Set in tests to attempt queued holding workflows in random order, see holding_chaos
+    pub holding_chaos: Option<Arc<HoldingChaos>>,
+    /// Counts the validation branches the instance runs while coverage is being recorded
+    pub validation_coverage: Arc<ValidationCoverage>,
+    /// How holding workflows with unresolved dependencies get retried and given up on
+    pub pending_validation_config: PendingValidationConfig,
+    /// How many validation packages of other agents the instance keeps, see
+    /// validation_package_cache
+    pub validation_package_cache_size: usize,
     pub p2p_config: P2pConfig,
     pub conductor_api: ConductorApi,
     pub(crate) signal_tx: Option<Sender<Signal>>,
@@ -167,6 +225,26 @@ impl Context {
             chain_storage,
             dht_storage,
             eav_storage: eav,
+            dna_storage: None,
+            storage_compactor: None,
+            client_keys: Vec::new(),
+            zome_call_rate_limiter: Arc::new(ZomeCallRateLimiter::new(
+                ZomeCallRateLimits::default(),
+            )),
+            resource_limits: ResourceLimits::default(),
+            storage_quota: None,
+            instance_pause: Arc::new(InstancePause::new()),
+            activity: Arc::new(InstanceActivity::new()),
+            causal_log: Arc::new(CausalLog::default()),
+            zome_call_watchdog: Arc::new(ZomeCallWatchdog::new(SlowZomeCallConfig::default())),
+            profiler: Arc::new(Profiler::new()),
+            action_recorder: Arc::new(ActionRecorder::new()),
+            validation_coverage: Arc::new(ValidationCoverage::new()),
+            holding_chaos: None,
+            pending_validation_config: PendingValidationConfig::default(),
+            validation_package_cache_size: DEFAULT_VALIDATION_PACKAGE_CACHE_SIZE,
+            clock: SharedClock::system(),
+            id_generator: SharedIdGenerator::process(),
             p2p_config,
             conductor_api: ConductorApi::new(Self::test_check_conductor_api(
                 conductor_api,
@@ -208,6 +286,26 @@ impl Context {
             chain_storage: cas.clone(),
             dht_storage: cas,
             eav_storage: eav,
+            dna_storage: None,
+            storage_compactor: None,
+            client_keys: Vec::new(),
+            zome_call_rate_limiter: Arc::new(ZomeCallRateLimiter::new(
+                ZomeCallRateLimits::default(),
+            )),
+            resource_limits: ResourceLimits::default(),
+            storage_quota: None,
+            instance_pause: Arc::new(InstancePause::new()),
+            activity: Arc::new(InstanceActivity::new()),
+            causal_log: Arc::new(CausalLog::default()),
+            zome_call_watchdog: Arc::new(ZomeCallWatchdog::new(SlowZomeCallConfig::default())),
+            profiler: Arc::new(Profiler::new()),
+            action_recorder: Arc::new(ActionRecorder::new()),
+            validation_coverage: Arc::new(ValidationCoverage::new()),
+            holding_chaos: None,
+            pending_validation_config: PendingValidationConfig::default(),
+            validation_package_cache_size: DEFAULT_VALIDATION_PACKAGE_CACHE_SIZE,
+            clock: SharedClock::system(),
+            id_generator: SharedIdGenerator::process(),
             p2p_config,
             conductor_api: ConductorApi::new(Self::test_check_conductor_api(None, agent_id)),
             instance_is_alive: Arc::new(AtomicBool::new(true)),
@@ -224,6 +322,11 @@ impl Context {
         self.instance_name.clone()
     }
 
+    /// Times a run of the workflow until the returned timer gets dropped
+    pub fn time_workflow(&self, workflow: TimedWorkflow) -> WorkflowTimer {
+        WorkflowTimer::start(self.activity.clone(), self.get_instance_name(), workflow)
+    }
+
     pub fn set_state(&mut self, state: Arc<RwLock<StateWrapper>>) {
         self.state = Some(state);
     }
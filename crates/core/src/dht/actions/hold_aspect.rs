@@ -2,13 +2,18 @@ use crate::{
     action::{Action, ActionWrapper},
     context::Context,
     dht::dht_store::HoldAspectAttemptId,
+    event_log::{record_instance_event, EVENT_HOLD},
     instance::dispatch_action,
+    network::actions::holding_receipt::send_holding_receipt,
 };
 use futures::{future::Future, task::Poll};
-use holochain_core_types::{error::HolochainError, network::entry_aspect::EntryAspect};
+use holochain_core_types::{
+    entry::Entry, error::HolochainError, network::entry_aspect::EntryAspect,
+};
 use holochain_net::aspect_map::AspectMap;
 use holochain_persistence_api::cas::content::AddressableContent;
 use lib3h_protocol::data_types::EntryListData;
+use serde_json::json;
 use snowflake::ProcessUniqueId;
 use std::{pin::Pin, sync::Arc};
 
@@ -30,12 +35,25 @@ pub fn ack_single(context: Arc<Context>, aspect: EntryAspect) {
     dispatch_action(context.action_channel(), ActionWrapper::new(action));
 }
 
+fn record_hold(context: &Context, aspect: &EntryAspect) {
+    if let Err(e) = record_instance_event(
+        context,
+        EVENT_HOLD,
+        json!({
+            "address": aspect.entry_address().ok(),
+            "aspect": aspect.type_hint(),
+        }),
+    ) {
+        log_error!(context, "dht/hold_aspect: could not record event: {:?}", e);
+    }
+}
+
 pub async fn hold_aspect_no_ack(
     pending_id: &ProcessUniqueId,
     aspect: EntryAspect,
     context: Arc<Context>,
 ) -> Result<(), HolochainError> {
-    let id = (*pending_id, ProcessUniqueId::new());
+    let id = (*pending_id, context.id_generator.next_id());
     let action_wrapper = ActionWrapper::new(Action::HoldAspect((aspect.clone(), id)));
     dispatch_action(context.action_channel(), action_wrapper.clone());
     let r = HoldAspectFuture {
@@ -46,6 +64,8 @@ pub async fn hold_aspect_no_ack(
     .await;
     if r.is_err() {
         error!("HoldAspect action completed with error: {:?}", r);
+    } else {
+        record_hold(&context, &aspect);
     }
     r
 }
@@ -55,7 +75,7 @@ pub async fn hold_aspect(
     aspect: EntryAspect,
     context: Arc<Context>,
 ) -> Result<(), HolochainError> {
-    let id = (*pending_id, ProcessUniqueId::new());
+    let id = (*pending_id, context.id_generator.next_id());
     let action_wrapper = ActionWrapper::new(Action::HoldAspect((aspect.clone(), id)));
     dispatch_action(context.action_channel(), action_wrapper.clone());
     let r = HoldAspectFuture {
@@ -67,6 +87,20 @@ pub async fn hold_aspect(
     if r.is_err() {
         error!("HoldAspect action completed with error: {:?}", r);
     } else {
+        record_hold(&context, &aspect);
+        // let the author know that their entry reached us
+        if let EntryAspect::Content(entry, header) = &aspect {
+            let is_header_entry = if let Entry::ChainHeader(_) = entry {
+                true
+            } else {
+                false
+            };
+            if let Some(author) = header.provenances().first().map(|p| p.source()) {
+                if !is_header_entry && author != context.agent_id.address() {
+                    send_holding_receipt(author, header.address(), context.clone());
+                }
+            }
+        }
         // send a gossip list with this aspect in it back to sim2h so it know we are holding it
         ack_single(context, aspect);
     }
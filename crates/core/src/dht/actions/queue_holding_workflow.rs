@@ -6,11 +6,7 @@ use crate::{
 };
 use futures::{future::Future, task::Poll};
 use snowflake::ProcessUniqueId;
-use std::{
-    pin::Pin,
-    sync::Arc,
-    time::{Duration, SystemTime},
-};
+use std::{pin::Pin, sync::Arc, time::Duration};
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn dispatch_queue_holding_workflow(
@@ -18,7 +14,7 @@ pub fn dispatch_queue_holding_workflow(
     delay: Option<Duration>,
     context: Arc<Context>,
 ) {
-    let delay_with_now = delay.map(|d| (SystemTime::now(), d));
+    let delay_with_now = delay.map(|d| (context.clock.now(), d));
     let action_wrapper =
         ActionWrapper::new(Action::QueueHoldingWorkflow((pending, delay_with_now)));
     dispatch_action(context.action_channel(), action_wrapper);
@@ -39,7 +35,7 @@ pub async fn queue_holding_workflow(
     {
         log_trace!(context, "Queueing holding workflow: {:?}", pending);
         dispatch_queue_holding_workflow(pending.clone(), delay, context.clone());
-        let id = ProcessUniqueId::new();
+        let id = context.id_generator.next_id();
         QueueHoldingWorkflowFuture {
             context,
             pending,
@@ -11,13 +11,15 @@ use std::{pin::Pin, sync::Arc, time::Duration};
 // This enum is used to specify what to do with the item
 // Processing moves it to the in_process queue
 // waiting moves it back from the in_process queue to the holding queue
-// and Done removes it from the in_process queue entirely.
+// Done removes it from the in_process queue entirely
+// and DeadLetter moves it from the in_process queue to the dead letters.
 // this is implemented in dht_reducers.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum HoldingWorkflowQueueing {
     Processing,
     Waiting(Duration),
     Done,
+    DeadLetter,
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
@@ -31,7 +33,7 @@ pub async fn remove_queued_holding_workflow(
         pending.clone(),
     )));
     dispatch_action(context.action_channel(), action_wrapper.clone());
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     RemoveQueuedHoldingWorkflowFuture {
         context,
         pending,
@@ -79,7 +81,7 @@ impl Future for RemoveQueuedHoldingWorkflowFuture {
                         Poll::Ready(())
                     }
                 }
-                HoldingWorkflowQueueing::Done => {
+                HoldingWorkflowQueueing::Done | HoldingWorkflowQueueing::DeadLetter => {
                     if store.has_exact_in_process_holding_workflow(&self.pending) {
                         Poll::Pending
                     } else {
@@ -4,10 +4,7 @@ use holochain_core_types::error::HolochainError;
 
 use crate::{
     action::{Action, ActionWrapper},
-    dht::{
-        dht_store::DhtStore,
-        pending_validations::{PendingValidationWithTimeout, ValidationTimeout},
-    },
+    dht::{dht_store::DhtStore, pending_validations::ValidationTimeout},
 };
 use std::sync::Arc;
 
@@ -46,10 +43,13 @@ pub fn reduce(old_store: Arc<DhtStore>, action_wrapper: &ActionWrapper) -> Arc<D
 fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<DhtReducer> {
     match action_wrapper.action() {
         Action::Commit(_) => Some(reduce_commit_entry),
+        Action::CommitBatch(_) => Some(reduce_commit_batch),
         Action::HoldAspect(_) => Some(reduce_hold_aspect),
+        Action::DropHeldEntries(_) => Some(reduce_drop_held_entries),
         Action::QueueHoldingWorkflow(_) => Some(reduce_queue_holding_workflow),
         Action::RemoveQueuedHoldingWorkflow(_) => Some(reduce_remove_queued_holding_workflow),
         Action::Prune => Some(reduce_prune),
+        Action::RollbackChain(_) => Some(reduce_rollback_chain),
         _ => None,
     }
 }
@@ -61,7 +61,7 @@ pub(crate) fn reduce_commit_entry(
 ) -> Option<DhtStore> {
     let (entry, _, _) = unwrap_to!(action_wrapper.action() => Action::Commit);
     let mut new_store = (*old_store).clone();
-    match reduce_store_entry_inner(&mut new_store, entry) {
+    match new_store.in_write_batch(|store| reduce_store_entry_inner(store, entry)) {
         Ok(()) => Some(new_store),
         Err(e) => {
             error!("{}", e);
@@ -70,6 +70,57 @@ pub(crate) fn reduce_commit_entry(
     }
 }
 
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub(crate) fn reduce_commit_batch(
+    old_store: &DhtStore,
+    action_wrapper: &ActionWrapper,
+) -> Option<DhtStore> {
+    let entries = unwrap_to!(action_wrapper.action() => Action::CommitBatch);
+    let mut new_store = (*old_store).clone();
+    let result = new_store.in_write_batch(|store| {
+        entries
+            .iter()
+            .try_for_each(|(entry, _, _)| reduce_store_entry_inner(store, entry))
+    });
+    match result {
+        Ok(()) => Some(new_store),
+        Err(e) => {
+            error!("{}", e);
+            None
+        }
+    }
+}
+
+/// Stops reporting entries and headers dropped from our source chain as held and marks them
+/// as orphaned. CAS and EAV are append-only, so their content and metadata stay, but getting
+/// them or links from or to them comes up empty until they get committed again.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub(crate) fn reduce_rollback_chain(
+    old_store: &DhtStore,
+    action_wrapper: &ActionWrapper,
+) -> Option<DhtStore> {
+    let (_, _, orphaned) = unwrap_to!(action_wrapper.action() => Action::RollbackChain);
+    let mut new_store = (*old_store).clone();
+    for address in orphaned {
+        new_store.remove_holdings_for(address);
+        new_store.orphan(address);
+    }
+    Some(new_store)
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub(crate) fn reduce_drop_held_entries(
+    old_store: &DhtStore,
+    action_wrapper: &ActionWrapper,
+) -> Option<DhtStore> {
+    let entry_addresses = unwrap_to!(action_wrapper.action() => Action::DropHeldEntries);
+    let mut new_store = (*old_store).clone();
+    for entry_address in entry_addresses {
+        new_store.remove_holdings_for(entry_address);
+    }
+    Some(new_store)
+}
+
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub(crate) fn reduce_hold_aspect(
     old_store: &DhtStore,
@@ -88,11 +139,11 @@ pub(crate) fn reduce_hold_aspect(
     let mut hold_result: Result<(), HolochainError> = Ok(());
     let mut maybe_store = match aspect {
         EntryAspect::Content(entry, header) => {
-            match reduce_store_entry_inner(&mut new_store, &entry) {
-                Ok(()) => {
-                    new_store.add_header_for_entry(&entry, &header).ok()?;
-                    Some(new_store)
-                }
+            match new_store.in_write_batch(|store| {
+                reduce_store_entry_inner(store, &entry)?;
+                store.add_header_for_entry(&entry, &header)
+            }) {
+                Ok(()) => Some(new_store),
                 Err(e) => {
                     let err = format!("EntryAspect::Content hold error: {}", e);
                     hold_result = Err(HolochainError::ErrorGeneric(err));
@@ -102,13 +153,15 @@ pub(crate) fn reduce_hold_aspect(
         }
         EntryAspect::LinkAdd(link_data, header) => {
             let entry = Entry::LinkAdd(link_data.clone());
-            match reduce_add_remove_link_inner(
-                &mut new_store,
-                &link_data,
-                &entry.address(),
-                LinkModification::Add,
-                header,
-            ) {
+            match new_store.in_write_batch(|store| {
+                reduce_add_remove_link_inner(
+                    store,
+                    &link_data,
+                    &entry.address(),
+                    LinkModification::Add,
+                    header,
+                )
+            }) {
                 Ok(_) => Some(new_store),
                 Err(e) => {
                     let err = format!("EntryAspect::LinkAdd hold error: {}", e);
@@ -118,31 +171,31 @@ pub(crate) fn reduce_hold_aspect(
             }
         }
         EntryAspect::LinkRemove((link_data, links_to_remove), header) => {
-            let folded_result = Some(links_to_remove.iter().fold(
-                new_store,
-                |mut store, link_addresses| {
-                    if let Err(e) = reduce_add_remove_link_inner(
-                        &mut store,
+            match new_store.in_write_batch(|store| {
+                links_to_remove.iter().try_for_each(|link_addresses| {
+                    reduce_add_remove_link_inner(
+                        store,
                         &link_data,
                         link_addresses,
                         LinkModification::Remove,
                         header,
-                    ) {
-                        let err = format!("EntryAspect::LinkRemove hold error: {}", e);
-                        hold_result = Err(HolochainError::ErrorGeneric(err));
-                    }
-                    store
-                },
-            ));
-            if hold_result.is_ok() {
-                folded_result
-            } else {
-                None
+                    )
+                    .map(|_| ())
+                })
+            }) {
+                Ok(()) => Some(new_store),
+                Err(e) => {
+                    let err = format!("EntryAspect::LinkRemove hold error: {}", e);
+                    hold_result = Err(HolochainError::ErrorGeneric(err));
+                    None
+                }
             }
         }
         EntryAspect::Update(entry, header) => {
             if let Some(crud_link) = header.link_update_delete() {
-                match reduce_update_entry_inner(&mut new_store, &crud_link, &entry.address()) {
+                match new_store.in_write_batch(|store| {
+                    reduce_update_entry_inner(store, &crud_link, &entry.address())
+                }) {
                     Ok(_) => Some(new_store),
                     Err(e) => {
                         let err = format!("EntryAspect::Update hold error: {}", e);
@@ -158,8 +211,9 @@ pub(crate) fn reduce_hold_aspect(
         }
         EntryAspect::Deletion(header) => {
             if let Some(crud_link) = header.link_update_delete() {
-                match reduce_remove_entry_inner(&mut new_store, &crud_link, &header.entry_address())
-                {
+                match new_store.in_write_batch(|store| {
+                    reduce_remove_entry_inner(store, &crud_link, &header.entry_address())
+                }) {
                     Ok(_) => Some(new_store),
                     Err(e) => {
                         let err = format!("EntryAspect::Deletion hold error: {}", e);
@@ -250,12 +304,7 @@ pub fn reduce_queue_holding_workflow(
         None
     } else {
         let mut new_store = (*old_store).clone();
-        new_store
-            .queued_holding_workflows
-            .push_back(PendingValidationWithTimeout::new(
-                pending.clone(),
-                maybe_delay.map(ValidationTimeout::from),
-            ));
+        new_store.queue_holding_workflow(pending.clone(), maybe_delay.map(ValidationTimeout::from));
         Some(new_store)
     }
 }
@@ -307,7 +356,7 @@ pub mod tests {
             actions::remove_queued_holding_workflow::HoldingWorkflowQueueing,
             dht_reducers::{
                 reduce, reduce_hold_aspect, reduce_queue_holding_workflow,
-                reduce_remove_queued_holding_workflow,
+                reduce_remove_queued_holding_workflow, reduce_rollback_chain,
             },
             dht_store::{create_get_links_eavi_query, DhtStore},
             pending_validations::{PendingValidation, PendingValidationStruct, ValidatingWorkflow},
@@ -320,12 +369,17 @@ pub mod tests {
     use holochain_core_types::{
         agent::{test_agent_id, test_agent_id_with_name},
         chain_header::{test_chain_header, test_chain_header_with_sig},
-        eav::Attribute,
+        crud_status::CrudStatus,
+        eav::{Attribute, EaviQuery},
         entry::{test_entry, test_sys_entry, Entry},
         link::{link_data::LinkData, Link, LinkActionKind},
         network::entry_aspect::EntryAspect,
     };
-    use holochain_persistence_api::cas::content::{Address, AddressableContent};
+    use holochain_persistence_api::{
+        cas::content::{Address, AddressableContent},
+        eav::IndexFilter,
+        hash::HashString,
+    };
     use snowflake::ProcessUniqueId;
     use std::{sync::Arc, time::SystemTime};
 
@@ -372,6 +426,120 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn reduce_rollback_chain_test() {
+        let context = test_context("bob", None);
+        let store = test_store(context);
+        let header = test_chain_header();
+        let aspect = EntryAspect::Content(test_entry(), header.clone());
+
+        let holding_store = reduce_hold_aspect(
+            &store.dht(),
+            &ActionWrapper::new(Action::HoldAspect((
+                aspect.clone(),
+                (ProcessUniqueId::new(), ProcessUniqueId::new()),
+            ))),
+        )
+        .expect("there should be a new store for holding an aspect");
+        assert!(holding_store.get_holding_map().contains(&aspect));
+
+        let rolled_back_store = reduce_rollback_chain(
+            &holding_store,
+            &ActionWrapper::new(Action::RollbackChain((
+                test_chain_header_with_sig("target", None),
+                vec![header.clone()],
+                vec![header.address(), test_entry().address()],
+            ))),
+        )
+        .expect("there should be a new store after a rollback");
+        assert!(!rolled_back_store.get_holding_map().contains(&aspect));
+        assert_eq!(
+            rolled_back_store.get(&test_entry().address()).unwrap(),
+            None
+        );
+
+        // Storing the entry again makes it visible again
+        let held_again_store = reduce_hold_aspect(
+            &rolled_back_store,
+            &ActionWrapper::new(Action::HoldAspect((
+                aspect,
+                (ProcessUniqueId::new(), ProcessUniqueId::new()),
+            ))),
+        )
+        .expect("there should be a new store for holding an aspect");
+        assert_eq!(
+            held_again_store.get(&test_entry().address()).unwrap(),
+            Some(test_entry())
+        );
+    }
+
+    #[test]
+    fn reduce_drop_held_entries_test() {
+        let context = test_context("bob", None);
+        let store = test_store(context);
+        let aspect = EntryAspect::Content(test_entry(), test_chain_header());
+
+        let holding_store = reduce_hold_aspect(
+            &store.dht(),
+            &ActionWrapper::new(Action::HoldAspect((
+                aspect.clone(),
+                (ProcessUniqueId::new(), ProcessUniqueId::new()),
+            ))),
+        )
+        .expect("there should be a new store for holding an aspect");
+        assert!(holding_store.get_holding_map().contains(&aspect));
+
+        let dropped_store = reduce_drop_held_entries(
+            &holding_store,
+            &ActionWrapper::new(Action::DropHeldEntries(vec![test_entry().address()])),
+        )
+        .expect("there should be a new store after dropping entries");
+        assert!(!dropped_store.get_holding_map().contains(&aspect));
+    }
+
+    #[test]
+    fn reduce_hold_agent_update_test() {
+        let context = test_context("bob", None);
+        let store = test_store(context);
+        let agent = test_agent_id();
+        let updated_agent = Entry::AgentId(agent.updated(Some("robert".to_string()), None));
+        let header = test_chain_header_with_sig("sig", Some(agent.address()));
+
+        let new_dht_store = reduce_hold_aspect(
+            &store.dht(),
+            &ActionWrapper::new(Action::HoldAspect((
+                EntryAspect::Update(updated_agent.clone(), header),
+                (ProcessUniqueId::new(), ProcessUniqueId::new()),
+            ))),
+        )
+        .expect("there should be a new store for holding an agent update");
+
+        // the update is stored under its own address and the original identity at the agent
+        // address points to it, so getting the agent address resolves the latest identity
+        assert_ne!(updated_agent.address(), agent.address());
+        assert_eq!(
+            new_dht_store.get(&updated_agent.address()).unwrap(),
+            Some(updated_agent.clone())
+        );
+        let crud_meta = new_dht_store
+            .fetch_eavi(&EaviQuery::new(
+                Some(agent.address()).into(),
+                None.into(),
+                None.into(),
+                IndexFilter::LatestByAttribute,
+                None,
+            ))
+            .unwrap();
+        assert!(crud_meta
+            .iter()
+            .any(|eavi| eavi.attribute() == Attribute::CrudStatus
+                && eavi.value() == HashString::from(String::from(CrudStatus::Modified))));
+        assert!(crud_meta
+            .iter()
+            .any(|eavi| eavi.attribute() == Attribute::CrudLink
+                && eavi.value() == updated_agent.address()));
+    }
+
     #[test]
     fn can_add_links() {
         enable_logging_for_test();
@@ -1,5 +1,9 @@
 use crate::{
-    content_store::{AddContent, GetContent},
+    clock::SharedClock,
+    content_store::{
+        add_with_shared_dna, fetch_with_shared_dna, AddContent, GetContent, SharedDnaStorage,
+        StorageWriteBatch,
+    },
     dht::{
         actions::remove_queued_holding_workflow::HoldingWorkflowQueueing,
         pending_validations::{PendingValidationWithTimeout, ValidationTimeout},
@@ -34,10 +38,10 @@ use holochain_json_api::error::JsonResult;
 use holochain_persistence_api::error::PersistenceResult;
 use snowflake::ProcessUniqueId;
 use std::{
-    collections::{BTreeSet, HashMap, VecDeque},
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
     convert::TryFrom,
     sync::Arc,
-    time::{Duration, SystemTime},
+    time::Duration,
 };
 
 /// A type for identifying holding attempts uniquely and by parent pending validation id
@@ -51,15 +55,29 @@ pub struct DhtStore {
     // Storages holding local shard data
     content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
     meta_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    // Conductor wide storage that DNA entries get written to instead, if set
+    dna_storage: Option<SharedDnaStorage>,
+    // Writes of the current action, if they get batched (see in_write_batch)
+    write_batch: Option<StorageWriteBatch>,
 
     /// All the entry aspects that the network has told us to hold
     holding_map: AspectMap,
 
+    /// Entries and headers that got rolled back from our source chain (see
+    /// agent::actions::rollback). The storages are append-only, so their content and metadata
+    /// stay, but lookups treat them as missing until they get stored again.
+    orphaned: HashSet<Address>,
+
     /// Hold aspect attempts that come from pending validations
     holding_attempt_results: HashMap<HoldAspectAttemptId, Result<(), HolochainError>>,
 
     pub(crate) queued_holding_workflows: VecDeque<PendingValidationWithTimeout>,
     pub(crate) in_process_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+    /// Holding workflows that got given up on, see PendingValidationConfig
+    pub(crate) dead_letter_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+
+    /// Timeouts of queued holding workflows are measured with this
+    clock: SharedClock,
 }
 
 impl PartialEq for DhtStore {
@@ -81,6 +99,27 @@ pub struct DhtStoreSnapshot {
     queued_holding_workflows: VecDeque<PendingValidationWithTimeout>,
     #[serde(default)]
     in_process_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+    #[serde(default)]
+    dead_letter_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+    #[serde(default)]
+    orphaned: HashSet<Address>,
+}
+
+impl DhtStoreSnapshot {
+    pub fn new(
+        holding_map: AspectMapBare,
+        queued_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+        in_process_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+        dead_letter_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+    ) -> Self {
+        DhtStoreSnapshot {
+            holding_map,
+            queued_holding_workflows,
+            in_process_holding_workflows,
+            dead_letter_holding_workflows,
+            orphaned: HashSet::new(),
+        }
+    }
 }
 
 impl From<&StateWrapper> for DhtStoreSnapshot {
@@ -89,6 +128,8 @@ impl From<&StateWrapper> for DhtStoreSnapshot {
             holding_map: state.dht().get_holding_map().bare().clone(),
             queued_holding_workflows: state.dht().queued_holding_workflows.clone(),
             in_process_holding_workflows: state.dht().in_process_holding_workflows.clone(),
+            dead_letter_holding_workflows: state.dht().dead_letter_holding_workflows.clone(),
+            orphaned: state.dht().orphaned.clone(),
         }
     }
 }
@@ -154,10 +195,15 @@ impl DhtStore {
         DhtStore {
             content_storage,
             meta_storage,
+            dna_storage: None,
+            write_batch: None,
             holding_map: AspectMap::new(),
+            orphaned: HashSet::new(),
             queued_holding_workflows: VecDeque::new(),
             in_process_holding_workflows: VecDeque::new(),
+            dead_letter_holding_workflows: VecDeque::new(),
             holding_attempt_results: HashMap::new(),
+            clock: SharedClock::system(),
         }
     }
 
@@ -165,9 +211,11 @@ impl DhtStore {
         content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
         meta_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
         mut snapshot: DhtStoreSnapshot,
+        clock: &SharedClock,
     ) -> Self {
-        let mut new_dht_store = Self::new(content_storage, meta_storage);
+        let mut new_dht_store = Self::new(content_storage, meta_storage).with_clock(clock.clone());
         new_dht_store.holding_map = snapshot.holding_map.into();
+        new_dht_store.orphaned = snapshot.orphaned;
 
         // the in_process queue is no longer in-process when being restored so
         // items are put in the waiting queue
@@ -175,11 +223,12 @@ impl DhtStore {
         new_dht_store
             .queued_holding_workflows
             .append(&mut snapshot.queued_holding_workflows);
+        new_dht_store.dead_letter_holding_workflows = snapshot.dead_letter_holding_workflows;
 
         // All items need the timeout reset
         for item in new_dht_store.queued_holding_workflows.iter_mut() {
             item.timeout = Some(ValidationTimeout::new(
-                SystemTime::now(),
+                clock.now(),
                 RETRY_VALIDATION_DURATION_MIN,
             ))
         }
@@ -187,6 +236,17 @@ impl DhtStore {
         new_dht_store
     }
 
+    /// Keep DNA entries in the given shared storage instead of the DHT's own storage.
+    pub fn with_dna_storage(mut self, dna_storage: Option<SharedDnaStorage>) -> Self {
+        self.dna_storage = dna_storage;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     ///This algorithm works by querying the EAVI Query for entries that match the address given, the link _type given, the tag given and a tombstone query set of RemovedLink(remove_link_address, link_type, tag)
     ///this means no matter how many links are added after one is removed, we will always say that the link has been removed.
     ///One thing to remember is that LinkAdd entries occupy the "Value" aspect of our EAVI link stores.
@@ -201,7 +261,14 @@ impl DhtStore {
         configuration: GetLinksQueryConfiguration,
     ) -> Result<Vec<(EntityAttributeValueIndex, CrudStatus)>, HolochainError> {
         let get_links_query = create_get_links_eavi_query(address, link_type, tag)?;
-        let filtered = self.meta_storage.read()?.fetch_eavi(&get_links_query)?;
+        let filtered: Vec<EntityAttributeValueIndex> = self
+            .meta_storage
+            .read()?
+            .fetch_eavi(&get_links_query)?
+            .into_iter()
+            // Links of rolled back link entries or from rolled back bases are gone
+            .filter(|eavi| !self.is_orphaned(&eavi.entity()) && !self.is_orphaned(&eavi.value()))
+            .collect();
         let pagination = configuration.pagination;
         let filter_with_sort_order: Box<dyn Iterator<Item = EntityAttributeValueIndex>> =
             match configuration.sort_order.unwrap_or_default() {
@@ -264,40 +331,37 @@ impl DhtStore {
     /// Get all headers for an entry by first looking in the DHT meta store
     /// for header addresses, then resolving them with the DHT CAS
     pub fn get_headers(&self, entry_address: Address) -> Result<Vec<ChainHeader>, HolochainError> {
-        self.meta_storage
-            .read()
-            .unwrap()
-            // fetch all EAV references to chain headers for this entry
-            .fetch_eavi(&EaviQuery::new(
-                Some(entry_address).into(),
-                Some(Attribute::EntryHeader).into(),
-                None.into(),
-                IndexFilter::LatestByAttribute,
-                None,
-            ))?
-            .into_iter()
-            // get the header addresses
-            .map(|eavi| eavi.value())
-            // fetch the header content from CAS
-            .map(|address| self.get(&address))
-            // rearrange
-            .collect::<Result<Vec<Option<_>>, _>>()
-            .map(|r| {
-                r.into_iter()
-                    // ignore None values
-                    .flatten()
-                    .map(|entry| match entry {
-                        Entry::ChainHeader(chain_header) => Ok(chain_header),
-                        _ => Err(HolochainError::ErrorGeneric(
-                            "Unexpected non-chain_header entry".to_string(),
-                        )),
-                    })
-                    .collect::<Result<Vec<_>, _>>()
-            })?
-            .map_err(|err| {
-                let hc_error: HolochainError = err;
-                hc_error
-            })
+        // fetch all EAV references to chain headers for this entry
+        self.fetch_eavi(&EaviQuery::new(
+            Some(entry_address).into(),
+            Some(Attribute::EntryHeader).into(),
+            None.into(),
+            IndexFilter::LatestByAttribute,
+            None,
+        ))?
+        .into_iter()
+        // get the header addresses
+        .map(|eavi| eavi.value())
+        // fetch the header content from CAS
+        .map(|address| self.get(&address))
+        // rearrange
+        .collect::<Result<Vec<Option<_>>, _>>()
+        .map(|r| {
+            r.into_iter()
+                // ignore None values
+                .flatten()
+                .map(|entry| match entry {
+                    Entry::ChainHeader(chain_header) => Ok(chain_header),
+                    _ => Err(HolochainError::ErrorGeneric(
+                        "Unexpected non-chain_header entry".to_string(),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })?
+        .map_err(|err| {
+            let hc_error: HolochainError = err;
+            hc_error
+        })
     }
 
     /// Add an entry and header to the CAS and EAV, respectively
@@ -320,6 +384,21 @@ impl DhtStore {
         self.holding_map.add(aspect);
     }
 
+    /// Forgets every aspect we hold for the given entry address.
+    pub(crate) fn remove_holdings_for(&mut self, entry_address: &Address) {
+        self.holding_map.remove_entry(&entry_address.clone().into());
+    }
+
+    /// Makes lookups of the entry or header and of links from or to it come up empty, until
+    /// it gets stored again.
+    pub(crate) fn orphan(&mut self, address: &Address) {
+        self.orphaned.insert(address.clone());
+    }
+
+    pub fn is_orphaned(&self, address: &Address) -> bool {
+        self.orphaned.contains(address)
+    }
+
     pub fn mark_hold_aspect_complete(
         &mut self,
         id: HoldAspectAttemptId,
@@ -343,14 +422,48 @@ impl DhtStore {
         &self,
         query: &EaviQuery,
     ) -> PersistenceResult<BTreeSet<EntityAttributeValueIndex>> {
-        self.meta_storage.read().unwrap().fetch_eavi(query)
+        let stored = self.meta_storage.read().unwrap().fetch_eavi(query)?;
+        match self.write_batch {
+            Some(ref batch) if !batch.eavis().is_empty() => {
+                Ok(query.run(stored.into_iter().chain(batch.eavis().iter().cloned())))
+            }
+            _ => Ok(stored),
+        }
     }
 
     pub(crate) fn add_eavi(
         &mut self,
         eavi: &EntityAttributeValueIndex,
     ) -> PersistenceResult<Option<EntityAttributeValueIndex>> {
-        self.meta_storage.write().unwrap().add_eavi(&eavi)
+        match self.write_batch {
+            Some(ref mut batch) => {
+                batch.add_eavi(eavi);
+                Ok(Some(eavi.clone()))
+            }
+            None => self.meta_storage.write().unwrap().add_eavi(&eavi),
+        }
+    }
+
+    /// Runs `f` with all its writes to the CAS and EAV batched, and commits them together
+    /// only if it succeeds. Writes of a failing `f` get dropped.
+    /// Within an already running batch, `f` simply becomes part of that batch.
+    pub(crate) fn in_write_batch<T, F>(&mut self, f: F) -> HcResult<T>
+    where
+        F: FnOnce(&mut DhtStore) -> HcResult<T>,
+    {
+        if self.write_batch.is_some() {
+            return f(self);
+        }
+        self.write_batch = Some(StorageWriteBatch::new());
+        let result = f(self);
+        let batch = self.write_batch.take().unwrap_or_default();
+        let value = result?;
+        batch.commit(
+            &self.content_storage,
+            Some(&self.meta_storage),
+            &self.dna_storage,
+        )?;
+        Ok(value)
     }
 
     pub(crate) fn next_queued_holding_workflow(
@@ -362,27 +475,41 @@ impl DhtStore {
             // filter so only free pending (those without dependencies also pending) are considered
             .filter(free_pending_filter(&self.queued_holding_workflows))
             // skip those for which the sleep delay has not elapsed
-            .skip_while(|PendingValidationWithTimeout { timeout, .. }| {
-                if let Some(ValidationTimeout {
-                    time_of_dispatch,
-                    delay,
-                }) = timeout
-                {
-                    let maybe_time_elapsed = time_of_dispatch.elapsed();
-                    if let Ok(time_elapsed) = maybe_time_elapsed {
-                        if time_elapsed < *delay {
-                            return true;
-                        }
-                    }
-                }
-                false
-            })
-            .map(|PendingValidationWithTimeout { pending, timeout }| {
-                (pending, timeout.map(|t| Some(t.delay)).unwrap_or(None))
-            })
+            .skip_while(|item| self.is_delayed(item))
+            .map(|item| (item.pending, item.timeout.map(|t| t.delay)))
             .next()
     }
 
+    /// All queued holding workflows that could run now: none of their dependencies is queued
+    /// as well and their retry delay is over
+    pub(crate) fn ready_queued_holding_workflows(
+        &self,
+    ) -> Vec<(PendingValidation, Option<Duration>)> {
+        self.queued_holding_workflows
+            .clone()
+            .into_iter()
+            .filter(free_pending_filter(&self.queued_holding_workflows))
+            .filter(|item| !self.is_delayed(item))
+            .map(|item| (item.pending, item.timeout.map(|t| t.delay)))
+            .collect()
+    }
+
+    fn is_delayed(&self, item: &PendingValidationWithTimeout) -> bool {
+        if let Some(ValidationTimeout {
+            time_of_dispatch,
+            delay,
+        }) = item.timeout
+        {
+            let maybe_time_elapsed = self.clock.elapsed(time_of_dispatch);
+            if let Ok(time_elapsed) = maybe_time_elapsed {
+                if time_elapsed < delay {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
     pub(crate) fn has_exact_queued_holding_workflow(&self, pending: &PendingValidation) -> bool {
         self.queued_holding_workflows.iter().any(
             |PendingValidationWithTimeout {
@@ -424,14 +551,49 @@ impl DhtStore {
         )
     }
 
-    pub(crate) fn queued_holding_workflows(&self) -> &VecDeque<PendingValidationWithTimeout> {
+    pub fn queued_holding_workflows(&self) -> &VecDeque<PendingValidationWithTimeout> {
         &self.queued_holding_workflows
     }
 
-    pub(crate) fn in_process_holding_workflows(&self) -> &VecDeque<PendingValidationWithTimeout> {
+    pub fn in_process_holding_workflows(&self) -> &VecDeque<PendingValidationWithTimeout> {
         &self.in_process_holding_workflows
     }
 
+    pub fn dead_letter_holding_workflows(&self) -> &VecDeque<PendingValidationWithTimeout> {
+        &self.dead_letter_holding_workflows
+    }
+
+    /// The queued holding workflow of exactly this pending validation, with its retries
+    pub(crate) fn queued_holding_workflow(
+        &self,
+        item: &PendingValidation,
+    ) -> Option<&PendingValidationWithTimeout> {
+        self.queued_holding_workflows
+            .iter()
+            .find(|PendingValidationWithTimeout { pending, .. }| pending == item)
+    }
+
+    pub(crate) fn queue_holding_workflow(
+        &mut self,
+        pending: PendingValidation,
+        timeout: Option<ValidationTimeout>,
+    ) {
+        // A dead letter that gets sent again gets another chance
+        self.remove_dead_letter_holding_workflow(&pending);
+        let mut queued = PendingValidationWithTimeout::new(pending, timeout);
+        queued.queued_at = Some(self.clock.now());
+        self.queued_holding_workflows.push_back(queued);
+    }
+
+    fn remove_dead_letter_holding_workflow(&mut self, item: &PendingValidation) {
+        self.dead_letter_holding_workflows.retain(
+            |PendingValidationWithTimeout { pending, .. }| {
+                pending.entry_with_header.header != item.entry_with_header.header
+                    || pending.workflow != item.workflow
+            },
+        );
+    }
+
     pub(crate) fn update_queued_holding_workflow(
         &mut self,
         state: &HoldingWorkflowQueueing,
@@ -453,7 +615,8 @@ impl DhtStore {
                     }
                     Some(pending) => {
                         let mut pending = pending;
-                        pending.timeout = Some(ValidationTimeout::new(SystemTime::now(), *delay));
+                        pending.timeout = Some(ValidationTimeout::new(self.clock.now(), *delay));
+                        pending.retries += 1;
                         self.queued_holding_workflows.push_back(pending);
                     }
                 }
@@ -476,6 +639,24 @@ impl DhtStore {
                     }
                 }
             }
+            HoldingWorkflowQueueing::DeadLetter => {
+                match self
+                    .in_process_holding_workflows
+                    .iter()
+                    .position(|PendingValidationWithTimeout { pending, .. }| pending == item)
+                    .and_then(|index| self.in_process_holding_workflows.remove(index))
+                {
+                    None => {
+                        error!(
+                            "update_queued_holding_workflow {:?} not found in process!",
+                            item
+                        );
+                    }
+                    Some(pending) => {
+                        self.dead_letter_holding_workflows.push_back(pending);
+                    }
+                }
+            }
             HoldingWorkflowQueueing::Done => {
                 if self
                     .in_process_holding_workflows
@@ -531,15 +712,28 @@ where
 
 impl GetContent for DhtStore {
     fn get_raw(&self, address: &Address) -> HcResult<Option<Content>> {
-        Ok((*self.content_storage.read().unwrap()).fetch(address)?)
+        if self.is_orphaned(address) {
+            return Ok(None);
+        }
+        if let Some(content) = self
+            .write_batch
+            .as_ref()
+            .and_then(|batch| batch.get_raw(address))
+        {
+            return Ok(Some(content));
+        }
+        fetch_with_shared_dna(&self.content_storage, &self.dna_storage, address)
     }
 }
 
 impl AddContent for DhtStore {
     fn add<T: AddressableContent>(&mut self, content: &T) -> HcResult<()> {
-        (*self.content_storage.write().unwrap())
-            .add(content)
-            .map_err(|e| e.into())
+        match self.write_batch {
+            Some(ref mut batch) => batch.add(content),
+            None => add_with_shared_dna(&self.content_storage, &self.dna_storage, content)?,
+        }
+        self.orphaned.remove(&content.address());
+        Ok(())
     }
 }
 
@@ -547,6 +741,7 @@ impl AddContent for DhtStore {
 pub mod tests {
     use super::*;
     use crate::{
+        clock::MockClock,
         dht::pending_validations::{PendingValidationStruct, ValidatingWorkflow},
         network::entry_with_header::EntryWithHeader,
     };
@@ -558,6 +753,7 @@ pub mod tests {
     use holochain_persistence_api::{
         cas::storage::ExampleContentAddressableStorage, eav::ExampleEntityAttributeValueStorage,
     };
+    use std::time::SystemTime;
 
     #[test]
     fn get_headers_roundtrip() {
@@ -576,6 +772,37 @@ pub mod tests {
         assert_eq!(headers, vec![header1, header2]);
     }
 
+    #[test]
+    fn write_batch_commits_all_or_nothing() {
+        let mut store = DhtStore::new(
+            Arc::new(RwLock::new(
+                ExampleContentAddressableStorage::new().unwrap(),
+            )),
+            Arc::new(RwLock::new(ExampleEntityAttributeValueStorage::new())),
+        );
+        let entry = test_entry();
+        let header = test_chain_header_with_sig("sig1", None);
+
+        let result: HcResult<()> = store.in_write_batch(|store| {
+            store.add_header_for_entry(&entry, &header)?;
+            // Writes within the batch are visible to the batch itself
+            assert_eq!(
+                store.get_headers(entry.address()).unwrap(),
+                vec![header.clone()]
+            );
+            Err(HolochainError::ErrorGeneric("fail".into()))
+        });
+        assert!(result.is_err());
+        assert!(!store.contains(&header.address()).unwrap());
+        assert!(store.get_headers(entry.address()).unwrap().is_empty());
+
+        store
+            .in_write_batch(|store| store.add_header_for_entry(&entry, &header))
+            .unwrap();
+        assert!(store.contains(&header.address()).unwrap());
+        assert_eq!(store.get_headers(entry.address()).unwrap(), vec![header]);
+    }
+
     fn pending_validation_for_entry(
         entry: Entry,
         dependencies: Vec<Address>,
@@ -589,6 +816,88 @@ pub mod tests {
         PendingValidationWithTimeout::new(Arc::new(pending_struct.clone()), None)
     }
 
+    #[test]
+    fn holding_workflows_survive_snapshots() {
+        let queued = pending_validation_for_entry(test_entry_a(), Vec::new());
+        let in_process = pending_validation_for_entry(test_entry_b(), Vec::new());
+        let mut dead_letter = pending_validation_for_entry(test_entry_c(), Vec::new());
+        dead_letter.retries = 3;
+        let snapshot = DhtStoreSnapshot::new(
+            AspectMap::new().bare().clone(),
+            vec![queued.clone()].into(),
+            vec![in_process.clone()].into(),
+            vec![dead_letter.clone()].into(),
+        );
+        let snapshot = DhtStoreSnapshot::try_from_content(&snapshot.content()).unwrap();
+
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let store = DhtStore::new_from_snapshot(
+            Arc::new(RwLock::new(
+                ExampleContentAddressableStorage::new().unwrap(),
+            )),
+            Arc::new(RwLock::new(ExampleEntityAttributeValueStorage::new())),
+            snapshot,
+            &SharedClock::new(MockClock::new(now)),
+        );
+
+        // Workflows that were in process get queued again, ahead of the waiting ones,
+        // and all of them are retried after the minimum delay
+        let timeout = Some(ValidationTimeout::new(now, RETRY_VALIDATION_DURATION_MIN));
+        assert_eq!(
+            store.queued_holding_workflows().iter().collect::<Vec<_>>(),
+            vec![
+                &PendingValidationWithTimeout::new(in_process.pending, timeout.clone()),
+                &PendingValidationWithTimeout::new(queued.pending, timeout),
+            ]
+        );
+        assert!(store.in_process_holding_workflows().is_empty());
+        // Dead letters stay given up on
+        assert_eq!(
+            store
+                .dead_letter_holding_workflows()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![&dead_letter]
+        );
+    }
+
+    #[test]
+    fn retried_workflows_count_retries_and_get_dead_lettered() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let mut store = DhtStore::new(
+            Arc::new(RwLock::new(
+                ExampleContentAddressableStorage::new().unwrap(),
+            )),
+            Arc::new(RwLock::new(ExampleEntityAttributeValueStorage::new())),
+        )
+        .with_clock(SharedClock::new(MockClock::new(now)));
+        let pending = pending_validation_for_entry(test_entry_a(), Vec::new()).pending;
+        store.queue_holding_workflow(pending.clone(), None);
+
+        for retries in 0..2 {
+            let queued = store.queued_holding_workflow(&pending).unwrap();
+            assert_eq!(queued.retries, retries);
+            assert_eq!(queued.queued_at, Some(now));
+            store.update_queued_holding_workflow(&HoldingWorkflowQueueing::Processing, &pending);
+            store.update_queued_holding_workflow(
+                &HoldingWorkflowQueueing::Waiting(Duration::from_secs(1)),
+                &pending,
+            );
+        }
+
+        store.update_queued_holding_workflow(&HoldingWorkflowQueueing::Processing, &pending);
+        store.update_queued_holding_workflow(&HoldingWorkflowQueueing::DeadLetter, &pending);
+        assert!(store.queued_holding_workflow(&pending).is_none());
+        assert!(store.in_process_holding_workflows().is_empty());
+        let dead_letters = store.dead_letter_holding_workflows();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].pending, pending);
+        assert_eq!(dead_letters[0].retries, 2);
+
+        store.queue_holding_workflow(Arc::new(pending.same()), None);
+        assert!(store.dead_letter_holding_workflows().is_empty());
+    }
+
     #[test]
     fn test_dependency_resolution_no_dependencies() {
         // A and B have no dependencies. Both should be free
@@ -622,6 +931,28 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn ready_workflows_leave_out_dependent_and_delayed_ones() {
+        let mut store = DhtStore::new(
+            Arc::new(RwLock::new(
+                ExampleContentAddressableStorage::new().unwrap(),
+            )),
+            Arc::new(RwLock::new(ExampleEntityAttributeValueStorage::new())),
+        );
+        let a = pending_validation_for_entry(test_entry_a(), vec![test_entry_b().address()]);
+        let b = pending_validation_for_entry(test_entry_b(), vec![]);
+        let mut c = pending_validation_for_entry(test_entry_c(), vec![]);
+        c.timeout = Some(ValidationTimeout::new(
+            store.clock.now(),
+            Duration::from_secs(60),
+        ));
+        store.queued_holding_workflows = vec![a, b.clone(), c].into();
+        assert_eq!(
+            store.ready_queued_holding_workflows(),
+            vec![(b.pending, None)]
+        );
+    }
+
     #[test]
     fn test_dependency_resolution_tree() {
         // A depends on B and C. B and C should be free
@@ -1,5 +1,6 @@
 use crate::{
     entry::validation_dependencies::ValidationDependencies,
+    instance::{RETRY_VALIDATION_DURATION_MAX, RETRY_VALIDATION_DURATION_MIN},
     network::entry_with_header::EntryWithHeader,
 };
 use chrono::{offset::Utc, DateTime};
@@ -12,6 +13,7 @@ use holochain_json_api::{error::JsonError, json::JsonString};
 use holochain_persistence_api::cas::content::Address;
 use snowflake::ProcessUniqueId;
 use std::{
+    cmp::min,
     convert::TryFrom,
     fmt,
     ops::Add,
@@ -76,6 +78,10 @@ pub struct PendingValidationStruct {
     pub dependencies: Vec<Address>,
     pub workflow: ValidatingWorkflow,
     pub uuid: ProcessUniqueId,
+    /// Trace context of the network message this validation came in with,
+    /// so that the holding workflow continues the trace of the sending node
+    #[serde(default)]
+    pub trace: Option<ht::EncodedSpanWrap<()>>,
 }
 
 impl PendingValidationStruct {
@@ -86,6 +92,7 @@ impl PendingValidationStruct {
             dependencies,
             workflow,
             uuid: ProcessUniqueId::new(),
+            trace: None,
         }
     }
 
@@ -128,9 +135,9 @@ impl TryFrom<EntryAspect> for PendingValidationStruct {
             EntryAspect::Deletion(header) => {
                 // reconstruct the deletion entry from the header.
                 let deleted_entry_address = header.link_update_delete().ok_or_else(|| {
-                    HolochainError::ValidationFailed(String::from(
-                        "Deletion header is missing deletion link",
-                    ))
+                    HolochainError::ValidationFailed(
+                        "Deletion header is missing deletion link".into(),
+                    )
                 })?;
                 let entry = Entry::Deletion(DeletionEntry::new(deleted_entry_address));
 
@@ -201,10 +208,108 @@ impl From<(SystemTime, Duration)> for ValidationTimeout {
 pub struct PendingValidationWithTimeout {
     pub pending: PendingValidation,
     pub timeout: Option<ValidationTimeout>,
+    /// How often the workflow got queued again because of unresolved dependencies
+    #[serde(default)]
+    pub retries: u32,
+    /// When the workflow got queued first
+    #[serde(default)]
+    pub queued_at: Option<SystemTime>,
 }
 
 impl PendingValidationWithTimeout {
     pub fn new(pending: PendingValidation, timeout: Option<ValidationTimeout>) -> Self {
-        Self { pending, timeout }
+        Self {
+            pending,
+            timeout,
+            retries: 0,
+            queued_at: None,
+        }
+    }
+}
+
+/// How holding workflows get retried while their dependencies are missing.
+/// Retries get delayed exponentially, starting at `initial_backoff_ms` and doubling with every
+/// retry up to `max_backoff_ms`. Workflows that still miss dependencies after `max_retries`
+/// retries, or `expiry_secs` after they got queued, are given up on and kept as dead letters.
+/// Without either of them set, workflows get retried forever.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PendingValidationConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiry_secs: Option<u64>,
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    RETRY_VALIDATION_DURATION_MIN.as_millis() as u64
+}
+
+fn default_max_backoff_ms() -> u64 {
+    RETRY_VALIDATION_DURATION_MAX.as_millis() as u64
+}
+
+impl Default for PendingValidationConfig {
+    fn default() -> Self {
+        PendingValidationConfig {
+            max_retries: None,
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            expiry_secs: None,
+        }
+    }
+}
+
+impl PendingValidationConfig {
+    /// How long to wait before the given retry, counted from 1
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u64.checked_shl(retry.saturating_sub(1)).unwrap_or(0);
+        let backoff_ms = match self.initial_backoff_ms.checked_mul(factor) {
+            Some(backoff_ms) if factor > 0 => min(backoff_ms, self.max_backoff_ms),
+            _ => self.max_backoff_ms,
+        };
+        Duration::from_millis(backoff_ms)
+    }
+
+    /// Whether a workflow that got retried that often and has been queued for that long
+    /// is to be dead-lettered instead of retried once more
+    pub fn is_exhausted(&self, retries: u32, queued_for: Duration) -> bool {
+        self.max_retries.map_or(false, |max| retries >= max)
+            || self
+                .expiry_secs
+                .map_or(false, |expiry| queued_for >= Duration::from_secs(expiry))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_exponentially_until_exhausted() {
+        let config = PendingValidationConfig {
+            max_retries: Some(3),
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 3000,
+            expiry_secs: Some(60),
+        };
+        assert_eq!(config.backoff(1), Duration::from_millis(1000));
+        assert_eq!(config.backoff(2), Duration::from_millis(2000));
+        assert_eq!(config.backoff(3), Duration::from_millis(3000));
+        assert_eq!(config.backoff(100), Duration::from_millis(3000));
+
+        assert!(!config.is_exhausted(2, Duration::from_secs(59)));
+        assert!(config.is_exhausted(3, Duration::from_secs(0)));
+        assert!(config.is_exhausted(0, Duration::from_secs(60)));
+        assert!(
+            !PendingValidationConfig::default().is_exhausted(1000, Duration::from_secs(1 << 30))
+        );
+        assert_eq!(
+            PendingValidationConfig::default().backoff(1),
+            RETRY_VALIDATION_DURATION_MIN
+        );
     }
 }
@@ -2,7 +2,11 @@
 
 pub mod validation_dependencies;
 
-use holochain_core_types::entry::entry_type::EntryType;
+use holochain_core_types::{
+    entry::{entry_type::EntryType, Entry},
+    error::HolochainError,
+};
+use holochain_persistence_api::cas::content::AddressableContent;
 
 use crate::context::Context;
 pub trait CanPublish {
@@ -13,7 +17,10 @@ pub trait CanPublish {
 impl CanPublish for EntryType {
     fn can_publish(&self, context: &Context) -> bool {
         match self {
-            EntryType::Dna | EntryType::CapTokenGrant | EntryType::CapTokenClaim => return false,
+            EntryType::Dna
+            | EntryType::CapTokenGrant
+            | EntryType::CapTokenClaim
+            | EntryType::CapTokenRevocation => return false,
             _ => {
                 if self.is_sys() {
                     return true;
@@ -42,11 +49,33 @@ impl CanPublish for EntryType {
     }
 }
 
+/// Links get published, so a link from a public entry to a private one would expose the
+/// private entry's address on the DHT. This makes sure such links are rejected.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn validate_link_visibility(
+    base: &Entry,
+    target: &Entry,
+    context: &Context,
+) -> Result<(), HolochainError> {
+    if base.entry_type().can_publish(context) && !target.entry_type().can_publish(context) {
+        return Err(HolochainError::ValidationFailed(
+            format!(
+                "Can not link public entry {} to private entry {}",
+                base.address(),
+                target.address()
+            )
+            .into(),
+        ));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
 
     use holochain_core_types::entry::entry_type::{AppEntryType, EntryType};
+    use holochain_json_api::json::RawString;
     use holochain_persistence_api::cas::content::{Address, AddressableContent};
 
     use test_utils::create_arbitrary_test_dna;
@@ -67,6 +96,7 @@ pub mod tests {
             EntryType::ChainMigrate,
             EntryType::CapTokenClaim,
             EntryType::CapTokenGrant,
+            EntryType::CapTokenRevocation,
         ]
     }
 
@@ -82,6 +112,7 @@ pub mod tests {
                 EntryType::Dna => assert!(!t.can_publish(&context)),
                 EntryType::CapTokenGrant => assert!(!t.can_publish(&context)),
                 EntryType::CapTokenClaim => assert!(!t.can_publish(&context)),
+                EntryType::CapTokenRevocation => assert!(!t.can_publish(&context)),
                 EntryType::App(entry_type_name) => match entry_type_name.to_string().as_str() {
                     "testEntryType" => assert!(t.can_publish(&context)),
                     "testEntryTypeC" => {
@@ -101,4 +132,19 @@ pub mod tests {
             }
         }
     }
+
+    #[test]
+    fn validate_link_visibility_test() {
+        let dna = create_arbitrary_test_dna();
+        let spoofed_dna_address: Address = dna.address();
+        let (_instance, context) =
+            test_instance_with_spoofed_dna(dna, spoofed_dna_address, "test").unwrap();
+        let public_entry = Entry::App("testEntryType".into(), RawString::from("public").into());
+        let private_entry = Entry::App("testEntryTypeC".into(), RawString::from("private").into());
+
+        assert!(validate_link_visibility(&public_entry, &public_entry, &context).is_ok());
+        assert!(validate_link_visibility(&private_entry, &public_entry, &context).is_ok());
+        assert!(validate_link_visibility(&private_entry, &private_entry, &context).is_ok());
+        assert!(validate_link_visibility(&public_entry, &private_entry, &context).is_err());
+    }
 }
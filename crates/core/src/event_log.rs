@@ -0,0 +1,235 @@
+//! Tamper-evident log of significant events, for deployments that need operational records.
+//!
+//! Once enabled with [enable_event_log], commits, held aspects, failed validations, uses of
+//! capability grants and admin operations get appended to the log file as one JSON object
+//! per line. Every entry carries the hash of the entry before it and a hash over its own
+//! contents, so changing, removing or reordering entries breaks the chain, which
+//! [verify_event_log] finds. The file is only ever opened for appending, and an existing log
+//! gets continued where it ended.
+use crate::context::Context;
+use chrono::{DateTime, Utc};
+use holochain_core_types::error::HolochainError;
+use holochain_locksmith::Mutex;
+use holochain_persistence_api::hash::HashString;
+use lazy_static::lazy_static;
+use multihash::Hash;
+use serde_json::Value;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::Path,
+    time::SystemTime,
+};
+
+/// Kinds of events that get logged
+pub const EVENT_COMMIT: &str = "commit";
+pub const EVENT_HOLD: &str = "hold";
+pub const EVENT_VALIDATION_FAILED: &str = "validation_failed";
+pub const EVENT_CAPABILITY_USE: &str = "capability_use";
+pub const EVENT_ADMIN: &str = "admin";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EventLogEntry {
+    /// Position in the log, starting at 0
+    pub seq: u64,
+    pub timestamp: String,
+    pub kind: String,
+    /// Instance the event happened in, none for conductor events like admin operations
+    pub instance_id: Option<String>,
+    pub details: Value,
+    /// Hash of the entry before, empty for the first one
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl EventLogEntry {
+    /// The hash over everything but the hash itself
+    fn content_hash(&self) -> Result<String, HolochainError> {
+        let content = serde_json::to_string(&(
+            self.seq,
+            &self.timestamp,
+            &self.kind,
+            &self.instance_id,
+            &self.details,
+            &self.prev_hash,
+        ))?;
+        Ok(HashString::encode_from_str(&content, Hash::SHA2256).to_string())
+    }
+}
+
+struct EventLog {
+    file: File,
+    next_seq: u64,
+    last_hash: String,
+}
+
+lazy_static! {
+    static ref EVENT_LOG: Mutex<Option<EventLog>> = Mutex::new(None);
+}
+
+/// Starts appending events to the file at `path`, creating it if needed.
+/// Fails if the file holds a log whose chain is broken.
+pub fn enable_event_log(path: &Path) -> Result<(), HolochainError> {
+    let (next_seq, last_hash) = if path.exists() {
+        match read_event_log(path)?.last() {
+            Some(last) => (last.seq + 1, last.hash.clone()),
+            None => (0, String::new()),
+        }
+    } else {
+        (0, String::new())
+    };
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *EVENT_LOG.lock().unwrap() = Some(EventLog {
+        file,
+        next_seq,
+        last_hash,
+    });
+    Ok(())
+}
+
+pub fn disable_event_log() {
+    *EVENT_LOG.lock().unwrap() = None;
+}
+
+pub fn event_log_enabled() -> bool {
+    EVENT_LOG.lock().unwrap().is_some()
+}
+
+/// Appends an event that happened at `time` to the log, if it is enabled.
+/// The log only moves on to the next entry once this one got written, so after a failed
+/// write the next event continues the chain where it ended.
+pub fn record_event(
+    time: SystemTime,
+    kind: &str,
+    instance_id: Option<String>,
+    details: Value,
+) -> Result<(), HolochainError> {
+    let mut event_log = EVENT_LOG.lock().unwrap();
+    if let Some(ref mut log) = *event_log {
+        let mut entry = EventLogEntry {
+            seq: log.next_seq,
+            timestamp: DateTime::<Utc>::from(time).to_rfc3339(),
+            kind: kind.to_string(),
+            instance_id,
+            details,
+            prev_hash: log.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.content_hash()?;
+        let line = serde_json::to_string(&entry)?;
+        writeln!(log.file, "{}", line).and_then(|_| log.file.flush())?;
+        log.next_seq += 1;
+        log.last_hash = entry.hash;
+    }
+    Ok(())
+}
+
+/// Appends an event of the instance of the given context to the log, if it is enabled,
+/// at the time of the context's clock.
+pub fn record_instance_event(
+    context: &Context,
+    kind: &str,
+    details: Value,
+) -> Result<(), HolochainError> {
+    record_event(
+        context.clock.now(),
+        kind,
+        Some(context.get_instance_name()),
+        details,
+    )
+}
+
+/// Reads the whole log and checks that its hash chain is intact.
+pub fn read_event_log(path: &Path) -> Result<Vec<EventLogEntry>, HolochainError> {
+    let mut entries: Vec<EventLogEntry> = Vec::new();
+    for (line_number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let broken = |reason: &str| {
+            HolochainError::ErrorGeneric(format!(
+                "Event log {} is broken at line {}: {}",
+                path.display(),
+                line_number + 1,
+                reason
+            ))
+        };
+        let entry: EventLogEntry =
+            serde_json::from_str(line).map_err(|e| broken(&e.to_string()))?;
+        let (expected_seq, expected_prev_hash) = entries
+            .last()
+            .map(|last| (last.seq + 1, last.hash.clone()))
+            .unwrap_or((0, String::new()));
+        if entry.seq != expected_seq {
+            return Err(broken(&format!(
+                "expected entry {} but found {}",
+                expected_seq, entry.seq
+            )));
+        }
+        if entry.prev_hash != expected_prev_hash {
+            return Err(broken("it does not follow the entry before"));
+        }
+        if entry.hash != entry.content_hash()? {
+            return Err(broken("its contents do not match its hash"));
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Checks the hash chain of the log and returns how many entries it has.
+pub fn verify_event_log(path: &Path) -> Result<usize, HolochainError> {
+    read_event_log(path).map(|entries| entries.len())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn events_form_a_verifiable_chain() {
+        let path = std::env::temp_dir().join("holochain_core_event_log_test");
+        let _ = fs::remove_file(&path);
+        enable_event_log(&path).unwrap();
+        let now = SystemTime::now();
+        record_event(
+            now,
+            EVENT_ADMIN,
+            None,
+            json!({"method": "admin/dna/install_from_file"}),
+        )
+        .unwrap();
+        record_event(
+            now,
+            EVENT_COMMIT,
+            Some("app".into()),
+            json!({"address": "Qm1"}),
+        )
+        .unwrap();
+
+        // Continues the existing log
+        enable_event_log(&path).unwrap();
+        record_event(
+            now,
+            EVENT_HOLD,
+            Some("app".into()),
+            json!({"address": "Qm2"}),
+        )
+        .unwrap();
+        disable_event_log();
+
+        let entries = read_event_log(&path).unwrap();
+        // Other tests might have recorded events while the log was enabled
+        assert!(entries.len() >= 3);
+        assert!(entries
+            .iter()
+            .any(|e| e.kind == EVENT_HOLD && e.details["address"] == "Qm2"));
+        assert_eq!(verify_event_log(&path).unwrap(), entries.len());
+
+        // Tampering with an entry breaks the chain
+        let tampered = fs::read_to_string(&path).unwrap().replace("Qm1", "Qm3");
+        fs::write(&path, tampered).unwrap();
+        assert!(verify_event_log(&path).is_err());
+    }
+}
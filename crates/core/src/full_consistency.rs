@@ -0,0 +1,160 @@
+//! Waiting in tests until a set of instances is fully consistent.
+//!
+//! A [FullConsistencyWaiter] looks at the state of the instances instead of their signals: the
+//! instances are fully consistent once every aspect that one of them published is held by all
+//! of them and none of them has holding workflows queued or running. Tests wait for that in
+//! place of sleeping for a fixed time after they commit, and get told what is still missing
+//! if it doesn't happen in time.
+//!
+//! Instances only receive what gets published while they are on the network, so aspects that
+//! were published before the waiter got created don't count.
+use crate::{context::Context, network::handler::lists::create_authoring_map};
+use holochain_core_types::error::{HcResult, HolochainError};
+use holochain_net::aspect_map::AspectMap;
+use holochain_persistence_api::cas::content::AddressableContent;
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct FullConsistencyWaiter {
+    contexts: Vec<Arc<Context>>,
+    published_before: AspectMap,
+}
+
+impl FullConsistencyWaiter {
+    /// Starts watching the instances of the given contexts, which have to be initialized
+    pub fn new(contexts: &[Arc<Context>]) -> Self {
+        let mut waiter = FullConsistencyWaiter {
+            contexts: contexts.to_vec(),
+            published_before: AspectMap::new(),
+        };
+        waiter.published_before = waiter.published();
+        waiter
+    }
+
+    fn published(&self) -> AspectMap {
+        self.contexts.iter().fold(AspectMap::new(), |map, context| {
+            AspectMap::merge(&map, &create_authoring_map(context.clone()))
+        })
+    }
+
+    /// What keeps the instances from being fully consistent, one line per problem
+    pub fn inconsistencies(&self) -> Vec<String> {
+        let published = self.published().diff(&self.published_before);
+        let mut inconsistencies = Vec::new();
+        for context in self.contexts.iter() {
+            let name = context.get_instance_name();
+            let state = match context.state() {
+                Some(state) => state,
+                None => {
+                    inconsistencies.push(format!("{} has no state", name));
+                    continue;
+                }
+            };
+            let dht = state.dht();
+            let missing = published.diff(dht.get_holding_map());
+            if !missing.empty() {
+                inconsistencies.push(format!(
+                    "{} does not hold {} aspects:\n{}",
+                    name,
+                    missing.aspect_hashes().len(),
+                    missing.pretty_string()
+                ));
+            }
+            let holding: Vec<String> = dht
+                .queued_holding_workflows()
+                .iter()
+                .map(|queued| ("queued", queued))
+                .chain(
+                    dht.in_process_holding_workflows()
+                        .iter()
+                        .map(|running| ("running", running)),
+                )
+                .map(|(status, item)| {
+                    format!(
+                        "{} {} of {}",
+                        status,
+                        item.pending.workflow,
+                        item.pending.entry_with_header.entry.address()
+                    )
+                })
+                .collect();
+            if !holding.is_empty() {
+                inconsistencies.push(format!("{} is still holding: {}", name, holding.join(", ")));
+            }
+        }
+        inconsistencies
+    }
+
+    /// Blocks until the instances are fully consistent, or fails with what is still missing
+    /// once the timeout is over
+    pub fn wait(&self, timeout: Duration) -> HcResult<()> {
+        let started = Instant::now();
+        loop {
+            let inconsistencies = self.inconsistencies();
+            if inconsistencies.is_empty() {
+                return Ok(());
+            }
+            if started.elapsed() > timeout {
+                return Err(HolochainError::Timeout(format!(
+                    "No full consistency after {:?}:\n{}",
+                    timeout,
+                    inconsistencies.join("\n")
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        nucleus::actions::tests::{instance_by_name, test_dna},
+        workflows::author_entry::author_entry,
+    };
+    use holochain_core_types::entry::test_entry_with_value;
+
+    #[test]
+    fn times_out_with_what_is_missing() {
+        let dna = test_dna();
+        // Instances on separate networks never get what the other one publishes
+        let (_instance1, context1) = instance_by_name(
+            "jill",
+            dna.clone(),
+            Some("times_out_with_what_is_missing, jill's network"),
+        );
+        let (_instance2, context2) = instance_by_name(
+            "jack",
+            dna,
+            Some("times_out_with_what_is_missing, jack's network"),
+        );
+        let waiter = FullConsistencyWaiter::new(&[context1.clone(), context2]);
+
+        let entry_address = context1
+            .block_on(author_entry(
+                &test_entry_with_value("{\"stuff\":\"test entry value\"}"),
+                None,
+                &context1,
+                &vec![],
+            ))
+            .unwrap()
+            .address();
+
+        let timeout = Duration::from_millis(500);
+        let started = Instant::now();
+        let diagnostic = match waiter.wait(timeout) {
+            Err(HolochainError::Timeout(diagnostic)) => diagnostic,
+            other => panic!("expected a timeout, got {:?}", other),
+        };
+        assert!(started.elapsed() >= timeout);
+        assert!(diagnostic.starts_with(&format!("No full consistency after {:?}:\n", timeout)));
+        assert!(diagnostic.contains("does not hold"));
+        assert!(diagnostic.contains(&entry_address.to_string()));
+    }
+}
@@ -0,0 +1,134 @@
+//! Entry points for fuzzing how core handles input from other nodes.
+//!
+//! The cargo-fuzz targets in `crates/core/fuzz` feed arbitrary bytes through these functions.
+//! Each of them deserializes the bytes as the network does, from JSON, and hands whatever
+//! parses to the code that handles such input in a running instance. Input that doesn't parse
+//! gets dropped like the network handler drops it.
+//!
+//! Network messages only get past the handler's checks if they are addressed to the space of
+//! the instance's DNA, so corpora for [FuzzTarget::handle_network_message] should start from
+//! messages with [FuzzTarget::dna_address] as their space address.
+use crate::{
+    context::Context,
+    network::{
+        direct_message::DirectMessage,
+        handler::{create_handler, fetch::handle_fetch_entry, parse_content},
+        query::{NetworkQuery, NetworkQueryResult},
+    },
+    nucleus::validation::{validate_entry, ValidationContext, ValidationResult},
+    test_harness::TestAgent,
+};
+use holochain_core_types::{
+    dna::Dna, entry::Entry, error::HcResult, network::entry_aspect::EntryAspect,
+    validation::ValidationData,
+};
+use holochain_json_api::error::JsonError;
+use holochain_net::connection::net_connection::NetHandler;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use lib3h_protocol::{data_types::FetchEntryData, protocol_server::Lib3hServerProtocol};
+use std::sync::Arc;
+
+/// What [FuzzTarget::validate_entry] takes, as JSON
+#[derive(Clone, Debug, Deserialize)]
+pub struct ValidationInput {
+    pub entry: Entry,
+    #[serde(default)]
+    pub link: Option<Address>,
+    pub validation_data: ValidationData,
+}
+
+/// An instance of a DNA that fuzz targets feed their input to
+pub struct FuzzTarget {
+    agent: TestAgent,
+    handler: NetHandler,
+    dna_address: Address,
+}
+
+impl FuzzTarget {
+    /// Starts an instance of the DNA on an in-memory network of its own. The DNA is used as
+    /// given, so that its address is the same in every run.
+    pub fn new(dna: Dna) -> HcResult<Self> {
+        let dna_address = dna.address();
+        let agent = TestAgent::new(dna, "fuzz", "fuzz")?;
+        let handler = create_handler(&agent.context, dna_address.to_string());
+        Ok(FuzzTarget {
+            agent,
+            handler,
+            dna_address,
+        })
+    }
+
+    pub fn context(&self) -> &Arc<Context> {
+        &self.agent.context
+    }
+
+    pub fn dna_address(&self) -> &Address {
+        &self.dna_address
+    }
+
+    /// Validates an entry as the holding workflows do with entries that other nodes publish.
+    /// Returns None if the input doesn't parse.
+    pub fn validate_entry(&self, data: &[u8]) -> Option<ValidationResult> {
+        let input: ValidationInput = serde_json::from_slice(data).ok()?;
+        let context = self.context();
+        Some(context.block_on(validate_entry(
+            input.entry,
+            input.link,
+            input.validation_data,
+            context,
+            ValidationContext::Holding,
+        )))
+    }
+
+    /// Answers a fetch request of the network
+    pub fn handle_fetch_entry(&self, data: &[u8]) {
+        if let Ok(fetch_entry_data) = serde_json::from_slice::<FetchEntryData>(data) {
+            handle_fetch_entry(fetch_entry_data, self.context().clone());
+        }
+    }
+
+    /// Hands a message to the network handler of the instance, as if the network had
+    /// delivered it
+    pub fn handle_network_message(&mut self, data: &[u8]) {
+        if let Ok(message) = serde_json::from_slice::<Lib3hServerProtocol>(data) {
+            let wrapped = ht::with_top_or_null(|s| s.child("fuzz")).wrap(message);
+            if let Err(error) = self.handler.handle(Ok(wrapped.into())) {
+                log_debug!(
+                    self.context(),
+                    "fuzzing: network handler returned error: {:?}",
+                    error
+                );
+            }
+        }
+    }
+}
+
+/// Runs the deserializers for the content that nodes put into the byte fields of network
+/// messages: entry aspects, direct messages, queries and query results
+pub fn parse_message_content(data: &[u8]) {
+    let _: Result<EntryAspect, JsonError> = parse_content(data);
+    let _: Result<DirectMessage, JsonError> = parse_content(data);
+    let _: Result<NetworkQuery, JsonError> = parse_content(data);
+    let _: Result<NetworkQueryResult, JsonError> = parse_content(data);
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::network::test_utils::test_wat_always_valid;
+    use holochain_core_types::entry::test_entry;
+    use test_utils::create_test_dna_with_wat;
+
+    #[test]
+    fn malformed_input_is_dropped() {
+        let dna = create_test_dna_with_wat("test_zome", Some(&test_wat_always_valid()));
+        let mut target = FuzzTarget::new(dna).unwrap();
+        let entry = serde_json::to_vec(&test_entry()).unwrap();
+        for data in [&b"\xff\xfe"[..], b"{}", b"null", &entry[..]].iter() {
+            assert!(target.validate_entry(data).is_none());
+            target.handle_fetch_entry(data);
+            target.handle_network_message(data);
+            parse_message_content(data);
+        }
+    }
+}
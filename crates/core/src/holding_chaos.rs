@@ -0,0 +1,85 @@
+//! Chaos mode of the holding queue, for tests.
+//!
+//! The holding loop attempts queued holding workflows in the order they got queued. Code that
+//! silently relies on that order, like holding a link only after its base, works in tests and
+//! fails rarely in the wild, where entries arrive in any order. With a [HoldingChaos] on the
+//! context, the holding loop picks a random one of the workflows that are ready instead, which
+//! are those whose dependencies aren't queued themselves and whose retry delay is over. So
+//! dependencies still get held first.
+//!
+//! The picks only depend on the seed, so a test that fails with a seed can be rerun with it.
+//! Test contexts get the seed from the `HC_HOLDING_CHAOS_SEED` environment variable:
+//!
+//! ```shell
+//! HC_HOLDING_CHAOS_SEED=42 cargo test -p holochain_core
+//! ```
+use holochain_locksmith::Mutex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{env, fmt};
+
+/// Environment variable that switches test contexts to chaos mode, with its value as seed
+pub const HOLDING_CHAOS_SEED_VAR: &str = "HC_HOLDING_CHAOS_SEED";
+
+pub struct HoldingChaos {
+    seed: u64,
+    rng: Mutex<StdRng>,
+}
+
+impl HoldingChaos {
+    pub fn new(seed: u64) -> Self {
+        HoldingChaos {
+            seed,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    /// Chaos with the seed that `HC_HOLDING_CHAOS_SEED` is set to, if it is set
+    pub fn from_env() -> Option<Self> {
+        env::var(HOLDING_CHAOS_SEED_VAR).ok().map(|seed| {
+            let seed = seed.parse().unwrap_or_else(|_| {
+                panic!("{} must be a number, not {}", HOLDING_CHAOS_SEED_VAR, seed)
+            });
+            HoldingChaos::new(seed)
+        })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Takes a random one of the candidates
+    pub fn pick<T>(&self, mut candidates: Vec<T>) -> Option<T> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let index = self.rng.lock().unwrap().gen_range(0, candidates.len());
+        Some(candidates.swap_remove(index))
+    }
+}
+
+impl fmt::Debug for HoldingChaos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HoldingChaos")
+            .field("seed", &self.seed)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_depend_only_on_the_seed() {
+        let picks = |chaos: &HoldingChaos| -> Vec<Option<usize>> {
+            (0..20).map(|_| chaos.pick((0..5).collect())).collect()
+        };
+        let first = picks(&HoldingChaos::new(7));
+        assert_eq!(first, picks(&HoldingChaos::new(7)));
+        assert_ne!(first, picks(&HoldingChaos::new(8)));
+        for candidate in 0..5 {
+            assert!(first.contains(&Some(candidate)));
+        }
+        assert_eq!(HoldingChaos::new(7).pick(Vec::<usize>::new()), None);
+    }
+}
@@ -0,0 +1,113 @@
+//! The generator that an instance draws the ids of its requests from.
+//!
+//! Zome calls, validation package requests, network queries and the futures that wait on
+//! commits, publishes and holding all get their ids from the [SharedIdGenerator] of the
+//! instance's context instead of calling `ProcessUniqueId::new()` directly. Instances use the
+//! [ProcessIdGenerator], while recorded scenarios and golden-output tests can put in a
+//! [SequentialIdGenerator], so that the ids in their output are the same in every run.
+use serde_json::json;
+use snowflake::ProcessUniqueId;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+pub trait IdGenerator: Send + Sync {
+    fn next_id(&self) -> ProcessUniqueId;
+}
+
+/// Generates ids that are unique within the process
+#[derive(Clone, Debug, Default)]
+pub struct ProcessIdGenerator;
+
+impl IdGenerator for ProcessIdGenerator {
+    fn next_id(&self) -> ProcessUniqueId {
+        ProcessUniqueId::new()
+    }
+}
+
+/// Counts up from a given number. Clones share the same count.
+///
+/// The ids only depend on the order they get generated in, so they are the same in every run
+/// of a test that makes its requests in the same order. They can't collide with ids from
+/// `ProcessUniqueId::new()`, but two sequential generators that are used side by side for
+/// the same instance would generate the same ids.
+#[derive(Clone, Debug, Default)]
+pub struct SequentialIdGenerator {
+    next: Arc<AtomicU64>,
+}
+
+/// Prefix of the sequential ids. `ProcessUniqueId::new()` counts its prefixes up from 0,
+/// so it never gets to this one.
+const SEQUENTIAL_PREFIX: usize = usize::max_value();
+
+impl SequentialIdGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn starting_at(first: u64) -> Self {
+        SequentialIdGenerator {
+            next: Arc::new(AtomicU64::new(first)),
+        }
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn next_id(&self) -> ProcessUniqueId {
+        let offset = self.next.fetch_add(1, Ordering::SeqCst);
+        // ProcessUniqueId has no constructor that takes its parts, but deserializes from them
+        serde_json::from_value(json!({ "prefix": SEQUENTIAL_PREFIX, "offset": offset }))
+            .expect("ProcessUniqueId must deserialize from its prefix and offset")
+    }
+}
+
+/// The id generator of an instance, shared by everything that runs for it
+#[derive(Clone)]
+pub struct SharedIdGenerator(Arc<dyn IdGenerator>);
+
+impl SharedIdGenerator {
+    pub fn new<G: IdGenerator + 'static>(generator: G) -> Self {
+        SharedIdGenerator(Arc::new(generator))
+    }
+
+    pub fn process() -> Self {
+        Self::new(ProcessIdGenerator)
+    }
+
+    pub fn next_id(&self) -> ProcessUniqueId {
+        self.0.next_id()
+    }
+}
+
+impl Default for SharedIdGenerator {
+    fn default() -> Self {
+        Self::process()
+    }
+}
+
+impl fmt::Debug for SharedIdGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SharedIdGenerator").finish()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn sequential_ids_repeat_across_generators() {
+        let first = SharedIdGenerator::new(SequentialIdGenerator::new());
+        let second = SharedIdGenerator::new(SequentialIdGenerator::new());
+        let ids: Vec<ProcessUniqueId> = (0..3).map(|_| first.next_id()).collect();
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[1], ids[2]);
+        assert_eq!(ids, (0..3).map(|_| second.next_id()).collect::<Vec<_>>());
+        assert_eq!(SequentialIdGenerator::starting_at(1).next_id(), ids[1]);
+        assert!(!ids.contains(&SharedIdGenerator::process().next_id()));
+    }
+}
@@ -1,10 +1,13 @@
 use crate::{
     action::{Action, ActionWrapper},
+    causal_log::variant_name,
     consistency::ConsistencyModel,
+    content_store::recover_write_batch,
     context::{ActionReceiver, ActionSender, Context},
     dht::actions::remove_queued_holding_workflow::{
         remove_queued_holding_workflow, HoldingWorkflowQueueing,
     },
+    instance_pause::InstancePause,
     network,
     persister::Persister,
     scheduled_jobs,
@@ -12,6 +15,7 @@ use crate::{
     state::{State, StateWrapper},
     state_dump::DumpOptions,
     workflows::{application, run_holding_workflow},
+    write_ahead_log,
 };
 #[cfg(test)]
 use crate::{
@@ -33,7 +37,10 @@ use snowflake::ProcessUniqueId;
 use std::{
     collections::HashMap,
     sync::{
-        atomic::Ordering::{self, Relaxed},
+        atomic::{
+            AtomicBool,
+            Ordering::{self, Relaxed},
+        },
         Arc,
     },
     thread,
@@ -43,6 +50,16 @@ use std::{
 pub const RETRY_VALIDATION_DURATION_MIN: Duration = Duration::from_millis(15000);
 pub const RETRY_VALIDATION_DURATION_MAX: Duration = Duration::from_secs(60 * 60);
 
+/// Marks the instance as not alive anymore when the action loop ends, also if it ends by
+/// panicking, so that the conductor can tell the loop died.
+struct ActionLoopAlive(Arc<AtomicBool>);
+
+impl Drop for ActionLoopAlive {
+    fn drop(&mut self) {
+        self.0.store(false, Relaxed);
+    }
+}
+
 pub enum WakerRequest {
     Add(ProcessUniqueId, Waker),
     Remove(ProcessUniqueId),
@@ -86,19 +103,37 @@ impl Instance {
                 context.clone(),
                 DumpOptions {
                     include_eavis: false,
+                    include_storage_usage: false,
                 },
             ));
         scheduler
             .every(1.second())
             .run(scheduled_jobs::create_timeout_callback(context.clone()));
+        scheduler
+            .every(1.second())
+            .run(scheduled_jobs::create_slow_zome_call_callback(
+                context.clone(),
+            ));
         scheduler
             .every(30.seconds())
             .run(scheduled_jobs::create_state_pruning_callback(
                 context.clone(),
             ));
+        scheduler
+            .every(1.hour())
+            .run(scheduled_jobs::create_storage_compaction_callback(
+                context.clone(),
+            ));
+        scheduler
+            .every(1.day())
+            .run(scheduled_jobs::create_storage_scrub_callback(
+                context.clone(),
+            ));
         self.scheduler_handle = Some(Arc::new(scheduler.watch_thread(Duration::from_millis(10))));
 
         self.persister = Some(context.persister.clone());
+        self.recover_write_batches(&context);
+        self.replay_write_ahead_log(&context);
 
         self.start_action_loop(context.clone(), rx_action, rx_observer, rx_waker);
         self.start_holding_loop(context.clone());
@@ -118,6 +153,22 @@ impl Instance {
         context.block_on(application::initialize(self, dna, context.clone()))
     }
 
+    /// Like initialize(None, ..) for loading a chain, but with the DNA the instance is
+    /// configured with. If that differs from the DNA found in the loaded chain, the chain gets
+    /// migrated to it before the network is started.
+    pub fn initialize_with_dna_update(
+        &mut self,
+        dna: Dna,
+        context: Arc<Context>,
+    ) -> HcResult<Arc<Context>> {
+        let context = self.inner_setup(context);
+        context.block_on(application::initialize_with_dna_update(
+            self,
+            dna,
+            context.clone(),
+        ))
+    }
+
     /// This function is only needed in tests to create integration tests in which an instance
     /// tries to publish invalid entries.
     /// The DNA needs to be spoofed then so that we can emulate a hacked node that does not
@@ -217,6 +268,7 @@ impl Instance {
                 ProcessUniqueId::new().to_string()
             ))
             .spawn(move || {
+                let _alive = ActionLoopAlive(instance_is_alive);
                 let mut state_observers: Vec<Observer> = Vec::new();
                 let mut unprocessed_action: Option<ht::SpanWrap<ActionWrapper>> = None;
                 let mut wakers: HashMap::<ProcessUniqueId, Waker> = HashMap::new();
@@ -266,7 +318,6 @@ impl Instance {
                         }
                     }
                 }
-                instance_is_alive.store(false, Relaxed);
             });
     }
 
@@ -297,6 +348,9 @@ impl Instance {
                     .into()
             });
         let _trace_guard = ht::push_span(span);
+        let _frame = context
+            .profiler
+            .frame(|| format!("action {}", variant_name(action_wrapper.data.action())));
         context.redux_wants_write.store(true, Relaxed);
         // Mutate state
         {
@@ -310,8 +364,44 @@ impl Instance {
                     HolochainError::Timeout(format!("timeout src: {}:{}", file!(), line!()))
                 })?;
 
+            // Storage writes of the reducers only become consistent with the state once it is
+            // saved, so actions with such writes get journaled for replay after a crash.
+            // An action that can't be journaled still gets reduced, so that whoever waits for
+            // its response gets one. It just can't be replayed if we crash before saving.
+            let journaled = match write_ahead_log::journal_action(
+                context,
+                action_wrapper.data.action(),
+                &state,
+            ) {
+                Ok(journaled) => journaled,
+                Err(e) => {
+                    log_error!(
+                        context,
+                        "instance/process_action: could not journal action, reducing it without journal entry: {:?}",
+                        e
+                    );
+                    false
+                }
+            };
+
+            if let Err(e) = context
+                .action_recorder
+                .record(context.clock.now(), &action_wrapper.data)
+            {
+                log_error!(
+                    context,
+                    "instance/process_action: could not record action: {:?}",
+                    e
+                );
+            }
+
             new_state = state.reduce(action_wrapper.data.clone());
 
+            // Recorded before the state changes, so that whoever sees the change finds the action
+            context
+                .causal_log
+                .record_action(context.clock.iso8601_now(), &action_wrapper.data);
+
             // Change the state
             *state = new_state;
 
@@ -322,6 +412,15 @@ impl Instance {
                     e
                 );
             } else {
+                if journaled {
+                    if let Err(e) = write_ahead_log::clear_journal(context) {
+                        log_error!(
+                            context,
+                            "instance/process_action: could not clear journal: {:?}",
+                            e
+                        );
+                    }
+                }
                 log_trace!(
                     context,
                     "reduce/process_actions: reducing {:?}",
@@ -335,6 +434,50 @@ impl Instance {
         Ok(())
     }
 
+    /// Completes the storage write batches an earlier run didn't get to finish committing.
+    /// The DHT storage goes first, with its EAV storage, in case the chain shares it.
+    fn recover_write_batches(&self, context: &Arc<Context>) {
+        for (storage, meta_storage) in &[
+            (&context.dht_storage, Some(&context.eav_storage)),
+            (&context.chain_storage, None),
+        ] {
+            match recover_write_batch(storage, *meta_storage, &context.dna_storage) {
+                Ok(true) => log_warn!(context, "instance: completed an interrupted write batch"),
+                Ok(false) => (),
+                Err(e) => log_error!(
+                    context,
+                    "instance: could not complete interrupted write batch: {:?}",
+                    e
+                ),
+            }
+        }
+    }
+
+    /// Reduces the action an earlier run journaled but didn't get to save the state of,
+    /// unless the loaded state shows it got applied after all.
+    fn replay_write_ahead_log(&self, context: &Arc<Context>) {
+        match write_ahead_log::action_to_replay(context, &self.state()) {
+            Ok(Some(action_wrapper)) => {
+                log_warn!(
+                    context,
+                    "instance: replaying unfinished action from write-ahead log: {:?}",
+                    action_wrapper.action()
+                );
+                let action_wrapper =
+                    ht::top_follower("replay_write_ahead_log").wrap(action_wrapper);
+                if let Err(e) = self.process_action(&action_wrapper, context) {
+                    log_error!(context, "instance: could not replay action: {:?}", e);
+                }
+            }
+            Ok(None) => {
+                if let Err(e) = write_ahead_log::clear_journal(context) {
+                    log_error!(context, "instance: could not clear journal: {:?}", e);
+                }
+            }
+            Err(e) => log_error!(context, "instance: could not read journal: {:?}", e),
+        }
+    }
+
     fn start_holding_loop(&mut self, context: Arc<Context>) {
         let (kill_sender, kill_receiver) = crossbeam_channel::unbounded();
         self.kill_switch_holding = Some(kill_sender);
@@ -347,6 +490,11 @@ impl Instance {
                 while kill_receiver.try_recv().is_err() {
                     log_trace!(context, "Checking holding queue...");
                     loop {
+                        // Leave the queue alone while the instance is paused
+                        let work = match InstancePause::begin_work(&context.instance_pause) {
+                            Ok(work) => work,
+                            Err(_) => break,
+                        };
                         // TODO: TRACING: it would be ideal to be able to associate a tracing Span with each queued holding workflow.
                         // To do this, we'd need to store a Span in each item of the DhtStore::queued_holding_workflows.
                         // However, Span is not Clone, and the entire DhtStore needs to be Cloned.
@@ -356,9 +504,18 @@ impl Instance {
                             .state()
                             .expect("Couldn't get state in run_pending_validations")
                             .dht();
-                        let maybe_holding_workflow = dht_store.next_queued_holding_workflow();
-                        if let Some((pending, maybe_delay)) = maybe_holding_workflow {
+                        let maybe_holding_workflow = match context.holding_chaos {
+                            Some(ref chaos) => {
+                                chaos.pick(dht_store.ready_queued_holding_workflows())
+                            }
+                            None => dht_store.next_queued_holding_workflow(),
+                        };
+                        if let Some((pending, _)) = maybe_holding_workflow {
                             log_debug!(context, "Found queued validation: {:?}", pending);
+                            let (retries, queued_at) = dht_store
+                                .queued_holding_workflow(&pending)
+                                .map(|queued| (queued.retries, queued.queued_at))
+                                .unwrap_or((0, None));
                             // NB: If for whatever reason we pop_next_holding_workflow anywhere else other than here,
                             // we can run into a race condition.
                             context.block_on(remove_queued_holding_workflow(
@@ -373,31 +530,29 @@ impl Instance {
                             let closure = async move || {
                                 let queuing = match run_holding_workflow(pending.clone(), c.clone()).await {
                                     // If we couldn't run the validation due to unresolved dependencies,
-                                    // we have to re-add this entry at the end of the queue:
+                                    // we have to try again later, unless we tried long enough:
                                     Err(HolochainError::ValidationPending) => {
-                                        // And with a delay so we are not trying to re-validate too often for nodes that have gone offline.
-                                        let mut delay = maybe_delay
-                                            .map(|old_delay| {
-                                                // Exponential back-off:
-                                                // If this was delayed before we double the delay.
-                                                old_delay * 2
-                                            })
-                                            .unwrap_or(RETRY_VALIDATION_DURATION_MIN);
-
-                                        // Cap delay with max duration
-                                        if delay > RETRY_VALIDATION_DURATION_MAX {
-                                            delay = RETRY_VALIDATION_DURATION_MAX
+                                        let config = &c.pending_validation_config;
+                                        let queued_for = queued_at
+                                            .and_then(|queued_at| c.clock.elapsed(queued_at).ok())
+                                            .unwrap_or_default();
+                                        if config.is_exhausted(retries, queued_for) {
+                                            // Its dependencies might never arrive, so we give up on it
+                                            log_warn!(
+                                                c,
+                                                "giving up on pending validation for {:?} after {} retries and {:?}",
+                                                pending,
+                                                retries,
+                                                queued_for,
+                                            );
+                                            HoldingWorkflowQueueing::DeadLetter
+                                        } else {
+                                            // Otherwise we re-add it at the end of the queue with a delay,
+                                            // so we are not trying to re-validate too often for nodes that have gone offline.
+                                            let delay = config.backoff(retries + 1);
+                                            log_debug!(c, "re-queuing pending validation for {:?} with a delay of {:?}", pending, delay);
+                                            HoldingWorkflowQueueing::Waiting(delay)
                                         }
-                                        log_debug!(c, "re-queuing pending validation for {:?} with a delay of {:?}", pending, delay);
-                                        HoldingWorkflowQueueing::Waiting(delay)
-
-
-/*                                        queue_holding_workflow(
-                                            Arc::new(pending.same()),
-                                            Some(delay),
-                                            c.clone(),
-                                        )
-                                        .await*/
                                     }
                                     Err(e) => {
                                         log_error!(
@@ -420,7 +575,10 @@ impl Instance {
                                 ).await
                             };
                             let future = closure();
-                            context.spawn_task(future);
+                            context.spawn_task(async move {
+                                let _work = work;
+                                future.await
+                            });
                         } else {
                             break;
                         }
@@ -553,6 +711,7 @@ pub mod tests {
             state::{AgentActionResponse, AgentState},
         },
         context::{test_memory_network_config, Context},
+        holding_chaos::HoldingChaos,
         logger::{test_logger, TestLogger},
     };
     use holochain_core_types::{
@@ -562,16 +721,24 @@ pub mod tests {
         entry::{entry_type::EntryType, test_entry},
     };
     use holochain_locksmith::{Mutex, RwLock};
-    use holochain_persistence_api::cas::content::AddressableContent;
+    use holochain_persistence_api::{
+        cas::{
+            content::{Address, AddressableContent, Content},
+            storage::ContentAddressableStorage,
+        },
+        error::{PersistenceError, PersistenceResult},
+        reporting::ReportStorage,
+    };
     use holochain_persistence_file::{cas::file::FilesystemStorage, eav::file::EavFileStorage};
     use tempfile;
     use test_utils;
+    use uuid::Uuid;
 
     use crate::persister::SimplePersister;
 
     use std::{sync::Arc, thread::sleep, time::Duration};
 
-    use test_utils::mock_signing::registered_test_agent;
+    use test_utils::mock_signing::{mock_conductor_api, registered_test_agent};
 
     use crate::nucleus::state::NucleusStatus;
     use holochain_core_types::entry::Entry;
@@ -598,24 +765,22 @@ pub mod tests {
         let content_storage = Arc::new(RwLock::new(MemoryStorage::new()));
         let meta_storage = Arc::new(RwLock::new(EavMemoryStorage::new()));
         let logger = test_logger();
-        (
-            Arc::new(Context::new(
-                "Test-context-and-logger-instance",
-                agent,
-                Arc::new(RwLock::new(SimplePersister::new(content_storage.clone()))),
-                content_storage.clone(),
-                content_storage.clone(),
-                meta_storage,
-                test_memory_network_config(network_name),
-                None,
-                None,
-                false,
-                holochain_metrics::config::MetricPublisherConfig::default()
-                    .create_metric_publisher(),
-                Arc::new(ht::null_tracer()),
-            )),
-            logger,
-        )
+        let mut context = Context::new(
+            "Test-context-and-logger-instance",
+            agent,
+            Arc::new(RwLock::new(SimplePersister::new(content_storage.clone()))),
+            content_storage.clone(),
+            content_storage.clone(),
+            meta_storage,
+            test_memory_network_config(network_name),
+            None,
+            None,
+            false,
+            holochain_metrics::config::MetricPublisherConfig::default().create_metric_publisher(),
+            Arc::new(ht::null_tracer()),
+        );
+        context.holding_chaos = HoldingChaos::from_env().map(Arc::new);
+        (Arc::new(context), logger)
     }
 
     /// create a test context
@@ -859,6 +1024,107 @@ pub mod tests {
         );
     }
 
+    #[test]
+    /// An action that got journaled but never reduced and saved, like after a crash in the
+    /// middle of processing it, gets reduced when the instance replays its write-ahead log.
+    pub fn replays_journaled_action_after_crash() {
+        let netname = Some("replays_journaled_action_after_crash");
+        let mut instance = Instance::new(test_context("jason", netname));
+        let context = instance.initialize_context(test_context("jane", netname));
+        let _channels = instance.initialize_channels();
+
+        let action_wrapper = test_action_wrapper_commit();
+        assert!(write_ahead_log::journal_action(
+            &context,
+            action_wrapper.data.action(),
+            &instance.state()
+        )
+        .unwrap());
+        // The crash: the action never gets reduced and the journal stays behind
+
+        instance.replay_write_ahead_log(&context);
+        let top_header = instance
+            .state()
+            .agent()
+            .top_chain_header()
+            .expect("the journaled commit should have been replayed");
+        assert_eq!(top_header.entry_address(), &test_entry().address());
+        assert_eq!(write_ahead_log::unfinished_action(&context).unwrap(), None);
+
+        // Nothing is left to replay, so the entry doesn't get committed twice
+        instance.replay_write_ahead_log(&context);
+        assert_eq!(
+            instance.state().agent().top_chain_header(),
+            Some(top_header)
+        );
+    }
+
+    /// A CAS that refuses to store the write-ahead log
+    #[derive(Clone, Debug)]
+    struct JournalRefusingStorage {
+        inner: MemoryStorage,
+    }
+
+    impl ContentAddressableStorage for JournalRefusingStorage {
+        fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+            if content.address() == Address::from(write_ahead_log::WAL_ADDRESS) {
+                return Err(PersistenceError::ErrorGeneric("disk full".to_string()));
+            }
+            self.inner.add(content)
+        }
+
+        fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+            self.inner.contains(address)
+        }
+
+        fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+            self.inner.fetch(address)
+        }
+
+        fn get_id(&self) -> Uuid {
+            self.inner.get_id()
+        }
+    }
+
+    impl ReportStorage for JournalRefusingStorage {}
+
+    #[test]
+    /// An action that can't be journaled still gets reduced, so that whoever waits for it
+    /// gets its response instead of running into a timeout.
+    pub fn reduces_action_that_could_not_be_journaled() {
+        let netname = Some("reduces_action_that_could_not_be_journaled");
+        let mut instance = Instance::new(test_context("jason", netname));
+        let mut context = (*test_context("jane", netname)).clone();
+        context.dht_storage = Arc::new(RwLock::new(JournalRefusingStorage {
+            inner: MemoryStorage::new(),
+        }));
+        let context = instance.initialize_context(Arc::new(context));
+        let _channels = instance.initialize_channels();
+
+        let action_wrapper = test_action_wrapper_commit();
+        assert!(write_ahead_log::journal_action(
+            &context,
+            action_wrapper.data.action(),
+            &instance.state()
+        )
+        .is_err());
+
+        instance
+            .process_action(&action_wrapper, &context)
+            .expect("process_action should run without error");
+        let response = instance
+            .state()
+            .agent()
+            .actions()
+            .get(&action_wrapper)
+            .cloned()
+            .expect("the action should have been reduced");
+        assert_eq!(
+            response.response(),
+            &AgentActionResponse::Commit(Ok(test_entry().address()))
+        );
+    }
+
     #[test]
     /// tests that an unimplemented init allows the nucleus to initialize
     /// @TODO is this right? should return unimplemented?
@@ -932,6 +1198,50 @@ pub mod tests {
         );
     }
 
+    #[test]
+    /// tests that genesis fails before anything is committed if the agent key can't sign
+    /// for the agent's address
+    fn test_init_fails_with_mismatched_agent_key() {
+        let dna = test_utils::create_test_dna_with_wat("test_zome", None);
+        let context = test_context("jane", Some("mismatched_agent_key"));
+        context
+            .conductor_api
+            .reset(mock_conductor_api(registered_test_agent("mallory")));
+
+        let mut instance = Instance::new(context.clone());
+        let result = instance.initialize(Some(dna), context);
+        assert!(result.is_err());
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("Agent key verification failed"));
+        assert_eq!(instance.state().agent().iter_chain().count(), 0);
+    }
+
+    #[test]
+    /// tests that genesis fails before anything is committed if the conductor manages keys
+    /// with DPKI and the agent key isn't registered there
+    fn test_init_fails_with_agent_key_not_registered_in_dpki() {
+        let dna = test_utils::create_test_dna_with_wat("test_zome", None);
+        let context = test_context("jane", Some("agent_key_not_registered_in_dpki"));
+        let mut conductor_api = mock_conductor_api(context.agent_id.clone());
+        conductor_api.add_method("agent/is_key_registered", |_| {
+            Ok(serde_json::json!({ "registered": false }))
+        });
+        context.conductor_api.reset(conductor_api);
+
+        let mut instance = Instance::new(context.clone());
+        let result = instance.initialize(Some(dna), context);
+        assert!(result.is_err());
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("is not registered in DPKI"));
+        assert_eq!(instance.state().agent().iter_chain().count(), 0);
+    }
+
     /// Committing a DnaEntry to source chain should work
     #[test]
     fn can_commit_dna() {
@@ -0,0 +1,272 @@
+//! Counters of what an instance did since it got instantiated, for the conductor's
+//! statistics.
+//!
+//! Zome calls get counted by the conductor that makes them, and peers get recorded by the
+//! network handler whenever they send us a message. The major workflows time themselves with
+//! a [WorkflowTimer], which adds their latency to a histogram per workflow.
+use holochain_locksmith::Mutex;
+use holochain_metrics::prometheus::{self, HISTOGRAM_BUCKETS};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// The workflows whose latencies get recorded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TimedWorkflow {
+    Publish,
+    HoldEntry,
+    HoldLink,
+    GetEntry,
+    GetLinks,
+    ZomeCall,
+}
+
+impl fmt::Display for TimedWorkflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TimedWorkflow::Publish => "publish",
+            TimedWorkflow::HoldEntry => "hold_entry",
+            TimedWorkflow::HoldLink => "hold_link",
+            TimedWorkflow::GetEntry => "get_entry",
+            TimedWorkflow::GetLinks => "get_links",
+            TimedWorkflow::ZomeCall => "zome_call",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct LatencyBucket {
+    /// Upper bound in seconds
+    pub le: f64,
+    /// Runs that took at most `le`, including those of the smaller buckets
+    pub count: u64,
+}
+
+/// Latencies of one workflow, in the buckets of [HISTOGRAM_BUCKETS]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LatencyHistogram {
+    pub count: u64,
+    pub sum_seconds: f64,
+    pub max_seconds: f64,
+    pub buckets: Vec<LatencyBucket>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            count: 0,
+            sum_seconds: 0.0,
+            max_seconds: 0.0,
+            buckets: HISTOGRAM_BUCKETS
+                .iter()
+                .map(|le| LatencyBucket { le: *le, count: 0 })
+                .collect(),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, seconds: f64) {
+        self.count += 1;
+        self.sum_seconds += seconds;
+        if seconds > self.max_seconds {
+            self.max_seconds = seconds;
+        }
+        for bucket in self
+            .buckets
+            .iter_mut()
+            .filter(|bucket| seconds <= bucket.le)
+        {
+            bucket.count += 1;
+        }
+    }
+
+    pub fn mean_seconds(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum_seconds / self.count as f64
+        }
+    }
+}
+
+/// Records the time from its creation until it gets dropped as a run of the workflow, so
+/// that every way out of a workflow counts
+pub struct WorkflowTimer {
+    activity: Arc<InstanceActivity>,
+    instance_name: String,
+    workflow: TimedWorkflow,
+    started: Instant,
+}
+
+impl WorkflowTimer {
+    pub fn start(
+        activity: Arc<InstanceActivity>,
+        instance_name: String,
+        workflow: TimedWorkflow,
+    ) -> Self {
+        WorkflowTimer {
+            activity,
+            instance_name,
+            workflow,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for WorkflowTimer {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        self.activity.record_workflow(self.workflow, elapsed);
+        prometheus::observe(
+            "holochain_workflow_duration_seconds",
+            "Time the major workflows took",
+            &[
+                ("instance", self.instance_name.as_str()),
+                ("workflow", self.workflow.to_string().as_str()),
+            ],
+            prometheus::seconds(elapsed),
+        );
+    }
+}
+
+pub struct InstanceActivity {
+    started: Instant,
+    zome_calls: AtomicU64,
+    zome_call_errors: AtomicU64,
+    /// When we last heard from each peer, by agent ID
+    peers: Mutex<HashMap<String, Instant>>,
+    workflow_latencies: Mutex<BTreeMap<TimedWorkflow, LatencyHistogram>>,
+}
+
+impl Default for InstanceActivity {
+    fn default() -> Self {
+        InstanceActivity {
+            started: Instant::now(),
+            zome_calls: AtomicU64::new(0),
+            zome_call_errors: AtomicU64::new(0),
+            peers: Mutex::new(HashMap::new()),
+            workflow_latencies: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl InstanceActivity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_zome_call(&self, succeeded: bool) {
+        self.zome_calls.fetch_add(1, Relaxed);
+        if !succeeded {
+            self.zome_call_errors.fetch_add(1, Relaxed);
+        }
+    }
+
+    pub fn record_peer(&self, agent_id: &str) {
+        self.peers
+            .lock()
+            .unwrap()
+            .insert(agent_id.to_string(), Instant::now());
+    }
+
+    pub fn record_workflow(&self, workflow: TimedWorkflow, duration: Duration) {
+        self.workflow_latencies
+            .lock()
+            .unwrap()
+            .entry(workflow)
+            .or_default()
+            .record(prometheus::seconds(duration));
+    }
+
+    /// Latency histograms by workflow name, of the workflows that ran at least once
+    pub fn workflow_latencies(&self) -> BTreeMap<String, LatencyHistogram> {
+        self.workflow_latencies
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(workflow, histogram)| (workflow.to_string(), histogram.clone()))
+            .collect()
+    }
+
+    pub fn zome_calls(&self) -> u64 {
+        self.zome_calls.load(Relaxed)
+    }
+
+    pub fn zome_call_errors(&self) -> u64 {
+        self.zome_call_errors.load(Relaxed)
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Number of peers we heard from within the given time.
+    /// Peers we haven't heard from for longer get forgotten.
+    pub fn active_peers(&self, within: Duration) -> usize {
+        let mut peers = self.peers.lock().unwrap();
+        peers.retain(|_, last_seen| last_seen.elapsed() <= within);
+        peers.len()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_zome_calls_and_errors() {
+        let activity = InstanceActivity::new();
+        activity.record_zome_call(true);
+        activity.record_zome_call(false);
+        activity.record_zome_call(true);
+        assert_eq!(activity.zome_calls(), 3);
+        assert_eq!(activity.zome_call_errors(), 1);
+    }
+
+    #[test]
+    fn aggregates_workflow_latencies() {
+        let activity = Arc::new(InstanceActivity::new());
+        activity.record_workflow(TimedWorkflow::GetEntry, Duration::from_millis(20));
+        activity.record_workflow(TimedWorkflow::GetEntry, Duration::from_millis(200));
+        drop(WorkflowTimer::start(
+            activity.clone(),
+            String::from("app"),
+            TimedWorkflow::Publish,
+        ));
+
+        let latencies = activity.workflow_latencies();
+        assert_eq!(
+            latencies.keys().collect::<Vec<_>>(),
+            vec!["get_entry", "publish"]
+        );
+        let get_entry = &latencies["get_entry"];
+        assert_eq!(get_entry.count, 2);
+        assert!((get_entry.mean_seconds() - 0.11).abs() < 1e-9);
+        assert!((get_entry.max_seconds - 0.2).abs() < 1e-9);
+        // Buckets up to 10ms, 25ms and 250ms
+        assert_eq!(get_entry.buckets[1].count, 0);
+        assert_eq!(get_entry.buckets[2].count, 1);
+        assert_eq!(get_entry.buckets[5].count, 2);
+        assert_eq!(latencies["publish"].count, 1);
+    }
+
+    #[test]
+    fn forgets_inactive_peers() {
+        let activity = InstanceActivity::new();
+        activity.record_peer("alice");
+        activity.record_peer("bob");
+        activity.record_peer("alice");
+        assert_eq!(activity.active_peers(Duration::from_secs(60)), 2);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(activity.active_peers(Duration::from_millis(1)), 0);
+        assert_eq!(activity.active_peers(Duration::from_secs(60)), 0);
+    }
+}
@@ -0,0 +1,253 @@
+//! Export of a whole instance into a single archive and import of it into another conductor.
+//!
+//! An [InstanceArchive] holds everything an instance gets loaded from: the state snapshots
+//! (top of the source chain, nucleus status, holding map and pending validations), the
+//! headers and entries of the source chain, all other content still referenced by the state
+//! and all meta data of the EAV storage. Importing it into the empty storages of an instance
+//! of the same agent and DNA and loading the instance from them restores exactly the state
+//! it was exported with, which makes it usable for moving instances between machines and for
+//! cold backups.
+use crate::{
+    agent::state::AgentStateSnapshot, content_store::fetch_with_shared_dna, context::Context,
+    dht::dht_store::DhtStoreSnapshot, nucleus::state::NucleusStateSnapshot,
+    storage_compaction::live_addresses,
+};
+use holochain_core_types::{
+    eav::{EaviQuery, EntityAttributeValueIndex},
+    error::{HcResult, HolochainError},
+};
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::{
+    cas::content::{Address, AddressableContent, Content},
+    eav::IndexFilter,
+};
+use std::{collections::HashSet, sync::Arc, thread, time::Duration};
+
+/// Version of the archive format. Archives of other versions get rejected on import.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Content together with the address it is stored under
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ArchivedContent {
+    pub address: Address,
+    pub content: String,
+}
+
+impl AddressableContent for ArchivedContent {
+    fn address(&self) -> Address {
+        self.address.clone()
+    }
+
+    fn content(&self) -> Content {
+        JsonString::from_json(&self.content)
+    }
+
+    fn try_from_content(_content: &Content) -> JsonResult<Self> {
+        Err(JsonError::ErrorGeneric(
+            "Archived content can't be restored without its address".to_string(),
+        ))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InstanceArchive {
+    pub version: u32,
+    /// Address of the agent whose instance got exported
+    pub agent: Address,
+    /// Address of the DNA the instance ran
+    pub dna: Option<Address>,
+    pub agent_snapshot: AgentStateSnapshot,
+    pub nucleus_snapshot: NucleusStateSnapshot,
+    pub dht_snapshot: DhtStoreSnapshot,
+    /// Headers and entries of the source chain
+    pub chain: Vec<ArchivedContent>,
+    /// All other content referenced by the state, like entries held for the DHT
+    pub cas: Vec<ArchivedContent>,
+    /// Meta data of the DHT
+    pub eav: Vec<EntityAttributeValueIndex>,
+}
+
+impl InstanceArchive {
+    /// Collects everything needed to restore the instance's current state.
+    pub fn export(context: &Arc<Context>) -> HcResult<InstanceArchive> {
+        if context.state().is_none() {
+            return Err(HolochainError::ErrorGeneric(
+                "Couldn't get instance state".to_string(),
+            ));
+        }
+        // Like compaction, hold the state's read lock so that snapshots, content and meta
+        // data don't change while we collect them
+        let state = loop {
+            match context.try_state() {
+                Some(state) => break state,
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+
+        let mut chain = Vec::new();
+        let mut in_chain = HashSet::new();
+        for header in state.agent().iter_chain() {
+            for address in &[header.address(), header.entry_address().clone()] {
+                if !in_chain.insert(address.clone()) {
+                    continue;
+                }
+                if let Some(content) =
+                    fetch_with_shared_dna(&context.chain_storage, &context.dna_storage, address)?
+                {
+                    chain.push(ArchivedContent {
+                        address: address.clone(),
+                        content: String::from(content),
+                    });
+                }
+            }
+        }
+
+        let agent_snapshot = AgentStateSnapshot::from(&*state);
+        let nucleus_snapshot = NucleusStateSnapshot::from(&*state);
+        let dht_snapshot = DhtStoreSnapshot::from(&*state);
+        let snapshots = vec![
+            agent_snapshot.address(),
+            nucleus_snapshot.address(),
+            dht_snapshot.address(),
+        ];
+
+        let mut cas = Vec::new();
+        for address in live_addresses(&state)? {
+            if in_chain.contains(&address) || snapshots.contains(&address) {
+                continue;
+            }
+            // Meta data also references things that aren't content, like link tags
+            if let Some(content) =
+                fetch_with_shared_dna(&context.dht_storage, &context.dna_storage, &address)?
+            {
+                cas.push(ArchivedContent {
+                    address,
+                    content: String::from(content),
+                });
+            }
+        }
+
+        let eav = state
+            .dht()
+            .fetch_eavi(&EaviQuery::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                IndexFilter::Range(None, None),
+                None,
+            ))?
+            .into_iter()
+            .collect();
+
+        Ok(InstanceArchive {
+            version: ARCHIVE_VERSION,
+            agent: context.agent_id.address(),
+            dna: state.nucleus().dna().map(|dna| dna.address()),
+            agent_snapshot,
+            nucleus_snapshot,
+            dht_snapshot,
+            chain,
+            cas,
+            eav,
+        })
+    }
+
+    /// Writes the archive's content, meta data and snapshots into the context's storages.
+    /// The instance then needs to get loaded from them to run with the imported state.
+    /// Fails if the archive has a different version or belongs to a different agent.
+    pub fn import(&self, context: &Arc<Context>) -> HcResult<()> {
+        if self.version != ARCHIVE_VERSION {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Unsupported instance archive version {}, expected {}",
+                self.version, ARCHIVE_VERSION
+            )));
+        }
+        if self.agent != context.agent_id.address() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Instance archive belongs to agent {}, not to {}",
+                self.agent,
+                context.agent_id.address()
+            )));
+        }
+
+        {
+            let mut chain_storage = context.chain_storage.write()?;
+            for content in &self.chain {
+                chain_storage.add(content)?;
+            }
+        }
+        {
+            let mut dht_storage = context.dht_storage.write()?;
+            for content in &self.cas {
+                dht_storage.add(content)?;
+            }
+        }
+        {
+            let mut eav_storage = context.eav_storage.write()?;
+            for eavi in &self.eav {
+                eav_storage.add_eavi(eavi)?;
+            }
+        }
+        // Snapshots go last, so an interrupted import doesn't leave a loadable state behind
+        // that references missing content
+        let mut dht_storage = context.dht_storage.write()?;
+        dht_storage.add(&self.agent_snapshot)?;
+        dht_storage.add(&self.nucleus_snapshot)?;
+        dht_storage.add(&self.dht_snapshot)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        instance::tests::{test_context, test_instance_and_context},
+        nucleus::actions::tests::test_dna,
+        persister::{Persister, SimplePersister},
+        workflows::author_entry::author_entry,
+    };
+    use holochain_core_types::entry::test_entry_with_value;
+
+    #[test]
+    fn import_restores_exported_state() {
+        let (_instance, context) = test_instance_and_context(test_dna(), None).unwrap();
+        let entry = test_entry_with_value("{\"stuff\":\"test entry value\"}");
+        context
+            .block_on(author_entry(&entry, None, &context, &vec![]))
+            .unwrap();
+        let top_header = context.state().unwrap().agent().top_chain_header();
+
+        let archive = InstanceArchive::export(&context).unwrap();
+        assert!(archive
+            .chain
+            .iter()
+            .any(|content| content.address == entry.address()));
+        let json = serde_json::to_string(&archive).unwrap();
+        let archive: InstanceArchive = serde_json::from_str(&json).unwrap();
+
+        let target = test_context("jane", None);
+        archive.import(&target).unwrap();
+        let loaded = SimplePersister::new(target.dht_storage.clone())
+            .load(target.clone())
+            .unwrap()
+            .expect("Imported state should be loadable");
+        assert_eq!(loaded.agent().top_chain_header(), top_header);
+        assert!(target
+            .chain_storage
+            .read()
+            .unwrap()
+            .contains(&entry.address())
+            .unwrap());
+    }
+
+    #[test]
+    fn import_rejects_other_agents() {
+        let (_instance, context) = test_instance_and_context(test_dna(), None).unwrap();
+        let archive = InstanceArchive::export(&context).unwrap();
+        assert!(archive.import(&test_context("bob", None)).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! Pausing an instance without shutting it down.
+//!
+//! A paused instance refuses zome calls and leaves its holding queue alone, but its action
+//! loop keeps running and it stays connected to the network, so it doesn't have to sync
+//! again when it gets resumed. Zome calls and holding workflows count as in flight while
+//! they run, so that [InstancePause::wait_until_idle] can wait for the ones that started
+//! before the pause to finish, e.g. before taking a backup of the instance's storage.
+use holochain_core_types::error::{HcResult, HolochainError};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+pub struct InstancePause {
+    paused: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+/// Counts a zome call or holding workflow as in flight until it gets dropped.
+pub struct InFlightWork {
+    pause: Arc<InstancePause>,
+}
+
+impl Drop for InFlightWork {
+    fn drop(&mut self) {
+        self.pause.in_flight.fetch_sub(1, SeqCst);
+    }
+}
+
+impl InstancePause {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(SeqCst)
+    }
+
+    /// Work started from now on gets refused until resume() is called.
+    pub fn pause(&self) {
+        self.paused.store(true, SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, SeqCst);
+    }
+
+    /// Number of zome calls and holding workflows that are running
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(SeqCst)
+    }
+
+    /// Counts new work as in flight. Fails if the instance is paused, in which case nothing
+    /// gets counted.
+    pub fn begin_work(pause: &Arc<InstancePause>) -> HcResult<InFlightWork> {
+        // Counting before checking makes sure wait_until_idle() can't miss work that got
+        // begun concurrently with pause()
+        pause.in_flight.fetch_add(1, SeqCst);
+        let work = InFlightWork {
+            pause: pause.clone(),
+        };
+        if pause.is_paused() {
+            return Err(HolochainError::LifecycleError(String::from(
+                "Instance is paused",
+            )));
+        }
+        Ok(work)
+    }
+
+    /// Blocks until no work is in flight anymore. Fails with [HolochainError::Timeout] if
+    /// that takes longer than the given timeout.
+    pub fn wait_until_idle(&self, timeout: Duration) -> HcResult<()> {
+        let start = Instant::now();
+        while self.in_flight() > 0 {
+            if start.elapsed() > timeout {
+                return Err(HolochainError::Timeout(format!(
+                    "{} zome calls or holding workflows still running",
+                    self.in_flight()
+                )));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_instance_refuses_work_and_waits_for_running_work() {
+        let pause = Arc::new(InstancePause::new());
+        let running = InstancePause::begin_work(&pause).unwrap();
+        assert_eq!(pause.in_flight(), 1);
+
+        pause.pause();
+        assert!(InstancePause::begin_work(&pause).is_err());
+        assert_eq!(pause.in_flight(), 1);
+        assert!(pause
+            .wait_until_idle(Duration::from_millis(50))
+            .unwrap_err()
+            .to_string()
+            .contains("1 zome calls"));
+
+        drop(running);
+        assert!(pause.wait_until_idle(Duration::from_millis(50)).is_ok());
+
+        pause.resume();
+        assert!(InstancePause::begin_work(&pause).is_ok());
+        assert_eq!(pause.in_flight(), 0);
+    }
+}
@@ -38,9 +38,17 @@ pub mod macros;
 // #[autotrace]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod action;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod action_debugger;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod action_recording;
 // #[autotrace]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod agent;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod causal_log;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod clock;
 // #[autotrace]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod consistency;
@@ -54,9 +62,25 @@ pub mod context;
 pub mod dht;
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod entry;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod event_log;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod full_consistency;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod fuzzing;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod holding_chaos;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod id_generator;
 #[autotrace]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod instance;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod instance_activity;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod instance_archive;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod instance_pause;
 #[cfg(test)]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod link_tests;
@@ -72,19 +96,42 @@ pub mod nucleus;
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod persister;
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod profiler;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod resource_limits;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod scenario;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod scheduled_jobs;
 // #[autotrace]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod signal;
 // #[autotrace]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod simulation;
+// #[autotrace]
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod state;
 // #[autotrace]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod state_dump;
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod storage_compaction;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod storage_migration;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod storage_scrubber;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod storage_usage;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod test_harness;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod validation_coverage;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod wasm_engine;
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod workflows;
+#[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
+pub mod write_ahead_log;
 
 new_relic_setup!("NEW_RELIC_LICENSE_KEY");
@@ -0,0 +1,29 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    context::Context,
+    instance::dispatch_action,
+};
+use holochain_persistence_api::cas::content::Address;
+use std::sync::Arc;
+
+/// Blocks the given agent in this instance: open connections with them get dropped,
+/// their direct messages, store and query requests get ignored, entries they signed fail
+/// validation and data they authored is no longer served to fetches or queries.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn block_agent(agent: Address, context: &Arc<Context>) {
+    log_info!(context, "net/block_agent: Blocking agent {}", agent);
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::BlockAgent(agent)),
+    );
+}
+
+/// Lifts a block added with block_agent().
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn unblock_agent(agent: Address, context: &Arc<Context>) {
+    log_info!(context, "net/block_agent: Unblocking agent {}", agent);
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::UnblockAgent(agent)),
+    );
+}
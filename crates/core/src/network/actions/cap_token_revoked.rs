@@ -0,0 +1,96 @@
+use crate::{
+    action::{Action, ActionWrapper, DirectMessageData},
+    context::Context,
+    instance::dispatch_action,
+    network::direct_message::DirectMessage,
+};
+use holochain_core_types::entry::{entry_type::EntryType, Entry};
+use holochain_persistence_api::cas::content::Address;
+use std::{sync::Arc, time::Duration};
+
+/// How long we keep the connection of a sent revocation notice open
+/// waiting for the grantee's acknowledgement.
+pub const CAP_TOKEN_REVOKED_TIMEOUT_MS: u64 = 20000;
+
+/// Tells a grantee that the capability grant with the given token was revoked.
+/// Like holding receipts this does not wait for a response, the grantee's
+/// CapTokenRevokedAck only resolves the direct connection.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn send_cap_token_revoked(grantee: Address, token: Address, context: Arc<Context>) {
+    log_debug!(
+        context,
+        "net/cap_token_revoked: Telling grantee {} that token {} is revoked",
+        grantee,
+        token
+    );
+    let direct_message_data = DirectMessageData {
+        address: grantee,
+        message: DirectMessage::CapTokenRevoked(token),
+        msg_id: nanoid::simple(),
+        is_response: false,
+    };
+    let timeout = (
+        context.clock.now(),
+        Duration::from_millis(CAP_TOKEN_REVOKED_TIMEOUT_MS),
+    );
+    let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
+        direct_message_data,
+        Some(timeout),
+    )));
+    dispatch_action(context.action_channel(), action_wrapper);
+}
+
+/// true if we committed a claim for the given token from the given grantor
+pub(crate) fn holds_claim(context: &Arc<Context>, grantor: &Address, token: &Address) -> bool {
+    context
+        .state()
+        .map(|state| {
+            let agent = state.agent();
+            agent
+                .chain_store()
+                .iter_type(&agent.top_chain_header(), &EntryType::CapTokenClaim)
+                .filter_map(|header| agent.chain_store().get(header.entry_address()).ok()?)
+                .any(|entry| match entry {
+                    Entry::CapTokenClaim(claim) => {
+                        &claim.token() == token && &claim.grantor() == grantor
+                    }
+                    _ => false,
+                })
+        })
+        .unwrap_or(false)
+}
+
+/// Grantee side of a revocation: remembers the token as revoked if we hold a claim
+/// for it from the sending grantor, and acknowledges the notice.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn handle_cap_token_revoked(
+    grantor: Address,
+    msg_id: String,
+    token: Address,
+    context: Arc<Context>,
+) {
+    if holds_claim(&context, &grantor, &token) {
+        dispatch_action(
+            context.action_channel(),
+            ActionWrapper::new(Action::AddRevokedCapToken(token)),
+        );
+    } else {
+        log_debug!(
+            context,
+            "net/cap_token_revoked: Ignoring revocation from {} for token {} we hold no claim for",
+            grantor,
+            token
+        );
+    }
+
+    let direct_message_data = DirectMessageData {
+        address: grantor,
+        message: DirectMessage::CapTokenRevokedAck,
+        msg_id,
+        is_response: true,
+    };
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::SendDirectMessage((direct_message_data, None))),
+    );
+}
@@ -0,0 +1,81 @@
+use crate::{
+    action::{Action, ActionWrapper, DirectMessageData},
+    context::Context,
+    instance::dispatch_action,
+    network::{actions::cap_token_revoked::holds_claim, direct_message::DirectMessage},
+};
+use holochain_persistence_api::cas::content::Address;
+use std::{sync::Arc, time::Duration};
+
+/// How long we keep the connection of a sent rotation notice open
+/// waiting for the grantee's acknowledgement.
+pub const CAP_TOKEN_ROTATED_TIMEOUT_MS: u64 = 20000;
+
+/// Tells a grantee that the capability grant with the old token got replaced by a grant
+/// with the new token. Like revocation notices this does not wait for a response.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn send_cap_token_rotated(
+    grantee: Address,
+    old_token: Address,
+    new_token: Address,
+    context: Arc<Context>,
+) {
+    log_debug!(
+        context,
+        "net/cap_token_rotated: Telling grantee {} that token {} is replaced by {}",
+        grantee,
+        old_token,
+        new_token
+    );
+    let direct_message_data = DirectMessageData {
+        address: grantee,
+        message: DirectMessage::CapTokenRotated((old_token, new_token)),
+        msg_id: nanoid::simple(),
+        is_response: false,
+    };
+    let timeout = (
+        context.clock.now(),
+        Duration::from_millis(CAP_TOKEN_ROTATED_TIMEOUT_MS),
+    );
+    let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
+        direct_message_data,
+        Some(timeout),
+    )));
+    dispatch_action(context.action_channel(), action_wrapper);
+}
+
+/// Grantee side of a rotation: starts using the new token in place of the old one if we
+/// hold a claim for the old token from the sending grantor, and acknowledges the notice.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn handle_cap_token_rotated(
+    grantor: Address,
+    msg_id: String,
+    old_token: Address,
+    new_token: Address,
+    context: Arc<Context>,
+) {
+    if holds_claim(&context, &grantor, &old_token) {
+        dispatch_action(
+            context.action_channel(),
+            ActionWrapper::new(Action::AddRotatedCapToken((old_token, new_token))),
+        );
+    } else {
+        log_debug!(
+            context,
+            "net/cap_token_rotated: Ignoring rotation from {} for token {} we hold no claim for",
+            grantor,
+            old_token
+        );
+    }
+
+    let direct_message_data = DirectMessageData {
+        address: grantor,
+        message: DirectMessage::CapTokenRotatedAck,
+        msg_id,
+        is_response: true,
+    };
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::SendDirectMessage((direct_message_data, None))),
+    );
+}
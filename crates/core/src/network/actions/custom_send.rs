@@ -8,7 +8,7 @@ use futures::{future::Future, task::Poll};
 use holochain_core_types::{error::HolochainError, time::Timeout};
 use holochain_persistence_api::cas::content::Address;
 use snowflake::ProcessUniqueId;
-use std::{pin::Pin, sync::Arc, time::SystemTime};
+use std::{pin::Pin, sync::Arc};
 
 /// SendDirectMessage Action Creator for custom (=app) messages
 /// This triggers the network module to open a synchronous node-to-node connection
@@ -31,11 +31,11 @@ pub async fn custom_send(
     };
     let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
         direct_message_data,
-        Some((SystemTime::now(), timeout.into())),
+        Some((context.clock.now(), timeout.into())),
     )));
     dispatch_action(context.action_channel(), action_wrapper);
 
-    let future_id = ProcessUniqueId::new();
+    let future_id = context.id_generator.next_id();
     SendResponseFuture {
         context: context.clone(),
         id,
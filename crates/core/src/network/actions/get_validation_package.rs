@@ -8,6 +8,7 @@ use futures::{future::Future, task::Poll};
 use holochain_core_types::{
     chain_header::ChainHeader, error::HcResult, validation::ValidationPackage,
 };
+use holochain_persistence_api::cas::content::AddressableContent;
 use snowflake::ProcessUniqueId;
 use std::{pin::Pin, sync::Arc};
 
@@ -15,6 +16,10 @@ use std::{pin::Pin, sync::Arc};
 /// This triggers the network module to retrieve the validation package for the
 /// entry given by the header.
 ///
+/// Packages the source sent us before get taken from the network state's
+/// validation_package_cache instead, and those it sends get cached. Packages the source
+/// sends for another header than the given one get dropped.
+///
 /// Returns a future that resolves to Option<ValidationPackage> (or HolochainError).
 /// If that is None this means that we couldn't get a validation package from the source.
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
@@ -22,20 +27,50 @@ pub async fn get_validation_package(
     header: ChainHeader,
     context: &Arc<Context>,
 ) -> HcResult<Option<ValidationPackage>> {
+    let cache = context
+        .state()
+        .map(|state| state.network().validation_package_cache.clone());
+    if let Some(package) = cache
+        .as_ref()
+        .and_then(|cache| cache.get(&header.address()))
+    {
+        return Ok(Some(package));
+    }
+
+    let requested_header = header.clone();
     let entry_address = header.entry_address().clone();
     let key = ValidationKey {
         address: entry_address,
-        id: snowflake::ProcessUniqueId::new().to_string(),
+        id: context.id_generator.next_id().to_string(),
     };
     let action_wrapper = ActionWrapper::new(Action::GetValidationPackage((key.clone(), header)));
     dispatch_action(context.action_channel(), action_wrapper.clone());
-    let id = ProcessUniqueId::new();
-    GetValidationPackageFuture {
+    let id = context.id_generator.next_id();
+    let result = GetValidationPackageFuture {
         context: context.clone(),
         key,
         id,
     }
-    .await
+    .await;
+    match result {
+        // A package for another header than the one we asked for is of no use to us
+        Ok(Some(package)) if package.chain_header != requested_header => {
+            log_warn!(
+                context,
+                "get_validation_package: source sent a package for {} instead of {}",
+                package.chain_header.address(),
+                requested_header.address()
+            );
+            Ok(None)
+        }
+        Ok(Some(package)) => {
+            if let Some(cache) = cache {
+                cache.insert(&requested_header, package.clone());
+            }
+            Ok(Some(package))
+        }
+        result => result,
+    }
 }
 
 /// GetValidationPackageFuture resolves to an Option<ValidationPackage>
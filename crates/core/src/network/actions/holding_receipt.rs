@@ -0,0 +1,85 @@
+use crate::{
+    action::{Action, ActionWrapper, DirectMessageData},
+    context::Context,
+    instance::dispatch_action,
+    network::direct_message::DirectMessage,
+};
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use std::{sync::Arc, time::Duration};
+
+/// How long we keep the connection of a sent holding receipt open
+/// waiting for the author's acknowledgement.
+pub const HOLDING_RECEIPT_TIMEOUT_MS: u64 = 20000;
+
+/// Tells the author of the header with the given address that we are now holding its entry.
+/// This does not wait for a response. The author's HoldingReceiptAck only
+/// resolves the direct connection, if it never comes the connection times out.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn send_holding_receipt(author: Address, header_address: Address, context: Arc<Context>) {
+    log_debug!(
+        context,
+        "net/holding_receipt: Sending holding receipt for header {} to author {}",
+        header_address,
+        author
+    );
+    let direct_message_data = DirectMessageData {
+        address: author,
+        message: DirectMessage::HoldingReceipt(header_address),
+        msg_id: nanoid::simple(),
+        is_response: false,
+    };
+    let timeout = (
+        context.clock.now(),
+        Duration::from_millis(HOLDING_RECEIPT_TIMEOUT_MS),
+    );
+    let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
+        direct_message_data,
+        Some(timeout),
+    )));
+    dispatch_action(context.action_channel(), action_wrapper);
+}
+
+/// Author side of a holding receipt: records the holder if the header is
+/// in our source chain and acknowledges the receipt.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn handle_holding_receipt(
+    holder: Address,
+    msg_id: String,
+    header_address: Address,
+    context: Arc<Context>,
+) {
+    let is_authored = context
+        .state()
+        .map(|state| {
+            state
+                .agent()
+                .iter_chain()
+                .any(|header| header.address() == header_address)
+        })
+        .unwrap_or(false);
+
+    if is_authored {
+        dispatch_action(
+            context.action_channel(),
+            ActionWrapper::new(Action::AddHoldingReceipt((header_address, holder.clone()))),
+        );
+    } else {
+        log_debug!(
+            context,
+            "net/holding_receipt: Ignoring holding receipt from {} for header {} that we did not author",
+            holder,
+            header_address
+        );
+    }
+
+    let direct_message_data = DirectMessageData {
+        address: holder,
+        message: DirectMessage::HoldingReceiptAck,
+        msg_id,
+        is_response: true,
+    };
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::SendDirectMessage((direct_message_data, None))),
+    );
+}
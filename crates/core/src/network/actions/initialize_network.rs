@@ -22,12 +22,15 @@ pub async fn initialize_network(context: &Arc<Context>) -> HcResult<()> {
         dna_address,
         agent_id: agent_id.clone(),
         handler,
+        bandwidth_limit: context.resource_limits.max_network_bytes_per_second,
+        instance_name: context.get_instance_name(),
+        validation_package_cache_size: context.validation_package_cache_size,
     };
     let action_wrapper = ActionWrapper::new(Action::InitNetwork(network_settings));
     dispatch_action(context.action_channel(), action_wrapper.clone());
 
     log_debug!(context, "waiting for network");
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     InitNetworkFuture {
         context: context.clone(),
         id,
@@ -49,11 +52,14 @@ pub async fn initialize_network_with_spoofed_dna(
         dna_address,
         agent_id,
         handler,
+        bandwidth_limit: context.resource_limits.max_network_bytes_per_second,
+        instance_name: context.get_instance_name(),
+        validation_package_cache_size: context.validation_package_cache_size,
     };
     let action_wrapper = ActionWrapper::new(Action::InitNetwork(network_settings));
     dispatch_action(context.action_channel(), action_wrapper.clone());
 
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     InitNetworkFuture {
         context: context.clone(),
         id,
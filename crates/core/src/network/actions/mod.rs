@@ -1,8 +1,16 @@
 #[autotrace]
+pub mod block_agent;
+#[autotrace]
+pub mod cap_token_revoked;
+#[autotrace]
+pub mod cap_token_rotated;
+#[autotrace]
 pub mod custom_send;
 #[autotrace]
 pub mod get_validation_package;
 #[autotrace]
+pub mod holding_receipt;
+#[autotrace]
 pub mod initialize_network;
 #[autotrace]
 pub mod publish;
@@ -17,7 +25,7 @@ use crate::state::ActionResponse;
 use holochain_core_types::error::HcResult;
 use holochain_json_api::{error::JsonError, json::JsonString};
 use holochain_persistence_api::cas::content::Address;
-use std::ops::Deref;
+use std::{ops::Deref, time::SystemTime};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, DefaultJson)]
 pub enum NetworkActionResponse {
@@ -36,8 +44,8 @@ impl Deref for Response {
     }
 }
 
-impl From<NetworkActionResponse> for Response {
-    fn from(r: NetworkActionResponse) -> Self {
-        Response(ActionResponse::new(r))
+impl Response {
+    pub fn new(r: NetworkActionResponse, created_at: SystemTime) -> Self {
+        Response(ActionResponse::new(r, created_at))
     }
 }
@@ -2,10 +2,12 @@ use crate::{
     action::{Action, ActionWrapper},
     context::Context,
     instance::dispatch_action,
+    instance_activity::TimedWorkflow,
     network::actions::NetworkActionResponse,
 };
+use crate::{content_store::GetContent, entry::CanPublish};
 use futures::{future::Future, task::Poll};
-use holochain_core_types::error::HcResult;
+use holochain_core_types::error::{HcResult, HolochainError};
 use holochain_persistence_api::cas::content::Address;
 use snowflake::ProcessUniqueId;
 use std::{pin::Pin, sync::Arc};
@@ -14,12 +16,30 @@ use std::{pin::Pin, sync::Arc};
 /// This is the high-level publish function that wraps the whole publish process and is what should
 /// be called from zome api functions and other contexts that don't care about implementation details.
 ///
+/// Entries of a private entry type are refused so they never leave the source chain.
+///
 /// Returns a future that resolves to an ActionResponse.
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub async fn publish(address: Address, context: &Arc<Context>) -> HcResult<Address> {
+    let _timer = context.time_workflow(TimedWorkflow::Publish);
+    let maybe_entry = context
+        .state()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Could not get state".to_string()))?
+        .agent()
+        .chain_store()
+        .get(&address)?;
+    if let Some(entry) = maybe_entry {
+        if !entry.entry_type().can_publish(context) {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Refusing to publish private entry {}",
+                address
+            )));
+        }
+    }
+
     let action_wrapper = ActionWrapper::new(Action::Publish(address));
     dispatch_action(context.action_channel(), action_wrapper.clone());
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     PublishFuture {
         context: context.clone(),
         action: action_wrapper,
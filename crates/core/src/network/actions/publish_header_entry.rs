@@ -16,7 +16,7 @@ use std::{pin::Pin, sync::Arc};
 pub async fn publish_header_entry(address: Address, context: &Arc<Context>) -> HcResult<Address> {
     let action_wrapper = ActionWrapper::new(Action::PublishHeaderEntry(address));
     dispatch_action(context.action_channel(), action_wrapper.clone());
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     PublishHeaderEntryFuture {
         context: context.clone(),
         action: action_wrapper,
@@ -14,7 +14,6 @@ use std::{pin::Pin, sync::Arc};
 
 use holochain_wasm_utils::api_serialization::get_links::{GetLinksArgs, LinksStatusRequestKind};
 use snowflake::ProcessUniqueId;
-use std::time::SystemTime;
 
 /// FetchEntry Action Creator
 /// This is the network version of get_entry that makes the network module start
@@ -68,11 +67,11 @@ pub async fn query(
     let entry = Action::Query((
         key.clone(),
         payload.clone(),
-        Some((SystemTime::now(), timeout.into())),
+        Some((context.clock.now(), timeout.into())),
     ));
     let action_wrapper = ActionWrapper::new(entry);
     dispatch_action(context.action_channel(), action_wrapper.clone());
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     QueryFuture {
         context: context.clone(),
         key: key.clone(),
@@ -3,6 +3,7 @@ use crate::action::ValidationKey;
 use holochain_json_api::{error::JsonError, json::JsonString};
 
 use holochain_core_types::validation::ValidationPackage;
+use holochain_persistence_api::cas::content::Address;
 
 /// This is direct message that got created by the zome code through hdk::send().
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, DefaultJson)]
@@ -35,4 +36,28 @@ pub enum DirectMessage {
     /// Option<> since there has to be a way to respond saying
     /// "I can't"
     ValidationPackage(Option<ValidationPackage>),
+
+    /// A DHT node tells the author of the header with the given address
+    /// that it has validated and is now holding its entry.
+    HoldingReceipt(Address),
+
+    /// With this message an author acknowledges a HoldingReceipt
+    /// so the holder can close the connection.
+    HoldingReceiptAck,
+
+    /// A grantor tells an assigned grantee that the capability grant
+    /// with the given token was revoked.
+    CapTokenRevoked(Address),
+
+    /// With this message a grantee acknowledges a CapTokenRevoked
+    /// so the grantor can close the connection.
+    CapTokenRevokedAck,
+
+    /// A grantor tells an assigned grantee that the capability grant with the first token
+    /// was replaced by a grant with the second token.
+    CapTokenRotated((Address, Address)),
+
+    /// With this message a grantee acknowledges a CapTokenRotated
+    /// so the grantor can close the connection.
+    CapTokenRotatedAck,
 }
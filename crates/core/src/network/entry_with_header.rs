@@ -23,9 +23,9 @@ impl EntryWithHeader {
         header: ChainHeader,
     ) -> Result<EntryWithHeader, HolochainError> {
         if entry.address() != *header.entry_address() {
-            Err(HolochainError::ValidationFailed(String::from(
-                "Entry/Header mismatch",
-            )))
+            Err(HolochainError::ValidationFailed(
+                "Entry/Header mismatch".into(),
+            ))
         } else {
             Ok(EntryWithHeader::new(entry, header))
         }
@@ -7,7 +7,11 @@ use crate::{
     },
 };
 use holochain_core_types::network::entry_aspect::EntryAspect;
-use lib3h_protocol::{data_types::FetchEntryData, types::EntryHash};
+use holochain_persistence_api::cas::content::AddressableContent;
+use lib3h_protocol::{
+    data_types::FetchEntryData,
+    types::{AspectHash, EntryHash},
+};
 use std::{collections::HashSet, sync::Arc};
 
 /// The network has requested a DHT entry from us.
@@ -16,33 +20,70 @@ use std::{collections::HashSet, sync::Arc};
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn handle_fetch_entry(get_dht_data: FetchEntryData, context: Arc<Context>) {
     let entry_hash = get_dht_data.entry_address.clone();
-    let aspect_set = fetch_aspects_for_entry(&entry_hash, context.clone());
-    let aspects = aspect_set.into_iter().collect::<Vec<_>>();
+    let requested = get_dht_data
+        .aspect_address_list
+        .as_ref()
+        .map(|list| list.iter().cloned().collect::<HashSet<AspectHash>>());
+    let aspect_set =
+        fetch_requested_aspects_for_entry(&entry_hash, requested.as_ref(), context.clone());
+    // Data authored by blocked agents is not served
+    let network_state = context.state().map(|state| state.network());
+    let aspects = aspect_set
+        .into_iter()
+        .filter(|aspect| {
+            network_state
+                .as_ref()
+                .map(|network| !network.is_authored_by_blocked_agent(aspect.header()))
+                .unwrap_or(true)
+        })
+        .collect::<Vec<_>>();
 
     let action_wrapper = ActionWrapper::new(Action::RespondFetch((get_dht_data, aspects)));
     dispatch_action(context.action_channel(), action_wrapper);
 }
 
+/// All aspects of the entry that we hold or authored
 pub fn fetch_aspects_for_entry(address: &EntryHash, context: Arc<Context>) -> HashSet<EntryAspect> {
-    let mut aspects: HashSet<EntryAspect> = HashSet::new();
+    fetch_requested_aspects_for_entry(address, None, context)
+}
 
-    // XXX: NB: we seem to be ignoring aspect_address_list and just attempting to get all aspects.
-    // Is that right?
+/// The aspects of the entry with the given aspect addresses, or all of them if `requested`
+/// is None. Looking up meta aspects means scanning the EAVs of the entry, which can hold
+/// thousands of links, so that only happens if content aspects don't cover the request.
+pub fn fetch_requested_aspects_for_entry(
+    address: &EntryHash,
+    requested: Option<&HashSet<AspectHash>>,
+    context: Arc<Context>,
+) -> HashSet<EntryAspect> {
+    let mut aspects: HashSet<EntryAspect> = HashSet::new();
+    let is_requested = |aspect: &EntryAspect| {
+        requested
+            .map(|requested| requested.contains(&AspectHash::from(aspect.address())))
+            .unwrap_or(true)
+    };
 
     match get_content_aspects(address, context.clone()) {
         Ok(content_aspects) => {
             // there may be more than one if the same entry data was committed twice
-            for aspect in content_aspects {
+            for aspect in content_aspects.into_iter().filter(|a| is_requested(a)) {
                 aspects.insert(aspect);
             }
+            if requested
+                .map(|requested| requested.len() <= aspects.len())
+                .unwrap_or(false)
+            {
+                return aspects;
+            }
             for result in &[
                 get_meta_aspects_from_chain(&address, context.clone()),
                 get_meta_aspects_from_dht_eav(&address, context.clone()),
             ] {
                 match result {
-                    Ok(meta_aspects) => meta_aspects.iter().for_each(|a| {
-                        aspects.insert(a.clone());
-                    }),
+                    Ok(meta_aspects) => {
+                        for aspect in meta_aspects.iter().filter(|a| is_requested(a)) {
+                            aspects.insert(aspect.clone());
+                        }
+                    }
                     Err(get_meta_error) => {
                         log_error!(context, "net/handle_fetch_entry: Error getting meta aspects for entry ({:?}), error: {:?}",
                             address,
@@ -62,3 +103,110 @@ pub fn fetch_aspects_for_entry(address: &EntryHash, context: Arc<Context>) -> Ha
 
     aspects
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        full_consistency::FullConsistencyWaiter, network::test_utils::test_wat_always_valid,
+        nucleus::actions::tests::instance_by_name, workflows::author_entry::author_entry,
+    };
+    use holochain_core_types::{
+        agent::test_agent_id,
+        chain_header::test_chain_header,
+        entry::{test_entry, test_entry_with_value, Entry},
+        link::link_data::LinkData,
+    };
+    use std::time::Duration;
+    use test_utils::create_test_dna_with_wat;
+
+    fn aspect_hashes(aspects: &[&EntryAspect]) -> HashSet<AspectHash> {
+        aspects
+            .iter()
+            .map(|aspect| AspectHash::from(aspect.address()))
+            .collect()
+    }
+
+    #[test]
+    fn fetches_only_the_requested_aspects() {
+        let mut dna = create_test_dna_with_wat("test_zome", Some(&test_wat_always_valid()));
+        dna.uuid = String::from("fetches_only_the_requested_aspects");
+        let netname = Some("fetches_only_the_requested_aspects, the network");
+        let (_instance, context) = instance_by_name("jill", dna, netname);
+        let waiter = FullConsistencyWaiter::new(&[context.clone()]);
+
+        let entry = test_entry();
+        let link_entry = Entry::LinkAdd(LinkData::new_add(
+            &entry.address(),
+            &entry.address(),
+            "test-tag",
+            "test-link",
+            test_chain_header(),
+            test_agent_id(),
+        ));
+        let update = test_entry_with_value("{\"stuff\":\"updated value\"}");
+        context
+            .block_on(author_entry(&entry, None, &context, &vec![]))
+            .unwrap();
+        context
+            .block_on(author_entry(&link_entry, None, &context, &vec![]))
+            .unwrap();
+        context
+            .block_on(author_entry(
+                &update,
+                Some(entry.address()),
+                &context,
+                &vec![],
+            ))
+            .unwrap();
+        waiter.wait(Duration::from_secs(10)).unwrap();
+
+        let entry_hash = EntryHash::from(entry.address());
+        let all_aspects = fetch_aspects_for_entry(&entry_hash, context.clone());
+        let find = |is_kind: fn(&EntryAspect) -> bool| {
+            all_aspects
+                .iter()
+                .find(|aspect| is_kind(aspect))
+                .cloned()
+                .expect("All kinds of aspects should be held")
+        };
+        let content = find(|aspect| match aspect {
+            EntryAspect::Content(..) => true,
+            _ => false,
+        });
+        let link = find(|aspect| match aspect {
+            EntryAspect::LinkAdd(..) => true,
+            _ => false,
+        });
+        let crud = find(|aspect| match aspect {
+            EntryAspect::Update(..) => true,
+            _ => false,
+        });
+
+        // The content aspect with its header alone, without link or CRUD aspects
+        let requested = aspect_hashes(&[&content]);
+        assert_eq!(
+            fetch_requested_aspects_for_entry(&entry_hash, Some(&requested), context.clone()),
+            vec![content.clone()].into_iter().collect()
+        );
+
+        // Meta aspects only, without the content aspect or the CRUD aspect
+        let requested = aspect_hashes(&[&link]);
+        assert_eq!(
+            fetch_requested_aspects_for_entry(&entry_hash, Some(&requested), context.clone()),
+            vec![link.clone()].into_iter().collect()
+        );
+
+        let requested = aspect_hashes(&[&content, &crud]);
+        assert_eq!(
+            fetch_requested_aspects_for_entry(&entry_hash, Some(&requested), context.clone()),
+            vec![content, crud].into_iter().collect()
+        );
+
+        // Aspects we don't hold don't get fetched
+        let requested = aspect_hashes(&[&EntryAspect::Header(test_chain_header())]);
+        assert!(
+            fetch_requested_aspects_for_entry(&entry_hash, Some(&requested), context).is_empty()
+        );
+    }
+}
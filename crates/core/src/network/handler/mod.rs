@@ -12,6 +12,7 @@ pub mod send;
 pub mod store;
 
 use crate::{
+    causal_log::{push_cause, variant_name},
     context::Context,
     entry::CanPublish,
     network::{
@@ -24,13 +25,15 @@ use crate::{
             send::*,
             store::*,
         },
+        traffic::TrafficEvent,
     },
     workflows::get_entry_result::get_entry_with_meta_workflow_local,
 };
 use holochain_core_types::{
     chain_header::ChainHeader, eav::Attribute, entry::Entry, error::HolochainError,
 };
-use holochain_json_api::json::JsonString;
+use holochain_json_api::{error::JsonError, json::JsonString};
+use holochain_metrics::prometheus;
 use holochain_net::connection::net_connection::NetHandler;
 use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use lib3h_protocol::{
@@ -55,6 +58,71 @@ fn is_my_id(context: &Arc<Context>, agent_id: &str) -> bool {
     true
 }
 
+// Traffic from agents blocked with Action::BlockAgent gets dropped right here.
+// Other agents count as active peers of the instance.
+fn is_blocked(context: &Arc<Context>, agent_id: &str) -> bool {
+    let blocked = context
+        .state()
+        .map(|state| state.network().is_agent_blocked(&Address::from(agent_id)))
+        .unwrap_or(false);
+    if blocked {
+        log_debug!(
+            context,
+            "net/handle: ignoring, agent {} is blocked",
+            agent_id
+        );
+    } else if context.agent_id.pub_sign_key != agent_id {
+        context.activity.record_peer(agent_id);
+    }
+    blocked
+}
+
+/// Deserializes the JSON that other nodes put into the opaque byte fields of network
+/// messages. Content that isn't UTF-8 or doesn't parse is an error for the handler to
+/// report, since it comes from the network unchecked.
+pub fn parse_content<T: TryFrom<JsonString, Error = JsonError>>(
+    content: &[u8],
+) -> Result<T, JsonError> {
+    let json = std::str::from_utf8(content)
+        .map_err(|error| JsonError::SerializationError(error.to_string()))?;
+    T::try_from(JsonString::from_json(json))
+}
+
+// Counts the message in the traffic summaries, with the agent that sent it if we know it.
+// Failures that lib3h reports also count as errors.
+fn record_received_traffic(context: &Arc<Context>, data: &Lib3hServerProtocol) {
+    let traffic = match context.state() {
+        Some(state) => state.network().traffic.clone(),
+        None => return,
+    };
+    let peer = match data {
+        Lib3hServerProtocol::HandleStoreEntryAspect(data) => {
+            Some(data.provider_agent_id.to_string())
+        }
+        Lib3hServerProtocol::HandleQueryEntry(data) => Some(data.requester_agent_id.to_string()),
+        Lib3hServerProtocol::QueryEntryResult(data) => Some(data.responder_agent_id.to_string()),
+        Lib3hServerProtocol::HandleSendDirectMessage(data)
+        | Lib3hServerProtocol::SendDirectMessageResult(data) => {
+            Some(data.from_agent_id.to_string())
+        }
+        _ => None,
+    };
+    let bytes = serde_json::to_vec(data)
+        .map(|bytes| bytes.len())
+        .unwrap_or(0);
+    let message_type = variant_name(data);
+    let now = context.clock.now();
+    traffic.record(
+        now,
+        &message_type,
+        peer.as_ref().map(String::as_str),
+        TrafficEvent::Received(bytes),
+    );
+    if let Lib3hServerProtocol::FailureResult(_) = data {
+        traffic.record(now, &message_type, None, TrafficEvent::Error);
+    }
+}
+
 // Since StoreEntryAspectData lives in the net crate and EntryAspect is specific
 // to core we can't implement fmt::Debug so that it spans over both, StoreEntryAspectData
 // and the type that is represented as opaque byte vector.
@@ -62,9 +130,7 @@ fn is_my_id(context: &Arc<Context>, agent_id: &str) -> bool {
 // This function enables pretty debug logs by deserializing the EntryAspect explicitly
 // and combining it with the top-level fields in a formatted and indented output.
 fn format_store_data(data: &StoreEntryAspectData) -> String {
-    let aspect_json =
-        JsonString::from_json(std::str::from_utf8(&*data.entry_aspect.aspect.clone()).unwrap());
-    let aspect = EntryAspect::try_from(aspect_json).unwrap();
+    let aspect: Result<EntryAspect, _> = parse_content(&*data.entry_aspect.aspect);
     format!(
         r#"
 StoreEntryAspectData {{
@@ -90,8 +156,7 @@ StoreEntryAspectData {{
 
 // See comment on fn format_store_data() - same reason for this function.
 fn format_message_data(data: &DirectMessageData) -> String {
-    let message_json = JsonString::from_json(std::str::from_utf8(&*data.content.clone()).unwrap());
-    let message = DirectMessage::try_from(message_json).unwrap();
+    let message: Result<DirectMessage, _> = parse_content(&*data.content);
     format!(
         r#"
 MessageData {{
@@ -139,7 +204,16 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
             return Ok(());
         }
         let message = message.unwrap();
-        let mut span = ht::SpanWrap::from(message.clone())
+        prometheus::increment_counter(
+            "holochain_network_messages_received_total",
+            "Messages received from the network",
+            &[("instance", &context.get_instance_name())],
+        );
+        record_received_traffic(&context, &message.data);
+        // The trace context of the message without the message, for continuing the
+        // sender's trace in workflows that run later
+        let trace = message.clone().map(|_| ());
+        let mut span = ht::SpanWrap::from(trace.clone())
             .follower(&context.tracer, "received message from handler")
             .unwrap_or_else(|| {
                 context
@@ -151,6 +225,13 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
         span.event(format!("message.data: {:?}", message.data));
         // Set this as the root span for autotrace
         let _guard = ht::push_span(span);
+        // and the message as the cause of the actions its handler dispatches
+        let _cause = push_cause(context.causal_log.record_cause(
+            context.clock.iso8601_now(),
+            None,
+            format!("network message {}", variant_name(&message.data)),
+            None,
+        ));
         match message.data {
             Lib3hServerProtocol::FailureResult(failure_data) => {
                 if !is_my_dna(&my_dna_address, &failure_data.space_address.to_string()) {
@@ -164,12 +245,15 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
                 if !is_my_dna(&my_dna_address, &dht_entry_data.space_address.to_string()) {
                     return Ok(());
                 }
+                if is_blocked(&context, &dht_entry_data.provider_agent_id.to_string()) {
+                    return Ok(());
+                }
                 log_debug!(
                     context,
                     "net/handle: HandleStoreEntryAspect: {}",
                     format_store_data(&dht_entry_data)
                 );
-                handle_store(dht_entry_data, context.clone())
+                handle_store(dht_entry_data, trace, context.clone())
             }
             Lib3hServerProtocol::HandleFetchEntry(fetch_entry_data) => {
                 if !is_my_dna(&my_dna_address, &fetch_entry_data.space_address.to_string()) {
@@ -200,6 +284,9 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
                 if !is_my_dna(&my_dna_address, &query_entry_data.space_address.to_string()) {
                     return Ok(());
                 }
+                if is_blocked(&context, &query_entry_data.requester_agent_id.to_string()) {
+                    return Ok(());
+                }
                 log_debug!(
                     context,
                     "net/handle: HandleQueryEntry: {:?}",
@@ -238,6 +325,10 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
                     ht::with_top(|span| span.event("not my id"));
                     return Ok(());
                 }
+                if is_blocked(&context, &message_data.from_agent_id.to_string()) {
+                    ht::with_top(|span| span.event("blocked agent"));
+                    return Ok(());
+                }
                 log_debug!(
                     context,
                     "net/handle: HandleSendMessage: {}",
@@ -253,6 +344,9 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
                 if !is_my_id(&context, &message_data.to_agent_id.to_string()) {
                     return Ok(());
                 }
+                if is_blocked(&context, &message_data.from_agent_id.to_string()) {
+                    return Ok(());
+                }
                 log_debug!(
                     context,
                     "net/handle: SendMessageResult: {}",
@@ -349,6 +443,15 @@ fn get_content_aspects(
     let mut aspects: Vec<EntryAspect> = Vec::new();
 
     if let Some(entry) = state.dht().get(entry_address)? {
+        // Private entries are stored locally but must never be served to others
+        if !entry.entry_type().can_publish(&context) {
+            log_debug!(
+                context,
+                "net/fetch/get_content_aspects: not serving private entry {}",
+                entry_address
+            );
+            return Ok(aspects);
+        }
         // If we have it in the DHT cas that's good,
         // but then we have to get the header like this:
         let headers = state.get_headers(entry_address.clone()).map_err(|error| {
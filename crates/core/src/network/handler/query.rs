@@ -3,8 +3,9 @@ use crate::{
     context::Context,
     entry::CanPublish,
     instance::dispatch_action,
-    network::query::{
-        GetLinksNetworkQuery, GetLinksNetworkResult, NetworkQuery, NetworkQueryResult,
+    network::{
+        handler::parse_content,
+        query::{GetLinksNetworkQuery, GetLinksNetworkResult, NetworkQuery, NetworkQueryResult},
     },
     nucleus,
     workflows::get_entry_result::get_entry_result_workflow_local,
@@ -16,15 +17,36 @@ use holochain_core_types::{
     error::HolochainError,
     network::query::{GetLinkData, GetLinksQueryConfiguration},
 };
-use holochain_json_api::json::JsonString;
 use holochain_persistence_api::cas::content::Address;
 use holochain_wasm_utils::api_serialization::get_entry::{
     GetEntryArgs, GetEntryOptions, GetEntryResultType,
 };
 use lib3h_protocol::data_types::{QueryEntryData, QueryEntryResultData};
-use std::{convert::TryInto, sync::Arc};
+use std::sync::Arc;
 
 pub type LinkTag = String;
+
+/// True if all headers we hold for the given address come from blocked agents.
+/// Such data is hidden from query results.
+pub(crate) fn is_held_for_blocked_agent(context: &Arc<Context>, address: &Address) -> bool {
+    let state = match context.state() {
+        Some(state) => state,
+        None => return false,
+    };
+    let network = state.network();
+    if network.blocked_agents.is_empty() {
+        return false;
+    }
+    state
+        .get_headers(address.clone())
+        .map(|headers| {
+            !headers.is_empty()
+                && headers
+                    .iter()
+                    .all(|header| network.is_authored_by_blocked_agent(header))
+        })
+        .unwrap_or(false)
+}
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn get_links(
     context: &Arc<Context>,
@@ -59,6 +81,7 @@ pub fn get_links(
             .expect("INVALID ATTRIBUTE ON EAV GET, SOMETHING VERY WRONG IN EAV QUERY");
             (eavi.value(), crud, tag)
         })
+        .filter(|(link_add_address, _, _)| !is_held_for_blocked_agent(context, link_add_address))
         //get targets from dht
         .map(|(link_add_address, crud, tag)| {
             let error = format!(
@@ -136,6 +159,9 @@ pub fn get_links(
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 fn get_entry(context: &Arc<Context>, address: Address) -> Option<EntryWithMetaAndHeader> {
+    if is_held_for_blocked_agent(context, &address) {
+        return None;
+    }
     let result = nucleus::actions::get_entry::get_entry_with_meta(&context, address.clone());
     match result {
         Err(error) => {
@@ -183,9 +209,7 @@ fn get_entry(context: &Arc<Context>, address: Address) -> Option<EntryWithMetaAn
 #[autotrace]
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn handle_query_entry_data(query_data: QueryEntryData, context: Arc<Context>) {
-    let query_json =
-        JsonString::from_json(&std::str::from_utf8(&*query_data.query.clone()).unwrap());
-    let action_wrapper = match query_json.clone().try_into() {
+    let action_wrapper = match parse_content(&*query_data.query) {
         Ok(NetworkQuery::GetLinks(link_type, tag, options, query)) => {
             match get_links(
                 &context,
@@ -226,7 +250,7 @@ pub fn handle_query_entry_data(query_data: QueryEntryData, context: Arc<Context>
                 context,
                 "net: Error ({:?}) deserializing Query {:?}",
                 err,
-                query_json
+                String::from_utf8_lossy(&*query_data.query)
             );
             return;
         }
@@ -239,15 +263,12 @@ pub fn handle_query_entry_data(query_data: QueryEntryData, context: Arc<Context>
 #[autotrace]
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn handle_query_entry_result(query_result_data: QueryEntryResultData, context: Arc<Context>) {
-    let query_result_json = JsonString::from_json(
-        std::str::from_utf8(&*query_result_data.clone().query_result).unwrap(),
-    );
     log_trace!(
         context,
         "handle_query_entry_result: {:?}",
         query_result_data
     );
-    let action_wrapper = match query_result_json.clone().try_into() {
+    let action_wrapper = match parse_content(&*query_result_data.query_result) {
         Ok(NetworkQueryResult::Entry(maybe_entry)) => {
             let payload = NetworkQueryResult::Entry(maybe_entry);
             ActionWrapper::new(Action::HandleQuery((
@@ -275,7 +296,7 @@ pub fn handle_query_entry_result(query_result_data: QueryEntryResultData, contex
                 context,
                 "net: Error ({:?}) deserializing QueryResult {:?}",
                 err,
-                query_result_json
+                String::from_utf8_lossy(&*query_result_data.query_result)
             );
             return;
         }
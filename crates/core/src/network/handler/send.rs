@@ -2,7 +2,14 @@ use crate::{
     action::{Action, ActionWrapper},
     context::Context,
     instance::dispatch_action,
-    network::direct_message::DirectMessage,
+    network::{
+        actions::{
+            cap_token_revoked::handle_cap_token_revoked,
+            cap_token_rotated::handle_cap_token_rotated, holding_receipt::handle_holding_receipt,
+        },
+        direct_message::DirectMessage,
+        handler::parse_content,
+    },
     workflows::{
         handle_custom_direct_message::handle_custom_direct_message,
         respond_validation_package_request::respond_validation_package_request,
@@ -10,25 +17,14 @@ use crate::{
 };
 use std::sync::Arc;
 
-use holochain_json_api::{error::JsonError, json::JsonString};
 use lib3h_protocol::data_types::DirectMessageData;
-use std::convert::TryFrom;
-
-#[autotrace]
-#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
-fn parse_direct_message(content: &[u8]) -> Result<DirectMessage, JsonError> {
-    DirectMessage::try_from(JsonString::from_json(
-        std::str::from_utf8(content)
-            .map_err(|error| JsonError::SerializationError(error.to_string()))?,
-    ))
-}
 
 /// We got a ProtocolWrapper::SendMessage, this means somebody initiates message roundtrip
 /// -> we are being called
 #[autotrace]
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn handle_send_message(message_data: DirectMessageData, context: Arc<Context>) {
-    let message = match parse_direct_message(&*message_data.content.clone()) {
+    let message: DirectMessage = match parse_content(&*message_data.content) {
         Ok(message) => message,
         Err(error) => {
             log_error!(
@@ -80,6 +76,37 @@ pub fn handle_send_message(message_data: DirectMessageData, context: Arc<Context
             "net: Got DirectMessage::ValidationPackage as initial message. This should not happen.",
         )
         }
+        DirectMessage::HoldingReceipt(header_address) => handle_holding_receipt(
+            message_data.from_agent_id.into(),
+            message_data.request_id,
+            header_address,
+            context,
+        ),
+        DirectMessage::HoldingReceiptAck => log_error!(
+            context,
+            "net: Got DirectMessage::HoldingReceiptAck as initial message. This should not happen.",
+        ),
+        DirectMessage::CapTokenRevoked(token) => handle_cap_token_revoked(
+            message_data.from_agent_id.into(),
+            message_data.request_id,
+            token,
+            context,
+        ),
+        DirectMessage::CapTokenRevokedAck => log_error!(
+            context,
+            "net: Got DirectMessage::CapTokenRevokedAck as initial message. This should not happen.",
+        ),
+        DirectMessage::CapTokenRotated((old_token, new_token)) => handle_cap_token_rotated(
+            message_data.from_agent_id.into(),
+            message_data.request_id,
+            old_token,
+            new_token,
+            context,
+        ),
+        DirectMessage::CapTokenRotatedAck => log_error!(
+            context,
+            "net: Got DirectMessage::CapTokenRotatedAck as initial message. This should not happen.",
+        ),
     };
 }
 
@@ -88,7 +115,7 @@ pub fn handle_send_message(message_data: DirectMessageData, context: Arc<Context
 #[autotrace]
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn handle_send_message_result(message_data: DirectMessageData, context: Arc<Context>) {
-    let response = match parse_direct_message(&message_data.content.clone()) {
+    let response: DirectMessage = match parse_content(&message_data.content) {
         Ok(message) => message,
         Err(error) => {
             log_error!(
@@ -147,5 +174,24 @@ pub fn handle_send_message_result(message_data: DirectMessageData, context: Arc<
                 ActionWrapper::new(Action::ResolveDirectConnection(message_data.request_id));
             dispatch_action(context.action_channel(), action_wrapper);
         }
+        DirectMessage::HoldingReceipt(_) => log_error!(
+            context,
+            "net: Got DirectMessage::HoldingReceipt as a response. This should not happen.",
+        ),
+        DirectMessage::HoldingReceiptAck
+        | DirectMessage::CapTokenRevokedAck
+        | DirectMessage::CapTokenRotatedAck => {
+            let action_wrapper =
+                ActionWrapper::new(Action::ResolveDirectConnection(message_data.request_id));
+            dispatch_action(context.action_channel(), action_wrapper);
+        }
+        DirectMessage::CapTokenRevoked(_) => log_error!(
+            context,
+            "net: Got DirectMessage::CapTokenRevoked as a response. This should not happen.",
+        ),
+        DirectMessage::CapTokenRotated(_) => log_error!(
+            context,
+            "net: Got DirectMessage::CapTokenRotated as a response. This should not happen.",
+        ),
     };
 }
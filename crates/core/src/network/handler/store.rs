@@ -6,60 +6,99 @@ use crate::{
         },
         pending_validations::PendingValidationStruct,
     },
+    network::{
+        handler::parse_content,
+        proof_of_work::{proof_of_work_from_type_hint, verify_proof_of_work},
+    },
 };
-use holochain_core_types::network::entry_aspect::EntryAspect;
-use holochain_json_api::json::JsonString;
-use lib3h_protocol::data_types::StoreEntryAspectData;
-use std::{
-    convert::{TryFrom, TryInto},
-    sync::Arc,
-};
+use holochain_core_types::{error::HolochainError, network::entry_aspect::EntryAspect};
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use lib3h_protocol::data_types::{EntryAspectData, StoreEntryAspectData};
+use std::{convert::TryFrom, sync::Arc};
+
+/// Parses the aspect and checks that the address it got sent under is the address of its
+/// content and carries a valid proof-of-work. Otherwise a single nonce computed for any
+/// address could be attached to arbitrary content.
+fn parse_aspect_with_proof_of_work(
+    aspect_data: &EntryAspectData,
+    proof_of_work_difficulty: u8,
+) -> Result<EntryAspect, HolochainError> {
+    let aspect: EntryAspect = parse_content(&*aspect_data.aspect)?;
+    let aspect_address: Address = aspect_data.aspect_address.clone().into();
+    if aspect.address() != aspect_address {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Aspect sent as {} has address {}",
+            aspect_address,
+            aspect.address()
+        )));
+    }
+    let nonce = proof_of_work_from_type_hint(&aspect_data.type_hint);
+    if !verify_proof_of_work(&aspect_address, nonce, proof_of_work_difficulty) {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Aspect {} has no valid proof-of-work",
+            aspect_address
+        )));
+    }
+    Ok(aspect)
+}
 
 /// The network requests us to store (i.e. hold) the given entry aspect data.
+/// The holding workflow continues the trace of the given message.
 #[autotrace]
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
-pub fn handle_store(dht_data: StoreEntryAspectData, context: Arc<Context>) {
-    let aspect_json =
-        JsonString::from_json(std::str::from_utf8(&*dht_data.entry_aspect.aspect).unwrap());
-    let maybe_aspect: Result<EntryAspect, _> = aspect_json.clone().try_into();
-    if let Ok(aspect) = maybe_aspect {
-        if context
-            .state()
-            .unwrap()
-            .dht()
-            .get_holding_map()
-            .contains(&aspect)
-        {
-            log_error!(
-                context,
-                "handle_store: Aspect already being held: {:?}, sending single ack back to sim2h",
-                aspect
-            );
-            ack_single(context, aspect);
-            return;
-        }
-        match PendingValidationStruct::try_from(aspect.clone()) {
-            Err(e) => log_error!(
-                context,
-                "net/handle: handle_store: received bad aspect: {:?}",
-                e,
-            ),
-            Ok(pending) => {
-                log_debug!(
+pub fn handle_store(
+    dht_data: StoreEntryAspectData,
+    trace: ht::EncodedSpanWrap<()>,
+    context: Arc<Context>,
+) {
+    let proof_of_work_difficulty = context.state().unwrap().network().proof_of_work_difficulty;
+    match parse_aspect_with_proof_of_work(&dht_data.entry_aspect, proof_of_work_difficulty) {
+        Ok(aspect) => {
+            // The author's chain moved on if this is newer than the packages we have from it
+            context
+                .state()
+                .unwrap()
+                .network()
+                .validation_package_cache
+                .see_header(aspect.header());
+            if context
+                .state()
+                .unwrap()
+                .dht()
+                .get_holding_map()
+                .contains(&aspect)
+            {
+                log_error!(
                     context,
-                    "net/handle: handle_store: Adding {} for aspect {:?} to holding queue...",
-                    pending.workflow,
-                    aspect,
+                    "handle_store: Aspect already being held: {:?}, sending single ack back to sim2h",
+                    aspect
                 );
-                dispatch_queue_holding_workflow(Arc::new(pending), None, context);
+                ack_single(context, aspect);
+                return;
+            }
+            match PendingValidationStruct::try_from(aspect.clone()) {
+                Err(e) => log_error!(
+                    context,
+                    "net/handle: handle_store: received bad aspect: {:?}",
+                    e,
+                ),
+                Ok(mut pending) => {
+                    pending.trace = Some(trace);
+                    log_debug!(
+                        context,
+                        "net/handle: handle_store: Adding {} for aspect {:?} to holding queue...",
+                        pending.workflow,
+                        aspect,
+                    );
+                    dispatch_queue_holding_workflow(Arc::new(pending), None, context);
+                }
             }
         }
-    } else {
-        log_error!(
+        Err(error) => log_warn!(
             context,
-            "net/handle_store: Unable to parse entry aspect: {}",
-            aspect_json
-        )
+            "net/handle_store: Dropping entry aspect: {}",
+            error
+        ),
     }
 }
 
@@ -142,3 +181,88 @@ pub fn handle_store_meta(dht_meta_data: DhtMetaData, context: Arc<Context>) {
     }
 }
 */
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        action::{Action, ActionWrapper},
+        instance::{tests::test_context_with_channels, Observer},
+        network::{handler::create_handler, reducers::publish::entry_data_to_entry_aspect_data},
+        state::StateWrapper,
+    };
+    use crossbeam_channel::unbounded;
+    use holochain_core_types::{
+        chain_header::{test_chain_header, test_chain_header_with_sig},
+        entry::test_entry,
+    };
+    use holochain_json_api::json::JsonString;
+    use holochain_locksmith::RwLock;
+    use lib3h_protocol::protocol_server::Lib3hServerProtocol;
+
+    #[test]
+    fn accepts_aspects_with_proof_of_work_over_their_address() {
+        let aspect = EntryAspect::Header(test_chain_header());
+        let aspect_data = entry_data_to_entry_aspect_data(&aspect, 8);
+        assert_eq!(
+            parse_aspect_with_proof_of_work(&aspect_data, 8).unwrap(),
+            aspect
+        );
+
+        let without_work = entry_data_to_entry_aspect_data(&aspect, 0);
+        assert!(parse_aspect_with_proof_of_work(&without_work, 0).is_ok());
+        assert!(parse_aspect_with_proof_of_work(&without_work, 8).is_err());
+    }
+
+    #[test]
+    fn rejects_valid_proof_of_work_paired_with_other_content() {
+        let aspect = EntryAspect::Header(test_chain_header());
+        let mut aspect_data = entry_data_to_entry_aspect_data(&aspect, 8);
+        let spam = EntryAspect::Header(test_chain_header_with_sig("spam", None));
+        let spam_json: JsonString = spam.into();
+        aspect_data.aspect = spam_json.to_bytes().into();
+
+        assert!(parse_aspect_with_proof_of_work(&aspect_data, 8).is_err());
+        assert!(parse_aspect_with_proof_of_work(&aspect_data, 0).is_err());
+    }
+
+    #[test]
+    fn holding_workflows_continue_the_trace_of_the_store_message() {
+        let (action_tx, action_rx) = unbounded::<ht::SpanWrap<ActionWrapper>>();
+        let (observer_tx, _observer_rx) = unbounded::<Observer>();
+        let mut context =
+            (*test_context_with_channels("holder", &action_tx.into(), &observer_tx, None)).clone();
+        let state = StateWrapper::new(Arc::new(context.clone()));
+        context.set_state(Arc::new(RwLock::new(state)));
+        let context = Arc::new(context);
+
+        // A span context as the author's node encodes it
+        let trace: ht::EncodedSpanWrap<()> = serde_json::from_str(
+            r#"{"data":null,"span_context":[149,217,162,104,57,50,215,185,128,95,199,101,105,81,143,213,10,14,105,185,134,247,194,247,0,0,0,0,0,0,0,0,1,0,0,0,0]}"#,
+        )
+        .unwrap();
+        let dna_address = "test-dna";
+        let aspect = EntryAspect::Content(test_entry(), test_chain_header());
+        let message = trace.clone().map(|_| {
+            Lib3hServerProtocol::HandleStoreEntryAspect(StoreEntryAspectData {
+                request_id: "store-1".into(),
+                space_address: Address::from(dna_address).into(),
+                provider_agent_id: String::from("author").into(),
+                entry_address: test_entry().address().into(),
+                entry_aspect: entry_data_to_entry_aspect_data(&aspect, 0),
+            })
+        });
+
+        let mut handler = create_handler(&context, dna_address.to_string());
+        handler.handle(Ok(message)).unwrap();
+
+        let pending = action_rx
+            .try_iter()
+            .find_map(|action_wrapper| match action_wrapper.data.action() {
+                Action::QueueHoldingWorkflow((pending, _)) => Some(pending.clone()),
+                _ => None,
+            })
+            .expect("the store message should have queued a holding workflow");
+        assert_eq!(pending.trace, Some(trace));
+    }
+}
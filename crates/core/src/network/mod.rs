@@ -3,9 +3,12 @@ pub mod direct_message;
 pub mod entry_with_header;
 #[autotrace]
 pub mod handler;
+pub mod proof_of_work;
 #[autotrace]
 pub mod reducers;
 pub mod state;
+pub mod traffic;
+pub mod validation_package_cache;
 #[cfg(test)]
 pub mod test_utils;
 
@@ -13,26 +16,27 @@ pub use holochain_core_types::network::{entry_aspect, query};
 
 #[cfg(test)]
 pub mod tests {
+    #[cfg(feature = "broken-tests")]
+    use crate::{
+        agent::actions::commit::commit_entry, instance::tests::test_instance_and_context_by_name,
+        network::actions::publish::publish,
+    };
     use crate::{
-        agent::actions::commit::commit_entry,
-        instance::tests::test_instance_and_context_by_name,
         network::{
-            actions::{
-                publish::publish,
-                query::{query, QueryMethod},
-            },
+            actions::query::{query, QueryMethod},
             query::{
                 GetLinksNetworkQuery, GetLinksNetworkResult, GetLinksQueryConfiguration,
                 NetworkQueryResult,
             },
             test_utils::test_wat_always_valid,
         },
+        scenario::Scenario,
     };
     use holochain_core_types::{
         agent::test_agent_id,
         chain_header::test_chain_header,
         crud_status::CrudStatus,
-        entry::{entry_type::test_app_entry_type, test_entry, Entry, EntryWithMetaAndHeader},
+        entry::{entry_type::test_app_entry_type, test_entry, Entry},
         link::link_data::LinkData,
     };
     use holochain_json_api::json::JsonString;
@@ -56,45 +60,26 @@ pub mod tests {
             .try_init();
     }
 
-    // TODO: Should wait for a success or saturation response from the network module after Publish
     #[test]
     #[ignore]
     fn get_entry_roundtrip() {
-        let netname = Some("get_entry_roundtrip");
-        let mut dna = create_test_dna_with_wat("test_zome", None);
-        dna.uuid = netname.unwrap().to_string();
-        let (_instance1, context1) =
-            test_instance_and_context_by_name(dna.clone(), "alice1", netname).unwrap();
-        let (_instance2, context2) =
-            test_instance_and_context_by_name(dna.clone(), "bob1", netname).unwrap();
-
-        // Create Entry & metadata
-        let entry = test_entry();
+        let dna = create_test_dna_with_wat("test_zome", Some(&test_wat_always_valid()));
+        let mut s = Scenario::new(dna, &["alice1", "bob1"]).unwrap();
 
         // Store it on the network
-        let result = context1.block_on(commit_entry(entry.clone(), None, &context1));
-        assert!(result.is_ok(), "commit_entry() result = {:?}", result);
-        let result = context1.block_on(publish(entry.address(), &context1));
-        assert!(result.is_ok(), "publish() result = {:?}", result);
-
-        // TODO: Should wait for a success or saturation response from the network module instead
-        // std::thread::sleep(std::time::Duration::from_millis(2000));
+        let entry = test_entry();
+        s.commit("alice1", &entry).unwrap();
+        s.consistency().unwrap();
 
         // Get it from the network
-        // HACK: doing a loop because publish returns before actual confirmation from the network
-        let mut maybe_entry_with_meta: Option<EntryWithMetaAndHeader> = None;
-        let mut loop_count = 0;
-        while maybe_entry_with_meta.is_none() && loop_count < 10 {
-            loop_count += 1;
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            let result = context2.block_on(query(
-                context2.clone(),
-                QueryMethod::Entry(entry.address().clone()),
-                Default::default(),
-            ));
-            assert!(result.is_ok(), "get_entry() result = {:?}", result);
-            maybe_entry_with_meta = unwrap_to!(result.unwrap()=>NetworkQueryResult::Entry).clone();
-        }
+        let context2 = s.agent("bob1").unwrap().context.clone();
+        let result = context2.block_on(query(
+            context2.clone(),
+            QueryMethod::Entry(entry.address()),
+            Default::default(),
+        ));
+        assert!(result.is_ok(), "get_entry() result = {:?}", result);
+        let maybe_entry_with_meta = unwrap_to!(result.unwrap()=>NetworkQueryResult::Entry).clone();
         assert!(
             maybe_entry_with_meta.is_some(),
             "maybe_entry_with_meta = {:?}",
@@ -262,14 +247,8 @@ pub mod tests {
     #[test]
     #[ignore]
     fn get_links_roundtrip() {
-        let netname = Some("get_links_roundtrip");
-        let wat = &test_wat_always_valid();
-        let mut dna = create_test_dna_with_wat("test_zome", Some(wat));
-        dna.uuid = netname.unwrap().to_string();
-        let (_instance1, context1) =
-            test_instance_and_context_by_name(dna.clone(), "alex2", netname).unwrap();
-        let (_instance2, context2) =
-            test_instance_and_context_by_name(dna.clone(), "billy2", netname).unwrap();
+        let dna = create_test_dna_with_wat("test_zome", Some(&test_wat_always_valid()));
+        let mut s = Scenario::new(dna, &["alex2", "billy2"]).unwrap();
 
         let mut entry_addresses: Vec<Address> = Vec::new();
         for i in 0..3 {
@@ -277,12 +256,9 @@ pub mod tests {
                 test_app_entry_type(),
                 JsonString::from_json(&format!("entry{} value", i)),
             );
-            let address = context1
-                .block_on(commit_entry(entry.clone(), None, &context1))
+            let address = s
+                .commit("alex2", &entry)
                 .expect("Could not commit entry for testing");
-            let _ = context1
-                .block_on(publish(entry.address(), &context1))
-                .expect("Could not publish entry for testing");
             entry_addresses.push(address);
         }
 
@@ -303,24 +279,11 @@ pub mod tests {
             test_agent_id(),
         );
 
-        // Store link1 on the network
-        println!("\n add_link(link1) ...");
-        let entry = Entry::LinkAdd(link1);
-        let result = context1.block_on(commit_entry(entry.clone(), None, &context1));
-        assert!(result.is_ok(), "commit_entry() result = {:?}", result);
-        let result = context1.block_on(publish(entry.address(), &context1));
-        assert!(result.is_ok(), "publish() result = {:?}", result);
-
-        // Store link2 on the network
-        println!("\n add_link(link2) ...");
-        let entry = Entry::LinkAdd(link2);
-        let result = context1.block_on(commit_entry(entry.clone(), None, &context1));
-        assert!(result.is_ok(), "commit_entry() result = {:?}", result);
-        let result = context1.block_on(publish(entry.address(), &context1));
-        assert!(result.is_ok(), "publish() result = {:?}", result);
-
-        // TODO: Should wait for a success or saturation response from the network module instead
-        // std::thread::sleep(std::time::Duration::from_millis(1000));
+        // Store the links on the network
+        s.commit("alex2", &Entry::LinkAdd(link1)).unwrap();
+        s.commit("alex2", &Entry::LinkAdd(link2)).unwrap();
+        s.consistency().unwrap();
+        let context2 = s.agent("billy2").unwrap().context.clone();
 
         println!("\n get_links() ...");
         let get_links_args = GetLinksArgs {
@@ -0,0 +1,107 @@
+//! Optional anti-spam proof-of-work on published aspects.
+//!
+//! A DNA can set the magic property `publish_proof_of_work_difficulty` to the number of
+//! leading zero bits that the SHA-256 hash of `"<aspect address>:<nonce>"` has to have.
+//! Publishers search for such a nonce and attach it to the aspect's type hint, holders check
+//! it in the store handler before queueing the aspect for validation. Verifying takes a single
+//! hash while producing the proof takes about 2^difficulty of them, which makes flooding an
+//! open network with aspects expensive.
+use holochain_core_types::dna::Dna;
+use holochain_persistence_api::cas::content::Address;
+use lib3h_sodium::{hash::sha256, secbuf::SecBuf};
+
+/// Name of the DNA property that switches on proof-of-work for publishes.
+pub const PROOF_OF_WORK_PROPERTY: &str = "publish_proof_of_work_difficulty";
+
+/// Upper bound for the difficulty so that a misconfigured DNA can't make publishing hang.
+pub const MAX_PROOF_OF_WORK_DIFFICULTY: u8 = 32;
+
+const TYPE_HINT_SEPARATOR: &str = "#pow:";
+
+/// Reads the required proof-of-work difficulty from the DNA properties.
+/// Returns 0 (no proof-of-work) if the property is not set.
+pub fn proof_of_work_difficulty(dna: &Dna) -> u8 {
+    dna.properties
+        .as_object()
+        .and_then(|props| props.get(PROOF_OF_WORK_PROPERTY))
+        .and_then(|difficulty| difficulty.as_u64())
+        .map(|difficulty| difficulty.min(u64::from(MAX_PROOF_OF_WORK_DIFFICULTY)) as u8)
+        .unwrap_or(0)
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut zeros = 0;
+    for byte in bytes {
+        zeros += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    zeros
+}
+
+fn work_hash_zero_bits(aspect_address: &Address, nonce: u64) -> u32 {
+    let mut input = SecBuf::with_insecure_from_string(format!("{}:{}", aspect_address, nonce));
+    let mut output = SecBuf::with_insecure(32);
+    sha256(&mut input, &mut output).expect("Could not hash proof-of-work input");
+    let hash = output.read_lock();
+    leading_zero_bits(&**hash)
+}
+
+/// Searches the first nonce that satisfies the given difficulty for the aspect address.
+pub fn compute_proof_of_work(aspect_address: &Address, difficulty: u8) -> u64 {
+    (0..)
+        .find(|nonce| work_hash_zero_bits(aspect_address, *nonce) >= u32::from(difficulty))
+        .expect("nonce space exhausted")
+}
+
+/// Checks the nonce against the aspect address. A difficulty of 0 accepts anything.
+pub fn verify_proof_of_work(aspect_address: &Address, nonce: Option<u64>, difficulty: u8) -> bool {
+    if difficulty == 0 {
+        return true;
+    }
+    nonce
+        .map(|nonce| work_hash_zero_bits(aspect_address, nonce) >= u32::from(difficulty))
+        .unwrap_or(false)
+}
+
+/// Appends the nonce to an aspect type hint, e.g. `content#pow:1234`.
+pub fn type_hint_with_proof_of_work(type_hint: String, nonce: u64) -> String {
+    format!("{}{}{}", type_hint, TYPE_HINT_SEPARATOR, nonce)
+}
+
+/// Extracts the nonce attached by `type_hint_with_proof_of_work`, if there is one.
+pub fn proof_of_work_from_type_hint(type_hint: &str) -> Option<u64> {
+    type_hint
+        .rfind(TYPE_HINT_SEPARATOR)
+        .and_then(|index| type_hint[index + TYPE_HINT_SEPARATOR.len()..].parse().ok())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn computed_proof_of_work_verifies() {
+        let address = Address::from("QmSomeAspectAddress");
+        let nonce = compute_proof_of_work(&address, 8);
+        assert!(verify_proof_of_work(&address, Some(nonce), 8));
+        assert!(!verify_proof_of_work(&address, None, 8));
+        assert!(verify_proof_of_work(&address, None, 0));
+
+        let hint = type_hint_with_proof_of_work("content".to_string(), nonce);
+        assert_eq!(proof_of_work_from_type_hint(&hint), Some(nonce));
+        assert_eq!(proof_of_work_from_type_hint("content"), None);
+    }
+
+    #[test]
+    fn difficulty_is_read_from_dna_properties() {
+        let mut dna = Dna::new();
+        assert_eq!(proof_of_work_difficulty(&dna), 0);
+        dna.properties = json!({ "publish_proof_of_work_difficulty": 12 });
+        assert_eq!(proof_of_work_difficulty(&dna), 12);
+        dna.properties = json!({ "publish_proof_of_work_difficulty": 1000 });
+        assert_eq!(proof_of_work_difficulty(&dna), MAX_PROOF_OF_WORK_DIFFICULTY);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    network::{direct_message::DirectMessage, state::NetworkState},
+    state::State,
+};
+use holochain_core_types::error::HolochainError;
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn reduce_block_agent(
+    network_state: &mut NetworkState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let agent = unwrap_to!(action => Action::BlockAgent);
+
+    network_state.blocked_agents.insert(agent.clone());
+
+    // Drop all open connections with the blocked agent.
+    // Pending custom sends get an error so the zome call waiting for them returns right away.
+    let dropped_ids: Vec<String> = network_state
+        .direct_message_peers
+        .iter()
+        .filter(|(_, peer)| *peer == agent)
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in dropped_ids {
+        network_state.direct_message_peers.remove(&id);
+        network_state.direct_message_timeouts.remove(&id);
+        if let Some(DirectMessage::Custom(_)) = network_state.direct_message_connections.remove(&id)
+        {
+            network_state.custom_direct_message_replys.insert(
+                id,
+                Err(HolochainError::ErrorGeneric(format!(
+                    "Agent {} is blocked",
+                    agent
+                ))),
+            );
+        }
+    }
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn reduce_unblock_agent(
+    network_state: &mut NetworkState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let agent = unwrap_to!(action => Action::UnblockAgent);
+
+    network_state.blocked_agents.remove(agent);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instance::tests::test_context, network::direct_message::CustomDirectMessage,
+        state::test_store,
+    };
+    use holochain_persistence_api::cas::content::Address;
+    use std::time::Duration;
+
+    #[test]
+    fn block_agent_drops_connections_and_unblock_lifts_block() {
+        let context = test_context("alice", None);
+        let store = test_store(context);
+        let mut network_state = (*store.network()).clone();
+        let bob = Address::from("bob");
+        let carol = Address::from("carol");
+
+        let custom = DirectMessage::Custom(CustomDirectMessage {
+            zome: "zome".to_string(),
+            payload: Ok("hi".to_string()),
+        });
+        for (id, peer) in &[("to-bob", &bob), ("to-carol", &carol)] {
+            network_state
+                .direct_message_connections
+                .insert(id.to_string(), custom.clone());
+            network_state
+                .direct_message_peers
+                .insert(id.to_string(), (*peer).clone());
+            network_state
+                .direct_message_timeouts
+                .insert(id.to_string(), (store.clock.now(), Duration::from_secs(10)));
+        }
+
+        reduce_block_agent(
+            &mut network_state,
+            &store,
+            &ActionWrapper::new(Action::BlockAgent(bob.clone())),
+        );
+        assert!(network_state.is_agent_blocked(&bob));
+        assert!(!network_state.is_agent_blocked(&carol));
+        assert!(network_state
+            .direct_message_connections
+            .get("to-bob")
+            .is_none());
+        assert!(network_state
+            .direct_message_connections
+            .get("to-carol")
+            .is_some());
+        assert!(network_state
+            .custom_direct_message_replys
+            .get("to-bob")
+            .unwrap()
+            .is_err());
+
+        reduce_unblock_agent(
+            &mut network_state,
+            &store,
+            &ActionWrapper::new(Action::UnblockAgent(bob.clone())),
+        );
+        assert!(!network_state.is_agent_blocked(&bob));
+    }
+}
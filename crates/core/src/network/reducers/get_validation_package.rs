@@ -30,14 +30,14 @@ fn inner(
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn reduce_get_validation_package(
     network_state: &mut NetworkState,
-    _root_state: &State,
+    root_state: &State,
     action_wrapper: &ActionWrapper,
 ) {
     let action = action_wrapper.action();
     let (key, header) = unwrap_to!(action => crate::action::Action::GetValidationPackage);
 
     let timeout = (
-        SystemTime::now(),
+        root_state.clock.now(),
         Duration::from_millis(GET_VALIDATION_PACKAGE_MESSAGE_TIMEOUT_MS),
     );
 
@@ -72,6 +72,7 @@ pub fn reduce_get_validation_package_timeout(
         return;
     }
 
+    network_state.record_timeout("validation_package", None);
     network_state.get_validation_package_results.insert(
         key.clone(),
         Some(Err(HolochainError::Timeout(format!(
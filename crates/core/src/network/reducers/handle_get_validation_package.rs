@@ -3,7 +3,7 @@ use crate::{action::ActionWrapper, network::state::NetworkState, state::State};
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn reduce_handle_get_validation_package(
     network_state: &mut NetworkState,
-    _root_state: &State,
+    root_state: &State,
     action_wrapper: &ActionWrapper,
 ) {
     let action = action_wrapper.action();
@@ -15,6 +15,10 @@ pub fn reduce_handle_get_validation_package(
         .insert(address.clone(), Some(Ok(maybe_validation_package.clone())));
 
     if let Some(validation_package) = maybe_validation_package {
-        network_state.cache_validation(responder.clone(), validation_package);
+        network_state.cache_validation(
+            responder.clone(),
+            validation_package,
+            root_state.clock.now(),
+        );
     }
 }
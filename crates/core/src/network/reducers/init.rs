@@ -1,6 +1,10 @@
 use crate::{
     action::{Action, ActionWrapper},
-    network::state::NetworkState,
+    network::{
+        proof_of_work::proof_of_work_difficulty, state::NetworkState,
+        validation_package_cache::ValidationPackageCache,
+    },
+    resource_limits::BandwidthThrottle,
     state::State,
 };
 use holochain_net::{
@@ -9,6 +13,7 @@ use holochain_net::{
 use holochain_persistence_api::cas::content::AddressableContent;
 use lib3h_protocol::{data_types::SpaceData, protocol_client::Lib3hClientProtocol, Address};
 use log::{debug, error, info};
+use std::sync::Arc;
 
 #[autotrace]
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
@@ -91,6 +96,14 @@ pub fn reduce_init(state: &mut NetworkState, root_state: &State, action_wrapper:
 
     state.dna_address = Some(network_settings.dna_address.clone());
     state.agent_id = Some(network_settings.agent_id.clone());
+    state.proof_of_work_difficulty = proof_of_work_difficulty(dna);
+    state.bandwidth_throttle = network_settings
+        .bandwidth_limit
+        .map(|bytes_per_second| Arc::new(BandwidthThrottle::new(bytes_per_second)));
+    state.instance_name = network_settings.instance_name.clone();
+    state.validation_package_cache = Arc::new(ValidationPackageCache::new(
+        network_settings.validation_package_cache_size,
+    ));
 
     if let Err(err) = network.send(ht::top_follower("reduce_init").wrap(json).into()) {
         error!("Could not send JsonProtocol::TrackDna. Error: {:?}", err);
@@ -162,6 +175,9 @@ pub mod test {
             dna_address,
             agent_id,
             handler,
+            bandwidth_limit: None,
+            instance_name: context.get_instance_name(),
+            validation_package_cache_size: 0,
         };
         let action_wrapper = ActionWrapper::new(Action::InitNetwork(network_settings));
 
@@ -186,6 +202,9 @@ pub mod test {
             dna_address,
             agent_id,
             handler,
+            bandwidth_limit: None,
+            instance_name: context.get_instance_name(),
+            validation_package_cache_size: 0,
         };
         let action_wrapper = ActionWrapper::new(Action::InitNetwork(network_settings));
 
@@ -219,6 +238,9 @@ pub mod test {
             dna_address,
             agent_id,
             handler,
+            bandwidth_limit: None,
+            instance_name: context.get_instance_name(),
+            validation_package_cache_size: 0,
         };
         let action_wrapper = ActionWrapper::new(Action::InitNetwork(network_settings));
 
@@ -1,3 +1,4 @@
+pub mod block_agent;
 pub mod clear;
 pub mod clear_action_response;
 pub mod get_validation_package;
@@ -19,9 +20,11 @@ pub mod shutdown;
 
 use crate::{
     action::{Action, ActionWrapper, NetworkReduceFn},
+    causal_log::variant_name,
     network::{
         direct_message::DirectMessage,
         reducers::{
+            block_agent::{reduce_block_agent, reduce_unblock_agent},
             clear_action_response::reduce_clear_action_response,
             get_validation_package::{
                 reduce_get_validation_package, reduce_get_validation_package_timeout,
@@ -43,11 +46,13 @@ use crate::{
             shutdown::reduce_shutdown,
         },
         state::NetworkState,
+        traffic::TrafficEvent,
     },
     state::State,
 };
 use holochain_core_types::error::HolochainError;
 use holochain_json_api::json::JsonString;
+use holochain_metrics::prometheus;
 use holochain_net::connection::net_connection::NetSend;
 
 use lib3h_protocol::{data_types::DirectMessageData, protocol_client::Lib3hClientProtocol};
@@ -67,6 +72,7 @@ use std::{
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<NetworkReduceFn> {
     match action_wrapper.action() {
+        Action::BlockAgent(_) => Some(reduce_block_agent),
         Action::ClearActionResponse(_) => Some(reduce_clear_action_response),
         Action::ClearQueryResult(_) => Some(reduce_clear_query_result),
         Action::ClearValidationPackageResult(_) => Some(reduce_clear_validation_package_result),
@@ -90,6 +96,7 @@ fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<NetworkReduceFn> {
         Action::SendDirectMessage(_) => Some(reduce_send_direct_message),
         Action::SendDirectMessageTimeout(_) => Some(reduce_send_direct_message_timeout),
         Action::ShutdownNetwork => Some(reduce_shutdown),
+        Action::UnblockAgent(_) => Some(reduce_unblock_agent),
         _ => None,
     }
 }
@@ -120,7 +127,31 @@ pub fn send(
     network_state: &mut NetworkState,
     msg: Lib3hClientProtocol,
 ) -> Result<(), HolochainError> {
-    network_state
+    let bytes = serde_json::to_vec(&msg)
+        .map(|bytes| bytes.len())
+        .unwrap_or(0);
+    // Holds up this instance's action loop only, so other instances keep their bandwidth
+    if let Some(throttle) = network_state.bandwidth_throttle.as_ref() {
+        throttle.throttle(bytes);
+    }
+    prometheus::increment_counter(
+        "holochain_network_messages_sent_total",
+        "Messages sent to the network",
+        &[("instance", &network_state.instance_name)],
+    );
+    let message_type = variant_name(&msg);
+    let peer = match msg {
+        Lib3hClientProtocol::SendDirectMessage(ref data)
+        | Lib3hClientProtocol::HandleSendDirectMessageResult(ref data) => {
+            Some(data.to_agent_id.to_string())
+        }
+        _ => None,
+    };
+    let traffic = network_state.traffic.clone();
+    let peer = peer.as_ref().map(String::as_str);
+    let clock = network_state.clock.clone();
+    traffic.record(clock.now(), &message_type, peer, TrafficEvent::Sent(bytes));
+    let result = network_state
         .network
         .as_mut()
         .map(|network| {
@@ -134,7 +165,11 @@ pub fn send(
                 .send(span.wrap(msg).into())
                 .map_err(|error| HolochainError::IoError(error.to_string()))
         })
-        .ok_or_else(|| HolochainError::ErrorGeneric("Network not initialized".to_string()))?
+        .ok_or_else(|| HolochainError::ErrorGeneric("Network not initialized".to_string()))?;
+    if result.is_err() {
+        traffic.record(clock.now(), &message_type, peer, TrafficEvent::Error);
+    }
+    result
 }
 
 /// Sends the given DirectMessage to the node given by to_agent_id.
@@ -149,6 +184,12 @@ pub fn send_message(
     message: DirectMessage,
     timeout: (SystemTime, Duration),
 ) -> Result<(), HolochainError> {
+    if network_state.is_agent_blocked(to_agent_id) {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Agent {} is blocked",
+            to_agent_id
+        )));
+    }
     let id = nanoid::simple();
 
     let content_json_string: JsonString = message.to_owned().into();
@@ -168,6 +209,9 @@ pub fn send_message(
     network_state
         .direct_message_connections
         .insert(id.clone(), message);
+    network_state
+        .direct_message_peers
+        .insert(id.clone(), to_agent_id.clone());
     network_state.direct_message_timeouts.insert(id, timeout);
 
     Ok(())
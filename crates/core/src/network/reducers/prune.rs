@@ -8,7 +8,7 @@ use std::time::Duration;
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn reduce_prune(
     network_state: &mut NetworkState,
-    _root_state: &State,
+    root_state: &State,
     action_wrapper: &ActionWrapper,
 ) {
     assert_eq!(action_wrapper.action(), &Action::Prune);
@@ -17,7 +17,7 @@ pub fn reduce_prune(
         .actions
         .iter()
         .filter(|(_, response)| {
-            if let Ok(elapsed) = response.created_at.elapsed() {
+            if let Ok(elapsed) = root_state.clock.elapsed(response.created_at) {
                 if elapsed > Duration::from_millis(ACTION_PRUNE_MS) {
                     return false;
                 }
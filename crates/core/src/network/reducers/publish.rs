@@ -4,6 +4,7 @@ use crate::{
         actions::NetworkActionResponse,
         entry_aspect::EntryAspect,
         entry_with_header::{fetch_entry_with_header, EntryWithHeader},
+        proof_of_work::{compute_proof_of_work, type_hint_with_proof_of_work},
         reducers::send,
         state::NetworkState,
     },
@@ -25,9 +26,17 @@ use crate::network::actions::Response;
 use holochain_persistence_api::cas::content::{Address, AddressableContent};
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
-pub fn entry_data_to_entry_aspect_data(ea: &EntryAspect) -> EntryAspectData {
-    let type_hint = ea.type_hint();
+pub fn entry_data_to_entry_aspect_data(
+    ea: &EntryAspect,
+    proof_of_work_difficulty: u8,
+) -> EntryAspectData {
     let aspect_address = ea.address();
+    let type_hint = if proof_of_work_difficulty > 0 {
+        let nonce = compute_proof_of_work(&aspect_address, proof_of_work_difficulty);
+        type_hint_with_proof_of_work(ea.type_hint(), nonce)
+    } else {
+        ea.type_hint()
+    };
     let ts: DateTime<FixedOffset> = ea.header().timestamp().into();
     let aspect_json: JsonString = ea.into();
     EntryAspectData {
@@ -51,10 +60,13 @@ fn publish_entry(
             provider_agent_id: network_state.agent_id.clone().unwrap().into(),
             entry: EntryData {
                 entry_address: entry_with_header.entry.address().into(),
-                aspect_list: vec![entry_data_to_entry_aspect_data(&EntryAspect::Content(
-                    entry_with_header.entry.clone(),
-                    entry_with_header.header.clone(),
-                ))],
+                aspect_list: vec![entry_data_to_entry_aspect_data(
+                    &EntryAspect::Content(
+                        entry_with_header.entry.clone(),
+                        entry_with_header.header.clone(),
+                    ),
+                    network_state.proof_of_work_difficulty,
+                )],
             },
         }),
     )
@@ -91,7 +103,10 @@ fn publish_update_delete_meta(
             provider_agent_id: network_state.agent_id.clone().unwrap().into(),
             entry: EntryData {
                 entry_address: orig_entry_address.into(),
-                aspect_list: vec![entry_data_to_entry_aspect_data(&aspect)],
+                aspect_list: vec![entry_data_to_entry_aspect_data(
+                    &aspect,
+                    network_state.proof_of_work_difficulty,
+                )],
             },
         }),
     )?;
@@ -132,7 +147,10 @@ fn publish_link_meta(
             provider_agent_id: network_state.agent_id.clone().unwrap().into(),
             entry: EntryData {
                 entry_address: base.into(),
-                aspect_list: vec![entry_data_to_entry_aspect_data(&aspect)],
+                aspect_list: vec![entry_data_to_entry_aspect_data(
+                    &aspect,
+                    network_state.proof_of_work_difficulty,
+                )],
             },
         }),
     )
@@ -195,10 +213,13 @@ pub fn reduce_publish(
     let result = reduce_publish_inner(network_state, root_state, &address);
     network_state.actions.insert(
         action_wrapper.clone(),
-        Response::from(NetworkActionResponse::Publish(match result {
-            Ok(_) => Ok(address.clone()),
-            Err(e) => Err(HolochainError::ErrorGeneric(e.to_string())),
-        })),
+        Response::new(
+            NetworkActionResponse::Publish(match result {
+                Ok(_) => Ok(address.clone()),
+                Err(e) => Err(HolochainError::ErrorGeneric(e.to_string())),
+            }),
+            root_state.clock.now(),
+        ),
     );
 }
 
@@ -209,6 +230,7 @@ mod tests {
     use crate::{
         action::{Action, ActionWrapper},
         instance::tests::test_context,
+        network::proof_of_work::{proof_of_work_from_type_hint, verify_proof_of_work},
         state::test_store,
     };
     use chrono::{offset::FixedOffset, DateTime};
@@ -231,7 +253,7 @@ mod tests {
     fn can_convert_into_entry_aspect_data() {
         let chain_header = test_chain_header();
         let aspect = EntryAspect::Header(chain_header.clone());
-        let aspect_data: EntryAspectData = entry_data_to_entry_aspect_data(&aspect);
+        let aspect_data: EntryAspectData = entry_data_to_entry_aspect_data(&aspect, 0);
         let aspect_json: JsonString = aspect.clone().into();
         let ts: DateTime<FixedOffset> = chain_header.timestamp().into();
         assert_eq!(aspect_data.type_hint, aspect.type_hint());
@@ -242,4 +264,13 @@ mod tests {
         assert_eq!(*aspect_data.aspect, aspect_json.to_bytes());
         assert_eq!(aspect_data.publish_ts, ts.timestamp() as u64);
     }
+
+    #[test]
+    fn attaches_proof_of_work_to_entry_aspect_data() {
+        let aspect = EntryAspect::Header(test_chain_header());
+        let aspect_data = entry_data_to_entry_aspect_data(&aspect, 8);
+        let nonce = proof_of_work_from_type_hint(&aspect_data.type_hint);
+        assert!(nonce.is_some());
+        assert!(verify_proof_of_work(&aspect.address(), nonce, 8));
+    }
 }
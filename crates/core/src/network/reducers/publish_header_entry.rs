@@ -36,9 +36,10 @@ fn publish_header(
             provider_agent_id: network_state.agent_id.clone().unwrap().into(),
             entry: EntryData {
                 entry_address: entry.address().into(),
-                aspect_list: vec![entry_data_to_entry_aspect_data(&EntryAspect::Content(
-                    entry, header,
-                ))],
+                aspect_list: vec![entry_data_to_entry_aspect_data(
+                    &EntryAspect::Content(entry, header),
+                    network_state.proof_of_work_difficulty,
+                )],
             },
         }),
     )
@@ -67,10 +68,13 @@ pub fn reduce_publish_header_entry(
     let result = reduce_publish_header_entry_inner(network_state, root_state, &address);
     network_state.actions.insert(
         action_wrapper.clone(),
-        Response::from(NetworkActionResponse::PublishHeaderEntry(match result {
-            Ok(_) => Ok(address.clone()),
-            Err(e) => Err(HolochainError::ErrorGeneric(e.to_string())),
-        })),
+        Response::new(
+            NetworkActionResponse::PublishHeaderEntry(match result {
+                Ok(_) => Ok(address.clone()),
+                Err(e) => Err(HolochainError::ErrorGeneric(e.to_string())),
+            }),
+            root_state.clock.now(),
+        ),
     );
 }
 
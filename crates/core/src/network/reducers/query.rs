@@ -77,6 +77,7 @@ pub fn reduce_query_timeout(
     }
 
     if network_state.get_query_results.get(key).unwrap().is_none() {
+        network_state.record_timeout("query", None);
         network_state.get_query_results.insert(
             key.clone(),
             Some(Err(HolochainError::Timeout(format!(
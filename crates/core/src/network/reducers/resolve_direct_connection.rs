@@ -11,4 +11,5 @@ pub fn reduce_resolve_direct_connection(
 
     network_state.direct_message_connections.remove(id);
     network_state.direct_message_timeouts.remove(id);
+    network_state.direct_message_peers.remove(id);
 }
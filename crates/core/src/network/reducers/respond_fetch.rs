@@ -26,6 +26,7 @@ fn reduce_respond_fetch_data_inner(
     aspects: &Vec<EntryAspect>,
 ) -> Result<(), HolochainError> {
     network_state.initialized()?;
+    let proof_of_work_difficulty = network_state.proof_of_work_difficulty;
     send(
         network_state,
         Lib3hClientProtocol::HandleFetchEntryResult(FetchEntryResultData {
@@ -36,7 +37,7 @@ fn reduce_respond_fetch_data_inner(
                 entry_address: fetch_data.entry_address.clone(),
                 aspect_list: aspects
                     .iter()
-                    .map(|a| entry_data_to_entry_aspect_data(a))
+                    .map(|a| entry_data_to_entry_aspect_data(a, proof_of_work_difficulty))
                     .collect(),
             },
         }),
@@ -46,7 +47,7 @@ fn reduce_respond_fetch_data_inner(
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn reduce_respond_fetch_data(
     network_state: &mut NetworkState,
-    _root_state: &State,
+    root_state: &State,
     action_wrapper: &ActionWrapper,
 ) {
     let action = action_wrapper.action();
@@ -54,9 +55,12 @@ pub fn reduce_respond_fetch_data(
     let result = reduce_respond_fetch_data_inner(network_state, fetch_data, maybe_entry);
     network_state.actions.insert(
         action_wrapper.clone(),
-        Response::from(NetworkActionResponse::Respond(match result {
-            Ok(_) => Ok(()),
-            Err(e) => Err(HolochainError::ErrorGeneric(e.to_string())),
-        })),
+        Response::new(
+            NetworkActionResponse::Respond(match result {
+                Ok(_) => Ok(()),
+                Err(e) => Err(HolochainError::ErrorGeneric(e.to_string())),
+            }),
+            root_state.clock.now(),
+        ),
     );
 }
@@ -39,7 +39,7 @@ fn reduce_respond_query_inner(
 /// Will return an empty content field if it actually doesn't have the data.
 pub fn reduce_respond_query(
     network_state: &mut NetworkState,
-    _root_state: &State,
+    root_state: &State,
     action_wrapper: &ActionWrapper,
 ) {
     let action = action_wrapper.action();
@@ -50,6 +50,9 @@ pub fn reduce_respond_query(
 
     network_state.actions.insert(
         action_wrapper.clone(),
-        Response::from(NetworkActionResponse::Respond(result)),
+        Response::new(
+            NetworkActionResponse::Respond(result),
+            root_state.clock.now(),
+        ),
     );
 }
@@ -33,6 +33,9 @@ fn inner(
         network_state
             .direct_message_connections
             .insert(data.request_id.clone(), direct_message_data.message.clone());
+        network_state
+            .direct_message_peers
+            .insert(data.request_id.clone(), direct_message_data.address.clone());
         Lib3hClientProtocol::SendDirectMessage(data)
     };
 
@@ -48,6 +51,18 @@ pub fn reduce_send_direct_message(
 ) {
     let action = action_wrapper.action();
     let (dm_data, maybe_timeout) = unwrap_to!(action => crate::action::Action::SendDirectMessage);
+    if network_state.is_agent_blocked(&dm_data.address) {
+        if !dm_data.is_response {
+            network_state.custom_direct_message_replys.insert(
+                dm_data.msg_id.clone(),
+                Err(HolochainError::ErrorGeneric(format!(
+                    "Agent {} is blocked",
+                    dm_data.address
+                ))),
+            );
+        }
+        return;
+    }
     if let Some(timeout) = maybe_timeout {
         network_state
             .direct_message_timeouts
@@ -69,11 +84,13 @@ pub fn reduce_send_direct_message_timeout(
 
     network_state.direct_message_timeouts.remove(id);
     network_state.direct_message_connections.remove(id);
+    let peer = network_state.direct_message_peers.remove(id);
 
     if network_state.custom_direct_message_replys.get(id).is_some() {
         return;
     }
 
+    network_state.record_timeout("direct_message", peer.as_ref());
     network_state.custom_direct_message_replys.insert(
         id.clone(),
         Err(HolochainError::Timeout(format!(
@@ -115,6 +132,9 @@ mod tests {
             dna_address: "reduce_send_direct_message_timeout_test".into(),
             agent_id: String::from("alice"),
             handler,
+            bandwidth_limit: None,
+            instance_name: context.get_instance_name(),
+            validation_package_cache_size: 0,
         }));
 
         store = store.reduce(action_wrapper);
@@ -1,6 +1,14 @@
 use crate::{
     action::{ActionWrapper, QueryKey, ValidationKey},
-    network::{actions::Response, direct_message::DirectMessage, query::NetworkQueryResult},
+    clock::SharedClock,
+    network::{
+        actions::Response,
+        direct_message::DirectMessage,
+        query::NetworkQueryResult,
+        traffic::{NetworkTraffic, TrafficEvent},
+        validation_package_cache::{ValidationPackageCache, DEFAULT_VALIDATION_PACKAGE_CACHE_SIZE},
+    },
+    resource_limits::BandwidthThrottle,
 };
 use boolinator::*;
 use holochain_core_types::{
@@ -10,10 +18,14 @@ use holochain_core_types::{
     validation::{ValidationPackage, ValidationPackageDefinition},
 };
 use holochain_json_api::{error::JsonError, json::JsonString};
+use holochain_metrics::prometheus;
 use holochain_net::p2p_network::P2pNetwork;
 use holochain_persistence_api::cas::content::Address;
-use im::HashMap;
-use std::time::{Duration, SystemTime};
+use im::{HashMap, HashSet};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 type Actions = HashMap<ActionWrapper, Response>;
 
@@ -51,6 +63,15 @@ pub struct NetworkState {
     pub network: Option<P2pNetwork>,
     pub dna_address: Option<Address>,
     pub agent_id: Option<String>,
+    /// Leading zero bits required from the proof-of-work on published aspects (0 = off),
+    /// taken from the DNA when the network gets initialized.
+    pub proof_of_work_difficulty: u8,
+    /// Delays outgoing messages once the instance sent more than its bandwidth limit
+    pub bandwidth_throttle: Option<Arc<BandwidthThrottle>>,
+    /// Name of the instance, which network metrics get labelled with
+    pub instance_name: String,
+    /// Recent traffic per peer and message type, shared by all versions of the state
+    pub traffic: Arc<NetworkTraffic>,
 
     // Here are the results of every get action
     pub get_query_results: HashMap<QueryKey, GetResults>,
@@ -60,6 +81,8 @@ pub struct NetworkState {
     /// None means that we are still waiting for a result from the network.
     pub get_validation_package_results: HashMap<ValidationKey, GetValidationPackageResult>,
     pub get_validation_package_timeouts: HashMap<ValidationKey, (SystemTime, Duration)>,
+    /// Validation packages authors sent us recently, shared by all versions of the state
+    pub validation_package_cache: Arc<ValidationPackageCache>,
 
     /// This stores every open (= waiting for response) node-to-node messages.
     /// Entries get removed when we receive an answer through Action::ResolveDirectConnection.
@@ -67,7 +90,14 @@ pub struct NetworkState {
     pub direct_message_timeouts: HashMap<String, (SystemTime, Duration)>,
 
     pub custom_direct_message_replys: HashMap<String, Result<String, HolochainError>>,
+    /// The agent on the other end of every open direct message connection,
+    /// so connections can get dropped when that agent gets blocked.
+    pub direct_message_peers: HashMap<String, Address>,
+    /// Agents we refuse any traffic from and whose data we don't serve or validate.
+    pub blocked_agents: HashSet<Address>,
     pub validation_cache: HashMap<Address, ValidationCacheData>,
+    /// Traffic gets counted in the minute of this clock
+    pub clock: SharedClock,
 
     id: String,
 }
@@ -87,19 +117,34 @@ impl NetworkState {
             network: None,
             dna_address: None,
             agent_id: None,
+            proof_of_work_difficulty: 0,
+            bandwidth_throttle: None,
+            instance_name: String::new(),
+            traffic: Arc::new(NetworkTraffic::new()),
             get_query_results: HashMap::new(),
             query_timeouts: HashMap::new(),
             get_validation_package_results: HashMap::new(),
             get_validation_package_timeouts: HashMap::new(),
+            validation_package_cache: Arc::new(ValidationPackageCache::new(
+                DEFAULT_VALIDATION_PACKAGE_CACHE_SIZE,
+            )),
             direct_message_connections: HashMap::new(),
             direct_message_timeouts: HashMap::new(),
             custom_direct_message_replys: HashMap::new(),
+            direct_message_peers: HashMap::new(),
+            blocked_agents: HashSet::new(),
             validation_cache: HashMap::new(),
+            clock: SharedClock::system(),
 
             id: nanoid::simple(),
         }
     }
 
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     pub fn actions(&self) -> Actions {
         self.actions.clone()
     }
@@ -110,7 +155,45 @@ impl NetworkState {
         )
     }
 
-    pub(crate) fn cache_validation(&mut self, agent: Address, validation: &ValidationPackage) {
+    /// Counts a request of the given kind that got no response in time
+    pub fn record_timeout(&self, request: &str, peer: Option<&Address>) {
+        prometheus::increment_counter(
+            "holochain_network_timeouts_total",
+            "Network requests that got no response in time",
+            &[("instance", &self.instance_name), ("request", request)],
+        );
+        // Counted against the message that went unanswered, so the timeout rate makes sense
+        let message_type = match request {
+            "query" => "QueryEntry",
+            _ => "SendDirectMessage",
+        };
+        let peer = peer.map(|peer| String::from(peer.clone()));
+        self.traffic.record(
+            self.clock.now(),
+            message_type,
+            peer.as_ref().map(String::as_str),
+            TrafficEvent::Timeout,
+        );
+    }
+
+    pub fn is_agent_blocked(&self, agent: &Address) -> bool {
+        self.blocked_agents.contains(agent)
+    }
+
+    /// True if any of the header's provenances is from a blocked agent.
+    pub fn is_authored_by_blocked_agent(&self, header: &ChainHeader) -> bool {
+        header
+            .provenances()
+            .iter()
+            .any(|provenance| self.is_agent_blocked(&provenance.source()))
+    }
+
+    pub(crate) fn cache_validation(
+        &mut self,
+        agent: Address,
+        validation: &ValidationPackage,
+        now: SystemTime,
+    ) {
         if validation.source_chain_headers.is_none() {
             return;
         }
@@ -143,7 +226,7 @@ impl NetworkState {
                 ValidationCacheData {
                     entries: validation.source_chain_entries.clone(),
                     headers: validation.source_chain_headers.as_ref().unwrap().clone(),
-                    cached_at: SystemTime::now(),
+                    cached_at: now,
                 },
             );
         }
@@ -117,6 +117,120 @@ pub fn test_wat_always_valid() -> String {
     .to_string()
 }
 
+/// Like `test_wat_always_valid`, but app entries can't be deleted
+pub fn test_wat_refusing_deletes() -> String {
+    r#"
+(module
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func
+        (export "__hdk_validate_app_entry")
+        (param $allocation i64)
+        (result i64)
+
+        ;; The arguments start with {"validation_data":{" followed by the variant, so the
+        ;; byte at offset 21 of the allocation is a "D" for deletes
+        (if
+            (i32.eq
+                (i32.load8_u
+                    (i32.add
+                        (i32.wrap/i64 (i64.shr_u (get_local $allocation) (i64.const 32)))
+                        (i32.const 21)
+                    )
+                )
+                (i32.const 68)
+            )
+            (then
+                ;; This writes "FAIL wat" into memory
+                (i64.store (i32.const 0) (i64.const 70))
+                (i64.store (i32.const 1) (i64.const 65))
+                (i64.store (i32.const 2) (i64.const 73))
+                (i64.store (i32.const 3) (i64.const 76))
+                (i64.store (i32.const 4) (i64.const 32))
+                (i64.store (i32.const 5) (i64.const 119))
+                (i64.store (i32.const 6) (i64.const 97))
+                (i64.store (i32.const 7) (i64.const 116))
+
+                (return (i64.const 8))
+            )
+        )
+
+        (i64.const 0)
+    )
+
+    (func
+        (export "__hdk_validate_agent_entry")
+        (param $allocation i64)
+        (result i64)
+
+        (i64.const 0)
+    )
+
+    (func
+        (export "__hdk_validate_link")
+        (param $allocation i64)
+        (result i64)
+
+        (i64.const 0)
+    )
+
+
+    (func
+        (export "__hdk_get_validation_package_for_entry_type")
+        (param $allocation i64)
+        (result i64)
+
+        ;; This writes "Entry" into memory
+        (i64.store (i32.const 0) (i64.const 34))
+        (i64.store (i32.const 1) (i64.const 69))
+        (i64.store (i32.const 2) (i64.const 110))
+        (i64.store (i32.const 3) (i64.const 116))
+        (i64.store (i32.const 4) (i64.const 114))
+        (i64.store (i32.const 5) (i64.const 121))
+        (i64.store (i32.const 6) (i64.const 34))
+
+        (i64.const 7)
+    )
+
+    (func
+        (export "__hdk_get_validation_package_for_link")
+        (param $allocation i64)
+        (result i64)
+
+        ;; This writes "Entry" into memory
+        (i64.store (i32.const 0) (i64.const 34))
+        (i64.store (i32.const 1) (i64.const 69))
+        (i64.store (i32.const 2) (i64.const 110))
+        (i64.store (i32.const 3) (i64.const 116))
+        (i64.store (i32.const 4) (i64.const 114))
+        (i64.store (i32.const 5) (i64.const 121))
+        (i64.store (i32.const 6) (i64.const 34))
+
+        (i64.const 7)
+    )
+
+    (func
+        (export "__list_traits")
+        (param $allocation i64)
+        (result i64)
+
+        (i64.const 0)
+    )
+
+    (func
+        (export "__list_functions")
+        (param $allocation i64)
+        (result i64)
+
+        (i64.const 0)
+    )
+)
+                "#
+    .to_string()
+}
+
 pub fn test_wat_always_invalid() -> String {
     r#"
 (module
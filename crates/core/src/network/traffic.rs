@@ -0,0 +1,259 @@
+//! Rolling summaries of an instance's network traffic, per peer and per message type.
+//!
+//! Every message the instance sends or receives gets counted in the bucket of the current
+//! minute, together with sends that failed, failures the network reported and requests that
+//! timed out. The buckets of the last [TRAFFIC_HISTORY_MINUTES] minutes are kept, and a
+//! [TrafficSummary] adds up the buckets of the requested number of minutes.
+use holochain_locksmith::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How many minutes of traffic an instance remembers
+pub const TRAFFIC_HISTORY_MINUTES: u64 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrafficEvent {
+    /// A message of the given size went out
+    Sent(usize),
+    /// A message of the given size came in
+    Received(usize),
+    /// Sending failed or the network reported a failure
+    Error,
+    /// No response came in time
+    Timeout,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TrafficCounts {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub errors: u64,
+    pub timeouts: u64,
+    /// Errors per sent message
+    pub error_rate: f64,
+    /// Timeouts per sent message
+    pub timeout_rate: f64,
+}
+
+impl TrafficCounts {
+    fn count(&mut self, event: TrafficEvent) {
+        match event {
+            TrafficEvent::Sent(bytes) => {
+                self.messages_sent += 1;
+                self.bytes_sent += bytes as u64;
+            }
+            TrafficEvent::Received(bytes) => {
+                self.messages_received += 1;
+                self.bytes_received += bytes as u64;
+            }
+            TrafficEvent::Error => self.errors += 1,
+            TrafficEvent::Timeout => self.timeouts += 1,
+        }
+    }
+
+    fn add(&mut self, other: &TrafficCounts) {
+        self.messages_sent += other.messages_sent;
+        self.messages_received += other.messages_received;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+        self.errors += other.errors;
+        self.timeouts += other.timeouts;
+    }
+
+    fn with_rates(mut self) -> Self {
+        if self.messages_sent > 0 {
+            self.error_rate = self.errors as f64 / self.messages_sent as f64;
+            self.timeout_rate = self.timeouts as f64 / self.messages_sent as f64;
+        }
+        self
+    }
+}
+
+/// Traffic of an instance over the last `minutes` minutes
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TrafficSummary {
+    pub minutes: u64,
+    pub total: TrafficCounts,
+    /// Traffic with known peers, by agent ID
+    pub peers: BTreeMap<String, TrafficCounts>,
+    /// Traffic by the type of the lib3h protocol message, like `SendDirectMessage`
+    pub message_types: BTreeMap<String, TrafficCounts>,
+}
+
+#[derive(Default)]
+struct MinuteBucket {
+    minute: u64,
+    total: TrafficCounts,
+    peers: HashMap<String, TrafficCounts>,
+    message_types: HashMap<String, TrafficCounts>,
+}
+
+#[derive(Default)]
+pub struct NetworkTraffic {
+    buckets: Mutex<VecDeque<MinuteBucket>>,
+}
+
+impl fmt::Debug for NetworkTraffic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NetworkTraffic").finish()
+    }
+}
+
+fn minute_of(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() / 60)
+        .unwrap_or(0)
+}
+
+fn add_counts(
+    summed: &mut BTreeMap<String, TrafficCounts>,
+    counts: &HashMap<String, TrafficCounts>,
+) {
+    for (key, counts) in counts.iter() {
+        summed.entry(key.clone()).or_default().add(counts);
+    }
+}
+
+impl NetworkTraffic {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a message, or a failure of one, of the given type with the given peer
+    /// in the minute of `now`
+    pub fn record(
+        &self,
+        now: SystemTime,
+        message_type: &str,
+        peer: Option<&str>,
+        event: TrafficEvent,
+    ) {
+        self.record_at(minute_of(now), message_type, peer, event)
+    }
+
+    fn record_at(&self, minute: u64, message_type: &str, peer: Option<&str>, event: TrafficEvent) {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.back().map(|bucket| bucket.minute) != Some(minute) {
+            buckets.push_back(MinuteBucket {
+                minute,
+                ..MinuteBucket::default()
+            });
+        }
+        while buckets.front().map_or(false, |bucket| {
+            bucket.minute + TRAFFIC_HISTORY_MINUTES <= minute
+        }) {
+            buckets.pop_front();
+        }
+        let bucket = buckets
+            .back_mut()
+            .expect("Bucket of the minute was just added");
+        bucket.total.count(event);
+        bucket
+            .message_types
+            .entry(message_type.to_string())
+            .or_default()
+            .count(event);
+        if let Some(peer) = peer {
+            bucket
+                .peers
+                .entry(peer.to_string())
+                .or_default()
+                .count(event);
+        }
+    }
+
+    /// The traffic of the last `minutes` minutes, including the one of `now`
+    pub fn summary(&self, now: SystemTime, minutes: u64) -> TrafficSummary {
+        self.summary_at(minute_of(now), minutes)
+    }
+
+    fn summary_at(&self, minute: u64, minutes: u64) -> TrafficSummary {
+        let minutes = minutes.min(TRAFFIC_HISTORY_MINUTES).max(1);
+        let mut total = TrafficCounts::default();
+        let mut peers = BTreeMap::new();
+        let mut message_types = BTreeMap::new();
+        for bucket in self
+            .buckets
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|bucket| bucket.minute + minutes > minute)
+        {
+            total.add(&bucket.total);
+            add_counts(&mut peers, &bucket.peers);
+            add_counts(&mut message_types, &bucket.message_types);
+        }
+        let with_rates = |counts: BTreeMap<String, TrafficCounts>| {
+            counts
+                .into_iter()
+                .map(|(key, counts)| (key, counts.with_rates()))
+                .collect()
+        };
+        TrafficSummary {
+            minutes,
+            total: total.with_rates(),
+            peers: with_rates(peers),
+            message_types: with_rates(message_types),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_up_the_requested_minutes() {
+        let traffic = NetworkTraffic::new();
+        traffic.record_at(
+            100,
+            "SendDirectMessage",
+            Some("alice"),
+            TrafficEvent::Sent(100),
+        );
+        traffic.record_at(
+            130,
+            "SendDirectMessage",
+            Some("alice"),
+            TrafficEvent::Sent(200),
+        );
+        traffic.record_at(
+            130,
+            "SendDirectMessage",
+            Some("alice"),
+            TrafficEvent::Timeout,
+        );
+        traffic.record_at(
+            131,
+            "HandleStoreEntryAspect",
+            Some("bob"),
+            TrafficEvent::Received(50),
+        );
+        traffic.record_at(131, "PublishEntry", None, TrafficEvent::Sent(10));
+        traffic.record_at(131, "PublishEntry", None, TrafficEvent::Sent(10));
+
+        let summary = traffic.summary_at(131, 5);
+        assert_eq!(summary.minutes, 5);
+        assert_eq!(summary.total.messages_sent, 3);
+        assert_eq!(summary.total.bytes_sent, 220);
+        assert_eq!(summary.total.bytes_received, 50);
+        let alice = &summary.peers["alice"];
+        assert_eq!(alice.messages_sent, 1);
+        assert_eq!(alice.timeouts, 1);
+        assert!(alice.timeout_rate > 0.99);
+        assert_eq!(summary.peers["bob"].messages_received, 1);
+        assert_eq!(summary.message_types["PublishEntry"].messages_sent, 2);
+        assert_eq!(summary.message_types["PublishEntry"].timeouts, 0);
+
+        // The bucket of minute 100 got dropped when minute 160 began
+        assert_eq!(traffic.summary_at(131, 1000).total.messages_sent, 4);
+        traffic.record_at(160, "PublishEntry", None, TrafficEvent::Error);
+        assert_eq!(traffic.summary_at(160, 1000).total.messages_sent, 3);
+        assert_eq!(traffic.summary_at(160, 1).total.errors, 1);
+    }
+}
@@ -0,0 +1,234 @@
+//! Validation packages that authors sent us, by the address of the header they are for.
+//!
+//! Validating a burst of links from one author needs the same few validation packages over
+//! and over. [get_validation_package] looks a package up here before asking its author again.
+//! The cache keeps the packages of the `capacity` most recently used headers. Once an author
+//! publishes a header that is newer than the headers of its cached packages, the author's
+//! chain moved on, so all of its packages are dropped.
+//!
+//! [get_validation_package]: ../actions/get_validation_package/fn.get_validation_package.html
+use holochain_core_types::{
+    chain_header::ChainHeader, time::Iso8601, validation::ValidationPackage,
+};
+use holochain_locksmith::Mutex;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+/// How many validation packages an instance caches by default
+pub const DEFAULT_VALIDATION_PACKAGE_CACHE_SIZE: usize = 256;
+
+struct CachedPackage {
+    author: Address,
+    package: ValidationPackage,
+}
+
+#[derive(Default)]
+struct CachedPackages {
+    packages: HashMap<Address, CachedPackage>,
+    /// Header addresses from the least to the most recently used
+    usage: VecDeque<Address>,
+    /// Timestamp of the newest header of each author we cache packages of
+    chain_tops: HashMap<Address, Iso8601>,
+}
+
+impl CachedPackages {
+    fn touch(&mut self, header_address: &Address) {
+        if let Some(index) = self
+            .usage
+            .iter()
+            .position(|address| address == header_address)
+        {
+            self.usage.remove(index);
+        }
+        self.usage.push_back(header_address.clone());
+    }
+
+    fn remove_author(&mut self, author: &Address) {
+        let packages = &mut self.packages;
+        packages.retain(|_, cached| &cached.author != author);
+        self.usage.retain(|address| packages.contains_key(address));
+        self.chain_tops.remove(author);
+    }
+}
+
+pub struct ValidationPackageCache {
+    capacity: usize,
+    cached: Mutex<CachedPackages>,
+}
+
+impl fmt::Debug for ValidationPackageCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ValidationPackageCache")
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl ValidationPackageCache {
+    /// A cache of `capacity` packages, 0 turns caching off
+    pub fn new(capacity: usize) -> Self {
+        ValidationPackageCache {
+            capacity,
+            cached: Mutex::new(CachedPackages::default()),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.cached.lock().unwrap().packages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The cached package of the header, if there is one
+    pub fn get(&self, header_address: &Address) -> Option<ValidationPackage> {
+        let mut cached = self.cached.lock().unwrap();
+        let package = cached.packages.get(header_address)?.package.clone();
+        cached.touch(header_address);
+        Some(package)
+    }
+
+    /// Caches the package that the author sent for the header we asked it for, dropping the
+    /// least recently used package if the cache is full. Packages for any other header than
+    /// the requested one don't get cached, so that an author can't make us validate the
+    /// entries of others against a package of its choosing.
+    pub fn insert(&self, header: &ChainHeader, package: ValidationPackage) {
+        if self.capacity == 0 || package.chain_header != *header {
+            return;
+        }
+        let author = match header.provenances().first() {
+            Some(provenance) => provenance.source(),
+            None => return,
+        };
+        let mut cached = self.cached.lock().unwrap();
+        let timestamp = *header.timestamp();
+        let is_newer = cached
+            .chain_tops
+            .get(&author)
+            .map_or(true, |chain_top| timestamp > *chain_top);
+        if is_newer {
+            cached.chain_tops.insert(author.clone(), timestamp);
+        }
+        let header_address = header.address();
+        cached.touch(&header_address);
+        cached
+            .packages
+            .insert(header_address, CachedPackage { author, package });
+        while cached.packages.len() > self.capacity {
+            match cached.usage.pop_front() {
+                Some(evicted) => {
+                    if let Some(evicted) = cached.packages.remove(&evicted) {
+                        if !cached
+                            .packages
+                            .values()
+                            .any(|other| other.author == evicted.author)
+                        {
+                            cached.chain_tops.remove(&evicted.author);
+                        }
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Drops the cached packages of the header's authors if the header is newer than the
+    /// headers of their packages
+    pub fn see_header(&self, header: &ChainHeader) {
+        let mut cached = self.cached.lock().unwrap();
+        for provenance in header.provenances() {
+            let author = provenance.source();
+            let moved_on = cached
+                .chain_tops
+                .get(&author)
+                .map_or(false, |chain_top| header.timestamp() > chain_top);
+            if moved_on {
+                cached.remove_author(&author);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{
+        chain_header::test_provenances,
+        entry::{entry_type::test_entry_type, test_entry, test_entry_b},
+        signature::Provenance,
+    };
+
+    fn header(entry_address: &Address, provenances: &[Provenance], timestamp: u64) -> ChainHeader {
+        ChainHeader::new(
+            &test_entry_type(),
+            entry_address,
+            &provenances.to_vec(),
+            &None,
+            &None,
+            &None,
+            &Iso8601::from(timestamp),
+        )
+    }
+
+    #[test]
+    fn keeps_recently_used_packages_until_the_author_moves_on() {
+        let cache = ValidationPackageCache::new(2);
+        let alice = test_provenances("alice");
+        let first = header(&test_entry().address(), &alice, 1577836800);
+        let second = header(&test_entry_b().address(), &alice, 1577836801);
+        let third = header(&test_entry().address(), &alice, 1577836802);
+
+        cache.insert(&first, ValidationPackage::only_header(first.clone()));
+        cache.insert(&second, ValidationPackage::only_header(second.clone()));
+        assert!(cache.get(&first.address()).is_some());
+        // The first package got used more recently, so the second one makes room
+        cache.insert(&third, ValidationPackage::only_header(third.clone()));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&second.address()).is_none());
+        assert_eq!(
+            cache
+                .get(&first.address())
+                .map(|package| package.chain_header),
+            Some(first.clone())
+        );
+
+        // Headers that aren't newer than what we have leave the cache alone
+        cache.see_header(&second);
+        assert_eq!(cache.len(), 2);
+        let fourth = header(&test_entry_b().address(), &alice, 1577836803);
+        cache.see_header(&fourth);
+        assert!(cache.is_empty());
+
+        let disabled = ValidationPackageCache::new(0);
+        disabled.insert(&first, ValidationPackage::only_header(first.clone()));
+        assert!(disabled.get(&first.address()).is_none());
+    }
+
+    #[test]
+    fn does_not_cache_packages_for_other_headers() {
+        let cache = ValidationPackageCache::new(2);
+        let requested = header(
+            &test_entry().address(),
+            &test_provenances("alice"),
+            1577836800,
+        );
+        let other = header(
+            &test_entry_b().address(),
+            &test_provenances("bob"),
+            1577836801,
+        );
+
+        cache.insert(&requested, ValidationPackage::only_header(other.clone()));
+        assert!(cache.is_empty());
+        assert!(cache.get(&requested.address()).is_none());
+        assert!(cache.get(&other.address()).is_none());
+    }
+}
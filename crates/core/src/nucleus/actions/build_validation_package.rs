@@ -1,5 +1,5 @@
 use crate::{
-    agent::{self, find_chain_header},
+    agent::{self, find_chain_header, state::AgentState},
     content_store::GetContent,
     context::Context,
     entry::CanPublish,
@@ -23,6 +23,20 @@ pub fn build_validation_package<'a>(
     entry: &'a Entry,
     context: Arc<Context>,
     provenances: &'a Vec<Provenance>,
+) -> Result<ValidationPackage, HolochainError> {
+    let agent_state = context.state()?.agent();
+    build_validation_package_on_chain(entry, context, provenances, &agent_state)
+}
+
+/// Builds the validation package of the entry as if it got authored on top of the given
+/// chain, which may hold entries of the same batch that are not committed yet.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+#[allow(clippy::ptr_arg)]
+pub fn build_validation_package_on_chain<'a>(
+    entry: &'a Entry,
+    context: Arc<Context>,
+    provenances: &'a Vec<Provenance>,
+    chain: &'a AgentState,
 ) -> Result<ValidationPackage, HolochainError> {
     match entry.entry_type() {
         EntryType::App(app_entry_type) => {
@@ -35,10 +49,9 @@ pub fn build_validation_package<'a>(
                 .get_zome_name_for_app_entry_type(&app_entry_type)
                 .is_none()
             {
-                return Err(HolochainError::ValidationFailed(format!(
-                    "Unknown app entry type '{}'",
-                    String::from(app_entry_type),
-                )));
+                return Err(HolochainError::ValidationFailed(
+                    format!("Unknown app entry type '{}'", String::from(app_entry_type),).into(),
+                ));
             }
         }
 
@@ -62,10 +75,13 @@ pub fn build_validation_package<'a>(
             // FIXME
         }
         _ => {
-            return Err(HolochainError::ValidationFailed(format!(
-                "Attempted to validate system entry type {:?}",
-                entry.entry_type(),
-            )));
+            return Err(HolochainError::ValidationFailed(
+                format!(
+                    "Attempted to validate system entry type {:?}",
+                    entry.entry_type(),
+                )
+                .into(),
+            ));
         }
     };
 
@@ -92,7 +108,7 @@ pub fn build_validation_package<'a>(
             let state = State::new(context.clone());
             agent::state::create_new_chain_header(
                 &entry,
-                &context.state()?.agent(),
+                chain,
                 &StateWrapper::from(state),
                 &None,
                 provenances,
@@ -119,23 +135,24 @@ pub fn build_validation_package<'a>(
                     let mut package = ValidationPackage::only_header(entry_header);
                     package.source_chain_entries = Some(public_chain_entries_from_headers(
                         &context,
-                        &all_chain_headers_before_header(&context, &package.chain_header),
+                        chain,
+                        &all_chain_headers_before_header(chain, &package.chain_header),
                     ));
                     package
                 }
                 ChainHeaders => {
                     let mut package = ValidationPackage::only_header(entry_header);
                     package.source_chain_headers = Some(all_chain_headers_before_header(
-                        &context,
+                        chain,
                         &package.chain_header,
                     ));
                     package
                 }
                 ChainFull => {
                     let mut package = ValidationPackage::only_header(entry_header);
-                    let headers = all_chain_headers(&context);
+                    let headers = all_chain_headers(chain);
                     package.source_chain_entries =
-                        Some(public_chain_entries_from_headers(&context, &headers));
+                        Some(public_chain_entries_from_headers(&context, chain, &headers));
                     package.source_chain_headers = Some(headers);
                     package
                 }
@@ -152,17 +169,15 @@ pub fn build_validation_package<'a>(
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 fn public_chain_entries_from_headers(
     context: &Arc<Context>,
+    chain: &AgentState,
     headers: &[ChainHeader],
 ) -> Vec<Entry> {
+    let chain_store = chain.chain_store();
     headers
         .iter()
         .filter(|ref chain_header| chain_header.entry_type().can_publish(context))
         .map(|chain_header| {
-            context
-                .state()
-                .expect("No state in public_chain_entries_from_headers")
-                .agent()
-                .chain_store()
+            chain_store
                 .get(chain_header.entry_address())
                 .expect("Could not read entry from CAS")
                 .expect("Entry does not exist")
@@ -171,27 +186,18 @@ fn public_chain_entries_from_headers(
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
-fn all_chain_headers(context: &Arc<Context>) -> Vec<ChainHeader> {
-    let state = &context
-        .state()
-        .expect("No state in build_validation_package")
-        .agent();
-    let top = state.top_chain_header().expect("there has to be a top");
-    let chain = state.chain_store();
-    chain.iter(&Some(top)).collect()
+fn all_chain_headers(chain: &AgentState) -> Vec<ChainHeader> {
+    let top = chain.top_chain_header().expect("there has to be a top");
+    chain.chain_store().iter(&Some(top)).collect()
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
-fn all_chain_headers_before_header(
-    context: &Arc<Context>,
-    header: &ChainHeader,
-) -> Vec<ChainHeader> {
-    let chain = context
-        .state()
-        .expect("No state in all_chain_headers_before_header")
-        .agent()
-        .chain_store();
-    chain.iter(&Some(header.clone())).skip(1).collect()
+fn all_chain_headers_before_header(chain: &AgentState, header: &ChainHeader) -> Vec<ChainHeader> {
+    chain
+        .chain_store()
+        .iter(&Some(header.clone()))
+        .skip(1)
+        .collect()
 }
 
 #[cfg(test)]
@@ -202,6 +208,10 @@ mod tests {
     use holochain_core_types::{time::Iso8601, validation::ValidationPackage};
     use holochain_persistence_api::cas::content::{Address, AddressableContent};
 
+    fn chain(context: &Arc<Context>) -> Arc<AgentState> {
+        context.state().unwrap().agent()
+    }
+
     #[test]
     fn test_building_validation_package_entry() {
         let (_instance, context) = instance(None);
@@ -251,7 +261,8 @@ mod tests {
             chain_header: chain_header.clone(),
             source_chain_entries: Some(public_chain_entries_from_headers(
                 &context,
-                &all_chain_headers_before_header(&context, &chain_header),
+                &chain(&context),
+                &all_chain_headers_before_header(&chain(&context), &chain_header),
             )),
             source_chain_headers: None,
             custom: None,
@@ -281,7 +292,10 @@ mod tests {
         let expected = ValidationPackage {
             chain_header: chain_header.clone(),
             source_chain_entries: None,
-            source_chain_headers: Some(all_chain_headers_before_header(&context, &chain_header)),
+            source_chain_headers: Some(all_chain_headers_before_header(
+                &chain(&context),
+                &chain_header,
+            )),
             custom: None,
         };
 
@@ -303,11 +317,15 @@ mod tests {
             build_validation_package(&test_entry_package_chain_full(), context.clone(), &vec![]);
         assert!(maybe_validation_package.is_ok());
 
-        let headers = all_chain_headers(&context);
+        let headers = all_chain_headers(&chain(&context));
 
         let expected = ValidationPackage {
             chain_header,
-            source_chain_entries: Some(public_chain_entries_from_headers(&context, &headers)),
+            source_chain_entries: Some(public_chain_entries_from_headers(
+                &context,
+                &chain(&context),
+                &headers,
+            )),
             source_chain_headers: Some(headers),
             custom: None,
         };
@@ -325,7 +343,7 @@ mod tests {
             .agent()
             .top_chain_header()
             .expect("There must be a top chain header");
-        let headers = all_chain_headers_before_header(&context, &top_header);
+        let headers = all_chain_headers_before_header(&chain(&context), &top_header);
         assert_eq!(headers.len(), 1) // includes the DNA entry only (no agent entry)
     }
 
@@ -350,7 +368,7 @@ mod tests {
             &Iso8601::new(0, 0),
         );
 
-        let headers = all_chain_headers_before_header(&context, &new_entry_header);
+        let headers = all_chain_headers_before_header(&chain(&context), &new_entry_header);
         // entry should not appear in the validating chain
         assert_eq!(headers.contains(&new_entry_header), false);
         assert_eq!(headers.len(), 2) // includes the DNA and agent entries
@@ -361,7 +379,7 @@ mod tests {
         let (_instance, context) = instance(None);
         // entry is added to the local chain
         let chain_header = commit(test_entry_package_chain_full(), &context);
-        let headers = all_chain_headers_before_header(&context, &chain_header);
+        let headers = all_chain_headers_before_header(&chain(&context), &chain_header);
         // entry should not appear in the validating chain
         assert_eq!(headers.contains(&chain_header), false);
         assert_eq!(headers.len(), 2) // includes the DNA and agent entries
@@ -372,13 +390,13 @@ mod tests {
         let (_instance, context) = instance(None);
         // entry is added to the local chain
         let chain_header = commit(test_entry_package_chain_full(), &context);
-        let pre_commit_headers = all_chain_headers_before_header(&context, &chain_header);
+        let pre_commit_headers = all_chain_headers_before_header(&chain(&context), &chain_header);
 
         // commit come more entries
         commit(test_entry_package_chain_entries(), &context);
         commit(test_entry_package_entry(), &context);
 
-        let post_commit_headers = all_chain_headers_before_header(&context, &chain_header);
+        let post_commit_headers = all_chain_headers_before_header(&chain(&context), &chain_header);
         assert_eq!(pre_commit_headers, post_commit_headers)
     }
 }
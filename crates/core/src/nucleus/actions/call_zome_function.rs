@@ -1,33 +1,45 @@
 use crate::{
     action::{Action, ActionWrapper},
+    causal_log::push_cause,
     context::Context,
-    nucleus::{actions::get_entry::get_entry_from_agent_chain, ZomeFnCall, ZomeFnResult},
+    event_log::{record_instance_event, EVENT_CAPABILITY_USE},
+    instance_pause::InstancePause,
+    nucleus::{
+        actions::get_entry::get_entry_from_agent_chain, rate_limit::ZomeCallRateLimiter,
+        slow_calls::ZomeCallWatchdog, ZomeFnCall, ZomeFnResult,
+    },
     wasm_engine::{self, WasmCallData},
 };
 use holochain_core_types::{
-    dna::{capabilities::CapabilityRequest, wasm::DnaWasm},
+    dna::{
+        capabilities::{CapabilityCheckOutcome, CapabilityRequest, CapabilityUseRecord},
+        wasm::DnaWasm,
+    },
     entry::{
-        cap_entries::{CapTokenGrant, CapabilityType},
+        cap_entries::{CapTokenDelegation, CapTokenGrant, CapabilityType},
+        entry_type::EntryType,
         Entry,
     },
     error::HolochainError,
     signature::{Provenance, Signature},
+    time::Iso8601,
 };
 
 use holochain_json_api::json::JsonString;
 use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use holochain_tracing::channel::lax_send_wrapped;
 
-use holochain_dpki::utils::Verify;
+use holochain_dpki::audit::verify_audited;
 
 use crate::instance::dispatch_action;
 use base64;
 use futures::{future::Future, task::Poll};
 use holochain_wasm_utils::api_serialization::crypto::CryptoMethod;
+use serde_json::json;
 use snowflake::ProcessUniqueId;
 use std::{pin::Pin, sync::Arc};
 
-#[derive(Clone, Debug, PartialEq, Hash, Serialize)]
+#[derive(Clone, Debug, PartialEq, Hash, Serialize, Deserialize)]
 pub struct ExecuteZomeFnResponse {
     call: ZomeFnCall,
     result: ZomeFnResult,
@@ -78,6 +90,13 @@ pub async fn call_zome_function(
     // 1. Validate the call (a number of things could go wrong)
     validate_call(context.clone(), &zome_call)?;
 
+    // 2. Refuse the call if the instance is paused, count it as in flight otherwise
+    let _work = InstancePause::begin_work(&context.instance_pause)?;
+
+    // 3. Check the rate limits of the token and the caller.
+    // The permit counts the call as in flight until we return.
+    let _permit = ZomeCallRateLimiter::acquire(&context.zome_call_rate_limiter, &zome_call)?;
+
     log_debug!(
         context,
         "actions/call_zome_fn: executing call: {:?}",
@@ -99,7 +118,7 @@ pub async fn call_zome_function(
         zome_call
     );
 
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     CallResultFuture {
         context: context.clone(),
         zome_call,
@@ -134,8 +153,15 @@ pub fn validate_call(
         (dna.name.clone(), zome.code.clone())
     };
 
-    if check_capability(context.clone(), fn_call)
-        || (is_token_the_agent(context, &fn_call.cap)
+    let is_agent_call = is_token_the_agent(context.clone(), &fn_call.cap);
+    let granted = check_capability(context.clone(), fn_call);
+    if !is_agent_call {
+        record_capability_use(&context, fn_call, granted);
+    }
+
+    if granted
+        || (is_agent_call
+            && is_agent_or_client_key(&context, &fn_call.cap.provenance.source())
             && verify_call_sig(
                 &fn_call.cap.provenance,
                 &fn_call.fn_name,
@@ -148,23 +174,98 @@ pub fn validate_call(
     }
 }
 
+/// adds a call made with a capability token to the instance's capability audit log
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn record_capability_use(context: &Arc<Context>, fn_call: &ZomeFnCall, granted: bool) {
+    let outcome = if granted {
+        CapabilityCheckOutcome::Granted
+    } else {
+        CapabilityCheckOutcome::Denied
+    };
+    let record = CapabilityUseRecord::new(
+        &fn_call.cap,
+        &fn_call.zome_name,
+        &fn_call.fn_name,
+        now(context),
+        outcome,
+    );
+    if let Err(e) = record_instance_event(
+        context,
+        EVENT_CAPABILITY_USE,
+        json!({
+            "token": fn_call.cap.cap_token,
+            "caller": fn_call.cap.provenance.source(),
+            "zome": fn_call.zome_name,
+            "function": fn_call.fn_name,
+            "granted": granted,
+        }),
+    ) {
+        log_error!(
+            context,
+            "zome: could not record capability use event: {:?}",
+            e
+        );
+    }
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::RecordCapabilityUse(record)),
+    );
+}
+
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 fn is_token_the_agent(context: Arc<Context>, request: &CapabilityRequest) -> bool {
     context.agent_id.pub_sign_key == request.cap_token.to_string()
 }
 
+/// true if calls signed by the given key may use the agent's own token,
+/// i.e. it is the agent key or one of the client keys registered for the agent
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
-fn get_grant(context: &Arc<Context>, address: &Address) -> Option<CapTokenGrant> {
+fn is_agent_or_client_key(context: &Arc<Context>, key: &Address) -> bool {
+    key == &context.agent_id.address() || context.client_keys.contains(key)
+}
+
+/// Looks up a live grant on our chain, i.e. one that has not been revoked.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub(crate) fn get_grant(context: &Arc<Context>, address: &Address) -> Option<CapTokenGrant> {
+    if is_grant_revoked(context, address) {
+        return None;
+    }
     match get_entry_from_agent_chain(context, address).ok()?? {
         Entry::CapTokenGrant(grant) => Some(grant),
         _ => None,
     }
 }
 
+/// true if a revocation of the grant with the given token got committed to our chain
+/// and has taken effect
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn is_grant_revoked(context: &Arc<Context>, token: &Address) -> bool {
+    let now = now(context);
+    context
+        .state()
+        .map(|state| {
+            let agent = state.agent();
+            agent
+                .chain_store()
+                .iter_type(&agent.top_chain_header(), &EntryType::CapTokenRevocation)
+                .filter_map(|header| agent.chain_store().get(header.entry_address()).ok()?)
+                .any(|entry| match entry {
+                    Entry::CapTokenRevocation(revocation) => {
+                        &revocation.token() == token && revocation.is_effective_at(&now)
+                    }
+                    _ => false,
+                })
+        })
+        .unwrap_or(false)
+}
+
 /// checks to see if a given function call is allowable according to the capabilities
 /// that have been registered to callers by looking for grants in the chain.
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn check_capability(context: Arc<Context>, fn_call: &ZomeFnCall) -> bool {
+    if !fn_call.cap.delegation.is_empty() {
+        return verify_delegation(context, &fn_call.cap.delegation, fn_call);
+    }
     let maybe_grant = get_grant(&context.clone(), &fn_call.cap_token());
     match maybe_grant {
         None => false,
@@ -209,7 +310,13 @@ pub fn verify_call_sig<J: Into<JsonString>>(
     parameters: J,
 ) -> bool {
     let what_was_signed = encode_call_data_for_signing(function, parameters);
-    provenance.verify(what_was_signed).unwrap()
+    verify_audited(
+        provenance,
+        what_was_signed,
+        "zome_call_signature",
+        &format!("call of {}", function),
+    )
+    .unwrap()
 }
 
 /// creates a capability request for a zome call by signing the function name and parameters
@@ -227,6 +334,168 @@ pub fn make_cap_request_for_call<J: Into<JsonString>>(
     )
 }
 
+fn now(context: &Context) -> Iso8601 {
+    Iso8601::from(context.clock.since_epoch().as_secs())
+}
+
+/// verifies a call made with a delegated sub-token by walking the chain of delegations
+/// back to the delegatable grant on our chain that it was minted from.
+/// Every delegation has to be made by the delegatee of its parent, and the caller has to be
+/// the delegatee of the last one.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn verify_delegation(
+    context: Arc<Context>,
+    delegation: &[CapTokenDelegation],
+    fn_call: &ZomeFnCall,
+) -> bool {
+    let leaf = match delegation.last() {
+        Some(leaf) => leaf,
+        None => return false,
+    };
+    if leaf.token() != fn_call.cap_token() {
+        log_debug!(
+            context,
+            "actions/verify_delegation: delegation token doesn't match: expecting {:?} got {:?}",
+            leaf.token(),
+            fn_call.cap_token()
+        );
+        return false;
+    }
+    if leaf.delegatee() != fn_call.cap.provenance.source() {
+        log_debug!(
+            context,
+            "actions/verify_delegation: caller {:?} is not the delegatee of the sub-token",
+            fn_call.cap.provenance.source()
+        );
+        return false;
+    }
+
+    let root_token = delegation[0].parent_token();
+    let grant = match get_grant(&context, &root_token) {
+        Some(grant) => grant,
+        None => {
+            log_debug!(
+                context,
+                "actions/verify_delegation: no grant found for root token {:?}",
+                root_token
+            );
+            return false;
+        }
+    };
+    if !grant.is_delegatable() {
+        log_debug!(
+            context,
+            "actions/verify_delegation: grant {:?} is not delegatable",
+            root_token
+        );
+        return false;
+    }
+    if let Some(assignees) = grant.assignees() {
+        if !assignees.contains(&delegation[0].delegator()) {
+            log_debug!(
+                context,
+                "actions/verify_delegation: delegator not one of the grant's assignees"
+            );
+            return false;
+        }
+    }
+
+    let now = now(&context);
+    if grant.is_expired_at(&now) {
+        log_debug!(
+            context,
+            "actions/verify_delegation: grant expired at {:?}",
+            grant.expires_at()
+        );
+        return false;
+    }
+
+    let mut parent_token = root_token;
+    let mut parent_delegatee = None;
+    let mut functions = grant.functions();
+    let mut arg_constraints = grant.arg_constraints();
+    let mut expires_at = grant.expires_at();
+    for link in delegation {
+        if link.parent_token() != parent_token {
+            log_debug!(
+                context,
+                "actions/verify_delegation: delegation chain broken at {:?}",
+                link.token()
+            );
+            return false;
+        }
+        if let Some(parent_delegatee) = parent_delegatee {
+            if link.delegator() != parent_delegatee {
+                log_debug!(
+                    context,
+                    "actions/verify_delegation: delegation {:?} not made by the delegatee of its parent",
+                    link.token()
+                );
+                return false;
+            }
+        }
+        if !link.is_within(&functions, &arg_constraints, expires_at) {
+            log_debug!(
+                context,
+                "actions/verify_delegation: delegation {:?} exceeds the scope of its parent",
+                link.token()
+            );
+            return false;
+        }
+        if link.is_expired_at(&now) {
+            log_debug!(
+                context,
+                "actions/verify_delegation: delegation {:?} expired at {:?}",
+                link.token(),
+                link.expires_at()
+            );
+            return false;
+        }
+        if !verify_audited(
+            &link.provenance(),
+            link.signing_payload(),
+            "capability_delegation",
+            &format!("delegation {} in call of {}", link.token(), fn_call.fn_name),
+        )
+        .unwrap_or(false)
+        {
+            log_debug!(
+                context,
+                "actions/verify_delegation: signature of delegation {:?} did not match",
+                link.token()
+            );
+            return false;
+        }
+        parent_token = link.token();
+        parent_delegatee = Some(link.delegatee());
+        functions = link.functions();
+        arg_constraints = link.arg_constraints();
+        expires_at = Some(link.expires_at());
+    }
+
+    if !leaf.allows_call(&fn_call.zome_name, &fn_call.fn_name, &fn_call.parameters) {
+        log_debug!(
+            context,
+            "actions/verify_delegation: call to {:?} not allowed by delegation",
+            fn_call.fn_name
+        );
+        return false;
+    }
+
+    if !verify_call_sig(
+        &fn_call.cap.provenance,
+        &fn_call.fn_name,
+        fn_call.parameters.clone(),
+    ) {
+        log_debug!(
+            context,
+            "actions/verify_delegation: call signature did not match"
+        );
+        return false;
+    }
+    true
+}
+
 /// verifies that this grant is valid for a given requester and token value
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn verify_grant(context: Arc<Context>, grant: &CapTokenGrant, fn_call: &ZomeFnCall) -> bool {
@@ -251,6 +520,16 @@ pub fn verify_grant(context: Arc<Context>, grant: &CapTokenGrant, fn_call: &Zome
         return false;
     }
 
+    if !grant.args_allowed(&fn_call.zome_name, &fn_call.fn_name, &fn_call.parameters) {
+        log_debug!(
+            context,
+            "actions/verify_grant: arguments {:?} not allowed by grant constraints {:?}",
+            fn_call.parameters,
+            grant.arg_constraints()
+        );
+        return false;
+    }
+
     if grant.token() != fn_call.cap_token() {
         log_debug!(
             context,
@@ -261,6 +540,15 @@ pub fn verify_grant(context: Arc<Context>, grant: &CapTokenGrant, fn_call: &Zome
         return false;
     }
 
+    if grant.is_expired_at(&now(&context)) {
+        log_debug!(
+            context,
+            "actions/verify_grant: grant expired at {:?}",
+            grant.expires_at()
+        );
+        return false;
+    }
+
     if !verify_call_sig(
         &fn_call.cap.provenance,
         &fn_call.fn_name,
@@ -301,6 +589,15 @@ pub fn spawn_zome_function(context: Arc<Context>, zome_call: ZomeFnCall) {
     std::thread::Builder::new()
         .name(format!("{:?}", zome_call))
         .spawn(move || {
+            // Whatever the zome function does was caused by queueing the call
+            let _cause = context
+                .causal_log
+                .find("QueueZomeFunctionCall", &zome_call.id().to_string())
+                .map(|entry| push_cause(entry.id));
+            let _watched = ZomeCallWatchdog::watch(&context.zome_call_watchdog, &zome_call);
+            let _frame = context
+                .profiler
+                .frame(|| format!("zome_call {}/{}", zome_call.zome_name, zome_call.fn_name));
             let start = Instant::now();
             // Have Ribosome spin up DNA and call the zome function
             let call_result = wasm_engine::run_dna(
@@ -383,17 +680,22 @@ pub mod tests {
         context::Context,
         instance::tests::*,
         nucleus::{actions::tests::test_dna, tests::*},
+        wasm_engine::api::capabilities::delegate_capability,
         workflows::author_entry::author_entry,
     };
     use holochain_core_types::{
         dna::capabilities::CapabilityRequest,
         entry::{
-            cap_entries::{CapFunctions, CapTokenGrant, CapabilityType},
+            cap_entries::{
+                ArgConstraint, CapArgConstraints, CapFunctions, CapTokenGrant, CapabilityType,
+            },
             Entry,
         },
         signature::Signature,
     };
     use holochain_persistence_api::cas::content::{Address, AddressableContent};
+    use holochain_wasm_utils::api_serialization::capabilities::DelegateCapabilityArgs;
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_agent_as_token() {
@@ -591,5 +893,253 @@ pub mod tests {
             &grant,
             &zome_call_valid(context2.clone(), &token)
         ));
+
+        let mut cap_functions = CapFunctions::new();
+        cap_functions.insert("test_zome".to_string(), vec![String::from("test")]);
+        let expired_grant = CapTokenGrant::create(
+            "foo",
+            CapabilityType::Transferable,
+            None,
+            cap_functions.clone(),
+        )
+        .unwrap()
+        .with_expires_at(Some(Iso8601::from(1000)));
+        assert!(!verify_grant(
+            context.clone(),
+            &expired_grant,
+            &zome_call_valid(context.clone(), &expired_grant.token())
+        ));
+
+        let mut constraint = ArgConstraint::new();
+        constraint.insert("channel".to_string(), json!("general"));
+        let mut zome_constraints = BTreeMap::new();
+        zome_constraints.insert("test".to_string(), constraint);
+        let mut arg_constraints = CapArgConstraints::new();
+        arg_constraints.insert("test_zome".to_string(), zome_constraints);
+        let constrained_grant = CapTokenGrant::create(
+            "foo",
+            CapabilityType::Transferable,
+            None,
+            cap_functions.clone(),
+        )
+        .unwrap()
+        .with_arg_constraints(arg_constraints);
+        let constrained_token = constrained_grant.token();
+        let call_with_args = |args: &str| {
+            let args = JsonString::from_json(args);
+            ZomeFnCall::new(
+                "test_zome",
+                make_cap_request_for_call(
+                    context.clone(),
+                    constrained_token.clone(),
+                    "test",
+                    args.clone(),
+                ),
+                "test",
+                args,
+            )
+        };
+        assert!(verify_grant(
+            context.clone(),
+            &constrained_grant,
+            &call_with_args(r#"{"channel":"general"}"#)
+        ));
+        assert!(!verify_grant(
+            context.clone(),
+            &constrained_grant,
+            &call_with_args(r#"{"channel":"private"}"#)
+        ));
+
+        let unexpired_grant =
+            CapTokenGrant::create("foo", CapabilityType::Transferable, None, cap_functions)
+                .unwrap()
+                .with_expires_at(Some(Iso8601::from(i64::from(i32::max_value()) * 2)));
+        assert!(verify_grant(
+            context.clone(),
+            &unexpired_grant,
+            &zome_call_valid(context.clone(), &unexpired_grant.token())
+        ));
+    }
+
+    #[test]
+    fn test_verify_delegation() {
+        let dna = test_dna();
+        let (_instance, context) =
+            test_instance_and_context(dna, None).expect("Could not initialize test instance");
+        let bob = test_context("bob", None);
+        let carol = test_context("carol", None);
+        let dave = test_context("dave", None);
+
+        let mut cap_functions = CapFunctions::new();
+        cap_functions.insert(
+            "test_zome".to_string(),
+            vec![String::from("test"), String::from("other_fn")],
+        );
+        let commit_grant = |grant: CapTokenGrant| {
+            context
+                .block_on(author_entry(
+                    &Entry::CapTokenGrant(grant),
+                    None,
+                    &context,
+                    &vec![],
+                ))
+                .unwrap()
+                .address()
+        };
+        let grant = CapTokenGrant::create(
+            "foo",
+            CapabilityType::Assigned,
+            Some(vec![bob.agent_id.address()]),
+            cap_functions.clone(),
+        )
+        .unwrap()
+        .with_delegatable(true);
+        let token = commit_grant(grant);
+        let not_delegatable_token = commit_grant(
+            CapTokenGrant::create(
+                "bar",
+                CapabilityType::Assigned,
+                Some(vec![bob.agent_id.address()]),
+                cap_functions,
+            )
+            .unwrap(),
+        );
+
+        let mut test_only = CapFunctions::new();
+        test_only.insert("test_zome".to_string(), vec![String::from("test")]);
+        let delegate = |delegator: &Arc<Context>,
+                        parent_token: &Address,
+                        parent_delegation: Vec<CapTokenDelegation>,
+                        delegatee: &Arc<Context>,
+                        functions: CapFunctions| {
+            delegate_capability(
+                delegator,
+                DelegateCapabilityArgs {
+                    parent_token: parent_token.clone(),
+                    parent_delegation,
+                    delegatee: delegatee.agent_id.address(),
+                    functions,
+                    arg_constraints: CapArgConstraints::new(),
+                    expires_at: Iso8601::from(2_000_000_000),
+                },
+            )
+        };
+        let call_with =
+            |caller: &Arc<Context>, delegation: Vec<CapTokenDelegation>, function: &str| {
+                let sub_token = delegation.last().unwrap().token();
+                ZomeFnCall::new(
+                    "test_zome",
+                    make_cap_request_for_call(caller.clone(), sub_token, function, "{}")
+                        .with_delegation(delegation),
+                    function,
+                    "{}",
+                )
+            };
+
+        let delegation = delegate(&bob, &token, Vec::new(), &carol, test_only.clone()).unwrap();
+        assert_eq!(delegation.len(), 1);
+        assert!(check_capability(
+            context.clone(),
+            &call_with(&carol, delegation.clone(), "test")
+        ));
+        // the sub-token only covers the delegated function
+        assert!(!check_capability(
+            context.clone(),
+            &call_with(&carol, delegation.clone(), "other_fn")
+        ));
+
+        // only the delegatee can call with the sub-token
+        assert!(!check_capability(
+            context.clone(),
+            &call_with(&dave, delegation.clone(), "test")
+        ));
+
+        // delegations can be delegated further, but only narrowing their parent
+        let chain_to_dave = delegate(
+            &carol,
+            &delegation[0].token(),
+            delegation.clone(),
+            &dave,
+            test_only.clone(),
+        )
+        .unwrap();
+        assert!(check_capability(
+            context.clone(),
+            &call_with(&dave, chain_to_dave.clone(), "test")
+        ));
+        assert!(!check_capability(
+            context.clone(),
+            &call_with(&carol, chain_to_dave, "test")
+        ));
+        let mut broader = CapFunctions::new();
+        broader.insert(
+            "test_zome".to_string(),
+            vec![String::from("test"), String::from("other_fn")],
+        );
+        assert!(delegate(
+            &carol,
+            &delegation[0].token(),
+            delegation.clone(),
+            &dave,
+            broader.clone()
+        )
+        .is_err());
+
+        // only the delegatee of a delegation can delegate it further
+        assert!(delegate(
+            &dave,
+            &delegation[0].token(),
+            delegation.clone(),
+            &dave,
+            test_only.clone()
+        )
+        .is_err());
+        // and a link spliced onto someone else's chain doesn't verify
+        let mut spliced = delegation.clone();
+        spliced.extend(
+            delegate(
+                &dave,
+                &delegation[0].token(),
+                Vec::new(),
+                &dave,
+                test_only.clone(),
+            )
+            .unwrap(),
+        );
+        assert!(!check_capability(
+            context.clone(),
+            &call_with(&dave, spliced, "test")
+        ));
+
+        // only the grant's assignees can delegate it
+        let delegation_by_carol =
+            delegate(&carol, &token, Vec::new(), &carol, test_only.clone()).unwrap();
+        assert!(!check_capability(
+            context.clone(),
+            &call_with(&carol, delegation_by_carol, "test")
+        ));
+
+        // grants have to be marked delegatable
+        let delegation_of_not_delegatable =
+            delegate(&bob, &not_delegatable_token, Vec::new(), &carol, test_only).unwrap();
+        assert!(!check_capability(
+            context.clone(),
+            &call_with(&carol, delegation_of_not_delegatable, "test")
+        ));
+
+        // tampering with a delegation invalidates its signature
+        let tampered = CapTokenDelegation::new(
+            token.clone(),
+            bob.agent_id.address(),
+            carol.agent_id.address(),
+            broader,
+            CapArgConstraints::new(),
+            Iso8601::from(2_000_000_000),
+        )
+        .with_signature(delegation[0].provenance().signature());
+        assert!(!check_capability(
+            context.clone(),
+            &call_with(&carol, vec![tampered], "other_fn")
+        ));
     }
 }
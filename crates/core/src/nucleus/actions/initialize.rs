@@ -17,8 +17,11 @@ use holochain_core_types::{
         Entry,
     },
     error::HolochainError,
+    signature::{Provenance, Signature},
 };
-use holochain_persistence_api::cas::content::Address;
+use holochain_dpki::audit::verify_audited;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use holochain_wasm_utils::api_serialization::crypto::CryptoMethod;
 
 use crate::instance::dispatch_action;
 use snowflake::ProcessUniqueId;
@@ -70,7 +73,7 @@ pub async fn initialize_chain(
 
     let action_wrapper = ActionWrapper::new(Action::InitializeChain(dna.clone()));
     dispatch_action(context.action_channel(), action_wrapper.clone());
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     let _ = InitializingFuture {
         context: context.clone(),
         id,
@@ -89,6 +92,14 @@ pub async fn initialize_chain(
             .expect("Action channel not usable in initialize_chain()");
     }
 
+    // Make sure the agent key can actually sign before anything gets written to the chain.
+    // An agent whose key doesn't match its address would otherwise end up with a chain
+    // nobody can validate.
+    if let Err(error) = verify_agent_key(&dna, &context_clone) {
+        dispatch_error_result(&context_clone, error.clone());
+        return Err(error);
+    }
+
     // Commit DNA to chain
     let dna_entry = Entry::Dna(Box::new(dna.clone()));
     let dna_commit = commit_entry(dna_entry.clone(), None, &context_clone).await;
@@ -107,7 +118,12 @@ pub async fn initialize_chain(
 
     let ewh = create_entry_with_header_for_header(&context.state().unwrap(), dna_header)?;
     let entry_aspect = EntryAspect::Content(ewh.entry, ewh.header);
-    hold_aspect_no_ack(&ProcessUniqueId::new(), entry_aspect, context.clone()).await?;
+    hold_aspect_no_ack(
+        &context.id_generator.next_id(),
+        entry_aspect,
+        context.clone(),
+    )
+    .await?;
 
     // Commit AgentId to chain
     let agent_id_entry = Entry::AgentId(context_clone.agent_id.clone());
@@ -128,11 +144,21 @@ pub async fn initialize_chain(
 
         // mark the entry and it's header as held in the dht store because we always hold ourselves.
         let entry_aspect = EntryAspect::Content(agent_id_entry, agent_id_header.clone());
-        hold_aspect_no_ack(&ProcessUniqueId::new(), entry_aspect, context.clone()).await?;
+        hold_aspect_no_ack(
+            &context.id_generator.next_id(),
+            entry_aspect,
+            context.clone(),
+        )
+        .await?;
 
         let ewh = create_entry_with_header_for_header(&context.state().unwrap(), agent_id_header)?;
         let entry_aspect = EntryAspect::Content(ewh.entry, ewh.header);
-        hold_aspect_no_ack(&ProcessUniqueId::new(), entry_aspect, context.clone()).await?;
+        hold_aspect_no_ack(
+            &context.id_generator.next_id(),
+            entry_aspect,
+            context.clone(),
+        )
+        .await?;
     }
 
     let mut cap_functions = CapFunctions::new();
@@ -189,7 +215,12 @@ pub async fn initialize_chain(
                 let ewh =
                     create_entry_with_header_for_header(&context.state().unwrap(), grant_header)?;
                 let entry_aspect = EntryAspect::Content(ewh.entry, ewh.header);
-                hold_aspect_no_ack(&ProcessUniqueId::new(), entry_aspect, context.clone()).await?;
+                hold_aspect_no_ack(
+                    &context.id_generator.next_id(),
+                    entry_aspect,
+                    context.clone(),
+                )
+                .await?;
 
                 log_debug!(context, "initialize: created public token: {:?}", addr);
                 Some(addr)
@@ -215,7 +246,7 @@ pub async fn initialize_chain(
         )))
         .expect("Action channel not usable in initialize_chain()");
 
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     InitializationFuture {
         context: context.clone(),
         created_at: Instant::now(),
@@ -224,6 +255,51 @@ pub async fn initialize_chain(
     .await
 }
 
+/// Signs a genesis challenge with the agent's key through the conductor API and checks that
+/// the signature verifies against the agent's address. If the conductor manages keys with
+/// DPKI, the key also has to be registered there.
+pub fn verify_agent_key(dna: &Dna, context: &Arc<Context>) -> Result<(), HolochainError> {
+    let agent_address = context.agent_id.address();
+    match context.conductor_api.is_agent_key_registered() {
+        Ok(None) | Ok(Some(true)) => (),
+        Ok(Some(false)) => {
+            return Err(HolochainError::InitializationFailed(format!(
+                "Agent key verification failed: key {} is not registered in DPKI",
+                agent_address
+            )))
+        }
+        Err(error) => {
+            return Err(HolochainError::InitializationFailed(format!(
+                "Agent key verification failed: could not check DPKI registration of {}: {}",
+                agent_address, error
+            )))
+        }
+    }
+    let challenge = format!("genesis:{}:{}", dna.address(), agent_address);
+    let signature = context
+        .conductor_api
+        .execute(challenge.clone(), CryptoMethod::Sign)
+        .map_err(|error| {
+            HolochainError::InitializationFailed(format!(
+                "Agent key verification failed: could not sign with key for {}: {}",
+                agent_address, error
+            ))
+        })?;
+    let provenance = Provenance::new(agent_address.clone(), Signature::from(signature));
+    match verify_audited(
+        &provenance,
+        challenge,
+        "genesis_key_check",
+        &format!("genesis of instance {}", context.instance_name),
+    ) {
+        Ok(true) => Ok(()),
+        _ => Err(HolochainError::InitializationFailed(format!(
+            "Agent key verification failed: signature does not match agent address {}",
+            agent_address
+        ))),
+    }
+}
+
 /// Tracks if the initialization has started and the DNA is set in the nucleus.
 pub struct InitializingFuture {
     context: Arc<Context>,
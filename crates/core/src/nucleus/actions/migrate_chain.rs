@@ -0,0 +1,101 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    context::Context,
+    instance::dispatch_action,
+    wasm_engine::callback::{migrate_chain::migrate_chain, CallbackParams, CallbackResult},
+};
+use futures::{future::Future, task::Poll};
+use holochain_core_types::{
+    dna::Dna,
+    error::{HcResult, HolochainError},
+};
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use holochain_wasm_utils::api_serialization::migrate_chain::MigrateChainParams;
+use snowflake::ProcessUniqueId;
+use std::{pin::Pin, sync::Arc};
+
+/// Update DNA Action Creator
+/// Replaces the DNA held in the nucleus with the given new version.
+/// Returns a future that resolves once the new DNA is set.
+pub async fn update_dna(dna: Dna, context: &Arc<Context>) -> HcResult<()> {
+    let dna_address = dna.address();
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::UpdateDna(dna)),
+    );
+    UpdateDnaFuture {
+        context: context.clone(),
+        dna_address,
+        id: context.id_generator.next_id(),
+    }
+    .await
+}
+
+/// Calls the migrate_chain callback of every zome in the (new) DNA.
+/// Zomes that don't implement the callback are skipped.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn call_migrate_chain(
+    dna: &Dna,
+    params: MigrateChainParams,
+    context: &Arc<Context>,
+) -> HcResult<()> {
+    let callback_params = CallbackParams::MigrateChain(params);
+    let errors: Vec<(String, String)> = dna
+        .zomes
+        .keys()
+        .map(|zome_name| {
+            (
+                zome_name,
+                migrate_chain(context.clone(), zome_name, &callback_params),
+            )
+        })
+        .filter_map(|(zome_name, result)| match result {
+            CallbackResult::Fail(error_string) => Some((zome_name.to_owned(), error_string)),
+            _ => None,
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(HolochainError::ErrorGeneric(format!(
+            "At least one zome migrate_chain returned error: {:?}",
+            errors
+        )))
+    }
+}
+
+/// UpdateDnaFuture resolves to Ok(()) once the nucleus holds the DNA with the given address.
+pub struct UpdateDnaFuture {
+    context: Arc<Context>,
+    dna_address: Address,
+    id: ProcessUniqueId,
+}
+
+impl Future for UpdateDnaFuture {
+    type Output = HcResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context) -> Poll<Self::Output> {
+        if let Some(err) = self.context.action_channel_error("UpdateDnaFuture") {
+            return Poll::Ready(Err(err));
+        }
+        self.context
+            .register_waker(self.id.clone(), cx.waker().clone());
+
+        if let Some(state) = self.context.try_state() {
+            let updated = state
+                .nucleus()
+                .dna()
+                .map(|dna| dna.address() == self.dna_address)
+                .unwrap_or(false);
+            if updated {
+                self.context.unregister_waker(self.id.clone());
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -6,7 +6,7 @@ use crate::{
     },
     wasm_engine::{self, runtime::WasmCallData},
 };
-use holochain_core_types::error::HolochainError;
+use holochain_core_types::{error::HolochainError, validation::ValidationFailure};
 use holochain_persistence_api::cas::content::Address;
 use std::sync::Arc;
 
@@ -43,7 +43,9 @@ pub async fn run_validation_callback(
                     if call_result.is_null() {
                         Ok(())
                     } else {
-                        Err(ValidationError::Fail(call_result.to_string()))
+                        Err(ValidationError::Fail(
+                            ValidationFailure::from_callback_result(&call_result.to_string()),
+                        ))
                     }
                 }
                 // TODO: have "not matching schema" be its own error
@@ -1,7 +1,9 @@
 /// Nucleus is the module that handles DNA, including the Ribosome.
 ///
 pub mod actions;
+pub mod rate_limit;
 pub mod reducers;
+pub mod slow_calls;
 pub mod state;
 pub mod validation;
 pub use crate::{
@@ -22,7 +24,7 @@ use snowflake;
 use std::sync::Arc;
 
 /// Struct holding data for tracing the call of an HDK function from a zome function
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct HdkFnCall {
     pub function: ZomeApiFunction,
     pub parameters: JsonString,
@@ -31,7 +33,7 @@ pub struct HdkFnCall {
 pub type HdkFnCallResult = Result<JsonString, String>;
 
 /// Struct holding data for requesting the execution of a Zome function (QueueZomeFunctionCall Action)
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ZomeFnCall {
     id: snowflake::ProcessUniqueId,
     pub zome_name: String,
@@ -66,12 +68,16 @@ impl ZomeFnCall {
         parameters: J,
     ) -> Self {
         let params = parameters.into();
-        ZomeFnCall::new(
-            zome,
-            make_cap_request_for_call(context, token, function, params.clone()),
-            function,
-            params,
-        )
+        let id = context.id_generator.next_id();
+        ZomeFnCall {
+            id,
+            ..ZomeFnCall::new(
+                zome,
+                make_cap_request_for_call(context, token, function, params.clone()),
+                function,
+                params,
+            )
+        }
     }
 
     pub fn same_fn_as(&self, fn_call: &ZomeFnCall) -> bool {
@@ -0,0 +1,223 @@
+//! Rate limiting of incoming zome calls.
+//!
+//! Limits are counted separately for every capability token and every caller (the source of
+//! the call's provenance), so a misbehaving client can't monopolize the instance's nucleus
+//! queue. A call has to be within the limits of both its token and its caller. It is checked
+//! after validation and before the call gets queued, and it counts as in flight until its
+//! result is returned.
+use crate::nucleus::ZomeFnCall;
+use holochain_core_types::error::HolochainError;
+use holochain_locksmith::Mutex;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+/// Usage key of all calls to the instance, for its resource limits
+const INSTANCE_KEY: &str = "instance";
+
+/// Zome call limits per capability token and per caller. Limits that are not set don't apply.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ZomeCallRateLimits {
+    /// Maximum number of calls within a sliding window of one minute
+    #[serde(default)]
+    pub calls_per_minute: Option<u32>,
+    /// Maximum number of calls running at the same time
+    #[serde(default)]
+    pub concurrent_calls: Option<u32>,
+}
+
+#[derive(Default)]
+struct Usage {
+    recent_calls: VecDeque<Instant>,
+    in_flight: u32,
+}
+
+impl Usage {
+    fn forget_calls_before(&mut self, start: Instant) {
+        while self
+            .recent_calls
+            .front()
+            .map_or(false, |call| *call < start)
+        {
+            self.recent_calls.pop_front();
+        }
+    }
+}
+
+pub struct ZomeCallRateLimiter {
+    limits: ZomeCallRateLimits,
+    max_concurrent_calls: Option<u32>,
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+/// Marks a call as in flight until it gets dropped.
+pub struct RateLimitPermit {
+    limiter: Arc<ZomeCallRateLimiter>,
+    keys: Vec<String>,
+}
+
+impl Drop for RateLimitPermit {
+    fn drop(&mut self) {
+        let mut usage = self.limiter.usage.lock().unwrap();
+        for key in self.keys.iter() {
+            if let Some(usage) = usage.get_mut(key) {
+                usage.in_flight = usage.in_flight.saturating_sub(1);
+            }
+        }
+    }
+}
+
+impl ZomeCallRateLimiter {
+    pub fn new(limits: ZomeCallRateLimits) -> Self {
+        ZomeCallRateLimiter {
+            limits,
+            max_concurrent_calls: None,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Also limits the number of calls running at the same time across all tokens and
+    /// callers, see [ResourceLimits](crate::resource_limits::ResourceLimits).
+    pub fn with_max_concurrent_calls(mut self, max_concurrent_calls: Option<u32>) -> Self {
+        self.max_concurrent_calls = max_concurrent_calls;
+        self
+    }
+
+    pub fn limits(&self) -> &ZomeCallRateLimits {
+        &self.limits
+    }
+
+    /// Counts the call against the limits of its token and its caller.
+    /// Fails with [HolochainError::RateLimited] if either of them is exhausted, or with
+    /// [HolochainError::ResourceLimitExceeded] if the instance runs as many calls as it may,
+    /// in which case nothing gets counted.
+    pub fn acquire(
+        limiter: &Arc<ZomeCallRateLimiter>,
+        fn_call: &ZomeFnCall,
+    ) -> Result<RateLimitPermit, HolochainError> {
+        let mut keys = Vec::new();
+        if limiter.limits != ZomeCallRateLimits::default() {
+            keys.push(format!("token:{}", fn_call.cap_token()));
+            keys.push(format!("caller:{}", fn_call.cap.provenance.source()));
+        }
+        if limiter.max_concurrent_calls.is_some() {
+            keys.push(INSTANCE_KEY.to_string());
+        }
+        if keys.is_empty() {
+            return Ok(RateLimitPermit {
+                limiter: limiter.clone(),
+                keys: Vec::new(),
+            });
+        }
+
+        let now = Instant::now();
+        let window_start = now.checked_sub(RATE_LIMIT_WINDOW).unwrap_or(now);
+        let mut usage = limiter.usage.lock().unwrap();
+        for key in keys.iter() {
+            let usage = usage.entry(key.clone()).or_default();
+            usage.forget_calls_before(window_start);
+            if key == INSTANCE_KEY {
+                if let Some(max) = limiter.max_concurrent_calls {
+                    if usage.in_flight >= max {
+                        return Err(HolochainError::ResourceLimitExceeded(format!(
+                            "the instance runs {} calls already",
+                            max
+                        )));
+                    }
+                }
+                continue;
+            }
+            if let Some(max) = limiter.limits.calls_per_minute {
+                if usage.recent_calls.len() >= max as usize {
+                    return Err(HolochainError::RateLimited(format!(
+                        "more than {} calls per minute for {}",
+                        max, key
+                    )));
+                }
+            }
+            if let Some(max) = limiter.limits.concurrent_calls {
+                if usage.in_flight >= max {
+                    return Err(HolochainError::RateLimited(format!(
+                        "more than {} concurrent calls for {}",
+                        max, key
+                    )));
+                }
+            }
+        }
+        for key in keys.iter() {
+            let usage = usage.entry(key.clone()).or_default();
+            usage.recent_calls.push_back(now);
+            usage.in_flight += 1;
+        }
+        Ok(RateLimitPermit {
+            limiter: limiter.clone(),
+            keys,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::nucleus::tests::test_zome_call;
+
+    fn limiter(
+        calls_per_minute: Option<u32>,
+        concurrent_calls: Option<u32>,
+    ) -> Arc<ZomeCallRateLimiter> {
+        Arc::new(ZomeCallRateLimiter::new(ZomeCallRateLimits {
+            calls_per_minute,
+            concurrent_calls,
+        }))
+    }
+
+    #[test]
+    fn limits_calls_per_minute() {
+        let limiter = limiter(Some(2), None);
+        let call = test_zome_call();
+        assert!(ZomeCallRateLimiter::acquire(&limiter, &call).is_ok());
+        assert!(ZomeCallRateLimiter::acquire(&limiter, &call).is_ok());
+        match ZomeCallRateLimiter::acquire(&limiter, &call) {
+            Err(HolochainError::RateLimited(_)) => (),
+            _ => panic!("third call within a minute should be rate limited"),
+        }
+    }
+
+    #[test]
+    fn limits_concurrent_calls_until_permit_is_dropped() {
+        let limiter = limiter(None, Some(1));
+        let call = test_zome_call();
+        let permit = ZomeCallRateLimiter::acquire(&limiter, &call).unwrap();
+        assert!(ZomeCallRateLimiter::acquire(&limiter, &call).is_err());
+        drop(permit);
+        assert!(ZomeCallRateLimiter::acquire(&limiter, &call).is_ok());
+    }
+
+    #[test]
+    fn limits_concurrent_calls_of_the_instance() {
+        let limiter = Arc::new(
+            ZomeCallRateLimiter::new(ZomeCallRateLimits::default())
+                .with_max_concurrent_calls(Some(1)),
+        );
+        let call = test_zome_call();
+        let permit = ZomeCallRateLimiter::acquire(&limiter, &call).unwrap();
+        match ZomeCallRateLimiter::acquire(&limiter, &call) {
+            Err(HolochainError::ResourceLimitExceeded(_)) => (),
+            _ => panic!("second concurrent call should exceed the instance's limit"),
+        }
+        drop(permit);
+        assert!(ZomeCallRateLimiter::acquire(&limiter, &call).is_ok());
+    }
+
+    #[test]
+    fn no_limits_let_everything_through() {
+        let limiter = limiter(None, None);
+        let call = test_zome_call();
+        let _permits: Vec<_> = (0..100)
+            .map(|_| ZomeCallRateLimiter::acquire(&limiter, &call).unwrap())
+            .collect();
+    }
+}
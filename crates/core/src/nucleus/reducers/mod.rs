@@ -5,6 +5,7 @@ pub mod return_initialization_result;
 pub mod return_zome_function_result;
 pub mod trace_invoke_hdk_function;
 pub mod trace_return_hdk_function;
+pub mod update_dna;
 
 use crate::{
     action::{Action, ActionWrapper, NucleusReduceFn},
@@ -17,6 +18,7 @@ use crate::{
             return_zome_function_result::reduce_return_zome_function_result,
             trace_invoke_hdk_function::reduce_trace_invoke_hdk_function,
             trace_return_hdk_function::reduce_trace_return_hdk_function,
+            update_dna::reduce_update_dna,
         },
         state::NucleusState,
     },
@@ -35,6 +37,7 @@ fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<NucleusReduceFn> {
         Action::QueueZomeFunctionCall(_) => Some(reduce_queue_zome_function_call),
         Action::TraceInvokeHdkFunction(_) => Some(reduce_trace_invoke_hdk_function),
         Action::TraceReturnHdkFunction(_) => Some(reduce_trace_return_hdk_function),
+        Action::UpdateDna(_) => Some(reduce_update_dna),
         _ => None,
     }
 }
@@ -0,0 +1,42 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    nucleus::state::NucleusState,
+    state::State,
+};
+
+/// Reduce UpdateDna Action
+/// Swaps the DNA of the nucleus for a new version, leaving its status untouched.
+#[allow(unknown_lints)]
+#[allow(clippy::needless_pass_by_value)]
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn reduce_update_dna(
+    state: &mut NucleusState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let dna = unwrap_to!(action => Action::UpdateDna);
+    state.dna = Some(dna.clone());
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{instance::tests::test_context, state::test_store};
+    use holochain_core_types::dna::Dna;
+
+    #[test]
+    fn can_reduce_update_dna() {
+        let context = test_context("jimmy", None);
+        let store = test_store(context);
+        let mut nucleus = NucleusState::new();
+        nucleus.dna = Some(Dna::new());
+
+        let mut new_dna = Dna::new();
+        new_dna.uuid = "new version".to_string();
+        let action_wrapper = ActionWrapper::new(Action::UpdateDna(new_dna.clone()));
+        reduce_update_dna(&mut nucleus, &store, &action_wrapper);
+
+        assert_eq!(Some(new_dna), nucleus.dna());
+    }
+}
@@ -0,0 +1,304 @@
+//! Detection of zome calls that run too long.
+//!
+//! The [ZomeCallWatchdog] of an instance knows every running zome call and the HDK functions
+//! it called so far. A scheduled job checks it every second and reports each call that
+//! exceeds its threshold once: as a warning in the log with the HDK calls so far, as a
+//! [SlowZomeCall] signal and in the `holochain_slow_zome_calls_total` metric.
+use crate::{
+    context::Context,
+    nucleus::ZomeFnCall,
+    signal::Signal,
+    wasm_engine::{api::ZomeApiFunction, Defn},
+};
+use holochain_locksmith::Mutex;
+use holochain_metrics::prometheus;
+use snowflake::ProcessUniqueId;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+fn default_threshold_ms() -> u64 {
+    10_000
+}
+
+/// When zome calls count as slow
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SlowZomeCallConfig {
+    /// Calls running longer than this many milliseconds get reported
+    #[serde(default = "default_threshold_ms")]
+    pub threshold_ms: u64,
+    /// Thresholds of single functions in milliseconds, by `<zome>/<function>`
+    #[serde(default)]
+    pub functions: BTreeMap<String, u64>,
+}
+
+impl Default for SlowZomeCallConfig {
+    fn default() -> Self {
+        SlowZomeCallConfig {
+            threshold_ms: default_threshold_ms(),
+            functions: BTreeMap::new(),
+        }
+    }
+}
+
+impl SlowZomeCallConfig {
+    pub fn threshold(&self, call: &ZomeFnCall) -> Duration {
+        let function = format!("{}/{}", call.zome_name, call.fn_name);
+        Duration::from_millis(*self.functions.get(&function).unwrap_or(&self.threshold_ms))
+    }
+}
+
+/// Time a slow call spent in one HDK function
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HdkCallBreakdown {
+    pub function: String,
+    pub calls: u32,
+    pub total_ms: u64,
+    /// Whether the zome call is in this function right now
+    pub running: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SlowZomeCall {
+    pub zome: String,
+    pub function: String,
+    pub running_ms: u64,
+    pub threshold_ms: u64,
+    /// HDK functions in the order they got called first
+    pub hdk_calls: Vec<HdkCallBreakdown>,
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+struct HdkCallTiming {
+    function: ZomeApiFunction,
+    started: Instant,
+    duration: Option<Duration>,
+}
+
+struct RunningCall {
+    call: ZomeFnCall,
+    started: Instant,
+    hdk_calls: Vec<HdkCallTiming>,
+    reported: bool,
+}
+
+impl RunningCall {
+    fn report(&self, now: Instant, threshold: Duration) -> SlowZomeCall {
+        let mut hdk_calls: Vec<HdkCallBreakdown> = Vec::new();
+        for timing in self.hdk_calls.iter() {
+            let function = timing.function.as_str().to_string();
+            let index = match hdk_calls.iter().position(|call| call.function == function) {
+                Some(index) => index,
+                None => {
+                    hdk_calls.push(HdkCallBreakdown {
+                        function,
+                        calls: 0,
+                        total_ms: 0,
+                        running: false,
+                    });
+                    hdk_calls.len() - 1
+                }
+            };
+            let breakdown = &mut hdk_calls[index];
+            breakdown.calls += 1;
+            breakdown.total_ms += millis(
+                timing
+                    .duration
+                    .unwrap_or_else(|| now.duration_since(timing.started)),
+            );
+            breakdown.running |= timing.duration.is_none();
+        }
+        SlowZomeCall {
+            zome: self.call.zome_name.clone(),
+            function: self.call.fn_name.clone(),
+            running_ms: millis(now.duration_since(self.started)),
+            threshold_ms: millis(threshold),
+            hdk_calls,
+        }
+    }
+}
+
+/// The running zome calls of an instance with their HDK calls
+pub struct ZomeCallWatchdog {
+    config: SlowZomeCallConfig,
+    running: Mutex<HashMap<ProcessUniqueId, RunningCall>>,
+}
+
+/// Keeps a call watched until it gets dropped
+pub struct WatchedCall {
+    watchdog: Arc<ZomeCallWatchdog>,
+    id: ProcessUniqueId,
+}
+
+impl Drop for WatchedCall {
+    fn drop(&mut self) {
+        self.watchdog.running.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl ZomeCallWatchdog {
+    pub fn new(config: SlowZomeCallConfig) -> Self {
+        ZomeCallWatchdog {
+            config,
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &SlowZomeCallConfig {
+        &self.config
+    }
+
+    /// Watches the call from now on until the returned guard gets dropped
+    pub fn watch(watchdog: &Arc<ZomeCallWatchdog>, call: &ZomeFnCall) -> WatchedCall {
+        watchdog.running.lock().unwrap().insert(
+            call.id(),
+            RunningCall {
+                call: call.clone(),
+                started: Instant::now(),
+                hdk_calls: Vec::new(),
+                reported: false,
+            },
+        );
+        WatchedCall {
+            watchdog: watchdog.clone(),
+            id: call.id(),
+        }
+    }
+
+    pub fn begin_hdk_call(&self, call: &ZomeFnCall, function: ZomeApiFunction) {
+        if let Some(running) = self.running.lock().unwrap().get_mut(&call.id()) {
+            running.hdk_calls.push(HdkCallTiming {
+                function,
+                started: Instant::now(),
+                duration: None,
+            });
+        }
+    }
+
+    /// Ends the latest HDK call of the zome call that is still running
+    pub fn end_hdk_call(&self, call: &ZomeFnCall) {
+        if let Some(running) = self.running.lock().unwrap().get_mut(&call.id()) {
+            if let Some(timing) = running
+                .hdk_calls
+                .iter_mut()
+                .rev()
+                .find(|timing| timing.duration.is_none())
+            {
+                timing.duration = Some(timing.started.elapsed());
+            }
+        }
+    }
+
+    /// The calls that exceeded their threshold since the last check
+    pub fn check(&self) -> Vec<SlowZomeCall> {
+        let now = Instant::now();
+        let mut running = self.running.lock().unwrap();
+        running
+            .values_mut()
+            .filter_map(|call| {
+                let threshold = self.config.threshold(&call.call);
+                if call.reported || now.duration_since(call.started) < threshold {
+                    return None;
+                }
+                call.reported = true;
+                Some(call.report(now, threshold))
+            })
+            .collect()
+    }
+}
+
+/// Logs, signals and counts the calls that just exceeded their threshold
+pub fn report_slow_zome_calls(context: &Context) {
+    for slow_call in context.zome_call_watchdog.check() {
+        let hdk_calls = slow_call
+            .hdk_calls
+            .iter()
+            .map(|call| {
+                format!(
+                    "{} x{} {} ms{}",
+                    call.function,
+                    call.calls,
+                    call.total_ms,
+                    if call.running { " (running)" } else { "" }
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        log_warn!(
+            context,
+            "nucleus: Zome call {}/{} is running for {} ms, longer than {} ms. HDK calls so far: [{}]",
+            slow_call.zome,
+            slow_call.function,
+            slow_call.running_ms,
+            slow_call.threshold_ms,
+            hdk_calls
+        );
+        prometheus::increment_counter(
+            "holochain_slow_zome_calls_total",
+            "Zome calls that ran longer than their threshold",
+            &[
+                ("instance", &context.get_instance_name()),
+                ("zome", &slow_call.zome),
+                ("function", &slow_call.function),
+            ],
+        );
+        if let Some(tx) = context.signal_tx() {
+            if let Err(error) = tx.send(Signal::SlowZomeCall(slow_call)) {
+                log_warn!(
+                    context,
+                    "nucleus: Signal channel is closed! No signals can be sent ({:?}).",
+                    error
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::nucleus::tests::dummy_capability_request;
+    use std::thread;
+
+    #[test]
+    fn reports_calls_over_their_threshold_once() {
+        let mut functions = BTreeMap::new();
+        functions.insert(String::from("chat/fast"), 0);
+        let watchdog = Arc::new(ZomeCallWatchdog::new(SlowZomeCallConfig {
+            threshold_ms: 60_000,
+            functions,
+        }));
+        let fast = ZomeFnCall::new("chat", dummy_capability_request(), "fast", "{}");
+        let slow = ZomeFnCall::new("chat", dummy_capability_request(), "slow", "{}");
+        let _fast_watched = ZomeCallWatchdog::watch(&watchdog, &fast);
+        let slow_watched = ZomeCallWatchdog::watch(&watchdog, &slow);
+        watchdog.begin_hdk_call(&fast, ZomeApiFunction::GetAppEntry);
+        thread::sleep(Duration::from_millis(5));
+        watchdog.end_hdk_call(&fast);
+        watchdog.begin_hdk_call(&fast, ZomeApiFunction::GetAppEntry);
+        watchdog.end_hdk_call(&fast);
+        watchdog.begin_hdk_call(&fast, ZomeApiFunction::CommitAppEntry);
+
+        let reported = watchdog.check();
+        assert_eq!(reported.len(), 1);
+        let report = &reported[0];
+        assert_eq!(report.function, "fast");
+        assert_eq!(report.threshold_ms, 0);
+        assert_eq!(report.hdk_calls.len(), 2);
+        assert_eq!(report.hdk_calls[0].function, "hc_get_entry");
+        assert_eq!(report.hdk_calls[0].calls, 2);
+        assert!(report.hdk_calls[0].total_ms >= 5);
+        assert!(!report.hdk_calls[0].running);
+        assert_eq!(report.hdk_calls[1].function, "hc_commit_entry");
+        assert!(report.hdk_calls[1].running);
+
+        assert!(watchdog.check().is_empty());
+        drop(slow_watched);
+        assert_eq!(watchdog.running.lock().unwrap().len(), 1);
+    }
+}
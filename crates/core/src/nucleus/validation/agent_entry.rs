@@ -2,16 +2,18 @@ use crate::{
     context::Context,
     nucleus::{
         actions::run_validation_callback::run_validation_callback,
-        validation::{ValidationError, ValidationResult},
+        validation::{get_entry_with_header, record_coverage, ValidationError, ValidationResult},
         CallbackFnCall,
     },
+    validation_coverage::{ValidationBranch, ValidationVariant},
 };
 use holochain_core_types::{
     agent::AgentId,
-    entry::Entry,
+    entry::{entry_type::EntryType, Entry},
+    error::HolochainError,
     validation::{EntryValidationData, ValidationData},
 };
-use holochain_persistence_api::cas::content::AddressableContent;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use holochain_wasm_utils::api_serialization::validation::AgentIdValidationArgs;
 
 use futures::{future, future::FutureExt};
@@ -20,6 +22,7 @@ use std::sync::Arc;
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub async fn validate_agent_entry(
     entry: Entry,
+    link: Option<Address>,
     validation_data: ValidationData,
     context: &Arc<Context>,
 ) -> ValidationResult {
@@ -27,11 +30,38 @@ pub async fn validate_agent_entry(
 
     let agent_id = unwrap_to!(entry => Entry::AgentId);
 
-    let params = AgentIdValidationArgs {
-        validation_data: EntryValidationData::<AgentId>::Create {
+    let entry_validation_data = match link {
+        None => EntryValidationData::<AgentId>::Create {
             entry: agent_id.to_owned(),
             validation_data,
         },
+        Some(original_address) => {
+            let (old_entry, old_entry_header) =
+                get_entry_with_header(context.clone(), &original_address).map_err(|e| match e {
+                    HolochainError::Timeout(_) => ValidationError::Error(e),
+                    _ => ValidationError::UnresolvedDependencies(vec![original_address.clone()]),
+                })?;
+            let old_agent_id = match old_entry.entry {
+                Entry::AgentId(old_agent_id) => old_agent_id,
+                _ => {
+                    return Err(ValidationError::Fail(
+                        "Agent entry can only update another agent entry".into(),
+                    ))
+                }
+            };
+            validate_agent_update(agent_id, &old_agent_id, &validation_data)?;
+            validate_membrane_proof_kept(agent_id, &old_agent_id)?;
+            EntryValidationData::<AgentId>::Modify {
+                new_entry: agent_id.to_owned(),
+                old_entry: old_agent_id,
+                old_entry_header,
+                validation_data,
+            }
+        }
+    };
+
+    let params = AgentIdValidationArgs {
+        validation_data: entry_validation_data,
     };
 
     log_debug!(context, "Validating agent entry with args: {:?}", params);
@@ -44,6 +74,13 @@ pub async fn validate_agent_entry(
     }))
     .await;
 
+    let variant = ValidationVariant::of(&params.validation_data);
+    let agent_type = EntryType::AgentId.to_string();
+    for ((zome_name, _), result) in dna.zomes.iter().zip(results.iter()) {
+        let branch = ValidationBranch::entry(zome_name, &agent_type, variant);
+        record_coverage(context, &dna, branch, result);
+    }
+
     let errors: Vec<ValidationError> = results
         .iter()
         .filter_map(|r| match r {
@@ -61,3 +98,116 @@ pub async fn validate_agent_entry(
         ))
     }
 }
+
+/// System level checks for updated agent entries: the signing key (and so the agent address)
+/// must not change, the update must be a later revision than the entry it replaces and it has
+/// to be signed by that very agent.
+fn validate_agent_update(
+    agent_id: &AgentId,
+    old_agent_id: &AgentId,
+    validation_data: &ValidationData,
+) -> ValidationResult {
+    if agent_id.address() != old_agent_id.address() {
+        return Err(ValidationError::Fail(
+            "Agent entry update must keep the public signing key of the original".into(),
+        ));
+    }
+    if agent_id.revision.unwrap_or(0) <= old_agent_id.revision.unwrap_or(0) {
+        return Err(ValidationError::Fail(
+            "Agent entry update must have a higher revision than the original".into(),
+        ));
+    }
+    if !validation_data.sources().contains(&agent_id.address()) {
+        return Err(ValidationError::Fail(
+            "Agent entry update must be signed by the agent itself".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// The membrane proof is checked by the app when the agent joins, so an update must not be
+/// able to swap it out or drop it afterwards.
+fn validate_membrane_proof_kept(agent_id: &AgentId, old_agent_id: &AgentId) -> ValidationResult {
+    if agent_id.membrane_proof != old_agent_id.membrane_proof {
+        return Err(ValidationError::Fail(
+            "Agent entry update must keep the membrane proof of the original".into(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{
+        agent::test_agent_id,
+        chain_header::ChainHeader,
+        entry::entry_type::EntryType,
+        signature::{Provenance, Signature},
+        time::test_iso_8601,
+        validation::ValidationPackage,
+    };
+
+    fn validation_data_signed_by(agent_address: &Address, source: Address) -> ValidationData {
+        let header = ChainHeader::new(
+            &EntryType::AgentId,
+            agent_address,
+            &[Provenance::new(source, Signature::from("sig"))],
+            &None,
+            &None,
+            &Some(agent_address.clone()),
+            &test_iso_8601(),
+        );
+        ValidationData {
+            package: ValidationPackage::only_header(header),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn agent_update_must_keep_key_and_be_self_signed() {
+        let agent = test_agent_id();
+        let updated = agent.updated(Some("robert".to_string()), None);
+        let updated_address = Entry::AgentId(updated.clone()).address();
+
+        let own_data = validation_data_signed_by(&updated_address, agent.address());
+        assert_eq!(validate_agent_update(&updated, &agent, &own_data), Ok(()));
+
+        let other_agent = AgentId::new("bob", "some other agent".to_string());
+        assert!(validate_agent_update(&updated, &other_agent, &own_data).is_err());
+
+        let foreign_data = validation_data_signed_by(&updated_address, other_agent.address());
+        assert!(validate_agent_update(&updated, &agent, &foreign_data).is_err());
+    }
+
+    #[test]
+    fn agent_update_must_be_a_later_revision() {
+        let agent = test_agent_id();
+        let updated = agent.updated(Some("robert".to_string()), None);
+        let updated_again = updated.updated(Some("bobby".to_string()), None);
+        let own_data = validation_data_signed_by(&agent.address(), agent.address());
+
+        assert_eq!(
+            validate_agent_update(&updated_again, &updated, &own_data),
+            Ok(())
+        );
+        assert!(validate_agent_update(&updated, &updated_again, &own_data).is_err());
+        assert!(validate_agent_update(&agent, &updated, &own_data).is_err());
+        assert!(validate_agent_update(&updated, &updated, &own_data).is_err());
+    }
+
+    #[test]
+    fn agent_update_must_keep_membrane_proof() {
+        let agent = test_agent_id().with_membrane_proof(Some("invite".to_string()));
+        let updated = agent.updated(Some("robert".to_string()), None);
+        assert_eq!(validate_membrane_proof_kept(&updated, &agent), Ok(()));
+
+        let swapped = updated
+            .clone()
+            .with_membrane_proof(Some("forged".to_string()));
+        assert!(validate_membrane_proof_kept(&swapped, &agent).is_err());
+
+        let dropped = updated.with_membrane_proof(None);
+        assert!(validate_membrane_proof_kept(&dropped, &agent).is_err());
+    }
+}
@@ -4,9 +4,12 @@ use crate::{
         actions::{
             get_entry::get_entry_from_dht, run_validation_callback::run_validation_callback,
         },
-        validation::{entry_to_validation_data, ValidationError, ValidationResult},
+        validation::{
+            entry_to_validation_data, record_coverage, ValidationError, ValidationResult,
+        },
         CallbackFnCall,
     },
+    validation_coverage::{ValidationBranch, ValidationVariant},
 };
 use holochain_core_types::{
     entry::{entry_type::AppEntryType, Entry},
@@ -40,7 +43,14 @@ pub async fn validate_app_entry(
         validation_data: entry_to_validation_data(context.clone(), &entry, link, validation_data)
             .map_err(ValidationError::Error)?,
     };
+    let branch = ValidationBranch::entry(
+        &zome_name,
+        &app_entry_type.to_string(),
+        ValidationVariant::of(&params.validation_data),
+    );
     let call = CallbackFnCall::new(&zome_name, "__hdk_validate_app_entry", params);
 
-    run_validation_callback(entry.address(), call, &context).await
+    let result = run_validation_callback(entry.address(), call, &context).await;
+    record_coverage(context, &dna, branch, &result);
+    result
 }
@@ -0,0 +1,169 @@
+use crate::{
+    context::Context,
+    entry::CanPublish,
+    nucleus::validation::{
+        provenances::validate_header_provenances, ValidationError, ValidationResult,
+    },
+    workflows::get_entry_result::get_entry_with_meta_workflow,
+};
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    entry::{entry_type::EntryType, Entry, EntryWithMetaAndHeader},
+    time::Timeout,
+    validation::ValidationData,
+};
+use holochain_persistence_api::cas::content::Address;
+use std::sync::Arc;
+
+/// Header entries get published for every entry of a source chain, so other agents can
+/// hold them and rebuild the chain from the DHT. Since they come from peers we can't trust,
+/// the header inside the entry gets checked like the header of any other entry would be:
+///
+/// 1. Rejects headers that aren't published by one of their own authors
+/// 2. Rejects headers whose provenances don't sign their entry address
+/// 3. Rejects headers that don't link to a previous header, unless they start the chain
+///    with the DNA, and headers that are older than the header they link to
+/// 4. Waits for the entry of the header to be held, if it gets published at all, and
+///    rejects headers whose entry is of another type
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn validate_chain_header_entry(
+    entry: Entry,
+    validation_data: ValidationData,
+    context: &Arc<Context>,
+) -> ValidationResult {
+    let header = unwrap_to!(entry => Entry::ChainHeader);
+    let authors: Vec<Address> = header
+        .provenances()
+        .iter()
+        .map(|provenance| provenance.source())
+        .collect();
+    if !validation_data
+        .sources()
+        .iter()
+        .all(|source| authors.contains(source))
+    {
+        return Err(ValidationError::Fail(
+            format!(
+                "Header of entry {} is not published by its author",
+                header.entry_address()
+            )
+            .into(),
+        ));
+    }
+
+    validate_header_provenances(header)?;
+    validate_header_link(header)?;
+
+    if let Some(previous_address) = header.link() {
+        match get_held_entry(&previous_address, context).await? {
+            Entry::ChainHeader(previous) => {
+                if previous.timestamp() > header.timestamp() {
+                    return Err(ValidationError::Fail(
+                        format!(
+                            "Header of entry {} is older than the previous header {}",
+                            header.entry_address(),
+                            previous_address
+                        )
+                        .into(),
+                    ));
+                }
+            }
+            _ => {
+                return Err(ValidationError::Fail(
+                    format!(
+                        "Header of entry {} links to {}, which is no header",
+                        header.entry_address(),
+                        previous_address
+                    )
+                    .into(),
+                ))
+            }
+        }
+    }
+
+    if header.entry_type().can_publish(context) {
+        let held_entry = get_held_entry(header.entry_address(), context).await?;
+        if held_entry.entry_type() != *header.entry_type() {
+            return Err(ValidationError::Fail(
+                format!(
+                    "Header of entry {} has type {}, but the entry is of type {}",
+                    header.entry_address(),
+                    header.entry_type(),
+                    held_entry.entry_type()
+                )
+                .into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Only the first header of a chain, the one of its DNA, doesn't link to a previous header
+fn validate_header_link(header: &ChainHeader) -> ValidationResult {
+    match (header.entry_type(), header.link()) {
+        (EntryType::Dna, _) | (_, Some(_)) => Ok(()),
+        (entry_type, None) => Err(ValidationError::Fail(
+            format!(
+                "Header of {} entry {} doesn't link to a previous header",
+                entry_type,
+                header.entry_address()
+            )
+            .into(),
+        )),
+    }
+}
+
+/// Gets the entry from the DHT, or asks to try again later if no one holds it yet
+async fn get_held_entry(
+    address: &Address,
+    context: &Arc<Context>,
+) -> Result<Entry, ValidationError> {
+    match get_entry_with_meta_workflow(context, address, &Timeout::default()).await {
+        Ok(Some(EntryWithMetaAndHeader {
+            entry_with_meta, ..
+        })) => Ok(entry_with_meta.entry),
+        _ => Err(ValidationError::UnresolvedDependencies(vec![
+            address.clone()
+        ])),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{
+        chain_header::test_provenances,
+        entry::{entry_type::test_entry_type, test_entry},
+        time::test_iso_8601,
+    };
+    use holochain_persistence_api::cas::content::AddressableContent;
+
+    fn header(entry_type: &EntryType, link: Option<Address>) -> ChainHeader {
+        ChainHeader::new(
+            entry_type,
+            &test_entry().address(),
+            &test_provenances("sig"),
+            &link,
+            &None,
+            &None,
+            &test_iso_8601(),
+        )
+    }
+
+    #[test]
+    fn only_dna_headers_start_a_chain() {
+        assert_eq!(validate_header_link(&header(&EntryType::Dna, None)), Ok(()));
+        assert!(validate_header_link(&header(&test_entry_type(), None)).is_err());
+        assert!(validate_header_link(&header(&EntryType::AgentId, None)).is_err());
+        let previous = Some(Address::from("QmPreviousHeader"));
+        assert_eq!(
+            validate_header_link(&header(&test_entry_type(), previous)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_headers_with_invalid_signatures() {
+        assert!(validate_header_provenances(&header(&EntryType::Dna, None)).is_err());
+    }
+}
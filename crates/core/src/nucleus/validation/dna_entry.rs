@@ -0,0 +1,434 @@
+use crate::{
+    context::Context,
+    nucleus::{
+        actions::call_zome_function::is_grant_revoked,
+        validation::{
+            provenances::validate_header_provenances, ValidationContext, ValidationError,
+            ValidationResult,
+        },
+    },
+};
+use holochain_core_types::{
+    dna::Dna,
+    entry::{cap_entries::ReservedCapabilityId, entry_type::EntryType, Entry},
+    error::HolochainError,
+    time::Iso8601,
+    validation::ValidationData,
+};
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use std::sync::Arc;
+
+/// DNA property naming the agent that grants admin capabilities on chains running the DNA
+pub const ADMIN_GRANTOR_PROPERTY: &str = "admin_grantor";
+
+/// DNA entries only ever get committed by an instance to its own source chain: as its first
+/// entry, or when the instance migrates to a new version of its DNA, in which case the entry
+/// updates the address of the DNA it replaces. They never get published, so other agents
+/// have no business sending them to us for holding.
+///
+/// 1. Rejects DNA entries that are to be held
+/// 2. Rejects DNA entries that aren't signed by the agent of the source chain, as no
+///    capability grant extends to committing DNA
+/// 3. Rejects DNA entries of another DNA than the one the instance runs
+/// 4. Rejects DNA entries after the start of the chain, unless they update the DNA the chain
+///    ran on until then
+/// 5. Rejects such updates unless they pass validate_dna_update
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn validate_dna_entry(
+    entry: Entry,
+    link: Option<Address>,
+    validation_data: ValidationData,
+    context: &Arc<Context>,
+    validation_context: ValidationContext,
+) -> ValidationResult {
+    if let ValidationContext::Holding = validation_context {
+        return Err(ValidationError::Fail(
+            "DNA entries are private to their source chain and can't be held".into(),
+        ));
+    }
+
+    if !validation_data
+        .sources()
+        .contains(&context.agent_id.address())
+    {
+        return Err(ValidationError::Fail(
+            "DNA entries can only be committed by the agent of the source chain".into(),
+        ));
+    }
+
+    let dna_address = entry.address();
+    let running_dna = context
+        .get_dna()
+        .ok_or(ValidationError::Error(HolochainError::DnaMissing))?;
+    if dna_address != running_dna.address() {
+        return Err(ValidationError::Fail(
+            format!(
+                "DNA entry {} is not the DNA {} the instance runs",
+                dna_address,
+                running_dna.address()
+            )
+            .into(),
+        ));
+    }
+
+    if validation_data.package.chain_header.link().is_none() {
+        return Ok(());
+    }
+    let replaced_dna = link.ok_or_else(|| {
+        ValidationError::Fail(
+            "DNA entries can only be committed after the start of the chain to migrate it".into(),
+        )
+    })?;
+    let state = context
+        .state()
+        .ok_or_else(|| ValidationError::Error("Couldn't get instance state".into()))?;
+    let chain_dna = state
+        .agent()
+        .iter_chain()
+        .find(|header| header.entry_type() == &EntryType::Dna)
+        .map(|header| header.entry_address().clone());
+    if replaced_dna == dna_address || Some(&replaced_dna) != chain_dna.as_ref() {
+        return Err(ValidationError::Fail(
+            format!(
+                "Migration to DNA {} has to replace the DNA the chain ran on, but replaces {}",
+                dna_address, replaced_dna
+            )
+            .into(),
+        ));
+    }
+    let replaced_dna = match state.agent().chain_store().get(&replaced_dna) {
+        Ok(Some(Entry::Dna(replaced_dna))) => replaced_dna,
+        _ => {
+            return Err(ValidationError::Error(
+                format!("Couldn't get replaced DNA {} from the chain", replaced_dna).into(),
+            ))
+        }
+    };
+    validate_dna_update(&running_dna, &replaced_dna, context)
+}
+
+/// Checks that the instance's chain may be moved from the replaced DNA to the new one.
+/// Called before a chain migration swaps the DNA, and again when its DNA entry gets validated.
+///
+/// 1. Rejects updates of chains whose DNA doesn't name an admin grantor in its properties
+/// 2. Rejects updates unless the agent is that grantor or holds an admin grant from it, i.e.
+///    its chain has a grant with the reserved id `hc_admin` that is assigned to the agent,
+///    signed by the grantor, not expired and not revoked. The agent can commit such a grant
+///    itself, but can't sign it for the grantor.
+/// 3. Rejects updates declaring a properties hash that doesn't match the new properties
+/// 4. Rejects updates changing the properties without declaring their hash
+pub(crate) fn validate_dna_update(
+    new_dna: &Dna,
+    replaced_dna: &Dna,
+    context: &Arc<Context>,
+) -> ValidationResult {
+    let grantor = replaced_dna
+        .properties
+        .get(ADMIN_GRANTOR_PROPERTY)
+        .and_then(|grantor| grantor.as_str())
+        .map(Address::from)
+        .ok_or_else(|| {
+            ValidationError::Fail(
+                format!(
+                    "DNA {} names no {} that could allow updating it",
+                    replaced_dna.address(),
+                    ADMIN_GRANTOR_PROPERTY
+                )
+                .into(),
+            )
+        })?;
+    if grantor != context.agent_id.address() && !holds_admin_grant(&grantor, context) {
+        return Err(ValidationError::Fail(
+            "DNA updates need an admin capability grant from the admin grantor of the DNA".into(),
+        ));
+    }
+    match &new_dna.properties_hash {
+        Some(declared) if *declared != new_dna.properties_address() => Err(ValidationError::Fail(
+            format!(
+                "DNA properties hash to {}, not to the declared {}",
+                new_dna.properties_address(),
+                declared
+            )
+            .into(),
+        )),
+        None if new_dna.properties != replaced_dna.properties => Err(ValidationError::Fail(
+            "DNA updates changing the properties have to declare their hash".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn holds_admin_grant(grantor: &Address, context: &Arc<Context>) -> bool {
+    let agent = context.agent_id.address();
+    let now = Iso8601::from(context.clock.since_epoch().as_secs());
+    context
+        .state()
+        .map(|state| {
+            let chain = state.agent();
+            chain
+                .chain_store()
+                .iter_type(&chain.top_chain_header(), &EntryType::CapTokenGrant)
+                .filter(|header| {
+                    header
+                        .provenances()
+                        .iter()
+                        .any(|provenance| &provenance.source() == grantor)
+                        && validate_header_provenances(header).is_ok()
+                })
+                .filter_map(|header| chain.chain_store().get(header.entry_address()).ok()?)
+                .any(|entry| match entry {
+                    Entry::CapTokenGrant(grant) => {
+                        grant.id() == ReservedCapabilityId::Admin.as_str()
+                            && grant
+                                .assignees()
+                                .map_or(false, |assignees| assignees.contains(&agent))
+                            && !grant.is_expired_at(&now)
+                            && !is_grant_revoked(context, &grant.token())
+                    }
+                    _ => false,
+                })
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        nucleus::actions::{
+            migrate_chain::update_dna,
+            tests::{instance_by_name, test_dna},
+        },
+        workflows::author_entry::author_entry,
+    };
+    use holochain_core_types::{
+        agent::AgentId,
+        entry::cap_entries::{CapFunctions, CapTokenGrant, CapabilityType},
+        signature::{Provenance, Signature},
+        validation::{EntryLifecycle, ValidationPackage},
+    };
+    use serde_json::json;
+    use test_utils::mock_signing::{mock_signer, registered_test_agent};
+
+    /// The agent that the DNAs of these tests name as their admin grantor
+    pub fn test_admin_grantor() -> AgentId {
+        registered_test_agent("admin grantor")
+    }
+
+    /// Names test_admin_grantor as the admin grantor of the DNA
+    pub fn with_admin_grantor(mut dna: Dna) -> Dna {
+        dna.properties[ADMIN_GRANTOR_PROPERTY] = json!(test_admin_grantor().address());
+        dna
+    }
+
+    /// Commits an admin grant for the assignee, signed by the given grantor besides the agent
+    /// of the context, or only by the agent if there is none
+    fn commit_admin_grant_to(assignee: Address, grantor: Option<&AgentId>, context: &Arc<Context>) {
+        let grant = Entry::CapTokenGrant(
+            CapTokenGrant::create(
+                ReservedCapabilityId::Admin.as_str(),
+                CapabilityType::Assigned,
+                Some(vec![assignee]),
+                CapFunctions::new(),
+            )
+            .unwrap(),
+        );
+        let provenances = grantor
+            .map(|grantor| {
+                vec![Provenance::new(
+                    grantor.address(),
+                    Signature::from(mock_signer(grant.address().to_string(), grantor)),
+                )]
+            })
+            .unwrap_or_default();
+        context
+            .block_on(author_entry(&grant, None, context, &provenances))
+            .unwrap();
+    }
+
+    /// Commits the grant of test_admin_grantor that allows the agent of the context to update
+    /// its DNA
+    pub fn commit_admin_grant(context: &Arc<Context>) {
+        commit_admin_grant_to(
+            context.agent_id.address(),
+            Some(&test_admin_grantor()),
+            context,
+        );
+    }
+
+    #[test]
+    fn dna_entries_are_only_valid_as_genesis_or_migration() {
+        let dna = with_admin_grantor(test_dna());
+        let netname = Some("dna_entries_are_only_valid_as_genesis_or_migration");
+        let (_instance, context) = instance_by_name("jill", dna.clone(), netname);
+        let dna_entry = Entry::Dna(Box::new(dna.clone()));
+
+        // The chain already starts with the DNA, so committing it again is no migration
+        let result = context.block_on(author_entry(&dna_entry, None, &context, &vec![]));
+        assert!(result.is_err());
+
+        // Nor is "migrating" to the DNA the chain runs on already
+        let result = context.block_on(author_entry(
+            &dna_entry,
+            Some(dna.address()),
+            &context,
+            &vec![],
+        ));
+        assert!(result.is_err());
+
+        // Nor committing another DNA than the one the instance runs
+        let mut new_dna = dna.clone();
+        new_dna.uuid = String::from("dna_entries_are_only_valid_as_genesis_or_migration");
+        let new_entry = Entry::Dna(Box::new(new_dna.clone()));
+        let result = context.block_on(author_entry(
+            &new_entry,
+            Some(dna.address()),
+            &context,
+            &vec![],
+        ));
+        assert!(result.is_err());
+
+        let top_header = context.state().unwrap().agent().top_chain_header().unwrap();
+        let validation_data = ValidationData {
+            package: ValidationPackage::only_header(top_header),
+            lifecycle: EntryLifecycle::Chain,
+        };
+
+        // DNA entries never get held
+        assert_eq!(
+            validate_dna_entry(
+                new_entry.clone(),
+                Some(dna.address()),
+                validation_data.clone(),
+                &context,
+                ValidationContext::Holding
+            ),
+            Err(ValidationError::Fail(
+                "DNA entries are private to their source chain and can't be held".into()
+            ))
+        );
+
+        // Once the instance runs on the new DNA, its entry can replace the old one
+        commit_admin_grant(&context);
+        context.block_on(update_dna(new_dna, &context)).unwrap();
+        assert_eq!(
+            validate_dna_entry(
+                new_entry.clone(),
+                None,
+                validation_data.clone(),
+                &context,
+                ValidationContext::Authoring
+            ),
+            Err(ValidationError::Fail(
+                "DNA entries can only be committed after the start of the chain to migrate it"
+                    .into()
+            ))
+        );
+        assert_eq!(
+            validate_dna_entry(
+                new_entry,
+                Some(dna.address()),
+                validation_data,
+                &context,
+                ValidationContext::Authoring
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn dna_updates_are_rejected_without_an_admin_grant() {
+        let dna = with_admin_grantor(test_dna());
+        let netname = Some("dna_updates_are_rejected_without_an_admin_grant");
+        let (_instance, context) = instance_by_name("jill", dna.clone(), netname);
+        let mut new_dna = dna.clone();
+        new_dna.uuid = String::from("dna_updates_are_rejected_without_an_admin_grant");
+        let rejected = Err(ValidationError::Fail(
+            "DNA updates need an admin capability grant from the admin grantor of the DNA".into(),
+        ));
+
+        assert_eq!(validate_dna_update(&new_dna, &dna, &context), rejected);
+
+        // The agent can't grant itself admin rights
+        commit_admin_grant_to(context.agent_id.address(), None, &context);
+        assert_eq!(validate_dna_update(&new_dna, &dna, &context), rejected);
+
+        // Nor can any other agent than the grantor
+        let mallory = registered_test_agent("mallory");
+        commit_admin_grant_to(context.agent_id.address(), Some(&mallory), &context);
+        assert_eq!(validate_dna_update(&new_dna, &dna, &context), rejected);
+
+        // An admin grant for someone else doesn't count
+        commit_admin_grant_to(Address::from("bob"), Some(&test_admin_grantor()), &context);
+        assert_eq!(validate_dna_update(&new_dna, &dna, &context), rejected);
+
+        commit_admin_grant(&context);
+        assert_eq!(validate_dna_update(&new_dna, &dna, &context), Ok(()));
+    }
+
+    #[test]
+    fn only_the_admin_grantor_of_the_replaced_dna_allows_updates() {
+        let dna = with_admin_grantor(test_dna());
+        let netname = Some("only_the_admin_grantor_of_the_replaced_dna_allows_updates");
+        let (_instance, context) = instance_by_name("jill", dna.clone(), netname);
+        commit_admin_grant(&context);
+
+        // Chains of a DNA without admin grantor can't be updated at all
+        let ungranted_dna = test_dna();
+        assert_eq!(
+            validate_dna_update(&dna, &ungranted_dna, &context),
+            Err(ValidationError::Fail(
+                format!(
+                    "DNA {} names no {} that could allow updating it",
+                    ungranted_dna.address(),
+                    ADMIN_GRANTOR_PROPERTY
+                )
+                .into()
+            ))
+        );
+
+        // The grantor itself needs no grant
+        let mut new_dna = dna.clone();
+        new_dna.uuid = String::from("only_the_admin_grantor_of_the_replaced_dna_allows_updates");
+        let (_grantor_instance, grantor_context) =
+            instance_by_name("admin grantor", dna.clone(), netname);
+        assert_eq!(
+            validate_dna_update(&new_dna, &dna, &grantor_context),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn dna_property_updates_are_rejected_without_the_declared_properties_hash() {
+        let dna = with_admin_grantor(test_dna());
+        let netname =
+            Some("dna_property_updates_are_rejected_without_the_declared_properties_hash");
+        let (_instance, context) = instance_by_name("jill", dna.clone(), netname);
+        commit_admin_grant(&context);
+        let mut new_dna = dna.clone();
+        new_dna.properties = json!({ "admins": 2 });
+
+        assert_eq!(
+            validate_dna_update(&new_dna, &dna, &context),
+            Err(ValidationError::Fail(
+                "DNA updates changing the properties have to declare their hash".into()
+            ))
+        );
+
+        new_dna.properties_hash = Some(dna.properties_address());
+        assert_eq!(
+            validate_dna_update(&new_dna, &dna, &context),
+            Err(ValidationError::Fail(
+                format!(
+                    "DNA properties hash to {}, not to the declared {}",
+                    new_dna.properties_address(),
+                    dna.properties_address()
+                )
+                .into()
+            ))
+        );
+
+        new_dna.properties_hash = Some(new_dna.properties_address());
+        assert_eq!(validate_dna_update(&new_dna, &dna, &context), Ok(()));
+    }
+}
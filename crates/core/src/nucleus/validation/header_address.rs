@@ -7,5 +7,5 @@ use holochain_persistence_api::cas::content::AddressableContent;
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn validate_header_address(entry: &Entry, header: &ChainHeader) -> ValidationResult {
     (entry.address() == *header.entry_address())
-        .ok_or(ValidationError::Fail("Wrong header for entry".to_string()))
+        .ok_or(ValidationError::Fail("Wrong header for entry".into()))
 }
@@ -2,9 +2,10 @@ use crate::{
     context::Context,
     nucleus::{
         actions::run_validation_callback::run_validation_callback,
-        validation::{ValidationContext, ValidationError, ValidationResult},
+        validation::{record_coverage, ValidationContext, ValidationError, ValidationResult},
         CallbackFnCall,
     },
+    validation_coverage::{ValidationBranch, ValidationVariant},
     wasm_engine::callback::links_utils,
 };
 use boolinator::*;
@@ -63,7 +64,13 @@ pub async fn validate_link_entry(
         }
     };
     let link = link.link().clone();
-    let (base, target) = links_utils::get_link_entries(&link, context).map_err(|_| {
+    let link_entries = match validation_context {
+        ValidationContext::AuthoringOnChain(ref chain) => {
+            links_utils::get_link_entries_on_chain(&link, context, chain)
+        }
+        _ => links_utils::get_link_entries(&link, context),
+    };
+    let (base, target) = link_entries.map_err(|_| {
         ValidationError::UnresolvedDependencies(
             [link.base().clone(), link.target().clone()].to_vec(),
         )
@@ -111,7 +118,7 @@ pub async fn validate_link_entry(
             link.link_type(),
             base_type,
             base.entry_type().to_string(),
-        )))?;
+        ).into()))?;
 
     (target.entry_type().to_string() == target_type)
         .ok_or(ValidationError::Fail(format!(
@@ -119,7 +126,18 @@ pub async fn validate_link_entry(
             link.link_type(),
             target_type,
             target.entry_type().to_string(),
-        )))?;
+        ).into()))?;
+
+    let variant = match entry {
+        Entry::LinkRemove(_) => ValidationVariant::LinkRemove,
+        _ => ValidationVariant::LinkAdd,
+    };
+    let branch = ValidationBranch::link(
+        &link_definition_path.zome_name,
+        &link_definition_path.entry_type_name,
+        &link_definition_path.link_type,
+        variant,
+    );
 
     let validation_data = match entry.clone() {
         Entry::LinkAdd(link) => Ok(LinkValidationData::LinkAdd {
@@ -130,7 +148,7 @@ pub async fn validate_link_entry(
             link,
             validation_data,
         }),
-        _ => Err(ValidationError::Fail("Entry is not link".to_string())),
+        _ => Err(ValidationError::Fail("Entry is not link".into())),
     }?;
 
     let params = LinkValidationArgs {
@@ -145,5 +163,7 @@ pub async fn validate_link_entry(
         params,
     );
 
-    run_validation_callback(address, call, context).await
+    let result = run_validation_callback(address, call, context).await;
+    record_coverage(context, &dna, branch, &result);
+    result
 }
@@ -1,10 +1,14 @@
-use crate::{context::Context, workflows::get_entry_result::get_entry_with_meta_workflow};
+use crate::{
+    agent::state::AgentState, context::Context, validation_coverage::ValidationBranch,
+    workflows::get_entry_result::get_entry_with_meta_workflow,
+};
 use holochain_core_types::{
     chain_header::ChainHeader,
+    dna::Dna,
     entry::{entry_type::EntryType, Entry, EntryWithMeta},
     error::HolochainError,
     time::Timeout,
-    validation::{EntryValidationData, ValidationData},
+    validation::{EntryValidationData, ValidationData, ValidationFailure},
 };
 use holochain_persistence_api::cas::content::Address;
 
@@ -12,19 +16,23 @@ use std::sync::Arc;
 
 mod agent_entry;
 mod app_entry;
+mod chain_header;
+pub(crate) mod dna_entry;
 pub mod build_from_dht;
 mod header_address;
 mod link_entry;
 mod provenances;
 mod remove_entry;
 
+pub use self::dna_entry::ADMIN_GRANTOR_PROPERTY;
+
 #[derive(Clone, Debug, PartialEq, Serialize)]
 /// A failed validation.
 pub enum ValidationError {
     /// `Fail` means the validation function did run successfully and recognized the entry
-    /// as invalid. The String parameter holds the non-zero return value of the app validation
-    /// function.
-    Fail(String),
+    /// as invalid. The ValidationFailure holds the code, message and data that the app
+    /// validation function returned, or a plain message of a system validation.
+    Fail(ValidationFailure),
 
     /// The entry could not get validated because known dependencies (like base and target
     /// for links) were not present yet.
@@ -50,7 +58,7 @@ impl From<ValidationError> for HolochainError {
         match ve {
             ValidationError::Fail(reason) => HolochainError::ValidationFailed(reason),
             ValidationError::UnresolvedDependencies(_) => {
-                HolochainError::ValidationFailed("Missing dependencies".to_string())
+                HolochainError::ValidationFailed("Missing dependencies".into())
             }
             ValidationError::NotImplemented => {
                 HolochainError::NotImplemented("Validation not implemented".to_string())
@@ -64,6 +72,9 @@ impl From<ValidationError> for HolochainError {
 /// or as part of authoring entries
 pub enum ValidationContext {
     Authoring,
+    /// Authoring on top of the given chain, which holds the not yet committed entries of
+    /// the same batch that the entry may depend on
+    AuthoringOnChain(AgentState),
     Holding,
 }
 
@@ -98,11 +109,16 @@ pub async fn validate_entry(
 
     header_address::validate_header_address(&entry, &validation_data.package.chain_header)?;
     provenances::validate_provenances(&validation_data)?;
+    provenances::validate_authors_not_blocked(&validation_data, context)?;
 
     match entry.entry_type() {
-        // DNA entries are not validated currently and always valid
-        // TODO: Specify when DNA can be commited as an update and how to implement validation of DNA entries then.
-        EntryType::Dna => Ok(()),
+        EntryType::Dna => dna_entry::validate_dna_entry(
+            entry.clone(),
+            link,
+            validation_data,
+            context,
+            validation_context,
+        ),
 
         EntryType::App(app_entry_type) => {
             app_entry::validate_app_entry(
@@ -135,8 +151,8 @@ pub async fn validate_entry(
             .await
         }
 
-        // Deletion entries are not validated currently and always valid
-        // TODO: Specify how Deletion can be commited to chain.
+        // Deletions get validated by the zome of the deleted entry, with
+        // EntryValidationData::Delete carrying the deleted entry and its header
         EntryType::Deletion => {
             remove_entry::validate_remove_entry(entry.clone(), validation_data, context).await
         }
@@ -145,16 +161,34 @@ pub async fn validate_entry(
         EntryType::CapTokenGrant => Ok(()),
 
         EntryType::AgentId => {
-            agent_entry::validate_agent_entry(entry.clone(), validation_data, context).await
+            agent_entry::validate_agent_entry(entry.clone(), link, validation_data, context).await
         }
 
-        // chain headers always pass for now. In future this should check that the entry is valid
-        EntryType::ChainHeader => Ok(()),
+        EntryType::ChainHeader => {
+            chain_header::validate_chain_header_entry(entry.clone(), validation_data, context).await
+        }
 
         _ => Err(ValidationError::NotImplemented),
     }
 }
 
+/// Counts the result of a validation callback in the validation coverage of the instance,
+/// if coverage is being recorded
+fn record_coverage(
+    context: &Arc<Context>,
+    dna: &Dna,
+    branch: ValidationBranch,
+    result: &ValidationResult,
+) {
+    if let Err(e) = context.validation_coverage.record(dna, branch, result) {
+        log_error!(
+            context,
+            "validation: could not record validation coverage: {:?}",
+            e
+        );
+    }
+}
+
 /// interprets the validation error from validate_entry. for use by the various workflows
 pub fn process_validation_err(
     src: &str,
@@ -266,7 +300,7 @@ pub fn entry_to_validation_data(
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
-fn get_entry_with_header(
+pub(crate) fn get_entry_with_header(
     context: Arc<Context>,
     address: &Address,
 ) -> Result<(EntryWithMeta, ChainHeader), HolochainError> {
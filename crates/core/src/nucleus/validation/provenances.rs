@@ -1,33 +1,70 @@
-use crate::nucleus::validation::{ValidationError, ValidationResult};
+use crate::{
+    context::Context,
+    nucleus::validation::{ValidationError, ValidationResult},
+};
 use boolinator::Boolinator;
-use holochain_core_types::validation::ValidationData;
-use holochain_dpki::utils::Verify;
+use holochain_core_types::{chain_header::ChainHeader, validation::ValidationData};
+use holochain_dpki::audit::verify_audited;
+use std::sync::Arc;
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn validate_provenances(validation_data: &ValidationData) -> ValidationResult {
-    let header = &validation_data.package.chain_header;
+    validate_header_provenances(&validation_data.package.chain_header)
+}
+
+/// Checks that every provenance of the header signs the header's entry address
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn validate_header_provenances(header: &ChainHeader) -> ValidationResult {
     header
         .provenances()
         .iter()
         .map(|provenance| {
-            let maybe_has_authored = provenance.verify(header.entry_address().to_string());
+            let maybe_has_authored = verify_audited(
+                provenance,
+                header.entry_address().to_string(),
+                "entry_provenance",
+                &format!("validation of entry {}", header.entry_address()),
+            );
             match maybe_has_authored {
                 Err(_) => {
                     Err(ValidationError::Fail(format!(
                         "Signature of entry {} from author {} failed to verify public signing key. Key might be invalid.",
                         header.entry_address(),
                         provenance.source(),
-                    )))
+                    ).into()))
                 },
                 Ok(has_authored) => {
                     has_authored.ok_or(ValidationError::Fail(format!(
                         "Signature of entry {} from author {} invalid",
                         header.entry_address(),
                         provenance.source(),
-                    )))
+                    ).into()))
                 },
             }
         })
         .collect::<Result<Vec<()>, ValidationError>>()?;
     Ok(())
 }
+
+/// Rejects entries signed by an agent that we have blocked.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn validate_authors_not_blocked(
+    validation_data: &ValidationData,
+    context: &Arc<Context>,
+) -> ValidationResult {
+    let header = &validation_data.package.chain_header;
+    let is_blocked = context
+        .state()
+        .map(|state| state.network().is_authored_by_blocked_agent(header))
+        .unwrap_or(false);
+    if is_blocked {
+        return Err(ValidationError::Fail(
+            format!(
+                "Entry {} is authored by a blocked agent",
+                header.entry_address()
+            )
+            .into(),
+        ));
+    }
+    Ok(())
+}
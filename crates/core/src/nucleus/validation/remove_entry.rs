@@ -4,11 +4,14 @@ use crate::{
         actions::{
             get_entry::get_entry_from_dht, run_validation_callback::run_validation_callback,
         },
-        validation::{entry_to_validation_data, ValidationError, ValidationResult},
+        validation::{
+            entry_to_validation_data, record_coverage, ValidationError, ValidationResult,
+        },
         CallbackFnCall,
     },
+    validation_coverage::{ValidationBranch, ValidationVariant},
 };
-use holochain_core_types::{entry::Entry, validation::ValidationData};
+use holochain_core_types::{entry::Entry, error::HolochainError, validation::ValidationData};
 use holochain_persistence_api::cas::content::AddressableContent;
 use holochain_wasm_utils::api_serialization::validation::EntryValidationArgs;
 use std::sync::Arc;
@@ -25,12 +28,12 @@ pub async fn validate_remove_entry(
     let entry_to_delete = get_entry_from_dht(&context.clone(), &deletion_address)
         .map_err(|_| ValidationError::UnresolvedDependencies(vec![deletion_address.clone()]))?
         .ok_or_else(|| {
-            ValidationError::Fail("Could not obtain entry for link_update_delte".to_string())
+            ValidationError::Fail("Could not obtain entry for link_update_delte".into())
         })?;
     let app_entry_type = match entry_to_delete.clone() {
         Entry::App(app_entry_type, _) => Ok(app_entry_type),
         _ => Err(ValidationError::Fail(
-            "Entry type should be App Type".to_string(),
+            "Entry type should be App Type".into(),
         )),
     }?;
 
@@ -38,13 +41,23 @@ pub async fn validate_remove_entry(
         .get_zome_name_for_app_entry_type(&app_entry_type)
         .ok_or(ValidationError::NotImplemented)?;
 
-    let params = EntryValidationArgs {
-        validation_data: entry_to_validation_data(context.clone(), &entry, None, validation_data)
-            .map_err(|_| {
-            ValidationError::Fail("Could not get entry validation".to_string())
-        })?,
-    };
+    // The zome gets the deleted entry with its header, so it can tell who authored it
+    let validation_data = entry_to_validation_data(context.clone(), &entry, None, validation_data)
+        .map_err(|e| match e {
+            HolochainError::Timeout(_) => {
+                ValidationError::UnresolvedDependencies(vec![deletion_address.clone()])
+            }
+            _ => ValidationError::Error(e),
+        })?;
+    let params = EntryValidationArgs { validation_data };
 
+    let branch = ValidationBranch::entry(
+        &zome_name,
+        &app_entry_type.to_string(),
+        ValidationVariant::Delete,
+    );
     let call = CallbackFnCall::new(&zome_name, "__hdk_validate_app_entry", params);
-    run_validation_callback(entry.address(), call, context).await
+    let result = run_validation_callback(entry.address(), call, context).await;
+    record_coverage(context, &dna, branch, &result);
+    result
 }
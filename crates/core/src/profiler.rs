@@ -0,0 +1,217 @@
+//! Sampling profiler for the zome calls and workflows of an instance.
+//!
+//! Profiling a production conductor with perf needs access to the host, so instances can
+//! profile themselves instead. While a profile is being taken, zome calls, HDK functions, WASM
+//! executions, holding workflows and reduced actions push a frame onto a stack of the thread
+//! they run in. [Profiler::profile] looks at the stacks of all threads at a fixed frequency
+//! and counts how often it found each of them, which gives a profile in the folded stack
+//! format that flamegraph.pl, inferno and speedscope read.
+//!
+//! When profiling is off, frames cost one atomic load. Work that was already running when
+//! profiling started only shows up with the frames it enters afterwards.
+use futures::{future::Future, task::Poll};
+use holochain_core_types::error::HolochainError;
+use holochain_locksmith::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+/// Samples per second if none are given, off the usual 100 so it does not run in lockstep
+/// with periodic work
+pub const DEFAULT_FREQUENCY_HZ: u64 = 99;
+pub const MAX_FREQUENCY_HZ: u64 = 1000;
+pub const MAX_DURATION_SECS: u64 = 300;
+
+type Stack = Arc<Mutex<Vec<String>>>;
+
+#[derive(Default)]
+pub struct Profiler {
+    active: AtomicBool,
+    stacks: Mutex<HashMap<ThreadId, Stack>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub duration_ms: u64,
+    pub frequency_hz: u64,
+    /// Samples taken, including those in which nothing was running
+    pub samples: u64,
+    /// How often each stack got sampled, with frames separated by `;` and the root first
+    pub stacks: BTreeMap<String, u64>,
+}
+
+impl Profile {
+    /// The profile in the folded stack format, one `<stack> <count>` line per stack
+    pub fn folded(&self) -> String {
+        self.stacks
+            .iter()
+            .map(|(stack, count)| format!("{} {}\n", stack, count))
+            .collect()
+    }
+}
+
+/// A frame on the stack of the current thread, which gets popped when this gets dropped
+pub struct ProfileFrame {
+    stack: Option<Stack>,
+}
+
+impl Drop for ProfileFrame {
+    fn drop(&mut self) {
+        if let Some(ref stack) = self.stack {
+            stack.lock().unwrap().pop();
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Pushes a frame onto the stack of the current thread, if a profile is being taken.
+    /// The name only gets built then.
+    pub fn frame<F: FnOnce() -> String>(&self, name: F) -> ProfileFrame {
+        if !self.is_profiling() {
+            return ProfileFrame { stack: None };
+        }
+        let stack = self
+            .stacks
+            .lock()
+            .unwrap()
+            .entry(thread::current().id())
+            .or_insert_with(|| Arc::new(Mutex::new(Vec::new())))
+            .clone();
+        stack.lock().unwrap().push(name());
+        ProfileFrame { stack: Some(stack) }
+    }
+
+    /// Takes a profile for the given duration, blocking the calling thread meanwhile.
+    /// Only one profile of an instance can be taken at a time.
+    pub fn profile(
+        &self,
+        duration: Duration,
+        frequency_hz: u64,
+    ) -> Result<Profile, HolochainError> {
+        if duration > Duration::from_secs(MAX_DURATION_SECS) {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Profiles can take {}s at most",
+                MAX_DURATION_SECS
+            )));
+        }
+        if frequency_hz == 0 || frequency_hz > MAX_FREQUENCY_HZ {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Profiling frequency has to be between 1 and {}Hz",
+                MAX_FREQUENCY_HZ
+            )));
+        }
+        if self
+            .active
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(HolochainError::ErrorGeneric(String::from(
+                "Instance is already being profiled",
+            )));
+        }
+
+        let interval = Duration::from_nanos(1_000_000_000 / frequency_hz);
+        let started = Instant::now();
+        let mut samples = 0;
+        let mut stacks = BTreeMap::new();
+        while started.elapsed() < duration {
+            thread::sleep(interval);
+            samples += 1;
+            let threads: Vec<Stack> = self.stacks.lock().unwrap().values().cloned().collect();
+            for stack in threads {
+                let stack = stack.lock().unwrap();
+                if !stack.is_empty() {
+                    *stacks.entry(stack.join(";")).or_insert(0) += 1;
+                }
+            }
+        }
+        self.active.store(false, Ordering::SeqCst);
+        self.stacks.lock().unwrap().clear();
+
+        Ok(Profile {
+            duration_ms: started.elapsed().as_millis() as u64,
+            frequency_hz,
+            samples,
+            stacks,
+        })
+    }
+}
+
+/// A future that is in the given frame whenever it gets polled, so that the frame stays on
+/// the stack of the thread that does the work, even if that changes between polls
+pub struct Profiled<F> {
+    profiler: Arc<Profiler>,
+    name: String,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Profiled<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context) -> Poll<Self::Output> {
+        let name = &self.name;
+        let _frame = self.profiler.frame(|| name.clone());
+        self.future.as_mut().poll(cx)
+    }
+}
+
+pub fn profiled<F: Future>(profiler: &Arc<Profiler>, name: String, future: F) -> Profiled<F> {
+    Profiled {
+        profiler: profiler.clone(),
+        name,
+        future: Box::pin(future),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_the_stacks_of_running_threads() {
+        let profiler = Arc::new(Profiler::new());
+        // No frames while not profiling
+        assert!(profiler.frame(|| String::from("idle")).stack.is_none());
+
+        let worker_profiler = profiler.clone();
+        let worker = thread::spawn(move || {
+            while !worker_profiler.is_profiling() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let _call = worker_profiler.frame(|| String::from("zome_call blog/create_post"));
+            let _hdk = worker_profiler.frame(|| String::from("hdk hc_commit_entry"));
+            thread::sleep(Duration::from_millis(300));
+        });
+
+        let profile = profiler
+            .profile(Duration::from_millis(200), DEFAULT_FREQUENCY_HZ)
+            .unwrap();
+        worker.join().unwrap();
+        assert!(profile.samples > 0);
+        assert!(profile.stacks["zome_call blog/create_post;hdk hc_commit_entry"] > 0);
+        assert!(profile
+            .folded()
+            .contains("zome_call blog/create_post;hdk hc_commit_entry "));
+        assert!(!profiler.is_profiling());
+
+        assert!(profiler.profile(Duration::from_secs(0), 0).is_err());
+        assert!(profiler
+            .profile(Duration::from_secs(MAX_DURATION_SECS + 1), 1)
+            .is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Limits on the resources a single instance may use, so that one greedy hApp can't degrade
+//! every other instance running in the same conductor.
+//!
+//! Each limit is enforced by the subsystem that uses the resource:
+//! * WASM memory is checked whenever a zome calls into the host and when it returns, and the
+//!   call fails once its memory grew beyond the limit.
+//! * Storage gets counted by a [StorageQuota] that the instance's content storages check
+//!   before writing new content.
+//! * Concurrent zome calls are counted by the instance's
+//!   [ZomeCallRateLimiter](crate::nucleus::rate_limit::ZomeCallRateLimiter), across all
+//!   tokens and callers.
+//! * Outgoing network traffic passes a [BandwidthThrottle], which delays messages once the
+//!   instance sent more than its share.
+use holochain_core_types::error::{HcResult, HolochainError};
+use holochain_locksmith::Mutex;
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Resource limits of an instance. Limits that are not set don't apply.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Maximum size of the memory of a zome's WASM while it runs a call
+    #[serde(default)]
+    pub max_wasm_memory_bytes: Option<usize>,
+    /// Maximum number of bytes of chain and DHT content the instance stores, counted like
+    /// storage usage reports it
+    #[serde(default)]
+    pub max_storage_bytes: Option<usize>,
+    /// Maximum number of zome calls the instance runs at the same time
+    #[serde(default)]
+    pub max_concurrent_calls: Option<u32>,
+    /// Maximum average number of bytes per second the instance sends to the network
+    #[serde(default)]
+    pub max_network_bytes_per_second: Option<u64>,
+}
+
+/// Counts the bytes an instance stores against its limit.
+#[derive(Debug)]
+pub struct StorageQuota {
+    max_bytes: usize,
+    used_bytes: AtomicUsize,
+}
+
+impl StorageQuota {
+    pub fn new(max_bytes: usize) -> Self {
+        StorageQuota {
+            max_bytes,
+            used_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes.load(Ordering::SeqCst)
+    }
+
+    /// Sets what the storages held already when the instance got loaded.
+    pub fn set_used_bytes(&self, bytes: usize) {
+        self.used_bytes.store(bytes, Ordering::SeqCst);
+    }
+
+    /// Counts the given number of bytes if they fit into the quota, and fails with
+    /// [HolochainError::ResourceLimitExceeded] without counting anything otherwise.
+    pub fn reserve(&self, bytes: usize) -> HcResult<()> {
+        let mut used = self.used_bytes();
+        loop {
+            if used.saturating_add(bytes) > self.max_bytes {
+                return Err(HolochainError::ResourceLimitExceeded(format!(
+                    "storing {} more bytes would exceed the storage limit of {} bytes",
+                    bytes, self.max_bytes
+                )));
+            }
+            match self.used_bytes.compare_exchange(
+                used,
+                used + bytes,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(current) => used = current,
+            }
+        }
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+/// Token bucket that allows sending a second's worth of bytes at once and delays messages
+/// beyond that until the average rate is back within the limit.
+pub struct BandwidthThrottle {
+    bytes_per_second: u64,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl fmt::Debug for BandwidthThrottle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BandwidthThrottle")
+            .field("bytes_per_second", &self.bytes_per_second)
+            .finish()
+    }
+}
+
+impl BandwidthThrottle {
+    pub fn new(bytes_per_second: u64) -> Self {
+        BandwidthThrottle {
+            bytes_per_second,
+            bucket: Mutex::new((bytes_per_second as f64, Instant::now())),
+        }
+    }
+
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second
+    }
+
+    /// Takes the given number of bytes out of the bucket and returns how long the sender
+    /// has to wait before it has earned them.
+    pub fn delay_for(&self, bytes: usize, now: Instant) -> Duration {
+        let rate = self.bytes_per_second.max(1) as f64;
+        let mut bucket = self.bucket.lock().unwrap();
+        let (available, last_refill) = *bucket;
+        let elapsed = if now > last_refill {
+            now.duration_since(last_refill)
+        } else {
+            Duration::from_secs(0)
+        };
+        let refilled = seconds(elapsed) * rate;
+        let available = (available + refilled).min(rate) - bytes as f64;
+        *bucket = (available, now);
+        if available >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_nanos((-available / rate * 1e9) as u64)
+        }
+    }
+
+    /// Blocks until the given number of bytes may be sent.
+    pub fn throttle(&self, bytes: usize) {
+        let delay = self.delay_for(bytes, Instant::now());
+        if delay > Duration::from_secs(0) {
+            thread::sleep(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_quota_refuses_what_does_not_fit() {
+        let quota = StorageQuota::new(100);
+        quota.set_used_bytes(40);
+        assert!(quota.reserve(50).is_ok());
+        assert_eq!(quota.used_bytes(), 90);
+        match quota.reserve(20) {
+            Err(HolochainError::ResourceLimitExceeded(_)) => (),
+            _ => panic!("should not store more than the quota"),
+        }
+        assert_eq!(quota.used_bytes(), 90);
+        assert!(quota.reserve(10).is_ok());
+    }
+
+    #[test]
+    fn bandwidth_throttle_delays_traffic_beyond_the_rate() {
+        let throttle = BandwidthThrottle::new(1000);
+        let start = Instant::now();
+        assert_eq!(throttle.delay_for(1000, start), Duration::from_secs(0));
+        let delay = throttle.delay_for(500, start);
+        assert!(delay > Duration::from_millis(490) && delay < Duration::from_millis(510));
+
+        // After waiting, the bucket has refilled
+        let later = start + Duration::from_secs(3);
+        assert_eq!(throttle.delay_for(1000, later), Duration::from_secs(0));
+    }
+}
@@ -0,0 +1,229 @@
+//! Multi-agent integration tests, written as a sequence of steps.
+//!
+//! A [Scenario] starts a [TestHarness] with named agents and offers what integration tests
+//! keep doing with them as steps: zome calls and commits of an agent, waiting for
+//! consistency or for a signal, checking what an agent holds of the DHT, and partitioning
+//! and healing the network between agents. Agents are referred to by name. Steps that
+//! fail return an error that says which step it was, and [Scenario::steps] lists those
+//! taken so far.
+//!
+//! ```rust,ignore
+//! let mut s = Scenario::new(dna, &["alice", "bob"])?;
+//! let address = s.commit("alice", &entry)?;
+//! s.consistency()?;
+//! s.assert_held("bob", &address, Some(&entry))?;
+//! s.partition("alice", "bob")?;
+//! ```
+use crate::{
+    signal::Signal,
+    test_harness::{TestAgent, TestHarness},
+};
+use holochain_core_types::{
+    dna::Dna,
+    entry::Entry,
+    error::{HcResult, HolochainError},
+};
+use holochain_json_api::json::JsonString;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long steps that wait get by default
+pub const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(30);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct Scenario {
+    harness: TestHarness,
+    timeout: Duration,
+    steps: Vec<String>,
+}
+
+impl Scenario {
+    /// Starts an agent for each of the given names, on a network of their own
+    pub fn new(dna: Dna, agents: &[&str]) -> HcResult<Self> {
+        Ok(Scenario {
+            harness: TestHarness::with_agents(dna, agents)?,
+            timeout: DEFAULT_STEP_TIMEOUT,
+            steps: Vec::new(),
+        })
+    }
+
+    /// How long steps that wait get before they fail
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn harness(&mut self) -> &mut TestHarness {
+        &mut self.harness
+    }
+
+    /// The steps taken so far, numbered from 1
+    pub fn steps(&self) -> &[String] {
+        &self.steps
+    }
+
+    pub fn agent(&self, name: &str) -> HcResult<&TestAgent> {
+        self.index(name).map(|index| self.harness.agent(index))
+    }
+
+    /// The address the agent is known by on the network
+    pub fn address_of(&self, name: &str) -> HcResult<Address> {
+        self.agent(name).map(|agent| agent.agent_id().address())
+    }
+
+    fn index(&self, name: &str) -> HcResult<usize> {
+        self.harness
+            .agents()
+            .iter()
+            .position(|agent| agent.name == name)
+            .ok_or_else(|| HolochainError::ErrorGeneric(format!("Scenario has no agent {}", name)))
+    }
+
+    /// Runs a step, adding the step to errors it fails with
+    fn step<T, F>(&mut self, description: String, run: F) -> HcResult<T>
+    where
+        F: FnOnce(&mut Scenario) -> HcResult<T>,
+    {
+        self.steps.push(description.clone());
+        let number = self.steps.len();
+        run(self).map_err(|error| {
+            HolochainError::ErrorGeneric(format!(
+                "Step {} ({}) failed: {}",
+                number, description, error
+            ))
+        })
+    }
+
+    pub fn call<J: Into<JsonString>>(
+        &mut self,
+        agent: &str,
+        zome: &str,
+        function: &str,
+        parameters: J,
+    ) -> HcResult<JsonString> {
+        let parameters = parameters.into();
+        self.step(format!("{} calls {}/{}", agent, zome, function), |s| {
+            s.agent(agent)?.call(zome, function, parameters)
+        })
+    }
+
+    pub fn commit(&mut self, agent: &str, entry: &Entry) -> HcResult<Address> {
+        self.step(format!("{} commits {}", agent, entry.address()), |s| {
+            s.agent(agent)?.commit(entry)
+        })
+    }
+
+    /// Waits until everything the agents did so far has reached all agents that should see it
+    pub fn consistency(&mut self) -> HcResult<()> {
+        self.step(String::from("await consistency"), |s| {
+            let timeout = s.timeout;
+            s.harness.await_consistency(timeout)
+        })
+    }
+
+    /// Waits until the agent emits a signal that matches
+    pub fn await_signal<F: Fn(&Signal) -> bool>(
+        &mut self,
+        agent: &str,
+        description: &str,
+        matches: F,
+    ) -> HcResult<Signal> {
+        self.step(format!("{} signals {}", agent, description), |s| {
+            let index = s.index(agent)?;
+            let timeout = s.timeout;
+            s.harness.await_signal(index, timeout, matches)
+        })
+    }
+
+    /// Waits until what the agent holds at the address is the expected entry, or nothing if
+    /// none is expected
+    pub fn assert_held(
+        &mut self,
+        agent: &str,
+        address: &Address,
+        expected: Option<&Entry>,
+    ) -> HcResult<()> {
+        let description = match expected {
+            Some(_) => format!("{} holds {}", agent, address),
+            None => format!("{} does not hold {}", agent, address),
+        };
+        self.step(description, |s| {
+            let holder = s.agent(agent)?;
+            let started = Instant::now();
+            loop {
+                let held = holder.get_from_dht(address)?;
+                if held.as_ref() == expected {
+                    return Ok(());
+                }
+                if started.elapsed() > s.timeout {
+                    return Err(HolochainError::Timeout(format!(
+                        "after {:?} the agent holds {:?}",
+                        s.timeout, held
+                    )));
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        })
+    }
+
+    /// Cuts the network connection between the two agents until it gets healed
+    pub fn partition(&mut self, a: &str, b: &str) -> HcResult<()> {
+        self.step(format!("partition {} from {}", a, b), |s| {
+            s.harness
+                .faults()
+                .partition(&s.address_of(a)?, &s.address_of(b)?);
+            Ok(())
+        })
+    }
+
+    pub fn heal(&mut self, a: &str, b: &str) -> HcResult<()> {
+        self.step(format!("heal {} and {}", a, b), |s| {
+            s.harness
+                .faults()
+                .heal(&s.address_of(a)?, &s.address_of(b)?);
+            Ok(())
+        })
+    }
+
+    pub fn heal_all(&mut self) -> HcResult<()> {
+        self.step(String::from("heal all partitions"), |s| {
+            s.harness.faults().heal_all();
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::network::test_utils::test_wat_always_valid;
+    use holochain_core_types::entry::test_entry;
+    use test_utils::create_test_dna_with_wat;
+
+    #[test]
+    fn steps_name_agents_and_report_failures() {
+        let dna = create_test_dna_with_wat("test_zome", Some(&test_wat_always_valid()));
+        let mut s = Scenario::new(dna, &["alice", "bob"])
+            .unwrap()
+            .with_timeout(Duration::from_secs(10));
+
+        let entry = test_entry();
+        let address = s.commit("alice", &entry).unwrap();
+        s.consistency().unwrap();
+        s.assert_held("bob", &address, Some(&entry)).unwrap();
+
+        let error = s.commit("carol", &entry).unwrap_err();
+        assert_eq!(
+            error,
+            HolochainError::ErrorGeneric(format!(
+                "Step 4 (carol commits {}) failed: Scenario has no agent carol",
+                address
+            ))
+        );
+        assert_eq!(s.steps()[0], format!("alice commits {}", address));
+    }
+}
@@ -5,9 +5,18 @@ use crate::{
     action::{Action, ActionWrapper},
     context::Context,
     instance::dispatch_action,
+    nucleus::slow_calls::report_slow_zome_calls,
     state_dump::DumpOptions,
+    storage_compaction::compact_storage,
+    storage_scrubber::scrub_and_restore,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
 };
-use std::sync::Arc;
 
 pub fn create_state_dump_callback(
     context: Arc<Context>,
@@ -27,6 +36,14 @@ pub fn create_timeout_callback(context: Arc<Context>) -> impl 'static + FnMut()
     }
 }
 
+pub fn create_slow_zome_call_callback(
+    context: Arc<Context>,
+) -> impl 'static + FnMut() + Sync + Send {
+    move || {
+        report_slow_zome_calls(&context);
+    }
+}
+
 pub fn create_state_pruning_callback(
     context: Arc<Context>,
 ) -> impl 'static + FnMut() + Sync + Send {
@@ -34,3 +51,63 @@ pub fn create_state_pruning_callback(
         dispatch_action(context.action_channel(), ActionWrapper::new(Action::Prune));
     }
 }
+
+/// Runs the job in a thread of its own, so that the other jobs keep running meanwhile,
+/// unless its previous run is still going.
+fn run_exclusively<F>(running: &Arc<AtomicBool>, job: F)
+where
+    F: 'static + FnOnce() + Send,
+{
+    if running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let running = running.clone();
+    thread::spawn(move || {
+        job();
+        running.store(false, Ordering::SeqCst);
+    });
+}
+
+/// Compacts the instance's storage, if the storage backend supports it.
+pub fn create_storage_compaction_callback(
+    context: Arc<Context>,
+) -> impl 'static + FnMut() + Sync + Send {
+    let running = Arc::new(AtomicBool::new(false));
+    move || {
+        if context.storage_compactor.is_none() {
+            return;
+        }
+        let context = context.clone();
+        run_exclusively(&running, move || {
+            if let Err(error) = compact_storage(&context) {
+                log_error!(
+                    context,
+                    "scheduled_jobs: storage compaction failed: {}",
+                    error
+                );
+            }
+        });
+    }
+}
+
+/// Checks the integrity of the instance's storage, if the storage backend supports it.
+pub fn create_storage_scrub_callback(
+    context: Arc<Context>,
+) -> impl 'static + FnMut() + Sync + Send {
+    let running = Arc::new(AtomicBool::new(false));
+    move || {
+        if context.storage_compactor.is_none() {
+            return;
+        }
+        let context = context.clone();
+        run_exclusively(&running, move || {
+            if let Err(error) = scrub_and_restore(&context) {
+                log_error!(
+                    context,
+                    "scheduled_jobs: storage scrubbing failed: {}",
+                    error
+                );
+            }
+        });
+    }
+}
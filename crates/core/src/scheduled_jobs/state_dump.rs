@@ -103,6 +103,29 @@ pub fn state_dump(context: Arc<Context>, options: DumpOptions) {
         )
         .collect::<Vec<String>>();
 
+    let dead_letter_holding_workflows_strings = dump
+        .dead_letter_holding_workflows
+        .iter()
+        .map(
+            |PendingValidationWithTimeout {
+                 pending, retries, ..
+             }| {
+                format!(
+                    "<{}({})> {}: depends on : {:?}, retries: {}",
+                    pending.workflow.to_string(),
+                    pending.entry_with_header.header.entry_type(),
+                    pending.entry_with_header.entry.address(),
+                    pending
+                        .dependencies
+                        .iter()
+                        .map(|addr| addr.to_string())
+                        .collect::<Vec<_>>(),
+                    retries,
+                )
+            },
+        )
+        .collect::<Vec<String>>();
+
     let holding_strings = dump
         .held_aspects
         .iter()
@@ -132,6 +155,34 @@ pub fn state_dump(context: Arc<Context>, options: DumpOptions) {
         })
         .collect::<Vec<String>>();
 
+    let storage_usage_string = dump
+        .storage_usage
+        .as_ref()
+        .map(|usage| {
+            format!(
+                "Storage usage (bytes):\n\tchain: {}\n\tcas: {}\n\teav: {}\n\tcaches: {}",
+                usage.chain.total.bytes,
+                usage.cas.total.bytes,
+                usage.eav.total.bytes,
+                usage.caches.total.bytes
+            )
+        })
+        .unwrap_or_default();
+
+    let workflow_latency_strings = dump
+        .workflow_latencies
+        .iter()
+        .map(|(workflow, histogram)| {
+            format!(
+                "{}: {} runs, mean {:.3}s, max {:.3}s",
+                workflow,
+                histogram.count,
+                histogram.mean_seconds(),
+                histogram.max_seconds
+            )
+        })
+        .collect::<Vec<String>>();
+
     let debug_dump = format!(
         r#"
 =============STATE DUMP===============
@@ -162,10 +213,18 @@ Queued validations {qlen}:
 
 In-process validations {iplen}:
 {in_process_holding_workflows_strings}
+
+Given up validations {dllen}:
+{dead_letter_holding_workflows_strings}
 --------
 Holding:
 {holding_list}
 --------
+{storage_usage}
+
+Workflow latencies:
+===================
+{workflow_latencies}
     "#,
         source_chain = source_chain_strings.join("\n\n"),
         queued_calls = dump.queued_calls,
@@ -175,10 +234,14 @@ Holding:
         queued_holding_workflows_strings = queued_holding_workflows_strings.join("\n"),
         iplen = dump.in_process_holding_workflows.len(),
         in_process_holding_workflows_strings = in_process_holding_workflows_strings.join("\n"),
+        dllen = dump.dead_letter_holding_workflows.len(),
+        dead_letter_holding_workflows_strings = dead_letter_holding_workflows_strings.join("\n"),
         flows = dump.query_flows,
         validation_packages = dump.validation_package_flows,
         direct_messages = dump.direct_message_flows,
-        holding_list = holding_strings.join("\n")
+        holding_list = holding_strings.join("\n"),
+        storage_usage = storage_usage_string,
+        workflow_latencies = workflow_latency_strings.join("\n")
     );
 
     log_info!(context, "debug/state_dump: {}", debug_dump);
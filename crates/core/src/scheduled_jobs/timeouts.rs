@@ -9,7 +9,7 @@ use std::sync::Arc;
 pub fn check_network_processes_for_timeouts(context: Arc<Context>) {
     let state = context.state().expect("Couldn't get state in timeout job");
     for (key, (time, duration)) in state.network().query_timeouts.iter() {
-        if let Ok(elapsed) = time.elapsed() {
+        if let Ok(elapsed) = context.clock.elapsed(*time) {
             if elapsed > *duration {
                 dispatch_action(
                     context.action_channel(),
@@ -20,7 +20,7 @@ pub fn check_network_processes_for_timeouts(context: Arc<Context>) {
     }
 
     for (key, (time, duration)) in state.network().direct_message_timeouts.iter() {
-        if let Ok(elapsed) = time.elapsed() {
+        if let Ok(elapsed) = context.clock.elapsed(*time) {
             if elapsed > *duration {
                 dispatch_action(
                     context.action_channel(),
@@ -31,7 +31,7 @@ pub fn check_network_processes_for_timeouts(context: Arc<Context>) {
     }
 
     for (key, (time, duration)) in state.network().get_validation_package_timeouts.iter() {
-        if let Ok(elapsed) = time.elapsed() {
+        if let Ok(elapsed) = context.clock.elapsed(*time) {
             if elapsed > *duration {
                 dispatch_action(
                     context.action_channel(),
@@ -44,15 +44,17 @@ pub fn check_network_processes_for_timeouts(context: Arc<Context>) {
 
 #[cfg(test)]
 mod tests {
+    use super::check_network_processes_for_timeouts;
     use crate::{
         action::{Action, ActionWrapper, DirectMessageData},
+        clock::{MockClock, SharedClock},
         instance::{dispatch_action, tests::test_context, Instance},
         network::direct_message::{CustomDirectMessage, DirectMessage},
     };
     use bitflags::_core::time::Duration;
     use holochain_core_types::error::HolochainError;
     use holochain_persistence_api::cas::content::Address;
-    use std::time::SystemTime;
+    use std::sync::Arc;
 
     #[test]
     pub fn reduce_send_direct_message_timeout_test() {
@@ -77,7 +79,7 @@ mod tests {
         };
         let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
             direct_message_data,
-            Some((SystemTime::now(), Duration::from_millis(500))),
+            Some((context.clock.now(), Duration::from_millis(500))),
         )));
 
         dispatch_action(context.action_channel(), action_wrapper);
@@ -100,4 +102,58 @@ mod tests {
             )))
         );
     }
+
+    #[test]
+    pub fn direct_messages_time_out_by_the_clock_of_the_instance() {
+        let clock = MockClock::at_unix_secs(1_000_000);
+        let mut context = (*test_context("alex", Some("timeout_by_mock_clock"))).clone();
+        context.clock = SharedClock::new(clock.clone());
+        let context = Arc::new(context);
+        let dna = test_utils::create_test_dna_with_wat("test_zome", None);
+        let mut instance = Instance::new(context.clone());
+        let context = instance.initialize(Some(dna), context).unwrap();
+
+        let msg_id = String::from("mock-clock");
+        let direct_message_data = DirectMessageData {
+            address: Address::from("bogus"),
+            message: DirectMessage::Custom(CustomDirectMessage {
+                zome: String::from("test"),
+                payload: Ok(String::from("test")),
+            }),
+            msg_id: msg_id.clone(),
+            is_response: false,
+        };
+        dispatch_action(
+            context.action_channel(),
+            ActionWrapper::new(Action::SendDirectMessage((
+                direct_message_data,
+                Some((context.clock.now(), Duration::from_millis(500))),
+            ))),
+        );
+        let reply = || {
+            context
+                .state()
+                .unwrap()
+                .network()
+                .custom_direct_message_replys
+                .get(&msg_id)
+                .cloned()
+        };
+
+        // The scheduled timeout checks keep running, but the clock of the instance stands still
+        std::thread::sleep(Duration::from_secs(1));
+        assert_eq!(reply(), None);
+
+        clock.advance(Duration::from_secs(1));
+        check_network_processes_for_timeouts(context.clone());
+        let mut tries = 0;
+        while reply().is_none() && tries < 100 {
+            std::thread::sleep(Duration::from_millis(10));
+            tries += 1;
+        }
+        match reply() {
+            Some(Err(HolochainError::Timeout(_))) => (),
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+    }
 }
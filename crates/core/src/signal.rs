@@ -1,4 +1,6 @@
-use crate::{action::ActionWrapper, consistency::ConsistencySignal};
+use crate::{
+    action::ActionWrapper, consistency::ConsistencySignal, nucleus::slow_calls::SlowZomeCall,
+};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use holochain_json_api::{error::JsonError, json::JsonString};
 use holochain_wasm_utils::api_serialization::emit_signal::EmitSignalArgs;
@@ -13,12 +15,16 @@ pub enum Signal {
     Trace(ActionWrapper),
     Consistency(ConsistencySignal<String>),
     User(UserSignal),
+    SlowZomeCall(SlowZomeCall),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, DefaultJson, PartialEq)]
 pub struct UserSignal {
     pub name: String,
     pub arguments: JsonString,
+    /// Zome that emitted the signal
+    #[serde(default)]
+    pub zome: String,
 }
 
 impl From<EmitSignalArgs> for UserSignal {
@@ -26,6 +32,7 @@ impl From<EmitSignalArgs> for UserSignal {
         UserSignal {
             name: args.name,
             arguments: args.arguments,
+            zome: String::new(),
         }
     }
 }
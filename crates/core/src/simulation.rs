@@ -0,0 +1,416 @@
+//! Simulation of how published data spreads through networks of many agents.
+//!
+//! Full instances need threads and storage of their own, which limits tests to a few dozen
+//! agents in one process. A [Simulation] models each agent only by what it holds and what it
+//! still has to validate, so that thousands of them fit into one process, and runs them in
+//! rounds of virtual time:
+//!
+//! 1. In each of the first `publish_rounds` rounds, every agent publishes new entries to the
+//!    agents that should hold them. Who holds what follows the naive sharding of sim2h: every
+//!    agent holds the entries located within an arc after its own location, sized so that
+//!    `redundancy` agents hold each entry.
+//! 2. Messages arrive `latency_rounds` rounds after they got sent, unless the mock network
+//!    drops them. Agents queue what they should hold and validate up to
+//!    `validations_per_round` queued entries per round.
+//! 3. Every agent gossips with `gossip_peers` random other agents per round, and sends them
+//!    what it published or holds that they should hold as well but don't have yet.
+//!
+//! After every round the simulation reports [RoundMetrics], such as the share of the
+//! holdings the sharding asks for that are there, and stops once all of them are. Simulated
+//! agents don't run a DNA, all validations succeed.
+use crate::clock::MockClock;
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    time::{Duration, SystemTime},
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    pub agents: usize,
+    /// Entries every agent publishes per publishing round
+    pub entries_per_round: usize,
+    pub publish_rounds: u64,
+    /// How many agents should hold each entry
+    pub redundancy: usize,
+    /// Agents every agent gossips with per round
+    pub gossip_peers: usize,
+    /// Rounds it takes a message to arrive
+    pub latency_rounds: u64,
+    /// Share of messages that get lost, between 0 and 1
+    pub drop_rate: f64,
+    pub validations_per_round: usize,
+    /// Virtual time a round takes
+    pub round_length_ms: u64,
+    /// Rounds after which the simulation stops, converged or not
+    pub max_rounds: u64,
+    /// Seed of all random decisions, the same seed runs the same simulation
+    pub seed: u64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            agents: 1000,
+            entries_per_round: 1,
+            publish_rounds: 1,
+            redundancy: 50,
+            gossip_peers: 3,
+            latency_rounds: 1,
+            drop_rate: 0.0,
+            validations_per_round: 10,
+            round_length_ms: 1000,
+            max_rounds: 1000,
+            seed: 1,
+        }
+    }
+}
+
+/// The state of a simulation after one round
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct RoundMetrics {
+    pub round: u64,
+    /// Virtual time since the simulation started
+    pub time_ms: u64,
+    /// Entries published so far
+    pub entries: usize,
+    /// Holdings the sharding asks for, for the entries published so far
+    pub expected_holdings: usize,
+    pub holdings: usize,
+    /// `holdings` divided by `expected_holdings`
+    pub convergence: f64,
+    /// Agents that hold everything they should
+    pub converged_agents: usize,
+    /// Entries that agents received but didn't validate yet, over all agents
+    pub queued: usize,
+    pub max_queue: usize,
+    /// Entries sent in publish messages this round
+    pub published_messages: usize,
+    /// Entries sent in gossip this round
+    pub gossiped_messages: usize,
+    pub dropped_messages: usize,
+}
+
+struct SimulatedAgent {
+    location: u32,
+    /// Entries the agent published, which it gossips as well
+    authored: Vec<usize>,
+    held: BTreeSet<usize>,
+    queue: VecDeque<usize>,
+    queued: HashSet<usize>,
+    /// Entries published so far that the agent should hold
+    expected: usize,
+}
+
+impl SimulatedAgent {
+    fn has(&self, entry: usize) -> bool {
+        self.held.contains(&entry) || self.queued.contains(&entry)
+    }
+}
+
+struct Message {
+    arrives: u64,
+    to: usize,
+    entry: usize,
+}
+
+/// xorshift64*, enough for picking peers and dropping messages reproducibly
+struct SimulationRng(u64);
+
+impl SimulationRng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck at 0
+        SimulationRng(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn next_location(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    fn chance(&mut self, rate: f64) -> bool {
+        rate > 0.0 && (self.next_u64() >> 11) as f64 / ((1u64 << 53) as f64) < rate
+    }
+}
+
+/// Whether the agent at `agent_location` should hold data at `data_location`, by the naive
+/// sharding of sim2h
+pub fn should_hold(
+    agent_location: u32,
+    data_location: u32,
+    agent_count: usize,
+    redundancy: usize,
+) -> bool {
+    if agent_count <= redundancy {
+        return true;
+    }
+    let arc = u32::max_value() as f64 / (agent_count as f64 / redundancy as f64);
+    data_location.wrapping_sub(agent_location) < arc as u32
+}
+
+pub struct Simulation {
+    config: SimulationConfig,
+    clock: MockClock,
+    started: SystemTime,
+    rng: SimulationRng,
+    agents: Vec<SimulatedAgent>,
+    /// Locations of the published entries, by entry
+    entries: Vec<u32>,
+    in_flight: VecDeque<Message>,
+    round: u64,
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig) -> Self {
+        let mut rng = SimulationRng::new(config.seed);
+        let agents = (0..config.agents)
+            .map(|_| SimulatedAgent {
+                location: rng.next_location(),
+                authored: Vec::new(),
+                held: BTreeSet::new(),
+                queue: VecDeque::new(),
+                queued: HashSet::new(),
+                expected: 0,
+            })
+            .collect();
+        let clock = MockClock::at_unix_secs(0);
+        Simulation {
+            started: SystemTime::UNIX_EPOCH,
+            clock,
+            rng,
+            agents,
+            entries: Vec::new(),
+            in_flight: VecDeque::new(),
+            round: 0,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    /// The virtual time of the simulation, which moves forward by a round length every round
+    pub fn clock(&self) -> &MockClock {
+        &self.clock
+    }
+
+    fn should_hold(&self, agent: usize, entry: usize) -> bool {
+        should_hold(
+            self.agents[agent].location,
+            self.entries[entry],
+            self.agents.len(),
+            self.config.redundancy,
+        )
+    }
+
+    fn send(&mut self, to: usize, entry: usize, metrics: &mut RoundMetrics) {
+        if self.rng.chance(self.config.drop_rate) {
+            metrics.dropped_messages += 1;
+            return;
+        }
+        self.in_flight.push_back(Message {
+            arrives: self.round + self.config.latency_rounds,
+            to,
+            entry,
+        });
+    }
+
+    fn publish(&mut self, metrics: &mut RoundMetrics) {
+        for author in 0..self.agents.len() * self.config.entries_per_round {
+            let entry = self.entries.len();
+            self.entries.push(self.rng.next_location());
+            let author = author % self.agents.len();
+            self.agents[author].authored.push(entry);
+            for holder in 0..self.agents.len() {
+                if self.should_hold(holder, entry) {
+                    self.agents[holder].expected += 1;
+                    metrics.published_messages += 1;
+                    self.send(holder, entry, metrics);
+                }
+            }
+        }
+    }
+
+    fn deliver(&mut self) {
+        while self
+            .in_flight
+            .front()
+            .map(|message| message.arrives <= self.round)
+            .unwrap_or(false)
+        {
+            let message = self.in_flight.pop_front().unwrap();
+            let agent = &mut self.agents[message.to];
+            if !agent.has(message.entry) {
+                agent.queue.push_back(message.entry);
+                agent.queued.insert(message.entry);
+            }
+        }
+    }
+
+    fn validate(&mut self) {
+        let validations = self.config.validations_per_round;
+        for agent in self.agents.iter_mut() {
+            for _ in 0..validations {
+                match agent.queue.pop_front() {
+                    Some(entry) => {
+                        agent.queued.remove(&entry);
+                        agent.held.insert(entry);
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    fn gossip(&mut self, metrics: &mut RoundMetrics) {
+        if self.agents.len() < 2 {
+            return;
+        }
+        for agent in 0..self.agents.len() {
+            for _ in 0..self.config.gossip_peers {
+                let mut peer = self.rng.below(self.agents.len() - 1);
+                if peer >= agent {
+                    peer += 1;
+                }
+                let gossiping = &self.agents[agent];
+                let missing: Vec<usize> = gossiping
+                    .authored
+                    .iter()
+                    .filter(|entry| !gossiping.held.contains(entry))
+                    .chain(gossiping.held.iter())
+                    .cloned()
+                    .filter(|entry| {
+                        !self.agents[peer].has(*entry) && self.should_hold(peer, *entry)
+                    })
+                    .collect();
+                for entry in missing {
+                    metrics.gossiped_messages += 1;
+                    self.send(peer, entry, metrics);
+                }
+            }
+        }
+    }
+
+    /// Runs one round and returns the state after it
+    pub fn step(&mut self) -> RoundMetrics {
+        let mut metrics = RoundMetrics::default();
+        if self.round < self.config.publish_rounds {
+            self.publish(&mut metrics);
+        }
+        self.deliver();
+        self.validate();
+        self.gossip(&mut metrics);
+
+        self.round += 1;
+        self.clock
+            .advance(Duration::from_millis(self.config.round_length_ms));
+        metrics.round = self.round;
+        metrics.time_ms = self
+            .clock
+            .now()
+            .duration_since(self.started)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        metrics.entries = self.entries.len();
+        for agent in self.agents.iter() {
+            metrics.expected_holdings += agent.expected;
+            metrics.holdings += agent.held.len();
+            if agent.held.len() == agent.expected {
+                metrics.converged_agents += 1;
+            }
+            metrics.queued += agent.queue.len();
+            metrics.max_queue = metrics.max_queue.max(agent.queue.len());
+        }
+        metrics.convergence = if metrics.expected_holdings == 0 {
+            1.0
+        } else {
+            metrics.holdings as f64 / metrics.expected_holdings as f64
+        };
+        metrics
+    }
+
+    /// Whether publishing is over and every agent holds everything it should
+    pub fn is_converged(&self) -> bool {
+        self.round >= self.config.publish_rounds
+            && self
+                .agents
+                .iter()
+                .all(|agent| agent.held.len() == agent.expected)
+    }
+
+    /// Runs rounds until the simulation converged or ran `max_rounds` rounds, handing the
+    /// metrics of every round to `on_round`. Returns the metrics of the last round.
+    pub fn run<F: FnMut(&RoundMetrics)>(&mut self, mut on_round: F) -> RoundMetrics {
+        let mut metrics = RoundMetrics::default();
+        while self.round < self.config.max_rounds && !self.is_converged() {
+            metrics = self.step();
+            on_round(&metrics);
+        }
+        metrics
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn config() -> SimulationConfig {
+        SimulationConfig {
+            agents: 200,
+            entries_per_round: 2,
+            publish_rounds: 2,
+            redundancy: 10,
+            max_rounds: 200,
+            ..SimulationConfig::default()
+        }
+    }
+
+    #[test]
+    fn gossip_makes_up_for_dropped_publishes() {
+        let mut lossless = Simulation::new(config());
+        let last = lossless.run(|_| ());
+        assert!(lossless.is_converged());
+        assert_eq!(last.convergence, 1.0);
+        assert_eq!(last.converged_agents, 200);
+        assert_eq!(last.dropped_messages, 0);
+        assert_eq!(last.entries, 800);
+        // roughly `redundancy` holders per entry
+        assert!(last.expected_holdings > 800 * 5 && last.expected_holdings < 800 * 15);
+
+        let lossy_config = SimulationConfig {
+            drop_rate: 0.3,
+            ..config()
+        };
+        let mut rounds = Vec::new();
+        let last =
+            Simulation::new(lossy_config.clone()).run(|metrics| rounds.push(metrics.clone()));
+        assert_eq!(last.convergence, 1.0);
+        assert!(rounds[0].convergence < 1.0);
+        assert!(rounds.iter().any(|metrics| metrics.dropped_messages > 0));
+        assert_eq!(last.time_ms, rounds.len() as u64 * 1000);
+
+        let mut again = Vec::new();
+        Simulation::new(lossy_config).run(|metrics| again.push(metrics.clone()));
+        assert_eq!(again, rounds);
+    }
+
+    #[test]
+    fn small_networks_hold_everything_everywhere() {
+        assert!(should_hold(10, 5, 3, 50));
+        let holders = (0..100u32)
+            .filter(|agent| should_hold(agent * (u32::max_value() / 100), 12345, 100, 10))
+            .count();
+        assert_eq!(holders, 10);
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
         chain_store::ChainStore,
         state::{AgentState, AgentStateSnapshot},
     },
+    clock::SharedClock,
     content_store::GetContent,
     context::Context,
     dht::dht_store::DhtStore,
@@ -18,17 +19,13 @@ use holochain_core_types::{
     entry::{entry_type::EntryType, Entry},
     error::{HcResult, HolochainError},
 };
-use holochain_locksmith::RwLock;
 use holochain_persistence_api::{
-    cas::{
-        content::{Address, AddressableContent},
-        storage::ContentAddressableStorage,
-    },
+    cas::content::{Address, AddressableContent},
     eav::IndexFilter,
 };
 
 use crate::dht::dht_store::DhtStoreSnapshot;
-use std::{convert::TryInto, sync::Arc, time::SystemTime};
+use std::{sync::Arc, time::SystemTime};
 
 pub const ACTION_PRUNE_MS: u64 = 60000;
 
@@ -39,9 +36,10 @@ pub struct ActionResponse<T> {
 }
 
 impl<T> ActionResponse<T> {
-    pub fn new(response: T) -> Self {
+    /// `created_at` is the time of the state's clock, which pruning compares it against
+    pub fn new(response: T, created_at: SystemTime) -> Self {
         ActionResponse::<T> {
-            created_at: SystemTime::now(),
+            created_at,
             response,
         }
     }
@@ -62,6 +60,7 @@ pub struct State {
     dht: Arc<DhtStore>,
     network: Arc<NetworkState>,
     pub conductor_api: ConductorApi,
+    pub clock: SharedClock,
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
@@ -76,12 +75,17 @@ impl State {
         State {
             nucleus: Arc::new(NucleusState::new()),
             agent: Arc::new(AgentState::new(
-                ChainStore::new(chain_cas.clone()),
+                ChainStore::new(chain_cas.clone()).with_dna_storage(context.dna_storage.clone()),
                 context.agent_id.address(),
             )),
-            dht: Arc::new(DhtStore::new(dht_cas.clone(), eav)),
-            network: Arc::new(NetworkState::new()),
+            dht: Arc::new(
+                DhtStore::new(dht_cas.clone(), eav)
+                    .with_dna_storage(context.dna_storage.clone())
+                    .with_clock(context.clock.clone()),
+            ),
+            network: Arc::new(NetworkState::new().with_clock(context.clock.clone())),
             conductor_api: context.conductor_api.clone(),
+            clock: context.clock.clone(),
         }
     }
 
@@ -97,7 +101,8 @@ impl State {
         let cas = context.dht_storage.clone();
         let eav = context.eav_storage.clone();
 
-        let dht_store = DhtStore::new(cas.clone(), eav.clone());
+        let dht_store =
+            DhtStore::new(cas.clone(), eav.clone()).with_dna_storage(context.dna_storage.clone());
         Self::new_with_agent_nucleus_dht(context, agent_state, nucleus_state, dht_store)
     }
 
@@ -107,24 +112,21 @@ impl State {
         mut nucleus_state: NucleusState,
         dht_store: DhtStore,
     ) -> Self {
-        let cas = context.dht_storage.clone();
-        //let eav = context.eav_storage.clone();
-
-        nucleus_state.dna = Self::get_dna(&agent_state, cas.clone()).ok();
+        nucleus_state.dna = Self::get_dna(&agent_state, &dht_store)
+            .ok()
+            .or_else(|| nucleus_state.dna.take());
 
         State {
             nucleus: Arc::new(nucleus_state),
             agent: Arc::new(agent_state),
-            dht: Arc::new(dht_store),
-            network: Arc::new(NetworkState::new()),
+            dht: Arc::new(dht_store.with_clock(context.clock.clone())),
+            network: Arc::new(NetworkState::new().with_clock(context.clock.clone())),
             conductor_api: context.conductor_api.clone(),
+            clock: context.clock.clone(),
         }
     }
 
-    fn get_dna(
-        agent_state: &AgentState,
-        cas: Arc<RwLock<dyn ContentAddressableStorage>>,
-    ) -> HcResult<Dna> {
+    fn get_dna(agent_state: &AgentState, dht_store: &DhtStore) -> HcResult<Dna> {
         let dna_entry_header = agent_state
             .chain_store()
             .iter_type(&agent_state.top_chain_header(), &EntryType::Dna)
@@ -135,12 +137,13 @@ impl State {
                         .to_string(),
                 )
             })?;
-        let json = (*cas.read().unwrap()).fetch(dna_entry_header.entry_address())?;
-        let entry: Entry = json.map(|e| e.try_into()).ok_or_else(|| {
-            HolochainError::ErrorGeneric(
-                "No DNA entry found in storage while creating state from agent".to_string(),
-            )
-        })??;
+        let entry = dht_store
+            .get(dna_entry_header.entry_address())?
+            .ok_or_else(|| {
+                HolochainError::ErrorGeneric(
+                    "No DNA entry found in storage while creating state from agent".to_string(),
+                )
+            })?;
         match entry {
             Entry::Dna(dna) => Ok(*dna),
             _ => Err(HolochainError::SerializationError(
@@ -171,6 +174,7 @@ impl State {
                 &action_wrapper,
             ),
             conductor_api: self.conductor_api.clone(),
+            clock: self.clock.clone(),
         }
     }
 
@@ -197,16 +201,20 @@ impl State {
         dht_store_snapshot: DhtStoreSnapshot,
     ) -> HcResult<State> {
         let agent_state = AgentState::new_with_top_chain_header(
-            ChainStore::new(context.chain_storage.clone()),
+            ChainStore::new(context.chain_storage.clone())
+                .with_dna_storage(context.dna_storage.clone()),
             agent_snapshot.top_chain_header().map(|h| h.to_owned()),
             context.agent_id.address(),
-        );
+        )
+        .with_header_holders(agent_snapshot.header_holders().clone());
         let nucleus_state = NucleusState::from(nucleus_snapshot);
         let dht_store = DhtStore::new_from_snapshot(
             context.dht_storage.clone(),
             context.eav_storage.clone(),
             dht_store_snapshot,
-        );
+            &context.clock,
+        )
+        .with_dna_storage(context.dna_storage.clone());
         Ok(State::new_with_agent_nucleus_dht(
             context,
             agent_state,
@@ -365,6 +373,14 @@ impl StateWrapper {
             .conductor_api
             .clone()
     }
+
+    pub fn clock(&self) -> SharedClock {
+        self.state
+            .as_ref()
+            .expect("Tried to use dropped state")
+            .clock
+            .clone()
+    }
 }
 
 impl From<State> for StateWrapper {
@@ -1,16 +1,29 @@
 use crate::{
     action::QueryKey,
-    content_store::GetContent,
+    agent::{chain_store::ChainStore, state::AgentState},
+    content_store::{add_with_shared_dna, fetch_with_shared_dna, GetContent},
     context::Context,
-    dht::pending_validations::PendingValidationWithTimeout,
+    dht::{
+        dht_store::{DhtStore, DhtStoreSnapshot},
+        pending_validations::PendingValidationWithTimeout,
+    },
+    instance_activity::LatencyHistogram,
+    instance_archive::ArchivedContent,
     network::{direct_message::DirectMessage, entry_with_header::EntryWithHeader},
-    nucleus::{ZomeFnCall, ZomeFnCallState},
+    nucleus::{
+        actions::initialize::Initialization,
+        state::{NucleusState, NucleusStatus},
+        ZomeFnCall, ZomeFnCallState,
+    },
+    state::State,
+    storage_usage::StorageUsage,
 };
 use holochain_core_types::{
     chain_header::ChainHeader,
+    dna::Dna,
     eav::{EaviQuery, EntityAttributeValueIndex},
     entry::{entry_type::EntryType, Entry},
-    error::HolochainError,
+    error::{HcResult, HolochainError},
 };
 use holochain_json_api::json::JsonString;
 use holochain_net::aspect_map::AspectMapBare;
@@ -18,11 +31,23 @@ use holochain_persistence_api::{
     cas::content::{Address, AddressableContent},
     eav::IndexFilter,
 };
-use std::{collections::VecDeque, convert::TryInto, sync::Arc};
+use lib3h_protocol::types::EntryHash;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    convert::TryInto,
+    sync::Arc,
+};
 
-#[derive(Serialize)]
+/// What an instance is doing and holding at one point in time.
+///
+/// Dumps deserialize from their JSON with the running calls left out, and missing fields
+/// default to empty, so that dumps taken from bug reports can be trimmed and redacted before
+/// they get loaded with [StateDump::load].
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StateDump {
     pub queued_calls: Vec<ZomeFnCall>,
+    #[serde(skip_deserializing)]
     pub running_calls: Vec<(ZomeFnCall, Option<ZomeFnCallState>)>,
     pub call_results: Vec<(ZomeFnCall, Result<JsonString, HolochainError>)>,
     pub query_flows: Vec<QueryKey>,
@@ -30,14 +55,24 @@ pub struct StateDump {
     pub direct_message_flows: Vec<(String, DirectMessage)>,
     pub queued_holding_workflows: VecDeque<PendingValidationWithTimeout>,
     pub in_process_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+    /// Holding workflows that got given up on because their dependencies never arrived
+    pub dead_letter_holding_workflows: VecDeque<PendingValidationWithTimeout>,
     pub held_aspects: AspectMapBare,
+    /// The source chain without the DNA entry, from the oldest header to the newest
     pub source_chain: Vec<(EntryWithHeader, Address)>,
+    /// Header of the DNA entry that the source chain starts with
+    pub dna_header: Option<ChainHeader>,
     pub eavis: Option<Vec<EntityAttributeValueIndex>>,
+    pub storage_usage: Option<StorageUsage>,
+    /// Latencies of the major workflows since the instance got instantiated
+    pub workflow_latencies: BTreeMap<String, LatencyHistogram>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct DumpOptions {
     pub include_eavis: bool,
+    pub include_storage_usage: bool,
 }
 
 impl StateDump {
@@ -53,6 +88,10 @@ impl StateDump {
         };
 
         let source_chain: Vec<ChainHeader> = agent.iter_chain().collect();
+        let dna_header = source_chain
+            .iter()
+            .find(|header| header.entry_type() == &EntryType::Dna)
+            .cloned();
         let source_chain: Vec<(EntryWithHeader, Address)> = source_chain
             .into_iter()
             .rev()
@@ -111,6 +150,7 @@ impl StateDump {
 
         let queued_holding_workflows = dht.queued_holding_workflows().clone();
         let in_process_holding_workflows = dht.in_process_holding_workflows().clone();
+        let dead_letter_holding_workflows = dht.dead_letter_holding_workflows().clone();
 
         let held_aspects = dht.get_holding_map().bare().clone();
 
@@ -133,6 +173,12 @@ impl StateDump {
             None
         };
 
+        let storage_usage = if options.include_storage_usage {
+            Some(StorageUsage::new(&context).expect("should be ok"))
+        } else {
+            None
+        };
+
         StateDump {
             queued_calls,
             running_calls,
@@ -142,10 +188,125 @@ impl StateDump {
             direct_message_flows,
             queued_holding_workflows,
             in_process_holding_workflows,
+            dead_letter_holding_workflows,
             held_aspects,
             source_chain,
+            dna_header,
             eavis: maybe_eavis,
+            storage_usage,
+            workflow_latencies: context.activity.workflow_latencies(),
+        }
+    }
+
+    /// Writes the dump's source chain, the entries it holds of them and its meta data, if it
+    /// has any, into the context's storages and returns a state with the dump's holding map and
+    /// pending validations, for an instance to start from with `Instance::from_state` and
+    /// `initialize(None, ..)`. Zome calls and network requests that were running when the dump
+    /// got taken don't get restored.
+    ///
+    /// Entries are stored under the addresses their headers give, so dumps with redacted entry
+    /// content still load. Fails if the dump belongs to a different agent or DNA.
+    pub fn load(&self, dna: Dna, context: &Arc<Context>) -> HcResult<State> {
+        let dumped_agent = self.source_chain.iter().find_map(|(entry_with_header, _)| {
+            match &entry_with_header.entry {
+                Entry::AgentId(agent) => Some(agent.address()),
+                _ => None,
+            }
+        });
+        if let Some(agent) = dumped_agent {
+            if agent != context.agent_id.address() {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "State dump belongs to agent {}, not to {}",
+                    agent,
+                    context.agent_id.address()
+                )));
+            }
+        }
+
+        if let Some(dna_header) = &self.dna_header {
+            if dna_header.entry_address() != &dna.address() {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "State dump was taken of DNA {}, not of {}",
+                    dna_header.entry_address(),
+                    dna.address()
+                )));
+            }
+            context.chain_storage.write()?.add(dna_header)?;
+            add_with_shared_dna(
+                &context.chain_storage,
+                &context.dna_storage,
+                &Entry::Dna(Box::new(dna.clone())),
+            )?;
+        }
+        let contents: Vec<(ArchivedContent, ArchivedContent)> = self
+            .source_chain
+            .iter()
+            .map(|(entry_with_header, header_address)| {
+                (
+                    ArchivedContent {
+                        address: header_address.clone(),
+                        content: String::from(entry_with_header.header.content()),
+                    },
+                    ArchivedContent {
+                        address: entry_with_header.header.entry_address().clone(),
+                        content: String::from(entry_with_header.entry.content()),
+                    },
+                )
+            })
+            .collect();
+        {
+            let mut chain_storage = context.chain_storage.write()?;
+            for (header, entry) in contents.iter() {
+                chain_storage.add(header)?;
+                chain_storage.add(entry)?;
+            }
         }
+        {
+            let mut dht_storage = context.dht_storage.write()?;
+            for (_, entry) in contents.iter() {
+                let entry_hash: EntryHash = entry.address.clone().into();
+                if self.held_aspects.contains_key(&entry_hash) {
+                    dht_storage.add(entry)?;
+                }
+            }
+        }
+        if let Some(eavis) = &self.eavis {
+            let mut eav_storage = context.eav_storage.write()?;
+            for eavi in eavis.iter() {
+                eav_storage.add_eavi(eavi)?;
+            }
+        }
+
+        let agent_state = AgentState::new_with_top_chain_header(
+            ChainStore::new(context.chain_storage.clone())
+                .with_dna_storage(context.dna_storage.clone()),
+            self.source_chain
+                .last()
+                .map(|(entry_with_header, _)| entry_with_header.header.clone())
+                .or_else(|| self.dna_header.clone()),
+            context.agent_id.address(),
+        );
+        let mut nucleus_state = NucleusState::new();
+        nucleus_state.dna = Some(dna);
+        nucleus_state.status = NucleusStatus::Initialized(Initialization::new());
+        let dht_store = DhtStore::new_from_snapshot(
+            context.dht_storage.clone(),
+            context.eav_storage.clone(),
+            DhtStoreSnapshot::new(
+                self.held_aspects.clone(),
+                self.queued_holding_workflows.clone(),
+                self.in_process_holding_workflows.clone(),
+                self.dead_letter_holding_workflows.clone(),
+            ),
+            &context.clock,
+        )
+        .with_dna_storage(context.dna_storage.clone());
+        Ok(State::new_with_agent_nucleus_dht(
+            context.clone(),
+            agent_state,
+            nucleus_state,
+            dht_store,
+        ))
     }
 }
 
@@ -155,6 +316,7 @@ impl From<Arc<Context>> for StateDump {
             context,
             DumpOptions {
                 include_eavis: false,
+                include_storage_usage: false,
             },
         )
     }
@@ -165,10 +327,7 @@ pub fn address_to_content_and_type(
     address: &Address,
     context: Arc<Context>,
 ) -> Result<(String, String), HolochainError> {
-    let raw_content = context
-        .dht_storage
-        .read()?
-        .fetch(address)?
+    let raw_content = fetch_with_shared_dna(&context.dht_storage, &context.dna_storage, address)?
         .ok_or(HolochainError::EntryNotFoundLocally)?;
     let maybe_entry: Result<Entry, _> = raw_content.clone().try_into();
     if let Ok(entry) = maybe_entry {
@@ -194,3 +353,44 @@ pub fn address_to_content_and_type(
         Ok((String::from("UNKNOWN"), raw_content.to_string()))
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        full_consistency::FullConsistencyWaiter, network::test_utils::test_wat_always_valid,
+        test_harness::TestAgent,
+    };
+    use holochain_core_types::entry::{test_entry, test_entry_b};
+    use std::time::Duration;
+    use test_utils::create_test_dna_with_wat;
+
+    #[test]
+    fn instances_start_from_a_loaded_dump() {
+        let dna = create_test_dna_with_wat("test_zome", Some(&test_wat_always_valid()));
+        let original = TestAgent::new(dna.clone(), "alice", "state-dump-original").unwrap();
+        let waiter = FullConsistencyWaiter::new(&[original.context.clone()]);
+        let entry = test_entry();
+        let address = original.commit(&entry).unwrap();
+        waiter.wait(Duration::from_secs(30)).unwrap();
+
+        let dump = StateDump::new(
+            original.context.clone(),
+            DumpOptions {
+                include_eavis: true,
+                include_storage_usage: false,
+            },
+        );
+        let dump: StateDump = serde_json::from_str(&serde_json::to_string(&dump).unwrap()).unwrap();
+
+        let loaded =
+            TestAgent::from_dump(dna.clone(), "alice", "state-dump-loaded", &dump).unwrap();
+        let top_chain_header =
+            |agent: &TestAgent| agent.context.state().unwrap().agent().top_chain_header();
+        assert_eq!(top_chain_header(&loaded), top_chain_header(&original));
+        assert_eq!(loaded.get_from_dht(&address).unwrap(), Some(entry));
+        loaded.commit(&test_entry_b()).unwrap();
+
+        assert!(TestAgent::from_dump(dna, "bob", "state-dump-bob", &dump).is_err());
+    }
+}
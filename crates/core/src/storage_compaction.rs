@@ -0,0 +1,222 @@
+//! Compaction of an instance's persistence backend.
+//!
+//! Content that is neither part of the source chain, nor held for the DHT, nor referenced by
+//! meta data, pending validations or caches is orphaned and only takes up disk space.
+//! [compact_storage] removes it and then lets the backend reclaim the space and rebuild its
+//! EAV indices. Backends opt in by implementing [CompactStorage] and getting set as the
+//! context's `storage_compactor`.
+use crate::{
+    agent::state::AGENT_SNAPSHOT_ADDRESS, content_store::WRITE_BATCH_JOURNAL_ADDRESS,
+    context::Context, dht::dht_store::DHT_STORE_SNAPSHOT_ADDRESS,
+    nucleus::state::NUCLEUS_SNAPSHOT_ADDRESS, state::StateWrapper, write_ahead_log::WAL_ADDRESS,
+};
+use holochain_core_types::{
+    eav::EaviQuery,
+    error::{HcResult, HolochainError},
+};
+use holochain_persistence_api::{
+    cas::content::{Address, AddressableContent},
+    eav::IndexFilter,
+};
+use std::{collections::HashSet, sync::Arc, thread, time::Duration};
+
+/// Maintenance operations of a persistence backend that compaction and the integrity scrubber
+/// need on top of the CAS and EAV storage traits.
+pub trait CompactStorage: Send + Sync {
+    /// Addresses of all content in the instance's CAS
+    fn addresses(&self) -> HcResult<Vec<Address>>;
+    /// Removes the content with the given addresses from the CAS
+    fn remove(&self, addresses: &[Address]) -> HcResult<()>;
+    /// Moves the content at the given address out of the CAS to a place where it is kept for
+    /// inspection but never served again
+    fn quarantine(&self, address: &Address) -> HcResult<()>;
+    /// Gives space freed by removed content back to the file system and rebuilds EAV indices
+    fn vacuum(&self) -> HcResult<()>;
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct CompactionReport {
+    /// Number of orphaned contents that got removed
+    pub removed: usize,
+    /// Number of contents that are still referenced and were kept
+    pub kept: usize,
+}
+
+/// Removes orphaned content from the instance's CAS and vacuums its storages.
+/// Fails if the instance's storage backend doesn't support compaction.
+pub fn compact_storage(context: &Arc<Context>) -> HcResult<CompactionReport> {
+    let compactor = context.storage_compactor.clone().ok_or_else(|| {
+        HolochainError::ErrorGeneric("Storage backend does not support compaction".to_string())
+    })?;
+    if context.state().is_none() {
+        return Err(HolochainError::ErrorGeneric(
+            "Couldn't get instance state".to_string(),
+        ));
+    }
+
+    let report = {
+        // Reducers write to the storages while holding the state's write lock, so holding
+        // the read lock keeps content from becoming referenced while we decide what to drop.
+        let state = loop {
+            match context.try_state() {
+                Some(state) => break state,
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        let live = live_addresses(&state)?;
+
+        let (kept, orphaned): (Vec<Address>, Vec<Address>) = compactor
+            .addresses()?
+            .into_iter()
+            .partition(|address| live.contains(address));
+        compactor.remove(&orphaned)?;
+        CompactionReport {
+            removed: orphaned.len(),
+            kept: kept.len(),
+        }
+    };
+
+    compactor.vacuum()?;
+    log_info!(
+        context,
+        "storage_compaction: removed {} orphaned contents, kept {}",
+        report.removed,
+        report.kept
+    );
+    Ok(report)
+}
+
+/// Addresses of all content that is referenced by the given state: snapshots, the source
+/// chain, content held for the DHT or referenced by its meta data, pending validations and
+/// cached validation packages.
+pub fn live_addresses(state: &StateWrapper) -> HcResult<HashSet<Address>> {
+    let mut live = HashSet::new();
+    for address in &[
+        AGENT_SNAPSHOT_ADDRESS,
+        NUCLEUS_SNAPSHOT_ADDRESS,
+        DHT_STORE_SNAPSHOT_ADDRESS,
+        WAL_ADDRESS,
+        WRITE_BATCH_JOURNAL_ADDRESS,
+    ] {
+        live.insert(Address::from(*address));
+    }
+
+    for header in state.agent().iter_chain() {
+        live.insert(header.entry_address().clone());
+        live.insert(header.address());
+    }
+
+    let dht = state.dht();
+    for entry_hash in dht.get_holding_map().bare().keys() {
+        live.insert(Address::from(entry_hash.to_string()));
+    }
+    let eavis = dht.fetch_eavi(&EaviQuery::new(
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        IndexFilter::Range(None, None),
+        None,
+    ))?;
+    for eavi in eavis {
+        live.insert(eavi.entity());
+        live.insert(eavi.value());
+    }
+    for pending in dht
+        .queued_holding_workflows()
+        .iter()
+        .chain(dht.in_process_holding_workflows().iter())
+    {
+        let entry_with_header = &pending.pending.entry_with_header;
+        live.insert(entry_with_header.entry.address());
+        live.insert(entry_with_header.header.address());
+    }
+
+    for cached in state.network().validation_cache.values() {
+        for header in &cached.headers {
+            live.insert(header.entry_address().clone());
+            live.insert(header.address());
+        }
+        for entry in cached.entries.iter().flatten() {
+            live.insert(entry.address());
+        }
+    }
+    Ok(live)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        instance::tests::{test_context, test_instance_and_context},
+        nucleus::actions::tests::test_dna,
+    };
+    use holochain_locksmith::Mutex;
+
+    /// Compactor over a plain set of addresses, recording what got removed
+    struct TestCompactor {
+        addresses: Mutex<Vec<Address>>,
+        vacuumed: Mutex<bool>,
+    }
+
+    impl CompactStorage for TestCompactor {
+        fn addresses(&self) -> HcResult<Vec<Address>> {
+            Ok(self.addresses.lock().unwrap().clone())
+        }
+
+        fn remove(&self, addresses: &[Address]) -> HcResult<()> {
+            self.addresses
+                .lock()
+                .unwrap()
+                .retain(|address| !addresses.contains(address));
+            Ok(())
+        }
+
+        fn quarantine(&self, address: &Address) -> HcResult<()> {
+            self.remove(&[address.clone()])
+        }
+
+        fn vacuum(&self) -> HcResult<()> {
+            *self.vacuumed.lock().unwrap() = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn compaction_needs_a_compactor() {
+        let context = test_context("alice", None);
+        assert!(compact_storage(&context).is_err());
+    }
+
+    #[test]
+    fn compaction_removes_only_orphaned_content() {
+        let (_instance, context) = test_instance_and_context(test_dna(), None).unwrap();
+        let chain_head = context
+            .state()
+            .unwrap()
+            .agent()
+            .top_chain_header()
+            .expect("Genesis should have committed to the chain");
+        let orphan = Address::from("orphaned content");
+        let compactor = Arc::new(TestCompactor {
+            addresses: Mutex::new(vec![chain_head.address(), orphan.clone()]),
+            vacuumed: Mutex::new(false),
+        });
+        let mut context = (*context).clone();
+        context.storage_compactor = Some(compactor.clone());
+        let context = Arc::new(context);
+
+        let report = compact_storage(&context).unwrap();
+        assert_eq!(
+            report,
+            CompactionReport {
+                removed: 1,
+                kept: 1
+            }
+        );
+        assert_eq!(
+            *compactor.addresses.lock().unwrap(),
+            vec![chain_head.address()]
+        );
+        assert!(*compactor.vacuumed.lock().unwrap());
+    }
+}
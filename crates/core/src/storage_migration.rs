@@ -0,0 +1,171 @@
+//! Migration of an instance's data from one persistence backend to another.
+//!
+//! [migrate_storage] copies everything an instance gets loaded from, the same data an
+//! [InstanceArchive] holds, from the storages of one context into the empty storages of
+//! another. It then reads all of it back from the target, checks entries and headers against
+//! their hashes and compares content, meta data and snapshots to what got exported, so that
+//! operators can switch an instance to a different backend without syncing it from the
+//! network again.
+use crate::{
+    agent::state::AGENT_SNAPSHOT_ADDRESS,
+    context::Context,
+    instance_archive::{ArchivedContent, InstanceArchive},
+    storage_scrubber::{expected_address, Expected},
+};
+use holochain_core_types::{
+    eav::EaviQuery,
+    error::{HcResult, HolochainError},
+};
+use holochain_locksmith::RwLock;
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent},
+        storage::ContentAddressableStorage,
+    },
+    eav::IndexFilter,
+};
+use std::{collections::BTreeSet, sync::Arc};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct MigrationReport {
+    /// Number of entries, headers and other contents that got copied
+    pub contents: usize,
+    /// Number of copied contents whose hash matched their address
+    pub verified: usize,
+    /// Number of EAVIs that got copied
+    pub eavis: usize,
+}
+
+/// Copies the source context's instance data into the target context's storages and verifies
+/// the copy. The target needs to be set up for the same agent and its storages need to be
+/// empty. The source instance should be stopped, so its data doesn't change while copying.
+pub fn migrate_storage(source: &Arc<Context>, target: &Arc<Context>) -> HcResult<MigrationReport> {
+    if target
+        .dht_storage
+        .read()?
+        .contains(&Address::from(AGENT_SNAPSHOT_ADDRESS))?
+    {
+        return Err(HolochainError::ErrorGeneric(
+            "Target storage already holds the state of an instance".to_string(),
+        ));
+    }
+
+    let archive = InstanceArchive::export(source)?;
+    archive.import(target)?;
+
+    let mut report = MigrationReport::default();
+    verify_contents(&target.chain_storage, &archive.chain, &mut report)?;
+    verify_contents(&target.dht_storage, &archive.cas, &mut report)?;
+
+    let expected: BTreeSet<_> = archive.eav.iter().cloned().collect();
+    let migrated = target.eav_storage.read()?.fetch_eavi(&EaviQuery::new(
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        IndexFilter::Range(None, None),
+        None,
+    ))?;
+    if migrated != expected {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Migrated {} EAVIs but the target storage holds {}",
+            expected.len(),
+            migrated.len()
+        )));
+    }
+    report.eavis = migrated.len();
+
+    let storage = target.dht_storage.read()?;
+    for (address, snapshot) in &[
+        (
+            archive.agent_snapshot.address(),
+            archive.agent_snapshot.content(),
+        ),
+        (
+            archive.nucleus_snapshot.address(),
+            archive.nucleus_snapshot.content(),
+        ),
+        (
+            archive.dht_snapshot.address(),
+            archive.dht_snapshot.content(),
+        ),
+    ] {
+        if storage.fetch(address)?.as_ref() != Some(snapshot) {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Snapshot {} did not get migrated",
+                address
+            )));
+        }
+    }
+    Ok(report)
+}
+
+fn verify_contents(
+    storage: &Arc<RwLock<dyn ContentAddressableStorage>>,
+    contents: &[ArchivedContent],
+    report: &mut MigrationReport,
+) -> HcResult<()> {
+    let storage = storage.read()?;
+    for archived in contents {
+        let migrated = storage.fetch(&archived.address)?.ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!(
+                "Content {} did not get migrated",
+                archived.address
+            ))
+        })?;
+        if migrated != archived.content() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Content {} changed during migration",
+                archived.address
+            )));
+        }
+        match expected_address(migrated) {
+            Expected::Address(ref address) if *address == archived.address => report.verified += 1,
+            Expected::Unchecked => (),
+            _ => {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Content {} doesn't match its address",
+                    archived.address
+                )))
+            }
+        }
+        report.contents += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        instance::tests::{test_context, test_instance_and_context},
+        nucleus::actions::tests::test_dna,
+        persister::{Persister, SimplePersister},
+        workflows::author_entry::author_entry,
+    };
+    use holochain_core_types::entry::test_entry_with_value;
+
+    #[test]
+    fn migration_copies_and_verifies_instance_data() {
+        let (_instance, source) = test_instance_and_context(test_dna(), None).unwrap();
+        let entry = test_entry_with_value("{\"stuff\":\"test entry value\"}");
+        source
+            .block_on(author_entry(&entry, None, &source, &vec![]))
+            .unwrap();
+        let top_header = source.state().unwrap().agent().top_chain_header();
+
+        let target = test_context("jane", None);
+        let report = migrate_storage(&source, &target).unwrap();
+        assert!(report.contents > 0);
+        assert!(report.verified > 0);
+        assert!(report.verified <= report.contents);
+
+        let loaded = SimplePersister::new(target.dht_storage.clone())
+            .load(target.clone())
+            .unwrap()
+            .expect("Migrated state should be loadable");
+        assert_eq!(loaded.agent().top_chain_header(), top_header);
+
+        // Migrating twice into the same storage would mix two instances' data
+        assert!(migrate_storage(&source, &target).is_err());
+    }
+}
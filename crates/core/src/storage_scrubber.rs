@@ -0,0 +1,216 @@
+//! Integrity checks of an instance's CAS.
+//!
+//! Content addressable storage means every entry and header is stored under the hash of its
+//! content. [scrub_storage] re-hashes everything in the CAS and compares it to the address it
+//! is stored under, so that silent corruption on disk gets noticed before it shows up as a
+//! confusing deserialization error somewhere else. Corrupted content gets quarantined by the
+//! backend. Entries this instance holds for the DHT are then fetched again from the network
+//! by [restore_from_network].
+use crate::{
+    agent::state::AGENT_SNAPSHOT_ADDRESS,
+    content_store::WRITE_BATCH_JOURNAL_ADDRESS,
+    context::Context,
+    dht::dht_store::DHT_STORE_SNAPSHOT_ADDRESS,
+    network::{self, actions::query::QueryMethod, query::NetworkQueryResult},
+    nucleus::state::NUCLEUS_SNAPSHOT_ADDRESS,
+    write_ahead_log::WAL_ADDRESS,
+};
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    entry::Entry,
+    error::{HcResult, HolochainError},
+    time::Timeout,
+};
+use holochain_persistence_api::cas::content::{Address, AddressableContent, Content};
+use lib3h_protocol::types::EntryHash;
+use std::sync::Arc;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ScrubReport {
+    /// Number of contents whose address got checked
+    pub checked: usize,
+    /// Number of contents that are neither entries nor headers and can't be checked
+    pub skipped: usize,
+    /// Addresses of contents that didn't match their address and got quarantined
+    pub quarantined: Vec<Address>,
+    /// Quarantined addresses that are held for the DHT and should be fetched again
+    pub held: Vec<Address>,
+    /// Held addresses that got fetched from the network and stored again
+    pub restored: Vec<Address>,
+}
+
+/// What the content at an address should hash to
+pub(crate) enum Expected {
+    Address(Address),
+    /// Content that is not addressed by its hash, like state snapshots
+    Unchecked,
+    Corrupted,
+}
+
+pub(crate) fn expected_address(content: Content) -> Expected {
+    if let Ok(header) = ChainHeader::try_from_content(&content) {
+        return Expected::Address(header.address());
+    }
+    if let Ok(entry) = Entry::try_from_content(&content) {
+        return Expected::Address(entry.address());
+    }
+    // Valid JSON we don't know the type of is fine, garbage is not
+    match serde_json::from_str::<serde_json::Value>(&String::from(content)) {
+        Ok(_) => Expected::Unchecked,
+        Err(_) => Expected::Corrupted,
+    }
+}
+
+/// Checks all content in the instance's CAS against its address and quarantines what
+/// doesn't match. Fails if the storage backend can't list or quarantine its content.
+pub fn scrub_storage(context: &Arc<Context>) -> HcResult<ScrubReport> {
+    let storage = context.storage_compactor.clone().ok_or_else(|| {
+        HolochainError::ErrorGeneric("Storage backend does not support scrubbing".to_string())
+    })?;
+    let state = context
+        .state()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Couldn't get instance state".to_string()))?;
+    let snapshots: Vec<Address> = vec![
+        AGENT_SNAPSHOT_ADDRESS.into(),
+        NUCLEUS_SNAPSHOT_ADDRESS.into(),
+        DHT_STORE_SNAPSHOT_ADDRESS.into(),
+        WAL_ADDRESS.into(),
+        WRITE_BATCH_JOURNAL_ADDRESS.into(),
+    ];
+
+    let mut report = ScrubReport::default();
+    for address in storage.addresses()? {
+        if snapshots.contains(&address) {
+            report.skipped += 1;
+            continue;
+        }
+        let expected = match context.dht_storage.read().unwrap().fetch(&address) {
+            Ok(Some(content)) => expected_address(content),
+            // Removed since we listed it
+            Ok(None) => Expected::Unchecked,
+            // Storage wrappers fail on corrupted data already, e.g. when decrypting
+            Err(_) => Expected::Corrupted,
+        };
+        match expected {
+            Expected::Address(ref expected) if *expected == address => report.checked += 1,
+            Expected::Unchecked => report.skipped += 1,
+            _ => {
+                report.checked += 1;
+                log_warn!(
+                    context,
+                    "storage_scrubber: content at {} is corrupted, quarantining it",
+                    address
+                );
+                storage.quarantine(&address)?;
+                let held = state
+                    .dht()
+                    .get_holding_map()
+                    .bare()
+                    .contains_key(&EntryHash::from(address.clone()));
+                if held {
+                    report.held.push(address.clone());
+                }
+                report.quarantined.push(address);
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Scrubs the instance's storage and fetches what got quarantined from the network again,
+/// if it is held for the DHT.
+pub fn scrub_and_restore(context: &Arc<Context>) -> HcResult<ScrubReport> {
+    let mut report = scrub_storage(context)?;
+    if !report.held.is_empty() {
+        report.restored = context.block_on(restore_from_network(context, &report.held));
+    }
+    log_info!(
+        context,
+        "storage_scrubber: checked {} contents, quarantined {}, restored {}",
+        report.checked,
+        report.quarantined.len(),
+        report.restored.len()
+    );
+    Ok(report)
+}
+
+/// Fetches the given entries from the network and stores them again in the DHT storage,
+/// together with their headers. Returns the addresses of the restored entries.
+pub async fn restore_from_network(context: &Arc<Context>, addresses: &[Address]) -> Vec<Address> {
+    let mut restored = Vec::new();
+    for address in addresses {
+        let result = network::actions::query::query(
+            context.clone(),
+            QueryMethod::Entry(address.clone()),
+            Timeout::default(),
+        )
+        .await;
+        let entry_with_meta_and_header = match result {
+            Ok(NetworkQueryResult::Entry(Some(entry))) => entry,
+            other => {
+                log_warn!(
+                    context,
+                    "storage_scrubber: could not fetch {} from the network: {:?}",
+                    address,
+                    other
+                );
+                continue;
+            }
+        };
+        let entry = entry_with_meta_and_header.entry_with_meta.entry;
+        if entry.address() != *address {
+            log_warn!(
+                context,
+                "storage_scrubber: network returned a different entry for {}",
+                address
+            );
+            continue;
+        }
+        let stored = {
+            let mut storage = context.dht_storage.write().unwrap();
+            storage.add(&entry).and_then(|_| {
+                entry_with_meta_and_header
+                    .headers
+                    .iter()
+                    .map(|header| storage.add(header))
+                    .collect()
+            })
+        };
+        match stored {
+            Ok(()) => restored.push(address.clone()),
+            Err(error) => log_error!(
+                context,
+                "storage_scrubber: could not store {} again: {}",
+                address,
+                error
+            ),
+        }
+    }
+    restored
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::entry::test_entry_with_value;
+    use holochain_json_api::json::{JsonString, RawString};
+
+    #[test]
+    fn only_known_content_gets_checked() {
+        let entry = test_entry_with_value("{\"stuff\":\"test entry value\"}");
+        match expected_address(entry.content()) {
+            Expected::Address(address) => assert_eq!(address, entry.address()),
+            _ => panic!("Entries should be checked"),
+        }
+
+        match expected_address(JsonString::from(RawString::from("just a string"))) {
+            Expected::Unchecked => (),
+            _ => panic!("Unknown content should not be checked"),
+        }
+
+        match expected_address(JsonString::from_json("{\"App\":[\"tes")) {
+            Expected::Corrupted => (),
+            _ => panic!("Garbage should be corrupted"),
+        }
+    }
+}
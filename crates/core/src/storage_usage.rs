@@ -0,0 +1,198 @@
+//! Reports how much storage an instance uses, per store and per kind of data, so operators can
+//! tell which hApps and which of their entry types make the disk grow.
+//!
+//! Sizes are the number of bytes of the serialized content as it gets handed to the storages,
+//! which is what storage backends write, minus their own overhead and compression.
+use crate::{
+    content_store::{fetch_with_shared_dna, SharedDnaStorage},
+    context::Context,
+};
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    eav::{Attribute, EaviQuery},
+    entry::Entry,
+    error::{HcResult, HolochainError},
+};
+use holochain_locksmith::RwLock;
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent},
+        storage::ContentAddressableStorage,
+    },
+    eav::IndexFilter,
+};
+use std::{collections::BTreeMap, convert::TryFrom, sync::Arc};
+
+/// Number of items and the bytes they take up
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct UsageCount {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+impl UsageCount {
+    fn add(&mut self, bytes: usize) {
+        self.count += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Usage of a single store, in total and broken down by entry type (or attribute for the EAV)
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct StoreUsage {
+    pub total: UsageCount,
+    pub by_type: BTreeMap<String, UsageCount>,
+}
+
+impl StoreUsage {
+    fn add(&mut self, kind: String, bytes: usize) {
+        self.total.add(bytes);
+        self.by_type.entry(kind).or_default().add(bytes);
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct StorageUsage {
+    /// Entries and headers of the source chain
+    pub chain: StoreUsage,
+    /// Entries held for the DHT, together with their headers
+    pub cas: StoreUsage,
+    /// Meta data of the DHT, by attribute
+    pub eav: StoreUsage,
+    /// Validation packages and network query results kept in memory
+    pub caches: StoreUsage,
+}
+
+impl StorageUsage {
+    pub fn new(context: &Arc<Context>) -> HcResult<StorageUsage> {
+        let state = context
+            .state()
+            .ok_or_else(|| HolochainError::ErrorGeneric("Couldn't get instance state".into()))?;
+        let (agent, network, dht) = (state.agent(), state.network(), state.dht());
+        let mut usage = StorageUsage::default();
+
+        for header in agent.iter_chain() {
+            let entry_type = header.entry_type().to_string();
+            let entry_bytes = content_size(
+                &context.chain_storage,
+                &context.dna_storage,
+                header.entry_address(),
+            )?;
+            usage
+                .chain
+                .add(entry_type, entry_bytes + header_size(&header));
+        }
+
+        for entry_hash in dht.get_holding_map().bare().keys() {
+            let address = Address::from(entry_hash.to_string());
+            let content = match fetch_with_shared_dna(
+                &context.dht_storage,
+                &context.dna_storage,
+                &address,
+            )? {
+                Some(content) => content,
+                // Only aspects like links or updates held for this entry
+                None => continue,
+            };
+            let entry_type = Entry::try_from(content.clone())
+                .map(|entry| entry.entry_type().to_string())
+                .unwrap_or_else(|_| String::from("UNKNOWN"));
+            let header_bytes: usize = dht.get_headers(address)?.iter().map(header_size).sum();
+            usage
+                .cas
+                .add(entry_type, String::from(content).len() + header_bytes);
+        }
+
+        let eavis = dht.fetch_eavi(&EaviQuery::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            IndexFilter::Range(None, None),
+            None,
+        ))?;
+        for eavi in eavis {
+            let bytes = String::from(eavi.entity()).len()
+                + eavi.attribute().to_string().len()
+                + String::from(eavi.value()).len()
+                + std::mem::size_of::<i64>();
+            usage.eav.add(attribute_kind(&eavi.attribute()), bytes);
+        }
+
+        for cached in network.validation_cache.values() {
+            let bytes = serde_json::to_string(cached).map(|s| s.len()).unwrap_or(0);
+            usage.caches.add(String::from("validation_package"), bytes);
+        }
+        for result in network.get_query_results.values() {
+            let bytes = match result {
+                Some(Ok(result)) => serde_json::to_string(result).map(|s| s.len()).unwrap_or(0),
+                _ => 0,
+            };
+            usage.caches.add(String::from("query_result"), bytes);
+        }
+
+        Ok(usage)
+    }
+}
+
+fn content_size(
+    storage: &Arc<RwLock<dyn ContentAddressableStorage>>,
+    dna_storage: &Option<SharedDnaStorage>,
+    address: &Address,
+) -> HcResult<usize> {
+    Ok(fetch_with_shared_dna(storage, dna_storage, address)?
+        .map(|content| String::from(content).len())
+        .unwrap_or(0))
+}
+
+fn header_size(header: &ChainHeader) -> usize {
+    String::from(header.content()).len()
+}
+
+/// Groups attributes without the link types and tags they carry
+fn attribute_kind(attribute: &Attribute) -> String {
+    match attribute {
+        Attribute::LinkTag(link_type, _) => format!("link__{}", link_type),
+        Attribute::RemovedLink(_, link_type, _) => format!("removed_link__{}", link_type),
+        attribute => attribute.to_string(),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        instance::tests::test_instance_and_context, nucleus::actions::tests::test_dna,
+        workflows::author_entry::author_entry,
+    };
+    use holochain_core_types::entry::test_entry_with_value;
+
+    #[test]
+    fn reports_chain_usage_by_entry_type() {
+        let (_instance, context) = test_instance_and_context(test_dna(), None).unwrap();
+        let before = StorageUsage::new(&context).unwrap();
+
+        let entry = test_entry_with_value("{\"stuff\":\"test entry value\"}");
+        context
+            .block_on(author_entry(&entry, None, &context, &vec![]))
+            .unwrap();
+
+        let after = StorageUsage::new(&context).unwrap();
+        assert_eq!(after.chain.total.count, before.chain.total.count + 1);
+        let app_usage = after
+            .chain
+            .by_type
+            .get(&entry.entry_type().to_string())
+            .expect("Entry type should be reported");
+        assert_eq!(app_usage.count, 1);
+        assert!(app_usage.bytes > String::from(entry.content()).len());
+    }
+
+    #[test]
+    fn attribute_kind_leaves_out_tags() {
+        assert_eq!(
+            attribute_kind(&Attribute::LinkTag("friend".into(), "secret".into())),
+            "link__friend"
+        );
+        assert_eq!(attribute_kind(&Attribute::CrudStatus), "crud-status");
+    }
+}
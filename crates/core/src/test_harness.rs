@@ -0,0 +1,424 @@
+//! In-process harness for integration tests with several agents.
+//!
+//! [TestHarness] runs a full instance of a DNA for each agent inside the calling process, all
+//! of them connected through the same in-memory network, so that tests of core and of hApps
+//! don't need external conductor processes or a sim2h server. Every agent gets a deterministic
+//! key derived from its name that signs for it in place of a conductor's keystore.
+//!
+//! Agents make zome calls and commit entries through [TestAgent::call] and
+//! [TestAgent::commit]. [TestHarness::await_consistency] then blocks until every effect that
+//! the consistency signals of all agents announced has happened: results of zome calls on the
+//! agent that made them, and holding of published entries, links and validations on every
+//! agent, since each node of the in-memory network holds everything.
+//!
+//! The network of a harness goes through the fault injector that [TestHarness::faults]
+//! returns, so tests can add latency, drop messages and partition agents while they run.
+use crate::{
+    consistency::ConsistencyGroup,
+    context::{test_memory_network_config, Context},
+    full_consistency::FullConsistencyWaiter,
+    holding_chaos::HoldingChaos,
+    instance::Instance,
+    nucleus::{
+        actions::{call_zome_function::call_zome_function, get_entry::get_entry_from_dht},
+        state::NucleusStatus,
+        ZomeFnCall,
+    },
+    persister::SimplePersister,
+    signal::{signal_channel, Signal, SignalReceiver},
+    state_dump::StateDump,
+    workflows::author_entry::author_entry,
+};
+use holochain_core_types::{
+    agent::AgentId,
+    dna::Dna,
+    entry::Entry,
+    error::{HcResult, HolochainError},
+};
+use holochain_dpki::{key_bundle::KeyBundle, SEED_SIZE};
+use holochain_json_api::json::JsonString;
+use holochain_locksmith::{Mutex, RwLock};
+use holochain_net::fault_injection::{fault_injector, FaultInjector};
+use holochain_persistence_api::cas::content::Address;
+use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+use jsonrpc_core::{self, types::params::Params, IoHandler};
+use lib3h_sodium::secbuf::SecBuf;
+use serde_json::json;
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long agents get to initialize their chains
+pub const INIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// One agent of a [TestHarness], with its own instance of the DNA
+pub struct TestAgent {
+    pub name: String,
+    pub instance: Instance,
+    pub context: Arc<Context>,
+    signals: SignalReceiver,
+}
+
+impl TestAgent {
+    /// Starts an instance of the DNA for the agent and waits until its chain got initialized
+    pub fn new(dna: Dna, name: &str, network_name: &str) -> HcResult<Self> {
+        let (context, signals) = agent_context(name, network_name)?;
+        let mut instance = Instance::new(context.clone());
+        let context = instance.initialize(Some(dna), context)?;
+
+        let started = Instant::now();
+        loop {
+            match instance.state().nucleus().status {
+                NucleusStatus::Initialized(_) => break,
+                NucleusStatus::InitializationFailed(ref error) => {
+                    return Err(HolochainError::ErrorGeneric(format!(
+                        "Agent {} could not initialize: {}",
+                        name, error
+                    )))
+                }
+                _ if started.elapsed() > INIT_TIMEOUT => {
+                    return Err(HolochainError::Timeout(format!(
+                        "Agent {} did not initialize within {:?}",
+                        name, INIT_TIMEOUT
+                    )))
+                }
+                _ => thread::sleep(POLL_INTERVAL),
+            }
+        }
+
+        Ok(TestAgent {
+            name: name.to_string(),
+            instance,
+            context,
+            signals,
+        })
+    }
+
+    /// Starts an instance of the DNA for the agent with the state of the dump, as
+    /// [StateDump::load] restores it. The dump has to be taken of an agent with the same name.
+    pub fn from_dump(dna: Dna, name: &str, network_name: &str, dump: &StateDump) -> HcResult<Self> {
+        let (context, signals) = agent_context(name, network_name)?;
+        let state = dump.load(dna, &context)?;
+        let mut instance = Instance::from_state(state, context.clone());
+        let context = instance.initialize(None, context)?;
+        Ok(TestAgent {
+            name: name.to_string(),
+            instance,
+            context,
+            signals,
+        })
+    }
+
+    pub fn agent_id(&self) -> AgentId {
+        self.context.agent_id.clone()
+    }
+
+    /// Calls a zome function with the public capability token of the instance
+    pub fn call<J: Into<JsonString>>(
+        &self,
+        zome: &str,
+        function: &str,
+        parameters: J,
+    ) -> HcResult<JsonString> {
+        let token = self.context.get_public_token()?;
+        let call = ZomeFnCall::create(self.context.clone(), zome, token, function, parameters);
+        self.context
+            .block_on(call_zome_function(call, self.context.clone()))
+    }
+
+    /// Commits the entry to the agent's chain and publishes it, as hdk::commit_entry does
+    pub fn commit(&self, entry: &Entry) -> HcResult<Address> {
+        self.context
+            .block_on(author_entry(entry, None, &self.context, &vec![]))
+            .map(|result| result.address())
+    }
+
+    /// Looks up an entry in what the agent holds of the DHT, without going to the network
+    pub fn get_from_dht(&self, address: &Address) -> HcResult<Option<Entry>> {
+        get_entry_from_dht(&self.context, address)
+    }
+}
+
+fn agent_context(name: &str, network_name: &str) -> HcResult<(Arc<Context>, SignalReceiver)> {
+    let (agent_id, keybundle) = test_agent_key(name)?;
+    let (signal_tx, signals) = signal_channel();
+    let storage = Arc::new(RwLock::new(MemoryStorage::new()));
+    let mut context = Context::new(
+        name,
+        agent_id,
+        Arc::new(RwLock::new(SimplePersister::new(storage.clone()))),
+        storage.clone(),
+        storage,
+        Arc::new(RwLock::new(EavMemoryStorage::new())),
+        test_memory_network_config(Some(network_name)).with_fault_injection(network_name),
+        Some(Arc::new(RwLock::new(signing_conductor_api(keybundle)))),
+        Some(signal_tx),
+        false,
+        holochain_metrics::config::MetricPublisherConfig::default().create_metric_publisher(),
+        Arc::new(ht::null_tracer()),
+    );
+    context.holding_chaos = HoldingChaos::from_env().map(Arc::new);
+    Ok((Arc::new(context), signals))
+}
+
+/// Several agents running the same DNA on a private in-memory network
+pub struct TestHarness {
+    agents: Vec<TestAgent>,
+    consistency: ConsistencyTracker,
+    full_consistency: FullConsistencyWaiter,
+    /// Signals of each agent other than consistency signals, until a test awaits them
+    signals: Vec<Vec<Signal>>,
+    network_name: String,
+}
+
+impl TestHarness {
+    /// Starts `agent_count` agents, named `agent0`, `agent1` and so on
+    pub fn new(dna: Dna, agent_count: usize) -> HcResult<Self> {
+        let names: Vec<String> = (0..agent_count).map(|i| format!("agent{}", i)).collect();
+        Self::with_agents(dna, &names.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    /// Starts an agent for each of the given names. The DNA's UUID gets changed to one that is
+    /// unique to this harness, so that harnesses of tests running in parallel don't meet.
+    pub fn with_agents(mut dna: Dna, names: &[&str]) -> HcResult<Self> {
+        let network_name = format!("test-harness-{}", snowflake::ProcessUniqueId::new());
+        dna.uuid = network_name.clone();
+        let agents = names
+            .iter()
+            .map(|name| TestAgent::new(dna.clone(), name, &network_name))
+            .collect::<HcResult<Vec<_>>>()?;
+        let contexts: Vec<Arc<Context>> =
+            agents.iter().map(|agent| agent.context.clone()).collect();
+        Ok(TestHarness {
+            consistency: ConsistencyTracker::new(agents.len()),
+            full_consistency: FullConsistencyWaiter::new(&contexts),
+            signals: vec![Vec::new(); agents.len()],
+            agents,
+            network_name,
+        })
+    }
+
+    pub fn agent(&self, index: usize) -> &TestAgent {
+        &self.agents[index]
+    }
+
+    pub fn agents(&self) -> &[TestAgent] {
+        &self.agents
+    }
+
+    /// Controls the faults of the network between the agents
+    pub fn faults(&self) -> Arc<FaultInjector> {
+        fault_injector(&self.network_name)
+    }
+
+    /// Blocks until everything the agents did so far has reached all agents that should see
+    /// it, or fails with the events that are still missing once the timeout is over
+    pub fn await_consistency(&mut self, timeout: Duration) -> HcResult<()> {
+        let started = Instant::now();
+        loop {
+            self.receive_signals();
+            if self.consistency.is_consistent() {
+                return Ok(());
+            }
+            if started.elapsed() > timeout {
+                return Err(HolochainError::Timeout(format!(
+                    "No consistency after {:?}, still waiting for: {}",
+                    timeout,
+                    self.consistency.missing().join(", ")
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Blocks until everything the agents published since they all started is held by every
+    /// agent and no agent is still holding anything. Unlike [TestHarness::await_consistency]
+    /// this looks at the agents' state, so it also covers what they did without signals.
+    pub fn await_full_consistency(&self, timeout: Duration) -> HcResult<()> {
+        self.full_consistency.wait(timeout)
+    }
+
+    /// Blocks until the agent with the given index emits a signal that matches, and returns
+    /// it. Signals emitted since the last call count as well, except for trace signals.
+    pub fn await_signal<F: Fn(&Signal) -> bool>(
+        &mut self,
+        agent: usize,
+        timeout: Duration,
+        matches: F,
+    ) -> HcResult<Signal> {
+        let started = Instant::now();
+        loop {
+            self.receive_signals();
+            if let Some(position) = self.signals[agent].iter().position(&matches) {
+                return Ok(self.signals[agent].remove(position));
+            }
+            if started.elapsed() > timeout {
+                return Err(HolochainError::Timeout(format!(
+                    "Agent {} did not emit the awaited signal within {:?}",
+                    self.agents[agent].name, timeout
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn receive_signals(&mut self) {
+        for (index, agent) in self.agents.iter().enumerate() {
+            for signal in agent.signals.try_iter() {
+                match signal {
+                    Signal::Consistency(signal) => {
+                        self.consistency.observe(index, signal.event());
+                        for (event, group) in signal.pending() {
+                            self.consistency.expect(index, event, group);
+                        }
+                    }
+                    // One for every action, too many to keep
+                    Signal::Trace(_) => (),
+                    signal => self.signals[index].push(signal),
+                }
+            }
+        }
+    }
+}
+
+/// Which events each agent emitted, and which ones the agents still wait for
+struct ConsistencyTracker {
+    seen: Vec<HashSet<String>>,
+    /// Events that should get emitted, and the agents that should emit them
+    expected: Vec<(String, Vec<usize>)>,
+}
+
+impl ConsistencyTracker {
+    fn new(agent_count: usize) -> Self {
+        ConsistencyTracker {
+            seen: vec![HashSet::new(); agent_count],
+            expected: Vec::new(),
+        }
+    }
+
+    fn observe(&mut self, agent: usize, event: &str) {
+        self.seen[agent].insert(event.to_string());
+    }
+
+    fn expect(&mut self, source: usize, event: &str, group: &ConsistencyGroup) {
+        let agents = match group {
+            ConsistencyGroup::Source => vec![source],
+            ConsistencyGroup::Validators => (0..self.seen.len()).collect(),
+        };
+        self.expected.push((event.to_string(), agents));
+    }
+
+    fn is_consistent(&mut self) -> bool {
+        let seen = &self.seen;
+        self.expected
+            .retain(|(event, agents)| agents.iter().any(|agent| !seen[*agent].contains(event)));
+        self.expected.is_empty()
+    }
+
+    fn missing(&self) -> Vec<String> {
+        self.expected
+            .iter()
+            .map(|(event, agents)| {
+                let waiting: Vec<String> = agents
+                    .iter()
+                    .filter(|agent| !self.seen[**agent].contains(event))
+                    .map(|agent| format!("agent {}", agent))
+                    .collect();
+                format!("{} on {}", event, waiting.join(" and "))
+            })
+            .collect()
+    }
+}
+
+/// Derives a key for the agent from its name, so the same name always gets the same agent
+fn test_agent_key(name: &str) -> HcResult<(AgentId, KeyBundle)> {
+    let mut seed = SecBuf::with_insecure(SEED_SIZE);
+    let name_bytes = name.as_bytes();
+    let seed_bytes: Vec<u8> = (0..SEED_SIZE)
+        .map(|i| name_bytes.get(i).cloned().unwrap_or(i as u8))
+        .collect();
+    seed.write(0, seed_bytes.as_slice())?;
+    let keybundle = KeyBundle::new_from_seed_buf(&mut seed)?;
+    Ok((AgentId::new(name, keybundle.get_id()), keybundle))
+}
+
+/// The `agent/sign` method of the conductor API, signing with the agent's key
+fn signing_conductor_api(keybundle: KeyBundle) -> IoHandler {
+    let keybundle = Arc::new(Mutex::new(keybundle));
+    let mut handler = IoHandler::new();
+    handler.add_method("agent/sign", move |params: Params| {
+        let params: serde_json::Map<String, serde_json::Value> = params.parse()?;
+        let payload = params
+            .get("payload")
+            .and_then(|payload| payload.as_str())
+            .ok_or_else(|| jsonrpc_core::Error::invalid_params("`payload` param not provided"))?
+            .to_string();
+        let decoded = base64::decode(&payload)
+            .map_err(|_| jsonrpc_core::Error::invalid_params("`payload` is not base64"))?;
+        let mut message = SecBuf::with_insecure(decoded.len());
+        message
+            .from_array(&decoded)
+            .map_err(|_| jsonrpc_core::Error::internal_error())?;
+        let mut signature = keybundle
+            .lock()
+            .unwrap()
+            .sign(&mut message)
+            .map_err(|_| jsonrpc_core::Error::internal_error())?;
+        let signature = signature.read_lock();
+        Ok(json!({"payload": payload, "signature": base64::encode(&**signature)}))
+    });
+    handler
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::network::test_utils::test_wat_always_valid;
+    use holochain_core_types::entry::test_entry;
+    use holochain_persistence_api::cas::content::AddressableContent;
+    use test_utils::create_test_dna_with_wat;
+
+    #[test]
+    fn tracks_expected_events_per_group() {
+        let mut tracker = ConsistencyTracker::new(2);
+        tracker.expect(0, "Hold", &ConsistencyGroup::Validators);
+        tracker.expect(1, "Return", &ConsistencyGroup::Source);
+        tracker.observe(0, "Hold");
+        assert!(!tracker.is_consistent());
+        assert_eq!(
+            tracker.missing(),
+            vec![
+                "Hold on agent 1".to_string(),
+                "Return on agent 1".to_string()
+            ]
+        );
+
+        // Events that happened before they got expected count as well
+        tracker.observe(1, "Return");
+        tracker.observe(1, "Hold");
+        assert!(tracker.is_consistent());
+        tracker.expect(0, "Hold", &ConsistencyGroup::Validators);
+        assert!(tracker.is_consistent());
+    }
+
+    #[test]
+    fn agents_see_each_others_commits() {
+        let dna = create_test_dna_with_wat("test_zome", Some(&test_wat_always_valid()));
+        let mut harness = TestHarness::new(dna, 2).unwrap();
+        assert_ne!(harness.agent(0).agent_id(), harness.agent(1).agent_id());
+
+        let entry = test_entry();
+        let address = harness.agent(0).commit(&entry).unwrap();
+        assert_eq!(address, entry.address());
+        harness.await_consistency(Duration::from_secs(30)).unwrap();
+        assert_eq!(
+            harness.agent(1).get_from_dht(&address).unwrap(),
+            Some(entry)
+        );
+    }
+}
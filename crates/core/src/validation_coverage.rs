@@ -0,0 +1,390 @@
+//! Coverage of the validation rules of a DNA.
+//!
+//! While an instance records validation coverage, every validation callback that runs gets
+//! counted per branch: the zome and entry type it belongs to, the kind of validation data it
+//! got (create, modify or delete of an entry, adding or removing a link of a given type, or
+//! an agent entry) and whether it found the entry valid. After each callback the instance
+//! writes a [CoverageReport] to the file it records to. The report lists all branches that
+//! the DNA defines, so the ones that a test suite never hit show up with zero counts.
+//!
+//! The reports of the instances of a test run can be merged with [CoverageReport::merge],
+//! which is what `hc validation-coverage` does.
+use crate::nucleus::validation::{ValidationError, ValidationResult};
+use holochain_core_types::{
+    dna::Dna, entry::entry_type::EntryType, error::HolochainError, validation::EntryValidationData,
+};
+use holochain_locksmith::Mutex;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationVariant {
+    Create,
+    Modify,
+    Delete,
+    LinkAdd,
+    LinkRemove,
+}
+
+impl ValidationVariant {
+    pub fn of<T>(validation_data: &EntryValidationData<T>) -> Self {
+        match validation_data {
+            EntryValidationData::Create { .. } => ValidationVariant::Create,
+            EntryValidationData::Modify { .. } => ValidationVariant::Modify,
+            EntryValidationData::Delete { .. } => ValidationVariant::Delete,
+        }
+    }
+}
+
+impl fmt::Display for ValidationVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ValidationVariant::Create => "create",
+            ValidationVariant::Modify => "modify",
+            ValidationVariant::Delete => "delete",
+            ValidationVariant::LinkAdd => "link add",
+            ValidationVariant::LinkRemove => "link remove",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One path through the validation rules of a DNA
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ValidationBranch {
+    pub zome: String,
+    /// Type of the validated entry, or for links the entry type that defines the link
+    pub entry_type: String,
+    pub link_type: Option<String>,
+    pub variant: ValidationVariant,
+}
+
+impl ValidationBranch {
+    pub fn entry(zome: &str, entry_type: &str, variant: ValidationVariant) -> Self {
+        ValidationBranch {
+            zome: zome.to_string(),
+            entry_type: entry_type.to_string(),
+            link_type: None,
+            variant,
+        }
+    }
+
+    pub fn link(zome: &str, entry_type: &str, link_type: &str, variant: ValidationVariant) -> Self {
+        ValidationBranch {
+            zome: zome.to_string(),
+            entry_type: entry_type.to_string(),
+            link_type: Some(link_type.to_string()),
+            variant,
+        }
+    }
+
+    /// All branches the DNA defines
+    pub fn all_of(dna: &Dna) -> Vec<ValidationBranch> {
+        let agent_type = EntryType::AgentId.to_string();
+        let mut branches = Vec::new();
+        for (zome_name, zome) in dna.zomes.iter() {
+            for variant in &[ValidationVariant::Create, ValidationVariant::Modify] {
+                branches.push(Self::entry(zome_name, &agent_type, *variant));
+            }
+            for (entry_type, entry_type_def) in zome.entry_types.iter() {
+                if let EntryType::App(app_entry_type) = entry_type {
+                    let entry_type = app_entry_type.to_string();
+                    for variant in &[
+                        ValidationVariant::Create,
+                        ValidationVariant::Modify,
+                        ValidationVariant::Delete,
+                    ] {
+                        branches.push(Self::entry(zome_name, &entry_type, *variant));
+                    }
+                }
+                let entry_type = entry_type.to_string();
+                let link_types = entry_type_def
+                    .links_to
+                    .iter()
+                    .map(|link| &link.link_type)
+                    .chain(
+                        entry_type_def
+                            .linked_from
+                            .iter()
+                            .map(|link| &link.link_type),
+                    );
+                for link_type in link_types {
+                    for variant in &[ValidationVariant::LinkAdd, ValidationVariant::LinkRemove] {
+                        branches.push(Self::link(zome_name, &entry_type, link_type, *variant));
+                    }
+                }
+            }
+        }
+        branches
+    }
+}
+
+impl fmt::Display for ValidationBranch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.zome, self.entry_type)?;
+        if let Some(ref link_type) = self.link_type {
+            write!(f, " '{}'", link_type)?;
+        }
+        write!(f, " {}", self.variant)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BranchCoverage {
+    #[serde(flatten)]
+    pub branch: ValidationBranch,
+    /// How often the callback found the entry valid
+    pub valid: u64,
+    /// How often the callback found the entry invalid
+    pub invalid: u64,
+}
+
+impl BranchCoverage {
+    pub fn is_covered(&self) -> bool {
+        self.valid + self.invalid > 0
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    pub branches: Vec<BranchCoverage>,
+}
+
+impl CoverageReport {
+    /// The report of the given counts, with all other branches of the DNA at zero
+    pub fn new(dna: &Dna, hits: &BTreeMap<ValidationBranch, (u64, u64)>) -> Self {
+        let mut branches: BTreeMap<ValidationBranch, (u64, u64)> = ValidationBranch::all_of(dna)
+            .into_iter()
+            .map(|branch| (branch, (0, 0)))
+            .collect();
+        branches.extend(
+            hits.iter()
+                .map(|(branch, counts)| (branch.clone(), *counts)),
+        );
+        CoverageReport {
+            branches: branches
+                .into_iter()
+                .map(|(branch, (valid, invalid))| BranchCoverage {
+                    branch,
+                    valid,
+                    invalid,
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds the counts of the other report to the ones of this one
+    pub fn merge(&mut self, other: &CoverageReport) {
+        let mut branches: BTreeMap<ValidationBranch, BranchCoverage> = self
+            .branches
+            .drain(..)
+            .map(|coverage| (coverage.branch.clone(), coverage))
+            .collect();
+        for coverage in other.branches.iter() {
+            let merged =
+                branches
+                    .entry(coverage.branch.clone())
+                    .or_insert_with(|| BranchCoverage {
+                        branch: coverage.branch.clone(),
+                        valid: 0,
+                        invalid: 0,
+                    });
+            merged.valid += coverage.valid;
+            merged.invalid += coverage.invalid;
+        }
+        self.branches = branches.into_iter().map(|(_, coverage)| coverage).collect();
+    }
+
+    pub fn covered(&self) -> usize {
+        self.branches
+            .iter()
+            .filter(|coverage| coverage.is_covered())
+            .count()
+    }
+
+    pub fn uncovered(&self) -> Vec<&ValidationBranch> {
+        self.branches
+            .iter()
+            .filter(|coverage| !coverage.is_covered())
+            .map(|coverage| &coverage.branch)
+            .collect()
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for coverage in self.branches.iter() {
+            if coverage.is_covered() {
+                writeln!(
+                    f,
+                    "  {}: {} valid, {} invalid",
+                    coverage.branch, coverage.valid, coverage.invalid
+                )?;
+            } else {
+                writeln!(f, "  {}: NOT HIT", coverage.branch)?;
+            }
+        }
+        let total = self.branches.len();
+        let percentage = if total == 0 {
+            100.0
+        } else {
+            self.covered() as f64 * 100.0 / total as f64
+        };
+        write!(
+            f,
+            "{} of {} validation branches hit ({:.1}%)",
+            self.covered(),
+            total,
+            percentage
+        )
+    }
+}
+
+struct CoverageFile {
+    path: PathBuf,
+    hits: BTreeMap<ValidationBranch, (u64, u64)>,
+}
+
+/// Counts the validation branches that an instance runs while it records coverage
+#[derive(Default)]
+pub struct ValidationCoverage {
+    file: Mutex<Option<CoverageFile>>,
+}
+
+impl ValidationCoverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.file.lock().unwrap().is_some()
+    }
+
+    /// Starts counting from zero, with the report going to the given path
+    pub fn start(&self, path: &Path) -> Result<(), HolochainError> {
+        let mut file = self.file.lock().unwrap();
+        if let Some(ref coverage) = *file {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Validation coverage is already being recorded to {}",
+                coverage.path.display()
+            )));
+        }
+        *file = Some(CoverageFile {
+            path: path.to_path_buf(),
+            hits: BTreeMap::new(),
+        });
+        Ok(())
+    }
+
+    /// Stops recording. Returns the path of the report, if there was one.
+    pub fn stop(&self) -> Option<PathBuf> {
+        self.file
+            .lock()
+            .unwrap()
+            .take()
+            .map(|coverage| coverage.path)
+    }
+
+    /// Counts the branch, if coverage is being recorded, and rewrites the report.
+    /// Results other than valid or invalid don't count, since the callback didn't decide.
+    pub fn record(
+        &self,
+        dna: &Dna,
+        branch: ValidationBranch,
+        result: &ValidationResult,
+    ) -> Result<(), HolochainError> {
+        let mut file = self.file.lock().unwrap();
+        let coverage = match *file {
+            Some(ref mut coverage) => coverage,
+            None => return Ok(()),
+        };
+        let counts = coverage.hits.entry(branch).or_insert((0, 0));
+        match result {
+            Ok(()) => counts.0 += 1,
+            Err(ValidationError::Fail(_)) => counts.1 += 1,
+            Err(_) => return Ok(()),
+        }
+        let report = CoverageReport::new(dna, &coverage.hits);
+        fs::write(&coverage.path, serde_json::to_string_pretty(&report)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::dna::{
+        entry_types::{EntryTypeDef, LinksTo},
+        zome::Zome,
+    };
+
+    fn blog_dna() -> Dna {
+        let mut post = EntryTypeDef::new();
+        post.links_to.push(LinksTo {
+            target_type: String::from("post"),
+            link_type: String::from("reply"),
+        });
+        let mut zome = Zome::empty();
+        zome.entry_types.insert(EntryType::from("post"), post);
+        let mut dna = Dna::new();
+        dna.zomes.insert(String::from("blog"), zome);
+        dna
+    }
+
+    #[test]
+    fn reports_list_branches_that_were_never_hit() {
+        let path = std::env::temp_dir().join("holochain_core_validation_coverage_test.json");
+        let _ = fs::remove_file(&path);
+        let dna = blog_dna();
+        let coverage = ValidationCoverage::new();
+        let create = ValidationBranch::entry("blog", "post", ValidationVariant::Create);
+        coverage.record(&dna, create.clone(), &Ok(())).unwrap();
+        assert!(!coverage.is_recording());
+        assert!(!path.exists());
+
+        coverage.start(&path).unwrap();
+        assert!(coverage.start(&path).is_err());
+        let reply = ValidationBranch::link("blog", "post", "reply", ValidationVariant::LinkAdd);
+        let fail = Err(ValidationError::Fail("too long".into()));
+        coverage.record(&dna, create.clone(), &Ok(())).unwrap();
+        coverage.record(&dna, create.clone(), &fail).unwrap();
+        coverage.record(&dna, reply.clone(), &Ok(())).unwrap();
+        let unresolved = Err(ValidationError::UnresolvedDependencies(Vec::new()));
+        coverage.record(&dna, reply.clone(), &unresolved).unwrap();
+        assert_eq!(coverage.stop(), Some(path.clone()));
+
+        let report: CoverageReport =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        // create, modify and delete of posts and agents, and adding and removing replies
+        assert_eq!(report.branches.len(), 7);
+        assert_eq!(report.covered(), 2);
+        let hits = |branch: &ValidationBranch| {
+            let coverage = report
+                .branches
+                .iter()
+                .find(|c| c.branch == *branch)
+                .unwrap();
+            (coverage.valid, coverage.invalid)
+        };
+        assert_eq!(hits(&create), (1, 1));
+        assert_eq!(hits(&reply), (1, 0));
+        assert!(report.uncovered().contains(&&ValidationBranch::entry(
+            "blog",
+            "post",
+            ValidationVariant::Delete
+        )));
+
+        let mut merged = report.clone();
+        merged.merge(&report);
+        assert_eq!(hits(&create), (1, 1));
+        let doubled = merged.branches.iter().find(|c| c.branch == create).unwrap();
+        assert_eq!((doubled.valid, doubled.invalid), (2, 2));
+        assert!(merged
+            .to_string()
+            .ends_with("2 of 7 validation branches hit (28.6%)"));
+    }
+}
@@ -12,7 +12,6 @@ use holochain_logging::prelude::*;
 
 use holochain_wasm_utils::api_serialization::{ZomeFnCallArgs, THIS_INSTANCE};
 use jsonrpc_lite::JsonRpc;
-use snowflake::ProcessUniqueId;
 use std::{convert::TryFrom, sync::Arc};
 use wasmi::{RuntimeArgs, RuntimeValue};
 
@@ -50,7 +49,7 @@ pub fn invoke_call(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
     // deserialize args
     let args_str = runtime.load_json_string_from_args(&args);
 
-    let input = match ZomeFnCallArgs::try_from(args_str.clone()) {
+    let mut input = match ZomeFnCallArgs::try_from(args_str.clone()) {
         Ok(input) => input,
         // Exit on error
         Err(_) => {
@@ -63,15 +62,38 @@ pub fn invoke_call(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
         }
     };
 
-    let span = context
-        .tracer
-        .span("hdk invoke_call")
-        .tag(ht::Tag::new("ZomeFnCallArgs", format!("{:?}", input)))
-        .start()
-        .into();
-    let _spanguard = ht::push_span(span);
+    // A child of the calling zome function's span
+    let _spanguard = ht::push_span_with(|span| {
+        span.child_("hdk invoke_call", |s| {
+            s.tag(ht::Tag::new("ZomeFnCallArgs", format!("{:?}", input)))
+                .start()
+        })
+        .into()
+    });
+
+    // If the grantor rotated the grant, use the replacement token
+    if let Some(state) = context.state() {
+        input.cap_token = state.agent().current_cap_token(&input.cap_token);
+    }
 
-    let result = if input.instance_handle == THIS_INSTANCE {
+    // The grantor told us this token is dead, no point in trying it
+    let is_revoked = context
+        .state()
+        .map(|state| state.agent().is_cap_token_revoked(&input.cap_token))
+        .unwrap_or(false);
+
+    let result = if is_revoked {
+        log_debug!(
+            context,
+            "zome: not calling {} with revoked capability token {}",
+            input.fn_name,
+            input.cap_token
+        );
+        Err(HolochainError::ErrorGeneric(format!(
+            "Capability token {} has been revoked by its grantor",
+            input.cap_token
+        )))
+    } else if input.instance_handle == THIS_INSTANCE {
         // ZomeFnCallArgs to ZomeFnCall
         let zome_call = ZomeFnCall::from_args(context.clone(), input.clone());
 
@@ -133,7 +155,7 @@ fn bridge_call(runtime: &mut Runtime, input: ZomeFnCallArgs) -> Result<JsonStrin
 
     let handler = conductor_api.get().write().unwrap();
 
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     // json-rpc format
     let request = format!(
         r#"{{"jsonrpc": "2.0", "method": "call", "params": {}, "id": "{}"}}"#,
@@ -511,6 +533,28 @@ pub mod tests {
         );
         let result = validate_call(context.clone(), &zome_call);
         assert!(result.is_ok());
+
+        // someone else signing a call with the agent token should fail
+        let client_context = test_context("client", None);
+        let client_call = ZomeFnCall::new(
+            "test_zome",
+            make_cap_request_for_call(
+                client_context.clone(),
+                Address::from(context.agent_id.address()), // <- agent token
+                "test",
+                "{}",
+            ),
+            "test",
+            "{}",
+        );
+        let result = validate_call(context.clone(), &client_call);
+        assert_eq!(result, Err(HolochainError::CapabilityCheckFailed));
+
+        // unless their key is registered as a client key of the agent
+        let mut context_with_client = (*context).clone();
+        context_with_client.client_keys = vec![client_context.agent_id.address()];
+        let result = validate_call(Arc::new(context_with_client), &client_call);
+        assert!(result.is_ok());
     }
 
     #[test]
@@ -1,20 +1,28 @@
 use crate::{
     agent::actions::commit::commit_entry,
+    context::Context,
     wasm_engine::{api::ZomeApiResult, Runtime},
+    workflows::{revoke_grant::revoke_grant_workflow, rotate_grant::rotate_grant_workflow},
 };
 use holochain_core_types::{
     entry::{
-        cap_entries::{CapTokenClaim, CapTokenGrant},
+        cap_entries::{CapTokenClaim, CapTokenDelegation, CapTokenGrant},
         Entry,
     },
     error::HolochainError,
+    signature::Signature,
 };
-use holochain_persistence_api::cas::content::Address;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
 
-use holochain_wasm_utils::api_serialization::capabilities::{
-    CommitCapabilityClaimArgs, CommitCapabilityGrantArgs,
+use holochain_wasm_utils::api_serialization::{
+    capabilities::{
+        CapabilityAuditLogResult, CommitCapabilityClaimArgs, CommitCapabilityGrantArgs,
+        DelegateCapabilityArgs, DelegateCapabilityResult, GetCapabilityAuditLogArgs,
+        RevokeCapabilityGrantArgs, RotateCapabilityGrantArgs,
+    },
+    crypto::CryptoMethod,
 };
-use std::convert::TryFrom;
+use std::{convert::TryFrom, sync::Arc, time::Duration};
 use wasmi::{RuntimeArgs, RuntimeValue};
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
@@ -30,7 +38,12 @@ pub fn invoke_commit_capability_grant(runtime: &mut Runtime, args: &RuntimeArgs)
     let task_result: Result<Address, HolochainError> =
         match CapTokenGrant::create(&args.id, args.cap_type, args.assignees, args.functions) {
             Ok(grant) => context.block_on(commit_entry(
-                Entry::CapTokenGrant(grant),
+                Entry::CapTokenGrant(
+                    grant
+                        .with_expires_at(args.expires_at)
+                        .with_arg_constraints(args.arg_constraints)
+                        .with_delegatable(args.delegatable),
+                ),
                 None,
                 &context.clone(),
             )),
@@ -61,6 +74,128 @@ pub fn invoke_commit_capability_claim(runtime: &mut Runtime, args: &RuntimeArgs)
     runtime.store_result(task_result)
 }
 
+/// Revokes one of this agent's capability grants and notifies its assignees
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn invoke_revoke_capability_grant(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    // deserialize args
+    let args_str = runtime.load_json_string_from_args(&args);
+    let args = match RevokeCapabilityGrantArgs::try_from(args_str) {
+        Ok(input) => input,
+        Err(..) => return ribosome_error_code!(ArgumentDeserializationFailed),
+    };
+
+    let task_result = context.block_on(revoke_grant_workflow(&args.token, &context));
+    runtime.store_result(task_result)
+}
+
+/// Replaces one of this agent's capability grants by one with a new token
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn invoke_rotate_capability_grant(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    // deserialize args
+    let args_str = runtime.load_json_string_from_args(&args);
+    let args = match RotateCapabilityGrantArgs::try_from(args_str) {
+        Ok(input) => input,
+        Err(..) => return ribosome_error_code!(ArgumentDeserializationFailed),
+    };
+
+    let task_result = context.block_on(rotate_grant_workflow(
+        &args.token,
+        Duration::from_secs(args.grace_period_secs),
+        args.notify_assignees,
+        &context,
+    ));
+    runtime.store_result(task_result)
+}
+
+/// Mints a sub-token of the given parent token for the given delegatee, signed by this agent.
+/// A delegated sub-token can only be delegated further by its delegatee.
+/// Returns the parent's delegation chain with the new delegation appended, which is what
+/// callers have to send along with the sub-token.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn delegate_capability(
+    context: &Arc<Context>,
+    args: DelegateCapabilityArgs,
+) -> Result<Vec<CapTokenDelegation>, HolochainError> {
+    let delegation = CapTokenDelegation::new(
+        args.parent_token.clone(),
+        context.agent_id.address(),
+        args.delegatee,
+        args.functions,
+        args.arg_constraints,
+        args.expires_at,
+    );
+    if let Some(parent) = args.parent_delegation.last() {
+        if parent.token() != args.parent_token {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Parent delegation {} does not match parent token {}",
+                parent.token(),
+                args.parent_token
+            )));
+        }
+        if parent.delegatee() != context.agent_id.address() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Parent delegation {} was not made to this agent",
+                parent.token()
+            )));
+        }
+        if !delegation.is_within(
+            &parent.functions(),
+            &parent.arg_constraints(),
+            Some(parent.expires_at()),
+        ) {
+            return Err(HolochainError::ErrorGeneric(
+                "Delegation exceeds the scope of its parent".to_string(),
+            ));
+        }
+    }
+    let signature = context
+        .conductor_api
+        .execute(delegation.signing_payload(), CryptoMethod::Sign)?;
+
+    let mut chain = args.parent_delegation;
+    chain.push(delegation.with_signature(Signature::from(signature)));
+    Ok(chain)
+}
+
+/// Mints a sub-token from a delegatable grant or from another delegation
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn invoke_delegate_capability(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    // deserialize args
+    let args_str = runtime.load_json_string_from_args(&args);
+    let args = match DelegateCapabilityArgs::try_from(args_str) {
+        Ok(input) => input,
+        Err(..) => return ribosome_error_code!(ArgumentDeserializationFailed),
+    };
+
+    let task_result = delegate_capability(&context, args)
+        .map(|delegation| DelegateCapabilityResult { delegation });
+    runtime.store_result(task_result)
+}
+
+/// Returns the recorded calls made to this instance with capability tokens,
+/// optionally only those made with a given token
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn invoke_get_capability_audit_log(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    // deserialize args
+    let args_str = runtime.load_json_string_from_args(&args);
+    let args = match GetCapabilityAuditLogArgs::try_from(args_str) {
+        Ok(input) => input,
+        Err(..) => return ribosome_error_code!(ArgumentDeserializationFailed),
+    };
+
+    let result = context
+        .state()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Could not get state".to_string()))
+        .map(|state| CapabilityAuditLogResult {
+            records: state.agent().cap_audit_log(args.token.as_ref()),
+        });
+    runtime.store_result(result)
+}
+
 #[cfg(test)]
 pub mod tests {
     use crate::wasm_engine::{
@@ -84,6 +219,9 @@ pub mod tests {
             cap_type: CapabilityType::Assigned,
             assignees: Some(vec![Address::from("fake address")]),
             functions,
+            expires_at: None,
+            arg_constraints: Default::default(),
+            delegatable: false,
         };
 
         JsonString::from(grant_args).to_bytes()
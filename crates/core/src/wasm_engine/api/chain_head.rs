@@ -0,0 +1,64 @@
+use crate::wasm_engine::{api::ZomeApiResult, Runtime};
+use holochain_core_types::error::HolochainError;
+use holochain_persistence_api::cas::content::AddressableContent;
+use holochain_wasm_utils::api_serialization::chain_head::ChainHeadResult;
+use wasmi::RuntimeArgs;
+
+/// ZomeApiFunction::GetChainHead function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Not expecting any complex input
+/// Returns the address, length and last commit timestamp of the agent's source chain
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn invoke_get_chain_head(runtime: &mut Runtime, _args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    let result = context
+        .state()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Could not get state".to_string()))
+        .map(|state| {
+            let agent_state = state.agent();
+            let top_header = agent_state.top_chain_header();
+            ChainHeadResult {
+                address: top_header.as_ref().map(|header| header.address()),
+                length: agent_state.chain_length(),
+                timestamp: top_header.map(|header| header.timestamp().clone()),
+            }
+        });
+
+    runtime.store_result(result)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::wasm_engine::{
+        api::{tests::test_zome_api_function, ZomeApiFunction},
+        Defn,
+    };
+    use holochain_core_types::error::ZomeApiInternalResult;
+    use holochain_json_api::json::JsonString;
+    use holochain_persistence_api::cas::content::AddressableContent;
+    use holochain_wasm_utils::api_serialization::chain_head::ChainHeadResult;
+    use std::convert::TryFrom;
+
+    #[test]
+    /// test that the head of the source chain is returned as it is in the agent state
+    fn test_get_chain_head() {
+        let (call_result, context) =
+            test_zome_api_function(ZomeApiFunction::GetChainHead.as_str(), vec![]);
+
+        let zome_api_internal_result = ZomeApiInternalResult::try_from(call_result).unwrap();
+        assert!(zome_api_internal_result.ok);
+        let chain_head =
+            ChainHeadResult::try_from(JsonString::from_json(&zome_api_internal_result.value))
+                .unwrap();
+
+        let state = context.state().unwrap();
+        let top_header = state.agent().top_chain_header().unwrap();
+        let length = state.agent().iter_chain().count() as u64;
+        // genesis committed at least the DNA and the agent id
+        assert!(length >= 2);
+        assert_eq!(chain_head.length, length);
+        assert_eq!(chain_head.sequence(), Some(length - 1));
+        assert_eq!(chain_head.address, Some(top_header.address()));
+        assert_eq!(chain_head.timestamp, Some(top_header.timestamp().clone()));
+    }
+}
@@ -1,7 +1,4 @@
-use crate::{
-    wasm_engine::{api::ZomeApiResult, Runtime},
-    workflows::author_entry::author_entry,
-};
+use crate::wasm_engine::{api::ZomeApiResult, Runtime};
 use holochain_core_types::error::HolochainError;
 
 use holochain_wasm_utils::api_serialization::commit_entry::{CommitEntryArgs, CommitEntryResult};
@@ -13,6 +10,8 @@ use wasmi::{RuntimeArgs, RuntimeValue};
 /// args: [0] encoded MemoryAllocation as u64
 /// Expected complex argument: CommitEntryArg
 /// Returns an HcApiReturnCode as I64
+/// The entry gets validated right away, but committed together with the entries of the
+/// following commit calls (see PendingCommits).
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 pub fn invoke_commit_app_entry(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
     let context = runtime.context()?;
@@ -45,13 +44,16 @@ pub fn invoke_commit_app_entry(runtime: &mut Runtime, args: &RuntimeArgs) -> Zom
         .into();
     let _spanguard = ht::push_span(span);
 
-    // Wait for future to be resolved
-    let task_result: Result<CommitEntryResult, HolochainError> = context.block_on(author_entry(
-        &commit_entry_arg.entry(),
-        None,
-        &context,
-        &commit_entry_arg.options().provenance(),
-    ));
+    let task_result: Result<CommitEntryResult, HolochainError> = runtime
+        .add_pending_commit(
+            &context,
+            (
+                commit_entry_arg.entry(),
+                None,
+                commit_entry_arg.options().provenance(),
+            ),
+        )
+        .map(CommitEntryResult::new);
 
     runtime.store_result(task_result)
 }
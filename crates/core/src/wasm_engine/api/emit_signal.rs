@@ -31,7 +31,14 @@ pub fn invoke_emit_signal(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiR
     };
 
     if let Some(sender) = context.signal_tx() {
-        let signal = Signal::User(UserSignal::from(emit_signal_args));
+        let zome = runtime
+            .call_data()
+            .map(|call_data| call_data.zome_name)
+            .unwrap_or_default();
+        let signal = Signal::User(UserSignal {
+            zome,
+            ..UserSignal::from(emit_signal_args)
+        });
         let _ = sender.send(signal).map_err(|err| {
             log_error!(
                 context,
@@ -106,7 +113,13 @@ pub mod tests {
         assert!(received.is_ok());
         let signal = received.unwrap();
         if let Signal::User(user_signal) = signal {
-            assert_eq!(user_signal, test_signal());
+            assert_eq!(
+                user_signal,
+                UserSignal {
+                    zome: test_zome_name(),
+                    ..test_signal()
+                }
+            );
         } else {
             assert!(false, "Expected a Signal::User");
         }
@@ -0,0 +1,38 @@
+use crate::wasm_engine::{api::ZomeApiResult, Runtime};
+use holochain_core_types::error::HolochainError;
+use holochain_persistence_api::cas::content::Address;
+use holochain_wasm_utils::api_serialization::entry_holders::EntryHoldersResult;
+use std::convert::TryFrom;
+use wasmi::{RuntimeArgs, RuntimeValue};
+
+/// ZomeApiFunction::GetEntryHolders function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected Address argument
+/// Returns the peers that reported holding this agent's entry at the given address
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn invoke_get_entry_holders(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    // deserialize args
+    let args_str = runtime.load_json_string_from_args(&args);
+    let address = match Address::try_from(args_str.clone()) {
+        Ok(address) => address,
+        Err(_) => {
+            log_error!(
+                context,
+                "zome: invoke_get_entry_holders failed to deserialize Address: {:?}",
+                args_str
+            );
+            return ribosome_error_code!(ArgumentDeserializationFailed);
+        }
+    };
+
+    let result = context
+        .state()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Could not get state".to_string()))
+        .map(|state| {
+            let holders = state.agent().entry_holders(&address);
+            EntryHoldersResult::new(address, holders)
+        });
+
+    runtime.store_result(result)
+}
@@ -9,7 +9,6 @@ use holochain_json_api::json::JsonString;
 use holochain_wasm_utils::api_serialization::keystore::KeystoreListResult;
 use jsonrpc_lite::JsonRpc;
 use serde_json::{self, Value};
-use snowflake::ProcessUniqueId;
 use std::sync::Arc;
 use wasmi::{RuntimeArgs, RuntimeValue};
 
@@ -24,7 +23,7 @@ fn conductor_callback<S: Into<String>>(
     let handler = conductor_api.get().write().unwrap();
 
     let method = method.into();
-    let id = ProcessUniqueId::new();
+    let id = context.id_generator.next_id();
     let request = format!(
         r#"{{"jsonrpc": "2.0", "method": "{}", "params": {}, "id": "{}"}}"#,
         method,
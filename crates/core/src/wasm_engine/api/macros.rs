@@ -17,7 +17,7 @@ macro_rules! link_zome_api {
         /// Enumeration of all the Zome Functions known and usable in Zomes.
         /// Enumeration can convert to str.
         #[repr(usize)]
-        #[derive(FromPrimitive, Clone, Hash, Debug, PartialEq, Eq, Serialize)]
+        #[derive(FromPrimitive, Clone, Hash, Debug, PartialEq, Eq, Serialize, Deserialize)]
         pub enum ZomeApiFunction {
             /// Error index for unimplemented functions
             MissingNo = 0,
@@ -77,12 +77,15 @@ macro_rules! link_zome_api {
                     ZomeApiFunction::Abort => ribosome_success!(),
                     $( ZomeApiFunction::$enum_variant => {
                         if let Ok(context) = runtime.context() {
+                            let _frame = context.profiler.frame(|| format!("hdk {}", self.as_str()));
                             if let WasmCallData::ZomeCall(zome_call_data) = runtime.data.clone() {
                                 let zome_api_call = zome_call_data.call;
                                 let parameters = runtime.load_json_string_from_args(&args);
                                 let hdk_fn_call = HdkFnCall { function: self.clone(), parameters };
                                 trace_invoke_hdk_function(zome_api_call.clone(), hdk_fn_call.clone(), &context);
+                                context.zome_call_watchdog.begin_hdk_call(&zome_api_call, self.clone());
                                 let result = $function_name(runtime, args);
+                                context.zome_call_watchdog.end_hdk_call(&zome_api_call);
                                 let hdk_fn_result = Ok(JsonString::from("TODO"));
                                 trace_return_hdk_function(zome_api_call.clone(), hdk_fn_call, hdk_fn_result, &context);
                                 result
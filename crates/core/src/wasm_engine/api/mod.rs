@@ -2,10 +2,12 @@
 //! ZomeApiFunctions are the functions provided by the ribosome that are callable by Zomes.
 
 pub mod call;
+pub mod chain_head;
 pub mod commit;
 pub mod debug;
 pub mod emit_signal;
 pub mod entry_address;
+pub mod entry_holders;
 pub mod get_entry;
 pub mod get_links;
 pub mod get_links_count;
@@ -23,18 +25,25 @@ pub mod remove_link;
 pub mod send;
 pub mod sign;
 pub mod sleep;
+pub mod staging;
 pub mod update_entry;
 pub mod verify_signature;
 
 use crate::wasm_engine::{
     api::{
         call::invoke_call,
-        capabilities::{invoke_commit_capability_claim, invoke_commit_capability_grant},
+        capabilities::{
+            invoke_commit_capability_claim, invoke_commit_capability_grant,
+            invoke_delegate_capability, invoke_get_capability_audit_log,
+            invoke_revoke_capability_grant, invoke_rotate_capability_grant,
+        },
+        chain_head::invoke_get_chain_head,
         commit::invoke_commit_app_entry,
         crypto::invoke_crypto,
         debug::invoke_debug,
         emit_signal::invoke_emit_signal,
         entry_address::invoke_entry_address,
+        entry_holders::invoke_get_entry_holders,
         get_entry::invoke_get_entry,
         get_links::invoke_get_links,
         get_links_count::invoke_get_links_count,
@@ -52,7 +61,8 @@ use crate::wasm_engine::{
         send::invoke_send,
         sign::invoke_sign_one_time,
         sleep::invoke_sleep,
-        update_entry::invoke_update_entry,
+        staging::invoke_staging,
+        update_entry::{invoke_update_agent, invoke_update_entry},
         verify_signature::invoke_verify_signature,
     },
     runtime::Runtime,
@@ -154,6 +164,30 @@ link_zome_api! {
 
     ///send a meta
     "hc_meta",Meta,invoke_meta;
+
+    /// Get the peers that reported holding one of this agent's entries
+    "hc_get_entry_holders", GetEntryHolders, invoke_get_entry_holders;
+
+    /// Get the address, length and last commit time of this agent's source chain
+    "hc_get_chain_head", GetChainHead, invoke_get_chain_head;
+
+    /// Stage entries without chaining them, then commit or discard the staging set
+    "hc_staging", Staging, invoke_staging;
+
+    /// Commit an updated nickname and/or profile metadata for this agent's identity entry
+    "hc_update_agent", UpdateAgent, invoke_update_agent;
+
+    /// Revoke a capability grant and tell its assignees the token is no longer valid
+    "hc_revoke_capability_grant", RevokeCapabilityGrant, invoke_revoke_capability_grant;
+
+    /// Replace a capability grant by one with a new token, revoking the old one after a grace period
+    "hc_rotate_capability_grant", RotateCapabilityGrant, invoke_rotate_capability_grant;
+
+    /// Mint a narrower sub-token from a delegatable capability grant
+    "hc_delegate_capability", DelegateCapability, invoke_delegate_capability;
+
+    /// Get the recorded calls made to this instance with capability tokens
+    "hc_get_capability_audit_log", GetCapabilityAuditLog, invoke_get_capability_audit_log;
 }
 
 #[cfg(test)]
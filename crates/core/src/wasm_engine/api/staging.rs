@@ -0,0 +1,46 @@
+use crate::{
+    wasm_engine::{api::ZomeApiResult, Runtime},
+    workflows::staging::{commit_staged_workflow, discard_staged_workflow, stage_entry_workflow},
+};
+use holochain_wasm_utils::api_serialization::staging::{StagingArgs, StagingResult};
+use std::convert::TryFrom;
+use wasmi::{RuntimeArgs, RuntimeValue};
+
+/// ZomeApiFunction::Staging function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected complex argument: StagingArgs
+/// Returns the addresses of the entries that got staged, committed or discarded
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn invoke_staging(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+
+    // deserialize args
+    let args_str = runtime.load_json_string_from_args(&args);
+    let staging_args = match StagingArgs::try_from(args_str.clone()) {
+        Ok(staging_args) => staging_args,
+        Err(error) => {
+            log_error!(
+                context,
+                "zome: invoke_staging failed to deserialize StagingArgs: {:?} with error {:?}",
+                args_str,
+                error
+            );
+            return ribosome_error_code!(ArgumentDeserializationFailed);
+        }
+    };
+
+    let result = match staging_args {
+        StagingArgs::Stage(commit_entry_args) => context
+            .block_on(stage_entry_workflow(
+                &commit_entry_args.entry(),
+                None,
+                &context,
+                &commit_entry_args.options().provenance(),
+            ))
+            .map(|address| vec![address]),
+        StagingArgs::Commit => context.block_on(commit_staged_workflow(&context)),
+        StagingArgs::Discard => context.block_on(discard_staged_workflow(&context)),
+    };
+
+    runtime.store_result(result.map(StagingResult::new))
+}
@@ -2,11 +2,11 @@ use crate::{
     wasm_engine::{api::ZomeApiResult, Runtime},
     workflows::{author_entry::author_entry, get_entry_result::get_entry_result_workflow},
 };
-use holochain_core_types::error::HolochainError;
+use holochain_core_types::{entry::Entry, error::HolochainError};
 
 use holochain_persistence_api::cas::content::{Address, AddressableContent};
 
-use holochain_wasm_utils::api_serialization::{get_entry::*, UpdateEntryArgs};
+use holochain_wasm_utils::api_serialization::{get_entry::*, UpdateAgentArgs, UpdateEntryArgs};
 use std::convert::TryFrom;
 use wasmi::{RuntimeArgs, RuntimeValue};
 
@@ -62,3 +62,48 @@ pub fn invoke_update_entry(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApi
 
     runtime.store_result(res)
 }
+
+/// ZomeApiFunction::UpdateAgent function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected complex argument: UpdateAgentArgs
+/// Commits the next revision of the agent's identity entry, carrying the changed nickname and/or
+/// profile metadata, as an update of the latest revision. The signing key, and with it the agent
+/// address, stays the same so other agents resolve the latest identity info through the CRUD
+/// links starting at the address they know.
+/// Returns an HcApiReturnCode as I64
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn invoke_update_agent(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    // deserialize args
+    let args_str = runtime.load_json_string_from_args(&args);
+    let agent_args = match UpdateAgentArgs::try_from(args_str.clone()) {
+        Ok(agent_input) => agent_input,
+        // Exit on error
+        Err(_) => {
+            log_error!(
+                context,
+                "zome: invoke_update_agent failed to deserialize UpdateAgentArgs: {:?}",
+                args_str
+            );
+            return ribosome_error_code!(ArgumentDeserializationFailed);
+        }
+    };
+
+    let res: Result<Address, HolochainError> = context
+        .state()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Could not get state".to_string()))
+        .and_then(|state| state.agent().get_agent())
+        .and_then(|current_agent| {
+            let new_agent = current_agent.updated(agent_args.nick, agent_args.metadata);
+            context
+                .block_on(author_entry(
+                    &Entry::AgentId(new_agent),
+                    Some(Entry::AgentId(current_agent).address()),
+                    &context.clone(),
+                    &vec![],
+                ))
+                .map(|result| result.address())
+        });
+
+    runtime.store_result(res)
+}
@@ -1,5 +1,5 @@
 use crate::wasm_engine::{api::ZomeApiResult, Runtime};
-use holochain_dpki::utils::Verify;
+use holochain_dpki::audit::verify_audited;
 use holochain_wasm_utils::api_serialization::verify_signature::VerifySignatureArgs;
 use std::convert::TryFrom;
 use wasmi::{RuntimeArgs, RuntimeValue};
@@ -35,9 +35,12 @@ pub fn invoke_verify_signature(runtime: &mut Runtime, args: &RuntimeArgs) -> Zom
         verification_args.payload.clone()
     );
 
-    let verification_result = verification_args
-        .provenance
-        .verify(verification_args.payload.clone());
+    let verification_result = verify_audited(
+        &verification_args.provenance,
+        verification_args.payload.clone(),
+        "zome_api_verify_signature",
+        &format!("zome API call in instance {}", context.instance_name),
+    );
 
     runtime.store_result(verification_result)
 }
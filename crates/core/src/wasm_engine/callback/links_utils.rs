@@ -1,9 +1,13 @@
-use crate::{context::Context, workflows::get_entry_result::get_entry_result_workflow};
+use crate::{
+    agent::state::AgentState, context::Context,
+    workflows::get_entry_result::get_entry_result_workflow,
+};
 use holochain_core_types::{
     entry::{entry_type::EntryType, Entry},
     error::HolochainError,
     link::Link,
 };
+use holochain_persistence_api::cas::content::Address;
 use holochain_wasm_utils::api_serialization::{get_entry::*, validation::LinkDirection};
 use std::sync::Arc;
 
@@ -14,35 +18,46 @@ pub fn get_link_entries(
     link: &Link,
     context: &Arc<Context>,
 ) -> Result<(Entry, Entry), HolochainError> {
-    let base_address = link.base();
-    let target_address = link.target();
-    let entry_args = &GetEntryArgs {
-        address: base_address.clone(),
-        options: Default::default(),
-    };
-    let base_entry_get_result =
-        context.block_on(get_entry_result_workflow(&context, entry_args))?;
-    if !base_entry_get_result.found() {
-        return Err(HolochainError::ErrorGeneric(format!(
-            "Base for link not found: {:?}",
-            link
-        )));
+    let base_entry = get_link_entry(link, link.base(), "Base", None, context)?;
+    let target_entry = get_link_entry(link, link.target(), "Target", None, context)?;
+    Ok((base_entry, target_entry))
+}
+
+/// Like [get_link_entries], but also finds base and target among the entries of the given
+/// chain that are not committed yet, i.e. the earlier entries of the same batch.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn get_link_entries_on_chain(
+    link: &Link,
+    context: &Arc<Context>,
+    chain: &AgentState,
+) -> Result<(Entry, Entry), HolochainError> {
+    let base_entry = get_link_entry(link, link.base(), "Base", Some(chain), context)?;
+    let target_entry = get_link_entry(link, link.target(), "Target", Some(chain), context)?;
+    Ok((base_entry, target_entry))
+}
+
+fn get_link_entry(
+    link: &Link,
+    address: &Address,
+    role: &str,
+    chain: Option<&AgentState>,
+    context: &Arc<Context>,
+) -> Result<Entry, HolochainError> {
+    if let Some(entry) = chain.and_then(|chain| chain.uncommitted_entry(address)) {
+        return Ok(entry);
     }
-    let base_entry = base_entry_get_result.latest().unwrap();
     let entry_args = &GetEntryArgs {
-        address: target_address.clone(),
+        address: address.clone(),
         options: Default::default(),
     };
-    let target_entry_get_result =
-        context.block_on(get_entry_result_workflow(&context, entry_args))?;
-    if !target_entry_get_result.found() {
+    let entry_get_result = context.block_on(get_entry_result_workflow(&context, entry_args))?;
+    if !entry_get_result.found() {
         return Err(HolochainError::ErrorGeneric(format!(
-            "Target for link not found: {:?}",
-            link
+            "{} for link not found: {:?}",
+            role, link
         )));
     }
-
-    Ok((base_entry, target_entry_get_result.latest().unwrap()))
+    Ok(entry_get_result.latest().unwrap())
 }
 
 /// This is a "path" in the DNA tree.
@@ -0,0 +1,87 @@
+use crate::{
+    context::Context,
+    nucleus::CallbackFnCall,
+    wasm_engine::{
+        callback::{run_callback, Callback, CallbackParams, CallbackResult},
+        Defn,
+    },
+};
+use holochain_json_api::json::JsonString;
+use std::sync::Arc;
+
+/// Calls the zome's `migrate_chain` callback after the instance switched to a new DNA version.
+/// Zomes that don't define the callback get CallbackResult::NotImplemented.
+#[autotrace]
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn migrate_chain(
+    context: Arc<Context>,
+    zome: &str,
+    parameters: &CallbackParams,
+) -> CallbackResult {
+    let params = match parameters {
+        CallbackParams::MigrateChain(params) => params,
+        _ => return CallbackResult::NotImplemented("migrate_chain/1".into()),
+    };
+
+    if context.get_wasm(zome).is_none() {
+        return CallbackResult::NotImplemented("migrate_chain/2".into());
+    }
+
+    let call = CallbackFnCall::new(
+        zome,
+        &Callback::MigrateChain.as_str().to_string(),
+        JsonString::from(params),
+    );
+    run_callback(context, call)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::migrate_chain;
+    use crate::{
+        instance::tests::test_context,
+        wasm_engine::{
+            callback::{tests::test_callback_instance, Callback, CallbackParams, CallbackResult},
+            Defn,
+        },
+    };
+    use holochain_persistence_api::cas::content::Address;
+    use holochain_wasm_utils::api_serialization::migrate_chain::MigrateChainParams;
+
+    fn test_params() -> CallbackParams {
+        CallbackParams::MigrateChain(MigrateChainParams {
+            old_dna_address: Address::from("old dna"),
+            new_dna_address: Address::from("new dna"),
+        })
+    }
+
+    #[test]
+    fn pass() {
+        let zome = "test_zome";
+        let netname = Some("migrate_chain::pass");
+        let instance = test_callback_instance(zome, Callback::MigrateChain.as_str(), 0, netname)
+            .expect("Test callback instance could not be initialized");
+        let context = instance.initialize_context(test_context("test", netname));
+
+        let result = migrate_chain(context, zome, &test_params());
+
+        assert_eq!(CallbackResult::Pass, result);
+    }
+
+    #[test]
+    fn not_implemented() {
+        let zome = "test_zome";
+        let netname = Some("migrate_chain::not_implemented");
+        let instance = test_callback_instance(zome, Callback::Receive.as_str(), 0, netname)
+            .expect("Test callback instance could not be initialized");
+        let context = instance.initialize_context(test_context("test", netname));
+
+        let result = migrate_chain(context, zome, &test_params());
+
+        if let CallbackResult::NotImplemented(_) = result {
+            ()
+        } else {
+            panic!("unexpected result");
+        }
+    }
+}
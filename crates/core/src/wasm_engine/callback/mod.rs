@@ -3,6 +3,7 @@
 
 pub mod init;
 pub mod links_utils;
+pub mod migrate_chain;
 pub mod receive;
 pub mod validation_package;
 
@@ -11,7 +12,7 @@ use crate::{
     nucleus::CallbackFnCall,
     wasm_engine::{
         self,
-        callback::{init::init, receive::receive},
+        callback::{init::init, migrate_chain::migrate_chain, receive::receive},
         runtime::WasmCallData,
         Defn,
     },
@@ -26,7 +27,8 @@ use holochain_json_api::{
 };
 
 use holochain_wasm_utils::{
-    api_serialization::receive::ReceiveParams, memory::allocation::WasmAllocation,
+    api_serialization::{migrate_chain::MigrateChainParams, receive::ReceiveParams},
+    memory::allocation::WasmAllocation,
 };
 use num_traits::FromPrimitive;
 use serde_json;
@@ -47,6 +49,9 @@ pub enum Callback {
 
     /// receive(from: Address, message: String) -> String
     Receive,
+
+    /// migrate_chain(old_dna_address: Address, new_dna_address: Address) -> bool
+    MigrateChain,
 }
 
 impl FromStr for Callback {
@@ -55,6 +60,7 @@ impl FromStr for Callback {
         match s {
             "init" => Ok(Callback::Init),
             "receive" => Ok(Callback::Receive),
+            "migrate_chain" => Ok(Callback::MigrateChain),
             other if other.is_empty() => Ok(Callback::MissingNo),
             _ => Err("Cannot convert string to Callback"),
         }
@@ -78,6 +84,7 @@ impl Callback {
             // @TODO call this from somewhere
             // @see https://github.com/holochain/holochain-rust/issues/201
             Callback::Receive => receive,
+            Callback::MigrateChain => migrate_chain,
         }
     }
 }
@@ -89,6 +96,7 @@ impl Defn for Callback {
             Callback::MissingNo => "",
             Callback::Init => "init",
             Callback::Receive => "receive",
+            Callback::MigrateChain => "migrate_chain",
         }
     }
 
@@ -113,6 +121,7 @@ pub enum CallbackParams {
     Init,
     ValidateCommit(Entry),
     Receive(ReceiveParams),
+    MigrateChain(MigrateChainParams),
 }
 
 impl ToString for CallbackParams {
@@ -123,6 +132,7 @@ impl ToString for CallbackParams {
                 String::from(JsonString::from(serialized_entry.to_owned()))
             }
             CallbackParams::Receive(params) => JsonString::from(params).to_string(),
+            CallbackParams::MigrateChain(params) => JsonString::from(params).to_string(),
         }
     }
 }
@@ -317,6 +327,10 @@ pub mod tests {
             Callback::Receive,
             Callback::from_str("receive").expect("string literal should be valid callback")
         );
+        assert_eq!(
+            Callback::MigrateChain,
+            Callback::from_str("migrate_chain").expect("string literal should be valid callback")
+        );
 
         assert_eq!(
             "Cannot convert string to Callback",
@@ -331,12 +345,13 @@ pub mod tests {
             (Callback::MissingNo, ""),
             (Callback::Init, "init"),
             (Callback::Receive, "receive"),
+            (Callback::MigrateChain, "migrate_chain"),
         ] {
             assert_eq!(output, input.as_str());
         }
 
         // str_to_index()
-        for (input, output) in vec![("", 0), ("init", 1), ("receive", 2)] {
+        for (input, output) in vec![("", 0), ("init", 1), ("receive", 2), ("migrate_chain", 3)] {
             assert_eq!(output, Callback::str_to_index(input));
         }
 
@@ -345,6 +360,7 @@ pub mod tests {
             (0, Callback::MissingNo),
             (1, Callback::Init),
             (2, Callback::Receive),
+            (3, Callback::MigrateChain),
         ] {
             assert_eq!(output, Callback::from_index(input));
         }
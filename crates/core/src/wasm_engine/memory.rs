@@ -5,6 +5,8 @@ use holochain_wasm_utils::memory::{
 };
 use wasmi::{MemoryRef, ModuleRef};
 
+const WASM_PAGE_BYTES: usize = 64 * 1024;
+
 //--------------------------------------------------------------------------------------------------
 // WASM Memory Manager
 //--------------------------------------------------------------------------------------------------
@@ -48,6 +50,11 @@ impl WasmPageManager {
         }
     }
 
+    /// Current size of the module's memory
+    pub fn memory_bytes(&self) -> usize {
+        self.wasm_memory.current_size().0 * WASM_PAGE_BYTES
+    }
+
     /// Allocate on stack without writing in it
     pub fn allocate(&mut self, length: Length) -> AllocationResult {
         let allocation = self.stack.next_allocation(length)?;
@@ -69,7 +69,13 @@ pub fn run_dna(parameters: Option<Vec<u8>>, data: WasmCallData) -> ZomeFnResult
     let mut runtime = Runtime {
         memory_manager: WasmPageManager::new(&wasm_instance),
         data,
+        pending_commits: Default::default(),
     };
+    runtime.check_memory_limit()?;
+    let _frame = runtime
+        .context()
+        .ok()
+        .map(|context| context.profiler.frame(|| format!("wasm {}", fn_name)));
 
     // Write input arguments in wasm memory
     // scope for mutable borrow of runtime
@@ -104,14 +110,17 @@ pub fn run_dna(parameters: Option<Vec<u8>>, data: WasmCallData) -> ZomeFnResult
         // invoke function in wasm instance
         // arguments are info for wasm on how to retrieve complex input arguments
         // which have been set in memory module
-        wasm_instance
-            .invoke_export(
-                &fn_name,
-                &[RuntimeValue::I64(
-                    encoded_allocation_of_input as RibosomeRuntimeBits,
-                )],
-                mut_runtime,
-            )
+        let invocation = wasm_instance.invoke_export(
+            &fn_name,
+            &[RuntimeValue::I64(
+                encoded_allocation_of_input as RibosomeRuntimeBits,
+            )],
+            mut_runtime,
+        );
+        // Entries the function committed last are still pending. They get committed even if
+        // the function failed afterwards, as they would have been without batching.
+        mut_runtime.commit_pending_entries()?;
+        invocation
             .map_err(|err| {
                 HolochainError::RibosomeFailed(format!(
                     "WASM invocation failed: {}. data = {:?}",
@@ -127,6 +136,7 @@ pub fn run_dna(parameters: Option<Vec<u8>>, data: WasmCallData) -> ZomeFnResult
                 ))
             })?
     };
+    runtime.check_memory_limit()?;
 
     // Handle result returned by called zome function
     let return_code = RibosomeEncodedValue::from(returned_encoding);
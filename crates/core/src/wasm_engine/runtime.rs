@@ -1,4 +1,5 @@
 use crate::{
+    agent::state::{AgentState, StagedEntry},
     context::Context,
     nucleus::{CallbackFnCall, ZomeFnCall},
     wasm_engine::{
@@ -6,6 +7,7 @@ use crate::{
         memory::WasmPageManager,
         Defn,
     },
+    workflows::author_entry::{commit_authored_entries, validate_authored_entry_on_chain},
 };
 use holochain_core_types::error::{
     HolochainError, RibosomeEncodedValue, RibosomeEncodingBits, RibosomeRuntimeBits,
@@ -13,6 +15,7 @@ use holochain_core_types::error::{
 };
 
 use holochain_json_api::json::JsonString;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
 
 use holochain_wasm_utils::memory::allocation::WasmAllocation;
 use std::{convert::TryFrom, fmt, sync::Arc};
@@ -51,6 +54,43 @@ impl fmt::Display for BadCallError {
 
 impl HostError for BadCallError {}
 
+#[derive(Debug)]
+struct ResourceLimitError(HolochainError);
+impl fmt::Display for ResourceLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl HostError for ResourceLimitError {}
+
+#[derive(Debug)]
+struct PendingCommitsError(HolochainError);
+impl fmt::Display for PendingCommitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Could not commit the entries of the zome call: {}",
+            self.0
+        )
+    }
+}
+
+impl HostError for PendingCommitsError {}
+
+/// How many entries of consecutive commit_entry calls get chained into one batch at most
+pub const MAX_PENDING_COMMITS: usize = 64;
+
+/// Entries that consecutive commit_entry calls of a zome function validated but did not
+/// commit yet. They get committed as one batch once the zome function calls any other zome
+/// API function, returns, or commits more than [MAX_PENDING_COMMITS] entries in a row.
+#[derive(Clone, Default)]
+pub struct PendingCommits {
+    entries: Vec<StagedEntry>,
+    /// The agent's chain with the pending entries appended, to validate the next one on
+    chain: Option<AgentState>,
+}
+
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 impl WasmCallData {
     pub fn new_zome_call(context: Arc<Context>, call: ZomeFnCall) -> Self {
@@ -103,6 +143,9 @@ pub struct Runtime {
 
     /// data to be made available to the function at runtime
     pub data: WasmCallData,
+
+    /// entries committed by the function that still have to be written to the chain
+    pub pending_commits: PendingCommits,
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
@@ -148,6 +191,28 @@ impl Runtime {
         }
     }
 
+    /// Fails once the zome's memory grew beyond the WASM memory limit of its instance.
+    /// Direct calls run without a context and aren't limited.
+    pub fn check_memory_limit(&self) -> Result<(), HolochainError> {
+        let max_bytes = match &self.data {
+            WasmCallData::ZomeCall(ref data) => data.context.resource_limits.max_wasm_memory_bytes,
+            WasmCallData::CallbackCall(ref data) => {
+                data.context.resource_limits.max_wasm_memory_bytes
+            }
+            WasmCallData::DirectCall(_, _) => None,
+        };
+        match max_bytes {
+            Some(max_bytes) if self.memory_manager.memory_bytes() > max_bytes => {
+                Err(HolochainError::ResourceLimitExceeded(format!(
+                    "the zome uses {} bytes of WASM memory, more than the limit of {} bytes",
+                    self.memory_manager.memory_bytes(),
+                    max_bytes
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn context(&self) -> Result<Arc<Context>, Trap> {
         match &self.data {
             WasmCallData::ZomeCall(ref data) => Ok(data.context.clone()),
@@ -159,6 +224,63 @@ impl Runtime {
         }
     }
 
+    /// Validates the entry on top of the chain plus the pending commits, like
+    /// [author_entry](crate::workflows::author_entry::author_entry) would, and adds it to the
+    /// pending commits.
+    pub fn add_pending_commit(
+        &mut self,
+        context: &Arc<Context>,
+        staged_entry: StagedEntry,
+    ) -> Result<Address, HolochainError> {
+        if self.pending_commits.entries.len() >= MAX_PENDING_COMMITS {
+            self.commit_pending_entries()?;
+        }
+        let chain = match self.pending_commits.chain.take() {
+            Some(chain) => chain,
+            None => (*context.state()?.agent()).clone(),
+        };
+        let (entry, maybe_link_update_delete, provenances) = &staged_entry;
+        let result = context
+            .block_on(validate_authored_entry_on_chain(
+                entry,
+                maybe_link_update_delete.clone(),
+                context,
+                provenances,
+                Some(&chain),
+            ))
+            .and_then(|_| chain.with_uncommitted_entry(&context.state()?, &staged_entry));
+        match result {
+            Ok(new_chain) => {
+                let address = entry.address();
+                self.pending_commits.chain = Some(new_chain);
+                self.pending_commits.entries.push(staged_entry);
+                Ok(address)
+            }
+            Err(error) => {
+                // Entries committed before stay pending, the next one gets validated on
+                // top of them
+                if !self.pending_commits.entries.is_empty() {
+                    self.pending_commits.chain = Some(chain);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Commits and publishes the pending commits as one batch.
+    pub fn commit_pending_entries(&mut self) -> Result<(), HolochainError> {
+        if self.pending_commits.entries.is_empty() {
+            return Ok(());
+        }
+        let context = self
+            .context()
+            .map_err(|trap| HolochainError::ErrorGeneric(trap.to_string()))?;
+        let pending_commits = std::mem::replace(&mut self.pending_commits, Default::default());
+        context
+            .block_on(commit_authored_entries(pending_commits.entries, &context))
+            .map(|_| ())
+    }
+
     /// Load a JsonString stored in wasm memory.
     /// Input RuntimeArgs should only have one input which is the encoded allocation holding
     /// the complex data as an utf8 string.
@@ -226,7 +348,14 @@ impl Runtime {
 // by implementing the Externals trait from Wasmi.
 impl Externals for Runtime {
     fn invoke_index(&mut self, index: usize, args: RuntimeArgs) -> ZomeApiResult {
+        self.check_memory_limit()
+            .map_err(|error| Trap::new(TrapKind::Host(Box::new(ResourceLimitError(error)))))?;
         let zf = ZomeApiFunction::from_index(index);
+        // Other functions may depend on the committed entries being on the chain
+        if zf != ZomeApiFunction::CommitAppEntry {
+            self.commit_pending_entries()
+                .map_err(|error| Trap::new(TrapKind::Host(Box::new(PendingCommitsError(error)))))?;
+        }
         match zf {
             ZomeApiFunction::MissingNo => panic!("unknown function index"),
             // convert the function to its callable form and call it with the given arguments
@@ -2,7 +2,10 @@ use crate::{
     context::{get_dna_and_agent, Context},
     instance::Instance,
     network::actions::initialize_network::initialize_network,
-    nucleus::actions::{call_init::call_init, initialize::initialize_chain},
+    nucleus::actions::{
+        call_init::call_init, initialize::initialize_chain, migrate_chain::call_migrate_chain,
+    },
+    workflows::migrate_chain::migrate_chain_workflow,
 };
 use holochain_core_types::{
     dna::Dna,
@@ -73,3 +76,21 @@ pub async fn initialize(
 
     Ok(instance_context)
 }
+
+/// Loading case of initialize() for an instance that is configured with a (possibly) newer
+/// DNA than the one found in its chain.
+/// Migrates the chain to the given DNA if needed, initializes the instance with it and then
+/// calls the migrate_chain callbacks, which can use the network like any zome call.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn initialize_with_dna_update(
+    instance: &Instance,
+    dna: Dna,
+    context: Arc<Context>,
+) -> HcResult<Arc<Context>> {
+    let maybe_migration = migrate_chain_workflow(dna.clone(), &context).await?;
+    let instance_context = initialize(instance, None, context).await?;
+    if let Some(params) = maybe_migration {
+        call_migrate_chain(&dna, params, &instance_context).await?;
+    }
+    Ok(instance_context)
+}
@@ -1,10 +1,15 @@
 use crate::{
-    agent::actions::commit::commit_entry,
+    agent::{
+        actions::commit::{commit_entries, commit_entry},
+        state::{AgentState, StagedEntry},
+    },
     context::Context,
-    entry::CanPublish,
+    entry::{validate_link_visibility, CanPublish},
     network::actions::{publish::publish, publish_header_entry::publish_header_entry},
     nucleus::{
-        actions::build_validation_package::build_validation_package,
+        actions::build_validation_package::{
+            build_validation_package, build_validation_package_on_chain,
+        },
         validation::{validate_entry, ValidationContext},
     },
 };
@@ -12,6 +17,7 @@ use crate::{
 use holochain_core_types::{
     entry::Entry,
     error::HolochainError,
+    link::Link,
     signature::Provenance,
     validation::{EntryLifecycle, ValidationData},
 };
@@ -20,7 +26,7 @@ use holochain_persistence_api::cas::content::{Address, AddressableContent};
 
 use holochain_wasm_utils::api_serialization::commit_entry::CommitEntryResult;
 
-use crate::wasm_engine::callback::links_utils::get_link_entries;
+use crate::wasm_engine::callback::links_utils::{get_link_entries, get_link_entries_on_chain};
 use std::{sync::Arc, vec::Vec};
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
@@ -39,16 +45,121 @@ pub async fn author_entry<'a>(
         entry
     );
 
-    // 0. If we are trying to author a link or link removal, make sure the linked entries exist:
+    // 0.-2. Make sure the entry is valid
+    validate_authored_entry(
+        entry,
+        maybe_link_update_delete.clone(),
+        context,
+        provenances,
+    )
+    .await?;
+
+    // 3. Commit the entry
+    log_debug!(
+        context,
+        "workflow/authoring_entry/{}: committing...",
+        address
+    );
+    let addr = commit_entry(entry.clone(), maybe_link_update_delete, &context).await?;
+    log_debug!(context, "workflow/authoring_entry/{}: committed", address);
+
+    // 4.-5. Publish the entry and its header
+    publish_authored_entry(entry, context).await?;
+
+    Ok(CommitEntryResult::new(addr))
+}
+
+/// Runs the validation an entry has to pass before it can get authored (steps 0 to 2 of
+/// [author_entry]) without committing or publishing it.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+#[allow(clippy::ptr_arg)]
+pub async fn validate_authored_entry<'a>(
+    entry: &'a Entry,
+    maybe_link_update_delete: Option<Address>,
+    context: &'a Arc<Context>,
+    provenances: &'a Vec<Provenance>,
+) -> Result<(), HolochainError> {
+    validate_authored_entry_on_chain(entry, maybe_link_update_delete, context, provenances, None)
+        .await
+}
+
+/// Validates entries that get committed together, in the given order.
+/// Each entry gets validated on top of the chain plus the entries before it, so entries may
+/// depend on earlier entries of the same batch, e.g. a link may point to one of them.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn validate_authored_entries(
+    entries: &[StagedEntry],
+    context: &Arc<Context>,
+) -> Result<(), HolochainError> {
+    let state = context.state()?;
+    let mut chain = (*state.agent()).clone();
+    for staged_entry in entries.iter() {
+        let (entry, maybe_link_update_delete, provenances) = staged_entry;
+        validate_authored_entry_on_chain(
+            entry,
+            maybe_link_update_delete.clone(),
+            context,
+            provenances,
+            Some(&chain),
+        )
+        .await?;
+        chain = chain.with_uncommitted_entry(&state, staged_entry)?;
+    }
+    Ok(())
+}
+
+/// The agent's chain with the given entries appended but not committed, to validate the
+/// entries that get committed after them on.
+pub fn chain_with_uncommitted_entries(
+    entries: &[StagedEntry],
+    context: &Arc<Context>,
+) -> Result<AgentState, HolochainError> {
+    let state = context.state()?;
+    entries
+        .iter()
+        .try_fold((*state.agent()).clone(), |chain, staged_entry| {
+            chain.with_uncommitted_entry(&state, staged_entry)
+        })
+}
+
+/// Like [validate_authored_entry], but on top of the given chain if there is one, see
+/// [chain_with_uncommitted_entries].
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+#[allow(clippy::ptr_arg)]
+pub async fn validate_authored_entry_on_chain<'a>(
+    entry: &'a Entry,
+    maybe_link_update_delete: Option<Address>,
+    context: &'a Arc<Context>,
+    provenances: &'a Vec<Provenance>,
+    chain: Option<&'a AgentState>,
+) -> Result<(), HolochainError> {
+    let address = entry.address();
+
+    // 0. If we are trying to author a link or link removal, make sure the linked entries exist
+    //    and that new links don't expose private entries:
+    let link_entries = |link: &Link| match chain {
+        Some(chain) => get_link_entries_on_chain(link, context, chain),
+        None => get_link_entries(link, context),
+    };
     if let Entry::LinkAdd(link_data) = entry {
-        get_link_entries(&link_data.link, context)?;
+        let (base, target) = link_entries(&link_data.link)?;
+        validate_link_visibility(&base, &target, context)?;
     }
     if let Entry::LinkRemove((link_data, _)) = entry {
-        get_link_entries(&link_data.link, context)?;
+        link_entries(&link_data.link)?;
     }
 
     // 1. Build the context needed for validation of the entry
-    let validation_package = build_validation_package(&entry, context.clone(), provenances)?;
+    let validation_package = match chain {
+        Some(chain) => {
+            build_validation_package_on_chain(&entry, context.clone(), provenances, chain)?
+        }
+        None => build_validation_package(&entry, context.clone(), provenances)?,
+    };
+    let validation_context = match chain {
+        Some(chain) => ValidationContext::AuthoringOnChain(chain.clone()),
+        None => ValidationContext::Authoring,
+    };
     let validation_data = ValidationData {
         package: validation_package,
         lifecycle: EntryLifecycle::Chain,
@@ -62,22 +173,40 @@ pub async fn author_entry<'a>(
     );
     validate_entry(
         entry.clone(),
-        maybe_link_update_delete.clone(),
+        maybe_link_update_delete,
         validation_data,
         &context,
-        ValidationContext::Authoring,
+        validation_context,
     )
     .await?;
     log_debug!(context, "worflow/authoring_entry {}: is valid!", address);
 
-    // 3. Commit the entry
-    log_debug!(
-        context,
-        "workflow/authoring_entry/{}: committing...",
-        address
-    );
-    let addr = commit_entry(entry.clone(), maybe_link_update_delete, &context).await?;
-    log_debug!(context, "workflow/authoring_entry/{}: committed", address);
+    Ok(())
+}
+
+/// Commits entries that passed [validate_authored_entries] as one batch and publishes them
+/// (steps 3 to 5 of [author_entry] for several entries at once).
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn commit_authored_entries(
+    entries: Vec<StagedEntry>,
+    context: &Arc<Context>,
+) -> Result<Vec<Address>, HolochainError> {
+    let committed: Vec<Entry> = entries.iter().map(|(entry, _, _)| entry.clone()).collect();
+    let addresses = commit_entries(entries, context).await?;
+    for entry in committed.iter() {
+        publish_authored_entry(entry, context).await?;
+    }
+    Ok(addresses)
+}
+
+/// Publishes a freshly committed entry and its header (steps 4 and 5 of [author_entry]).
+/// Private entries only get their header published.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn publish_authored_entry<'a>(
+    entry: &'a Entry,
+    context: &'a Arc<Context>,
+) -> Result<(), HolochainError> {
+    let address = entry.address();
 
     // 4. Publish the valid entry to DHT. This will call Hold to itself
     if entry.entry_type().can_publish(context) {
@@ -109,15 +238,18 @@ pub async fn author_entry<'a>(
         address
     );
 
-    Ok(CommitEntryResult::new(addr))
+    Ok(())
 }
+
 // TODO: Bring the old in-memory network up to speed and turn on this test again!
 
 #[cfg(test)]
 pub mod tests {
 
     use crate::{
+        full_consistency::FullConsistencyWaiter,
         holochain_wasm_utils::holochain_persistence_api::cas::content::AddressableContent,
+        network::test_utils::test_wat_refusing_deletes,
         nucleus::actions::{
             get_entry::get_entry_from_dht,
             tests::{instance_by_name, test_dna},
@@ -126,9 +258,11 @@ pub mod tests {
     };
     use holochain_core_types::{
         chain_header::ChainHeader,
-        entry::{test_entry_with_value, Entry},
+        entry::{deletion_entry::DeletionEntry, test_entry_with_value, Entry},
+        error::HolochainError,
     };
-    use std::{thread, time};
+    use std::time::Duration;
+    use test_utils::create_test_dna_with_wat;
 
     // TODO do this for all crate tests somehow
     #[allow(dead_code)]
@@ -152,6 +286,7 @@ pub mod tests {
         let netname = Some("test_commit_with_dht_publish, the network");
         let (_instance1, context1) = instance_by_name("jill", dna.clone(), netname.clone());
         let (_instance2, context2) = instance_by_name("jack", dna, netname);
+        let waiter = FullConsistencyWaiter::new(&[context1.clone(), context2.clone()]);
 
         let entry_address = context1
             .block_on(author_entry(
@@ -162,27 +297,49 @@ pub mod tests {
             ))
             .unwrap()
             .address();
-        thread::sleep(time::Duration::from_millis(1000));
-
-        let mut entry: Option<Entry> = None;
-        let mut tries = 0;
-        while entry.is_none() && tries < 10 {
-            tries = tries + 1;
-            {
-                entry = get_entry_from_dht(&context2, &entry_address)
-                    .expect("Could not retrieve entry from DHT");
-            }
-            println!("Try {}: {:?}", tries, entry);
-            if entry.is_none() {
-                thread::sleep(time::Duration::from_millis(1000));
-            }
-        }
+        waiter.wait(Duration::from_secs(10)).unwrap();
+
+        let entry = get_entry_from_dht(&context2, &entry_address)
+            .expect("Could not retrieve entry from DHT");
         assert_eq!(
             entry,
             Some(test_entry_with_value("{\"stuff\":\"test entry value\"}"))
         );
     }
 
+    #[test]
+    /// test that deleting an entry fails if the zome of the entry refuses its deletion
+    fn test_delete_refused_by_zome_fails_validation() {
+        let mut dna = create_test_dna_with_wat("test_zome", Some(&test_wat_refusing_deletes()));
+        dna.uuid = "test_delete_refused_by_zome_fails_validation".to_string();
+        let netname = Some("test_delete_refused_by_zome_fails_validation, the network");
+        let (_instance, context) = instance_by_name("jill", dna, netname);
+        let waiter = FullConsistencyWaiter::new(&[context.clone()]);
+
+        let entry = test_entry_with_value("{\"stuff\":\"protected entry\"}");
+        let entry_address = context
+            .block_on(author_entry(&entry, None, &context, &vec![]))
+            .unwrap()
+            .address();
+        waiter.wait(Duration::from_secs(10)).unwrap();
+
+        let deletion = Entry::Deletion(DeletionEntry::new(entry_address.clone()));
+        match context.block_on(author_entry(
+            &deletion,
+            Some(entry_address.clone()),
+            &context,
+            &vec![],
+        )) {
+            Err(HolochainError::ValidationFailed(_)) => (),
+            Err(error) => panic!("Expected the deletion to fail validation, got: {}", error),
+            Ok(_) => panic!("Deleting the protected entry should fail validation"),
+        }
+        assert_eq!(
+            get_entry_from_dht(&context, &entry_address).unwrap(),
+            Some(entry)
+        );
+    }
+
     #[test]
     /// test that the header of an entry can be retrieved directly by its hash by another agent connected
     /// via the in-memory network
@@ -192,6 +349,7 @@ pub mod tests {
         let netname = Some("test_commit_with_dht_publish_header_is_published, the network");
         let (_instance1, context1) = instance_by_name("jill", dna.clone(), netname);
         let (_instance2, context2) = instance_by_name("jack", dna, netname);
+        let waiter = FullConsistencyWaiter::new(&[context1.clone(), context2.clone()]);
 
         let entry_address = context1
             .block_on(author_entry(
@@ -202,8 +360,7 @@ pub mod tests {
             ))
             .unwrap()
             .address();
-
-        thread::sleep(time::Duration::from_millis(500));
+        waiter.wait(Duration::from_secs(10)).unwrap();
 
         // get the header from the top of Jill's chain
         let state = &context1.state().unwrap();
@@ -216,19 +373,8 @@ pub mod tests {
         let header_entry = Entry::ChainHeader(header);
 
         // try and load it by its address as Jack. This means it has been communicated over the mock network
-        let mut entry: Option<Entry> = None;
-        let mut tries = 0;
-        while entry.is_none() && tries < 10 {
-            tries = tries + 1;
-            {
-                entry = get_entry_from_dht(&context2, &header_entry.address())
-                    .expect("Could not retrieve entry from DHT");
-            }
-            println!("Try {}: {:?}", tries, entry);
-            if entry.is_none() {
-                thread::sleep(time::Duration::from_millis(1000));
-            }
-        }
+        let entry = get_entry_from_dht(&context2, &header_entry.address())
+            .expect("Could not retrieve entry from DHT");
         assert_eq!(entry, Some(header_entry),);
     }
 
@@ -241,6 +387,7 @@ pub mod tests {
         // the ordering of these is important. Jack will get Jills DNA and AgentId headers but not visa-versa
         let (_instance2, context2) = instance_by_name("jack", dna.clone(), netname);
         let (_instance1, context1) = instance_by_name("jill", dna.clone(), netname);
+        let waiter = FullConsistencyWaiter::new(&[context1.clone(), context2.clone()]);
 
         // Jill publishes an entry
         context1
@@ -252,7 +399,7 @@ pub mod tests {
             ))
             .unwrap()
             .address();
-        thread::sleep(time::Duration::from_millis(500));
+        waiter.wait(Duration::from_secs(10)).unwrap();
 
         // Jill publishes another entry
         context1
@@ -264,7 +411,7 @@ pub mod tests {
             ))
             .unwrap()
             .address();
-        thread::sleep(time::Duration::from_millis(500));
+        waiter.wait(Duration::from_secs(10)).unwrap();
 
         // collect Jills local chain
         let state = &context1.state().unwrap();
@@ -277,19 +424,8 @@ pub mod tests {
         let mut jack_headers: Vec<ChainHeader> = Vec::new();
         let mut next_header_addr = header.address();
         loop {
-            let mut entry: Option<Entry> = None;
-            let mut tries = 0;
-            while entry.is_none() && tries < 10 {
-                tries = tries + 1;
-                {
-                    entry = get_entry_from_dht(&context2, &next_header_addr)
-                        .expect("Could not retrieve entry from DHT");
-                }
-                println!("Try {}: {:?}", tries, entry);
-                if entry.is_none() {
-                    thread::sleep(time::Duration::from_millis(1000));
-                }
-            }
+            let entry = get_entry_from_dht(&context2, &next_header_addr)
+                .expect("Could not retrieve entry from DHT");
             if let Some(Entry::ChainHeader(header)) = entry {
                 jack_headers.push(header.clone());
                 if let Some(next_addr) = header.link() {
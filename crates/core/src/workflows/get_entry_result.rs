@@ -1,5 +1,6 @@
 use crate::{
     context::Context,
+    instance_activity::TimedWorkflow,
     network::{self, actions::query::QueryMethod, query::NetworkQueryResult},
     nucleus,
 };
@@ -95,6 +96,7 @@ pub async fn get_entry_result_workflow<'a>(
     context: &'a Arc<Context>,
     args: &'a GetEntryArgs,
 ) -> Result<GetEntryResult, HolochainError> {
+    let _timer = context.time_workflow(TimedWorkflow::GetEntry);
     // Setup
     let mut entry_result = GetEntryResult::new(args.options.status_request.clone(), None);
     let mut maybe_address = Some(args.address.clone());
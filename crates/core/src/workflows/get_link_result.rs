@@ -1,5 +1,6 @@
 use crate::{
     context::Context,
+    instance_activity::TimedWorkflow,
     network::{
         actions::query::{crud_status_from_link_args, query, QueryMethod},
         handler::query::get_links,
@@ -31,6 +32,7 @@ pub async fn get_link_result_workflow<'a>(
     context: &'a Arc<Context>,
     link_args: &'a GetLinksArgs,
 ) -> Result<GetLinksResult, HolochainError> {
+    let _timer = context.time_workflow(TimedWorkflow::GetLinks);
     let config = GetLinksQueryConfiguration {
         headers: link_args.options.headers,
         pagination: link_args.options.pagination.clone(),
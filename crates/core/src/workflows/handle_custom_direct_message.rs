@@ -9,10 +9,7 @@ use crate::{
 use holochain_core_types::error::HolochainError;
 use holochain_persistence_api::cas::content::Address;
 use holochain_wasm_utils::api_serialization::receive::ReceiveParams;
-use std::{
-    sync::Arc,
-    time::{Duration, SystemTime},
-};
+use std::{sync::Arc, time::Duration};
 
 const CUSTOM_DIRECT_MESSAGE_RESPONSE_TIMEOUT_MS: u64 = 60000;
 /// handles receiving a message from an api send call
@@ -59,7 +56,7 @@ pub async fn handle_custom_direct_message(
 
     // when sending back the response to given by the receive callback we have to timeout
     let timeout = (
-        SystemTime::now(),
+        context.clock.now(),
         Duration::from_millis(CUSTOM_DIRECT_MESSAGE_RESPONSE_TIMEOUT_MS),
     );
     let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
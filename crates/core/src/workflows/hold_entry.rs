@@ -1,6 +1,7 @@
 use crate::{
     context::Context,
     dht::actions::hold_aspect::hold_aspect,
+    instance_activity::TimedWorkflow,
     network::entry_with_header::EntryWithHeader,
     nucleus::validation::{validate_entry, ValidationContext},
 };
@@ -40,6 +41,7 @@ pub async fn hold_entry_workflow(
     entry_with_header: &EntryWithHeader,
     context: Arc<Context>,
 ) -> Result<(), HolochainError> {
+    let _timer = context.time_workflow(TimedWorkflow::HoldEntry);
     // 1. Get hold of validation package
     let maybe_validation_package = validation_package(&entry_with_header, context.clone())
         .await
@@ -144,7 +146,7 @@ pub mod tests {
         assert!(result.is_err());
         assert_eq!(
             result.err().unwrap(),
-            HolochainError::ValidationFailed(String::from("FAIL wat")),
+            HolochainError::ValidationFailed("FAIL wat".into()),
         );
     }
 }
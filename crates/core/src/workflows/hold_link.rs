@@ -1,6 +1,7 @@
 use crate::{
     context::Context,
     dht::actions::hold_aspect::hold_aspect,
+    instance_activity::TimedWorkflow,
     network::entry_with_header::EntryWithHeader,
     nucleus::validation::{process_validation_err, validate_entry, ValidationContext},
     workflows::{hold_entry::hold_content_aspect, validation_package},
@@ -22,6 +23,7 @@ pub async fn hold_link_workflow(
     entry_with_header: &EntryWithHeader,
     context: Arc<Context>,
 ) -> Result<(), HolochainError> {
+    let _timer = context.time_workflow(TimedWorkflow::HoldLink);
     let link_add = match &entry_with_header.entry {
         Entry::LinkAdd(link_add) => link_add,
         _ => Err(HolochainError::ErrorGeneric(
@@ -167,7 +169,7 @@ pub mod tests {
 
         assert_eq!(
             result.err().unwrap(),
-            HolochainError::ValidationFailed(String::from("FAIL wat")),
+            HolochainError::ValidationFailed("FAIL wat".into()),
         );
     }
 }
@@ -0,0 +1,143 @@
+use crate::{
+    agent::actions::commit::commit_entry,
+    context::Context,
+    nucleus::{actions::migrate_chain::update_dna, validation::dna_entry::validate_dna_update},
+};
+
+use holochain_core_types::{
+    chain_migrate::ChainMigrate,
+    dna::Dna,
+    entry::Entry,
+    error::{HcResult, HolochainError},
+};
+use holochain_persistence_api::cas::content::AddressableContent;
+use holochain_wasm_utils::api_serialization::migrate_chain::MigrateChainParams;
+use std::sync::Arc;
+
+/// Moves a loaded chain over to a new version of its DNA.
+///
+/// 0. Checks that the agent holds an admin grant and that changed properties match their
+///    declared hash, before anything changes
+/// 1. Swaps the DNA in the nucleus so the new zome code is used from here on
+/// 2. Commits the new DNA as an update of the old one, which is what makes DNA validation
+///    accept it after the start of the chain, and a ChainMigrate entry linking the old and
+///    new DNA addresses, so the DNA gets picked up from the chain on the next load
+///
+/// Returns the parameters for the migrate_chain callbacks, or None if the chain already runs
+/// on the given DNA. The callbacks are not called here because they may commit and publish
+/// entries, which needs the network to be initialized with the new DNA first.
+/// See application::initialize_with_dna_update.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn migrate_chain_workflow(
+    new_dna: Dna,
+    context: &Arc<Context>,
+) -> HcResult<Option<MigrateChainParams>> {
+    let old_dna = context
+        .state()
+        .and_then(|state| state.nucleus().dna())
+        .ok_or(HolochainError::DnaMissing)?;
+    let old_dna_address = old_dna.address();
+    let new_dna_address = new_dna.address();
+    if old_dna_address == new_dna_address {
+        return Ok(None);
+    }
+    validate_dna_update(&new_dna, &old_dna, context)?;
+
+    log_info!(
+        context,
+        "workflow/migrate_chain: migrating chain from DNA {} to {}",
+        old_dna_address,
+        new_dna_address
+    );
+
+    update_dna(new_dna.clone(), context).await?;
+    commit_entry(
+        Entry::Dna(Box::new(new_dna.clone())),
+        Some(old_dna_address.clone()),
+        context,
+    )
+    .await?;
+    commit_entry(
+        Entry::ChainMigrate(ChainMigrate::new(
+            old_dna_address.clone(),
+            new_dna_address.clone(),
+        )),
+        None,
+        context,
+    )
+    .await?;
+
+    Ok(Some(MigrateChainParams {
+        old_dna_address,
+        new_dna_address,
+    }))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::nucleus::{
+        actions::tests::{instance_by_name, test_dna},
+        validation::dna_entry::tests::{commit_admin_grant, with_admin_grantor},
+    };
+    use holochain_core_types::entry::entry_type::EntryType;
+
+    #[test]
+    fn migrate_chain_records_migration_entry() {
+        let mut dna = with_admin_grantor(test_dna());
+        dna.uuid = "migrate_chain_records_migration_entry".to_string();
+        let netname = Some("migrate_chain_records_migration_entry");
+        let (_instance, context) = instance_by_name("jill", dna.clone(), netname);
+
+        assert_eq!(
+            None,
+            context
+                .block_on(migrate_chain_workflow(dna.clone(), &context))
+                .unwrap()
+        );
+
+        let mut new_dna = dna.clone();
+        new_dna.uuid = "migrate_chain_records_migration_entry v2".to_string();
+
+        // Without an admin grant the chain stays on its DNA
+        assert!(context
+            .block_on(migrate_chain_workflow(new_dna.clone(), &context))
+            .is_err());
+        assert_eq!(
+            Some(dna.address()),
+            context
+                .state()
+                .unwrap()
+                .nucleus()
+                .dna()
+                .map(|dna| dna.address())
+        );
+
+        commit_admin_grant(&context);
+        let params = context
+            .block_on(migrate_chain_workflow(new_dna.clone(), &context))
+            .unwrap()
+            .expect("the chain should have been migrated");
+        assert_eq!(dna.address(), params.old_dna_address);
+        assert_eq!(new_dna.address(), params.new_dna_address);
+
+        let state = context.state().unwrap();
+        assert_eq!(
+            Some(new_dna.address()),
+            state.nucleus().dna().map(|dna| dna.address())
+        );
+        let agent_state = state.agent();
+        let top_header = agent_state.top_chain_header().unwrap();
+        assert_eq!(&EntryType::ChainMigrate, top_header.entry_type());
+        assert_eq!(
+            Some(Entry::ChainMigrate(ChainMigrate::new(
+                dna.address(),
+                new_dna.address()
+            ))),
+            agent_state
+                .chain_store()
+                .get(top_header.entry_address())
+                .unwrap()
+        );
+    }
+}
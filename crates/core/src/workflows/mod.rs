@@ -19,13 +19,26 @@ pub mod hold_entry_update;
 #[autotrace]
 pub mod hold_link;
 #[autotrace]
+pub mod migrate_chain;
+#[autotrace]
 pub mod remove_link;
 #[autotrace]
 pub mod respond_validation_package_request;
+#[autotrace]
+pub mod revalidate_held;
+#[autotrace]
+pub mod revoke_grant;
+#[autotrace]
+pub mod rotate_grant;
+#[autotrace]
+pub mod staging;
 
 use crate::{
+    causal_log::with_cause,
+    consistency::{emit_consistency_signal, holding_workflow_signal},
     context::Context,
     dht::pending_validations::{PendingValidation, ValidatingWorkflow},
+    event_log::{record_instance_event, EVENT_VALIDATION_FAILED},
     network::{
         actions::get_validation_package::get_validation_package, entry_with_header::EntryWithHeader,
     },
@@ -33,6 +46,7 @@ use crate::{
         actions::build_validation_package::build_validation_package,
         validation::build_from_dht::try_make_validation_package_dht,
     },
+    profiler::profiled,
     wasm_engine::callback::{
         validation_package::get_validation_package_definition, CallbackResult,
     },
@@ -46,8 +60,10 @@ use holochain_core_types::{
     error::HolochainError,
     validation::{ValidationPackage, ValidationPackageDefinition},
 };
+use holochain_metrics::prometheus;
 use holochain_persistence_api::cas::content::AddressableContent;
-use std::sync::Arc;
+use serde_json::json;
+use std::{sync::Arc, time::Instant};
 
 /// Try to create a ValidationPackage for the given entry without calling out to some other node.
 /// I.e. either create it just from/with the header if `ValidationPackageDefinition` is `Entry`,
@@ -285,21 +301,93 @@ pub async fn run_holding_workflow(
     pending: PendingValidation,
     context: Arc<Context>,
 ) -> Result<(), HolochainError> {
-    match pending.workflow {
-        ValidatingWorkflow::HoldLink => {
-            hold_link_workflow(&pending.uuid, &pending.entry_with_header, context.clone()).await
+    // Continues the trace of the node that sent us the aspect, if it came with one
+    let followed = pending
+        .trace
+        .clone()
+        .and_then(|trace| ht::SpanWrap::from(trace).follower(&context.tracer, "holding workflow"));
+    let span = match followed {
+        Some(mut span) => {
+            span.event(format!("workflow: {}", pending.workflow));
+            span.event(format!("pending_validation: {}", pending.uuid));
+            span
         }
-        ValidatingWorkflow::HoldEntry => {
-            hold_entry_workflow(&pending.uuid, &pending.entry_with_header, context.clone()).await
-        }
-        ValidatingWorkflow::RemoveLink => {
-            remove_link_workflow(&pending.uuid, &pending.entry_with_header, context.clone()).await
-        }
-        ValidatingWorkflow::UpdateEntry => {
-            hold_update_workflow(&pending.uuid, &pending.entry_with_header, context.clone()).await
+        None => context
+            .tracer
+            .span("holding workflow")
+            .tag(ht::Tag::new("workflow", pending.workflow.to_string()))
+            .tag(ht::Tag::new("pending_validation", pending.uuid.to_string()))
+            .start()
+            .into(),
+    };
+    let _spanguard = ht::push_span(span);
+    let started = Instant::now();
+    let queued = context
+        .causal_log
+        .find("QueueHoldingWorkflow", &pending.uuid.to_string())
+        .map(|entry| entry.id);
+    let cause = context.causal_log.record_cause(
+        context.clock.iso8601_now(),
+        queued,
+        format!("holding workflow {}", pending.workflow),
+        Some(pending.entry_with_header.entry.address().to_string()),
+    );
+    let workflow = with_cause(Some(cause), async {
+        match pending.workflow {
+            ValidatingWorkflow::HoldLink => {
+                hold_link_workflow(&pending.uuid, &pending.entry_with_header, context.clone()).await
+            }
+            ValidatingWorkflow::HoldEntry => {
+                hold_entry_workflow(&pending.uuid, &pending.entry_with_header, context.clone())
+                    .await
+            }
+            ValidatingWorkflow::RemoveLink => {
+                remove_link_workflow(&pending.uuid, &pending.entry_with_header, context.clone())
+                    .await
+            }
+            ValidatingWorkflow::UpdateEntry => {
+                hold_update_workflow(&pending.uuid, &pending.entry_with_header, context.clone())
+                    .await
+            }
+            ValidatingWorkflow::RemoveEntry => {
+                hold_remove_workflow(&pending.uuid, &pending.entry_with_header, context.clone())
+                    .await
+            }
         }
-        ValidatingWorkflow::RemoveEntry => {
-            hold_remove_workflow(&pending.uuid, &pending.entry_with_header, context.clone()).await
+    });
+    let frame = format!("holding_workflow {}", pending.workflow);
+    let result = profiled(&context.profiler, frame, workflow).await;
+    prometheus::observe(
+        "holochain_holding_duration_seconds",
+        "Time it took to validate and hold what the DHT gave the instance",
+        &[
+            ("instance", &context.get_instance_name()),
+            ("workflow", &pending.workflow.to_string()),
+            ("result", if result.is_ok() { "ok" } else { "error" }),
+        ],
+        prometheus::seconds(started.elapsed()),
+    );
+    if let Err(HolochainError::ValidationFailed(ref reason)) = result {
+        if let Err(e) = record_instance_event(
+            &context,
+            EVENT_VALIDATION_FAILED,
+            json!({
+                "address": pending.entry_with_header.entry.address(),
+                "workflow": pending.workflow.to_string(),
+                "reason": reason.message,
+                "code": reason.code,
+            }),
+        ) {
+            log_error!(
+                context,
+                "workflow/run_holding_workflow: could not record event: {:?}",
+                e
+            );
         }
     }
+    emit_consistency_signal(
+        &context,
+        holding_workflow_signal(pending.entry_with_header.entry.address(), &result),
+    );
+    result
 }
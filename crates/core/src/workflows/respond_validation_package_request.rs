@@ -13,11 +13,7 @@ use crate::{
 
 use holochain_core_types::signature::Provenance;
 use holochain_persistence_api::cas::content::Address;
-use std::{
-    sync::Arc,
-    time::{Duration, SystemTime},
-    vec::Vec,
-};
+use std::{sync::Arc, time::Duration, vec::Vec};
 
 #[autotrace]
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
@@ -57,7 +53,7 @@ pub fn respond_validation_package_request(
 
     // when responding to a validation package request we have to timeout the direct message
     let timeout = (
-        SystemTime::now(),
+        context.clock.now(),
         Duration::from_millis(GET_VALIDATION_PACKAGE_MESSAGE_TIMEOUT_MS),
     );
     let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
@@ -0,0 +1,171 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    content_store::GetContent,
+    context::Context,
+    instance::dispatch_action,
+    network::entry_with_header::EntryWithHeader,
+    nucleus::validation::{validate_entry, ValidationContext, ValidationError},
+    workflows::validation_package,
+};
+use holochain_core_types::{
+    dna::Dna,
+    entry::entry_type::EntryType,
+    error::{HcResult, HolochainError},
+    validation::{EntryLifecycle, ValidationData},
+};
+use holochain_persistence_api::cas::content::Address;
+use std::{collections::HashSet, sync::Arc};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct RevalidationReport {
+    /// Number of held entries that got validated again
+    pub checked: usize,
+    /// Entries that are invalid under the new rules and are not held anymore
+    pub invalid: Vec<Address>,
+    /// Entries that couldn't be validated, e.g. because their validation package
+    /// couldn't be fetched. They stay held.
+    pub pending: Vec<Address>,
+}
+
+/// Entry types whose validation rules differ between the two DNAs: those of zomes whose code
+/// changed, those whose definition changed and those the new DNA doesn't define anymore.
+pub fn changed_entry_types(old_dna: &Dna, new_dna: &Dna) -> HashSet<EntryType> {
+    let mut changed = HashSet::new();
+    for (zome_name, old_zome) in &old_dna.zomes {
+        let new_zome = new_dna.zomes.get(zome_name);
+        for (entry_type, old_def) in &old_zome.entry_types {
+            let unchanged = new_zome
+                .map(|new_zome| {
+                    new_zome.code == old_zome.code
+                        && new_zome.entry_types.get(entry_type) == Some(old_def)
+                })
+                .unwrap_or(false);
+            if !unchanged {
+                changed.insert(entry_type.clone());
+            }
+        }
+    }
+    for (zome_name, new_zome) in &new_dna.zomes {
+        if !old_dna.zomes.contains_key(zome_name) {
+            changed.extend(new_zome.entry_types.keys().cloned());
+        }
+    }
+    changed
+}
+
+/// Validates the entries we hold for the DHT whose type is in `entry_types` again, with the
+/// DNA the instance runs now, and stops holding those that turn out to be invalid.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn revalidate_held_entries_workflow(
+    entry_types: &HashSet<EntryType>,
+    context: &Arc<Context>,
+) -> HcResult<RevalidationReport> {
+    let mut report = RevalidationReport::default();
+    if entry_types.is_empty() {
+        return Ok(report);
+    }
+    let state = context
+        .state()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Couldn't get instance state".to_string()))?;
+    let dht = state.dht();
+
+    for entry_hash in dht.get_holding_map().bare().keys() {
+        let address = Address::from(entry_hash.to_string());
+        let entry = match dht.get(&address) {
+            Ok(Some(entry)) => entry,
+            // Headers and other aspects that aren't entries
+            _ => continue,
+        };
+        if !entry_types.contains(&entry.entry_type()) {
+            continue;
+        }
+        let header = match dht.get_headers(address.clone())?.into_iter().next() {
+            Some(header) => header,
+            None => {
+                report.pending.push(address);
+                continue;
+            }
+        };
+        let entry_with_header = EntryWithHeader { entry, header };
+
+        let package = match validation_package(&entry_with_header, context.clone()).await {
+            Ok(Some(package)) => package,
+            _ => {
+                report.pending.push(address);
+                continue;
+            }
+        };
+        let validation_data = ValidationData {
+            package,
+            lifecycle: EntryLifecycle::Dht,
+        };
+        report.checked += 1;
+        match validate_entry(
+            entry_with_header.entry,
+            None,
+            validation_data,
+            context,
+            ValidationContext::Holding,
+        )
+        .await
+        {
+            Ok(()) => (),
+            Err(ValidationError::Fail(_)) | Err(ValidationError::NotImplemented) => {
+                report.invalid.push(address)
+            }
+            Err(_) => report.pending.push(address),
+        }
+    }
+
+    if !report.invalid.is_empty() {
+        log_warn!(
+            context,
+            "workflow/revalidate_held_entries: dropping {} entries that are invalid under the new DNA",
+            report.invalid.len()
+        );
+        dispatch_action(
+            context.action_channel(),
+            ActionWrapper::new(Action::DropHeldEntries(report.invalid.clone())),
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::nucleus::actions::tests::test_dna;
+    use holochain_core_types::{
+        dna::{entry_types::EntryTypeDef, wasm::DnaWasm},
+        entry::entry_type::AppEntryType,
+    };
+
+    #[test]
+    fn only_entry_types_with_new_rules_changed() {
+        let old_dna = test_dna();
+        assert!(changed_entry_types(&old_dna, &old_dna).is_empty());
+
+        let zome_name = old_dna.zomes.keys().next().unwrap().clone();
+        let mut new_dna = old_dna.clone();
+        let new_type = EntryType::App(AppEntryType::from("new_type"));
+        new_dna
+            .zomes
+            .get_mut(&zome_name)
+            .unwrap()
+            .entry_types
+            .insert(new_type.clone(), EntryTypeDef::new());
+        // Adding a type to an unchanged zome doesn't change the rules of the existing ones,
+        // and nothing of the new type can be held yet
+        assert!(changed_entry_types(&old_dna, &new_dna).is_empty());
+
+        new_dna.zomes.get_mut(&zome_name).unwrap().code = DnaWasm::from_bytes(vec![1, 2, 3]);
+        let changed = changed_entry_types(&old_dna, &new_dna);
+        let old_types: HashSet<EntryType> = old_dna.zomes[&zome_name]
+            .entry_types
+            .keys()
+            .cloned()
+            .collect();
+        assert!(!old_types.is_empty());
+        assert_eq!(changed, old_types);
+    }
+}
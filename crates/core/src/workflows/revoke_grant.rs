@@ -0,0 +1,113 @@
+use crate::{
+    agent::actions::commit::commit_entry,
+    context::Context,
+    network::actions::cap_token_revoked::send_cap_token_revoked,
+    nucleus::actions::call_zome_function::{get_grant, is_grant_revoked},
+};
+
+use holochain_core_types::{
+    entry::{cap_entries::CapTokenRevocation, Entry},
+    error::{HcResult, HolochainError},
+};
+use holochain_persistence_api::cas::content::Address;
+use std::sync::Arc;
+
+/// Revokes the capability grant with the given token.
+///
+/// 1. Commits a CapTokenRevocation entry, which takes the grant out of the set of grants
+///    incoming calls get checked against
+/// 2. Tells every assignee of the grant that the token is dead, so they can stop using it
+///    right away instead of finding out through failing calls
+///
+/// Returns the address of the revocation entry.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn revoke_grant_workflow(token: &Address, context: &Arc<Context>) -> HcResult<Address> {
+    if is_grant_revoked(context, token) {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Capability grant {} is already revoked",
+            token
+        )));
+    }
+    let grant = get_grant(context, token).ok_or_else(|| {
+        HolochainError::ErrorGeneric(format!("No capability grant found for token {}", token))
+    })?;
+
+    log_debug!(
+        context,
+        "workflow/revoke_grant: revoking capability grant {}",
+        token
+    );
+    let revocation_address = commit_entry(
+        Entry::CapTokenRevocation(CapTokenRevocation::new(token.clone())),
+        None,
+        context,
+    )
+    .await?;
+
+    for grantee in grant.assignees().unwrap_or_default() {
+        send_cap_token_revoked(grantee, token.clone(), context.clone());
+    }
+
+    Ok(revocation_address)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::nucleus::{
+        actions::{
+            call_zome_function::{check_capability, make_cap_request_for_call},
+            tests::{instance_by_name, test_dna},
+        },
+        ZomeFnCall,
+    };
+    use holochain_core_types::entry::cap_entries::{CapFunctions, CapTokenGrant, CapabilityType};
+    use holochain_persistence_api::cas::content::AddressableContent;
+
+    #[test]
+    fn revoked_grant_no_longer_passes_capability_check() {
+        let mut dna = test_dna();
+        dna.uuid = "revoked_grant_no_longer_passes_capability_check".to_string();
+        let netname = Some("revoked_grant_no_longer_passes_capability_check");
+        let (_instance, context) = instance_by_name("jill", dna, netname);
+
+        let mut cap_functions = CapFunctions::new();
+        cap_functions.insert("test_zome".to_string(), vec![String::from("test")]);
+        let grant =
+            CapTokenGrant::create("share", CapabilityType::Transferable, None, cap_functions)
+                .unwrap();
+        let token = context
+            .block_on(commit_entry(
+                Entry::CapTokenGrant(grant.clone()),
+                None,
+                &context,
+            ))
+            .unwrap();
+        assert_eq!(token, grant.token());
+
+        let call = ZomeFnCall::new(
+            "test_zome",
+            make_cap_request_for_call(context.clone(), token.clone(), "test", "{}"),
+            "test",
+            "{}",
+        );
+        assert!(check_capability(context.clone(), &call));
+
+        let revocation_address = context
+            .block_on(revoke_grant_workflow(&token, &context))
+            .unwrap();
+        assert_eq!(
+            Entry::CapTokenRevocation(CapTokenRevocation::new(token.clone())).address(),
+            revocation_address
+        );
+        assert!(is_grant_revoked(&context, &token));
+        assert!(!check_capability(context.clone(), &call));
+
+        assert!(context
+            .block_on(revoke_grant_workflow(&token, &context))
+            .is_err());
+        assert!(context
+            .block_on(revoke_grant_workflow(&Address::from("unknown"), &context))
+            .is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{
+    agent::actions::commit::commit_entries,
+    context::Context,
+    network::actions::cap_token_rotated::send_cap_token_rotated,
+    nucleus::actions::call_zome_function::{get_grant, is_grant_revoked},
+};
+
+use holochain_core_types::{
+    entry::{cap_entries::CapTokenRevocation, Entry},
+    error::{HcResult, HolochainError},
+    time::Iso8601,
+};
+use holochain_persistence_api::cas::content::Address;
+use std::{sync::Arc, time::Duration};
+
+/// Replaces the capability grant with the given token by a grant with the same scope
+/// but a new token.
+///
+/// 1. Commits the replacement grant together with a revocation of the old grant that only
+///    takes effect after the grace period, so both land on the chain or neither does
+/// 2. If asked to, sends the new token to every assignee of the grant, who will then use it
+///    in place of the old one
+///
+/// Returns the new token.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn rotate_grant_workflow(
+    token: &Address,
+    grace_period: Duration,
+    notify_assignees: bool,
+    context: &Arc<Context>,
+) -> HcResult<Address> {
+    if is_grant_revoked(context, token) {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Capability grant {} is revoked",
+            token
+        )));
+    }
+    let grant = get_grant(context, token).ok_or_else(|| {
+        HolochainError::ErrorGeneric(format!("No capability grant found for token {}", token))
+    })?;
+
+    let replacement = grant.clone().with_rotated_from(Some(token.clone()));
+    let new_token = replacement.token();
+    let effective_at = context.clock.since_epoch() + grace_period;
+    let revocation = CapTokenRevocation::new(token.clone())
+        .with_effective_at(Some(Iso8601::from(effective_at.as_secs())));
+
+    log_debug!(
+        context,
+        "workflow/rotate_grant: replacing capability grant {} with {}",
+        token,
+        new_token
+    );
+    commit_entries(
+        vec![
+            (Entry::CapTokenGrant(replacement), None, Vec::new()),
+            (Entry::CapTokenRevocation(revocation), None, Vec::new()),
+        ],
+        context,
+    )
+    .await?;
+
+    if notify_assignees {
+        for grantee in grant.assignees().unwrap_or_default() {
+            send_cap_token_rotated(grantee, token.clone(), new_token.clone(), context.clone());
+        }
+    }
+
+    Ok(new_token)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        agent::actions::commit::commit_entry,
+        nucleus::{
+            actions::{
+                call_zome_function::{check_capability, make_cap_request_for_call},
+                tests::{instance_by_name, test_dna},
+            },
+            ZomeFnCall,
+        },
+    };
+    use holochain_core_types::entry::cap_entries::{CapFunctions, CapTokenGrant, CapabilityType};
+
+    #[test]
+    fn rotated_grant_replaces_old_token_after_grace_period() {
+        let mut dna = test_dna();
+        dna.uuid = "rotated_grant_replaces_old_token_after_grace_period".to_string();
+        let netname = Some("rotated_grant_replaces_old_token_after_grace_period");
+        let (_instance, context) = instance_by_name("jill", dna, netname);
+
+        let mut cap_functions = CapFunctions::new();
+        cap_functions.insert("test_zome".to_string(), vec![String::from("test")]);
+        let grant =
+            CapTokenGrant::create("share", CapabilityType::Transferable, None, cap_functions)
+                .unwrap();
+        let token = context
+            .block_on(commit_entry(Entry::CapTokenGrant(grant), None, &context))
+            .unwrap();
+        let call = |token: &Address| {
+            ZomeFnCall::new(
+                "test_zome",
+                make_cap_request_for_call(context.clone(), token.clone(), "test", "{}"),
+                "test",
+                "{}",
+            )
+        };
+
+        // within the grace period both tokens work
+        let new_token = context
+            .block_on(rotate_grant_workflow(
+                &token,
+                Duration::from_secs(3600),
+                false,
+                &context,
+            ))
+            .unwrap();
+        assert_ne!(new_token, token);
+        assert_eq!(
+            get_grant(&context, &new_token).unwrap().rotated_from(),
+            Some(token.clone())
+        );
+        assert!(check_capability(context.clone(), &call(&token)));
+        assert!(check_capability(context.clone(), &call(&new_token)));
+
+        // without one the old token stops working right away
+        let newer_token = context
+            .block_on(rotate_grant_workflow(
+                &new_token,
+                Duration::from_secs(0),
+                false,
+                &context,
+            ))
+            .unwrap();
+        assert!(!check_capability(context.clone(), &call(&new_token)));
+        assert!(check_capability(context.clone(), &call(&newer_token)));
+        assert!(context
+            .block_on(rotate_grant_workflow(
+                &new_token,
+                Duration::from_secs(0),
+                false,
+                &context,
+            ))
+            .is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    agent::actions::staging::{stage_entry, take_staged_entries},
+    context::Context,
+    workflows::author_entry::{
+        chain_with_uncommitted_entries, commit_authored_entries, validate_authored_entries,
+        validate_authored_entry_on_chain,
+    },
+};
+
+use holochain_core_types::{entry::Entry, error::HolochainError, signature::Provenance};
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use std::sync::Arc;
+
+/// Validates the given entry like it would be validated when authored after the entries that
+/// are staged already, and puts it into the staging area without touching the source chain
+/// or the network.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+#[allow(clippy::ptr_arg)]
+pub async fn stage_entry_workflow<'a>(
+    entry: &'a Entry,
+    maybe_link_update_delete: Option<Address>,
+    context: &'a Arc<Context>,
+    provenances: &'a Vec<Provenance>,
+) -> Result<Address, HolochainError> {
+    log_debug!(context, "workflow/stage_entry: {}", entry.address());
+    let staged_entries = context.state()?.agent().staged_entries();
+    let chain = chain_with_uncommitted_entries(&staged_entries, context)?;
+    validate_authored_entry_on_chain(
+        entry,
+        maybe_link_update_delete.clone(),
+        context,
+        provenances,
+        Some(&chain),
+    )
+    .await?;
+    stage_entry(
+        entry.clone(),
+        maybe_link_update_delete,
+        provenances.clone(),
+        context,
+    )
+    .await
+}
+
+/// Commits all staged entries in the order they were staged and empties the staging area.
+/// Entries get validated again since the chain may have changed since staging, each one on
+/// top of the entries staged before it. Then they are chained together as one batch and
+/// published.
+/// If validation of any of them fails, nothing gets committed and the staging set is dropped.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn commit_staged_workflow(
+    context: &Arc<Context>,
+) -> Result<Vec<Address>, HolochainError> {
+    let staged_entries = take_staged_entries(context).await?;
+    log_debug!(
+        context,
+        "workflow/commit_staged: committing {} staged entries",
+        staged_entries.len()
+    );
+    validate_authored_entries(&staged_entries, context).await?;
+    commit_authored_entries(staged_entries, context).await
+}
+
+/// Empties the staging area without authoring anything.
+/// Returns the addresses of the discarded entries.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn discard_staged_workflow(
+    context: &Arc<Context>,
+) -> Result<Vec<Address>, HolochainError> {
+    let staged_entries = take_staged_entries(context).await?;
+    log_debug!(
+        context,
+        "workflow/discard_staged: discarding {} staged entries",
+        staged_entries.len()
+    );
+    Ok(staged_entries
+        .into_iter()
+        .map(|(entry, _, _)| entry.address())
+        .collect())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        instance::tests::test_instance_and_context, network::test_utils::test_wat_always_valid,
+    };
+    use holochain_core_types::{
+        agent::test_agent_id, chain_header::test_chain_header, entry::test_entry,
+        link::link_data::LinkData,
+    };
+    use test_utils::create_test_dna_with_wat;
+
+    #[test]
+    fn staged_link_can_point_to_an_entry_staged_before() {
+        let mut dna = create_test_dna_with_wat("test_zome", Some(&test_wat_always_valid()));
+        dna.uuid = String::from("staged_link_can_point_to_an_entry_staged_before");
+        let (_instance, context) = test_instance_and_context(dna, None).unwrap();
+
+        let entry = test_entry();
+        let link_entry = Entry::LinkAdd(LinkData::new_add(
+            &entry.address(),
+            &entry.address(),
+            "test-tag",
+            "test-link",
+            test_chain_header(),
+            test_agent_id(),
+        ));
+
+        context
+            .block_on(stage_entry_workflow(&entry, None, &context, &vec![]))
+            .unwrap();
+        // The link gets validated on top of the staged entry, which isn't committed yet
+        context
+            .block_on(stage_entry_workflow(&link_entry, None, &context, &vec![]))
+            .unwrap();
+
+        // Both get validated again when committing, the link after the entry
+        let addresses = context.block_on(commit_staged_workflow(&context)).unwrap();
+        assert_eq!(addresses, vec![entry.address(), link_entry.address()]);
+        let agent_state = context.state().unwrap().agent();
+        assert_eq!(
+            agent_state.top_chain_header().unwrap().entry_address(),
+            &link_entry.address()
+        );
+        assert!(agent_state.staged_entries().is_empty());
+    }
+}
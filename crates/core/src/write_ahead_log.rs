@@ -0,0 +1,247 @@
+//! Write-ahead log for actions whose reducers write to the storages.
+//!
+//! Reducers of commits, held aspects and holding queue changes write content and meta data
+//! to the CAS and EAV storages, but the state that references it only gets persisted with
+//! the snapshots after the reducer is done. A crash in between leaves the chain and DHT
+//! stores disagreeing with the snapshots. So before such an action gets reduced, the
+//! instance journals it as a [WalRecord] in the CAS, next to the snapshots, and clears the
+//! record again once the snapshots are saved. When the instance gets set up and finds a
+//! record left behind, it replays the action, unless the loaded state shows it was applied.
+use crate::{
+    action::{Action, ActionWrapper},
+    agent::state::StagedEntry,
+    context::Context,
+    dht::{
+        actions::remove_queued_holding_workflow::HoldingWorkflowQueueing,
+        pending_validations::PendingValidation,
+    },
+    state::StateWrapper,
+};
+use holochain_core_types::{error::HcResult, network::entry_aspect::EntryAspect};
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::cas::content::{Address, AddressableContent, Content};
+use snowflake::ProcessUniqueId;
+use std::{
+    convert::TryFrom,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+pub static WAL_ADDRESS: &str = "WriteAheadLog";
+
+/// A state changing action as it gets journaled, together with what is needed to tell
+/// whether it got applied already
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum JournaledAction {
+    Commit {
+        entry: StagedEntry,
+        /// Chain head the entry gets committed on top of
+        chain_head: Option<Address>,
+    },
+    CommitBatch {
+        entries: Vec<StagedEntry>,
+        chain_head: Option<Address>,
+    },
+    HoldAspect(EntryAspect),
+    QueueHoldingWorkflow((PendingValidation, Option<(SystemTime, Duration)>)),
+    RemoveQueuedHoldingWorkflow((HoldingWorkflowQueueing, PendingValidation)),
+}
+
+impl JournaledAction {
+    /// The journal entry for the given action, if it is one whose reducer writes to storage
+    pub fn from_action(action: &Action, state: &StateWrapper) -> Option<JournaledAction> {
+        let chain_head = || {
+            state
+                .agent()
+                .top_chain_header()
+                .map(|header| header.address())
+        };
+        match action {
+            Action::Commit(entry) => Some(JournaledAction::Commit {
+                entry: entry.clone(),
+                chain_head: chain_head(),
+            }),
+            Action::CommitBatch(entries) => Some(JournaledAction::CommitBatch {
+                entries: entries.clone(),
+                chain_head: chain_head(),
+            }),
+            Action::HoldAspect((aspect, _)) => Some(JournaledAction::HoldAspect(aspect.clone())),
+            Action::QueueHoldingWorkflow(queued) => {
+                Some(JournaledAction::QueueHoldingWorkflow(queued.clone()))
+            }
+            Action::RemoveQueuedHoldingWorkflow(removed) => Some(
+                JournaledAction::RemoveQueuedHoldingWorkflow(removed.clone()),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The action to replay, or None if the given state already has its effects
+    pub fn replay_action(&self, state: &StateWrapper) -> Option<Action> {
+        let chain_head = state
+            .agent()
+            .top_chain_header()
+            .map(|header| header.address());
+        let dht = state.dht();
+        match self {
+            JournaledAction::Commit {
+                entry,
+                chain_head: journaled_head,
+            } if *journaled_head == chain_head => Some(Action::Commit(entry.clone())),
+            JournaledAction::CommitBatch {
+                entries,
+                chain_head: journaled_head,
+            } if *journaled_head == chain_head => Some(Action::CommitBatch(entries.clone())),
+            JournaledAction::HoldAspect(aspect) if !dht.get_holding_map().contains(aspect) => {
+                // Whoever waited for this attempt is gone, so any new id will do
+                let attempt = (ProcessUniqueId::new(), ProcessUniqueId::new());
+                Some(Action::HoldAspect((aspect.clone(), attempt)))
+            }
+            // Both are no-ops for workflows that are already queued or moved on
+            JournaledAction::QueueHoldingWorkflow(queued) => {
+                Some(Action::QueueHoldingWorkflow(queued.clone()))
+            }
+            JournaledAction::RemoveQueuedHoldingWorkflow((queueing, pending)) => {
+                let present = match queueing {
+                    HoldingWorkflowQueueing::Processing => {
+                        dht.has_same_queued_holding_worfkow(pending)
+                    }
+                    _ => dht.has_same_in_process_holding_worfkow(pending),
+                };
+                if present {
+                    Some(Action::RemoveQueuedHoldingWorkflow((
+                        queueing.clone(),
+                        pending.clone(),
+                    )))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The journal, stored at a fixed address in the CAS like the state snapshots.
+/// Holds the action that is being reduced, or nothing between actions.
+#[derive(Clone, Debug, Serialize, Deserialize, DefaultJson)]
+pub struct WalRecord {
+    pub action: Option<JournaledAction>,
+}
+
+impl AddressableContent for WalRecord {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+
+    fn address(&self) -> Address {
+        WAL_ADDRESS.into()
+    }
+}
+
+/// Journals the given action before it gets reduced, if it is one that needs journaling.
+/// Returns whether it got journaled.
+pub fn journal_action(
+    context: &Arc<Context>,
+    action: &Action,
+    state: &StateWrapper,
+) -> HcResult<bool> {
+    match JournaledAction::from_action(action, state) {
+        Some(journaled) => {
+            write_record(context, Some(journaled))?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Clears the journal after the state with the journaled action's effects got saved
+pub fn clear_journal(context: &Arc<Context>) -> HcResult<()> {
+    write_record(context, None)
+}
+
+/// The action left in the journal by an instance that didn't get to save its state
+pub fn unfinished_action(context: &Arc<Context>) -> HcResult<Option<JournaledAction>> {
+    let content = context
+        .dht_storage
+        .read()?
+        .fetch(&Address::from(WAL_ADDRESS))?;
+    Ok(match content {
+        Some(content) => WalRecord::try_from_content(&content)?.action,
+        None => None,
+    })
+}
+
+/// The unfinished action, if there is one whose effects are missing in the given state.
+/// It gets wrapped for the instance to reduce it again.
+pub fn action_to_replay(
+    context: &Arc<Context>,
+    state: &StateWrapper,
+) -> HcResult<Option<ActionWrapper>> {
+    Ok(unfinished_action(context)?
+        .and_then(|journaled| journaled.replay_action(state))
+        .map(ActionWrapper::new))
+}
+
+fn write_record(context: &Arc<Context>, action: Option<JournaledAction>) -> HcResult<()> {
+    context.dht_storage.write()?.add(&WalRecord { action })?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{instance::tests::test_instance_and_context, nucleus::actions::tests::test_dna};
+    use holochain_core_types::{chain_header::test_chain_header, entry::test_entry_with_value};
+
+    #[test]
+    fn commits_replay_only_on_their_chain_head() {
+        let (_instance, context) = test_instance_and_context(test_dna(), None).unwrap();
+        let state = context.state().unwrap();
+        let entry = test_entry_with_value("{\"stuff\":\"test entry value\"}");
+        let commit = Action::Commit((entry, None, Vec::new()));
+
+        let journaled = JournaledAction::from_action(&commit, &state).unwrap();
+        assert_eq!(journaled.replay_action(&state), Some(commit));
+
+        let moved_on = JournaledAction::Commit {
+            entry: match journaled {
+                JournaledAction::Commit { entry, .. } => entry,
+                _ => unreachable!(),
+            },
+            chain_head: Some(Address::from("some older chain head")),
+        };
+        assert_eq!(moved_on.replay_action(&state), None);
+    }
+
+    #[test]
+    fn journal_round_trips_through_storage() {
+        let (_instance, context) = test_instance_and_context(test_dna(), None).unwrap();
+        let state = context.state().unwrap();
+        assert_eq!(unfinished_action(&context).unwrap(), None);
+
+        let aspect = EntryAspect::Content(
+            test_entry_with_value("{\"stuff\":\"held\"}"),
+            test_chain_header(),
+        );
+        let attempt = (ProcessUniqueId::new(), ProcessUniqueId::new());
+        let hold = Action::HoldAspect((aspect.clone(), attempt));
+        assert!(journal_action(&context, &hold, &state).unwrap());
+        assert_eq!(
+            unfinished_action(&context).unwrap(),
+            Some(JournaledAction::HoldAspect(aspect))
+        );
+        assert!(action_to_replay(&context, &state).unwrap().is_some());
+
+        clear_journal(&context).unwrap();
+        assert_eq!(unfinished_action(&context).unwrap(), None);
+        assert!(!journal_action(&context, &Action::Ping, &state).unwrap());
+    }
+}
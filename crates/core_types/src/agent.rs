@@ -22,6 +22,21 @@ pub struct AgentId {
     pub nick: String,
     /// the encoded public signing key of this agent (the magnifier)
     pub pub_sign_key: Base32,
+    /// optional public profile metadata (e.g. a JSON blob), set through agent updates.
+    /// Omitted from the serialized form when empty so the content of plain agent
+    /// entries stays unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+    /// optional proof of membership supplied at genesis (e.g. an invitation signed by a
+    /// network authority). It travels with the agent entry so that the app's agent
+    /// validation callback can reject uninvited agents on every node holding the entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub membrane_proof: Option<String>,
+    /// how many times this identity got updated, `None` for the entry committed at genesis.
+    /// Updated identities are stored under the hash of their content so that they don't
+    /// overwrite the original entry at the agent address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u32>,
     // TODO: Add the encoded public encrypting key (the safe / padlock)
     // pub pub_enc_key: Base32,
 }
@@ -51,6 +66,27 @@ impl AgentId {
         AgentId {
             nick: nick.to_string(),
             pub_sign_key: key_b32,
+            metadata: None,
+            membrane_proof: None,
+            revision: None,
+        }
+    }
+
+    /// Attach the membrane proof that gets committed with this agent's entry at genesis.
+    pub fn with_membrane_proof(mut self, membrane_proof: Option<String>) -> Self {
+        self.membrane_proof = membrane_proof;
+        self
+    }
+
+    /// Build the next revision of this agent's identity, keeping the signing key (and thus the
+    /// agent address) but replacing the nickname and/or profile metadata.
+    pub fn updated(&self, nick: Option<String>, metadata: Option<String>) -> Self {
+        AgentId {
+            nick: nick.unwrap_or_else(|| self.nick.clone()),
+            pub_sign_key: self.pub_sign_key.clone(),
+            metadata: metadata.or_else(|| self.metadata.clone()),
+            membrane_proof: self.membrane_proof.clone(),
+            revision: Some(self.revision.unwrap_or(0) + 1),
         }
     }
 
@@ -157,4 +193,42 @@ mod tests {
             AgentId::try_from_content(&expected_content).unwrap(),
         );
     }
+
+    #[test]
+    fn it_updates_profile_under_a_new_entry_address() {
+        let original = test_agent_id();
+        let updated = original.updated(Some("robert".to_string()), Some("{\"bio\":\"hi\"}".into()));
+        assert_eq!(updated.nick, "robert");
+        assert_eq!(updated.metadata, Some("{\"bio\":\"hi\"}".to_string()));
+        assert_eq!(updated.revision, Some(1));
+        assert_eq!(original.address(), updated.address());
+        assert_ne!(original.content(), updated.content());
+        assert_eq!(
+            Entry::AgentId(original.clone()).address(),
+            original.address()
+        );
+        assert_ne!(Entry::AgentId(updated.clone()).address(), updated.address());
+
+        let renamed_only = updated.updated(Some("bobby".to_string()), None);
+        assert_eq!(renamed_only.metadata, updated.metadata);
+        assert_eq!(renamed_only.revision, Some(2));
+        assert_eq!(
+            renamed_only,
+            AgentId::try_from_content(&renamed_only.content()).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_carries_membrane_proof_through_updates() {
+        let original = test_agent_id().with_membrane_proof(Some("invite-123".to_string()));
+        assert_eq!(original.membrane_proof, Some("invite-123".to_string()));
+        assert_eq!(test_agent_id().address(), original.address());
+        assert_eq!(
+            original,
+            AgentId::try_from_content(&original.content()).unwrap()
+        );
+
+        let updated = original.updated(Some("robert".to_string()), None);
+        assert_eq!(updated.membrane_proof, original.membrane_proof);
+    }
 }
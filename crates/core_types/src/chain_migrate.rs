@@ -1,4 +1,45 @@
-//! NOT YET AVAILABLE
+//! ChainMigrate entries mark the point in a source chain where an instance switched from one
+//! DNA version to another. Entries committed after it were authored under the new DNA.
 
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
-pub struct ChainMigrate {}
+use holochain_json_api::{error::JsonError, json::JsonString};
+use holochain_persistence_api::cas::content::Address;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, DefaultJson)]
+pub struct ChainMigrate {
+    old_dna_address: Address,
+    new_dna_address: Address,
+}
+
+impl ChainMigrate {
+    pub fn new(old_dna_address: Address, new_dna_address: Address) -> Self {
+        ChainMigrate {
+            old_dna_address,
+            new_dna_address,
+        }
+    }
+
+    pub fn old_dna_address(&self) -> &Address {
+        &self.old_dna_address
+    }
+
+    pub fn new_dna_address(&self) -> &Address {
+        &self.new_dna_address
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::entry::Entry;
+    use holochain_persistence_api::cas::content::AddressableContent;
+
+    #[test]
+    fn chain_migrate_entry_roundtrip() {
+        let migrate = ChainMigrate::new(Address::from("old dna"), Address::from("new dna"));
+        assert_eq!(&Address::from("old dna"), migrate.old_dna_address());
+        assert_eq!(&Address::from("new dna"), migrate.new_dna_address());
+
+        let entry = Entry::ChainMigrate(migrate.clone());
+        assert_eq!(entry, Entry::try_from_content(&entry.content()).unwrap());
+    }
+}
@@ -1,6 +1,10 @@
 /// capabilities implements the capability request functionality used to check
 /// that a given capability has been granted for actions like zome calls
-use crate::signature::{Provenance, Signature};
+use crate::{
+    entry::cap_entries::CapTokenDelegation,
+    signature::{Provenance, Signature},
+    time::Iso8601,
+};
 
 use holochain_json_api::{error::JsonError, json::JsonString};
 use holochain_persistence_api::cas::content::Address;
@@ -16,6 +20,10 @@ use holochain_persistence_api::cas::content::Address;
 pub struct CapabilityRequest {
     pub cap_token: Address,
     pub provenance: Provenance,
+    /// If the token is a delegated sub-token: the delegations leading from the original
+    /// grant to it, root first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delegation: Vec<CapTokenDelegation>,
 }
 
 impl CapabilityRequest {
@@ -23,6 +31,54 @@ impl CapabilityRequest {
         CapabilityRequest {
             cap_token: token,
             provenance: Provenance::new(requester, signature),
+            delegation: Vec::new(),
+        }
+    }
+
+    /// Returns the request with the chain of delegations for a delegated sub-token.
+    pub fn with_delegation(mut self, delegation: Vec<CapTokenDelegation>) -> Self {
+        self.delegation = delegation;
+        self
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// CapabilityUseRecord
+//--------------------------------------------------------------------------------------------------
+
+/// whether a capability-gated call passed the capability check
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum CapabilityCheckOutcome {
+    Granted,
+    Denied,
+}
+
+/// an entry of the capability audit log, recording one call made with a capability token
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash, DefaultJson)]
+pub struct CapabilityUseRecord {
+    pub token: Address,
+    pub caller: Address,
+    pub zome: String,
+    pub function: String,
+    pub timestamp: Iso8601,
+    pub outcome: CapabilityCheckOutcome,
+}
+
+impl CapabilityUseRecord {
+    pub fn new(
+        request: &CapabilityRequest,
+        zome: &str,
+        function: &str,
+        timestamp: Iso8601,
+        outcome: CapabilityCheckOutcome,
+    ) -> Self {
+        CapabilityUseRecord {
+            token: request.cap_token.clone(),
+            caller: request.provenance.source(),
+            zome: zome.to_string(),
+            function: function.to_string(),
+            timestamp,
+            outcome,
         }
     }
 }
@@ -43,6 +99,7 @@ pub mod tests {
             CapabilityRequest {
                 cap_token: Address::from("123"),
                 provenance: Provenance::new(Address::from("requester"), Signature::fake()),
+                delegation: Vec::new(),
             },
             cap_call
         );
@@ -42,7 +42,7 @@ use crate::{
     error::{DnaError, HcResult, HolochainError},
 };
 
-use holochain_persistence_api::cas::content::{AddressableContent, Content};
+use holochain_persistence_api::cas::content::{Address, AddressableContent, Content};
 
 use holochain_json_api::{
     error::{JsonError, JsonResult},
@@ -95,6 +95,12 @@ pub struct Dna {
     #[serde(default = "empty_object")]
     pub properties: Value,
 
+    /// Hash of the properties, see `properties_address()`. Whoever changes the properties
+    /// of a DNA that chains already run on declares it here, as the DNA update is refused
+    /// otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub properties_hash: Option<Address>,
+
     /// An array of zomes associated with your holochain application.
     #[serde(default)]
     pub zomes: BTreeMap<String, zome::Zome>,
@@ -122,6 +128,7 @@ impl Default for Dna {
             uuid: zero_uuid(),
             dna_spec_version: String::from("2.0"),
             properties: empty_object(),
+            properties_hash: None,
             zomes: BTreeMap::new(),
         }
     }
@@ -246,6 +253,12 @@ impl Dna {
         None
     }
 
+    /// The hash of the properties, which DNA updates changing them have to declare as
+    /// `properties_hash`
+    pub fn properties_address(&self) -> Address {
+        Address::encode_from_str(&self.properties.to_string(), multihash::Hash::SHA2256)
+    }
+
     pub fn multihash(&self) -> Result<Vec<u8>, HolochainError> {
         let s = String::from(JsonString::from(self.to_owned()));
         multihash::encode(multihash::Hash::SHA2256, &s.into_bytes())
@@ -375,7 +388,7 @@ pub mod tests {
     #[test]
     fn test_dna_new() {
         let dna = Dna::new();
-        assert_eq!(format!("{:?}",dna),"Dna { name: \"\", description: \"\", version: \"\", uuid: \"00000000-0000-0000-0000-000000000000\", dna_spec_version: \"2.0\", properties: Object({}), zomes: {} }")
+        assert_eq!(format!("{:?}",dna),"Dna { name: \"\", description: \"\", version: \"\", uuid: \"00000000-0000-0000-0000-000000000000\", dna_spec_version: \"2.0\", properties: Object({}), properties_hash: None, zomes: {} }")
     }
 
     #[test]
@@ -772,6 +785,21 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn properties_hash_is_declared_without_changing_the_dna_otherwise() {
+        let mut dna = test_dna();
+        let serialized = String::from(JsonString::from(dna.clone()));
+        assert!(!serialized.contains("properties_hash"));
+
+        let properties_address = dna.properties_address();
+        dna.properties = json!({ "admins": 2 });
+        assert_ne!(properties_address, dna.properties_address());
+
+        dna.properties_hash = Some(dna.properties_address());
+        let restored = Dna::try_from(JsonString::from(dna.clone())).unwrap();
+        assert_eq!(Some(dna.properties_address()), restored.properties_hash);
+    }
+
     #[test]
     fn get_wasm_from_zome_name() {
         let dna = Dna::try_from(JsonString::from_json(
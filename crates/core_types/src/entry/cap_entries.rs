@@ -1,10 +1,23 @@
-use crate::{entry::Entry, error::HolochainError};
-
-use holochain_persistence_api::cas::content::{Address, AddressableContent};
-
-use holochain_json_api::{error::JsonError, json::JsonString};
-
-use std::{collections::BTreeMap, str::FromStr};
+use crate::{
+    entry::Entry,
+    error::HolochainError,
+    signature::{Provenance, Signature},
+    time::Iso8601,
+};
+
+use holochain_persistence_api::cas::content::{Address, AddressableContent, Content};
+
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
 
 //--------------------------------------------------------------------------------------------------
 // CapabilityType
@@ -37,6 +50,8 @@ impl Default for CapabilityType {
 pub enum ReservedCapabilityId {
     /// used for identifying the default public capability
     Public,
+    /// used for identifying grants that allow updating the DNA of the grantor's chain
+    Admin,
 }
 
 impl FromStr for ReservedCapabilityId {
@@ -44,6 +59,7 @@ impl FromStr for ReservedCapabilityId {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "hc_public" => Ok(ReservedCapabilityId::Public),
+            "hc_admin" => Ok(ReservedCapabilityId::Admin),
             _ => Err("Cannot convert string to ReservedCapabilityId"),
         }
     }
@@ -53,6 +69,7 @@ impl ReservedCapabilityId {
     pub fn as_str(&self) -> &'static str {
         match *self {
             ReservedCapabilityId::Public => "hc_public",
+            ReservedCapabilityId::Admin => "hc_admin",
         }
     }
 }
@@ -62,6 +79,14 @@ pub type CapTokenValue = Address;
 /// a collection functions by zome name that are authorized within a capability
 pub type CapFunctions = BTreeMap<String, Vec<String>>;
 
+/// Fields the JSON arguments of a call have to match: every listed top level field of the
+/// argument object must be present and equal to the given value.
+pub type ArgConstraint = BTreeMap<String, serde_json::Value>;
+
+/// argument constraints of a capability by zome name and then function name.
+/// Functions without an entry here may be called with any arguments.
+pub type CapArgConstraints = BTreeMap<String, BTreeMap<String, ArgConstraint>>;
+
 /// System entry to hold a capability token claim for use as a caller
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, DefaultJson, Eq)]
 pub struct CapTokenClaim {
@@ -85,12 +110,84 @@ impl CapTokenClaim {
     }
 }
 
+fn args_match_constraints(
+    arg_constraints: &CapArgConstraints,
+    zome: &str,
+    function: &str,
+    args: &JsonString,
+) -> bool {
+    let constraint = match arg_constraints
+        .get(zome)
+        .and_then(|functions| functions.get(function))
+    {
+        Some(constraint) => constraint,
+        None => return true,
+    };
+    match serde_json::from_str::<serde_json::Value>(&String::from(args.clone())) {
+        Ok(serde_json::Value::Object(fields)) => constraint
+            .iter()
+            .all(|(name, value)| fields.get(name) == Some(value)),
+        _ => false,
+    }
+}
+
+/// System entry recording that the grant with the given token was revoked by its grantor.
+/// Calls using the token are refused from then on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, DefaultJson, Eq)]
+pub struct CapTokenRevocation {
+    token: CapTokenValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    effective_at: Option<Iso8601>,
+}
+
+impl CapTokenRevocation {
+    pub fn new(token: CapTokenValue) -> Self {
+        CapTokenRevocation {
+            token,
+            effective_at: None,
+        }
+    }
+
+    /// Returns the revocation taking effect only at the given time, the grant stays
+    /// valid until then. Used to give grantees a grace period when rotating grants.
+    pub fn with_effective_at(mut self, effective_at: Option<Iso8601>) -> Self {
+        self.effective_at = effective_at;
+        self
+    }
+
+    pub fn token(&self) -> CapTokenValue {
+        self.token.clone()
+    }
+
+    pub fn effective_at(&self) -> Option<Iso8601> {
+        self.effective_at
+    }
+
+    pub fn is_effective_at(&self, now: &Iso8601) -> bool {
+        self.effective_at
+            .map(|effective_at| *now >= effective_at)
+            .unwrap_or(true)
+    }
+}
+
 /// System entry to hold a capabilities granted by the callee
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, DefaultJson, Eq)]
 pub struct CapTokenGrant {
     id: String,
     assignees: Option<Vec<Address>>,
     functions: CapFunctions,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<Iso8601>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    arg_constraints: CapArgConstraints,
+    #[serde(default, skip_serializing_if = "is_false")]
+    delegatable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rotated_from: Option<CapTokenValue>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 impl CapTokenGrant {
@@ -99,9 +196,41 @@ impl CapTokenGrant {
             id: String::from(id),
             assignees,
             functions,
+            expires_at: None,
+            arg_constraints: CapArgConstraints::new(),
+            delegatable: false,
+            rotated_from: None,
         }
     }
 
+    /// Returns the grant with an expiry set. Calls made with its token after `expires_at`
+    /// are rejected. Note that this changes the grant's token.
+    pub fn with_expires_at(mut self, expires_at: Option<Iso8601>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    /// Returns the grant with argument constraints set, see `CapArgConstraints`.
+    /// Like the expiry these are part of the grant and so change its token.
+    pub fn with_arg_constraints(mut self, arg_constraints: CapArgConstraints) -> Self {
+        self.arg_constraints = arg_constraints;
+        self
+    }
+
+    /// Returns the grant marked as delegatable, meaning its grantees may mint narrower
+    /// sub-tokens from it, see `CapTokenDelegation`. Changes the grant's token.
+    pub fn with_delegatable(mut self, delegatable: bool) -> Self {
+        self.delegatable = delegatable;
+        self
+    }
+
+    /// Returns the grant marked as the replacement of the grant with the given token.
+    /// This gives a rotated grant the same scope as the old one but a new token.
+    pub fn with_rotated_from(mut self, rotated_from: Option<CapTokenValue>) -> Self {
+        self.rotated_from = rotated_from;
+        self
+    }
+
     pub fn create(
         id: &str,
         cap_type: CapabilityType,
@@ -169,6 +298,195 @@ impl CapTokenGrant {
     pub fn functions(&self) -> CapFunctions {
         self.functions.clone()
     }
+
+    pub fn arg_constraints(&self) -> CapArgConstraints {
+        self.arg_constraints.clone()
+    }
+
+    /// true if the grant allows calling the given function with the given arguments.
+    /// This only looks at the argument constraints, not at whether the function is granted.
+    pub fn args_allowed(&self, zome: &str, function: &str, args: &JsonString) -> bool {
+        args_match_constraints(&self.arg_constraints, zome, function, args)
+    }
+
+    pub fn is_delegatable(&self) -> bool {
+        self.delegatable
+    }
+
+    /// token of the grant this grant replaced, if it was created by rotating a grant
+    pub fn rotated_from(&self) -> Option<CapTokenValue> {
+        self.rotated_from.clone()
+    }
+
+    pub fn expires_at(&self) -> Option<Iso8601> {
+        self.expires_at
+    }
+
+    /// true if the grant has an expiry and `now` is past it
+    pub fn is_expired_at(&self, now: &Iso8601) -> bool {
+        self.expires_at
+            .map(|expires_at| *now > expires_at)
+            .unwrap_or(false)
+    }
+}
+
+/// A sub-token minted by the grantee of a delegatable grant, or by the holder of another
+/// delegation. It can only narrow what its parent allows: a subset of the functions, at least
+/// the parent's argument constraints and an expiry no later than the parent's.
+///
+/// Each delegation names the agent it is made to. Only that agent can delegate it further
+/// or call with it, so a chain that leaks can't be used or extended by anyone else.
+///
+/// Delegations are not stored by the grantor. Callers send the whole chain of delegations
+/// along with their capability request, root first, and the grantor verifies it back to the
+/// original grant on its chain. The sub-token is the address of the last delegation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, DefaultJson, Eq)]
+pub struct CapTokenDelegation {
+    parent_token: CapTokenValue,
+    delegator: Address,
+    delegatee: Address,
+    functions: CapFunctions,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    arg_constraints: CapArgConstraints,
+    expires_at: Iso8601,
+    signature: Signature,
+}
+
+impl CapTokenDelegation {
+    /// Builds an unsigned delegation. Sign the `signing_payload()` with the delegator's key
+    /// and add the signature with `with_signature`.
+    pub fn new(
+        parent_token: CapTokenValue,
+        delegator: Address,
+        delegatee: Address,
+        functions: CapFunctions,
+        arg_constraints: CapArgConstraints,
+        expires_at: Iso8601,
+    ) -> Self {
+        CapTokenDelegation {
+            parent_token,
+            delegator,
+            delegatee,
+            functions,
+            arg_constraints,
+            expires_at,
+            signature: Signature::from(""),
+        }
+    }
+
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.signature = signature;
+        self
+    }
+
+    /// the data the delegator signs, i.e. everything but the signature
+    pub fn signing_payload(&self) -> String {
+        JsonString::from(self.clone().with_signature(Signature::from(""))).to_string()
+    }
+
+    /// the sub-token this delegation stands for
+    pub fn token(&self) -> CapTokenValue {
+        self.address()
+    }
+
+    pub fn provenance(&self) -> Provenance {
+        Provenance::new(self.delegator.clone(), self.signature.clone())
+    }
+
+    pub fn parent_token(&self) -> CapTokenValue {
+        self.parent_token.clone()
+    }
+
+    pub fn delegator(&self) -> Address {
+        self.delegator.clone()
+    }
+
+    /// the agent this delegation is made to
+    pub fn delegatee(&self) -> Address {
+        self.delegatee.clone()
+    }
+
+    pub fn functions(&self) -> CapFunctions {
+        self.functions.clone()
+    }
+
+    pub fn arg_constraints(&self) -> CapArgConstraints {
+        self.arg_constraints.clone()
+    }
+
+    pub fn expires_at(&self) -> Iso8601 {
+        self.expires_at
+    }
+
+    pub fn is_expired_at(&self, now: &Iso8601) -> bool {
+        *now > self.expires_at
+    }
+
+    /// true if the delegation allows calling the given function with the given arguments
+    pub fn allows_call(&self, zome: &str, function: &str, args: &JsonString) -> bool {
+        self.functions
+            .get(zome)
+            .map(|functions| functions.iter().any(|f| f == function))
+            .unwrap_or(false)
+            && args_match_constraints(&self.arg_constraints, zome, function, args)
+    }
+
+    /// true if this delegation grants no more than a parent with the given scope
+    pub fn is_within(
+        &self,
+        functions: &CapFunctions,
+        arg_constraints: &CapArgConstraints,
+        expires_at: Option<Iso8601>,
+    ) -> bool {
+        let functions_within = self.functions.iter().all(|(zome, zome_functions)| {
+            functions
+                .get(zome)
+                .map(|parent| zome_functions.iter().all(|f| parent.contains(f)))
+                .unwrap_or(false)
+        });
+        let constraints_kept = arg_constraints.iter().all(|(zome, zome_constraints)| {
+            zome_constraints.iter().all(|(function, constraint)| {
+                let delegated = self
+                    .functions
+                    .get(zome)
+                    .map(|functions| functions.contains(function))
+                    .unwrap_or(false);
+                !delegated
+                    || self
+                        .arg_constraints
+                        .get(zome)
+                        .and_then(|functions| functions.get(function))
+                        .map(|own| {
+                            constraint
+                                .iter()
+                                .all(|(field, value)| own.get(field) == Some(value))
+                        })
+                        .unwrap_or(false)
+            })
+        });
+        let expiry_within = expires_at
+            .map(|parent_expiry| self.expires_at <= parent_expiry)
+            .unwrap_or(true);
+        functions_within && constraints_kept && expiry_within
+    }
+}
+
+impl AddressableContent for CapTokenDelegation {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        content.to_owned().try_into()
+    }
+}
+
+/// Delegations have no identity beyond their content, so they hash by their token.
+/// This lets them be part of `CapabilityRequest`s, which need to be hashable.
+impl Hash for CapTokenDelegation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.token().hash(state);
+    }
 }
 
 #[cfg(test)]
@@ -182,6 +500,10 @@ pub mod tests {
             Ok(ReservedCapabilityId::Public),
             ReservedCapabilityId::from_str("hc_public"),
         );
+        assert_eq!(
+            Ok(ReservedCapabilityId::Admin),
+            ReservedCapabilityId::from_str("hc_admin"),
+        );
         assert_eq!(
             Err("Cannot convert string to ReservedCapabilityId"),
             ReservedCapabilityId::from_str("foo"),
@@ -192,6 +514,7 @@ pub mod tests {
     /// test that a canonical string can be created from ReservedCapabilityId
     fn test_reserved_capid_as_str() {
         assert_eq!(ReservedCapabilityId::Public.as_str(), "hc_public");
+        assert_eq!(ReservedCapabilityId::Admin.as_str(), "hc_admin");
     }
 
     #[test]
@@ -204,6 +527,24 @@ pub mod tests {
         assert_eq!(claim.token(), token);
     }
 
+    #[test]
+    fn test_new_cap_token_revocation_entry() {
+        let token = Address::from("fake");
+        let revocation = CapTokenRevocation::new(token.clone());
+        assert_eq!(revocation.token(), token);
+        assert!(revocation.is_effective_at(&Iso8601::from(0)));
+        let entry = Entry::CapTokenRevocation(revocation);
+        assert_eq!(entry, Entry::try_from_content(&entry.content()).unwrap());
+
+        let delayed = CapTokenRevocation::new(token).with_effective_at(Some(Iso8601::from(1000)));
+        assert!(!delayed.is_effective_at(&Iso8601::from(999)));
+        assert!(delayed.is_effective_at(&Iso8601::from(1000)));
+        assert_ne!(
+            Entry::CapTokenRevocation(delayed).address(),
+            entry.address()
+        );
+    }
+
     #[test]
     fn test_new_cap_token_grant_entry() {
         let empty_functions = CapFunctions::new();
@@ -294,4 +635,160 @@ pub mod tests {
         assert_eq!(grant.cap_type(), CapabilityType::Assigned);
         assert_eq!(grant.assignees().unwrap()[0], test_address)
     }
+
+    #[test]
+    fn test_cap_token_grant_expiry() {
+        let grant = CapTokenGrant::create(
+            "foo",
+            CapabilityType::Transferable,
+            None,
+            CapFunctions::new(),
+        )
+        .unwrap();
+        assert_eq!(grant.expires_at(), None);
+        assert!(!grant.is_expired_at(&Iso8601::from(2_000_000_000)));
+
+        let expiring = grant.clone().with_expires_at(Some(Iso8601::from(1000)));
+        assert_eq!(expiring.expires_at(), Some(Iso8601::from(1000)));
+        assert!(!expiring.is_expired_at(&Iso8601::from(999)));
+        assert!(!expiring.is_expired_at(&Iso8601::from(1000)));
+        assert!(expiring.is_expired_at(&Iso8601::from(1001)));
+
+        // the expiry is part of the grant so it gets its own token
+        assert_ne!(grant.token(), expiring.token());
+        // grants without expiry keep their serialization
+        assert!(!JsonString::from(grant).to_string().contains("expires_at"));
+    }
+
+    #[test]
+    fn test_cap_token_grant_rotation() {
+        let mut functions = CapFunctions::new();
+        functions.insert("chat".to_string(), vec!["get_messages".to_string()]);
+        let grant =
+            CapTokenGrant::create("foo", CapabilityType::Transferable, None, functions).unwrap();
+        assert_eq!(grant.rotated_from(), None);
+
+        let rotated = grant.clone().with_rotated_from(Some(grant.token()));
+        assert_eq!(rotated.rotated_from(), Some(grant.token()));
+        assert_eq!(rotated.functions(), grant.functions());
+        assert_ne!(rotated.token(), grant.token());
+        assert!(!JsonString::from(grant).to_string().contains("rotated_from"));
+    }
+
+    #[test]
+    fn test_cap_token_grant_arg_constraints() {
+        let mut functions = CapFunctions::new();
+        functions.insert(
+            "chat".to_string(),
+            vec!["get_messages".to_string(), "post".to_string()],
+        );
+        let grant =
+            CapTokenGrant::create("foo", CapabilityType::Transferable, None, functions).unwrap();
+        let args = JsonString::from_json(r#"{"channel":"general","limit":10}"#);
+        assert!(grant.args_allowed("chat", "get_messages", &args));
+
+        let mut constraint = ArgConstraint::new();
+        constraint.insert("channel".to_string(), json!("general"));
+        let mut chat_constraints = BTreeMap::new();
+        chat_constraints.insert("get_messages".to_string(), constraint);
+        let mut arg_constraints = CapArgConstraints::new();
+        arg_constraints.insert("chat".to_string(), chat_constraints);
+        let constrained = grant.clone().with_arg_constraints(arg_constraints.clone());
+        assert_eq!(constrained.arg_constraints(), arg_constraints);
+        assert_ne!(grant.token(), constrained.token());
+
+        assert!(constrained.args_allowed("chat", "get_messages", &args));
+        assert!(!constrained.args_allowed(
+            "chat",
+            "get_messages",
+            &JsonString::from_json(r#"{"channel":"private"}"#)
+        ));
+        assert!(!constrained.args_allowed(
+            "chat",
+            "get_messages",
+            &JsonString::from_json(r#"{"limit":10}"#)
+        ));
+        assert!(!constrained.args_allowed("chat", "get_messages", &JsonString::from_json("42")));
+        // unconstrained functions take any arguments
+        assert!(constrained.args_allowed(
+            "chat",
+            "post",
+            &JsonString::from_json(r#"{"channel":"private"}"#)
+        ));
+
+        // grants without constraints keep their serialization
+        assert!(!JsonString::from(grant)
+            .to_string()
+            .contains("arg_constraints"));
+    }
+
+    #[test]
+    fn test_cap_token_delegation_scope() {
+        let mut functions = CapFunctions::new();
+        functions.insert(
+            "chat".to_string(),
+            vec!["get_messages".to_string(), "post".to_string()],
+        );
+        let mut channel = ArgConstraint::new();
+        channel.insert("channel".to_string(), json!("general"));
+        let mut chat_constraints = BTreeMap::new();
+        chat_constraints.insert("get_messages".to_string(), channel);
+        let mut arg_constraints = CapArgConstraints::new();
+        arg_constraints.insert("chat".to_string(), chat_constraints);
+
+        let mut get_messages = CapFunctions::new();
+        get_messages.insert("chat".to_string(), vec!["get_messages".to_string()]);
+        let delegation = CapTokenDelegation::new(
+            Address::from("parent"),
+            Address::from("delegator"),
+            Address::from("delegatee"),
+            get_messages.clone(),
+            arg_constraints.clone(),
+            Iso8601::from(1000),
+        );
+        assert!(delegation.is_within(&functions, &arg_constraints, None));
+        assert!(delegation.is_within(&functions, &arg_constraints, Some(Iso8601::from(1000))));
+        // can't outlive the parent
+        assert!(!delegation.is_within(&functions, &arg_constraints, Some(Iso8601::from(999))));
+        // can't add functions
+        assert!(!delegation.is_within(&CapFunctions::new(), &arg_constraints, None));
+        // has to keep the parent's constraints
+        let unconstrained = CapTokenDelegation::new(
+            Address::from("parent"),
+            Address::from("delegator"),
+            Address::from("delegatee"),
+            get_messages,
+            CapArgConstraints::new(),
+            Iso8601::from(1000),
+        );
+        assert!(!unconstrained.is_within(&functions, &arg_constraints, None));
+
+        assert!(delegation.allows_call(
+            "chat",
+            "get_messages",
+            &JsonString::from_json(r#"{"channel":"general"}"#)
+        ));
+        assert!(!delegation.allows_call(
+            "chat",
+            "get_messages",
+            &JsonString::from_json(r#"{"channel":"private"}"#)
+        ));
+        assert!(!delegation.allows_call("chat", "post", &JsonString::from_json("{}")));
+        assert!(delegation.is_expired_at(&Iso8601::from(1001)));
+
+        // the signature is not part of what gets signed, but it is part of the token
+        let signed = delegation.clone().with_signature(Signature::from("sig"));
+        assert_eq!(signed.signing_payload(), delegation.signing_payload());
+        assert_ne!(signed.token(), delegation.token());
+        // the delegatee is signed over
+        let to_other = CapTokenDelegation::new(
+            Address::from("parent"),
+            Address::from("delegator"),
+            Address::from("other delegatee"),
+            delegation.functions(),
+            delegation.arg_constraints(),
+            Iso8601::from(1000),
+        );
+        assert_ne!(to_other.signing_payload(), delegation.signing_payload());
+    }
 }
@@ -60,6 +60,7 @@ pub enum EntryType {
     ChainMigrate,
     CapTokenGrant,
     CapTokenClaim,
+    CapTokenRevocation,
 }
 
 impl From<AppEntryType> for EntryType {
@@ -117,6 +118,7 @@ impl FromStr for EntryType {
             sys_prefix!("chain_migrate") => EntryType::ChainMigrate,
             sys_prefix!("cap_token_claim") => EntryType::CapTokenClaim,
             sys_prefix!("cap_token_grant") => EntryType::CapTokenGrant,
+            sys_prefix!("cap_token_revocation") => EntryType::CapTokenRevocation,
             _ => EntryType::App(AppEntryType(s.into())),
         })
     }
@@ -136,6 +138,7 @@ impl From<EntryType> for String {
             EntryType::ChainMigrate => sys_prefix!("chain_migrate"),
             EntryType::CapTokenClaim => sys_prefix!("cap_token_claim"),
             EntryType::CapTokenGrant => sys_prefix!("cap_token_grant"),
+            EntryType::CapTokenRevocation => sys_prefix!("cap_token_revocation"),
         })
     }
 }
@@ -205,6 +208,7 @@ pub mod tests {
             EntryType::ChainMigrate,
             EntryType::CapTokenClaim,
             EntryType::CapTokenGrant,
+            EntryType::CapTokenRevocation,
         ]
     }
 
@@ -243,6 +247,10 @@ pub mod tests {
             (sys_prefix!("chain_migrate"), EntryType::ChainMigrate),
             (sys_prefix!("cap_token_claim"), EntryType::CapTokenClaim),
             (sys_prefix!("cap_token_grant"), EntryType::CapTokenGrant),
+            (
+                sys_prefix!("cap_token_revocation"),
+                EntryType::CapTokenRevocation,
+            ),
         ] {
             assert_eq!(
                 variant,
@@ -8,7 +8,7 @@ pub mod deletion_entry;
 pub mod entry_type;
 
 use self::{
-    cap_entries::{CapTokenClaim, CapTokenGrant},
+    cap_entries::{CapTokenClaim, CapTokenGrant, CapTokenRevocation},
     deletion_entry::DeletionEntry,
 };
 use agent::{test_agent_id, AgentId};
@@ -77,6 +77,7 @@ pub enum Entry {
     ChainMigrate(ChainMigrate),
     CapTokenClaim(CapTokenClaim),
     CapTokenGrant(CapTokenGrant),
+    CapTokenRevocation(CapTokenRevocation),
 }
 
 impl Entry {
@@ -93,6 +94,7 @@ impl Entry {
             Entry::ChainMigrate(_) => EntryType::ChainMigrate,
             Entry::CapTokenClaim(_) => EntryType::CapTokenClaim,
             Entry::CapTokenGrant(_) => EntryType::CapTokenGrant,
+            Entry::CapTokenRevocation(_) => EntryType::CapTokenRevocation,
         }
     }
 }
@@ -106,7 +108,8 @@ impl PartialEq for Entry {
 impl AddressableContent for Entry {
     fn address(&self) -> Address {
         match &self {
-            Entry::AgentId(agent_id) => agent_id.address(),
+            // Updated identities get their own address, see AgentId::revision
+            Entry::AgentId(agent_id) if agent_id.revision.is_none() => agent_id.address(),
             Entry::ChainHeader(chain_header) => chain_header.address(),
             _ => Address::encode_from_str(&String::from(self.content()), Hash::SHA2256),
         }
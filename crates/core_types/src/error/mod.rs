@@ -6,6 +6,7 @@ mod ribosome_error;
 
 use self::HolochainError::*;
 pub use self::{dna_error::*, ribosome_error::*};
+use crate::validation::ValidationFailure;
 use futures::channel::oneshot::Canceled as FutureCanceled;
 use holochain_json_api::{
     error::{JsonError, JsonResult},
@@ -107,7 +108,7 @@ pub enum HolochainError {
     SerializationError(String),
     InvalidOperationOnSysEntry,
     CapabilityCheckFailed,
-    ValidationFailed(String),
+    ValidationFailed(ValidationFailure),
     ValidationPending,
     Ribosome(RibosomeErrorCode),
     RibosomeFailed(String),
@@ -118,6 +119,9 @@ pub enum HolochainError {
     DnaHashMismatch(HashString, HashString),
     EntryNotFoundLocally,
     EntryIsPrivate,
+    KeystoreLocked,
+    RateLimited(String),
+    ResourceLimitExceeded(String),
     List(Vec<HolochainError>),
 }
 
@@ -169,6 +173,9 @@ impl fmt::Display for HolochainError {
                 f,
                 "The requested entry is private and should not be shared via gossip"
             ),
+            KeystoreLocked => write!(f, "The keystore is locked"),
+            RateLimited(reason) => write!(f, "Rate limited: {}", reason),
+            ResourceLimitExceeded(reason) => write!(f, "Resource limit exceeded: {}", reason),
             List(list) => {
                 //most windows system know that \n is a newline so we should be good.
                 let error_list = list
@@ -424,6 +431,15 @@ mod tests {
                 HolochainError::EntryIsPrivate,
                 "The requested entry is private and should not be shared via gossip",
             ),
+            (HolochainError::KeystoreLocked, "The keystore is locked"),
+            (
+                HolochainError::RateLimited("too many calls".to_string()),
+                "Rate limited: too many calls",
+            ),
+            (
+                HolochainError::ResourceLimitExceeded("storage".to_string()),
+                "Resource limit exceeded: storage",
+            ),
         ] {
             assert_eq!(output, &input.to_string());
         }
@@ -209,6 +209,9 @@ impl From<HolochainError> for RibosomeErrorCode {
             HolochainError::DnaHashMismatch(_, _) => RibosomeErrorCode::Unspecified,
             HolochainError::EntryNotFoundLocally => RibosomeErrorCode::Unspecified,
             HolochainError::EntryIsPrivate => RibosomeErrorCode::Unspecified,
+            HolochainError::KeystoreLocked => RibosomeErrorCode::Unspecified,
+            HolochainError::RateLimited(_) => RibosomeErrorCode::Unspecified,
+            HolochainError::ResourceLimitExceeded(_) => RibosomeErrorCode::Unspecified,
             HolochainError::List(_) => RibosomeErrorCode::Unspecified,
         }
     }
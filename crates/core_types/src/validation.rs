@@ -17,7 +17,7 @@ use holochain_persistence_api::cas::content::Address;
 
 use chain_header::test_chain_header;
 
-use std::convert::TryFrom;
+use std::{cmp::Ordering, convert::TryFrom, fmt};
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, DefaultJson)]
 pub struct ValidationPackage {
@@ -186,3 +186,105 @@ pub enum LinkAction {
     Create,
     Delete,
 }
+
+/// Why an entry or link is invalid, in a form that UIs can handle programmatically:
+/// a machine-readable `code`, a human readable `message` and optional `data` with details.
+///
+/// Validation callbacks return their failures as strings. Converting a ValidationFailure
+/// into a String encodes it as JSON, which core decodes again, so zomes can return
+/// `Err(ValidationFailure::new("not_author", "Only the author can delete").into())`.
+/// Any other failure string becomes the message of a failure with the default code.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, DefaultJson)]
+pub struct ValidationFailure {
+    pub code: String,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<JsonString>,
+}
+
+impl ValidationFailure {
+    /// Code of failures that are given as plain messages
+    pub const DEFAULT_CODE: &'static str = "invalid";
+
+    pub fn new<C: Into<String>, M: Into<String>>(code: C, message: M) -> Self {
+        ValidationFailure {
+            code: code.into(),
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data<D: Into<JsonString>>(mut self, data: D) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Decodes the failure a validation callback returned: a ValidationFailure encoded as
+    /// JSON, or a plain message
+    pub fn from_callback_result(result: &str) -> Self {
+        serde_json::from_str(result).unwrap_or_else(|_| ValidationFailure::from(result))
+    }
+
+    fn sort_key(&self) -> (&str, &str, Option<String>) {
+        (
+            &self.code,
+            &self.message,
+            self.data.as_ref().map(|data| data.to_string()),
+        )
+    }
+}
+
+impl From<String> for ValidationFailure {
+    fn from(message: String) -> Self {
+        ValidationFailure::new(ValidationFailure::DEFAULT_CODE, message)
+    }
+}
+
+impl From<&str> for ValidationFailure {
+    fn from(message: &str) -> Self {
+        ValidationFailure::from(message.to_string())
+    }
+}
+
+impl From<ValidationFailure> for String {
+    fn from(failure: ValidationFailure) -> Self {
+        serde_json::to_string(&failure).unwrap_or(failure.message)
+    }
+}
+
+impl fmt::Display for ValidationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl PartialOrd for ValidationFailure {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ValidationFailure {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn validation_failures_survive_callback_strings() {
+        let failure = ValidationFailure::new("not_author", "Only the author can delete")
+            .with_data(JsonString::from_json("{\"author\":\"alice\"}"));
+        let returned: String = failure.clone().into();
+        assert_eq!(ValidationFailure::from_callback_result(&returned), failure);
+        assert_eq!(failure.to_string(), "Only the author can delete");
+
+        assert_eq!(
+            ValidationFailure::from_callback_result("too long"),
+            ValidationFailure::new(ValidationFailure::DEFAULT_CODE, "too long")
+        );
+    }
+}
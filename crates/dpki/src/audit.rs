@@ -0,0 +1,108 @@
+//! Security audit log of signature verifications.
+//!
+//! Deployments that need forensic traceability of authentication decisions can enable the audit
+//! log with [enable_audit_log]. From then on every verification made through [verify_audited]
+//! (provenance checks, signed zome calls, capability delegations, handshakes, ...) gets appended
+//! to the log file as one JSON object per line, with its kind, the key, some context and the
+//! outcome. The file is only ever opened for appending, never truncated.
+use crate::utils::Verify;
+use holochain_core_types::{error::HcResult, signature::Provenance};
+use holochain_persistence_api::cas::content::Address;
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+lazy_static! {
+    static ref AUDIT_LOG: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// Starts appending verifications to the file at `path`, creating it if needed.
+pub fn enable_audit_log(path: &Path) -> HcResult<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *AUDIT_LOG.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+pub fn disable_audit_log() {
+    *AUDIT_LOG.lock().unwrap() = None;
+}
+
+pub fn audit_log_enabled() -> bool {
+    AUDIT_LOG.lock().unwrap().is_some()
+}
+
+/// Appends a verification and its outcome to the audit log, if it is enabled.
+pub fn record_verification(kind: &str, key: &Address, context: &str, outcome: &HcResult<bool>) {
+    let mut audit_log = AUDIT_LOG.lock().unwrap();
+    if let Some(ref mut file) = *audit_log {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let outcome = match outcome {
+            Ok(true) => "valid".to_string(),
+            Ok(false) => "invalid".to_string(),
+            Err(err) => format!("error: {}", err),
+        };
+        let line = serde_json::json!({
+            "timestamp": timestamp,
+            "kind": kind,
+            "key": key.to_string(),
+            "context": context,
+            "outcome": outcome,
+        });
+        // An audit log that silently misses entries would be worse than none at all
+        writeln!(file, "{}", line)
+            .and_then(|_| file.flush())
+            .expect("Could not write to security audit log");
+    }
+}
+
+/// Verifies the provenance against the data like [Verify::verify] and records the outcome in
+/// the audit log.
+pub fn verify_audited(
+    provenance: &Provenance,
+    data: String,
+    kind: &str,
+    context: &str,
+) -> HcResult<bool> {
+    let outcome = provenance.verify(data);
+    record_verification(kind, &provenance.source(), context, &outcome);
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_core_types::signature::Signature;
+    use std::fs;
+
+    #[test]
+    fn verifications_get_appended_to_audit_log() {
+        let path = std::env::temp_dir().join("holochain_dpki_audit_log_test");
+        let _ = fs::remove_file(&path);
+        enable_audit_log(&path).unwrap();
+
+        let provenance = Provenance::new(
+            Address::from("HcScIkRaAaaaaaaaaaAaaaAAAAaaaaaaaaAaaaaAaaaaaaaaAaaAAAAatzu4aqa"),
+            Signature::from("not a signature"),
+        );
+        let outcome = verify_audited(&provenance, "data".to_string(), "test", "audit log test");
+        assert!(outcome.is_err());
+        disable_audit_log();
+
+        let log = fs::read_to_string(&path).unwrap();
+        let entry = log
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .find(|entry| entry["context"] == "audit log test")
+            .expect("verification should be in the audit log");
+        assert_eq!(entry["kind"], "test");
+        assert_eq!(entry["key"], provenance.source().to_string());
+        assert!(entry["outcome"].as_str().unwrap().starts_with("error"));
+    }
+}
@@ -21,6 +21,7 @@ lazy_static! {
         hcid::HcidEncoding::with_kind("hck0").expect("HCID failed miserably with_hck0.");
 }
 
+pub mod audit;
 pub mod key_blob;
 pub mod key_bundle;
 pub mod keypair;
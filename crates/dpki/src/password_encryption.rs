@@ -41,6 +41,25 @@ pub(crate) fn pw_hash(
     Ok(())
 }
 
+/// Derives a symmetric secret key from a passphrase, e.g. for encrypting data at rest.
+/// The same passphrase, salt and config always result in the same key.
+/// @param {SecBuf} passphrase - the passphrase to derive the key from
+/// @param {Buffer} salt - SALTBYTES of salt, which should be random and stored with the data
+/// @param {Option<PwHashConfig>} config - Optional hashing settings
+/// @return {SecBuf} - the derived key, usable with aead
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_DPKI)]
+pub fn derive_secret_key(
+    passphrase: &mut SecBuf,
+    salt: &[u8],
+    config: Option<PwHashConfig>,
+) -> HcResult<SecBuf> {
+    let mut salt_buf = SecBuf::with_insecure(SALTBYTES);
+    salt_buf.from_array(salt)?;
+    let mut secret = SecBuf::with_secure(kx::SESSIONKEYBYTES);
+    pw_hash(passphrase, &mut salt_buf, &mut secret, config)?;
+    Ok(secret)
+}
+
 /// Simple API for encrypting a buffer with a pwhash-ed passphrase
 /// @param {Buffer} data - the data to encrypt
 /// @param {SecBuf} passphrase - the passphrase to use for encrypting
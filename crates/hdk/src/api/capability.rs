@@ -1,9 +1,19 @@
 use crate::{error::ZomeApiResult, Dispatch};
-use holochain_core_types::entry::cap_entries::{CapFunctions, CapabilityType};
+use holochain_core_types::{
+    dna::capabilities::CapabilityUseRecord,
+    entry::cap_entries::{
+        CapArgConstraints, CapFunctions, CapTokenDelegation, CapTokenValue, CapabilityType,
+    },
+    time::Iso8601,
+};
 use holochain_persistence_api::cas::content::Address;
+pub use holochain_wasm_utils::api_serialization::capabilities::CommitCapabilityGrantArgs;
 use holochain_wasm_utils::api_serialization::capabilities::{
-    CommitCapabilityClaimArgs, CommitCapabilityGrantArgs,
+    CapabilityAuditLogResult, CommitCapabilityClaimArgs, DelegateCapabilityArgs,
+    DelegateCapabilityResult, GetCapabilityAuditLogArgs, RevokeCapabilityGrantArgs,
+    RotateCapabilityGrantArgs,
 };
+use std::time::Duration;
 
 /// Adds a capability grant to the local chain
 pub fn commit_capability_grant<S: Into<String>>(
@@ -12,14 +22,93 @@ pub fn commit_capability_grant<S: Into<String>>(
     assignees: Option<Vec<Address>>,
     functions: CapFunctions,
 ) -> ZomeApiResult<Address> {
-    Dispatch::CommitCapabilityGrant.with_input(CommitCapabilityGrantArgs {
+    commit_capability_grant_with(CommitCapabilityGrantArgs {
         id: id.into(),
         cap_type,
         assignees,
         functions,
+        ..Default::default()
+    })
+}
+
+/// Adds a capability grant with all the options of `CommitCapabilityGrantArgs` to the local
+/// chain. Options left at their default don't restrict the grant, e.g. a grant that stops
+/// being valid after `expires_at` for short-lived share links:
+/// `CommitCapabilityGrantArgs { expires_at: Some(expires_at), ..args }`
+///
+/// `arg_constraints` restrict the arguments granted functions may be called with, e.g. only
+/// allowing `get_messages` for one `channel`. See `CapArgConstraints` for how constraints are
+/// matched against call arguments.
+///
+/// Grantees of `delegatable` grants may mint narrower sub-tokens from them with
+/// `delegate_capability`, e.g. to hand a single function on to a helper agent. Expiry and
+/// argument constraints of the grant apply to these sub-tokens as well.
+pub fn commit_capability_grant_with(args: CommitCapabilityGrantArgs) -> ZomeApiResult<Address> {
+    Dispatch::CommitCapabilityGrant.with_input(args)
+}
+
+/// Mints a sub-token of `parent_token`, which is either the token of a delegatable grant
+/// we hold or a sub-token delegated to us, in which case `parent_delegation` is the chain
+/// we received with it. The sub-token can only allow a subset of the parent's functions,
+/// has to keep the parent's argument constraints and can not outlive the parent.
+/// Only `delegatee` can call with the sub-token or delegate it further.
+///
+/// Returns the new delegation chain. The sub-token is the token of its last element and
+/// callers have to send the whole chain along with their capability request.
+pub fn delegate_capability(
+    parent_token: CapTokenValue,
+    parent_delegation: Vec<CapTokenDelegation>,
+    delegatee: Address,
+    functions: CapFunctions,
+    arg_constraints: CapArgConstraints,
+    expires_at: Iso8601,
+) -> ZomeApiResult<Vec<CapTokenDelegation>> {
+    let result: DelegateCapabilityResult =
+        Dispatch::DelegateCapability.with_input(DelegateCapabilityArgs {
+            parent_token,
+            parent_delegation,
+            delegatee,
+            functions,
+            arg_constraints,
+            expires_at,
+        })?;
+    Ok(result.delegation)
+}
+
+/// Revokes the capability grant with the given token. Calls using the token are refused
+/// from then on and the grant's assignees get told that the token is no longer valid.
+/// Returns the address of the revocation entry.
+pub fn revoke_capability_grant(token: Address) -> ZomeApiResult<Address> {
+    Dispatch::RevokeCapabilityGrant.with_input(RevokeCapabilityGrantArgs { token })
+}
+
+/// Replaces the capability grant with the given token by a grant with the same scope but
+/// a new token, e.g. for periodically changing credentials. The old token keeps working for
+/// `grace_period` and is revoked after that. If `notify_assignees` is set the grant's
+/// assignees get sent the new token and use it in place of the old one from then on.
+/// Returns the new token.
+pub fn rotate_capability_grant(
+    token: Address,
+    grace_period: Duration,
+    notify_assignees: bool,
+) -> ZomeApiResult<Address> {
+    Dispatch::RotateCapabilityGrant.with_input(RotateCapabilityGrantArgs {
+        token,
+        grace_period_secs: grace_period.as_secs(),
+        notify_assignees,
     })
 }
 
+/// Returns the most recent calls made to this instance with capability tokens, oldest first,
+/// including calls that were refused. Pass a token to only get the calls made with it,
+/// e.g. to see who has been using a grant before revoking it.
+/// Only a bounded number of calls is kept, older ones get dropped.
+pub fn get_capability_audit_log(token: Option<Address>) -> ZomeApiResult<Vec<CapabilityUseRecord>> {
+    let result: CapabilityAuditLogResult =
+        Dispatch::GetCapabilityAuditLog.with_input(GetCapabilityAuditLogArgs { token })?;
+    Ok(result.records)
+}
+
 /// Adds a capability claim to the local chain
 pub fn commit_capability_claim<S: Into<String>>(
     id: S,
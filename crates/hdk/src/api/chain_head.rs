@@ -0,0 +1,20 @@
+use super::Dispatch;
+use crate::error::ZomeApiResult;
+use holochain_wasm_utils::api_serialization::chain_head::ChainHeadResult;
+
+/// Returns the address of the top header of this agent's source chain, the chain's length
+/// and the timestamp of the latest commit.
+/// This is cheap to call and can be used for optimistic-concurrency checks, i.e. to
+/// detect whether anything got committed since the chain head was last looked at.
+/// # Examples
+/// ```rust
+/// # extern crate hdk;
+/// # use hdk::error::ZomeApiResult;
+/// pub fn handle_chain_length() -> ZomeApiResult<u64> {
+///     let head = hdk::get_chain_head()?;
+///     Ok(head.length)
+/// }
+/// ```
+pub fn get_chain_head() -> ZomeApiResult<ChainHeadResult> {
+    Dispatch::GetChainHead.without_input()
+}
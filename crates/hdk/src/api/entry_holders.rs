@@ -0,0 +1,24 @@
+use super::Dispatch;
+use crate::error::ZomeApiResult;
+use holochain_persistence_api::cas::content::Address;
+use holochain_wasm_utils::api_serialization::entry_holders::EntryHoldersResult;
+
+/// Returns the peers that have confirmed holding an entry this agent authored.
+/// Holders send a receipt to the author once they have validated and stored the
+/// entry, so this can be used to check whether data has actually reached the DHT,
+/// e.g. before going offline.
+/// Entries that were not authored by this agent always return an empty list.
+/// # Examples
+/// ```rust
+/// # extern crate hdk;
+/// # extern crate holochain_persistence_api;
+/// # use hdk::error::ZomeApiResult;
+/// # use holochain_persistence_api::cas::content::Address;
+/// pub fn handle_is_replicated(address: Address) -> ZomeApiResult<bool> {
+///     let holders = hdk::get_entry_holders(&address)?;
+///     Ok(holders.count() >= 3)
+/// }
+/// ```
+pub fn get_entry_holders(address: &Address) -> ZomeApiResult<EntryHoldersResult> {
+    Dispatch::GetEntryHolders.with_input(address.to_owned())
+}
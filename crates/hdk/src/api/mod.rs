@@ -6,15 +6,13 @@ use holochain_json_api::json::{default_to_json, JsonString, RawString};
 use holochain_persistence_api::{cas::content::Address, hash::HashString};
 use lazy_static::lazy_static;
 
-use holochain_core_types::{
-    dna::capabilities::CapabilityRequest,
-    error::{RibosomeEncodedAllocation, RibosomeEncodingBits, ZomeApiInternalResult},
-};
+#[cfg(target_arch = "wasm32")]
+use holochain_core_types::error::{RibosomeEncodedAllocation, ZomeApiInternalResult};
+use holochain_core_types::{dna::capabilities::CapabilityRequest, error::RibosomeEncodingBits};
 pub use holochain_wasm_utils::api_serialization::validation::*;
-use holochain_wasm_utils::{
-    api_serialization::ZomeApiGlobals,
-    memory::{ribosome::load_ribosome_encoded_json, stack::WasmStack},
-};
+#[cfg(target_arch = "wasm32")]
+use holochain_wasm_utils::memory::ribosome::load_ribosome_encoded_json;
+use holochain_wasm_utils::{api_serialization::ZomeApiGlobals, memory::stack::WasmStack};
 
 use crate::init_globals::init_globals;
 use std::convert::{TryFrom, TryInto};
@@ -22,12 +20,14 @@ use std::convert::{TryFrom, TryInto};
 mod bundle;
 mod call;
 mod capability;
+mod chain_head;
 mod commit_entry;
 mod debug;
 mod decrypt;
 mod emit_signal;
 mod encrypt;
 mod entry_address;
+mod entry_holders;
 mod entry_type_properties;
 mod get_entry;
 mod get_links;
@@ -39,19 +39,26 @@ mod remove_link;
 mod send;
 mod sign;
 mod sleep;
+mod staging;
 mod update_remove;
 mod version;
 
 pub use self::{
     bundle::{close_bundle, start_bundle},
     call::call,
-    capability::{commit_capability_claim, commit_capability_grant},
+    capability::{
+        commit_capability_claim, commit_capability_grant, commit_capability_grant_with,
+        delegate_capability, get_capability_audit_log, revoke_capability_grant,
+        rotate_capability_grant, CommitCapabilityGrantArgs,
+    },
+    chain_head::get_chain_head,
     commit_entry::{commit_entry, commit_entry_result},
     debug::debug,
     decrypt::decrypt,
     emit_signal::emit_signal,
     encrypt::encrypt,
     entry_address::entry_address,
+    entry_holders::get_entry_holders,
     entry_type_properties::entry_type_properties,
     get_entry::{get_entry, get_entry_history, get_entry_initial, get_entry_result},
     get_links::{
@@ -69,6 +76,7 @@ pub use self::{
     send::send,
     sign::{sign, sign_one_time, verify_signature},
     sleep::sleep,
+    staging::{commit_staged, discard_staged, stage_entry, stage_entry_result},
     update_remove::{remove_entry, update_agent, update_entry},
     version::{version, version_hash},
 };
@@ -80,6 +88,7 @@ macro_rules! def_api_fns {
         )*
     ) => {
 
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
         pub enum Dispatch {
             $( $enum_variant ),*
         }
@@ -92,6 +101,7 @@ macro_rules! def_api_fns {
                 self.with_input(JsonString::empty_object())
             }
 
+            #[cfg(target_arch = "wasm32")]
             pub fn with_input<I: TryInto<JsonString>, O: TryFrom<JsonString>>(
                 &self,
                 input: I,
@@ -128,6 +138,23 @@ macro_rules! def_api_fns {
                     Err(ZomeApiError::from(result.error))
                 }
             }
+
+            /// Outside of WebAssembly there is no Ribosome to call, so calls go to the
+            /// mock host instead, which makes zome code unit-testable with `cargo test`.
+            #[cfg(not(target_arch = "wasm32"))]
+            pub fn with_input<I: TryInto<JsonString>, O: TryFrom<JsonString>>(
+                &self,
+                input: I,
+            ) -> ZomeApiResult<O> {
+                let input = input
+                    .try_into()
+                    .map_err(|_| ZomeApiError::from(format!("Failed to serialize input of {:?}", self)))?;
+                let output = crate::mock::dispatch(*self, input)?;
+                let description = String::from(output.clone());
+                output
+                    .try_into()
+                    .map_err(|_| ZomeApiError::from(format!("Failed to deserialize return value: {}", description)))
+            }
         }
 
         // Invokable functions in the Ribosome
@@ -196,6 +223,14 @@ def_api_fns! {
     hc_commit_capability_grant, CommitCapabilityGrant;
     hc_commit_capability_claim, CommitCapabilityClaim;
     hc_emit_signal, EmitSignal;
+    hc_get_entry_holders, GetEntryHolders;
+    hc_get_chain_head, GetChainHead;
+    hc_staging, Staging;
+    hc_update_agent, UpdateAgent;
+    hc_revoke_capability_grant, RevokeCapabilityGrant;
+    hc_delegate_capability, DelegateCapability;
+    hc_get_capability_audit_log, GetCapabilityAuditLog;
+    hc_rotate_capability_grant, RotateCapabilityGrant;
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -232,7 +267,8 @@ lazy_static! {
     #[doc(hidden)]
     /// The hash of the most recent identity entry that has been committed to your chain.
     /// Starts with the same value as AGENT_INITIAL_HASH.
-    /// After a call to `update_agent` it will have the value of the hash of the newly committed identity entry.
+    /// Since the agent address is the public signing key, this stays the same after a call to
+    /// `update_agent`.
     pub static ref AGENT_LATEST_HASH: &'static HashString = &GLOBALS.agent_latest_hash;
 
     /// The Address of the public token (if any)
@@ -0,0 +1,68 @@
+use crate::{
+    error::{ZomeApiError, ZomeApiResult},
+    Dispatch,
+};
+use holochain_core_types::entry::Entry;
+use holochain_persistence_api::cas::content::Address;
+use holochain_wasm_utils::api_serialization::{
+    commit_entry::{CommitEntryArgs, CommitEntryOptions},
+    staging::{StagingArgs, StagingResult},
+};
+
+/// Validates an entry against the defined validation rules for its entry type and puts it
+/// into the agent's staging area. Staged entries are neither added to the source chain nor
+/// published until [commit_staged] is called, and [discard_staged] drops them without a
+/// trace on the chain. This enables draft/preview flows.
+/// Returns the address the entry will have once committed.
+/// # Examples
+/// ```rust
+/// # extern crate hdk;
+/// # extern crate holochain_core_types;
+/// # extern crate holochain_persistence_api;
+/// # use hdk::error::ZomeApiResult;
+/// # use holochain_core_types::entry::Entry;
+/// # use holochain_persistence_api::cas::content::Address;
+/// pub fn handle_publish_draft(drafts: Vec<Entry>, confirmed: bool) -> ZomeApiResult<Vec<Address>> {
+///     for draft in drafts.iter() {
+///         hdk::stage_entry(draft)?;
+///     }
+///     if confirmed {
+///         hdk::commit_staged()
+///     } else {
+///         hdk::discard_staged()
+///     }
+/// }
+/// ```
+pub fn stage_entry(entry: &Entry) -> ZomeApiResult<Address> {
+    stage_entry_result(entry, CommitEntryOptions::default())
+}
+
+/// Same as [stage_entry] but with the options of [commit_entry_result](fn.commit_entry_result.html),
+/// i.e. additional provenances the entry will get committed with.
+pub fn stage_entry_result(entry: &Entry, options: CommitEntryOptions) -> ZomeApiResult<Address> {
+    let result: StagingResult = Dispatch::Staging.with_input(StagingArgs::Stage(
+        CommitEntryArgs::new(entry.to_owned(), options),
+    ))?;
+    result
+        .addresses
+        .into_iter()
+        .next()
+        .ok_or_else(|| ZomeApiError::Internal("No address returned for staged entry".into()))
+}
+
+/// Commits all staged entries to the source chain, in the order they were staged, and
+/// publishes them like [commit_entry](fn.commit_entry.html) would.
+/// The entries get written as one batch, so importing many entries by staging them first
+/// is considerably faster than committing them one by one.
+/// Empties the staging area. Returns the addresses of the committed entries.
+pub fn commit_staged() -> ZomeApiResult<Vec<Address>> {
+    let result: StagingResult = Dispatch::Staging.with_input(StagingArgs::Commit)?;
+    Ok(result.addresses)
+}
+
+/// Drops all staged entries without committing them.
+/// Returns the addresses of the discarded entries.
+pub fn discard_staged() -> ZomeApiResult<Vec<Address>> {
+    let result: StagingResult = Dispatch::Staging.with_input(StagingArgs::Discard)?;
+    Ok(result.addresses)
+}
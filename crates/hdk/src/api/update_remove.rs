@@ -1,10 +1,7 @@
-use crate::{
-    error::{ZomeApiError, ZomeApiResult},
-    Dispatch,
-};
+use crate::{error::ZomeApiResult, Dispatch};
 use holochain_core_types::entry::Entry;
 use holochain_persistence_api::cas::content::Address;
-use holochain_wasm_utils::api_serialization::UpdateEntryArgs;
+use holochain_wasm_utils::api_serialization::{UpdateAgentArgs, UpdateEntryArgs};
 
 /// Commit an entry to your local source chain that "updates" a previous entry, meaning when getting
 /// the previous entry, the updated entry will be returned.
@@ -19,9 +16,14 @@ pub fn update_entry(new_entry: Entry, address: &Address) -> ZomeApiResult<Addres
     })
 }
 
-/// NOT YET AVAILABLE
-pub fn update_agent() -> ZomeApiResult<Address> {
-    Err(ZomeApiError::FunctionNotImplemented)
+/// Commit an updated identity entry for this agent, replacing its nickname and/or public
+/// profile metadata. Arguments left as `None` keep their current value.
+/// The public signing key cannot change, so the agent address stays the same. The new identity
+/// entry is stored under its own address as an update of the previous one, and other agents
+/// getting the agent address will receive the latest identity entry once it has been validated.
+/// Returns the address of the new identity entry.
+pub fn update_agent(nick: Option<String>, metadata: Option<String>) -> ZomeApiResult<Address> {
+    Dispatch::UpdateAgent.with_input(UpdateAgentArgs { nick, metadata })
 }
 
 /// Commit a DeletionEntry to your local source chain that marks an entry as 'deleted' by setting
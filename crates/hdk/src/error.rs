@@ -1,6 +1,9 @@
 //! This file contains defitions for Zome errors and also Zome Results.
 
-use crate::holochain_core_types::error::{HolochainError, RibosomeErrorCode};
+use crate::holochain_core_types::{
+    error::{HolochainError, RibosomeErrorCode},
+    validation::ValidationFailure,
+};
 
 use crate::holochain_persistence_api::error::PersistenceError;
 use holochain_json_api::{error::JsonError, json::JsonString};
@@ -17,7 +20,7 @@ pub enum ZomeApiError {
     Internal(String),
     FunctionNotImplemented,
     HashNotFound,
-    ValidationFailed(String),
+    ValidationFailed(ValidationFailure),
     Timeout(String),
 }
 
@@ -34,6 +34,8 @@ pub mod error;
 pub mod global_fns;
 pub mod init_globals;
 pub mod macros;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mock;
 
 pub use holochain_wasm_utils::api_serialization::{validation::*, THIS_INSTANCE};
 
@@ -40,7 +40,7 @@ macro_rules! load_string {
 
 /// Every Zome must utilize the `define_zome`
 /// macro in the main library file in their Zome.
-/// The `define_zome` macro has 4 component parts:
+/// The `define_zome` macro has 5 component parts:
 /// 1. entries: an array of [ValidatingEntryType](entry_definition/struct.ValidatingEntryType.html) as returned by using the [entry](macro.entry.html) macro
 /// 2. init: `init` is a callback called by Holochain to every Zome implemented within a DNA.
 ///     It gets called when a new agent is initializing an instance of the DNA for the first time, and
@@ -48,7 +48,11 @@ macro_rules! load_string {
 /// 3. receive (optional): `receive` is a callback called by Holochain when another agent on a hApp has initiated a node-to-node direct message.
 ///     That node-to-node message is initiated via the [**send** function of the API](api/fn.send.html), which is where you can read further about use of `send` and `receive`.
 ///     `receive` is optional to include, based on whether you use `send` anywhere in the code.
-/// 4. functions:
+/// 4. migrate_chain (optional): `migrate_chain` is a callback called by Holochain when an existing
+///     chain gets loaded with a new version of its DNA. It receives the addresses of the old and the
+///     new DNA, can read the old chain contents with [query](api/fn.query.html) and re-commit transformed
+///     data. Like `init` it should return `Ok` or an `Err`; an `Err` fails loading the instance.
+/// 5. functions:
 ///     `functions` declares all the zome's functions with their input/output signatures
 /// # Examples
 ///
@@ -136,6 +140,22 @@ macro_rules! load_string {
 /// # pub fn hc_commit_capability_claim(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
 /// # #[no_mangle]
 /// # pub fn hc_emit_signal(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_rotate_capability_grant(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_get_capability_audit_log(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_delegate_capability(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_revoke_capability_grant(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_update_agent(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_staging(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_get_chain_head(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_get_entry_holders(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
 ///
 /// #[derive(Serialize, Deserialize, Debug, DefaultJson,Clone)]
 /// pub struct Post {
@@ -187,7 +207,14 @@ macro_rules! load_string {
 ///     }
 ///
 ///     validate_agent: |validation_data : EntryValidationData::<AgentId>| {
-///         Ok(())
+///         // Only admit agents that joined with a membrane proof (e.g. an invitation)
+///         match validation_data {
+///             EntryValidationData::Create { entry, .. } => entry
+///                 .membrane_proof
+///                 .map(|_| ())
+///                 .ok_or_else(|| "Agent joined without a membrane proof".to_string()),
+///             _ => Ok(()),
+///         }
 ///     }
 ///
 ///     receive: |from, payload| {
@@ -195,6 +222,11 @@ macro_rules! load_string {
 ///       format!("Received: {} from {}", payload, from)
 ///     }
 ///
+///     migrate_chain: |old_dna_address, new_dna_address| {
+///       // nothing to transform between these DNA versions
+///       Ok(())
+///     }
+///
 ///     functions: [
 ///             // the name of this function, "post_address" is the
 ///             // one to give while performing a `call` method to this function.
@@ -236,6 +268,12 @@ macro_rules! define_zome {
             }
         )*
 
+        $(
+            migrate_chain : |$migrate_old_dna:ident, $migrate_new_dna:ident| {
+                $migrate_expr:expr
+            }
+        )*
+
         functions : [
             $(
                         $zome_function_name:ident : {
@@ -330,6 +368,41 @@ macro_rules! define_zome {
             }
         )*
 
+        $(
+            #[no_mangle]
+            pub extern "C" fn migrate_chain(encoded_allocation_of_input: hdk::holochain_core_types::error::RibosomeEncodingBits) -> hdk::holochain_core_types::error::RibosomeEncodingBits {
+                let maybe_allocation = $crate::holochain_wasm_utils::memory::allocation::WasmAllocation::try_from_ribosome_encoding(encoded_allocation_of_input);
+                let allocation = match maybe_allocation {
+                    Ok(allocation) => allocation,
+                    Err(allocation_error) => return hdk::holochain_core_types::error::RibosomeEncodedValue::from(allocation_error).into(),
+                };
+                let init = $crate::global_fns::init_global_memory(allocation);
+                if init.is_err() {
+                    return $crate::holochain_wasm_utils::memory::ribosome::return_code_for_allocation_result(
+                        init
+                    ).into();
+                }
+
+                // Deserialize input
+                let input = load_json!(encoded_allocation_of_input);
+
+                fn execute(input: $crate::holochain_wasm_utils::api_serialization::migrate_chain::MigrateChainParams) -> Result<(), String> {
+                    let $migrate_old_dna = input.old_dna_address;
+                    let $migrate_new_dna = input.new_dna_address;
+                    $migrate_expr
+                }
+
+                match execute(input) {
+                    Ok(_) => hdk::holochain_core_types::error::RibosomeEncodedValue::Success.into(),
+                    Err(e) => $crate::holochain_wasm_utils::memory::ribosome::return_code_for_allocation_result(
+                        $crate::global_fns::write_json(
+                            $crate::holochain_wasm_utils::holochain_json_api::json::RawString::from(e)
+                        )
+                    ).into(),
+                }
+            }
+        )*
+
         use std::collections::HashMap;
 
         #[no_mangle]
@@ -0,0 +1,350 @@
+//! A mock of the host functions that the HDK calls, for unit testing zome code natively,
+//! without a conductor.
+//!
+//! When a zome crate is compiled for anything but WebAssembly (which is what `cargo test`
+//! does), the HDK functions don't call into the Ribosome but into this mock host. By default it
+//! behaves like a tiny in-memory instance: committed entries can be fetched by their address,
+//! links can be looked up and entries show up in queries. Responses of any function can be
+//! scripted with [respond](fn.respond.html) or computed by a closure set with
+//! [respond_with](fn.respond_with.html), and every call gets recorded so that tests can assert
+//! what the code under test asked the host for.
+//!
+//! The mock host is kept per thread, so tests that run in parallel don't see each other's
+//! entries and calls. Tests should start with a call to [reset](fn.reset.html) anyway, since
+//! test threads get reused.
+//!
+//! # Examples
+//! ```rust
+//! # extern crate hdk;
+//! # extern crate holochain_core_types;
+//! # extern crate holochain_json_api;
+//! # use hdk::{api::Dispatch, error::ZomeApiError, mock};
+//! # use holochain_core_types::entry::Entry;
+//! # use holochain_json_api::json::JsonString;
+//! # fn main() {
+//! mock::reset();
+//! let entry = Entry::App("post".into(), JsonString::from_json("{\"content\":\"hi\"}"));
+//! let address = hdk::commit_entry(&entry).unwrap();
+//! assert_eq!(hdk::get_entry(&address).unwrap(), Some(entry));
+//! assert_eq!(mock::calls(Dispatch::CommitEntry).len(), 1);
+//!
+//! let locked = ZomeApiError::Internal("keystore locked".into());
+//! mock::respond::<JsonString>(Dispatch::Crypto, Err(locked));
+//! assert!(hdk::sign("hello").is_err());
+//! # }
+//! ```
+use crate::{
+    api::Dispatch,
+    error::{ZomeApiError, ZomeApiResult},
+};
+use holochain_core_types::{
+    crud_status::CrudStatus,
+    entry::{Entry, EntryWithMeta},
+};
+use holochain_json_api::json::JsonString;
+use holochain_persistence_api::{
+    cas::content::{Address, AddressableContent},
+    hash::HashString,
+};
+use holochain_wasm_utils::api_serialization::{
+    commit_entry::{CommitEntryArgs, CommitEntryResult},
+    crypto::{CryptoArgs, CryptoMethod},
+    get_entry::{GetEntryArgs, GetEntryResult},
+    get_links::{GetLinksArgs, GetLinksResult, GetLinksResultCount, LinksResult},
+    link_entries::LinkEntriesArgs,
+    query::{QueryArgs, QueryArgsNames, QueryResult},
+    ZomeApiGlobals,
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+};
+
+type Handler = Box<dyn FnMut(JsonString) -> ZomeApiResult<JsonString>>;
+
+#[derive(Default)]
+struct MockHost {
+    responses: HashMap<Dispatch, VecDeque<ZomeApiResult<JsonString>>>,
+    handlers: HashMap<Dispatch, Handler>,
+    calls: Vec<(Dispatch, JsonString)>,
+    chain: Vec<Entry>,
+    links: Vec<LinkEntriesArgs>,
+}
+
+thread_local! {
+    static HOST: RefCell<MockHost> = RefCell::new(MockHost::default());
+}
+
+/// Forgets all scripted responses, handlers, recorded calls, committed entries and links.
+pub fn reset() {
+    HOST.with(|host| *host.borrow_mut() = MockHost::default());
+}
+
+/// Queues a response for the next call of `function`.
+/// Queued responses are returned in the order they were queued, before any handler or
+/// default behaviour of the function kicks in.
+pub fn respond<R: Into<JsonString>>(function: Dispatch, response: ZomeApiResult<R>) {
+    HOST.with(|host| {
+        host.borrow_mut()
+            .responses
+            .entry(function)
+            .or_default()
+            .push_back(response.map(Into::into))
+    });
+}
+
+/// Computes the responses to calls of `function` with the given closure, which gets the
+/// JSON of the arguments of each call.
+pub fn respond_with<F>(function: Dispatch, handler: F)
+where
+    F: FnMut(JsonString) -> ZomeApiResult<JsonString> + 'static,
+{
+    HOST.with(|host| {
+        host.borrow_mut()
+            .handlers
+            .insert(function, Box::new(handler))
+    });
+}
+
+/// The JSON of the arguments of every call of `function` since the last reset, oldest first.
+pub fn calls(function: Dispatch) -> Vec<JsonString> {
+    HOST.with(|host| {
+        host.borrow()
+            .calls
+            .iter()
+            .filter(|(called, _)| *called == function)
+            .map(|(_, input)| input.clone())
+            .collect()
+    })
+}
+
+/// Every call since the last reset, oldest first.
+pub fn all_calls() -> Vec<(Dispatch, JsonString)> {
+    HOST.with(|host| host.borrow().calls.clone())
+}
+
+/// The entries committed since the last reset, oldest first.
+pub fn committed() -> Vec<Entry> {
+    HOST.with(|host| host.borrow().chain.clone())
+}
+
+/// The values of `DNA_NAME`, `AGENT_ADDRESS` and the other globals under the mock host.
+/// They are the same in all tests, since the HDK only reads them once per process.
+pub fn globals() -> ZomeApiGlobals {
+    ZomeApiGlobals {
+        dna_name: "mock_dna".to_string(),
+        dna_address: Address::from("QmMockDnaAddress"),
+        agent_id_str: "mock_agent".to_string(),
+        agent_address: Address::from("HcMockAgentAddress"),
+        agent_initial_hash: HashString::from("QmMockAgentInitialHash"),
+        agent_latest_hash: HashString::from("QmMockAgentInitialHash"),
+        public_token: Address::from("QmMockPublicToken"),
+        cap_request: None,
+        properties: JsonString::empty_object(),
+    }
+}
+
+pub(crate) fn dispatch(function: Dispatch, input: JsonString) -> ZomeApiResult<JsonString> {
+    let scripted = HOST.with(|host| {
+        let mut host = host.borrow_mut();
+        host.calls.push((function, input.clone()));
+        host.responses
+            .get_mut(&function)
+            .and_then(VecDeque::pop_front)
+    });
+    if let Some(response) = scripted {
+        return response;
+    }
+    // Handlers are taken out while they run, so that they can use the mock host themselves.
+    if let Some(mut handler) = HOST.with(|host| host.borrow_mut().handlers.remove(&function)) {
+        let response = handler(input);
+        HOST.with(|host| {
+            host.borrow_mut()
+                .handlers
+                .entry(function)
+                .or_insert(handler);
+        });
+        return response;
+    }
+    HOST.with(|host| host.borrow_mut().default_response(function, input))
+}
+
+impl MockHost {
+    fn default_response(
+        &mut self,
+        function: Dispatch,
+        input: JsonString,
+    ) -> ZomeApiResult<JsonString> {
+        match function {
+            Dispatch::InitGlobals => Ok(globals().into()),
+            Dispatch::Debug => Ok(JsonString::null()),
+            Dispatch::EntryAddress => Ok(Entry::try_from(input)?.address().into()),
+            Dispatch::CommitEntry => {
+                let args = CommitEntryArgs::try_from(input)?;
+                let address = args.entry.address();
+                self.chain.push(args.entry);
+                Ok(CommitEntryResult::new(address).into())
+            }
+            Dispatch::GetEntry => {
+                let args = GetEntryArgs::try_from(input)?;
+                let entry_with_meta = self
+                    .chain
+                    .iter()
+                    .find(|entry| entry.address() == args.address)
+                    .map(|entry| EntryWithMeta {
+                        entry: entry.clone(),
+                        crud_status: CrudStatus::Live,
+                        maybe_link_update_delete: None,
+                    });
+                Ok(GetEntryResult::new(
+                    args.options.status_request,
+                    entry_with_meta.as_ref().map(|entry| (entry, Vec::new())),
+                )
+                .into())
+            }
+            Dispatch::LinkEntries => {
+                let args = LinkEntriesArgs::try_from(input)?;
+                self.links.push(args);
+                Ok(Address::from(format!("QmMockLink{}", self.links.len())).into())
+            }
+            Dispatch::GetLinks => {
+                let links = self.links_matching(GetLinksArgs::try_from(input)?);
+                Ok(GetLinksResult::new(links).into())
+            }
+            Dispatch::GetLinksCount => {
+                let count = self.links_matching(GetLinksArgs::try_from(input)?).len();
+                Ok(GetLinksResultCount { count }.into())
+            }
+            Dispatch::Query => {
+                let args = QueryArgs::try_from(input)?;
+                if args.options.headers {
+                    return Err(ZomeApiError::Internal(
+                        "the mock host has no headers to return for queries".to_string(),
+                    ));
+                }
+                let names = match args.entry_type_names {
+                    QueryArgsNames::QueryName(name) => vec![name],
+                    QueryArgsNames::QueryList(names) => names,
+                };
+                let limit = if args.options.limit == 0 {
+                    usize::max_value()
+                } else {
+                    args.options.limit
+                };
+                let entries = self
+                    .chain
+                    .iter()
+                    .rev()
+                    .filter(|entry| {
+                        let entry_type = entry.entry_type().to_string();
+                        names.is_empty() || names.iter().any(|n| n == "*" || *n == entry_type)
+                    })
+                    .skip(args.options.start)
+                    .take(limit);
+                Ok(if args.options.entries {
+                    QueryResult::Entries(entries.map(|e| (e.address(), e.clone())).collect())
+                } else {
+                    QueryResult::Addresses(entries.map(Entry::address).collect())
+                }
+                .into())
+            }
+            Dispatch::Crypto => {
+                let args = CryptoArgs::try_from(input)?;
+                match args.method {
+                    CryptoMethod::Sign => Ok(JsonString::from_json(&format!(
+                        "mock-signature-of-{}",
+                        args.payload
+                    ))),
+                    method => Err(ZomeApiError::Internal(format!(
+                        "the mock host does not implement crypto method {:?}, script a response for it",
+                        method
+                    ))),
+                }
+            }
+            _ => Err(ZomeApiError::Internal(format!(
+                "the mock host does not implement {:?}, script a response for it",
+                function
+            ))),
+        }
+    }
+
+    fn links_matching(&self, args: GetLinksArgs) -> Vec<LinksResult> {
+        self.links
+            .iter()
+            .filter(|link| link.base == args.entry_address)
+            .filter(|link| args.link_type.iter().all(|t| *t == link.link_type))
+            .filter(|link| args.tag.iter().all(|t| *t == link.tag))
+            .map(|link| LinksResult {
+                address: link.target.clone(),
+                headers: Vec::new(),
+                tag: link.tag.clone(),
+                status: CrudStatus::Live,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::link::LinkMatch;
+
+    fn post(content: &str) -> Entry {
+        Entry::App(
+            "post".into(),
+            JsonString::from_json(&format!("{{\"content\":\"{}\"}}", content)),
+        )
+    }
+
+    #[test]
+    fn committed_entries_can_be_fetched_linked_and_queried() {
+        reset();
+        let first = crate::commit_entry(&post("first")).unwrap();
+        let second = crate::commit_entry(&post("second")).unwrap();
+        assert_eq!(crate::get_entry(&first).unwrap(), Some(post("first")));
+        assert_eq!(crate::get_entry(&Address::from("QmNothing")).unwrap(), None);
+
+        crate::link_entries(&first, &second, "replies", "").unwrap();
+        let links =
+            crate::get_links(&first, LinkMatch::Exactly("replies"), LinkMatch::Any).unwrap();
+        assert_eq!(links.addresses(), vec![second.clone()]);
+        let links = crate::get_links(&second, LinkMatch::Any, LinkMatch::Any).unwrap();
+        assert!(links.addresses().is_empty());
+
+        assert_eq!(
+            crate::query("post".into(), 0, 0).unwrap(),
+            vec![second, first]
+        );
+        assert_eq!(committed(), vec![post("first"), post("second")]);
+    }
+
+    #[test]
+    fn scripted_responses_come_first_and_calls_get_recorded() {
+        reset();
+        respond(Dispatch::Crypto, Ok(JsonString::from_json("scripted")));
+        assert_eq!(crate::sign("a").unwrap(), "scripted");
+        assert_eq!(crate::sign("b").unwrap(), "mock-signature-of-b");
+
+        respond_with(Dispatch::GetEntry, |_| {
+            Err(ZomeApiError::Internal("timeout".to_string()))
+        });
+        assert!(crate::get_entry(&Address::from("QmAnything")).is_err());
+
+        let signed: Vec<CryptoArgs> = calls(Dispatch::Crypto)
+            .into_iter()
+            .map(|input| CryptoArgs::try_from(input).unwrap())
+            .collect();
+        assert_eq!(
+            signed
+                .iter()
+                .map(|args| args.payload.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        assert_eq!(all_calls().len(), 3);
+
+        reset();
+        assert!(all_calls().is_empty());
+    }
+}
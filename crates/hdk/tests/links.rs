@@ -203,6 +203,46 @@ pub fn hc_emit_signal(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
     RibosomeEncodedValue::Success.into()
 }
 
+#[no_mangle]
+pub fn hc_rotate_capability_grant(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_get_capability_audit_log(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_delegate_capability(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_revoke_capability_grant(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_update_agent(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_staging(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_get_chain_head(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_get_entry_holders(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
 #[test]
 pub fn test_invalid_target_link() {
     let (mut hc, _, _signal_receiver) =
@@ -214,8 +254,9 @@ pub fn test_invalid_target_link() {
     );
     let expected_result: ZomeApiResult<()> =
         serde_json::from_str::<ZomeApiResult<()>>(&result.clone().unwrap().to_string()).unwrap();
-    let zome_internal_error =
-        generate_zome_internal_error(String::from(r#"{"ValidationFailed":"invalid tag"}"#));
+    let zome_internal_error = generate_zome_internal_error(String::from(
+        r#"{"ValidationFailed":{"code":"invalid","message":"invalid tag","data":null}}"#,
+    ));
     assert_zome_internal_errors_equivalent(&expected_result.unwrap_err(), &zome_internal_error)
 }
 
@@ -324,7 +365,7 @@ fn can_validate_links() {
         let core_error: CoreError = serde_json::from_str(&error).unwrap();
         assert_eq!(
             core_error.kind,
-            HolochainError::ValidationFailed("Target stuff is not longer".to_string()),
+            HolochainError::ValidationFailed("Target stuff is not longer".into()),
         );
     } else {
         assert!(false);
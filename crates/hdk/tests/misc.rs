@@ -208,6 +208,46 @@ pub fn hc_emit_signal(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
     RibosomeEncodedValue::Success.into()
 }
 
+#[no_mangle]
+pub fn hc_rotate_capability_grant(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_get_capability_audit_log(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_delegate_capability(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_revoke_capability_grant(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_update_agent(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_staging(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_get_chain_head(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
+#[no_mangle]
+pub fn hc_get_entry_holders(_: RibosomeEncodingBits) -> RibosomeEncodingBits {
+    RibosomeEncodedValue::Success.into()
+}
+
 #[test]
 fn can_use_globals() {
     let (mut hc, _, _) = start_holochain_instance("can_use_globals", "alice");
@@ -267,7 +307,7 @@ fn can_invalidate_invalid_commit() {
         .to_str()
         .expect("path should have been created");
     let formatted_path_string = path_string.replace("\\", &vec!["\\", "\\", "\\", "\\"].join(""));
-    let error_string = format!("{{\"Err\":{{\"Internal\":\"{{\\\"kind\\\":{{\\\"ValidationFailed\\\":\\\"FAIL content is not allowed\\\"}},\\\"file\\\":\\\"{}\\\",\\\"line\\\":\\\"",formatted_path_string);
+    let error_string = format!("{{\"Err\":{{\"Internal\":\"{{\\\"kind\\\":{{\\\"ValidationFailed\\\":{{\\\"code\\\":\\\"invalid\\\",\\\"message\\\":\\\"FAIL content is not allowed\\\",\\\"data\\\":null}}}},\\\"file\\\":\\\"{}\\\",\\\"line\\\":\\\"",formatted_path_string);
     assert!(result.is_ok(), "result = {:?}", result);
     assert!(result.unwrap().to_string().contains(&error_string));
 }
@@ -525,6 +565,7 @@ fn test_signal() {
                     == &UserSignal {
                         name: String::from("test-signal"),
                         arguments: JsonString::from(r#"{"message":"test message"}"#),
+                        zome: String::from("test_zome"),
                     }
             }
             _ => false,
@@ -33,7 +33,10 @@ extern crate structopt;
 use shrust::{Shell, ShellIO};
 
 use holochain_conductor_lib::{
-    conductor::{mount_conductor_from_config, Conductor, ConductorDebug, CONDUCTOR},
+    conductor::{
+        mount_conductor_from_config, record_panic, spawn_instance_supervisor, Conductor,
+        ConductorDebug, CONDUCTOR, SUPERVISED_THREADS,
+    },
     config::{self, load_configuration, Configuration},
 };
 use holochain_core::{
@@ -63,9 +66,23 @@ lazy_static! {
         let orig_handler = std::panic::take_hook();
         std::panic::set_hook(Box::new(move |panic_info| {
             eprintln!("THREAD PANIC {:#?}", panic_info);
-            // invoke the default handler and exit the process
+            record_panic(panic_info);
+            // invoke the default handler and exit the process, unless the instance supervisor
+            // restarts what the thread was running
             orig_handler(panic_info);
-            std::process::exit(1);
+            let supervised = std::thread::current()
+                .name()
+                .map(|name| SUPERVISED_THREADS.iter().any(|prefix| name.starts_with(prefix)))
+                .unwrap_or(false);
+            if !supervised {
+                // The panicking thread might hold the conductor, in which case we go without
+                if let Some(conductor) = CONDUCTOR.try_lock() {
+                    if let Some(conductor) = conductor.as_ref() {
+                        let _ = conductor.write_crash_report(None, "conductor panicked");
+                    }
+                }
+                std::process::exit(1);
+            }
         }));
         true
     };
@@ -155,6 +172,7 @@ fn main() {
                                 key,
                                 DumpOptions {
                                     include_eavis: true,
+                                    include_storage_usage: true,
                                 },
                             )
                             .expect("should dump");
@@ -180,6 +198,7 @@ fn main() {
                 conductor
                     .start_all_static_servers()
                     .expect("Could not start UI servers!");
+                spawn_instance_supervisor();
 
                 if opt.repl {
                     let mut shell = Shell::new(conductor);
@@ -188,7 +207,7 @@ fn main() {
                             println!("-----------------------------------------------------\nChecking: {}\n-----------------------------------------------------\n", key);
                             let hc = conductor.instances().get(key).unwrap();
                             let context = hc.read().unwrap().context()?;
-                            let dump = conductor.state_dump_for_instance(key, DumpOptions {include_eavis: false}).expect("should dump");
+                            let dump = conductor.state_dump_for_instance(key, DumpOptions {include_eavis: false, include_storage_usage: false}).expect("should dump");
                             for (entry_hash, held_list_aspect_map) in dump.held_aspects {
                                 let aspects =  fetch_aspects_for_entry(&entry_hash,context.clone());
                                 let actually_held_aspect_map : HashSet<AspectHash> = aspects.clone().into_iter().map(|aspect| AspectHash::from(aspect.address())).collect();
@@ -215,7 +234,7 @@ fn main() {
                     shell.new_command_noargs("dump", "dump the current instances states", |io, conductor| {
                         for key in conductor.instances().keys() {
                             println!("-----------------------------------------------------\nSTATE DUMP FOR: {}\n-----------------------------------------------------\n", key);
-                            let dump = conductor.state_dump_for_instance(key, DumpOptions {include_eavis: true}).expect("should dump");
+                            let dump = conductor.state_dump_for_instance(key, DumpOptions {include_eavis: true, include_storage_usage: true}).expect("should dump");
                             let json_dump = serde_json::to_value(dump).expect("should convert");
                             let str_dump = serde_json::to_string_pretty(&json_dump).unwrap();
                             writeln!(io, "{}", str_dump)?;
@@ -0,0 +1,289 @@
+//! Counters, gauges and histograms that get scraped in Prometheus' text format.
+//!
+//! Recording is a no-op until [enable_prometheus] got called, so that instrumented code
+//! costs nothing in conductors that don't serve metrics. Durations get recorded in seconds
+//! into histograms with Prometheus' default buckets.
+use holochain_locksmith::Mutex;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+/// Upper bounds of the histogram buckets, in seconds
+pub const HISTOGRAM_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Content type of [render]'s output
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+}
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Starts recording metrics
+pub fn enable_prometheus() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn prometheus_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Histogram => "histogram",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Series {
+    Value(f64),
+    Histogram {
+        /// Observations per bucket, not cumulative
+        buckets: [u64; 11],
+        sum: f64,
+        count: u64,
+    },
+}
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Debug)]
+struct Family {
+    help: &'static str,
+    kind: Kind,
+    series: BTreeMap<Labels, Series>,
+}
+
+/// All metrics by name
+#[derive(Debug, Default)]
+pub struct Registry {
+    families: BTreeMap<&'static str, Family>,
+}
+
+impl Registry {
+    fn series(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        kind: Kind,
+        labels: &[(&str, &str)],
+    ) -> &mut Series {
+        let family = self.families.entry(name).or_insert_with(|| Family {
+            help,
+            kind,
+            series: BTreeMap::new(),
+        });
+        let mut labels: Labels = labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        labels.sort();
+        family.series.entry(labels).or_insert_with(|| match kind {
+            Kind::Histogram => Series::Histogram {
+                buckets: [0; 11],
+                sum: 0.0,
+                count: 0,
+            },
+            _ => Series::Value(0.0),
+        })
+    }
+
+    pub fn increment_counter(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+    ) {
+        if let Series::Value(value) = self.series(name, help, Kind::Counter, labels) {
+            *value += 1.0;
+        }
+    }
+
+    pub fn set_gauge(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        new_value: f64,
+    ) {
+        if let Series::Value(value) = self.series(name, help, Kind::Gauge, labels) {
+            *value = new_value;
+        }
+    }
+
+    pub fn observe(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        observed: f64,
+    ) {
+        if let Series::Histogram {
+            buckets,
+            sum,
+            count,
+        } = self.series(name, help, Kind::Histogram, labels)
+        {
+            if let Some(bucket) = HISTOGRAM_BUCKETS
+                .iter()
+                .position(|bound| observed <= *bound)
+            {
+                buckets[bucket] += 1;
+            }
+            *sum += observed;
+            *count += 1;
+        }
+    }
+
+    /// All metrics in Prometheus' text format
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for (name, family) in self.families.iter() {
+            let _ = writeln!(text, "# HELP {} {}", name, family.help);
+            let _ = writeln!(text, "# TYPE {} {}", name, family.kind.name());
+            for (labels, series) in family.series.iter() {
+                match series {
+                    Series::Value(value) => {
+                        let _ = writeln!(text, "{}{} {}", name, render_labels(labels, None), value);
+                    }
+                    Series::Histogram {
+                        buckets,
+                        sum,
+                        count,
+                    } => {
+                        let mut cumulative = 0;
+                        for (bound, observations) in HISTOGRAM_BUCKETS.iter().zip(buckets.iter()) {
+                            cumulative += observations;
+                            let le = bound.to_string();
+                            let _ = writeln!(
+                                text,
+                                "{}_bucket{} {}",
+                                name,
+                                render_labels(labels, Some(&le)),
+                                cumulative
+                            );
+                        }
+                        let _ = writeln!(
+                            text,
+                            "{}_bucket{} {}",
+                            name,
+                            render_labels(labels, Some("+Inf")),
+                            count
+                        );
+                        let labels = render_labels(labels, None);
+                        let _ = writeln!(text, "{}_sum{} {}", name, labels, sum);
+                        let _ = writeln!(text, "{}_count{} {}", name, labels, count);
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn render_labels(labels: &[(String, String)], le: Option<&str>) -> String {
+    let mut rendered: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect();
+    if let Some(le) = le {
+        rendered.push(format!("le=\"{}\"", le));
+    }
+    if rendered.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", rendered.join(","))
+    }
+}
+
+/// Adds one to a counter, if metrics are enabled
+pub fn increment_counter(name: &'static str, help: &'static str, labels: &[(&str, &str)]) {
+    if prometheus_enabled() {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .increment_counter(name, help, labels);
+    }
+}
+
+/// Sets a gauge, if metrics are enabled
+pub fn set_gauge(name: &'static str, help: &'static str, labels: &[(&str, &str)], value: f64) {
+    if prometheus_enabled() {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .set_gauge(name, help, labels, value);
+    }
+}
+
+/// Records an observation in a histogram, if metrics are enabled
+pub fn observe(name: &'static str, help: &'static str, labels: &[(&str, &str)], value: f64) {
+    if prometheus_enabled() {
+        REGISTRY.lock().unwrap().observe(name, help, labels, value);
+    }
+}
+
+/// A duration in seconds, the unit of all durations in Prometheus
+pub fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+/// All recorded metrics in Prometheus' text format
+pub fn render() -> String {
+    REGISTRY.lock().unwrap().render()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renders_counters_gauges_and_histograms() {
+        let mut registry = Registry::default();
+        let labels = [("instance", "app"), ("zome", "chat")];
+        registry.increment_counter("calls_total", "Calls", &labels);
+        registry.increment_counter("calls_total", "Calls", &labels);
+        registry.set_gauge("queue_depth", "Queue", &[("instance", "a\"b")], 3.0);
+        registry.observe("duration_seconds", "Durations", &labels, 0.02);
+        registry.observe("duration_seconds", "Durations", &labels, 0.2);
+        registry.observe("duration_seconds", "Durations", &labels, 20.0);
+
+        let text = registry.render();
+        assert!(text.contains("# TYPE calls_total counter\n"));
+        assert!(text.contains("calls_total{instance=\"app\",zome=\"chat\"} 2\n"));
+        assert!(text.contains("queue_depth{instance=\"a\\\"b\"} 3\n"));
+        assert!(text
+            .contains("duration_seconds_bucket{instance=\"app\",zome=\"chat\",le=\"0.01\"} 0\n"));
+        assert!(text
+            .contains("duration_seconds_bucket{instance=\"app\",zome=\"chat\",le=\"0.025\"} 1\n"));
+        assert!(
+            text.contains("duration_seconds_bucket{instance=\"app\",zome=\"chat\",le=\"10\"} 2\n")
+        );
+        assert!(text
+            .contains("duration_seconds_bucket{instance=\"app\",zome=\"chat\",le=\"+Inf\"} 3\n"));
+        assert!(text.contains("duration_seconds_count{instance=\"app\",zome=\"chat\"} 3\n"));
+    }
+}
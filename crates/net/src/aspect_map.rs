@@ -52,6 +52,11 @@ impl AspectMap {
             .insert(entry_aspect_address);
     }
 
+    /// Forgets all aspects held for the given entry.
+    pub fn remove_entry(&mut self, entry_address: &EntryHash) -> Option<AspectSet> {
+        self.0.remove(entry_address)
+    }
+
     pub fn entry_addresses(&self) -> impl Iterator<Item = &EntryHash> {
         self.0.keys()
     }
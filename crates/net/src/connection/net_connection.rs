@@ -32,6 +32,13 @@ impl NetHandler {
     }
 }
 
+/// A handler that drops all messages
+impl Default for NetHandler {
+    fn default() -> Self {
+        NetHandler::new(Box::new(|_| Ok(())))
+    }
+}
+
 impl PartialEq for NetHandler {
     fn eq(&self, _: &NetHandler) -> bool {
         false
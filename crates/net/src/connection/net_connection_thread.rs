@@ -20,11 +20,21 @@ const TICK_SLEEP_MIN_US: u64 = 100;
 const TICK_SLEEP_MAX_US: u64 = 10_000;
 const TICK_SLEEP_STARTUP_RETRY_MS: u64 = 3_000;
 
+/// Clears the shared running flag when the worker thread ends, also if it panics
+struct WorkerRunning(Arc<AtomicBool>);
+
+impl Drop for WorkerRunning {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
 /// Struct for holding a network connection running on a separate thread.
 /// It is itself a NetSend, and spawns a NetWorker.
 #[derive(Clone)]
 pub struct NetConnectionThread {
     can_keep_running: Arc<AtomicBool>,
+    is_running: Arc<AtomicBool>,
     send_channel: ht::channel::EncodedSpanSender<Lib3hClientProtocol>,
     thread: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
     pub endpoint: String,
@@ -47,6 +57,8 @@ impl NetConnectionThread {
         // Create shared bool between self and spawned thread
         let can_keep_running = Arc::new(AtomicBool::new(true));
         let can_keep_running_child = can_keep_running.clone();
+        let is_running = Arc::new(AtomicBool::new(true));
+        let is_running_child = is_running.clone();
         // Create channels between self and spawned thread
         let (send_channel, recv_channel) = crossbeam_channel::unbounded();
         let (send_endpoint, recv_endpoint) = crossbeam_channel::unbounded();
@@ -58,6 +70,7 @@ impl NetConnectionThread {
                 ProcessUniqueId::new().to_string()
             ))
             .spawn(move || {
+                let _running = WorkerRunning(is_running_child);
                 // Try to create a worker. Keep retrying if unsuccessful
                 let mut worker = loop {
                     match worker_factory(handler.clone()) {
@@ -143,6 +156,7 @@ impl NetConnectionThread {
         // Done
         Ok(NetConnectionThread {
             can_keep_running,
+            is_running,
             send_channel: send_channel.into(),
             thread: Arc::new(Mutex::new(Some(thread))),
             endpoint,
@@ -156,6 +170,11 @@ impl NetConnectionThread {
         self.can_keep_running.store(false, Ordering::Relaxed);
     }
 
+    /// False once the worker thread ended, because it got stopped or because it died
+    pub fn is_running(&self) -> bool {
+        self.is_running.load(Ordering::Relaxed)
+    }
+
     /// Wait for the worker thread to join (which it may not have done yet when running `stop`)
     #[allow(dead_code)]
     pub fn join_thread(&mut self) -> NetResult<()> {
@@ -224,7 +243,33 @@ mod tests {
 
         con.send(success_client_result("tick".to_string().into_bytes()))
             .unwrap();
+        assert!(con.is_running());
         con.stop();
+        con.join_thread().unwrap();
+        assert!(!con.is_running());
+    }
+
+    struct PanickingWorker;
+
+    impl NetWorker for PanickingWorker {
+        fn tick(&mut self) -> NetResult<bool> {
+            panic!("worker died");
+        }
+
+        fn p2p_endpoint(&self) -> Option<url::Url> {
+            Some(url::Url::parse("test://panicking-worker").unwrap())
+        }
+    }
+
+    #[test]
+    fn it_is_not_running_when_the_worker_dies() {
+        let mut con = NetConnectionThread::new(
+            NetHandler::new(Box::new(move |_r| Ok(()))),
+            Box::new(|_h| Ok(Box::new(PanickingWorker) as Box<dyn NetWorker>)),
+        )
+        .unwrap();
+        assert!(con.join_thread().is_err());
+        assert!(!con.is_running());
     }
 
     struct SimpleWorker {
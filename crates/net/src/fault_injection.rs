@@ -0,0 +1,497 @@
+//! Fault injection, to test how workflows cope with a bad network.
+//!
+//! A network whose P2pConfig has `fault_injection` set to a name sends its messages through
+//! the [FaultInjector] registered under that name, which all networks configured with the same
+//! name share. While running, tests can change the [FaultConfig] of the injector to delay,
+//! drop and reorder the messages that agents send to each other, and cut the connection
+//! between specific agents with [FaultInjector::partition].
+//!
+//! The random decisions come from a generator seeded with [FaultConfig::seed], so the same
+//! seed makes the same decisions for the same sequence of messages.
+use crate::{
+    connection::{
+        net_connection::{NetHandler, NetWorker, NetWorkerFactory},
+        NetResult,
+    },
+    p2p_network::Lib3hClientProtocolWrapped,
+};
+use holochain_locksmith::{Mutex, RwLock};
+use holochain_persistence_api::cas::content::Address;
+use lib3h_protocol::{protocol_client::Lib3hClientProtocol, protocol_server::Lib3hServerProtocol};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    static ref FAULT_INJECTORS: RwLock<HashMap<String, Arc<FaultInjector>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// The fault injector with the given name, which gets created if there is none yet
+pub fn fault_injector(name: &str) -> Arc<FaultInjector> {
+    FAULT_INJECTORS
+        .write()
+        .unwrap()
+        .entry(name.to_string())
+        .or_insert_with(|| Arc::new(FaultInjector::default()))
+        .clone()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FaultConfig {
+    /// Delay of every message sent to other agents
+    pub latency_ms: u64,
+    /// Up to this much random delay gets added to the latency
+    pub jitter_ms: u64,
+    /// Probability between 0 and 1 that a message gets dropped
+    pub drop_rate: f64,
+    /// Probability between 0 and 1 that a message gets held back, so that later ones overtake it
+    pub reorder_rate: f64,
+    /// How long held back messages wait on top of their latency
+    pub reorder_delay_ms: u64,
+    pub seed: u64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        FaultConfig {
+            latency_ms: 0,
+            jitter_ms: 0,
+            drop_rate: 0.0,
+            reorder_rate: 0.0,
+            reorder_delay_ms: 100,
+            seed: 0,
+        }
+    }
+}
+
+/// How many messages the injector interfered with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FaultStats {
+    pub delayed: u64,
+    pub dropped: u64,
+    pub reordered: u64,
+    /// Messages that did not get through because of a partition
+    pub partitioned: u64,
+}
+
+/// What happens to a message
+#[derive(Clone, Debug, PartialEq)]
+enum Fate {
+    Drop,
+    Delay(Duration),
+}
+
+/// xorshift64*, which is good enough to pick faults and keeps them reproducible
+struct FaultRng(u64);
+
+impl FaultRng {
+    fn new(seed: u64) -> Self {
+        FaultRng(seed.wrapping_add(0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number between 0 and 1
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+pub struct FaultInjector {
+    config: RwLock<FaultConfig>,
+    rng: Mutex<FaultRng>,
+    /// Pairs of agents that can't reach each other, the smaller address first
+    partitions: RwLock<HashSet<(String, String)>>,
+    stats: Mutex<FaultStats>,
+}
+
+impl Default for FaultInjector {
+    fn default() -> Self {
+        FaultInjector {
+            config: RwLock::new(FaultConfig::default()),
+            rng: Mutex::new(FaultRng::new(0)),
+            partitions: RwLock::new(HashSet::new()),
+            stats: Mutex::new(FaultStats::default()),
+        }
+    }
+}
+
+fn pair(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+impl FaultInjector {
+    pub fn config(&self) -> FaultConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Applies to messages sent from now on, and restarts the random decisions from the seed
+    pub fn set_config(&self, config: FaultConfig) {
+        *self.rng.lock().unwrap() = FaultRng::new(config.seed);
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Cuts the connection between the two agents in both directions
+    pub fn partition(&self, a: &Address, b: &Address) {
+        self.partitions
+            .write()
+            .unwrap()
+            .insert(pair(&a.to_string(), &b.to_string()));
+    }
+
+    /// Cuts the connections between each agent of one group and each agent of the other
+    pub fn partition_groups(&self, group_a: &[Address], group_b: &[Address]) {
+        for a in group_a {
+            for b in group_b {
+                self.partition(a, b);
+            }
+        }
+    }
+
+    pub fn heal(&self, a: &Address, b: &Address) {
+        self.partitions
+            .write()
+            .unwrap()
+            .remove(&pair(&a.to_string(), &b.to_string()));
+    }
+
+    pub fn heal_all(&self) {
+        self.partitions.write().unwrap().clear();
+    }
+
+    /// Removes all partitions and faults
+    pub fn reset(&self) {
+        self.heal_all();
+        self.set_config(FaultConfig::default());
+        *self.stats.lock().unwrap() = FaultStats::default();
+    }
+
+    pub fn is_partitioned(&self, a: &str, b: &str) -> bool {
+        self.partitions.read().unwrap().contains(&pair(a, b))
+    }
+
+    pub fn stats(&self) -> FaultStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    fn count_partitioned(&self) {
+        self.stats.lock().unwrap().partitioned += 1;
+    }
+
+    fn fate(&self) -> Fate {
+        let config = self.config();
+        let mut rng = self.rng.lock().unwrap();
+        let mut stats = self.stats.lock().unwrap();
+        if config.drop_rate > 0.0 && rng.next_f64() < config.drop_rate {
+            stats.dropped += 1;
+            return Fate::Drop;
+        }
+        let mut delay_ms = config.latency_ms;
+        if config.jitter_ms > 0 {
+            delay_ms += rng.next_u64() % (config.jitter_ms + 1);
+        }
+        if config.reorder_rate > 0.0 && rng.next_f64() < config.reorder_rate {
+            stats.reordered += 1;
+            delay_ms += config.reorder_delay_ms;
+        }
+        if delay_ms > 0 {
+            stats.delayed += 1;
+        }
+        Fate::Delay(Duration::from_millis(delay_ms))
+    }
+}
+
+/// Whether the message goes to other agents, and to which one if it has a single recipient
+fn peer_traffic(message: &Lib3hClientProtocol) -> Option<Option<String>> {
+    match message {
+        Lib3hClientProtocol::SendDirectMessage(data)
+        | Lib3hClientProtocol::HandleSendDirectMessageResult(data) => {
+            Some(Some(data.to_agent_id.to_string()))
+        }
+        Lib3hClientProtocol::PublishEntry(_)
+        | Lib3hClientProtocol::QueryEntry(_)
+        | Lib3hClientProtocol::HandleQueryEntryResult(_)
+        | Lib3hClientProtocol::HandleFetchEntryResult(_) => Some(None),
+        _ => None,
+    }
+}
+
+/// The agent that sent a message we received, if it came from another agent
+fn sender(message: &Lib3hServerProtocol) -> Option<String> {
+    match message {
+        Lib3hServerProtocol::HandleSendDirectMessage(data)
+        | Lib3hServerProtocol::SendDirectMessageResult(data) => {
+            Some(data.from_agent_id.to_string())
+        }
+        Lib3hServerProtocol::HandleStoreEntryAspect(data) => {
+            Some(data.provider_agent_id.to_string())
+        }
+        Lib3hServerProtocol::HandleQueryEntry(data) => Some(data.requester_agent_id.to_string()),
+        Lib3hServerProtocol::QueryEntryResult(data) => Some(data.responder_agent_id.to_string()),
+        _ => None,
+    }
+}
+
+/// Wraps the workers that the factory creates, so that their messages go through the injector
+pub fn with_faults(factory: NetWorkerFactory, injector: Arc<FaultInjector>) -> NetWorkerFactory {
+    Box::new(move |mut handler: NetHandler| {
+        let agent: Arc<RwLock<Option<String>>> = Arc::new(RwLock::new(None));
+        let receiving_agent = agent.clone();
+        let receiving_injector = injector.clone();
+        // Messages from agents that we are partitioned from don't arrive
+        let filtered_handler = NetHandler::new(Box::new(move |message| {
+            if let Ok(ref wrapped) = message {
+                let me = receiving_agent.read().unwrap().clone();
+                if let (Some(me), Some(peer)) = (me, sender(&wrapped.data)) {
+                    if receiving_injector.is_partitioned(&me, &peer) {
+                        receiving_injector.count_partitioned();
+                        return Ok(());
+                    }
+                }
+            }
+            handler.handle(message)
+        }));
+        Ok(Box::new(FaultyWorker {
+            inner: factory(filtered_handler)?,
+            injector: injector.clone(),
+            agent,
+            delayed: Vec::new(),
+        }) as Box<dyn NetWorker>)
+    })
+}
+
+/// Passes messages on to the actual worker, unless the injector decides otherwise
+struct FaultyWorker {
+    inner: Box<dyn NetWorker>,
+    injector: Arc<FaultInjector>,
+    /// Our agent, once it joined a space
+    agent: Arc<RwLock<Option<String>>>,
+    /// Messages held back, with the time they are due
+    delayed: Vec<(Instant, Lib3hClientProtocolWrapped)>,
+}
+
+impl NetWorker for FaultyWorker {
+    fn receive(&mut self, data: Lib3hClientProtocolWrapped) -> NetResult<()> {
+        if let Lib3hClientProtocol::JoinSpace(ref space) = data.data {
+            *self.agent.write().unwrap() = Some(space.agent_id.to_string());
+        }
+        let recipient = match peer_traffic(&data.data) {
+            Some(recipient) => recipient,
+            None => return self.inner.receive(data),
+        };
+        let me = self.agent.read().unwrap().clone();
+        if let (Some(me), Some(recipient)) = (me, recipient) {
+            if self.injector.is_partitioned(&me, &recipient) {
+                self.injector.count_partitioned();
+                return Ok(());
+            }
+        }
+        match self.injector.fate() {
+            Fate::Drop => Ok(()),
+            Fate::Delay(delay) if delay == Duration::from_millis(0) => self.inner.receive(data),
+            Fate::Delay(delay) => {
+                self.delayed.push((Instant::now() + delay, data));
+                Ok(())
+            }
+        }
+    }
+
+    fn tick(&mut self) -> NetResult<bool> {
+        let now = Instant::now();
+        // Stable, so messages that are due at the same time keep their order
+        self.delayed.sort_by_key(|(due, _)| *due);
+        let due = self
+            .delayed
+            .iter()
+            .take_while(|(due, _)| *due <= now)
+            .count();
+        let released: Vec<_> = self.delayed.drain(..due).collect();
+        for (_, data) in released {
+            self.inner.receive(data)?;
+        }
+        let did_something = self.inner.tick()?;
+        Ok(did_something || due > 0)
+    }
+
+    fn stop(self: Box<Self>) -> NetResult<()> {
+        self.inner.stop()
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        self.inner.endpoint()
+    }
+
+    fn p2p_endpoint(&self) -> Option<url::Url> {
+        self.inner.p2p_endpoint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib3h_protocol::data_types::{DirectMessageData, SpaceData};
+
+    /// Keeps the messages it receives
+    struct RecordingWorker {
+        received: Arc<Mutex<Vec<Lib3hClientProtocol>>>,
+    }
+
+    impl NetWorker for RecordingWorker {
+        fn receive(&mut self, data: Lib3hClientProtocolWrapped) -> NetResult<()> {
+            self.received.lock().unwrap().push(data.data);
+            Ok(())
+        }
+
+        fn p2p_endpoint(&self) -> Option<url::Url> {
+            None
+        }
+    }
+
+    fn faulty_worker(
+        injector: Arc<FaultInjector>,
+    ) -> (Box<dyn NetWorker>, Arc<Mutex<Vec<Lib3hClientProtocol>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recording = received.clone();
+        let factory: NetWorkerFactory = Box::new(move |_handler| {
+            Ok(Box::new(RecordingWorker {
+                received: recording.clone(),
+            }) as Box<dyn NetWorker>)
+        });
+        let worker = with_faults(factory, injector)(NetHandler::new(Box::new(|_| Ok(())))).unwrap();
+        (worker, received)
+    }
+
+    fn join(worker: &mut Box<dyn NetWorker>, agent: &str) {
+        worker
+            .receive(ht::test_wrap_enc(Lib3hClientProtocol::JoinSpace(
+                SpaceData {
+                    request_id: String::from("join"),
+                    space_address: Address::from("dna").into(),
+                    agent_id: Address::from(agent).into(),
+                },
+            )))
+            .unwrap();
+    }
+
+    fn message(to: &str, content: &str) -> Lib3hClientProtocolWrapped {
+        ht::test_wrap_enc(Lib3hClientProtocol::SendDirectMessage(DirectMessageData {
+            space_address: Address::from("dna").into(),
+            request_id: String::from(content),
+            to_agent_id: Address::from(to).into(),
+            from_agent_id: Address::from("alice").into(),
+            content: content.as_bytes().to_vec().into(),
+        }))
+    }
+
+    fn contents(received: &Arc<Mutex<Vec<Lib3hClientProtocol>>>) -> Vec<String> {
+        received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|message| match message {
+                Lib3hClientProtocol::SendDirectMessage(data) => Some(data.request_id.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn partitions_cut_agents_off_until_healed() {
+        let injector = Arc::new(FaultInjector::default());
+        let (mut worker, received) = faulty_worker(injector.clone());
+        join(&mut worker, "alice");
+
+        injector.partition(&Address::from("bob"), &Address::from("alice"));
+        assert!(injector.is_partitioned("alice", "bob"));
+        worker.receive(message("bob", "to bob")).unwrap();
+        worker.receive(message("carol", "to carol")).unwrap();
+        assert_eq!(contents(&received), vec!["to carol".to_string()]);
+        assert_eq!(injector.stats().partitioned, 1);
+
+        injector.heal(&Address::from("alice"), &Address::from("bob"));
+        worker.receive(message("bob", "to bob again")).unwrap();
+        assert_eq!(
+            contents(&received),
+            vec!["to carol".to_string(), "to bob again".to_string()]
+        );
+    }
+
+    #[test]
+    fn messages_get_dropped_and_delayed() {
+        let injector = Arc::new(FaultInjector::default());
+        let (mut worker, received) = faulty_worker(injector.clone());
+        join(&mut worker, "alice");
+
+        injector.set_config(FaultConfig {
+            drop_rate: 1.0,
+            ..FaultConfig::default()
+        });
+        worker.receive(message("bob", "dropped")).unwrap();
+        worker.tick().unwrap();
+        assert!(contents(&received).is_empty());
+        assert_eq!(injector.stats().dropped, 1);
+
+        injector.set_config(FaultConfig {
+            latency_ms: 50,
+            ..FaultConfig::default()
+        });
+        worker.receive(message("bob", "delayed")).unwrap();
+        worker.tick().unwrap();
+        assert!(contents(&received).is_empty());
+        std::thread::sleep(Duration::from_millis(60));
+        worker.tick().unwrap();
+        assert_eq!(contents(&received), vec!["delayed".to_string()]);
+    }
+
+    #[test]
+    fn held_back_messages_get_overtaken() {
+        let injector = Arc::new(FaultInjector::default());
+        let (mut worker, received) = faulty_worker(injector.clone());
+        join(&mut worker, "alice");
+
+        injector.set_config(FaultConfig {
+            reorder_rate: 1.0,
+            reorder_delay_ms: 50,
+            ..FaultConfig::default()
+        });
+        worker.receive(message("bob", "first")).unwrap();
+        injector.set_config(FaultConfig::default());
+        worker.receive(message("bob", "second")).unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        worker.tick().unwrap();
+        assert_eq!(
+            contents(&received),
+            vec!["second".to_string(), "first".to_string()]
+        );
+    }
+
+    #[test]
+    fn same_seed_makes_same_decisions() {
+        let config = FaultConfig {
+            drop_rate: 0.5,
+            jitter_ms: 100,
+            seed: 42,
+            ..FaultConfig::default()
+        };
+        let fates = || {
+            let injector = FaultInjector::default();
+            injector.set_config(config.clone());
+            (0..20).map(|_| injector.fate()).collect::<Vec<_>>()
+        };
+        assert_eq!(fates(), fates());
+    }
+}
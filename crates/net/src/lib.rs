@@ -27,6 +27,7 @@ extern crate holochain_tracing_macros;
 pub mod aspect_map;
 pub mod connection;
 pub mod error;
+pub mod fault_injection;
 pub mod in_memory;
 pub mod lib3h_worker;
 pub mod p2p_config;
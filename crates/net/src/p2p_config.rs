@@ -69,6 +69,10 @@ pub struct P2pConfig {
     pub backend_kind: P2pBackendKind,
     pub backend_config: BackendConfig,
     pub maybe_end_user_config: Option<serde_json::Value>,
+    /// Name of the fault injector all messages of the network go through, see
+    /// fault_injection. Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fault_injection: Option<String>,
 }
 
 // Conversions
@@ -98,9 +102,16 @@ impl P2pConfig {
             backend_kind,
             backend_config,
             maybe_end_user_config,
+            fault_injection: None,
         }
     }
 
+    /// Sends the messages of the network through the fault injector with the given name
+    pub fn with_fault_injection(mut self, name: &str) -> Self {
+        self.fault_injection = Some(name.to_string());
+        self
+    }
+
     pub fn from_file(filepath: &str) -> Self {
         let config_file =
             File::open(filepath).expect("Failed to open filepath on P2pConfig creation.");
@@ -8,6 +8,7 @@ use crate::{
         net_connection_thread::NetConnectionThread,
         NetResult,
     },
+    fault_injection::{fault_injector, with_faults},
     in_memory::memory_worker::InMemoryWorker,
     log_d, log_e,
     p2p_config::*,
@@ -110,6 +111,10 @@ impl P2pNetwork {
                 )?) as Box<dyn NetWorker>)
             }),
         };
+        let worker_factory = match p2p_config.fault_injection {
+            Some(ref name) => with_faults(worker_factory, fault_injector(name)),
+            None => worker_factory,
+        };
 
         let (t, rx) = crossbeam_channel::unbounded();
         let tx = t.clone();
@@ -183,6 +188,11 @@ impl P2pNetwork {
         self.connection.stop();
     }
 
+    /// False once the thread of the network connection ended
+    pub fn is_running(&self) -> bool {
+        self.connection.is_running()
+    }
+
     /// Getter of the endpoint of its connection
     pub fn endpoint(&self) -> String {
         self.connection.endpoint.clone()
@@ -353,59 +353,70 @@ impl Sim2hHandle {
         };
 
         // you have to be in a space to proceed further
-        tokio::task::spawn(async move {
-            // -- right now each agent can only be part of a single space :/ --
+        // (the task stays in the span that follows the message, so whatever gets sent on
+        // continues the sender's trace)
+        tokio::task::spawn(
+            async move {
+                // -- right now each agent can only be part of a single space :/ --
+
+                let (agent_id, space_hash) = {
+                    let state = sim2h_handle.state().get_clone().await;
+                    if let Some(info) = state.get_space_info_from_uri(&uri) {
+                        info
+                    } else {
+                        error!(
+                            "uri has not joined space, cannot proceed {} {}",
+                            uri,
+                            message.message_type()
+                        );
+                        sim2h_handle.disconnect(vec![uri.clone()]);
+                        return;
+                    }
+                };
 
-            let (agent_id, space_hash) = {
-                let state = sim2h_handle.state().get_clone().await;
-                if let Some(info) = state.get_space_info_from_uri(&uri) {
-                    info
-                } else {
+                if *agent_id != signer {
                     error!(
-                        "uri has not joined space, cannot proceed {} {}",
-                        uri,
-                        message.message_type()
+                        "signer {} does not match joined agent {:?}",
+                        signer, agent_id
                     );
-                    sim2h_handle.disconnect(vec![uri.clone()]);
                     return;
                 }
-            };
-
-            if *agent_id != signer {
-                error!(
-                    "signer {} does not match joined agent {:?}",
-                    signer, agent_id
-                );
-                return;
-            }
 
-            sim2h_handle.send_receipt(&receipt, &signer, &uri);
+                sim2h_handle.send_receipt(&receipt, &signer, &uri);
 
-            match message {
-                WireMessage::ClientToLib3h(ht::EncodedSpanWrap { data, .. }) => {
-                    return client_to_lib3h(data, uri, sim2h_handle, signer, space_hash);
-                }
-                WireMessage::Lib3hToClientResponse(ht::EncodedSpanWrap { data, .. }) => {
-                    return lib3h_to_client_response(data, uri, sim2h_handle, signer, space_hash);
-                }
-                WireMessage::MultiSendResponse(messages) => {
-                    for ht::EncodedSpanWrap { data, .. } in messages {
-                        lib3h_to_client_response(
+                match message {
+                    WireMessage::ClientToLib3h(ht::EncodedSpanWrap { data, .. }) => {
+                        return client_to_lib3h(data, uri, sim2h_handle, signer, space_hash);
+                    }
+                    WireMessage::Lib3hToClientResponse(ht::EncodedSpanWrap { data, .. }) => {
+                        return lib3h_to_client_response(
                             data,
-                            uri.clone(),
-                            sim2h_handle.clone(),
-                            signer.clone(),
-                            space_hash.clone(),
+                            uri,
+                            sim2h_handle,
+                            signer,
+                            space_hash,
                         );
                     }
-                    return;
-                }
-                message => {
-                    error!("unhandled message type {:?}", message);
-                    return;
+                    WireMessage::MultiSendResponse(messages) => {
+                        for ht::EncodedSpanWrap { data, .. } in messages {
+                            lib3h_to_client_response(
+                                data,
+                                uri.clone(),
+                                sim2h_handle.clone(),
+                                signer.clone(),
+                                space_hash.clone(),
+                            );
+                        }
+                        return;
+                    }
+                    message => {
+                        error!("unhandled message type {:?}", message);
+                        return;
+                    }
                 }
             }
-        });
+            .instrument(Span::current()),
+        );
     }
 
     /// disconnect an active connection
@@ -758,51 +769,55 @@ fn spawn_handle_message_publish_entry(
         return;
     }
 
-    tokio::task::spawn(async move {
-        /*        let aspect_list: im::HashSet<AspectHash> = data
-        .entry
-        .aspect_list
-        .iter()
-        .map(|a| a.aspect_address.clone())
-        .collect();*/
-        let mut multi_message = Vec::new();
-        for aspect in data.entry.aspect_list {
-            let data = Lib3hToClient::HandleStoreEntryAspect(StoreEntryAspectData {
-                request_id: "".into(),
-                space_address: (&*space_hash).clone(),
-                provider_agent_id: signer.clone(),
-                entry_address: data.entry.entry_address.clone(),
-                entry_aspect: aspect,
-            });
-            multi_message.push(ht::span_wrap_encode!(Level::INFO, data).into());
-        }
+    tokio::task::spawn(
+        async move {
+            /*        let aspect_list: im::HashSet<AspectHash> = data
+            .entry
+            .aspect_list
+            .iter()
+            .map(|a| a.aspect_address.clone())
+            .collect();*/
+            let mut multi_message = Vec::new();
+            for aspect in data.entry.aspect_list {
+                let data = Lib3hToClient::HandleStoreEntryAspect(StoreEntryAspectData {
+                    request_id: "".into(),
+                    space_address: (&*space_hash).clone(),
+                    provider_agent_id: signer.clone(),
+                    entry_address: data.entry.entry_address.clone(),
+                    entry_aspect: aspect,
+                });
+                multi_message.push(ht::span_wrap_encode!(Level::INFO, data).into());
+            }
 
-        if multi_message.is_empty() {
-            return;
-        }
+            if multi_message.is_empty() {
+                return;
+            }
 
-        let multi_message = WireMessage::MultiSend(multi_message);
+            let multi_message = WireMessage::MultiSend(multi_message);
 
-        let state = sim2h_handle.state().get_clone().await;
-        let send_to =
-            match state.get_agents_that_should_hold_entry(&space_hash, &data.entry.entry_address) {
+            let state = sim2h_handle.state().get_clone().await;
+            let send_to = match state
+                .get_agents_that_should_hold_entry(&space_hash, &data.entry.entry_address)
+            {
                 None => return,
                 Some(send_to) => send_to,
             };
 
-        for agent_id in send_to {
-            if let Some(uri) = state.lookup_joined(&space_hash, &agent_id) {
-                sim2h_handle.send((&*agent_id).clone(), uri.clone(), &multi_message);
+            for agent_id in send_to {
+                if let Some(uri) = state.lookup_joined(&space_hash, &agent_id) {
+                    sim2h_handle.send((&*agent_id).clone(), uri.clone(), &multi_message);
+                }
+                /* send not guaranteed to work so we can't mark as held.
+                sim2h_handle.state().spawn_agent_holds_aspects(
+                    (&*space_hash).clone(),
+                    (&*agent_id).clone(),
+                    data.entry.entry_address.clone(),
+                    aspect_list.clone(),
+                );*/
             }
-            /* send not guaranteed to work so we can't mark as held.
-            sim2h_handle.state().spawn_agent_holds_aspects(
-                (&*space_hash).clone(),
-                (&*agent_id).clone(),
-                data.entry.entry_address.clone(),
-                aspect_list.clone(),
-            );*/
         }
-    });
+        .instrument(debug_span!("spawn_handle_message_publish_entry")),
+    );
 }
 
 fn spawn_handle_message_list_data(
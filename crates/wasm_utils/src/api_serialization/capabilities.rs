@@ -1,7 +1,13 @@
 use holochain_json_api::{error::JsonError, json::*};
 use holochain_persistence_api::cas::content::Address;
 
-use holochain_core_types::entry::cap_entries::{CapFunctions, CapabilityType};
+use holochain_core_types::{
+    dna::capabilities::CapabilityUseRecord,
+    entry::cap_entries::{
+        CapArgConstraints, CapFunctions, CapTokenDelegation, CapTokenValue, CapabilityType,
+    },
+    time::Iso8601,
+};
 
 // arguments required for calling commit_capability_grant
 #[derive(Deserialize, Default, Debug, Serialize, DefaultJson)]
@@ -10,6 +16,12 @@ pub struct CommitCapabilityGrantArgs {
     pub cap_type: CapabilityType,
     pub assignees: Option<Vec<Address>>,
     pub functions: CapFunctions,
+    #[serde(default)]
+    pub expires_at: Option<Iso8601>,
+    #[serde(default)]
+    pub arg_constraints: CapArgConstraints,
+    #[serde(default)]
+    pub delegatable: bool,
 }
 
 // arguments required for calling commit_capability_claim
@@ -19,3 +31,55 @@ pub struct CommitCapabilityClaimArgs {
     pub grantor: Address,
     pub token: Address,
 }
+
+// arguments required for calling revoke_capability_grant
+#[derive(Deserialize, Default, Debug, Serialize, DefaultJson)]
+pub struct RevokeCapabilityGrantArgs {
+    pub token: Address,
+}
+
+// arguments required for calling rotate_capability_grant
+#[derive(Deserialize, Default, Debug, Serialize, DefaultJson)]
+pub struct RotateCapabilityGrantArgs {
+    pub token: Address,
+    /// seconds the old token keeps working after the rotation
+    #[serde(default)]
+    pub grace_period_secs: u64,
+    /// whether to send the new token to the grant's assignees
+    #[serde(default)]
+    pub notify_assignees: bool,
+}
+
+// arguments required for calling delegate_capability
+#[derive(Deserialize, Debug, Serialize, DefaultJson)]
+pub struct DelegateCapabilityArgs {
+    pub parent_token: CapTokenValue,
+    #[serde(default)]
+    pub parent_delegation: Vec<CapTokenDelegation>,
+    /// the agent that may use the sub-token
+    pub delegatee: Address,
+    pub functions: CapFunctions,
+    #[serde(default)]
+    pub arg_constraints: CapArgConstraints,
+    pub expires_at: Iso8601,
+}
+
+// result of calling delegate_capability
+#[derive(Deserialize, Default, Debug, Serialize, DefaultJson)]
+pub struct DelegateCapabilityResult {
+    pub delegation: Vec<CapTokenDelegation>,
+}
+
+// arguments required for calling get_capability_audit_log
+#[derive(Deserialize, Default, Debug, Serialize, DefaultJson)]
+pub struct GetCapabilityAuditLogArgs {
+    /// only return records of calls made with this token
+    #[serde(default)]
+    pub token: Option<Address>,
+}
+
+// result of calling get_capability_audit_log
+#[derive(Deserialize, Default, Debug, Serialize, DefaultJson, Clone, PartialEq)]
+pub struct CapabilityAuditLogResult {
+    pub records: Vec<CapabilityUseRecord>,
+}
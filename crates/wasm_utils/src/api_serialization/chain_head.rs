@@ -0,0 +1,22 @@
+use holochain_core_types::time::Iso8601;
+use holochain_json_api::{error::JsonError, json::*};
+use holochain_persistence_api::cas::content::Address;
+
+/// Snapshot of the top of an agent's source chain.
+/// All fields are empty/zero for a chain that has no headers yet.
+#[derive(Deserialize, Debug, Serialize, DefaultJson, Clone, PartialEq)]
+pub struct ChainHeadResult {
+    /// Address of the top chain header
+    pub address: Option<Address>,
+    /// Number of headers in the chain, i.e. the sequence number of the top header plus one
+    pub length: u64,
+    /// Timestamp of the most recent commit
+    pub timestamp: Option<Iso8601>,
+}
+
+impl ChainHeadResult {
+    /// Sequence number of the top chain header, starting at 0 for the first header
+    pub fn sequence(&self) -> Option<u64> {
+        self.length.checked_sub(1)
+    }
+}
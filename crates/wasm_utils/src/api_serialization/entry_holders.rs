@@ -0,0 +1,20 @@
+use holochain_json_api::{error::JsonError, json::*};
+use holochain_persistence_api::cas::content::Address;
+
+/// Peers that have sent us a holding receipt for one of our authored entries.
+#[derive(Deserialize, Debug, Serialize, DefaultJson, Clone, PartialEq)]
+pub struct EntryHoldersResult {
+    pub address: Address,
+    pub holders: Vec<Address>,
+}
+
+impl EntryHoldersResult {
+    pub fn new(address: Address, holders: Vec<Address>) -> Self {
+        EntryHoldersResult { address, holders }
+    }
+
+    /// Number of distinct peers known to hold the entry.
+    pub fn count(&self) -> usize {
+        self.holders.len()
+    }
+}
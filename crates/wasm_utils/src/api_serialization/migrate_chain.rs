@@ -0,0 +1,10 @@
+use holochain_json_api::{error::JsonError, json::*};
+use holochain_persistence_api::cas::content::Address;
+
+/// Input of the `migrate_chain` callback, called when an instance is loaded with a new
+/// version of its DNA. The old chain contents are still available through `query`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, DefaultJson)]
+pub struct MigrateChainParams {
+    pub old_dna_address: Address,
+    pub new_dna_address: Address,
+}
@@ -10,18 +10,22 @@ mod call;
 /// For the case of HDK-rust we can use the exact same types by
 /// importing this module.
 pub mod capabilities;
+pub mod chain_head;
 pub mod commit_entry;
 pub mod crypto;
 pub mod emit_signal;
+pub mod entry_holders;
 pub mod get_entry;
 pub mod get_links;
 pub mod keystore;
 pub mod link_entries;
 pub mod meta;
+pub mod migrate_chain;
 pub mod query;
 pub mod receive;
 pub mod send;
 pub mod sign;
+pub mod staging;
 mod update_entry;
 pub mod validation;
 pub mod verify_signature;
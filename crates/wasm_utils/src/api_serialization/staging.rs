@@ -0,0 +1,26 @@
+use crate::api_serialization::commit_entry::CommitEntryArgs;
+use holochain_json_api::{error::JsonError, json::*};
+use holochain_persistence_api::cas::content::Address;
+
+/// Operations on the staging area for uncommitted entries.
+#[derive(Deserialize, Debug, Serialize, DefaultJson)]
+pub enum StagingArgs {
+    /// Validate an entry and add it to the staging area
+    Stage(CommitEntryArgs),
+    /// Commit all staged entries to the source chain
+    Commit,
+    /// Drop all staged entries
+    Discard,
+}
+
+/// Addresses of the entries that got staged, committed or discarded.
+#[derive(Deserialize, Debug, Serialize, DefaultJson, Clone, PartialEq)]
+pub struct StagingResult {
+    pub addresses: Vec<Address>,
+}
+
+impl StagingResult {
+    pub fn new(addresses: Vec<Address>) -> Self {
+        StagingResult { addresses }
+    }
+}
@@ -8,3 +8,11 @@ pub struct UpdateEntryArgs {
     pub new_entry: Entry,
     pub address: Address,
 }
+
+/// Struct for input data received when Zome API function update_agent() is invoked.
+/// Fields left as `None` keep their current value on the agent's identity entry.
+#[derive(Deserialize, Clone, PartialEq, Debug, Serialize, DefaultJson, Default)]
+pub struct UpdateAgentArgs {
+    pub nick: Option<String>,
+    pub metadata: Option<String>,
+}
@@ -16,10 +16,14 @@ use holochain_core::{
     action::Action,
     context::Context,
     logger::{test_logger, TestLogger},
-    nucleus::actions::call_zome_function::make_cap_request_for_call,
+    nucleus::{
+        actions::call_zome_function::make_cap_request_for_call, validation::ADMIN_GRANTOR_PROPERTY,
+    },
     signal::{signal_channel, Signal, SignalReceiver},
+    workflows::author_entry::author_entry,
 };
 use holochain_core_types::{
+    agent::AgentId,
     crud_status::CrudStatus,
     dna::{
         entry_types::{EntryTypeDef, LinkedFrom, LinksTo, Sharing},
@@ -30,9 +34,11 @@ use holochain_core_types::{
         Dna,
     },
     entry::{
+        cap_entries::{CapFunctions, CapTokenGrant, CapabilityType, ReservedCapabilityId},
         entry_type::{test_app_entry_type, AppEntryType, EntryType},
         Entry, EntryWithMeta,
     },
+    signature::{Provenance, Signature},
 };
 use holochain_json_api::{error::JsonError, json::JsonString};
 use holochain_locksmith::Mutex;
@@ -343,6 +349,42 @@ pub fn create_test_context(agent_name: &str) -> Arc<Context> {
     )
 }
 
+/// the agent that DNAs prepared with `with_admin_grantor` let grant admin capabilities
+pub fn test_admin_grantor() -> AgentId {
+    mock_signing::registered_test_agent("admin grantor")
+}
+
+/// name `test_admin_grantor` as the admin grantor in the properties of the DNA
+pub fn with_admin_grantor(mut dna: Dna) -> Dna {
+    dna.properties[ADMIN_GRANTOR_PROPERTY] = json!(test_admin_grantor().address());
+    dna
+}
+
+/// commit the admin grant that allows the agent of the context to migrate its chain to a new DNA,
+/// signed by `test_admin_grantor`
+pub fn commit_admin_grant(context: &Arc<Context>) {
+    let grant = Entry::CapTokenGrant(
+        CapTokenGrant::create(
+            ReservedCapabilityId::Admin.as_str(),
+            CapabilityType::Assigned,
+            Some(vec![context.agent_id.address()]),
+            CapFunctions::new(),
+        )
+        .expect("admin grant should be valid"),
+    );
+    let grantor = test_admin_grantor();
+    let provenance = Provenance::new(
+        grantor.address(),
+        Signature::from(mock_signing::mock_signer(
+            grant.address().to_string(),
+            &grantor,
+        )),
+    );
+    context
+        .block_on(author_entry(&grant, None, context, &vec![provenance]))
+        .expect("admin grant should be committed");
+}
+
 // @TODO this is a first attempt at replacing history.len() tests
 // @see https://github.com/holochain/holochain-rust/issues/195
 pub fn expect_action<F>(rx: &Receiver<Signal>, f: F) -> Result<Action, String>